@@ -0,0 +1,272 @@
+//! A microbenchmark harness for the calculation engine, run with the hidden `bcalc bench`
+//! subcommand. Each expression in a fixed suite is repeatedly tokenized, parsed, evaluated, and
+//! formatted, and the average time spent in each stage is printed as a table. The results can be
+//! saved and given to a later run as a baseline, which makes the table also show how the time
+//! taken changed. This is meant for catching performance regressions in the parser and operations.
+
+use crate::{
+    error::CalculatorFailure,
+    operations::format_decimal_string,
+    token::{ParsedInput, Tokenizer},
+    Args, BenchArgs, SyntaxTree, VariableStore,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+// Expressions are evaluated in this order using the same `VariableStore`, so later expressions can
+// use variables assigned by earlier ones.
+const SUITE: &[&str] = &[
+    "1 + 2 * 3 - 4 / 5",
+    "((((1 + 2) * 3 - 4) / 5) ^ 2 + 6) % 7",
+    "123456789012345678901234567890.0987654321 * 98765432109876543210 / 3",
+    "1/3 + 1/7 + 1/11 + 1/13 + 1/17 + 1/19 + 1/23 + 1/29 + 1/31 + 1/37",
+    "max(1, 2, 3, min(4, 5, 6, abs(-7)), sqrt(81))",
+    "$bench = 3 / 7",
+    "$bench * $bench + 1 / $bench - $bench ^ 3",
+    "2 ^ 1000",
+    "2 ^ (1 / 2)",
+    "(10 ^ 300 + 7) ^ (1 / 3)",
+];
+
+// Each expression is run repeatedly until at least this much time has passed, so that the averages
+// are meaningful even for very fast expressions.
+const MIN_SAMPLE_TIME: Duration = Duration::from_millis(200);
+// But very fast expressions don't need to be run more times than this.
+const MAX_ITERATIONS: u32 = 100_000;
+
+const EXPRESSION_HEADER: &str = "Expression";
+const COLUMN_HEADERS: &[&str] = &["Tokenize", "Parse", "Evaluate", "Format", "Total"];
+const BASELINE_COLUMN_HEADERS: &[&str] = &["Baseline", "Change"];
+const COLUMN_WIDTH: usize = 10;
+
+/// The average time, in nanoseconds, that each stage took for one expression.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Timing {
+    pub expression: String,
+    pub tokenize_ns: f64,
+    pub parse_ns: f64,
+    pub evaluate_ns: f64,
+    pub format_ns: f64,
+}
+
+impl Timing {
+    pub fn total_ns(&self) -> f64 {
+        self.tokenize_ns + self.parse_ns + self.evaluate_ns + self.format_ns
+    }
+}
+
+/// Times the suite, saving the results and comparing them to a baseline as `bench_args` specifies,
+/// and returns the table to display. The default settings are always used so that the results of
+/// different runs can be compared.
+pub fn run(bench_args: &BenchArgs) -> Result<String, Box<dyn std::error::Error>> {
+    let maybe_baseline: Option<HashMap<String, Timing>> = match &bench_args.baseline {
+        Some(path) => {
+            let timings: Vec<Timing> = serde_json::from_str(&fs::read_to_string(path)?)?;
+            Some(
+                timings
+                    .into_iter()
+                    .map(|timing| (timing.expression.clone(), timing))
+                    .collect(),
+            )
+        }
+        None => None,
+    };
+
+    let timings = time_suite(&Args::default(), MIN_SAMPLE_TIME)?;
+
+    if let Some(path) = &bench_args.save {
+        fs::write(path, serde_json::to_string_pretty(&timings)?)?;
+    }
+
+    Ok(make_table(&timings, maybe_baseline.as_ref()))
+}
+
+fn time_suite(
+    args: &Args,
+    min_sample_time: Duration,
+) -> Result<Vec<Timing>, Box<dyn std::error::Error>> {
+    let tokenizer = Tokenizer::new();
+    let mut vars = VariableStore::new();
+    let mut timings = Vec::new();
+    for expression in SUITE {
+        let timing = time_expression(expression, &tokenizer, &mut vars, args, min_sample_time)
+            .map_err(|e| -> Box<dyn std::error::Error> {
+                match e {
+                    CalculatorFailure::InputError(e) => {
+                        format!("Failed to evaluate '{}': {}", expression, e.value).into()
+                    }
+                    CalculatorFailure::RuntimeError(e) => e,
+                }
+            })?;
+        timings.push(timing);
+    }
+    Ok(timings)
+}
+
+fn time_expression(
+    expression: &str,
+    tokenizer: &Tokenizer,
+    vars: &mut VariableStore,
+    args: &Args,
+    min_sample_time: Duration,
+) -> Result<Timing, CalculatorFailure> {
+    let mut tokenize_time = Duration::ZERO;
+    let mut parse_time = Duration::ZERO;
+    let mut evaluate_time = Duration::ZERO;
+    let mut format_time = Duration::ZERO;
+    let mut iterations: u32 = 0;
+
+    let sample_start = Instant::now();
+    while iterations == 0
+        || (sample_start.elapsed() < min_sample_time && iterations < MAX_ITERATIONS)
+    {
+        let start = Instant::now();
+        let tokens = match tokenizer.tokenize(expression, args.radix)? {
+            ParsedInput::Tokens(t) => t,
+            ParsedInput::Command(_) => unreachable!("The suite doesn't contain commands"),
+        };
+        let tokenized = Instant::now();
+        let st = SyntaxTree::new(tokens.into())?;
+        let parsed = Instant::now();
+        let value = st.execute(None, Some(vars), None, args)?;
+        let evaluated = Instant::now();
+        // This skips the cache that `format_value` uses, since otherwise only the first iteration
+        // would actually be timing the conversion. None of the suite's results are complex, so only
+        // the real part needs formatting.
+        black_box(format_decimal_string(
+            &value.re(),
+            args.radix,
+            args.precision,
+            args.commas.then_some(args.group_size),
+            args.upper,
+        ));
+        let formatted = Instant::now();
+
+        tokenize_time += tokenized - start;
+        parse_time += parsed - tokenized;
+        evaluate_time += evaluated - parsed;
+        format_time += formatted - evaluated;
+        iterations += 1;
+    }
+
+    let average_ns = |time: Duration| time.as_nanos() as f64 / f64::from(iterations);
+    Ok(Timing {
+        expression: expression.to_string(),
+        tokenize_ns: average_ns(tokenize_time),
+        parse_ns: average_ns(parse_time),
+        evaluate_ns: average_ns(evaluate_time),
+        format_ns: average_ns(format_time),
+    })
+}
+
+fn make_table(timings: &[Timing], maybe_baseline: Option<&HashMap<String, Timing>>) -> String {
+    let expression_width = timings
+        .iter()
+        .map(|timing| timing.expression.len())
+        .chain([EXPRESSION_HEADER.len()])
+        .max()
+        .unwrap();
+
+    let mut headers: Vec<&str> = COLUMN_HEADERS.to_vec();
+    if maybe_baseline.is_some() {
+        headers.extend(BASELINE_COLUMN_HEADERS);
+    }
+    let mut output = format!("{:<width$}", EXPRESSION_HEADER, width = expression_width);
+    for header in headers {
+        output.push_str(&format!("{:>width$}", header, width = COLUMN_WIDTH));
+    }
+
+    for timing in timings {
+        output.push('\n');
+        output.push_str(&format!(
+            "{:<width$}",
+            timing.expression,
+            width = expression_width
+        ));
+        let mut cells = vec![
+            format_nanoseconds(timing.tokenize_ns),
+            format_nanoseconds(timing.parse_ns),
+            format_nanoseconds(timing.evaluate_ns),
+            format_nanoseconds(timing.format_ns),
+            format_nanoseconds(timing.total_ns()),
+        ];
+        if let Some(baseline) = maybe_baseline {
+            match baseline.get(&timing.expression) {
+                Some(baseline_timing) => {
+                    let baseline_ns = baseline_timing.total_ns();
+                    let change = (timing.total_ns() - baseline_ns) / baseline_ns * 100.0;
+                    cells.push(format_nanoseconds(baseline_ns));
+                    cells.push(format!("{:+.1}%", change));
+                }
+                None => {
+                    cells.push("-".to_string());
+                    cells.push("-".to_string());
+                }
+            }
+        }
+        for cell in cells {
+            output.push_str(&format!("{:>width$}", cell, width = COLUMN_WIDTH));
+        }
+    }
+    output
+}
+
+fn format_nanoseconds(ns: f64) -> String {
+    if ns < 1_000.0 {
+        format!("{:.0}ns", ns)
+    } else if ns < 1_000_000.0 {
+        format!("{:.1}µs", ns / 1_000.0)
+    } else if ns < 1_000_000_000.0 {
+        format!("{:.1}ms", ns / 1_000_000.0)
+    } else {
+        format!("{:.2}s", ns / 1_000_000_000.0)
+    }
+}
+
+#[cfg(test)]
+mod bench_tests {
+    use crate::{
+        bench::{make_table, time_suite, Timing, SUITE},
+        Args,
+    };
+    use std::{collections::HashMap, time::Duration};
+
+    #[test]
+    fn suite_evaluates() {
+        let timings = time_suite(&Args::default(), Duration::ZERO).unwrap();
+        assert_eq!(timings.len(), SUITE.len());
+        for (timing, expression) in timings.iter().zip(SUITE) {
+            assert_eq!(timing.expression, *expression);
+        }
+    }
+
+    #[test]
+    fn table_with_baseline() {
+        let timing = |expression: &str, total_ns: f64| Timing {
+            expression: expression.to_string(),
+            tokenize_ns: total_ns / 4.0,
+            parse_ns: total_ns / 4.0,
+            evaluate_ns: total_ns / 4.0,
+            format_ns: total_ns / 4.0,
+        };
+        let timings = vec![timing("1 + 2", 1_500.0), timing("3 ^ 4", 2_000_000.0)];
+        let baseline: HashMap<String, Timing> = [("1 + 2".to_string(), timing("1 + 2", 1_000.0))]
+            .into_iter()
+            .collect();
+        let table = make_table(&timings, Some(&baseline));
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "Expression  Tokenize     Parse  Evaluate    Format     Total  Baseline    Change",
+                "1 + 2          375ns     375ns     375ns     375ns     1.5µs     1.0µs    +50.0%",
+                "3 ^ 4        500.0µs   500.0µs   500.0µs   500.0µs     2.0ms         -         -",
+            ]
+        );
+    }
+}
@@ -1,54 +1,161 @@
 use crate::{
+    constants::{find_constant, CONSTANT_PREFIX, PHYSICAL_CONSTANTS},
+    currency::CurrencyRates,
     error::{
-        CalculatorFailure::{self, InputError},
-        MissingCapabilityError,
+        CalculatorFailure::{self, InputError, RuntimeError},
+        InputErrorKind, MissingCapabilityError, SyntaxError,
     },
+    format_quantity, format_real, format_table, format_value,
     input_history::InputHistory,
+    keystroke_macro::{KeystrokeMacro, MAX_MACRO_PLAYS},
+    network::{
+        address_argument, broadcast_address, format_ipv4, host_count, netmask, network_address,
+        parse_ipv4, MAX_PREFIX_LENGTH,
+    },
+    operations::{make_base64_string, parse_base64, MAX_RADIX},
+    plot::braille_plot,
+    polynomial::{find_roots, polynomial_from_values, MAX_DEGREE},
     position::{MaybePositioned, Position, Positioned},
-    saved_data::{validate_max_history_size, SavedData},
-    token::Tokenizer,
-    variable::VariableStore,
-    Args,
+    registers::input_text,
+    saved_data::{validate_max_history_size, validate_profile_name, SavedData},
+    storage::{reborrow, SavedSetting, Storage},
+    suggestion::{closest_matches, format_suggestions},
+    syntax_tree::{NodeKind, SyntaxTree, TraceStep},
+    token::{ParsedInput, Token, Tokenizer},
+    tokenize_input,
+    transcript::Transcript,
+    units::{convert, Dimension, Unit, UnitTable},
+    variable::{
+        displayed_variable, in_namespace, Variable, VariableFormat, VariableName, VariableStore,
+        NAMESPACE_SEPARATOR,
+    },
+    AngleUnit, Args, CloseParens, ErrorAlert, FractionStyle, GroupSeparator, Notation, Value,
 };
+use crossterm::terminal;
+use num::{rational::BigRational, BigInt, One, Signed, ToPrimitive, Zero};
 use std::{
     cmp::max,
     collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
 };
 
+/// The variable that the memory used by `/m+`, `/m-`, `/mr`, and `/mc` is kept in. Since it is a
+/// variable, the memory is saved along with the other variables and can be used in expressions.
+pub const MEMORY_VARIABLE_NAME: &str = "$mem";
+
+// The number of terminal rows that `/plot` leaves for the input line, the x axis labels, and the
+// rest of the output around the graph.
+const PLOT_RESERVED_ROWS: usize = 4;
+// The range of heights that `/plot` graphs are drawn at, in lines.
+const PLOT_MIN_LINES: usize = 4;
+const PLOT_MAX_LINES: usize = 16;
+// The narrowest that `/plot` graphs are drawn, in columns, even if the terminal is narrower.
+const PLOT_MIN_COLS: usize = 10;
+
+// The most rows that `/table` will output.
+const MAX_TABLE_ROWS: usize = 1000;
+
 // When a new command is created, the constructor function needs to be added to this list.
 const COMMAND_CONSTRUCTORS: &'static [fn() -> Box<dyn Command>] = &[
     HelpCommand::new,
+    AstCommand::new,
+    TraceCommand::new,
     ReloadVarCommand::new,
     PurgeVarCommand::new,
+    VarsCommand::new,
+    MemoryAddCommand::new,
+    MemorySubtractCommand::new,
+    MemoryRecallCommand::new,
+    MemoryClearCommand::new,
+    PinCommand::new,
+    UnpinCommand::new,
+    VarHistoryCommand::new,
+    RollbackCommand::new,
+    VarFormatCommand::new,
     HistoryCapacityCommand::new,
+    ExportHistoryCommand::new,
+    ImportHistoryCommand::new,
+    HistoryCommand::new,
+    ReplayCommand::new,
+    SessionsCommand::new,
+    FavCommand::new,
+    UnfavCommand::new,
+    FavsCommand::new,
+    SnippetCommand::new,
+    ProfileCommand::new,
     FractionalCommand::new,
+    MaxDenominatorCommand::new,
+    FactoredCommand::new,
     RadixCommand::new,
     ConvertToRadixCommand::new,
     UpperCommand::new,
+    BasesCommand::new,
+    RecallCommandsCommand::new,
+    CloseParensCommand::new,
+    StrictCommand::new,
+    SiSuffixesCommand::new,
+    AngleCommand::new,
+    ErrorAlertCommand::new,
     CommaCommand::new,
+    GroupSizeCommand::new,
+    GroupSepCommand::new,
+    DecimalCommaCommand::new,
     PrecisionCommand::new,
+    SciAboveCommand::new,
+    SciBelowCommand::new,
+    NotationCommand::new,
+    ConvertCommand::new,
+    DiffCommand::new,
+    PlotCommand::new,
+    TableCommand::new,
+    TranscriptCommand::new,
+    MacroCommand::new,
+    RatesCommand::new,
+    ConstantsCommand::new,
+    DefUnitCommand::new,
+    UndefUnitCommand::new,
+    IpCommand::new,
+    Base64Command::new,
+    RootsCommand::new,
 ];
 
-struct DataForCommands<'a> {
-    args: &'a mut Args,
-    tokenizer: &'a Tokenizer,
-    maybe_db: Option<&'a mut SavedData>,
-    // TODO: Maybe remove lint override? I want this in here for now because I think I may add
-    //       commands that need it later.
-    #[allow(dead_code)]
-    maybe_inputs: Option<&'a mut InputHistory>,
-    maybe_vars: Option<&'a mut VariableStore>,
+/// Everything that a command has access to while it runs. Any of the optional pieces may be
+/// missing, in which case commands that need them should return a `MissingCapabilityError`.
+pub struct DataForCommands<'a> {
+    pub args: &'a mut Args,
+    pub tokenizer: &'a Tokenizer,
+    pub maybe_db: Option<&'a mut SavedData>,
+    pub maybe_inputs: Option<&'a mut InputHistory>,
+    pub maybe_vars: Option<&'a mut VariableStore>,
+    /// The id of the input history entry for the command being executed. This is what should be
+    /// passed to `VariableStore` when changing variables.
+    pub maybe_input_history_id: Option<i64>,
+    /// The units that `/convert` can convert between.
+    pub units: &'a mut UnitTable,
+    /// The currency rates that were loaded into `units`, if any have been.
+    pub maybe_currency_rates: &'a mut Option<CurrencyRates>,
+    /// The transcript that `/transcript` is recording to, if any.
+    pub maybe_transcript: &'a mut Option<Transcript>,
+    /// The keys recorded with Alt+R, which `/macro` plays back.
+    pub keystroke_macro: &'a mut KeystrokeMacro,
     command_map: &'a HashMap<String, Box<dyn Command>>,
     alias_map: &'a HashMap<String, String>,
 }
 
-trait Command {
+/// A slash command, such as `/help`. Commands other than the built in ones can be added with
+/// `CommandExecutor::register_command`.
+pub trait Command {
+    /// The name used to invoke the command, without the leading `/`.
     fn name(&self) -> &'static str;
 
     fn aliases(&self) -> &'static [&'static str];
 
+    /// A one line description, shown in the `/help` command list. By convention, this starts with
+    /// "(unavailable) " if the data that the command needs is missing.
     fn short_help(&self, data: &DataForCommands) -> String;
 
+    /// The full description, shown by `/help command_name`.
     fn long_help(&self, data: &DataForCommands) -> String;
 
     /// Returns an output string and a vector of variables touched by the command
@@ -57,45 +164,98 @@ trait Command {
         command_name: Positioned<String>,
         arguments: Positioned<String>,
         data: DataForCommands,
-    ) -> Result<(String, Vec<String>), CalculatorFailure>;
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure>;
 }
 
 pub struct CommandExecutor {
     command_map: HashMap<String, Box<dyn Command>>,
     alias_map: HashMap<String, String>,
+    units: UnitTable,
+    maybe_currency_rates: Option<CurrencyRates>,
+    maybe_transcript: Option<Transcript>,
+    keystroke_macro: KeystrokeMacro,
+}
+
+impl Default for CommandExecutor {
+    fn default() -> CommandExecutor {
+        CommandExecutor::new()
+    }
 }
 
 impl CommandExecutor {
     pub fn new() -> CommandExecutor {
-        let mut command_map: HashMap<String, Box<dyn Command>> = HashMap::new();
-        let mut alias_map: HashMap<String, String> = HashMap::new();
+        let mut command_executor = CommandExecutor {
+            command_map: HashMap::new(),
+            alias_map: HashMap::new(),
+            units: UnitTable::new(),
+            maybe_currency_rates: None,
+            maybe_transcript: None,
+            keystroke_macro: KeystrokeMacro::new(),
+        };
         for constructor in COMMAND_CONSTRUCTORS {
-            let command = constructor();
-            let command_name = command.name().to_string();
-            for alias in command.aliases() {
-                let alias_string = alias.to_string();
-                if command_map.get(&alias_string).is_some() {
-                    panic!("Alias matches command: {}", alias);
-                }
-                if alias_map
-                    .insert(alias_string, command_name.clone())
-                    .is_some()
-                {
-                    panic!("Duplicate alias: {}", alias);
-                }
+            if let Err(message) = command_executor.register_command(constructor()) {
+                panic!("{}", message);
+            }
+        }
+        command_executor
+    }
+
+    /// Makes the units stored by `/defunit` available to `/convert`.
+    pub fn load_custom_units(
+        &mut self,
+        db: &mut SavedData,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        load_custom_units(&mut self.units, db)
+    }
+
+    /// The keys recorded with Alt+R, along with any requests by `/macro` to play them.
+    pub fn keystroke_macro_mut(&mut self) -> &mut KeystrokeMacro {
+        &mut self.keystroke_macro
+    }
+
+    /// If `/transcript` is recording, appends the input and the output that it produced to the
+    /// transcript. If that fails, recording is stopped and the error is returned.
+    pub fn record_in_transcript(
+        &mut self,
+        input: &str,
+        output: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(transcript) = self.maybe_transcript.as_mut() {
+            if let Err(e) = transcript.record(input, output) {
+                self.maybe_transcript = None;
+                return Err(e.into());
             }
-            if alias_map.get(&command_name).is_some() {
-                panic!("Command matches alias: {}", command_name);
+        }
+        Ok(())
+    }
+
+    /// Adds a command in addition to the built in ones. It will be listed by `/help` alongside
+    /// them. Returns an error, without registering anything, if the command's name or any of its
+    /// aliases is already in use as a command name or alias.
+    pub fn register_command(&mut self, command: Box<dyn Command>) -> Result<(), String> {
+        let command_name = command.name().to_string();
+        if self.command_map.contains_key(&command_name) {
+            return Err(format!("Duplicate command: {}", command_name));
+        }
+        if self.alias_map.contains_key(&command_name) {
+            return Err(format!("Command matches alias: {}", command_name));
+        }
+        let mut new_aliases: HashSet<&str> = HashSet::new();
+        for alias in command.aliases() {
+            if self.command_map.contains_key(*alias) || *alias == command_name {
+                return Err(format!("Alias matches command: {}", alias));
             }
-            if let Some(replaced_command) = command_map.insert(command_name, command) {
-                panic!("Duplicate command: {}", replaced_command.name());
+            if self.alias_map.contains_key(*alias) || !new_aliases.insert(alias) {
+                return Err(format!("Duplicate alias: {}", alias));
             }
         }
 
-        CommandExecutor {
-            command_map,
-            alias_map,
+        for alias in new_aliases {
+            self.alias_map
+                .insert(alias.to_string(), command_name.clone());
         }
+        self.command_map.insert(command_name, command);
+        Ok(())
     }
 
     pub fn execute_command(
@@ -107,7 +267,8 @@ impl CommandExecutor {
         maybe_db: Option<&mut SavedData>,
         maybe_inputs: Option<&mut InputHistory>,
         maybe_vars: Option<&mut VariableStore>,
-    ) -> Result<(String, Vec<String>), CalculatorFailure> {
+        maybe_input_history_id: Option<i64>,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
         let command_name = match self.alias_map.get(&alias_name.value) {
             Some(name) => name,
             None => &alias_name.value,
@@ -115,21 +276,32 @@ impl CommandExecutor {
 
         match self.command_map.get(command_name) {
             Some(command) => {
+                // Commands like `/convert` use radians at the same precision as expressions do.
+                self.units.set_precision(
+                    program_arguments.precision + program_arguments.extra_precision,
+                    program_arguments.radix,
+                );
                 let data = DataForCommands {
                     args: program_arguments,
                     tokenizer,
                     maybe_db,
                     maybe_inputs,
                     maybe_vars,
+                    maybe_input_history_id,
+                    units: &mut self.units,
+                    maybe_currency_rates: &mut self.maybe_currency_rates,
+                    maybe_transcript: &mut self.maybe_transcript,
+                    keystroke_macro: &mut self.keystroke_macro,
                     command_map: &self.command_map,
                     alias_map: &self.alias_map,
                 };
                 command.execute(alias_name, arguments, data)
             }
-            None => Err(InputError(MaybePositioned::new_positioned(
-                format!("No such command: '{}'", alias_name.value),
-                alias_name.position,
-            ))),
+            None => Err(no_such_command_error(
+                alias_name,
+                &self.command_map,
+                &self.alias_map,
+            )),
         }
     }
 }
@@ -171,7 +343,7 @@ impl Command for HelpCommand {
         _command_name: Positioned<String>,
         mut arguments: Positioned<String>,
         data: DataForCommands,
-    ) -> Result<(String, Vec<String>), CalculatorFailure> {
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
         arguments.trim();
         let alias_name = arguments;
 
@@ -198,16 +370,278 @@ impl Command for HelpCommand {
             match data.command_map.get(command_name) {
                 Some(command) => Ok((command.long_help(&data), Vec::new())),
                 None => {
-                    return Err(InputError(MaybePositioned::new_positioned(
-                        format!("No such command: '{}'", alias_name.value),
-                        alias_name.position,
-                    )))
+                    return Err(no_such_command_error(
+                        alias_name,
+                        data.command_map,
+                        data.alias_map,
+                    ))
                 }
             }
         }
     }
 }
 
+// The error for a command name that isn't a command or an alias. Suggests the commands and aliases
+// that are closest to it.
+fn no_such_command_error(
+    alias_name: Positioned<String>,
+    command_map: &HashMap<String, Box<dyn Command>>,
+    alias_map: &HashMap<String, String>,
+) -> CalculatorFailure {
+    let names = command_map
+        .keys()
+        .chain(alias_map.keys())
+        .map(String::as_str);
+    let suggestions = closest_matches(&alias_name.value, names);
+    InputError(MaybePositioned::new_positioned(
+        format!(
+            "No such command: '{}'{}",
+            alias_name.value,
+            format_suggestions(&suggestions)
+        )
+        .into(),
+        alias_name.position,
+    ))
+}
+
+// Parses an expression given as a command argument. Positions in the resulting syntax tree or
+// error are relative to the start of the expression. See `offset_input_error`.
+fn parse_expression(
+    expression: &str,
+    tokenizer: &Tokenizer,
+    args: &Args,
+) -> Result<SyntaxTree, CalculatorFailure> {
+    let tokens = match tokenize_input(tokenizer, expression, args)? {
+        ParsedInput::Tokens(tokens) if !tokens.is_empty() => tokens,
+        _ => {
+            return Err(InputError(MaybePositioned::new_positioned(
+                InputErrorKind::Syntax(SyntaxError::NoInput),
+                Position {
+                    start: 0,
+                    width: expression.len(),
+                },
+            )));
+        }
+    };
+    Ok(SyntaxTree::new_with_args(tokens.into(), args)?)
+}
+
+// Errors from evaluating an expression given as a command argument have positions relative to the
+// start of the expression. They need to be adjusted to be positions within the whole input before
+// they can be used to point out errors.
+fn offset_input_error(error: CalculatorFailure, expression_start: usize) -> CalculatorFailure {
+    match error {
+        InputError(mut message) => {
+            if let Some(position) = message.maybe_position.as_mut() {
+                position.start += expression_start;
+            }
+            InputError(message)
+        }
+        e => e,
+    }
+}
+
+// Evaluates an expression given as part of a command's arguments, which starts at
+// `expression_start` within the whole input. Returns the value along with its dimension, if that
+// is known. Any variables that the expression refers to are added to `variables_touched`. The
+// value has to be real.
+fn evaluate_argument(
+    expression: &str,
+    expression_start: usize,
+    data: &mut DataForCommands,
+    variables_touched: &mut Vec<VariableName>,
+) -> Result<(BigRational, Option<Dimension>), CalculatorFailure> {
+    let syntax_tree = parse_expression(expression, data.tokenizer, data.args)
+        .map_err(|e| offset_input_error(e, expression_start))?;
+    let maybe_dimension = syntax_tree
+        .dimension()
+        .map_err(|e| offset_input_error(e, expression_start))?;
+    let mut observer = |step: &TraceStep| {
+        if let NodeKind::Variable(name) = &step.kind {
+            variables_touched.push(name.clone());
+        }
+    };
+    let value = syntax_tree
+        .execute_traced(
+            data.maybe_input_history_id,
+            data.maybe_vars.as_deref_mut(),
+            data.maybe_db
+                .as_deref_mut()
+                .map(|db| db as &mut dyn Storage),
+            data.args,
+            Some(&mut observer),
+        )
+        .map_err(|e| offset_input_error(e, expression_start))?;
+    let value = expect_real(
+        value,
+        Position {
+            start: expression_start,
+            width: expression.len(),
+        },
+    )?;
+    Ok((value, maybe_dimension))
+}
+
+struct AstCommand;
+
+impl AstCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(AstCommand {})
+    }
+}
+
+impl Command for AstCommand {
+    fn name(&self) -> &'static str {
+        "ast"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Shows how an expression is parsed".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /ast [--json] expression\n\n",
+            "Shows the syntax tree that the expression is parsed into, without evaluating it. ",
+            "Each part of the expression is listed on its own line, with its operands listed below ",
+            "it and indented further.\n",
+            "If --json is given, the syntax tree is instead output as JSON, including the position ",
+            "of each part within the expression. This is intended for use by other tools. If the ",
+            "expression is invalid, a JSON object describing the error is output instead.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        let (json, expression_offset) = match arguments.value.strip_prefix("--json") {
+            Some("") => (true, arguments.value.len()),
+            Some(rest) if rest.starts_with(|c: char| c.is_ascii_whitespace()) => {
+                (true, arguments.value.len() - rest.len())
+            }
+            _ => (false, 0),
+        };
+        let expression = arguments.value[expression_offset..].trim_start();
+        let expression_offset = arguments.value.len() - expression.len();
+        let expression_start = arguments.position.start + expression_offset;
+
+        let parsed = parse_expression(expression, data.tokenizer, data.args);
+
+        // In JSON mode, input errors are output as JSON too so that other tools can tell what kind
+        // of error occurred. Positions are relative to the start of the expression, just like the
+        // positions in the syntax tree.
+        let output = match (parsed, json) {
+            (Ok(syntax_tree), false) => syntax_tree.outline(),
+            (Ok(syntax_tree), true) => {
+                serde_json::to_string_pretty(&syntax_tree).map_err(|e| RuntimeError(e.into()))?
+            }
+            (Err(InputError(error)), true) => {
+                serde_json::to_string_pretty(&serde_json::json!({ "error": error }))
+                    .map_err(|e| RuntimeError(e.into()))?
+            }
+            (Err(e), _) => return Err(offset_input_error(e, expression_start)),
+        };
+        Ok((output, Vec::new()))
+    }
+}
+
+struct TraceCommand;
+
+impl TraceCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(TraceCommand {})
+    }
+}
+
+impl Command for TraceCommand {
+    fn name(&self) -> &'static str {
+        "trace"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Evaluates an expression step by step".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /trace expression\n\n",
+            "Evaluates the expression, showing each step of the evaluation in the order that it ",
+            "happens. Each line shows part of the expression and the value that it evaluated to. ",
+            "Numbers and parentheses are not shown on their own. The last line shows the value of ",
+            "the whole expression.\n",
+            "If the expression assigns to a variable, the assignment happens just as it would if ",
+            "the expression had been entered without /trace.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        let expression = &arguments.value;
+        let expression_start = arguments.position.start;
+        let syntax_tree = parse_expression(expression, data.tokenizer, data.args)
+            .map_err(|e| offset_input_error(e, expression_start))?;
+
+        let mut steps: Vec<(NodeKind, Position, Value)> = Vec::new();
+        let mut observer = |step: &TraceStep| {
+            steps.push((step.kind.clone(), step.position, step.value.clone()));
+        };
+        let result = syntax_tree
+            .execute_traced(
+                data.maybe_input_history_id,
+                data.maybe_vars,
+                data.maybe_db.map(|db| db as &mut dyn Storage),
+                data.args,
+                Some(&mut observer),
+            )
+            .map_err(|e| offset_input_error(e, expression_start))?;
+
+        let mut output = String::new();
+        let mut variables_touched: Vec<VariableName> = Vec::new();
+        for (kind, position, value) in steps {
+            match kind {
+                NodeKind::Number(_)
+                | NodeKind::Imaginary(_)
+                | NodeKind::Quantity(..)
+                | NodeKind::Parenthesized => continue,
+                NodeKind::Variable(name) => variables_touched.push(name),
+                _ => {}
+            }
+            if !output.is_empty() {
+                output.push('\n');
+            }
+            output.push_str(&format!(
+                "{} = {}",
+                &expression[position.start..position.start + position.width],
+                format_value(&value, data.args)
+            ));
+        }
+        // If every step was skipped, the expression was just a number, possibly in parentheses.
+        if output.is_empty() {
+            output = format!("{} = {}", expression, format_value(&result, data.args));
+        }
+        Ok((output, variables_touched))
+    }
+}
+
 struct ReloadVarCommand;
 
 impl ReloadVarCommand {
@@ -262,8 +696,8 @@ impl Command for ReloadVarCommand {
         _command_name: Positioned<String>,
         arguments: Positioned<String>,
         data: DataForCommands,
-    ) -> Result<(String, Vec<String>), CalculatorFailure> {
-        let variable_tokens: HashSet<Positioned<String>> = data
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let variable_tokens: HashSet<Positioned<VariableName>> = data
             .tokenizer
             .tokenize_variable_list(&arguments.value)?
             .into_iter()
@@ -275,12 +709,12 @@ impl Command for ReloadVarCommand {
             .ok_or(MissingCapabilityError::NoVariableStore)?;
 
         let mut output = String::new();
-        let mut variables_touched: Vec<String> = Vec::new();
+        let mut variables_touched: Vec<VariableName> = Vec::new();
         for variable_token in variable_tokens {
             if !output.is_empty() {
                 output.push('\n');
             }
-            if let Some(reloaded) = vars.reload(variable_token.value.clone(), db)? {
+            if let Some(reloaded) = vars.reload(&variable_token.value, db)? {
                 output.push_str(&format!("Set {} to {}", reloaded.name, reloaded.value));
             } else {
                 output.push_str(&format!("{} unchanged", variable_token.value));
@@ -323,7 +757,9 @@ impl Command for PurgeVarCommand {
         let mut output = concat!(
             "Usage: /purgevar variable_name_1 [variable_name_2 [...]]\n\n",
             "Removes the variable(s) from both the variable store and the variable history in the ",
-            "on-disk database, if available."
+            "on-disk database, if available.\n",
+            "Every variable in a namespace can be removed at once by ending the namespace with .*, ",
+            "like $proj.* for $proj.rate and $proj.tax.rate."
         )
         .to_string();
         if data.maybe_vars.is_none() {
@@ -341,10 +777,23 @@ impl Command for PurgeVarCommand {
         _command_name: Positioned<String>,
         arguments: Positioned<String>,
         mut data: DataForCommands,
-    ) -> Result<(String, Vec<String>), CalculatorFailure> {
-        let variable_tokens: HashSet<Positioned<String>> = data
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        // Namespace wildcards, like `$proj.*`, are blanked out of the arguments before the rest are
+        // tokenized so that the positions of the remaining variables are unchanged.
+        let mut namespaces: Vec<&str> = Vec::new();
+        let mut remaining = arguments.value.clone();
+        let mut search_start = 0;
+        for word in arguments.value.split_ascii_whitespace() {
+            let start = search_start + arguments.value[search_start..].find(word).unwrap();
+            search_start = start + word.len();
+            if let Some(namespace) = word.strip_suffix(".*") {
+                remaining.replace_range(start..search_start, &" ".repeat(word.len()));
+                namespaces.push(namespace);
+            }
+        }
+        let variable_tokens: HashSet<Positioned<VariableName>> = data
             .tokenizer
-            .tokenize_variable_list(&arguments.value)?
+            .tokenize_variable_list(&remaining)?
             .into_iter()
             .collect();
 
@@ -352,11 +801,33 @@ impl Command for PurgeVarCommand {
             .maybe_vars
             .ok_or(MissingCapabilityError::NoVariableStore)?;
 
-        for variable_token in variable_tokens {
+        let mut names: HashSet<String> = variable_tokens
+            .into_iter()
+            .map(|token| token.value.to_string())
+            .collect();
+        if !namespaces.is_empty() {
+            let all_names = vars.all_names(
+                data.maybe_db
+                    .as_deref_mut()
+                    .map(|db| db as &mut dyn Storage),
+            )?;
+            names.extend(all_names.into_iter().filter(|name| {
+                namespaces
+                    .iter()
+                    .any(|namespace| in_namespace(name, namespace))
+            }));
+        }
+
+        for name in names {
             // `as_deref_mut` is used here to reborrow the database reference into a new `Option`.
             // If we didn't do that, we would move `data.maybe_db` into the `purge` call and then
             // wouldn't be able to call it again when we loop.
-            vars.purge(&variable_token.value, data.maybe_db.as_deref_mut())?;
+            vars.purge(
+                &name,
+                data.maybe_db
+                    .as_deref_mut()
+                    .map(|db| db as &mut dyn Storage),
+            )?;
         }
 
         // Technically this touches variables, but it also removes them. Which means that reporting
@@ -365,17 +836,17 @@ impl Command for PurgeVarCommand {
     }
 }
 
-struct HistoryCapacityCommand;
+struct VarsCommand;
 
-impl HistoryCapacityCommand {
+impl VarsCommand {
     fn new() -> Box<dyn Command> {
-        Box::new(HistoryCapacityCommand {})
+        Box::new(VarsCommand {})
     }
 }
 
-impl Command for HistoryCapacityCommand {
+impl Command for VarsCommand {
     fn name(&self) -> &'static str {
-        "histcap"
+        "vars"
     }
 
     fn aliases(&self) -> &'static [&'static str] {
@@ -384,33 +855,28 @@ impl Command for HistoryCapacityCommand {
 
     fn short_help(&self, data: &DataForCommands) -> String {
         let mut output = String::new();
-        if data.maybe_db.is_none() {
+        if data.maybe_vars.is_none() {
             output.push_str("(unavailable) ");
         }
-        output.push_str("Get/Set the on-disk history capacity");
+        output.push_str("Lists variables, optionally only those in a namespace");
 
         output
     }
 
     fn long_help(&self, data: &DataForCommands) -> String {
         let mut output = concat!(
-            "Usage: /histcap [size]\n\n",
-            "If a size is provided, the maximum on-disk history size is updated. If one is not ",
-            "provided, the current size is printed.\n",
-            "bcalc provides an in-memory input history as well as an on-disk one. The in-memory ",
-            "history is currently not limited but will, of course, be lost when the instance of ",
-            "bcalc exits. The on-disk history is not cleared when bcalc exits. It would be a ",
-            "problem for it to grow out of control, so it is limited to a certain number of ",
-            "inputs. After reaching the limit, old entries will be removed.\n",
-            "The variable history is also tied to the input history. Values will be removed from ",
-            "the variable history after the last input that accessed that value is removed from ",
-            "the input history.\n",
-            "Provided size will always be assumed to use radix (base) 10.",
+            "Usage: /vars [namespace]\n\n",
+            "Lists the variables and their values, including those in the variable history in ",
+            "the on-disk database, if available.\n",
+            "Variables can be grouped into namespaces by separating the namespace from the rest ",
+            "of the name with a dot, like $proj.rate. If a namespace is given, like proj, only ",
+            "the variables in it are listed. Namespaces can be nested, like $proj.tax.rate, which ",
+            "is in both proj and proj.tax."
         )
         .to_string();
-        if data.maybe_db.is_none() {
+        if data.maybe_vars.is_none() {
             output.push_str(concat!(
-                "\n\nThis command is currently unavailable because the on-disk database is ",
+                "\n\nThis command is currently unavailable because the variable store is ",
                 "unavailable."
             ));
         }
@@ -422,74 +888,109 @@ impl Command for HistoryCapacityCommand {
         &self,
         _command_name: Positioned<String>,
         arguments: Positioned<String>,
-        data: DataForCommands,
-    ) -> Result<(String, Vec<String>), CalculatorFailure> {
-        let mut parsed_args = data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
-        let input: Option<i64> = if parsed_args.is_empty() {
-            None
-        } else if parsed_args.len() == 1 {
-            let integer = parsed_args.pop().unwrap();
-            validate_max_history_size(integer.value)
-                .map_err(|s| InputError(MaybePositioned::new_positioned(s, integer.position)))?;
-            Some(integer.value)
-        } else {
-            let last_arg = parsed_args.pop().unwrap();
-            let first_arg = parsed_args.into_iter().next().unwrap();
-            return Err(InputError(MaybePositioned::new_span(
-                "Too many arguments".to_string(),
-                first_arg.position,
-                last_arg.position,
+        mut data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let namespace = arguments.value.trim();
+        if namespace.contains(char::is_whitespace) {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Expected at most one namespace".to_string().into(),
+                arguments.position,
             )));
-        };
+        }
 
-        let db = data.maybe_db.ok_or(MissingCapabilityError::NoDatabase)?;
+        let vars = data
+            .maybe_vars
+            .ok_or(MissingCapabilityError::NoVariableStore)?;
 
-        match input {
-            Some(size) => {
-                db.set_max_history_size(size)?;
-                Ok(("Done".to_string(), Vec::new()))
-            }
-            None => {
-                let capacity = db.get_max_history_size()?;
-                Ok((capacity.to_string(), Vec::new()))
+        let mut lines: Vec<String> = Vec::new();
+        for name in vars.all_names(
+            data.maybe_db
+                .as_deref_mut()
+                .map(|db| db as &mut dyn Storage),
+        )? {
+            if !namespace.is_empty() && !in_namespace(&name, namespace) {
+                continue;
             }
+            let name: VariableName = name.into();
+            let mut maybe_db = data
+                .maybe_db
+                .as_deref_mut()
+                .map(|db| db as &mut dyn Storage);
+            let Some(variable) = vars.get(name.clone(), reborrow(&mut maybe_db))? else {
+                continue;
+            };
+            let args = match vars.format(&name, maybe_db)? {
+                Some(format) => format.apply(data.args),
+                None => data.args.clone(),
+            };
+            lines.push(format!(
+                "{} = {}",
+                name,
+                format_value(&variable.value, &args)
+            ));
+        }
+
+        if lines.is_empty() {
+            let message = if namespace.is_empty() {
+                "No variables are defined".to_string()
+            } else {
+                format!(
+                    "No variables are in the {} namespace",
+                    namespace
+                        .trim_start_matches('$')
+                        .trim_end_matches(NAMESPACE_SEPARATOR)
+                )
+            };
+            return Ok((message, Vec::new()));
         }
+        Ok((lines.join("\n"), Vec::new()))
     }
 }
 
-struct FractionalCommand;
+struct MemoryAddCommand;
 
-impl FractionalCommand {
+impl MemoryAddCommand {
     fn new() -> Box<dyn Command> {
-        Box::new(FractionalCommand {})
+        Box::new(MemoryAddCommand {})
     }
 }
 
-impl Command for FractionalCommand {
+impl Command for MemoryAddCommand {
     fn name(&self) -> &'static str {
-        "fractional"
+        "m+"
     }
 
     fn aliases(&self) -> &'static [&'static str] {
-        &["f"]
+        &[]
     }
 
-    fn short_help(&self, _data: &DataForCommands) -> String {
-        "Retrieves or sets fractional display setting".to_string()
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_vars.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Adds the last result to the memory");
+
+        output
     }
 
-    fn long_help(&self, _data: &DataForCommands) -> String {
-        concat!(
-            "Usage: /fractional [enabled]\n",
-            "Alias: /f\n\n",
-            "If the enabled value is \"true\", non-integer numbers will be output as fractions. ",
-            "If the value is \"false\", non-integer numbers will be output as decimals.\n",
-            "If no value is provided, the current setting value is displayed.\n",
-            "If a value is given, the setting value is updated.\n",
-            "The value given should be a boolean, which can be represented as \"true\", ",
-            "\"false\", \"t\", or \"f\".",
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /m+\n\n",
+            "Adds the value that the last input evaluated to to the memory, which starts out at ",
+            "0, and displays the new memory value. With an empty input line, Alt+= does the same.\n",
+            "The memory is kept in the $mem variable, so it is saved like any other variable and ",
+            "can be used in expressions."
         )
-        .to_string()
+        .to_string();
+        if data.maybe_vars.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the variable store is ",
+                "unavailable."
+            ));
+        }
+
+        output
     }
 
     fn execute(
@@ -497,60 +998,56 @@ impl Command for FractionalCommand {
         _command_name: Positioned<String>,
         arguments: Positioned<String>,
         data: DataForCommands,
-    ) -> Result<(String, Vec<String>), CalculatorFailure> {
-        let arg_lower = arguments.value.to_lowercase();
-        let arg_string = arg_lower.trim();
-        if arg_string.is_empty() {
-            return Ok((format!("{}", data.args.fractional), Vec::new()));
-        }
-
-        let value = if arg_string == "f" || arg_string == "false" {
-            false
-        } else if arg_string == "t" || arg_string == "true" {
-            true
-        } else {
-            return Err(InputError(MaybePositioned::new_positioned(
-                "Invalid argument".to_string(),
-                arguments.position,
-            )));
-        };
-
-        data.args.fractional = value;
-        Ok(("Done".to_string(), Vec::new()))
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        add_to_memory(arguments, data, false)
     }
 }
 
-struct RadixCommand;
+struct MemorySubtractCommand;
 
-impl RadixCommand {
+impl MemorySubtractCommand {
     fn new() -> Box<dyn Command> {
-        Box::new(RadixCommand {})
+        Box::new(MemorySubtractCommand {})
     }
 }
 
-impl Command for RadixCommand {
+impl Command for MemorySubtractCommand {
     fn name(&self) -> &'static str {
-        "radix"
+        "m-"
     }
 
     fn aliases(&self) -> &'static [&'static str] {
-        &["r"]
+        &[]
     }
 
-    fn short_help(&self, _data: &DataForCommands) -> String {
-        "Retrieves or sets the current radix".to_string()
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_vars.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Subtracts the last result from the memory");
+
+        output
     }
 
-    fn long_help(&self, _data: &DataForCommands) -> String {
-        concat!(
-            "Usage: /radix [value]\n",
-            "Alias: /r\n\n",
-            "Value represents the radix used to parse and output numbers.\n",
-            "If no value is provided, the current setting value is displayed.\n",
-            "If a value is given, the setting value is updated.\n",
-            "The value given should be an integer between 2 and 16 (inclusive).",
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /m-\n\n",
+            "Subtracts the value that the last input evaluated to from the memory, which starts ",
+            "out at 0, and displays the new memory value. With an empty input line, Alt+- does ",
+            "the same.\n",
+            "The memory is kept in the $mem variable, so it is saved like any other variable and ",
+            "can be used in expressions."
         )
-        .to_string()
+        .to_string();
+        if data.maybe_vars.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the variable store is ",
+                "unavailable."
+            ));
+        }
+
+        output
     }
 
     fn execute(
@@ -558,75 +1055,55 @@ impl Command for RadixCommand {
         _command_name: Positioned<String>,
         arguments: Positioned<String>,
         data: DataForCommands,
-    ) -> Result<(String, Vec<String>), CalculatorFailure> {
-        let mut parsed_args = data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
-        let input: Option<u8> = if parsed_args.is_empty() {
-            None
-        } else if parsed_args.len() == 1 {
-            let integer = parsed_args.pop().unwrap();
-            if integer.value < 2 {
-                return Err(InputError(MaybePositioned::new_positioned(
-                    "Radix cannot be less than 2".to_string(),
-                    integer.position,
-                )));
-            }
-            if integer.value > 16 {
-                return Err(InputError(MaybePositioned::new_positioned(
-                    "Radix cannot be greater than 16".to_string(),
-                    integer.position,
-                )));
-            }
-            Some(integer.value.try_into().unwrap())
-        } else {
-            let last_arg = parsed_args.pop().unwrap();
-            let first_arg = parsed_args.into_iter().next().unwrap();
-            return Err(InputError(MaybePositioned::new_span(
-                "Too many arguments".to_string(),
-                first_arg.position,
-                last_arg.position,
-            )));
-        };
-
-        match input {
-            Some(value) => {
-                data.args.radix = value;
-                Ok(("Done".to_string(), Vec::new()))
-            }
-            None => Ok((format!("{}", data.args.radix), Vec::new())),
-        }
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        add_to_memory(arguments, data, true)
     }
 }
 
-struct ConvertToRadixCommand;
+struct MemoryRecallCommand;
 
-impl ConvertToRadixCommand {
+impl MemoryRecallCommand {
     fn new() -> Box<dyn Command> {
-        Box::new(ConvertToRadixCommand {})
+        Box::new(MemoryRecallCommand {})
     }
 }
 
-impl Command for ConvertToRadixCommand {
+impl Command for MemoryRecallCommand {
     fn name(&self) -> &'static str {
-        "converttoradix"
+        "mr"
     }
 
     fn aliases(&self) -> &'static [&'static str] {
         &[]
     }
 
-    fn short_help(&self, _data: &DataForCommands) -> String {
-        "Retrieves or sets the current output radix".to_string()
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_vars.is_none() || data.maybe_inputs.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Puts the memory value in the input line");
+
+        output
     }
 
-    fn long_help(&self, _data: &DataForCommands) -> String {
-        concat!(
-            "Usage: /converttoradix [value]\n\n",
-            "Value overrides the radix used to output numbers.\n",
-            "If no value is provided, the current setting value is displayed.\n",
-            "If a value is given, the setting value is updated.\n",
-            "The value given can be \"none\" or an integer between 2 and 16 (inclusive).",
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /mr\n\n",
+            "Replaces the input line with the memory value, written exactly so that it can be ",
+            "used in a new expression. Alt+M instead types the memory value in at the cursor.\n",
+            "The memory is kept in the $mem variable, so it is saved like any other variable and ",
+            "can be used in expressions."
         )
-        .to_string()
+        .to_string();
+        if data.maybe_vars.is_none() || data.maybe_inputs.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the variable store or the ",
+                "input history is unavailable."
+            ));
+        }
+
+        output
     }
 
     fn execute(
@@ -634,87 +1111,69 @@ impl Command for ConvertToRadixCommand {
         _command_name: Positioned<String>,
         arguments: Positioned<String>,
         data: DataForCommands,
-    ) -> Result<(String, Vec<String>), CalculatorFailure> {
-        // "none" is a valid input, but won't be tokenized successfully. So handle that possibility
-        // first.
-        if arguments.value.to_lowercase().trim() == "none" {
-            data.args.convert_to_radix = None;
-            return Ok(("Done".to_string(), Vec::new()));
-        }
-
-        let mut parsed_args = data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
-        let input: Option<u8> = if parsed_args.is_empty() {
-            None
-        } else if parsed_args.len() == 1 {
-            let integer = parsed_args.pop().unwrap();
-            if integer.value < 2 {
-                return Err(InputError(MaybePositioned::new_positioned(
-                    "Radix cannot be less than 2".to_string(),
-                    integer.position,
-                )));
-            }
-            if integer.value > 16 {
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        expect_no_arguments(&arguments)?;
+        let inputs = data
+            .maybe_inputs
+            .ok_or(MissingCapabilityError::NoInputHistory)?;
+        let value = match get_memory(data.maybe_vars, data.maybe_db)? {
+            Some(value) => value,
+            None => {
                 return Err(InputError(MaybePositioned::new_positioned(
-                    "Radix cannot be greater than 16".to_string(),
-                    integer.position,
-                )));
+                    "The memory is empty".to_string().into(),
+                    arguments.position,
+                )))
             }
-            Some(integer.value.try_into().unwrap())
-        } else {
-            let last_arg = parsed_args.pop().unwrap();
-            let first_arg = parsed_args.into_iter().next().unwrap();
-            return Err(InputError(MaybePositioned::new_span(
-                "Too many arguments".to_string(),
-                first_arg.position,
-                last_arg.position,
-            )));
         };
-
-        match input {
-            Some(value) => {
-                data.args.convert_to_radix = Some(value);
-                Ok(("Done".to_string(), Vec::new()))
-            }
-            None => match data.args.convert_to_radix {
-                Some(radix) => Ok((format!("{}", radix), Vec::new())),
-                None => Ok(("None".to_string(), Vec::new())),
-            },
-        }
+        inputs.set_current_line(input_text(&value, data.args.radix));
+        Ok((String::new(), vec![MEMORY_VARIABLE_NAME.into()]))
     }
 }
 
-struct UpperCommand;
+struct MemoryClearCommand;
 
-impl UpperCommand {
+impl MemoryClearCommand {
     fn new() -> Box<dyn Command> {
-        Box::new(UpperCommand {})
+        Box::new(MemoryClearCommand {})
     }
 }
 
-impl Command for UpperCommand {
+impl Command for MemoryClearCommand {
     fn name(&self) -> &'static str {
-        "upper"
+        "mc"
     }
 
     fn aliases(&self) -> &'static [&'static str] {
         &[]
     }
 
-    fn short_help(&self, _data: &DataForCommands) -> String {
-        "Retrieves or sets upper display setting".to_string()
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_vars.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Clears the memory");
+
+        output
     }
 
-    fn long_help(&self, _data: &DataForCommands) -> String {
-        concat!(
-            "Usage: /upper [enabled]\n\n",
-            "If the enabled value is \"true\", digits above 9 will be output in uppercase. If ",
-            "\"false\", they will be output in lowercase.\n",
-            "If no value is provided, the current setting value is displayed.\n",
-            "If a value is given, the setting value is updated.\n",
-            "The value given should be a boolean, which can be represented as \"true\", ",
-            "\"false\", \"t\", or \"f\".",
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /mc\n\n",
+            "Clears the memory so that it starts out at 0 again. With an empty input line, Alt+C ",
+            "does the same.\n",
+            "The memory is kept in the $mem variable, so it is saved like any other variable and ",
+            "can be used in expressions."
         )
-        .to_string()
+        .to_string();
+        if data.maybe_vars.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the variable store is ",
+                "unavailable."
+            ));
+        }
+
+        output
     }
 
     fn execute(
@@ -722,62 +1181,151 @@ impl Command for UpperCommand {
         _command_name: Positioned<String>,
         arguments: Positioned<String>,
         data: DataForCommands,
-    ) -> Result<(String, Vec<String>), CalculatorFailure> {
-        let arg_lower = arguments.value.to_lowercase();
-        let arg_string = arg_lower.trim();
-        if arg_string.is_empty() {
-            return Ok((format!("{}", data.args.upper), Vec::new()));
-        }
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        expect_no_arguments(&arguments)?;
+        let vars = data
+            .maybe_vars
+            .ok_or(MissingCapabilityError::NoVariableStore)?;
+        vars.purge(
+            MEMORY_VARIABLE_NAME,
+            data.maybe_db.map(|db| db as &mut dyn Storage),
+        )?;
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
 
-        let value = if arg_string == "f" || arg_string == "false" {
-            false
-        } else if arg_string == "t" || arg_string == "true" {
-            true
-        } else {
+fn expect_no_arguments(arguments: &Positioned<String>) -> Result<(), CalculatorFailure> {
+    if arguments.value.trim().is_empty() {
+        Ok(())
+    } else {
+        Err(InputError(MaybePositioned::new_positioned(
+            "Unexpected argument".to_string().into(),
+            arguments.position,
+        )))
+    }
+}
+
+// For commands that only work with exact real numbers. `position` should be where the value came
+// from.
+fn expect_real(value: Value, position: Position) -> Result<BigRational, CalculatorFailure> {
+    let message = match value {
+        Value::Real(value)
+        | Value::Surd {
+            approximation: value,
+            ..
+        } => return Ok(value),
+        Value::Complex { .. } => "Expected a real number",
+        Value::Interval { .. } => "Expected a number without an uncertainty",
+    };
+    Err(InputError(MaybePositioned::new_positioned(
+        message.to_string().into(),
+        position,
+    )))
+}
+
+// The memory value, or `None` if the memory is empty.
+fn get_memory(
+    maybe_vars: Option<&mut VariableStore>,
+    maybe_db: Option<&mut SavedData>,
+) -> Result<Option<Value>, CalculatorFailure> {
+    let vars = maybe_vars.ok_or(MissingCapabilityError::NoVariableStore)?;
+    let maybe_variable = vars.get(
+        MEMORY_VARIABLE_NAME.into(),
+        maybe_db.map(|db| db as &mut dyn Storage),
+    )?;
+    Ok(maybe_variable.map(|variable| variable.value))
+}
+
+// Adds the last result to the memory, or subtracts it if `subtract` is set. See `/m+` and `/m-`.
+fn add_to_memory(
+    arguments: Positioned<String>,
+    mut data: DataForCommands,
+    subtract: bool,
+) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+    expect_no_arguments(&arguments)?;
+    let maybe_result = data
+        .maybe_vars
+        .as_deref()
+        .and_then(|vars| vars.recent_results(1))
+        .and_then(|mut results| results.next().cloned());
+    let memory = get_memory(data.maybe_vars.as_deref_mut(), data.maybe_db.as_deref_mut())?
+        .unwrap_or_else(|| Value::Real(BigRational::zero()));
+    let result = match maybe_result {
+        Some(result) => result,
+        None => {
             return Err(InputError(MaybePositioned::new_positioned(
-                "Invalid argument".to_string(),
+                "There is no last result yet".to_string().into(),
                 arguments.position,
-            )));
-        };
+            )))
+        }
+    };
+    let value = if subtract {
+        memory - result
+    } else {
+        memory + result
+    };
 
-        data.args.upper = value;
-        Ok(("Done".to_string(), Vec::new()))
-    }
+    let output = format_value(&value, data.args);
+    let name: VariableName = MEMORY_VARIABLE_NAME.into();
+    let vars = data
+        .maybe_vars
+        .ok_or(MissingCapabilityError::NoVariableStore)?;
+    vars.update(
+        Variable {
+            name: name.clone(),
+            value,
+        },
+        data.maybe_input_history_id,
+        data.maybe_db.map(|db| db as &mut dyn Storage),
+    )?;
+    Ok((output, vec![name]))
 }
 
-struct CommaCommand;
+struct PinCommand;
 
-impl CommaCommand {
+impl PinCommand {
     fn new() -> Box<dyn Command> {
-        Box::new(CommaCommand {})
+        Box::new(PinCommand {})
     }
 }
 
-impl Command for CommaCommand {
+impl Command for PinCommand {
     fn name(&self) -> &'static str {
-        "commas"
+        "pin"
     }
 
     fn aliases(&self) -> &'static [&'static str] {
-        &["comma"]
+        &[]
     }
 
-    fn short_help(&self, _data: &DataForCommands) -> String {
-        "Retrieves or sets comma display setting".to_string()
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_db.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Keeps variable(s) in the on-disk history indefinitely");
+
+        output
     }
 
-    fn long_help(&self, _data: &DataForCommands) -> String {
-        concat!(
-            "Usage: /commas [enabled]\n",
-            "Alias: /comma\n\n",
-            "If the enabled value is \"true\", commas will be used as thousands separators when ",
-            "outputting numbers.\n",
-            "If no value is provided, the current setting value is displayed.\n",
-            "If a value is given, the setting value is updated.\n",
-            "The value given should be a boolean, which can be represented as \"true\", ",
-            "\"false\", \"t\", or \"f\".",
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /pin [variable_name_1 [variable_name_2 [...]]]\n\n",
+            "Normally, values are removed from the on-disk variable history once the last input ",
+            "that accessed them is removed from the input history (see /help histcap). Pinned ",
+            "variables are never removed this way. They can still be removed with /purgevar.\n",
+            "If no variables are given, lists the pinned variables.\n",
+            "See also: /unpin"
         )
-        .to_string()
+        .to_string();
+        if data.maybe_db.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the on-disk database is ",
+                "unavailable."
+            ));
+        }
+
+        output
     }
 
     fn execute(
@@ -785,66 +1333,78 @@ impl Command for CommaCommand {
         _command_name: Positioned<String>,
         arguments: Positioned<String>,
         data: DataForCommands,
-    ) -> Result<(String, Vec<String>), CalculatorFailure> {
-        let arg_lower = arguments.value.to_lowercase();
-        let arg_string = arg_lower.trim();
-        if arg_string.is_empty() {
-            return Ok((format!("{}", data.args.commas), Vec::new()));
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let variable_tokens: Vec<Positioned<VariableName>> =
+            data.tokenizer.tokenize_variable_list(&arguments.value)?;
+
+        let db = data.maybe_db.ok_or(MissingCapabilityError::NoDatabase)?;
+
+        if variable_tokens.is_empty() {
+            let names = db.get_pinned_variable_names()?;
+            if names.is_empty() {
+                return Ok(("No variables are pinned".to_string(), Vec::new()));
+            }
+            return Ok((names.join("\n"), Vec::new()));
         }
 
-        let value = if arg_string == "f" || arg_string == "false" {
-            false
-        } else if arg_string == "t" || arg_string == "true" {
-            true
-        } else {
-            return Err(InputError(MaybePositioned::new_positioned(
-                "Invalid argument".to_string(),
-                arguments.position,
-            )));
-        };
+        let mut variables_touched: Vec<VariableName> = Vec::new();
+        for variable_token in variable_tokens {
+            if !db.set_variable_pinned(&variable_token.value, true)? {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    format!("{} is not in the variable history", variable_token.value).into(),
+                    variable_token.position,
+                )));
+            }
+            variables_touched.push(variable_token.value);
+        }
 
-        data.args.commas = value;
-        Ok(("Done".to_string(), Vec::new()))
+        Ok(("Done".to_string(), variables_touched))
     }
 }
 
-struct PrecisionCommand;
+struct UnpinCommand;
 
-impl PrecisionCommand {
+impl UnpinCommand {
     fn new() -> Box<dyn Command> {
-        Box::new(PrecisionCommand {})
+        Box::new(UnpinCommand {})
     }
 }
 
-impl Command for PrecisionCommand {
+impl Command for UnpinCommand {
     fn name(&self) -> &'static str {
-        "precision"
+        "unpin"
     }
 
     fn aliases(&self) -> &'static [&'static str] {
-        &["p"]
+        &[]
     }
 
-    fn short_help(&self, _data: &DataForCommands) -> String {
-        "Retrieves or sets the current precision".to_string()
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_db.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Undoes /pin");
+
+        output
     }
 
-    fn long_help(&self, _data: &DataForCommands) -> String {
-        concat!(
-            "Usage: /precision [value [extra]]\n",
-            "Alias: /p\n\n",
-            "The value represents the maximum number of digits that are displayed after the ",
-            "decimal point when outputting numbers.\n",
-            "If no value is provided, the current setting value is displayed.\n",
-            "If a value is given, the setting value is updated.\n",
-            "The value given should be representable as an 8-bit unsigned integer.\n",
-            "If extra is given, it should also be representable as an 8-bit unsigned integer.\n",
-            "This will represent the additional precision that is stored internally but not displayed.\n",
-            "This is only really relevant for operations that cannot be done with infinite precision.\n",
-            "For example: sqrt(2)\n",
-            "value + extra must also be representable as an 8-bit unsigned integer."
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /unpin variable_name_1 [variable_name_2 [...]]\n\n",
+            "Unpins the variable(s) so that they are once again removed from the on-disk variable ",
+            "history along with the last input that accessed them.\n",
+            "See also: /pin"
         )
-        .to_string()
+        .to_string();
+        if data.maybe_db.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the on-disk database is ",
+                "unavailable."
+            ));
+        }
+
+        output
     }
 
     fn execute(
@@ -852,68 +1412,6045 @@ impl Command for PrecisionCommand {
         _command_name: Positioned<String>,
         arguments: Positioned<String>,
         data: DataForCommands,
-    ) -> Result<(String, Vec<String>), CalculatorFailure> {
-        let mut parsed_args = data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
-        let input: Option<(u8, u8)> = if parsed_args.is_empty() {
-            None
-        } else if parsed_args.len() <= 2 {
-            let mut parsed_args_iter = parsed_args.into_iter();
-            let precision_raw = parsed_args_iter.next().unwrap();
-            let precision: u8 = precision_raw.value.try_into().map_err(|_| {
-                InputError(MaybePositioned::new_positioned(
-                    "Precision must be representable as an 8-bit unsigned integer".to_string(),
-                    precision_raw.position.clone(),
-                ))
-            })?;
-            let maybe_extra = parsed_args_iter.next();
-            let extra: u8 = match &maybe_extra {
-                None => data.args.extra_precision,
-                Some(extra_raw) => extra_raw.value.try_into().map_err(|_| {
-                    InputError(MaybePositioned::new_positioned(
-                        "Extra must be representable as an 8-bit unsigned integer".to_string(),
-                        extra_raw.position.clone(),
-                    ))
-                })?,
-            };
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let variable_tokens: Vec<Positioned<VariableName>> =
+            data.tokenizer.tokenize_variable_list(&arguments.value)?;
+        if variable_tokens.is_empty() {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Expected at least one variable".to_string().into(),
+                arguments.position,
+            )));
+        }
 
-            if precision.checked_add(extra).is_none() {
-                let position = match maybe_extra {
-                    None => precision_raw.position,
-                    Some(extra_raw) => {
-                        Position::from_span(precision_raw.position, extra_raw.position)
-                    }
-                };
+        let db = data.maybe_db.ok_or(MissingCapabilityError::NoDatabase)?;
+
+        let mut variables_touched: Vec<VariableName> = Vec::new();
+        for variable_token in variable_tokens {
+            if !db.set_variable_pinned(&variable_token.value, false)? {
                 return Err(InputError(MaybePositioned::new_positioned(
-                    "Sum of precision and extra must be representable as an 8-bit unsigned integer"
-                        .to_string(),
-                    position,
+                    format!("{} is not in the variable history", variable_token.value).into(),
+                    variable_token.position,
                 )));
             }
+            variables_touched.push(variable_token.value);
+        }
 
-            Some((precision, extra))
-        } else {
-            let last_arg = parsed_args.pop().unwrap();
-            let first_arg = parsed_args.into_iter().next().unwrap();
-            return Err(InputError(MaybePositioned::new_span(
-                "Too many arguments".to_string(),
-                first_arg.position,
-                last_arg.position,
+        Ok(("Done".to_string(), variables_touched))
+    }
+}
+
+struct VarHistoryCommand;
+
+impl VarHistoryCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(VarHistoryCommand {})
+    }
+}
+
+impl Command for VarHistoryCommand {
+    fn name(&self) -> &'static str {
+        "varhistory"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_db.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Lists prior values of a variable");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /varhistory variable_name\n\n",
+            "Lists the current value of the variable from the on-disk variable history, followed ",
+            "by its prior values, most recent first. Each value is numbered by how many versions ",
+            "ago it was replaced. Only the most recent prior values are kept.\n",
+            "See also: /rollback"
+        )
+        .to_string();
+        if data.maybe_db.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the on-disk database is ",
+                "unavailable."
+            ));
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let mut variable_tokens: Vec<Positioned<VariableName>> =
+            data.tokenizer.tokenize_variable_list(&arguments.value)?;
+        if variable_tokens.len() != 1 {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Expected exactly one variable".to_string().into(),
+                arguments.position,
+            )));
+        }
+        let variable_token = variable_tokens.remove(0);
+
+        let db = data.maybe_db.ok_or(MissingCapabilityError::NoDatabase)?;
+
+        let current = match db.get_variable(&variable_token.value)? {
+            Some(var) => var.value,
+            None => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    format!("{} is not in the variable history", variable_token.value).into(),
+                    variable_token.position,
+                )))
+            }
+        };
+        let mut output = format!("0 (current): {}", current);
+        for (index, value) in db
+            .get_variable_versions(&variable_token.value)?
+            .iter()
+            .enumerate()
+        {
+            output.push_str(&format!("\n{}: {}", index + 1, value));
+        }
+
+        Ok((output, vec![variable_token.value]))
+    }
+}
+
+struct RollbackCommand;
+
+impl RollbackCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(RollbackCommand {})
+    }
+}
+
+impl Command for RollbackCommand {
+    fn name(&self) -> &'static str {
+        "rollback"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_db.is_none() || data.maybe_vars.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Restores a prior value of a variable");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /rollback variable_name [versions]\n\n",
+            "Sets the variable back to the value it had the given number of versions ago, as ",
+            "listed by /varhistory. If the number of versions is not given, it defaults to 1, ",
+            "restoring the value that was most recently replaced.\n",
+            "The value being replaced is itself kept as a prior value, so a rollback can be undone ",
+            "with another /rollback.\n",
+            "See also: /varhistory"
+        )
+        .to_string();
+        if data.maybe_db.is_none() || data.maybe_vars.is_none() {
+            output.push_str("\n\nThis command is currently unavailable because ");
+            if data.maybe_db.is_none() && data.maybe_vars.is_none() {
+                output.push_str("both the on-disk database and the variable store are");
+            } else if data.maybe_db.is_none() {
+                output.push_str("the on-disk database is");
+            } else if data.maybe_vars.is_none() {
+                output.push_str("the variable store is");
+            }
+            output.push_str(" unavailable.");
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        let (variable_str, versions_str) = arguments
+            .value
+            .split_once(char::is_whitespace)
+            .unwrap_or((&arguments.value, ""));
+
+        let mut variable_tokens: Vec<Positioned<VariableName>> =
+            data.tokenizer.tokenize_variable_list(variable_str)?;
+        if variable_tokens.len() != 1 {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Expected exactly one variable".to_string().into(),
+                arguments.position,
             )));
+        }
+        let variable_token = variable_tokens.remove(0);
+
+        let versions_tokens: Vec<Positioned<i64>> =
+            data.tokenizer.tokenize_int_list(versions_str, 10)?;
+        let versions: usize = match versions_tokens.as_slice() {
+            [] => 1,
+            [versions_token] => match usize::try_from(versions_token.value) {
+                Ok(versions) if versions >= 1 => versions,
+                _ => {
+                    return Err(InputError(MaybePositioned::new_positioned(
+                        "Number of versions must be at least 1".to_string().into(),
+                        versions_token.position,
+                    )))
+                }
+            },
+            [_, extra_token, ..] => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Expected at most one number of versions".to_string().into(),
+                    extra_token.position,
+                )))
+            }
         };
 
-        match input {
-            Some((precision, extra)) => {
-                data.args.precision = precision;
-                data.args.extra_precision = extra;
-                Ok(("Done".to_string(), Vec::new()))
+        let db = data.maybe_db.ok_or(MissingCapabilityError::NoDatabase)?;
+        let vars = data
+            .maybe_vars
+            .ok_or(MissingCapabilityError::NoVariableStore)?;
+
+        let prior_values = db.get_variable_versions(&variable_token.value)?;
+        let value = match prior_values.into_iter().nth(versions - 1) {
+            Some(value) => value,
+            None => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    format!(
+                        "{} does not have a value from {} version(s) ago",
+                        variable_token.value, versions
+                    )
+                    .into(),
+                    variable_token.position,
+                )))
             }
-            None => Ok((
-                format!(
-                    "Precision = {}\nExtra Precision = {}",
-                    data.args.precision, data.args.extra_precision
-                ),
-                Vec::new(),
-            )),
+        };
+        let var = Variable {
+            name: variable_token.value,
+            value,
+        };
+        let output = format!("Set {} to {}", var.name, var.value);
+        let name = var.name.clone();
+        vars.update(var, data.maybe_input_history_id, Some(db))?;
+
+        Ok((output, vec![name]))
+    }
+}
+
+struct VarFormatCommand;
+
+impl VarFormatCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(VarFormatCommand {})
+    }
+}
+
+impl Command for VarFormatCommand {
+    fn name(&self) -> &'static str {
+        "varformat"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_vars.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Sets how a variable's value is shown");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /varformat variable_name [format]\n",
+            "       /varformat variable_name none\n\n",
+            "Attaches a display format to a variable. When an input just refers to the variable or ",
+            "assigns to it, the result is shown in that format rather than with the global ",
+            "settings. The format is made up of any of these settings, separated by spaces:\n",
+            "  hex, dec, oct, bin  Shows the value in radix 16, 10, 8, or 2\n",
+            "  radix N             Shows the value in radix N\n",
+            "  precision N         Shows N digits after the radix point\n",
+            "  fractional          Shows the value as a fraction\n",
+            "Settings that aren't given fall back to the global settings. For example, ",
+            "\"/varformat $mask hex\" shows $mask in hexadecimal. The format is saved along with ",
+            "the variable and is kept when the variable is assigned a new value.\n",
+            "With no format, shows the variable's current format. A format of \"none\" removes it."
+        )
+        .to_string();
+        if data.maybe_vars.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the variable store is ",
+                "unavailable."
+            ));
         }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        mut data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        let (variable_str, format_str) = match arguments.value.split_once(char::is_whitespace) {
+            Some((variable_str, format_str)) => (variable_str, format_str),
+            None => (arguments.value.as_str(), ""),
+        };
+        let format_position = Position {
+            start: arguments.position.start + arguments.value.len() - format_str.len(),
+            width: format_str.len(),
+        };
+        let mut variable_tokens: Vec<Positioned<VariableName>> =
+            data.tokenizer.tokenize_variable_list(variable_str)?;
+        if variable_tokens.len() != 1 {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Expected a variable".to_string().into(),
+                arguments.position,
+            )));
+        }
+        let mut variable_token = variable_tokens.remove(0);
+        variable_token.position.start += arguments.position.start;
+        let name = variable_token.value;
+
+        let vars = data
+            .maybe_vars
+            .ok_or(MissingCapabilityError::NoVariableStore)?;
+        let mut maybe_db = data
+            .maybe_db
+            .as_deref_mut()
+            .map(|db| db as &mut dyn Storage);
+        if vars.get(name.clone(), reborrow(&mut maybe_db))?.is_none() {
+            return Err(InputError(MaybePositioned::new_positioned(
+                format!("{} is not defined", name).into(),
+                variable_token.position,
+            )));
+        }
+
+        let output = match format_str.trim() {
+            "" => match vars.format(&name, maybe_db)? {
+                Some(format) => format.to_string(),
+                None => format!("{} has no format", name),
+            },
+            "none" => {
+                vars.set_format(&name, None, maybe_db)?;
+                "Done".to_string()
+            }
+            format_str => {
+                let format = VariableFormat::parse(format_str).map_err(|message| {
+                    InputError(MaybePositioned::new_positioned(
+                        message.into(),
+                        format_position,
+                    ))
+                })?;
+                vars.set_format(&name, Some(format), maybe_db)?;
+                "Done".to_string()
+            }
+        };
+
+        Ok((output, vec![name]))
+    }
+}
+
+struct HistoryCapacityCommand;
+
+impl HistoryCapacityCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(HistoryCapacityCommand {})
+    }
+}
+
+impl Command for HistoryCapacityCommand {
+    fn name(&self) -> &'static str {
+        "histcap"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_db.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Get/Set the on-disk history capacity");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /histcap [size]\n\n",
+            "If a size is provided, the maximum on-disk history size is updated. If one is not ",
+            "provided, the current size is printed.\n",
+            "bcalc provides an in-memory input history as well as an on-disk one. The in-memory ",
+            "history is currently not limited but will, of course, be lost when the instance of ",
+            "bcalc exits. The on-disk history is not cleared when bcalc exits. It would be a ",
+            "problem for it to grow out of control, so it is limited to a certain number of ",
+            "inputs. After reaching the limit, old entries will be removed.\n",
+            "The variable history is also tied to the input history. Values will be removed from ",
+            "the variable history after the last input that accessed that value is removed from ",
+            "the input history.\n",
+            "Provided size will always be assumed to use radix (base) 10.",
+        )
+        .to_string();
+        if data.maybe_db.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the on-disk database is ",
+                "unavailable."
+            ));
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let mut parsed_args = data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
+        let input: Option<i64> = if parsed_args.is_empty() {
+            None
+        } else if parsed_args.len() == 1 {
+            let integer = parsed_args.pop().unwrap();
+            validate_max_history_size(integer.value).map_err(|s| {
+                InputError(MaybePositioned::new_positioned(s.into(), integer.position))
+            })?;
+            Some(integer.value)
+        } else {
+            let last_arg = parsed_args.pop().unwrap();
+            let first_arg = parsed_args.into_iter().next().unwrap();
+            return Err(InputError(MaybePositioned::new_span(
+                "Too many arguments".to_string().into(),
+                first_arg.position,
+                last_arg.position,
+            )));
+        };
+
+        let db = data.maybe_db.ok_or(MissingCapabilityError::NoDatabase)?;
+
+        match input {
+            Some(size) => {
+                db.set_max_history_size(size)?;
+                Ok(("Done".to_string(), Vec::new()))
+            }
+            None => {
+                let capacity = db.get_max_history_size()?;
+                Ok((capacity.to_string(), Vec::new()))
+            }
+        }
+    }
+}
+
+struct ExportHistoryCommand;
+
+impl ExportHistoryCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(ExportHistoryCommand {})
+    }
+}
+
+impl Command for ExportHistoryCommand {
+    fn name(&self) -> &'static str {
+        "exporthistory"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_db.is_none() && data.maybe_inputs.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Writes the input history to a file");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /exporthistory path\n\n",
+            "Writes the input history to the file at the given path, one input per line, ordered ",
+            "from oldest to newest. If what an input evaluated to is known, it follows the input ",
+            "on the same line, separated by a tab. If the file already exists, it is ",
+            "overwritten.\n",
+            "No times are written, since the history only records when each session started, ",
+            "and imported inputs become part of the session that imports them.\n",
+            "If the on-disk database is available, the on-disk input history is written. ",
+            "Otherwise, only the inputs from the current session are written.\n",
+            "The file can be read back in with /importhistory."
+        )
+        .to_string();
+        if data.maybe_db.is_none() && data.maybe_inputs.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because both the on-disk database ",
+                "and the input history are unavailable."
+            ));
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        if arguments.value.is_empty() {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Expected a path".to_string().into(),
+                arguments.position,
+            )));
+        }
+
+        let entries: Vec<(String, Option<String>)> = match (data.maybe_db, data.maybe_inputs) {
+            (Some(db), _) => db
+                .get_all_input_history_entries()?
+                .into_iter()
+                .map(|entry| (entry.input, entry.maybe_result))
+                .collect(),
+            (None, Some(inputs)) => inputs
+                .session_inputs()
+                .iter()
+                .cloned()
+                .zip(inputs.session_results().iter().cloned())
+                .collect(),
+            (None, None) => return Err(MissingCapabilityError::NoDatabase.into()),
+        };
+
+        let mut contents = String::new();
+        for (input, maybe_result) in &entries {
+            contents.push_str(input);
+            if let Some(result) = maybe_result {
+                contents.push('\t');
+                contents.push_str(result);
+            }
+            contents.push('\n');
+        }
+        fs::write(&arguments.value, contents).map_err(|e| {
+            InputError(MaybePositioned::new_positioned(
+                format!("Unable to write to '{}': {}", arguments.value, e).into(),
+                arguments.position,
+            ))
+        })?;
+
+        Ok((format!("Exported {} inputs", entries.len()), Vec::new()))
+    }
+}
+
+struct ImportHistoryCommand;
+
+impl ImportHistoryCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(ImportHistoryCommand {})
+    }
+}
+
+impl Command for ImportHistoryCommand {
+    fn name(&self) -> &'static str {
+        "importhistory"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_inputs.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Adds inputs from a file to the input history");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /importhistory path\n\n",
+            "Reads the file at the given path, which should contain one input per line ordered ",
+            "from oldest to newest, such as a file written by /exporthistory. Each input is added ",
+            "to the input history as if it had been entered, but it is not evaluated. If an input ",
+            "is followed by a tab, the rest of the line is kept as what it evaluated to. Empty ",
+            "lines are skipped.\n",
+            "If the on-disk database is available, the inputs are also added to the on-disk input ",
+            "history, which is still subject to the size limit set by /histcap."
+        )
+        .to_string();
+        if data.maybe_inputs.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the input history is ",
+                "unavailable."
+            ));
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        if arguments.value.is_empty() {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Expected a path".to_string().into(),
+                arguments.position,
+            )));
+        }
+
+        let inputs_history = data
+            .maybe_inputs
+            .ok_or(MissingCapabilityError::NoInputHistory)?;
+
+        let contents = fs::read_to_string(&arguments.value).map_err(|e| {
+            InputError(MaybePositioned::new_positioned(
+                format!("Unable to read '{}': {}", arguments.value, e).into(),
+                arguments.position,
+            ))
+        })?;
+
+        let mut entries: Vec<(String, Option<String>)> = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            // Inputs can't be edited properly unless they are ASCII, so we check every line before
+            // we import any of them.
+            if !line.is_ascii() {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    format!(
+                        "Line {} of '{}' contains non-ASCII data",
+                        index + 1,
+                        arguments.value
+                    )
+                    .into(),
+                    arguments.position,
+                )));
+            }
+            // Inputs can't contain tabs, since the tab key isn't inserted into the input line.
+            entries.push(match line.split_once('\t') {
+                Some((input, result)) => (input.to_string(), Some(result.to_string())),
+                None => (line.to_string(), None),
+            });
+        }
+
+        let count = entries.len();
+        inputs_history.import_with_results(entries, data.maybe_db)?;
+
+        Ok((format!("Imported {} inputs", count), Vec::new()))
+    }
+}
+
+// The inputs that came before the one currently being handled, most recent first. If the on-disk
+// database is available, the on-disk input history is used. Otherwise, only the inputs from the
+// current session are used.
+fn prior_inputs(
+    maybe_db: Option<&mut SavedData>,
+    maybe_inputs: Option<&InputHistory>,
+) -> Result<Vec<String>, CalculatorFailure> {
+    let mut inputs: Vec<String> = match (maybe_db, maybe_inputs) {
+        (Some(db), _) => db.get_all_input_history()?,
+        (None, Some(inputs)) => inputs.session_inputs().to_vec(),
+        (None, None) => return Err(MissingCapabilityError::NoDatabase.into()),
+    };
+    // The input being handled is added to the history before it is handled, but only if the input
+    // history is available.
+    if maybe_inputs.is_some() {
+        inputs.pop();
+    }
+    inputs.reverse();
+    Ok(inputs)
+}
+
+struct HistoryCommand;
+
+impl HistoryCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(HistoryCommand {})
+    }
+}
+
+impl Command for HistoryCommand {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_db.is_none() && data.maybe_inputs.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Lists recent inputs");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /history [count]\n\n",
+            "Lists the given number of the most recent inputs, most recent first. If the count ",
+            "is not given, it defaults to 10. Each input is numbered by how many inputs ago it ",
+            "was entered, not counting the /history command itself.\n",
+            "If the on-disk database is available, the on-disk input history is listed. ",
+            "Otherwise, only the inputs from the current session are listed.\n",
+            "See also: /replay"
+        )
+        .to_string();
+        if data.maybe_db.is_none() && data.maybe_inputs.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because both the on-disk database ",
+                "and the input history are unavailable."
+            ));
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let count_tokens: Vec<Positioned<i64>> =
+            data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
+        let count: usize = match count_tokens.as_slice() {
+            [] => 10,
+            [count_token] => match usize::try_from(count_token.value) {
+                Ok(count) if count >= 1 => count,
+                _ => {
+                    return Err(InputError(MaybePositioned::new_positioned(
+                        "Count must be at least 1".to_string().into(),
+                        count_token.position,
+                    )))
+                }
+            },
+            [_, extra_token, ..] => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Expected at most one count".to_string().into(),
+                    extra_token.position,
+                )))
+            }
+        };
+
+        let inputs = prior_inputs(data.maybe_db, data.maybe_inputs.as_deref())?;
+        if inputs.is_empty() {
+            return Ok(("The input history is empty".to_string(), Vec::new()));
+        }
+        let lines: Vec<String> = inputs
+            .iter()
+            .take(count)
+            .enumerate()
+            .map(|(index, input)| format!("{}: {}", index + 1, input))
+            .collect();
+
+        Ok((lines.join("\n"), Vec::new()))
+    }
+}
+
+struct ReplayCommand;
+
+impl ReplayCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(ReplayCommand {})
+    }
+}
+
+impl Command for ReplayCommand {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_db.is_none() && data.maybe_inputs.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Evaluates a prior input again");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /replay number\n\n",
+            "Evaluates the input from the given number of inputs ago again, as numbered by ",
+            "/history. The input is evaluated using the current values of any variables, so the ",
+            "result may differ from the original one. If the input assigns to a variable, the ",
+            "variable is assigned again.\n",
+            "The new result is recorded in the input history as the result of the /replay ",
+            "command. Commands cannot be replayed.\n",
+            "See also: /history"
+        )
+        .to_string();
+        if data.maybe_db.is_none() && data.maybe_inputs.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because both the on-disk database ",
+                "and the input history are unavailable."
+            ));
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        mut data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let number_tokens: Vec<Positioned<i64>> =
+            data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
+        let number_token = match number_tokens.as_slice() {
+            [number_token] => number_token,
+            _ => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Expected exactly one input number".to_string().into(),
+                    arguments.position,
+                )))
+            }
+        };
+        let number = match usize::try_from(number_token.value) {
+            Ok(number) if number >= 1 => number,
+            _ => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Input number must be at least 1".to_string().into(),
+                    number_token.position,
+                )))
+            }
+        };
+
+        let inputs = prior_inputs(data.maybe_db.as_deref_mut(), data.maybe_inputs.as_deref())?;
+        let input = match inputs.into_iter().nth(number - 1) {
+            Some(input) => input,
+            None => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    format!("There is no input from {} input(s) ago", number).into(),
+                    number_token.position,
+                )))
+            }
+        };
+
+        // Positions within the replayed input don't mean anything in the current input, so errors
+        // point at the input number instead.
+        let reposition = |error: CalculatorFailure| match error {
+            InputError(message) => InputError(MaybePositioned::new_positioned(
+                message.value,
+                number_token.position,
+            )),
+            e => e,
+        };
+
+        let tokens = match tokenize_input(data.tokenizer, &input, data.args)
+            .map_err(|e| reposition(e.into()))?
+        {
+            ParsedInput::Tokens(tokens) if !tokens.is_empty() => tokens,
+            _ => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    format!("'{}' is a command; only expressions can be replayed", input).into(),
+                    number_token.position,
+                )))
+            }
+        };
+        let variables_touched: Vec<VariableName> = tokens
+            .iter()
+            .filter_map(|token| match &token.value {
+                Token::Variable(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let maybe_format = match (displayed_variable(&tokens), data.maybe_vars.as_deref_mut()) {
+            (Some(name), Some(vars)) => vars.format(
+                &name,
+                data.maybe_db
+                    .as_deref_mut()
+                    .map(|db| db as &mut dyn Storage),
+            )?,
+            _ => None,
+        };
+
+        let syntax_tree = SyntaxTree::new_with_args(tokens.into(), data.args)
+            .map_err(|e| reposition(e.into()))?;
+        let maybe_dimension = syntax_tree.dimension().map_err(reposition)?;
+        let result = syntax_tree
+            .execute(
+                data.maybe_input_history_id,
+                data.maybe_vars.as_deref_mut(),
+                data.maybe_db
+                    .as_deref_mut()
+                    .map(|db| db as &mut dyn Storage),
+                data.args,
+            )
+            .map_err(reposition)?;
+        if let Some(vars) = data.maybe_vars {
+            vars.push_result(result.clone());
+        }
+
+        let output = match maybe_format {
+            Some(format) => format_quantity(&result, maybe_dimension, &format.apply(data.args)),
+            None => format_quantity(&result, maybe_dimension, data.args),
+        };
+        if let Some(inputs) = data.maybe_inputs {
+            inputs.set_last_result(&output, data.maybe_input_history_id, data.maybe_db)?;
+        }
+
+        Ok((format!("{}  = {}", input, output), variables_touched))
+    }
+}
+
+struct SessionsCommand;
+
+impl SessionsCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(SessionsCommand {})
+    }
+}
+
+impl Command for SessionsCommand {
+    fn name(&self) -> &'static str {
+        "sessions"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["session"]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_db.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Browses the input history by session");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /sessions [session_id]\n\n",
+            "Without an argument, lists the sessions that still have inputs in the on-disk input ",
+            "history, along with when they started. Given a session id from that list, shows the ",
+            "inputs from that session and what they evaluated to.\n",
+            "Inputs stored before sessions were recorded don't belong to any session.\n",
+            "When scrolling through the input history, holding Control or Shift while pressing ",
+            "the up or down arrow keys moves a whole session at a time."
+        )
+        .to_string();
+        if data.maybe_db.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the on-disk database is ",
+                "unavailable."
+            ));
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let session_tokens: Vec<Positioned<i64>> =
+            data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
+
+        let db = data.maybe_db.ok_or(MissingCapabilityError::NoDatabase)?;
+
+        match session_tokens.as_slice() {
+            [] => {
+                let sessions = db.get_sessions()?;
+                if sessions.is_empty() {
+                    return Ok(("No sessions have been recorded".to_string(), Vec::new()));
+                }
+                let current = db.current_session();
+                let lines: Vec<String> = sessions
+                    .iter()
+                    .map(|session| {
+                        format!(
+                            "{}: {} ({} input{}){}",
+                            session.id,
+                            session.started,
+                            session.input_count,
+                            if session.input_count == 1 { "" } else { "s" },
+                            if Some(session.id) == current {
+                                " (current)"
+                            } else {
+                                ""
+                            }
+                        )
+                    })
+                    .collect();
+                Ok((lines.join("\n"), Vec::new()))
+            }
+            [session_token] => {
+                let entries = db.get_session_inputs(session_token.value)?;
+                if entries.is_empty() {
+                    return Err(InputError(MaybePositioned::new_positioned(
+                        format!(
+                            "No inputs from session {} are in the history",
+                            session_token.value
+                        )
+                        .into(),
+                        session_token.position,
+                    )));
+                }
+                let lines: Vec<String> = entries
+                    .into_iter()
+                    .map(|entry| match entry.maybe_result {
+                        Some(result) => format!("{}  = {}", entry.input, result),
+                        None => entry.input,
+                    })
+                    .collect();
+                Ok((lines.join("\n"), Vec::new()))
+            }
+            [_, extra_token, ..] => Err(InputError(MaybePositioned::new_positioned(
+                "Expected at most one session id".to_string().into(),
+                extra_token.position,
+            ))),
+        }
+    }
+}
+
+struct FavCommand;
+
+impl FavCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(FavCommand {})
+    }
+}
+
+impl Command for FavCommand {
+    fn name(&self) -> &'static str {
+        "fav"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_inputs.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Marks an input as a favorite");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /fav [input]\n\n",
+            "Marks the given input as a favorite. If no input is given, the previous input from ",
+            "this session is used.\n",
+            "Page Up and Page Down cycle through the favorites, replacing the input currently ",
+            "being edited. If the on-disk database is available, favorites are saved there and, ",
+            "unlike the input history, are never evicted.\n",
+            "See also: /unfav, /favs"
+        )
+        .to_string();
+        if data.maybe_inputs.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the input history is ",
+                "unavailable."
+            ));
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        let inputs = data
+            .maybe_inputs
+            .ok_or(MissingCapabilityError::NoInputHistory)?;
+
+        let input = if arguments.value.is_empty() {
+            // The last session input is this command, so the one before it is the input the user
+            // wants.
+            let session_inputs = inputs.session_inputs();
+            if session_inputs.len() < 2 {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "There is no previous input in this session"
+                        .to_string()
+                        .into(),
+                    arguments.position,
+                )));
+            }
+            session_inputs[session_inputs.len() - 2].clone()
+        } else {
+            arguments.value
+        };
+
+        if !inputs.add_favorite(&input, data.maybe_db)? {
+            return Ok((format!("'{}' is already a favorite", input), Vec::new()));
+        }
+
+        Ok((format!("Added '{}' to favorites", input), Vec::new()))
+    }
+}
+
+struct UnfavCommand;
+
+impl UnfavCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(UnfavCommand {})
+    }
+}
+
+impl Command for UnfavCommand {
+    fn name(&self) -> &'static str {
+        "unfav"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_inputs.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Undoes /fav");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /unfav input\n\n",
+            "Removes the given input from the favorites.\n",
+            "See also: /fav, /favs"
+        )
+        .to_string();
+        if data.maybe_inputs.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the input history is ",
+                "unavailable."
+            ));
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        if arguments.value.is_empty() {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Expected an input".to_string().into(),
+                arguments.position,
+            )));
+        }
+        let inputs = data
+            .maybe_inputs
+            .ok_or(MissingCapabilityError::NoInputHistory)?;
+
+        if !inputs.remove_favorite(&arguments.value, data.maybe_db)? {
+            return Err(InputError(MaybePositioned::new_positioned(
+                format!("'{}' is not a favorite", arguments.value).into(),
+                arguments.position,
+            )));
+        }
+
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct FavsCommand;
+
+impl FavsCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(FavsCommand {})
+    }
+}
+
+impl Command for FavsCommand {
+    fn name(&self) -> &'static str {
+        "favs"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["favorites"]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_inputs.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Lists the favorite inputs");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /favs\n\n",
+            "Lists the favorite inputs, ordered from least to most recently favorited.\n",
+            "See also: /fav, /unfav"
+        )
+        .to_string();
+        if data.maybe_inputs.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the input history is ",
+                "unavailable."
+            ));
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        if !arguments.value.is_empty() {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Unexpected argument".to_string().into(),
+                arguments.position,
+            )));
+        }
+        let inputs = data
+            .maybe_inputs
+            .ok_or(MissingCapabilityError::NoInputHistory)?;
+
+        if inputs.favorites().is_empty() {
+            return Ok(("There are no favorites".to_string(), Vec::new()));
+        }
+        Ok((inputs.favorites().join("\n"), Vec::new()))
+    }
+}
+
+struct SnippetCommand;
+
+impl SnippetCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(SnippetCommand {})
+    }
+}
+
+impl Command for SnippetCommand {
+    fn name(&self) -> &'static str {
+        "snippet"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_inputs.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Saves and inserts expression templates");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /snippet [save name = template | insert name | delete name]\n\n",
+            "Saves expressions that are used often as named templates. A template can contain ",
+            "placeholders, which are a ? immediately followed by a letter, for the parts that ",
+            "change from use to use. For example:\n",
+            "  /snippet save pyth = sqrt(?a^2 + ?b^2)\n",
+            "\"insert\" replaces the input line with the named template and places the cursor on ",
+            "its first placeholder so that it can be filled in. \"delete\" removes the named ",
+            "snippet. If no arguments are given, the saved snippets are listed.\n",
+            "Snippet names must start with a letter and contain only letters, digits, and ",
+            "underscores. Saving a snippet with the same name as an existing one replaces it. If ",
+            "the on-disk database is available, snippets are saved there.",
+        )
+        .to_string();
+        if data.maybe_inputs.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the input history is ",
+                "unavailable."
+            ));
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let inputs = data
+            .maybe_inputs
+            .ok_or(MissingCapabilityError::NoInputHistory)?;
+
+        let trimmed = arguments.value.trim_start();
+        let action_start = arguments.position.start + arguments.value.len() - trimmed.len();
+        let (action, rest) = trimmed
+            .split_once(|c: char| c.is_ascii_whitespace())
+            .unwrap_or((trimmed, ""));
+        let rest_start = action_start + trimmed.len() - rest.len();
+        let (name, template) = match rest.split_once('=') {
+            Some((name, template)) => (name.trim(), Some(template.trim())),
+            None => (rest.trim(), None),
+        };
+        let name_position = Position {
+            start: rest_start + rest.len() - rest.trim_start().len(),
+            width: name.len(),
+        };
+        let action_position = Position {
+            start: action_start,
+            width: action.len(),
+        };
+
+        if action.is_empty() {
+            if inputs.snippets().is_empty() {
+                return Ok(("There are no snippets".to_string(), Vec::new()));
+            }
+            let lines: Vec<String> = inputs
+                .snippets()
+                .iter()
+                .map(|(name, template)| format!("{} = {}", name, template))
+                .collect();
+            return Ok((lines.join("\n"), Vec::new()));
+        }
+
+        let is_valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_valid_name {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Expected a snippet name".to_string().into(),
+                name_position,
+            )));
+        }
+
+        match (action, template) {
+            ("save", Some(template)) => {
+                if template.is_empty() {
+                    return Err(InputError(MaybePositioned::new_positioned(
+                        "Expected a template after '='".to_string().into(),
+                        arguments.position,
+                    )));
+                }
+                inputs.set_snippet(name, template, data.maybe_db)?;
+                Ok(("Done".to_string(), Vec::new()))
+            }
+            ("save", None) => Err(InputError(MaybePositioned::new_positioned(
+                "Expected '=' after the snippet name".to_string().into(),
+                name_position,
+            ))),
+            ("insert", None) => {
+                if !inputs.insert_snippet(name) {
+                    return Err(InputError(MaybePositioned::new_positioned(
+                        format!("No snippet named '{}' has been saved", name).into(),
+                        name_position,
+                    )));
+                }
+                Ok((String::new(), Vec::new()))
+            }
+            ("delete", None) => {
+                if !inputs.remove_snippet(name, data.maybe_db)? {
+                    return Err(InputError(MaybePositioned::new_positioned(
+                        format!("No snippet named '{}' has been saved", name).into(),
+                        name_position,
+                    )));
+                }
+                Ok(("Done".to_string(), Vec::new()))
+            }
+            ("insert" | "delete", Some(_)) => Err(InputError(MaybePositioned::new_positioned(
+                "Unexpected argument".to_string().into(),
+                arguments.position,
+            ))),
+            _ => Err(InputError(MaybePositioned::new_positioned(
+                format!("Unknown action: '{}'", action).into(),
+                action_position,
+            ))),
+        }
+    }
+}
+
+struct ProfileCommand;
+
+impl ProfileCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(ProfileCommand {})
+    }
+}
+
+impl Command for ProfileCommand {
+    fn name(&self) -> &'static str {
+        "profile"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, data: &DataForCommands) -> String {
+        let mut output = String::new();
+        if data.maybe_db.is_none() {
+            output.push_str("(unavailable) ");
+        }
+        output.push_str("Get/Set the profile used for the on-disk history and variables");
+
+        output
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /profile [name]\n\n",
+            "Each profile has its own on-disk input history, variable history, and saved ",
+            "settings. If a name is provided, bcalc switches to that profile, creating it if it ",
+            "does not yet exist. Switching profiles clears the variables and input history of the ",
+            "current session, but leaves the current settings unchanged. If a name is not ",
+            "provided, the current profile is printed.\n",
+            "The profile used when bcalc starts can be chosen via the --profile argument. The ",
+            "profile named \"default\" is the one used when no profile is given.\n",
+            "Profile names may only contain letters, numbers, underscores, and dashes.",
+        )
+        .to_string();
+        if data.maybe_db.is_none() {
+            output.push_str(concat!(
+                "\n\nThis command is currently unavailable because the on-disk database is ",
+                "unavailable."
+            ));
+        }
+
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        let db = data.maybe_db.ok_or(MissingCapabilityError::NoDatabase)?;
+
+        if arguments.value.is_empty() {
+            return Ok((db.profile().to_string(), Vec::new()));
+        }
+
+        validate_profile_name(&arguments.value).map_err(|s| {
+            InputError(MaybePositioned::new_positioned(
+                s.into(),
+                arguments.position,
+            ))
+        })?;
+
+        // `SavedData::open` only returns `None` if the environment isn't set up for a database, in
+        // which case we wouldn't have been able to get this far.
+        db.replace(
+            SavedData::open(Some(&arguments.value))?.ok_or(MissingCapabilityError::NoDatabase)?,
+        )?;
+        if let Some(inputs) = data.maybe_inputs {
+            *inputs = InputHistory::new(true);
+            inputs.load_favorites(db)?;
+        }
+        if let Some(vars) = data.maybe_vars {
+            *vars = VariableStore::new();
+        }
+        load_custom_units(data.units, db)?;
+
+        Ok((
+            db.take_rebuild_report()
+                .unwrap_or_else(|| "Done".to_string()),
+            Vec::new(),
+        ))
+    }
+}
+
+struct FractionalCommand;
+
+impl FractionalCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(FractionalCommand {})
+    }
+}
+
+impl Command for FractionalCommand {
+    fn name(&self) -> &'static str {
+        "fractional"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["f"]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets fractional display setting".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /fractional [enabled]\n",
+            "Alias: /f\n\n",
+            "If the enabled value is \"true\", non-integer numbers will be output as fractions, ",
+            "like 7/3. If the value is \"mixed\", they will be output as mixed numbers, like ",
+            "2 1/3. If the value is \"both\", they will be output as fractions followed by their ",
+            "decimal values, like 7/3 (2.33333). If the value is \"false\", non-integer numbers ",
+            "will be output as decimals.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "Besides \"mixed\" and \"both\", the value given can be a boolean, which can be ",
+            "represented as \"true\", \"false\", \"t\", or \"f\".",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            let setting = match data.args.fraction_style {
+                _ if !data.args.fractional => "false",
+                FractionStyle::Improper => "true",
+                FractionStyle::Mixed => "mixed",
+                FractionStyle::Both => "both",
+            };
+            return Ok((setting.to_string(), Vec::new()));
+        }
+
+        let (value, style) = match arg_string {
+            "f" | "false" => (false, data.args.fraction_style),
+            "t" | "true" => (true, FractionStyle::Improper),
+            "mixed" => (true, FractionStyle::Mixed),
+            "both" => (true, FractionStyle::Both),
+            _ => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Invalid argument".to_string().into(),
+                    arguments.position,
+                )));
+            }
+        };
+
+        data.args.fractional = value;
+        data.args.fraction_style = style;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::Fractional, Some(value.into()))?;
+            db.set_setting(SavedSetting::FractionStyle, Some(style.to_saved_value()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct MaxDenominatorCommand;
+
+impl MaxDenominatorCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(MaxDenominatorCommand {})
+    }
+}
+
+impl Command for MaxDenominatorCommand {
+    fn name(&self) -> &'static str {
+        "maxdenominator"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets the largest denominator shown in fractional output".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /maxdenominator [value]\n\n",
+            "When fractional display is enabled, values whose fully reduced denominators are ",
+            "larger than this value are output as decimals instead.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given can be \"none\" or a positive integer.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        // "none" is a valid input, but won't be tokenized successfully. So handle that possibility
+        // first.
+        if arguments.value.to_lowercase().trim() == "none" {
+            data.args.max_denominator = None;
+            if let Some(db) = data.maybe_db {
+                db.set_setting(SavedSetting::MaxDenominator, None)?;
+            }
+            return Ok(("Done".to_string(), Vec::new()));
+        }
+
+        let mut parsed_args = data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
+        let input: Option<i64> = if parsed_args.is_empty() {
+            None
+        } else if parsed_args.len() == 1 {
+            let integer = parsed_args.pop().unwrap();
+            if integer.value < 1 {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Maximum denominator must be at least 1".to_string().into(),
+                    integer.position,
+                )));
+            }
+            Some(integer.value)
+        } else {
+            let last_arg = parsed_args.pop().unwrap();
+            let first_arg = parsed_args.into_iter().next().unwrap();
+            return Err(InputError(MaybePositioned::new_span(
+                "Too many arguments".to_string().into(),
+                first_arg.position,
+                last_arg.position,
+            )));
+        };
+
+        match input {
+            Some(value) => {
+                data.args.max_denominator = Some(value.try_into().unwrap());
+                if let Some(db) = data.maybe_db {
+                    db.set_setting(SavedSetting::MaxDenominator, Some(value))?;
+                }
+                Ok(("Done".to_string(), Vec::new()))
+            }
+            None => match data.args.max_denominator {
+                Some(max_denominator) => Ok((format!("{}", max_denominator), Vec::new())),
+                None => Ok(("None".to_string(), Vec::new())),
+            },
+        }
+    }
+}
+
+struct FactoredCommand;
+
+impl FactoredCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(FactoredCommand {})
+    }
+}
+
+impl Command for FactoredCommand {
+    fn name(&self) -> &'static str {
+        "factored"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets factored denominator display setting".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /factored [enabled]\n\n",
+            "If the enabled value is \"true\", the denominators of fractional output will be ",
+            "shown as products of prime powers, like 35/(2^2*3). If the value is \"false\", ",
+            "they will be shown as plain integers.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given should be a boolean, which can be represented as \"true\", ",
+            "\"false\", \"t\", or \"f\".",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            return Ok((format!("{}", data.args.factored), Vec::new()));
+        }
+
+        let value = if arg_string == "f" || arg_string == "false" {
+            false
+        } else if arg_string == "t" || arg_string == "true" {
+            true
+        } else {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Invalid argument".to_string().into(),
+                arguments.position,
+            )));
+        };
+
+        data.args.factored = value;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::Factored, Some(value.into()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct RadixCommand;
+
+impl RadixCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(RadixCommand {})
+    }
+}
+
+impl Command for RadixCommand {
+    fn name(&self) -> &'static str {
+        "radix"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["r"]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets the current radix".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /radix [value]\n",
+            "Alias: /r\n\n",
+            "Value represents the radix used to parse and output numbers.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given should be an integer between 2 and 36 (inclusive). Digits above 9 ",
+            "are the letters a to z.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let mut parsed_args = data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
+        let input: Option<u8> = if parsed_args.is_empty() {
+            None
+        } else if parsed_args.len() == 1 {
+            let integer = parsed_args.pop().unwrap();
+            if integer.value < 2 {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Radix cannot be less than 2".to_string().into(),
+                    integer.position,
+                )));
+            }
+            if integer.value > i64::from(MAX_RADIX) {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    format!("Radix cannot be greater than {}", MAX_RADIX).into(),
+                    integer.position,
+                )));
+            }
+            Some(integer.value.try_into().unwrap())
+        } else {
+            let last_arg = parsed_args.pop().unwrap();
+            let first_arg = parsed_args.into_iter().next().unwrap();
+            return Err(InputError(MaybePositioned::new_span(
+                "Too many arguments".to_string().into(),
+                first_arg.position,
+                last_arg.position,
+            )));
+        };
+
+        match input {
+            Some(value) => {
+                data.args.radix = value;
+                if let Some(db) = data.maybe_db {
+                    db.set_setting(SavedSetting::Radix, Some(value.into()))?;
+                }
+                Ok(("Done".to_string(), Vec::new()))
+            }
+            None => Ok((format!("{}", data.args.radix), Vec::new())),
+        }
+    }
+}
+
+struct ConvertToRadixCommand;
+
+impl ConvertToRadixCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(ConvertToRadixCommand {})
+    }
+}
+
+impl Command for ConvertToRadixCommand {
+    fn name(&self) -> &'static str {
+        "converttoradix"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets the current output radix".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /converttoradix [value]\n\n",
+            "Value overrides the radix used to output numbers.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given can be \"none\" or an integer between 2 and 36 (inclusive).",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        // "none" is a valid input, but won't be tokenized successfully. So handle that possibility
+        // first.
+        if arguments.value.to_lowercase().trim() == "none" {
+            data.args.convert_to_radix = None;
+            if let Some(db) = data.maybe_db {
+                db.set_setting(SavedSetting::ConvertToRadix, None)?;
+            }
+            return Ok(("Done".to_string(), Vec::new()));
+        }
+
+        let mut parsed_args = data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
+        let input: Option<u8> = if parsed_args.is_empty() {
+            None
+        } else if parsed_args.len() == 1 {
+            let integer = parsed_args.pop().unwrap();
+            if integer.value < 2 {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Radix cannot be less than 2".to_string().into(),
+                    integer.position,
+                )));
+            }
+            if integer.value > i64::from(MAX_RADIX) {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    format!("Radix cannot be greater than {}", MAX_RADIX).into(),
+                    integer.position,
+                )));
+            }
+            Some(integer.value.try_into().unwrap())
+        } else {
+            let last_arg = parsed_args.pop().unwrap();
+            let first_arg = parsed_args.into_iter().next().unwrap();
+            return Err(InputError(MaybePositioned::new_span(
+                "Too many arguments".to_string().into(),
+                first_arg.position,
+                last_arg.position,
+            )));
+        };
+
+        match input {
+            Some(value) => {
+                data.args.convert_to_radix = Some(value);
+                if let Some(db) = data.maybe_db {
+                    db.set_setting(SavedSetting::ConvertToRadix, Some(value.into()))?;
+                }
+                Ok(("Done".to_string(), Vec::new()))
+            }
+            None => match data.args.convert_to_radix {
+                Some(radix) => Ok((format!("{}", radix), Vec::new())),
+                None => Ok(("None".to_string(), Vec::new())),
+            },
+        }
+    }
+}
+
+struct UpperCommand;
+
+impl UpperCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(UpperCommand {})
+    }
+}
+
+impl Command for UpperCommand {
+    fn name(&self) -> &'static str {
+        "upper"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets upper display setting".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /upper [enabled]\n\n",
+            "If the enabled value is \"true\", digits above 9 will be output in uppercase. If ",
+            "\"false\", they will be output in lowercase.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given should be a boolean, which can be represented as \"true\", ",
+            "\"false\", \"t\", or \"f\".",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            return Ok((format!("{}", data.args.upper), Vec::new()));
+        }
+
+        let value = if arg_string == "f" || arg_string == "false" {
+            false
+        } else if arg_string == "t" || arg_string == "true" {
+            true
+        } else {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Invalid argument".to_string().into(),
+                arguments.position,
+            )));
+        };
+
+        data.args.upper = value;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::Upper, Some(value.into()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct BasesCommand;
+
+impl BasesCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(BasesCommand {})
+    }
+}
+
+impl Command for BasesCommand {
+    fn name(&self) -> &'static str {
+        "bases"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets whether results are shown in several radices at once".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /bases [enabled]\n\n",
+            "If the enabled value is \"true\", results without units will be output in decimal, ",
+            "hexadecimal, octal, and binary on one line, like \"255  0xff  0o377  0b11111111\". ",
+            "This takes the place of the output radix and of fractional and scientific output. ",
+            "If \"false\", results will be output as usual.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given should be a boolean, which can be represented as \"true\", ",
+            "\"false\", \"t\", or \"f\".",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            return Ok((format!("{}", data.args.bases), Vec::new()));
+        }
+
+        let value = if arg_string == "f" || arg_string == "false" {
+            false
+        } else if arg_string == "t" || arg_string == "true" {
+            true
+        } else {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Invalid argument".to_string().into(),
+                arguments.position,
+            )));
+        };
+
+        data.args.bases = value;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::Bases, Some(value.into()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct RecallCommandsCommand;
+
+impl RecallCommandsCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(RecallCommandsCommand {})
+    }
+}
+
+impl Command for RecallCommandsCommand {
+    fn name(&self) -> &'static str {
+        "recallcommands"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets whether the arrow keys recall commands".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /recallcommands [enabled]\n\n",
+            "If the enabled value is \"true\", the up and down arrow keys scroll through commands ",
+            "in the input history as well as expressions. If the value is \"false\", commands ",
+            "are skipped. Either way, holding Alt while using the up and down arrow keys scrolls ",
+            "through only the commands.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given should be a boolean, which can be represented as \"true\", ",
+            "\"false\", \"t\", or \"f\".",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            return Ok((format!("{}", data.args.recall_commands), Vec::new()));
+        }
+
+        let value = if arg_string == "f" || arg_string == "false" {
+            false
+        } else if arg_string == "t" || arg_string == "true" {
+            true
+        } else {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Invalid argument".to_string().into(),
+                arguments.position,
+            )));
+        };
+
+        data.args.recall_commands = value;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::RecallCommands, Some(value.into()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct CloseParensCommand;
+
+impl CloseParensCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(CloseParensCommand {})
+    }
+}
+
+impl Command for CloseParensCommand {
+    fn name(&self) -> &'static str {
+        "closeparens"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets how missing close parentheses are handled".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /closeparens [value]\n\n",
+            "Controls what happens when Enter is pressed on an input that is only missing close ",
+            "parentheses at its end, like \"2 * (3 + max(4, 5\".\n",
+            "If the value is \"offer\", which is the default, the missing parentheses are added to ",
+            "the input line, and pressing Enter again evaluates it. If the value is \"auto\", the ",
+            "missing parentheses are assumed and the input is evaluated right away, with a ",
+            "warning saying how many were assumed. If the value is \"off\", the input is rejected ",
+            "like any other mismatched parenthesis.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            return Ok((data.args.close_parens.name().to_string(), Vec::new()));
+        }
+
+        let value = match arg_string {
+            "off" => CloseParens::Off,
+            "offer" => CloseParens::Offer,
+            "auto" => CloseParens::Auto,
+            _ => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Invalid argument".to_string().into(),
+                    arguments.position,
+                )));
+            }
+        };
+
+        data.args.close_parens = value;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::CloseParens, Some(value.to_saved_value()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct ErrorAlertCommand;
+
+impl ErrorAlertCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(ErrorAlertCommand {})
+    }
+}
+
+impl Command for ErrorAlertCommand {
+    fn name(&self) -> &'static str {
+        "erroralert"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets how input errors are made noticeable".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /erroralert [value]\n\n",
+            "If the value is \"bell\", the terminal bell is sounded when an input has an error. ",
+            "If the value is \"flash\", the input line briefly flashes instead. If the value is ",
+            "\"none\", which is the default, errors are only shown by their messages.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            return Ok((data.args.error_alert.name().to_string(), Vec::new()));
+        }
+
+        let value = match arg_string {
+            "none" => ErrorAlert::None,
+            "bell" => ErrorAlert::Bell,
+            "flash" => ErrorAlert::Flash,
+            _ => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Invalid argument".to_string().into(),
+                    arguments.position,
+                )));
+            }
+        };
+
+        data.args.error_alert = value;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::ErrorAlert, Some(value.to_saved_value()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct StrictCommand;
+
+impl StrictCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(StrictCommand {})
+    }
+}
+
+impl Command for StrictCommand {
+    fn name(&self) -> &'static str {
+        "strict"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets whether ambiguous input conveniences are rejected".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /strict [enabled]\n\n",
+            "If the enabled value is \"true\", conveniences that make input ambiguous are ",
+            "rejected with an error rather than assumed: functions must be called with ",
+            "parentheses, like sqrt(4) rather than sqrt 4, and inputs that are missing close ",
+            "parentheses are rejected regardless of the /closeparens setting. Implicit ",
+            "multiplication, like 2(3), is never accepted.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given should be a boolean, which can be represented as \"true\", ",
+            "\"false\", \"t\", or \"f\".",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            return Ok((format!("{}", data.args.strict), Vec::new()));
+        }
+
+        let value = if arg_string == "f" || arg_string == "false" {
+            false
+        } else if arg_string == "t" || arg_string == "true" {
+            true
+        } else {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Invalid argument".to_string().into(),
+                arguments.position,
+            )));
+        };
+
+        data.args.strict = value;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::Strict, Some(value.into()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct SiSuffixesCommand;
+
+impl SiSuffixesCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(SiSuffixesCommand {})
+    }
+}
+
+impl Command for SiSuffixesCommand {
+    fn name(&self) -> &'static str {
+        "sisuffixes"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets whether numbers can be written with SI suffixes".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /sisuffixes [enabled]\n\n",
+            "If the enabled value is \"true\", numbers can be written with SI suffixes, like 4.7k ",
+            "for 4700 or 10u for 0.00001. The suffixes are P, T, G, M, k, u, n, and p. A number ",
+            "followed by m is a number of minutes, so milli is left out unless the value is ",
+            "\"milli\", which also makes m after a number mean milli, like 4.7m for 0.0047. ",
+            "Minutes can then be written as min. Turning this off makes a number written with a ",
+            "stray suffix an error instead.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given should be \"milli\" or a boolean, which can be represented as ",
+            "\"true\", \"false\", \"t\", or \"f\".",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            let output = if data.args.no_si_suffixes {
+                "false"
+            } else if data.args.milli_suffix {
+                "milli"
+            } else {
+                "true"
+            };
+            return Ok((output.to_string(), Vec::new()));
+        }
+
+        let (value, milli) = if arg_string == "f" || arg_string == "false" {
+            (false, false)
+        } else if arg_string == "t" || arg_string == "true" {
+            (true, false)
+        } else if arg_string == "milli" {
+            (true, true)
+        } else {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Invalid argument".to_string().into(),
+                arguments.position,
+            )));
+        };
+
+        data.args.no_si_suffixes = !value;
+        data.args.milli_suffix = milli;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::NoSiSuffixes, Some((!value).into()))?;
+            db.set_setting(SavedSetting::MilliSuffix, Some(milli.into()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct AngleCommand;
+
+impl AngleCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(AngleCommand {})
+    }
+}
+
+impl Command for AngleCommand {
+    fn name(&self) -> &'static str {
+        "angle"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets whether trigonometric functions use radians or degrees".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /angle [value]\n\n",
+            "Controls the unit that trigonometric functions like sin take plain numbers to be ",
+            "angles in, and that inverse trigonometric functions like asin give angles in. The ",
+            "value can be \"radians\", which is the default, or \"degrees\" (\"rad\" and \"deg\" ",
+            "are accepted too). Values with angle units, like \"30 deg\", are always converted as ",
+            "needed, regardless of this setting.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            return Ok((data.args.angle_unit.name().to_string(), Vec::new()));
+        }
+
+        let value = match arg_string {
+            "radians" | "rad" => AngleUnit::Radians,
+            "degrees" | "deg" => AngleUnit::Degrees,
+            _ => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Invalid argument".to_string().into(),
+                    arguments.position,
+                )));
+            }
+        };
+
+        data.args.angle_unit = value;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::AngleUnit, Some(value.to_saved_value()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct CommaCommand;
+
+impl CommaCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(CommaCommand {})
+    }
+}
+
+impl Command for CommaCommand {
+    fn name(&self) -> &'static str {
+        "commas"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["comma"]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets comma display setting".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /commas [enabled]\n",
+            "Alias: /comma\n\n",
+            "If the enabled value is \"true\", commas will be used to separate groups of digits ",
+            "when outputting numbers. By default, groups are 3 digits long (see /groupsize).\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given should be a boolean, which can be represented as \"true\", ",
+            "\"false\", \"t\", or \"f\".",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            return Ok((format!("{}", data.args.commas), Vec::new()));
+        }
+
+        let value = if arg_string == "f" || arg_string == "false" {
+            false
+        } else if arg_string == "t" || arg_string == "true" {
+            true
+        } else {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Invalid argument".to_string().into(),
+                arguments.position,
+            )));
+        };
+
+        data.args.commas = value;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::Commas, Some(value.into()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct GroupSizeCommand;
+
+impl GroupSizeCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(GroupSizeCommand {})
+    }
+}
+
+impl Command for GroupSizeCommand {
+    fn name(&self) -> &'static str {
+        "groupsize"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets the number of digits between commas".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /groupsize [size]\n\n",
+            "Sets how many digits are put between each comma when commas are enabled (see ",
+            "/commas). The default is 3, as in \"1,000,000\". A size of 4 is useful for ",
+            "hexadecimal and binary output, where each group of digits is then 16 or 4 bits.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The size must be between 1 and 16."
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let mut parsed_args = data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
+        if parsed_args.is_empty() {
+            return Ok((data.args.group_size.to_string(), Vec::new()));
+        }
+        if parsed_args.len() > 1 {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Expected a single group size".to_string().into(),
+                parsed_args[1].position,
+            )));
+        }
+        let size_arg = parsed_args.remove(0);
+        let group_size = match u8::try_from(size_arg.value) {
+            Ok(size) if (1..=16).contains(&size) => size,
+            _ => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Group size must be between 1 and 16".to_string().into(),
+                    size_arg.position,
+                )))
+            }
+        };
+
+        data.args.group_size = group_size;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::GroupSize, Some(group_size.into()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct GroupSepCommand;
+
+impl GroupSepCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(GroupSepCommand {})
+    }
+}
+
+impl Command for GroupSepCommand {
+    fn name(&self) -> &'static str {
+        "groupsep"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets the character and size of digit groups".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /groupsep [separator [size]]\n\n",
+            "Sets the character that separates groups of digits when commas are enabled (see ",
+            "/commas), and optionally how many digits are in each group (see /groupsize). The ",
+            "separator can be \"comma\" (or \",\"), which is the default, \"underscore\" (or ",
+            "\"_\"), \"space\", or \"period\" (or \".\"). If the separator is the same as the ",
+            "decimal separator, the other of comma and period is used instead. For example, ",
+            "\"/groupsep _ 4\" shows 0xdeadbeef as dead_beef in hexadecimal. In radices other ",
+            "than 10, the digits after the radix point are grouped too.\n",
+            "If no value is provided, the current separator and group size are displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let mut words = arg_lower.split_whitespace();
+        let separator = match words.next() {
+            None => {
+                let setting = format!(
+                    "{} {}",
+                    data.args.group_separator.name(),
+                    data.args.group_size
+                );
+                return Ok((setting, Vec::new()));
+            }
+            Some("comma" | ",") => GroupSeparator::Comma,
+            Some("underscore" | "_") => GroupSeparator::Underscore,
+            Some("space") => GroupSeparator::Space,
+            Some("period" | ".") => GroupSeparator::Period,
+            Some(_) => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Invalid separator".to_string().into(),
+                    arguments.position,
+                )));
+            }
+        };
+        let maybe_group_size = match (words.next(), words.next()) {
+            (None, _) => None,
+            (Some(size), None) => match size.parse::<u8>() {
+                Ok(size) if (1..=16).contains(&size) => Some(size),
+                _ => {
+                    return Err(InputError(MaybePositioned::new_positioned(
+                        "Group size must be between 1 and 16".to_string().into(),
+                        arguments.position,
+                    )));
+                }
+            },
+            (Some(_), Some(_)) => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Too many arguments".to_string().into(),
+                    arguments.position,
+                )));
+            }
+        };
+
+        data.args.group_separator = separator;
+        if let Some(group_size) = maybe_group_size {
+            data.args.group_size = group_size;
+        }
+        if let Some(db) = data.maybe_db {
+            db.set_setting(
+                SavedSetting::GroupSeparator,
+                Some(separator.to_saved_value()),
+            )?;
+            if let Some(group_size) = maybe_group_size {
+                db.set_setting(SavedSetting::GroupSize, Some(group_size.into()))?;
+            }
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct DecimalCommaCommand;
+
+impl DecimalCommaCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(DecimalCommaCommand {})
+    }
+}
+
+impl Command for DecimalCommaCommand {
+    fn name(&self) -> &'static str {
+        "decimalcomma"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets whether numbers use a decimal comma".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /decimalcomma [enabled]\n\n",
+            "If the enabled value is \"true\", numbers are written with a comma between their ",
+            "whole and fractional parts, like 1,5, in both input and output. In input, groups of ",
+            "three digits can be separated by dots or spaces, like 1.234.567,89 or 1 234 567,89, ",
+            "and function arguments are separated by semicolons, like max(1,5; 2). When commas ",
+            "are enabled (see /commas), output digits are grouped with dots instead.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given should be a boolean, which can be represented as \"true\", ",
+            "\"false\", \"t\", or \"f\".",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            return Ok((format!("{}", data.args.decimal_comma), Vec::new()));
+        }
+
+        let value = if arg_string == "f" || arg_string == "false" {
+            false
+        } else if arg_string == "t" || arg_string == "true" {
+            true
+        } else {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Invalid argument".to_string().into(),
+                arguments.position,
+            )));
+        };
+
+        data.args.decimal_comma = value;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::DecimalComma, Some(value.into()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct PrecisionCommand;
+
+impl PrecisionCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(PrecisionCommand {})
+    }
+}
+
+impl Command for PrecisionCommand {
+    fn name(&self) -> &'static str {
+        "precision"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["p"]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets the current precision".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /precision [value [extra]]\n",
+            "Alias: /p\n\n",
+            "The value represents the maximum number of digits that are displayed after the ",
+            "decimal point when outputting numbers.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given should be representable as an 8-bit unsigned integer.\n",
+            "If extra is given, it should also be representable as an 8-bit unsigned integer.\n",
+            "This will represent the additional precision that is stored internally but not displayed.\n",
+            "This is only really relevant for operations that cannot be done with infinite precision.\n",
+            "For example: sqrt(2)\n",
+            "value + extra must also be representable as an 8-bit unsigned integer."
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let mut parsed_args = data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
+        let input: Option<(u8, u8)> = if parsed_args.is_empty() {
+            None
+        } else if parsed_args.len() <= 2 {
+            let mut parsed_args_iter = parsed_args.into_iter();
+            let precision_raw = parsed_args_iter.next().unwrap();
+            let precision: u8 = precision_raw.value.try_into().map_err(|_| {
+                InputError(MaybePositioned::new_positioned(
+                    "Precision must be representable as an 8-bit unsigned integer"
+                        .to_string()
+                        .into(),
+                    precision_raw.position,
+                ))
+            })?;
+            let maybe_extra = parsed_args_iter.next();
+            let extra: u8 = match &maybe_extra {
+                None => data.args.extra_precision,
+                Some(extra_raw) => extra_raw.value.try_into().map_err(|_| {
+                    InputError(MaybePositioned::new_positioned(
+                        "Extra must be representable as an 8-bit unsigned integer"
+                            .to_string()
+                            .into(),
+                        extra_raw.position,
+                    ))
+                })?,
+            };
+
+            if precision.checked_add(extra).is_none() {
+                let position = match maybe_extra {
+                    None => precision_raw.position,
+                    Some(extra_raw) => {
+                        Position::from_span(precision_raw.position, extra_raw.position)
+                    }
+                };
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Sum of precision and extra must be representable as an 8-bit unsigned integer"
+                        .to_string()
+                        .into(),
+                    position,
+                )));
+            }
+
+            Some((precision, extra))
+        } else {
+            let last_arg = parsed_args.pop().unwrap();
+            let first_arg = parsed_args.into_iter().next().unwrap();
+            return Err(InputError(MaybePositioned::new_span(
+                "Too many arguments".to_string().into(),
+                first_arg.position,
+                last_arg.position,
+            )));
+        };
+
+        match input {
+            Some((precision, extra)) => {
+                data.args.precision = precision;
+                data.args.extra_precision = extra;
+                if let Some(db) = data.maybe_db {
+                    db.set_setting(SavedSetting::Precision, Some(precision.into()))?;
+                    db.set_setting(SavedSetting::ExtraPrecision, Some(extra.into()))?;
+                }
+                Ok(("Done".to_string(), Vec::new()))
+            }
+            None => Ok((
+                format!(
+                    "Precision = {}\nExtra Precision = {}",
+                    data.args.precision, data.args.extra_precision
+                ),
+                Vec::new(),
+            )),
+        }
+    }
+}
+
+struct SciAboveCommand;
+
+impl SciAboveCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(SciAboveCommand {})
+    }
+}
+
+impl Command for SciAboveCommand {
+    fn name(&self) -> &'static str {
+        "sciabove"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets when large values are shown in scientific notation".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /sciabove [value]\n\n",
+            "Values with more than this many digits before the decimal point are output in ",
+            "scientific notation, like 1.23457e20, rather than being written out in full.\n",
+            "Scientific notation is only used for decimal output.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given can be \"none\" or a positive integer.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        // "none" is a valid input, but won't be tokenized successfully. So handle that possibility
+        // first.
+        if arguments.value.to_lowercase().trim() == "none" {
+            data.args.sci_above = None;
+            if let Some(db) = data.maybe_db {
+                db.set_setting(SavedSetting::SciAbove, None)?;
+            }
+            return Ok(("Done".to_string(), Vec::new()));
+        }
+
+        let mut parsed_args = data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
+        let input: Option<u16> = if parsed_args.is_empty() {
+            None
+        } else if parsed_args.len() == 1 {
+            let integer = parsed_args.pop().unwrap();
+            if integer.value < 1 {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Value must be at least 1".to_string().into(),
+                    integer.position,
+                )));
+            }
+            if integer.value > i64::from(u16::MAX) {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    format!("Value cannot be greater than {}", u16::MAX).into(),
+                    integer.position,
+                )));
+            }
+            Some(integer.value.try_into().unwrap())
+        } else {
+            let last_arg = parsed_args.pop().unwrap();
+            let first_arg = parsed_args.into_iter().next().unwrap();
+            return Err(InputError(MaybePositioned::new_span(
+                "Too many arguments".to_string().into(),
+                first_arg.position,
+                last_arg.position,
+            )));
+        };
+
+        match input {
+            Some(value) => {
+                data.args.sci_above = Some(value);
+                if let Some(db) = data.maybe_db {
+                    db.set_setting(SavedSetting::SciAbove, Some(value.into()))?;
+                }
+                Ok(("Done".to_string(), Vec::new()))
+            }
+            None => match data.args.sci_above {
+                Some(value) => Ok((format!("{}", value), Vec::new())),
+                None => Ok(("None".to_string(), Vec::new())),
+            },
+        }
+    }
+}
+
+struct SciBelowCommand;
+
+impl SciBelowCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(SciBelowCommand {})
+    }
+}
+
+impl Command for SciBelowCommand {
+    fn name(&self) -> &'static str {
+        "scibelow"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets when small values are shown in scientific notation".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /scibelow [value]\n\n",
+            "Nonzero values smaller in magnitude than 10^-value are output in scientific ",
+            "notation, like 1.5e-7, rather than being rounded to something like 0.00000.\n",
+            "Scientific notation is only used for decimal output.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.\n",
+            "The value given can be \"none\" or a positive integer.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        // "none" is a valid input, but won't be tokenized successfully. So handle that possibility
+        // first.
+        if arguments.value.to_lowercase().trim() == "none" {
+            data.args.sci_below = None;
+            if let Some(db) = data.maybe_db {
+                db.set_setting(SavedSetting::SciBelow, None)?;
+            }
+            return Ok(("Done".to_string(), Vec::new()));
+        }
+
+        let mut parsed_args = data.tokenizer.tokenize_int_list(&arguments.value, 10)?;
+        let input: Option<u16> = if parsed_args.is_empty() {
+            None
+        } else if parsed_args.len() == 1 {
+            let integer = parsed_args.pop().unwrap();
+            if integer.value < 1 {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Value must be at least 1".to_string().into(),
+                    integer.position,
+                )));
+            }
+            if integer.value > i64::from(u16::MAX) {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    format!("Value cannot be greater than {}", u16::MAX).into(),
+                    integer.position,
+                )));
+            }
+            Some(integer.value.try_into().unwrap())
+        } else {
+            let last_arg = parsed_args.pop().unwrap();
+            let first_arg = parsed_args.into_iter().next().unwrap();
+            return Err(InputError(MaybePositioned::new_span(
+                "Too many arguments".to_string().into(),
+                first_arg.position,
+                last_arg.position,
+            )));
+        };
+
+        match input {
+            Some(value) => {
+                data.args.sci_below = Some(value);
+                if let Some(db) = data.maybe_db {
+                    db.set_setting(SavedSetting::SciBelow, Some(value.into()))?;
+                }
+                Ok(("Done".to_string(), Vec::new()))
+            }
+            None => match data.args.sci_below {
+                Some(value) => Ok((format!("{}", value), Vec::new())),
+                None => Ok(("None".to_string(), Vec::new())),
+            },
+        }
+    }
+}
+
+struct NotationCommand;
+
+impl NotationCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(NotationCommand {})
+    }
+}
+
+impl Command for NotationCommand {
+    fn name(&self) -> &'static str {
+        "notation"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Retrieves or sets whether decimal output is in scientific or engineering notation"
+            .to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /notation [value]\n\n",
+            "If the value is \"scientific\", decimal output is always in scientific notation, ",
+            "like 1.2345e9. If the value is \"engineering\", it is always in engineering ",
+            "notation, where the exponent is a multiple of 3, like 123.45e-6. If the value is ",
+            "\"auto\", which is the default, decimal output only switches to scientific notation ",
+            "as /sciabove and /scibelow say to. \"sci\" and \"eng\" are accepted too. Output in ",
+            "radices other than 10 is never in either notation.\n",
+            "If no value is provided, the current setting value is displayed.\n",
+            "If a value is given, the setting value is updated. If the on-disk database is ",
+            "available, the new value will also be used by future sessions.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let arg_lower = arguments.value.to_lowercase();
+        let arg_string = arg_lower.trim();
+        if arg_string.is_empty() {
+            return Ok((data.args.notation.name().to_string(), Vec::new()));
+        }
+
+        let value = match arg_string {
+            "auto" => Notation::Auto,
+            "scientific" | "sci" => Notation::Scientific,
+            "engineering" | "eng" => Notation::Engineering,
+            _ => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Invalid argument".to_string().into(),
+                    arguments.position,
+                )));
+            }
+        };
+
+        data.args.notation = value;
+        if let Some(db) = data.maybe_db {
+            db.set_setting(SavedSetting::Notation, Some(value.to_saved_value()))?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct ConvertCommand;
+
+impl ConvertCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(ConvertCommand {})
+    }
+}
+
+impl Command for ConvertCommand {
+    fn name(&self) -> &'static str {
+        "convert"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Converts a value from one unit to another".to_string()
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /convert expression from_unit to to_unit\n\n",
+            "Evaluates the expression and converts the result from one unit to another. For ",
+            "example, \"/convert 5 mi to km\". The units must measure the same kind of quantity. ",
+            "The result is output using the current display settings, such as the precision.\n",
+            "Unit names are case sensitive, but a unit can be given in a different case if that ",
+            "doesn't make it ambiguous.\n",
+            "Currencies can be converted once exchange rates have been loaded from a file (see ",
+            "/help rates).\n\n",
+            "Available units:",
+        )
+        .to_string();
+        let mut dimensions = Vec::new();
+        for unit in data.units.units() {
+            if !dimensions.contains(&unit.dimension) {
+                dimensions.push(unit.dimension);
+            }
+        }
+        for dimension in dimensions {
+            let names: Vec<&str> = data
+                .units
+                .units()
+                .iter()
+                .filter(|unit| unit.dimension == dimension)
+                .map(|unit| unit.name())
+                .collect();
+            output.push_str(&format!("\n  {}: {}", dimension, names.join(", ")));
+        }
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        mut data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        // A problem with the rates file only matters if a unit can't be found, since that unit may
+        // have been a currency.
+        let mut maybe_rates_error = load_currency_rates_if_needed(&mut data).err();
+
+        // Each word is paired with its position within the whole input.
+        let words: Vec<Positioned<&str>> = arguments
+            .value
+            .split_ascii_whitespace()
+            .map(|word| {
+                let offset = word.as_ptr() as usize - arguments.value.as_ptr() as usize;
+                Positioned::new_raw(word, arguments.position.start + offset, word.len())
+            })
+            .collect();
+        let (from_word, to_word) = match words.as_slice() {
+            [.., _, from_word, separator, to_word] if separator.value == "to" => {
+                (from_word, to_word)
+            }
+            _ => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Expected an expression, a unit, \"to\", and another unit"
+                        .to_string()
+                        .into(),
+                    arguments.position,
+                )));
+            }
+        };
+
+        let mut find_unit = |word: &Positioned<&str>| match data.units.find(word.value) {
+            Some(unit) => Ok(unit),
+            None => Err(maybe_rates_error.take().unwrap_or_else(|| {
+                InputError(MaybePositioned::new_positioned(
+                    format!("Unknown unit: '{}'", word.value).into(),
+                    word.position,
+                ))
+            })),
+        };
+        let from_unit = find_unit(from_word)?;
+        let to_unit = find_unit(to_word)?;
+        if from_unit.dimension != to_unit.dimension {
+            return Err(InputError(MaybePositioned::new_span(
+                format!(
+                    "Cannot convert {} ({}) to {} ({})",
+                    from_word.value, from_unit.dimension, to_word.value, to_unit.dimension
+                )
+                .into(),
+                from_word.position,
+                to_word.position,
+            )));
+        }
+
+        // The expression is everything before the unit being converted from.
+        let expression_start = words[0].position.start;
+        let expression = arguments.value[expression_start - arguments.position.start
+            ..from_word.position.start - arguments.position.start]
+            .trim_end();
+        let syntax_tree = parse_expression(expression, data.tokenizer, data.args)
+            .map_err(|e| offset_input_error(e, expression_start))?;
+
+        let mut variables_touched: Vec<VariableName> = Vec::new();
+        let mut observer = |step: &TraceStep| {
+            if let NodeKind::Variable(name) = &step.kind {
+                variables_touched.push(name.clone());
+            }
+        };
+        let value = syntax_tree
+            .execute_traced(
+                data.maybe_input_history_id,
+                data.maybe_vars,
+                data.maybe_db.map(|db| db as &mut dyn Storage),
+                data.args,
+                Some(&mut observer),
+            )
+            .map_err(|e| offset_input_error(e, expression_start))?;
+
+        let value = expect_real(
+            value,
+            Position {
+                start: expression_start,
+                width: expression.len(),
+            },
+        )?;
+        let converted = convert(&value, from_unit, to_unit).map_err(|e| RuntimeError(e.into()))?;
+        Ok((
+            format!("{} {}", format_real(&converted, data.args), to_unit.name()),
+            variables_touched,
+        ))
+    }
+}
+
+struct DiffCommand;
+
+impl DiffCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(DiffCommand {})
+    }
+}
+
+impl Command for DiffCommand {
+    fn name(&self) -> &'static str {
+        "diff"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Compares the results of two expressions".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /diff expression ; expression\n\n",
+            "Evaluates both expressions and shows their difference (the first minus the second), ",
+            "their ratio (the first divided by the second), and whether they are exactly equal. ",
+            "Since results are stored as exact ratios, this is a handy way of checking that two ",
+            "ways of writing a calculation really are the same, like:\n",
+            "  /diff ($x + 1)^2 ; $x^2 + 2 * $x + 1\n",
+            "Results that had to be approximated, like square roots, are only equal if their ",
+            "approximations are.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        mut data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let (first, second) = match arguments.value.split_once(';') {
+            Some(expressions) => expressions,
+            None => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Expected two expressions separated by ';'"
+                        .to_string()
+                        .into(),
+                    arguments.position,
+                )));
+            }
+        };
+        let first_start = arguments.position.start;
+        let second_start = first_start + first.len() + 1;
+
+        let mut variables_touched: Vec<VariableName> = Vec::new();
+        let (first_value, first_dimension) =
+            evaluate_argument(first, first_start, &mut data, &mut variables_touched)?;
+        let (second_value, second_dimension) =
+            evaluate_argument(second, second_start, &mut data, &mut variables_touched)?;
+
+        let maybe_dimension = match (first_dimension, second_dimension) {
+            (Some(a), Some(b)) if a != b => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    format!("Cannot compare values in {} and {}", a, b).into(),
+                    arguments.position,
+                )));
+            }
+            (Some(dimension), _) | (_, Some(dimension)) => Some(dimension),
+            (None, None) => None,
+        };
+
+        let difference = &first_value - &second_value;
+        let ratio = if second_value.is_zero() {
+            "undefined".to_string()
+        } else {
+            format_real(&(&first_value / &second_value), data.args)
+        };
+        let lines = [
+            format!(
+                "Difference: {}",
+                format_quantity(&difference.clone().into(), maybe_dimension, data.args)
+            ),
+            format!("Ratio: {}", ratio),
+            format!(
+                "Exactly equal: {}",
+                if difference.is_zero() { "yes" } else { "no" }
+            ),
+        ];
+        Ok((lines.join("\n"), variables_touched))
+    }
+}
+
+struct PlotCommand;
+
+impl PlotCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(PlotCommand {})
+    }
+}
+
+impl Command for PlotCommand {
+    fn name(&self) -> &'static str {
+        "plot"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Draws a graph of an expression in $x".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /plot expression from start to end\n\n",
+            "Draws a graph of the expression as $x goes from the start value to the end value, ",
+            "like:\n",
+            "  /plot $x^2 - 2 from -3 to 3\n",
+            "The graph is sized to fit the terminal. The lowest and highest values reached are ",
+            "shown on the left. Points where the expression can't be evaluated, such as where it ",
+            "would divide by zero, are left out.\n",
+            "$x is only set while the graph is drawn. Any variable that it already has keeps its ",
+            "value.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        mut data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        // Each word is paired with its position within the whole input.
+        let words: Vec<Positioned<&str>> = arguments
+            .value
+            .split_ascii_whitespace()
+            .map(|word| {
+                let offset = word.as_ptr() as usize - arguments.value.as_ptr() as usize;
+                Positioned::new_raw(word, arguments.position.start + offset, word.len())
+            })
+            .collect();
+        let maybe_from_index = words.iter().rposition(|word| word.value == "from");
+        let maybe_to_index = words.iter().rposition(|word| word.value == "to");
+        let (from_index, to_index) = match (maybe_from_index, maybe_to_index) {
+            (Some(from_index), Some(to_index))
+                if from_index > 0 && to_index > from_index + 1 && to_index + 1 < words.len() =>
+            {
+                (from_index, to_index)
+            }
+            _ => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Expected an expression, \"from\", a start value, \"to\", and an end value"
+                        .to_string()
+                        .into(),
+                    arguments.position,
+                )));
+            }
+        };
+        // Returns the part of the input from the start of the first word to the end of the last.
+        let span = |first: usize, last: usize| {
+            let start = words[first].position.start;
+            let end = words[last].position.start + words[last].position.width;
+            let offset = arguments.position.start;
+            (&arguments.value[start - offset..end - offset], start)
+        };
+        let (expression, expression_start) = span(0, from_index - 1);
+        let (start_expression, start_start) = span(from_index + 1, to_index - 1);
+        let (end_expression, end_start) = span(to_index + 1, words.len() - 1);
+
+        let mut variables_touched: Vec<VariableName> = Vec::new();
+        let (start, _) = evaluate_argument(
+            start_expression,
+            start_start,
+            &mut data,
+            &mut variables_touched,
+        )?;
+        let (end, _) =
+            evaluate_argument(end_expression, end_start, &mut data, &mut variables_touched)?;
+        if start >= end {
+            return Err(InputError(MaybePositioned::new_span(
+                "The start value must be less than the end value"
+                    .to_string()
+                    .into(),
+                words[from_index + 1].position,
+                words[words.len() - 1].position,
+            )));
+        }
+        let syntax_tree = parse_expression(expression, data.tokenizer, data.args)
+            .map_err(|e| offset_input_error(e, expression_start))?;
+
+        let (terminal_cols, terminal_rows) = terminal::size().unwrap_or((80, 24));
+        let lines = usize::from(terminal_rows)
+            .saturating_sub(PLOT_RESERVED_ROWS)
+            .clamp(PLOT_MIN_LINES, PLOT_MAX_LINES);
+
+        // The width of the graph depends on how wide the labels are, which depends on the range of
+        // the values. So the expression is sampled at one point per dot across the whole terminal
+        // first, and the graph is then drawn with as many of those as fit.
+        let variable: VariableName = "$x".into();
+        let sample_count = 2 * usize::from(terminal_cols).max(PLOT_MIN_COLS);
+        let step = (&end - &start) / BigInt::from(sample_count - 1);
+        let mut values: Vec<Option<BigRational>> = Vec::new();
+        let mut maybe_first_error = None;
+        for index in 0..sample_count {
+            let x = &start + &step * BigInt::from(index);
+            match syntax_tree
+                .clone()
+                .execute_bound(
+                    &variable,
+                    x,
+                    data.maybe_vars.as_deref_mut(),
+                    data.maybe_db
+                        .as_deref_mut()
+                        .map(|db| db as &mut dyn Storage),
+                    data.args,
+                )
+                .and_then(|value| {
+                    // Points where the value is complex are left out of the graph, like errors are.
+                    expect_real(
+                        value,
+                        Position {
+                            start: 0,
+                            width: expression.len(),
+                        },
+                    )
+                }) {
+                Ok(value) => values.push(Some(value)),
+                Err(e) => {
+                    maybe_first_error.get_or_insert(e);
+                    values.push(None);
+                }
+            }
+        }
+        let (low, high) = match (values.iter().flatten().min(), values.iter().flatten().max()) {
+            (Some(low), Some(high)) => (low.clone(), high.clone()),
+            // The expression couldn't be evaluated anywhere, so the reason why is probably more
+            // interesting than the graph.
+            _ => {
+                return Err(offset_input_error(
+                    maybe_first_error.unwrap(),
+                    expression_start,
+                ))
+            }
+        };
+
+        let high_label = format_real(&high, data.args);
+        let low_label = format_real(&low, data.args);
+        let label_width = max(high_label.len(), low_label.len());
+        let plot_cols = usize::from(terminal_cols)
+            .saturating_sub(label_width + 2)
+            .max(PLOT_MIN_COLS);
+        // Only every so many samples are kept so that they still span the whole range.
+        let kept_count = 2 * plot_cols;
+        let samples: Vec<Option<f64>> = (0..kept_count)
+            .map(|index| {
+                let sample_index = index * (sample_count - 1) / (kept_count - 1);
+                values[sample_index]
+                    .as_ref()
+                    .and_then(|value| value.to_f64())
+            })
+            .collect();
+        let (low_f64, high_f64) = (low.to_f64().unwrap_or(0.0), high.to_f64().unwrap_or(0.0));
+
+        let mut output: Vec<String> = Vec::new();
+        let plot_lines = braille_plot(&samples, low_f64, high_f64, lines);
+        let last_line = plot_lines.len() - 1;
+        for (index, plot_line) in plot_lines.into_iter().enumerate() {
+            let label = match index {
+                0 => high_label.as_str(),
+                _ if index == last_line => low_label.as_str(),
+                _ => "",
+            };
+            output.push(format!(
+                "{:>width$} |{}",
+                label,
+                plot_line,
+                width = label_width
+            ));
+        }
+        let start_label = format_real(&start, data.args);
+        let end_label = format_real(&end, data.args);
+        let gap = plot_cols
+            .saturating_sub(start_label.len() + end_label.len())
+            .max(1);
+        output.push(format!(
+            "{:width$}  {}{:gap$}{}",
+            "",
+            start_label,
+            "",
+            end_label,
+            width = label_width,
+            gap = gap
+        ));
+        Ok((output.join("\n"), variables_touched))
+    }
+}
+
+struct TableCommand;
+
+impl TableCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(TableCommand {})
+    }
+}
+
+impl Command for TableCommand {
+    fn name(&self) -> &'static str {
+        "table"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Evaluates an expression over a range of values".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        format!(
+            concat!(
+                "Usage: /table expression for $variable = start to end [step size]\n\n",
+                "Evaluates the expression with the variable set to each value from the start ",
+                "value to the end value, going up (or down) by the step size each time, and shows ",
+                "the results in a table, like:\n",
+                "  /table $x^2 for $x = 0 to 10 step 0.5\n",
+                "If no step size is given, it is 1 (or -1, if the end value is less than the start ",
+                "value). Results that can't be evaluated, such as because they would divide by ",
+                "zero, are shown as \"error\". At most {} rows are shown.\n",
+                "The variable is only set while the table is made. If it already has a value, it ",
+                "keeps it.",
+            ),
+            MAX_TABLE_ROWS
+        )
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        arguments: Positioned<String>,
+        mut data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        let usage_error = || {
+            InputError(MaybePositioned::new_positioned(
+                "Expected an expression, \"for\", a variable, \"=\", a start value, \"to\", and an end value"
+                    .to_string()
+                    .into(),
+                arguments.position,
+            ))
+        };
+        // Each word is paired with its position within the whole input.
+        let words: Vec<Positioned<&str>> = arguments
+            .value
+            .split_ascii_whitespace()
+            .map(|word| {
+                let offset = word.as_ptr() as usize - arguments.value.as_ptr() as usize;
+                Positioned::new_raw(word, arguments.position.start + offset, word.len())
+            })
+            .collect();
+        let find = |word: &str, after: usize| {
+            words
+                .iter()
+                .skip(after)
+                .position(|w| w.value == word)
+                .map(|index| index + after)
+        };
+        let for_index = words
+            .iter()
+            .rposition(|word| word.value == "for")
+            .filter(|index| *index > 0)
+            .ok_or_else(usage_error)?;
+        let to_index = find("to", for_index).ok_or_else(usage_error)?;
+        let maybe_step_index = find("step", to_index);
+        let range_end_index = maybe_step_index.unwrap_or(words.len());
+        if range_end_index <= to_index + 1 || maybe_step_index == Some(words.len() - 1) {
+            return Err(usage_error());
+        }
+        // Returns the part of the input from the start of the word at `first` to the end of the
+        // word before `end`.
+        let span = |first: usize, end: usize| {
+            let start = words[first].position.start;
+            let last = &words[end - 1];
+            let offset = arguments.position.start;
+            (
+                &arguments.value
+                    [start - offset..last.position.start + last.position.width - offset],
+                start,
+            )
+        };
+        let (expression, expression_start) = span(0, for_index);
+        let (binding, binding_start) = span(for_index + 1, to_index);
+        let (end_expression, end_start) = span(to_index + 1, range_end_index);
+
+        // The binding is the variable, "=", and the start value.
+        let (variable_text, start_expression) = binding.split_once('=').ok_or_else(usage_error)?;
+        let variable: VariableName = match data.tokenizer.tokenize(variable_text, data.args.radix) {
+            Ok(ParsedInput::Tokens(tokens)) => match tokens.as_slice() {
+                [Positioned {
+                    value: Token::Variable(name),
+                    ..
+                }] => name.clone(),
+                _ => return Err(usage_error()),
+            },
+            _ => return Err(usage_error()),
+        };
+        let start_start = binding_start + variable_text.len() + 1;
+
+        let mut variables_touched: Vec<VariableName> = Vec::new();
+        let (start, _) = evaluate_argument(
+            start_expression,
+            start_start,
+            &mut data,
+            &mut variables_touched,
+        )?;
+        let (end, _) =
+            evaluate_argument(end_expression, end_start, &mut data, &mut variables_touched)?;
+        let step = match maybe_step_index {
+            Some(step_index) => {
+                let (step_expression, step_start) = span(step_index + 1, words.len());
+                let (step, _) = evaluate_argument(
+                    step_expression,
+                    step_start,
+                    &mut data,
+                    &mut variables_touched,
+                )?;
+                if step.is_zero()
+                    || (end > start && step.is_negative())
+                    || (end < start && step.is_positive())
+                {
+                    return Err(InputError(MaybePositioned::new_span(
+                        "The step size must go from the start value towards the end value"
+                            .to_string()
+                            .into(),
+                        words[step_index].position,
+                        words[words.len() - 1].position,
+                    )));
+                }
+                step
+            }
+            None if end < start => -BigRational::one(),
+            None => BigRational::one(),
+        };
+        let row_count = ((&end - &start) / &step).floor().to_integer() + BigInt::one();
+        if row_count > BigInt::from(MAX_TABLE_ROWS) {
+            return Err(InputError(MaybePositioned::new_positioned(
+                format!("The table would have more than {} rows", MAX_TABLE_ROWS).into(),
+                arguments.position,
+            )));
+        }
+
+        let syntax_tree = parse_expression(expression, data.tokenizer, data.args)
+            .map_err(|e| offset_input_error(e, expression_start))?;
+        let maybe_dimension = syntax_tree
+            .dimension()
+            .map_err(|e| offset_input_error(e, expression_start))?;
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut maybe_first_error = None;
+        let mut value = start;
+        for _ in 0..row_count.to_usize().unwrap() {
+            let result = syntax_tree.clone().execute_bound(
+                &variable,
+                value.clone(),
+                data.maybe_vars.as_deref_mut(),
+                data.maybe_db
+                    .as_deref_mut()
+                    .map(|db| db as &mut dyn Storage),
+                data.args,
+            );
+            let result_cell = match result {
+                Ok(result) => format_quantity(&result, maybe_dimension, data.args),
+                Err(e) => {
+                    maybe_first_error.get_or_insert(e);
+                    "error".to_string()
+                }
+            };
+            rows.push(vec![format_real(&value, data.args), result_cell]);
+            value += &step;
+        }
+        if let (Some(e), true) = (maybe_first_error, rows.iter().all(|row| row[1] == "error")) {
+            // None of the results could be evaluated, so the reason why is probably more
+            // interesting than the table.
+            return Err(offset_input_error(e, expression_start));
+        }
+        Ok((
+            format_table(
+                Some(&[&variable, expression]),
+                &rows,
+                data.args.radix_point(),
+            ),
+            variables_touched,
+        ))
+    }
+}
+
+struct TranscriptCommand;
+
+impl TranscriptCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(TranscriptCommand {})
+    }
+}
+
+impl Command for TranscriptCommand {
+    fn name(&self) -> &'static str {
+        "transcript"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Starts or stops recording the session to a file".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /transcript [on path | off]\n\n",
+            "\"/transcript on path\" starts appending a record of every input and the output that ",
+            "it produced to the file at the given path, each with a timestamp (in UTC). The file ",
+            "is created if it doesn't exist. \"/transcript off\" stops recording.\n",
+            "If no arguments are given, the file currently being recorded to is displayed.\n",
+            "The transcript is kept separately from the input history, so it is never trimmed and ",
+            "is recorded even if the on-disk database isn't available. Only the current session ",
+            "is recorded to it.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        let (action, path) = match arguments.value.split_once(char::is_whitespace) {
+            Some((action, path)) => (action, path.trim()),
+            None => (arguments.value.as_str(), ""),
+        };
+        let path_start = arguments.position.start + arguments.value.len() - path.len();
+        match action.to_lowercase().as_str() {
+            "" => Ok((
+                match data.maybe_transcript {
+                    Some(transcript) => {
+                        format!("Recording to '{}'", transcript.path().display())
+                    }
+                    None => "Not recording".to_string(),
+                },
+                Vec::new(),
+            )),
+            "on" if path.is_empty() => Err(InputError(MaybePositioned::new_positioned(
+                "Expected a path".to_string().into(),
+                arguments.position,
+            ))),
+            "on" => {
+                let transcript = Transcript::open(path).map_err(|e| {
+                    InputError(MaybePositioned::new_positioned(
+                        format!("Unable to write to '{}': {}", path, e).into(),
+                        Position {
+                            start: path_start,
+                            width: path.len(),
+                        },
+                    ))
+                })?;
+                if let Some(previous) = data.maybe_transcript.replace(transcript) {
+                    previous
+                        .close()
+                        .map_err(Box::<dyn std::error::Error>::from)?;
+                }
+                Ok(("Done".to_string(), Vec::new()))
+            }
+            "off" if !path.is_empty() => Err(InputError(MaybePositioned::new_positioned(
+                "Too many arguments".to_string().into(),
+                Position {
+                    start: path_start,
+                    width: path.len(),
+                },
+            ))),
+            "off" => match data.maybe_transcript.take() {
+                Some(transcript) => {
+                    transcript
+                        .close()
+                        .map_err(Box::<dyn std::error::Error>::from)?;
+                    Ok(("Done".to_string(), Vec::new()))
+                }
+                None => Err(InputError(MaybePositioned::new_positioned(
+                    "Not recording a transcript".to_string().into(),
+                    arguments.position,
+                ))),
+            },
+            _ => Err(InputError(MaybePositioned::new_positioned(
+                format!("Unknown action: '{}'", action).into(),
+                Position {
+                    start: arguments.position.start,
+                    width: action.len(),
+                },
+            ))),
+        }
+    }
+}
+
+// Loads the currency rates from the file given by the `rates_file` setting into the unit table,
+// unless they have already been loaded from that file.
+fn load_currency_rates_if_needed(data: &mut DataForCommands) -> Result<(), CalculatorFailure> {
+    let path = match &data.args.rates_file {
+        Some(path) => path.clone(),
+        None => return Ok(()),
+    };
+    match data.maybe_currency_rates {
+        Some(rates) if rates.path == path => Ok(()),
+        _ => load_currency_rates(data, path),
+    }
+}
+
+fn load_currency_rates(data: &mut DataForCommands, path: PathBuf) -> Result<(), CalculatorFailure> {
+    let rates = CurrencyRates::load(&path).map_err(|e| {
+        InputError(MaybePositioned::new_unpositioned(
+            format!(
+                "Unable to load currency rates from '{}': {}",
+                path.display(),
+                e
+            )
+            .into(),
+        ))
+    })?;
+    data.units.set_currencies(&rates.rates);
+    *data.maybe_currency_rates = Some(rates);
+    Ok(())
+}
+
+struct MacroCommand;
+
+impl MacroCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(MacroCommand {})
+    }
+}
+
+impl Command for MacroCommand {
+    fn name(&self) -> &'static str {
+        "macro"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Plays back or clears the keys recorded with Alt+R".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        format!(
+            concat!(
+                "Usage: /macro [play [times] | clear]\n\n",
+                "Pressing Alt+R starts recording the keys that are pressed, including the ones ",
+                "that submit inputs, and pressing it again stops recording. Alt+P then plays the ",
+                "recorded keys back once, as if they had been typed again.\n",
+                "If no arguments are given, whether recording is in progress and how many keys ",
+                "were recorded is displayed.\n",
+                "\"play\" plays the recorded keys back the given number of times (at most {}), or ",
+                "once if no number is given, after this input is done. It can't be used while ",
+                "recording.\n",
+                "\"clear\" discards the recorded keys and stops any recording in progress."
+            ),
+            MAX_MACRO_PLAYS
+        )
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        let (action, times) = match arguments.value.split_once(char::is_whitespace) {
+            Some((action, times)) => (action, times.trim()),
+            None => (arguments.value.as_str(), ""),
+        };
+        let times_position = Position {
+            start: arguments.position.start + arguments.value.len() - times.len(),
+            width: times.len(),
+        };
+        let keystroke_macro = data.keystroke_macro;
+        match action.to_lowercase().as_str() {
+            "" => {
+                let recorded = match keystroke_macro.keys().len() {
+                    0 => "No keys are recorded".to_string(),
+                    1 => "1 key is recorded".to_string(),
+                    count => format!("{} keys are recorded", count),
+                };
+                if keystroke_macro.is_recording() {
+                    Ok((format!("Recording. {}", recorded), Vec::new()))
+                } else {
+                    Ok((recorded, Vec::new()))
+                }
+            }
+            "play" => {
+                let times = if times.is_empty() {
+                    1
+                } else {
+                    match times.parse::<usize>() {
+                        Ok(times) if (1..=MAX_MACRO_PLAYS).contains(&times) => times,
+                        _ => {
+                            return Err(InputError(MaybePositioned::new_positioned(
+                                format!("Expected a number of times from 1 to {}", MAX_MACRO_PLAYS)
+                                    .into(),
+                                times_position,
+                            )))
+                        }
+                    }
+                };
+                if keystroke_macro.is_recording() {
+                    return Err(InputError(MaybePositioned::new_positioned(
+                        "Stop recording with Alt+R before playing the recorded keys"
+                            .to_string()
+                            .into(),
+                        arguments.position,
+                    )));
+                }
+                if keystroke_macro.keys().is_empty() {
+                    return Err(InputError(MaybePositioned::new_positioned(
+                        "No keys are recorded; press Alt+R to start recording"
+                            .to_string()
+                            .into(),
+                        arguments.position,
+                    )));
+                }
+                keystroke_macro.request_plays(times);
+                Ok((String::new(), Vec::new()))
+            }
+            "clear" if times.is_empty() => {
+                keystroke_macro.clear();
+                Ok(("Done".to_string(), Vec::new()))
+            }
+            "clear" => Err(InputError(MaybePositioned::new_positioned(
+                "Unexpected argument".to_string().into(),
+                times_position,
+            ))),
+            _ => Err(InputError(MaybePositioned::new_positioned(
+                format!("Unknown action: '{}'", action).into(),
+                arguments.position,
+            ))),
+        }
+    }
+}
+
+struct RatesCommand;
+
+impl RatesCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(RatesCommand {})
+    }
+}
+
+impl Command for RatesCommand {
+    fn name(&self) -> &'static str {
+        "rates"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Shows or reloads the currency exchange rates".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /rates\n",
+            "       /rates refresh\n",
+            "       /rates load path\n\n",
+            "Exchange rates are read from a local file so that currencies can be converted with ",
+            "/convert. They are never downloaded. The file can be given with the --rates-file ",
+            "command line argument or loaded with \"/rates load\".\n",
+            "With no arguments, shows the rates that are currently loaded. \"/rates refresh\" ",
+            "reads the file again, which is useful after it has been updated.\n\n",
+            "A file whose name ends in \".csv\" should have one currency per line, given as its ",
+            "code, a comma, and its rate (ex: \"EUR,0.92\"). Any other file should contain a JSON ",
+            "object mapping codes to rates, or an object with the codes and rates in a \"rates\" ",
+            "member and the code of the currency that they are relative to in a \"base\" member.\n",
+            "Each rate is how much of that currency is equivalent to one unit of the base ",
+            "currency. The US dollar can also be written as \"$\".",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        mut data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        let (subcommand, rest) = match arguments
+            .value
+            .split_once(|c: char| c.is_ascii_whitespace())
+        {
+            Some((subcommand, rest)) => (subcommand, rest.trim()),
+            None => (arguments.value.as_str(), ""),
+        };
+
+        match (subcommand, rest) {
+            ("", _) => load_currency_rates_if_needed(&mut data)?,
+            ("refresh", "") => match data.args.rates_file.clone() {
+                Some(path) => load_currency_rates(&mut data, path)?,
+                None => return Err(no_rates_file_error()),
+            },
+            ("load", path) if !path.is_empty() => {
+                let path = PathBuf::from(path);
+                load_currency_rates(&mut data, path.clone())?;
+                data.args.rates_file = Some(path);
+            }
+            _ => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Invalid argument".to_string().into(),
+                    arguments.position,
+                )));
+            }
+        }
+
+        let rates = match data.maybe_currency_rates {
+            Some(rates) => rates,
+            None => return Err(no_rates_file_error()),
+        };
+        let mut output = format!("Rates from '{}'", rates.path.display());
+        if let Some(base) = &rates.maybe_base {
+            output.push_str(&format!(", relative to {}", base));
+        }
+        output.push(':');
+        let max_code_width = rates
+            .rates
+            .iter()
+            .fold(0, |acc, (code, _)| max(acc, code.len()));
+        for (code, rate) in rates.rates.iter() {
+            output.push_str(&format!(
+                "\n  {:max_code_width$} {}",
+                code,
+                format_real(rate, data.args)
+            ));
+        }
+        Ok((output, Vec::new()))
+    }
+}
+
+fn no_rates_file_error() -> CalculatorFailure {
+    InputError(MaybePositioned::new_unpositioned(
+        "No currency rates file has been given. Use --rates-file or \"/rates load path\""
+            .to_string()
+            .into(),
+    ))
+}
+
+struct ConstantsCommand;
+
+impl ConstantsCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(ConstantsCommand {})
+    }
+}
+
+impl Command for ConstantsCommand {
+    fn name(&self) -> &'static str {
+        "constants"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["const"]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Lists the physical constants".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /constants [name]\n\n",
+            "Physical constants can be used in expressions by writing \"const.\" before their ",
+            "name, such as \"const.c * 2\". Names are case sensitive. Values are in SI units and ",
+            "are the CODATA 2018 recommended values. Constants that are exact by definition are ",
+            "stored exactly; the rest are stored to the precision that they are known to.\n",
+            "With no arguments, lists every constant along with its value and unit. With a name, ",
+            "shows just that constant.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        _data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        if !arguments.value.is_empty() {
+            let name = arguments
+                .value
+                .strip_prefix(CONSTANT_PREFIX)
+                .unwrap_or(&arguments.value);
+            let constant = find_constant(name).ok_or_else(|| {
+                InputError(MaybePositioned::new_positioned(
+                    format!("Unknown constant: '{}'", arguments.value).into(),
+                    arguments.position,
+                ))
+            })?;
+            let mut output = format!("{}{} = {}", CONSTANT_PREFIX, constant.name, constant.value);
+            if !constant.unit.is_empty() {
+                output.push_str(&format!(" {}", constant.unit));
+            }
+            output.push_str(&format!(
+                "\n{} ({})",
+                constant.description,
+                if constant.exact { "exact" } else { "measured" }
+            ));
+            return Ok((output, Vec::new()));
+        }
+
+        let max_name_width = PHYSICAL_CONSTANTS
+            .iter()
+            .fold(0, |acc, constant| max(acc, constant.name.len()));
+        let max_value_width = PHYSICAL_CONSTANTS.iter().fold(0, |acc, constant| {
+            max(acc, constant.value.len() + 1 + constant.unit.len())
+        });
+        let mut output = "Constants:".to_string();
+        for constant in PHYSICAL_CONSTANTS {
+            let value = format!("{} {}", constant.value, constant.unit);
+            output.push_str(&format!(
+                "\n  {:max_name_width$} {:max_value_width$} {}",
+                constant.name, value, constant.description
+            ));
+        }
+        Ok((output, Vec::new()))
+    }
+}
+
+// Replaces any custom units in `units` with the ones stored in the database. Stored units whose
+// names have since been taken by built in units are skipped.
+fn load_custom_units(
+    units: &mut UnitTable,
+    db: &mut SavedData,
+) -> Result<(), Box<dyn std::error::Error>> {
+    units.clear_custom_units();
+    for unit in db.get_custom_units()? {
+        let _ = units.add_custom_unit(unit);
+    }
+    Ok(())
+}
+
+struct DefUnitCommand;
+
+impl DefUnitCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(DefUnitCommand {})
+    }
+}
+
+impl Command for DefUnitCommand {
+    fn name(&self) -> &'static str {
+        "defunit"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Defines a unit for use with /convert".to_string()
+    }
+
+    fn long_help(&self, data: &DataForCommands) -> String {
+        let mut output = concat!(
+            "Usage: /defunit\n",
+            "       /defunit name = expression unit\n\n",
+            "Defines a new unit as some amount of an existing unit. For example, ",
+            "\"/defunit furlong = 201.168 m\". The new unit can then be used with /convert. ",
+            "Defining a unit that was already defined replaces it. Built in units can't be ",
+            "redefined. Names must start with a letter and may contain only letters, digits, and ",
+            "underscores.\n",
+            "With no arguments, lists the units that have been defined.\n",
+            "See also: /undefunit",
+        )
+        .to_string();
+        if data.maybe_db.is_none() {
+            output.push_str(concat!(
+                "\n\nUnits defined now will only last until bcalc exits because the database ",
+                "is unavailable."
+            ));
+        }
+        output
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        mut data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        if arguments.value.is_empty() {
+            let mut output = String::new();
+            for unit in data.units.units().iter().filter(|unit| unit.is_custom) {
+                if !output.is_empty() {
+                    output.push('\n');
+                }
+                match data.units.base_unit(unit.dimension) {
+                    Some(base_unit) => output.push_str(&format!(
+                        "{} = {} {}",
+                        unit.name(),
+                        format_real(&unit.factor, data.args),
+                        base_unit.name()
+                    )),
+                    None => output.push_str(&format!("{} ({})", unit.name(), unit.dimension)),
+                }
+            }
+            if output.is_empty() {
+                output.push_str("No units have been defined");
+            }
+            return Ok((output, Vec::new()));
+        }
+
+        let maybe_rates_error = load_currency_rates_if_needed(&mut data).err();
+
+        let (name, definition) = match arguments.value.split_once('=') {
+            Some((name, definition)) => (name.trim(), definition),
+            None => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Expected a name, \"=\", an expression, and a unit"
+                        .to_string()
+                        .into(),
+                    arguments.position,
+                )));
+            }
+        };
+        let name_position = Position {
+            start: arguments.position.start,
+            width: name.len(),
+        };
+        let mut name_chars = name.chars();
+        let valid_name = name_chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+            && name_chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(InputError(MaybePositioned::new_positioned(
+                format!("Invalid unit name: '{}'", name).into(),
+                name_position,
+            )));
+        }
+
+        // Each word is paired with its position within the whole input.
+        let definition_start = arguments.position.start + arguments.value.len() - definition.len();
+        let words: Vec<Positioned<&str>> = definition
+            .split_ascii_whitespace()
+            .map(|word| {
+                let offset = word.as_ptr() as usize - definition.as_ptr() as usize;
+                Positioned::new_raw(word, definition_start + offset, word.len())
+            })
+            .collect();
+        let unit_word = match words.last() {
+            Some(word) => word,
+            None => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "Expected an expression and a unit after \"=\""
+                        .to_string()
+                        .into(),
+                    Position {
+                        start: arguments.position.start + arguments.position.width,
+                        width: 0,
+                    },
+                )));
+            }
+        };
+        let unit = match data.units.find(unit_word.value) {
+            Some(unit) => unit,
+            None => {
+                return Err(maybe_rates_error.unwrap_or_else(|| {
+                    InputError(MaybePositioned::new_positioned(
+                        format!("Unknown unit: '{}'", unit_word.value).into(),
+                        unit_word.position,
+                    ))
+                }));
+            }
+        };
+
+        // The expression is everything between the "=" and the unit. It may be left out, in which
+        // case the new unit is just another name for the existing one.
+        let mut variables_touched: Vec<VariableName> = Vec::new();
+        let amount = if words.len() > 1 {
+            let expression_start = words[0].position.start;
+            let expression = definition
+                [expression_start - definition_start..unit_word.position.start - definition_start]
+                .trim_end();
+            let syntax_tree = parse_expression(expression, data.tokenizer, data.args)
+                .map_err(|e| offset_input_error(e, expression_start))?;
+            let maybe_dimension = syntax_tree
+                .dimension()
+                .map_err(|e| offset_input_error(e, expression_start))?;
+            if maybe_dimension.is_some_and(|dimension| !dimension.is_none()) {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    "The amount can't have units of its own".to_string().into(),
+                    Position {
+                        start: expression_start,
+                        width: expression.len(),
+                    },
+                )));
+            }
+            let mut observer = |step: &TraceStep| {
+                if let NodeKind::Variable(name) = &step.kind {
+                    variables_touched.push(name.clone());
+                }
+            };
+            let value = syntax_tree
+                .execute_traced(
+                    data.maybe_input_history_id,
+                    data.maybe_vars,
+                    data.maybe_db
+                        .as_deref_mut()
+                        .map(|db| db as &mut dyn Storage),
+                    data.args,
+                    Some(&mut observer),
+                )
+                .map_err(|e| offset_input_error(e, expression_start))?;
+            expect_real(
+                value,
+                Position {
+                    start: expression_start,
+                    width: expression.len(),
+                },
+            )?
+        } else {
+            BigRational::one()
+        };
+        if !amount.is_positive() {
+            return Err(InputError(MaybePositioned::new_unpositioned(
+                "A unit must be defined as a positive amount"
+                    .to_string()
+                    .into(),
+            )));
+        }
+
+        let output = format!(
+            "1 {} = {} {}",
+            name,
+            format_real(&amount, data.args),
+            unit.name()
+        );
+        let new_unit = Unit {
+            names: vec![name.to_string()],
+            dimension: unit.dimension,
+            factor: amount * &unit.factor,
+            is_custom: true,
+        };
+        data.units
+            .add_custom_unit(new_unit.clone())
+            .map_err(|message| {
+                InputError(MaybePositioned::new_positioned(
+                    message.into(),
+                    name_position,
+                ))
+            })?;
+        if let Some(db) = data.maybe_db {
+            db.set_custom_unit(&new_unit)?;
+        }
+        Ok((output, variables_touched))
+    }
+}
+
+struct UndefUnitCommand;
+
+impl UndefUnitCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(UndefUnitCommand {})
+    }
+}
+
+impl Command for UndefUnitCommand {
+    fn name(&self) -> &'static str {
+        "undefunit"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Removes a unit defined with /defunit".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /undefunit name\n\n",
+            "Removes a unit that was defined with /defunit. Built in units can't be removed.\n",
+            "See also: /defunit",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        if !data.units.remove_custom_unit(&arguments.value) {
+            return Err(InputError(MaybePositioned::new_positioned(
+                format!("No unit named '{}' has been defined", arguments.value).into(),
+                arguments.position,
+            )));
+        }
+        if let Some(db) = data.maybe_db {
+            db.remove_custom_unit(&arguments.value)?;
+        }
+        Ok(("Done".to_string(), Vec::new()))
+    }
+}
+
+struct IpCommand;
+
+impl IpCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(IpCommand {})
+    }
+}
+
+impl Command for IpCommand {
+    fn name(&self) -> &'static str {
+        "ip"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Shows a value as an IPv4 address or describes a CIDR block".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /ip expression
+",
+            "       /ip address/prefix_length
+
+",
+            "Evaluates the expression and shows the result as an IPv4 address. For example, ",
+            "\"/ip network(192.168.1.10/24)\" shows 192.168.1.0.\n",
+            "Given a CIDR block, such as \"/ip 10.0.0.0/8\", shows its netmask, network and ",
+            "broadcast addresses, and the number of hosts that it can hold.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        let maybe_block = arguments
+            .value
+            .split_once('/')
+            .and_then(|(address, prefix_length)| {
+                let prefix_length: u32 = prefix_length.parse().ok()?;
+                (prefix_length <= MAX_PREFIX_LENGTH)
+                    .then_some((parse_ipv4(address)?, prefix_length))
+            });
+        if let Some((address, prefix_length)) = maybe_block {
+            let output = format!(
+                "Address:   {}\nNetmask:   {}\nNetwork:   {}\nBroadcast: {}\nHosts:     {}",
+                format_ipv4(address),
+                format_ipv4(netmask(prefix_length)),
+                format_ipv4(network_address(address, prefix_length)),
+                format_ipv4(broadcast_address(address, prefix_length)),
+                host_count(prefix_length)
+            );
+            return Ok((output, Vec::new()));
+        }
+
+        let expression_start = arguments.position.start;
+        let syntax_tree = parse_expression(&arguments.value, data.tokenizer, data.args)
+            .map_err(|e| offset_input_error(e, expression_start))?;
+        let mut variables_touched: Vec<VariableName> = Vec::new();
+        let mut observer = |step: &TraceStep| {
+            if let NodeKind::Variable(name) = &step.kind {
+                variables_touched.push(name.clone());
+            }
+        };
+        let value = syntax_tree
+            .execute_traced(
+                data.maybe_input_history_id,
+                data.maybe_vars,
+                data.maybe_db.map(|db| db as &mut dyn Storage),
+                data.args,
+                Some(&mut observer),
+            )
+            .map_err(|e| offset_input_error(e, expression_start))?;
+        let value = expect_real(value, arguments.position)?;
+        let address = address_argument(&value).map_err(|message| {
+            InputError(MaybePositioned::new_positioned(
+                message.into(),
+                arguments.position,
+            ))
+        })?;
+        Ok((format_ipv4(address), variables_touched))
+    }
+}
+
+struct Base64Command;
+
+impl Base64Command {
+    fn new() -> Box<dyn Command> {
+        Box::new(Base64Command {})
+    }
+}
+
+impl Command for Base64Command {
+    fn name(&self) -> &'static str {
+        "base64"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Shows an integer as base64 or decodes base64 into an integer".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        concat!(
+            "Usage: /base64 expression\n",
+            "       /base64 decode text\n\n",
+            "Evaluates the expression, which must give a non-negative integer, and shows the ",
+            "integer's big-endian bytes in base64. For example, \"/base64 65537\" shows AQAB.\n",
+            "Given \"decode\" followed by base64 text, shows the integer that the text encodes, ",
+            "in the output radix. Padding with \"=\" is optional.",
+        )
+        .to_string()
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        if let Some(text) = arguments.value.strip_prefix("decode ") {
+            let value = parse_base64(text.trim()).ok_or_else(|| {
+                InputError(MaybePositioned::new_positioned(
+                    "Invalid base64".to_string().into(),
+                    arguments.position,
+                ))
+            })?;
+            let value = Value::from(BigInt::from(value));
+            return Ok((format_value(&value, data.args), Vec::new()));
+        }
+
+        let expression_start = arguments.position.start;
+        let syntax_tree = parse_expression(&arguments.value, data.tokenizer, data.args)
+            .map_err(|e| offset_input_error(e, expression_start))?;
+        let mut variables_touched: Vec<VariableName> = Vec::new();
+        let mut observer = |step: &TraceStep| {
+            if let NodeKind::Variable(name) = &step.kind {
+                variables_touched.push(name.clone());
+            }
+        };
+        let value = syntax_tree
+            .execute_traced(
+                data.maybe_input_history_id,
+                data.maybe_vars,
+                data.maybe_db.map(|db| db as &mut dyn Storage),
+                data.args,
+                Some(&mut observer),
+            )
+            .map_err(|e| offset_input_error(e, expression_start))?;
+        let value = expect_real(value, arguments.position)?;
+        let integer = value
+            .is_integer()
+            .then(|| value.to_integer().to_biguint())
+            .flatten()
+            .ok_or_else(|| {
+                InputError(MaybePositioned::new_positioned(
+                    "Expected a non-negative integer".to_string().into(),
+                    arguments.position,
+                ))
+            })?;
+        Ok((make_base64_string(&integer), variables_touched))
+    }
+}
+
+struct RootsCommand;
+
+impl RootsCommand {
+    fn new() -> Box<dyn Command> {
+        Box::new(RootsCommand {})
+    }
+}
+
+impl Command for RootsCommand {
+    fn name(&self) -> &'static str {
+        "roots"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn short_help(&self, _data: &DataForCommands) -> String {
+        "Finds the roots of a polynomial".to_string()
+    }
+
+    fn long_help(&self, _data: &DataForCommands) -> String {
+        format!(
+            concat!(
+                "Usage: /roots coefficient coefficient...\n",
+                "       /roots expression\n\n",
+                "Finds the values of x for which a polynomial is 0. The polynomial can be given as ",
+                "its coefficients, starting with the highest power of x. For example, ",
+                "\"/roots 1 -3 2\" finds the roots of x^2 - 3x + 2. Each coefficient may be an ",
+                "expression, as long as it contains no spaces. Alternatively, the polynomial can ",
+                "be written as an expression using the variable $x, as in ",
+                "\"/roots $x^2 - 3 * $x + 2\". In that case, other variables can't be used.\n",
+                "Rational roots are found exactly. Other real roots are approximated using the ",
+                "current precision and marked with \"~\". Complex roots are counted but not ",
+                "shown. Each root is listed once, even if it is repeated. Polynomials can have a ",
+                "degree of at most {}."
+            ),
+            MAX_DEGREE
+        )
+    }
+
+    fn execute(
+        &self,
+        _command_name: Positioned<String>,
+        mut arguments: Positioned<String>,
+        mut data: DataForCommands,
+    ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+        arguments.trim();
+        let expression_start = arguments.position.start;
+        let variable: VariableName = "$x".into();
+        let uses_variable = match data.tokenizer.tokenize(&arguments.value, data.args.radix) {
+            Ok(ParsedInput::Tokens(tokens)) => tokens
+                .iter()
+                .any(|token| matches!(&token.value, Token::Variable(name) if *name == variable)),
+            _ => false,
+        };
+
+        let mut variables_touched: Vec<VariableName> = Vec::new();
+        let coefficients = if uses_variable {
+            // The expression is evaluated at enough points to recover the polynomial from its
+            // values, using a separate variable store so that `$x` isn't actually changed.
+            let syntax_tree = parse_expression(&arguments.value, data.tokenizer, data.args)
+                .map_err(|e| offset_input_error(e, expression_start))?;
+            let mut values = Vec::new();
+            for x in 0..MAX_DEGREE + 3 {
+                let mut vars = VariableStore::new();
+                let value = Value::from(BigInt::from(x));
+                vars.update(
+                    Variable {
+                        name: variable.clone(),
+                        value,
+                    },
+                    None,
+                    None,
+                )
+                .map_err(RuntimeError)?;
+                let value = syntax_tree
+                    .clone()
+                    .execute(None, Some(&mut vars), None, data.args)
+                    .map_err(|e| offset_input_error(e, expression_start))?;
+                values.push(expect_real(value, arguments.position)?);
+            }
+            polynomial_from_values(&values).ok_or_else(|| {
+                InputError(MaybePositioned::new_positioned(
+                    format!(
+                        "Expected a polynomial in {} with a degree of at most {}",
+                        variable, MAX_DEGREE
+                    )
+                    .into(),
+                    arguments.position,
+                ))
+            })?
+        } else {
+            let mut coefficients = Vec::new();
+            let words: Vec<&str> = arguments.value.split_ascii_whitespace().collect();
+            for word in words {
+                let word_start = arguments.position.start
+                    + (word.as_ptr() as usize - arguments.value.as_ptr() as usize);
+                let syntax_tree = parse_expression(word, data.tokenizer, data.args)
+                    .map_err(|e| offset_input_error(e, word_start))?;
+                let mut observer = |step: &TraceStep| {
+                    if let NodeKind::Variable(name) = &step.kind {
+                        variables_touched.push(name.clone());
+                    }
+                };
+                let coefficient = syntax_tree
+                    .execute_traced(
+                        data.maybe_input_history_id,
+                        data.maybe_vars.as_deref_mut(),
+                        data.maybe_db
+                            .as_deref_mut()
+                            .map(|db| db as &mut dyn Storage),
+                        data.args,
+                        Some(&mut observer),
+                    )
+                    .map_err(|e| offset_input_error(e, word_start))?;
+                coefficients.push(expect_real(
+                    coefficient,
+                    Position {
+                        start: word_start,
+                        width: word.len(),
+                    },
+                )?);
+            }
+            // Coefficients are given starting with the highest power.
+            coefficients.reverse();
+            coefficients
+        };
+        if coefficients.is_empty() {
+            return Err(InputError(MaybePositioned::new_positioned(
+                "Expected coefficients or an expression".to_string().into(),
+                arguments.position,
+            )));
+        }
+
+        // Roots are approximated to the precision that they will be shown with, plus the extra
+        // precision.
+        let output_radix = data.args.convert_to_radix.unwrap_or(data.args.radix);
+        let digits = u32::from(data.args.precision) + u32::from(data.args.extra_precision);
+        let tolerance = BigRational::new(BigInt::one(), BigInt::from(output_radix).pow(digits));
+        let roots = find_roots(&coefficients, &tolerance).map_err(|message| {
+            InputError(MaybePositioned::new_positioned(
+                message.into(),
+                arguments.position,
+            ))
+        })?;
+
+        let mut lines: Vec<String> = Vec::new();
+        for root in roots.exact.iter() {
+            lines.push(format!("x = {}", format_real(root, data.args)));
+        }
+        for root in roots.approximate.iter() {
+            lines.push(format!("x ~ {}", format_real(root, data.args)));
+        }
+        if lines.is_empty() {
+            lines.push("No real roots".to_string());
+        }
+        match roots.complex_count {
+            0 => {}
+            1 => lines.push("(1 complex root not shown)".to_string()),
+            count => lines.push(format!("({} complex roots not shown)", count)),
+        }
+        Ok((lines.join("\n"), variables_touched))
+    }
+}
+
+#[cfg(test)]
+mod command_tests {
+    use crate::{
+        commands::{Command, CommandExecutor, DataForCommands},
+        error::{
+            CalculatorFailure::{self, InputError, RuntimeError},
+            Warning,
+        },
+        format_quantity, format_value,
+        input_history::InputHistory,
+        position::Positioned,
+        rounding_warning,
+        saved_data::SavedData,
+        storage::Storage,
+        token::Tokenizer,
+        units::Dimension,
+        variable::{Variable, VariableFormat, VariableName, VariableStore},
+        AngleUnit, Args, CloseParens, ErrorAlert, GroupSeparator, Value,
+    };
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use num::{rational::BigRational, BigInt};
+
+    struct GreetCommand;
+
+    impl Command for GreetCommand {
+        fn name(&self) -> &'static str {
+            "greet"
+        }
+
+        fn aliases(&self) -> &'static [&'static str] {
+            &["hi"]
+        }
+
+        fn short_help(&self, _data: &DataForCommands) -> String {
+            "Says hello".to_string()
+        }
+
+        fn long_help(&self, _data: &DataForCommands) -> String {
+            "Usage: /greet name".to_string()
+        }
+
+        fn execute(
+            &self,
+            _command_name: Positioned<String>,
+            mut arguments: Positioned<String>,
+            _data: DataForCommands,
+        ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+            arguments.trim();
+            Ok((format!("Hello, {}", arguments.value), Vec::new()))
+        }
+    }
+
+    struct HelpImpostorCommand;
+
+    impl Command for HelpImpostorCommand {
+        fn name(&self) -> &'static str {
+            "impostor"
+        }
+
+        fn aliases(&self) -> &'static [&'static str] {
+            &["help"]
+        }
+
+        fn short_help(&self, _data: &DataForCommands) -> String {
+            String::new()
+        }
+
+        fn long_help(&self, _data: &DataForCommands) -> String {
+            String::new()
+        }
+
+        fn execute(
+            &self,
+            _command_name: Positioned<String>,
+            _arguments: Positioned<String>,
+            _data: DataForCommands,
+        ) -> Result<(String, Vec<VariableName>), CalculatorFailure> {
+            Ok((String::new(), Vec::new()))
+        }
+    }
+
+    fn run(command_executor: &mut CommandExecutor, command: &str, arguments: &str) -> String {
+        run_result(
+            command_executor,
+            command,
+            arguments,
+            &mut Args::default(),
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn run_result(
+        command_executor: &mut CommandExecutor,
+        command: &str,
+        arguments: &str,
+        args: &mut Args,
+        maybe_inputs: Option<&mut InputHistory>,
+        maybe_vars: Option<&mut VariableStore>,
+    ) -> Result<String, String> {
+        command_executor
+            .execute_command(
+                Positioned::new_raw(command.to_string(), 0, command.len() + 1),
+                Positioned::new_raw(arguments.to_string(), command.len() + 2, arguments.len()),
+                args,
+                &Tokenizer::new(),
+                None,
+                maybe_inputs,
+                maybe_vars,
+                None,
+            )
+            .map(|(output, _)| output)
+            .map_err(failure_message)
+    }
+
+    // Runs the command in `input` the way that an interactive session would, with the input first
+    // added to the input history in `db`.
+    fn run_with_db(
+        command_executor: &mut CommandExecutor,
+        input: &str,
+        db: &mut SavedData,
+        vars: &mut VariableStore,
+    ) -> Result<String, String> {
+        let id = db.add_to_input_history(input).unwrap();
+        let (command, arguments) = input[1..].split_once(' ').unwrap_or((&input[1..], ""));
+        command_executor
+            .execute_command(
+                Positioned::new_raw(command.to_string(), 0, command.len() + 1),
+                Positioned::new_raw(arguments.to_string(), command.len() + 2, arguments.len()),
+                &mut Args::default(),
+                &Tokenizer::new(),
+                Some(db),
+                None,
+                Some(vars),
+                Some(id),
+            )
+            .map(|(output, _)| output)
+            .map_err(failure_message)
+    }
+
+    fn failure_message(failure: CalculatorFailure) -> String {
+        match failure {
+            InputError(message) => message.value.to_string(),
+            RuntimeError(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn vars() {
+        let mut command_executor = CommandExecutor::new();
+        let mut vars = VariableStore::new();
+        for (name, value) in [("$proj.rate", 3), ("$proj.tax.rate", 20), ("$project", 1)] {
+            vars.update(
+                Variable {
+                    name: name.into(),
+                    value: BigInt::from(value).into(),
+                },
+                None,
+                None,
+            )
+            .unwrap();
+        }
+        let mut execute = |command: &str, arguments: &str| {
+            run_result(
+                &mut command_executor,
+                command,
+                arguments,
+                &mut Args::default(),
+                None,
+                Some(&mut vars),
+            )
+        };
+        assert_eq!(
+            execute("vars", "").unwrap(),
+            "$proj.rate = 3\n$proj.tax.rate = 20\n$project = 1"
+        );
+        assert_eq!(
+            execute("vars", "proj").unwrap(),
+            "$proj.rate = 3\n$proj.tax.rate = 20"
+        );
+        assert_eq!(execute("vars", "$proj.tax").unwrap(), "$proj.tax.rate = 20");
+        assert_eq!(
+            execute("vars", "other").unwrap(),
+            "No variables are in the other namespace"
+        );
+        assert_eq!(
+            execute("vars", "proj other").unwrap_err(),
+            "Expected at most one namespace"
+        );
+
+        assert_eq!(execute("purgevar", "$project $proj.tax.*").unwrap(), "Done");
+        assert_eq!(execute("vars", "").unwrap(), "$proj.rate = 3");
+        assert_eq!(execute("purgevar", "$proj.*").unwrap(), "Done");
+        assert_eq!(execute("vars", "").unwrap(), "No variables are defined");
+    }
+
+    #[test]
+    fn memory() {
+        let mut command_executor = CommandExecutor::new();
+        let mut vars = VariableStore::new();
+        let mut inputs = InputHistory::new(false);
+        let mut execute = |command: &str,
+                           arguments: &str,
+                           vars: &mut VariableStore,
+                           inputs: &mut InputHistory| {
+            run_result(
+                &mut command_executor,
+                command,
+                arguments,
+                &mut Args::default(),
+                Some(inputs),
+                Some(vars),
+            )
+        };
+        let ratio =
+            |numer: i64, denom: i64| Value::Real(BigRational::new(numer.into(), denom.into()));
+
+        assert_eq!(
+            execute("m+", "", &mut vars, &mut inputs).unwrap_err(),
+            "There is no last result yet"
+        );
+        assert_eq!(
+            execute("mr", "", &mut vars, &mut inputs).unwrap_err(),
+            "The memory is empty"
+        );
+        vars.push_result(ratio(5, 1));
+        assert_eq!(execute("m+", "", &mut vars, &mut inputs).unwrap(), "5");
+        vars.push_result(ratio(1, 2));
+        assert_eq!(execute("m+", "", &mut vars, &mut inputs).unwrap(), "5.5");
+        vars.push_result(ratio(2, 1));
+        assert_eq!(execute("m-", "", &mut vars, &mut inputs).unwrap(), "3.5");
+        assert_eq!(
+            execute("m-", "2", &mut vars, &mut inputs).unwrap_err(),
+            "Unexpected argument"
+        );
+        assert_eq!(
+            vars.get("$mem".into(), None).unwrap().unwrap().value,
+            ratio(7, 2)
+        );
+
+        assert_eq!(execute("mr", "", &mut vars, &mut inputs).unwrap(), "");
+        assert_eq!(inputs.current_line(), "(7/2)");
+        assert_eq!(execute("mc", "", &mut vars, &mut inputs).unwrap(), "Done");
+        assert!(vars.get("$mem".into(), None).unwrap().is_none());
+    }
+
+    #[test]
+    fn varformat() {
+        let mut command_executor = CommandExecutor::new();
+        let mut vars = VariableStore::new();
+        vars.update(
+            Variable {
+                name: "$mask".into(),
+                value: BigInt::from(0xff00).into(),
+            },
+            None,
+            None,
+        )
+        .unwrap();
+        let mut varformat = |arguments: &str| {
+            run_result(
+                &mut command_executor,
+                "varformat",
+                arguments,
+                &mut Args::default(),
+                None,
+                Some(&mut vars),
+            )
+        };
+        assert_eq!(varformat("$mask").unwrap(), "$mask has no format");
+        assert_eq!(varformat("$mask hex precision 0").unwrap(), "Done");
+        assert_eq!(varformat(" $mask ").unwrap(), "hex precision 0");
+        assert_eq!(
+            varformat("$mask radix").unwrap_err(),
+            "Expected 'radix' to be followed by a number from 2 to 36"
+        );
+        assert_eq!(
+            varformat("$mask hexadecimal").unwrap_err(),
+            "Unknown format setting: 'hexadecimal'"
+        );
+        assert_eq!(
+            varformat("$other hex").unwrap_err(),
+            "$other is not defined"
+        );
+        assert_eq!(varformat("$mask none").unwrap(), "Done");
+        assert_eq!(varformat("$mask").unwrap(), "$mask has no format");
+
+        let format = VariableFormat::parse("radix 3 fractional").unwrap();
+        assert_eq!(format.to_string(), "radix 3 fractional");
+        let args = VariableFormat::parse("hex")
+            .unwrap()
+            .apply(&Args::default());
+        assert_eq!(format_value(&BigInt::from(0xff00).into(), &args), "ff00");
+    }
+
+    #[test]
+    fn scientific_notation() {
+        let mut command_executor = CommandExecutor::new();
+        let mut args = Args::default();
+        let mut set = |command: &str, arguments: &str, args: &mut Args| {
+            run_result(&mut command_executor, command, arguments, args, None, None)
+        };
+        let ratio =
+            |numer: i64, denom: i64| Value::Real(BigRational::new(numer.into(), denom.into()));
+
+        assert_eq!(set("sciabove", "", &mut args).unwrap(), "None");
+        assert_eq!(set("sciabove", "6", &mut args).unwrap(), "Done");
+        assert_eq!(set("scibelow", "3", &mut args).unwrap(), "Done");
+        assert_eq!(set("scibelow", "", &mut args).unwrap(), "3");
+        assert_eq!(format_value(&ratio(999_999, 1), &args), "999999");
+        assert_eq!(format_value(&ratio(1_250_000, 1), &args), "1.25e6");
+        assert_eq!(format_value(&ratio(1, 1000), &args), "0.001");
+        assert_eq!(format_value(&ratio(-1, 8000), &args), "-1.25e-4");
+        assert_eq!(format_value(&ratio(0, 1), &args), "0");
+        assert_eq!(
+            rounding_warning(&ratio(1, 3_000_000), None, &args),
+            Some(Warning::Rounded(5))
+        );
+        assert_eq!(rounding_warning(&ratio(1, 8000), None, &args), None);
+        assert_eq!(
+            set("scibelow", "0", &mut args).unwrap_err(),
+            "Value must be at least 1"
+        );
+        assert_eq!(set("sciabove", "none", &mut args).unwrap(), "Done");
+        assert_eq!(format_value(&ratio(1_250_000, 1), &args), "1250000");
+
+        assert_eq!(set("notation", "", &mut args).unwrap(), "auto");
+        assert_eq!(set("notation", "Sci", &mut args).unwrap(), "Done");
+        assert_eq!(format_value(&ratio(1_250_000, 1), &args), "1.25e6");
+        assert_eq!(format_value(&ratio(3, 2), &args), "1.5e0");
+        assert_eq!(format_value(&ratio(0, 1), &args), "0");
+        assert_eq!(set("notation", "engineering", &mut args).unwrap(), "Done");
+        assert_eq!(set("notation", "", &mut args).unwrap(), "engineering");
+        assert_eq!(format_value(&ratio(12_500_000, 1), &args), "12.5e6");
+        assert_eq!(format_value(&ratio(-1, 8000), &args), "-125e-6");
+        assert_eq!(
+            rounding_warning(&ratio(1, 3_000), None, &args),
+            Some(Warning::Rounded(5))
+        );
+        assert_eq!(rounding_warning(&ratio(1, 8000), None, &args), None);
+        args.radix = 16;
+        assert_eq!(format_value(&ratio(255, 1), &args), "ff");
+        assert_eq!(
+            set("notation", "exponential", &mut args).unwrap_err(),
+            "Invalid argument"
+        );
+    }
+
+    #[test]
+    fn error_alert() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(run(&mut command_executor, "erroralert", ""), "none");
+        let mut args = Args::default();
+        assert_eq!(
+            run_result(
+                &mut command_executor,
+                "erroralert",
+                "Flash",
+                &mut args,
+                None,
+                None
+            )
+            .unwrap(),
+            "Done"
+        );
+        assert_eq!(args.error_alert, ErrorAlert::Flash);
+    }
+
+    #[test]
+    fn close_parens() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(run(&mut command_executor, "closeparens", ""), "offer");
+        let mut args = Args::default();
+        assert_eq!(
+            run_result(
+                &mut command_executor,
+                "closeparens",
+                "auto",
+                &mut args,
+                None,
+                None
+            )
+            .unwrap(),
+            "Done"
+        );
+        assert_eq!(args.close_parens, CloseParens::Auto);
+    }
+
+    #[test]
+    fn strict() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(run(&mut command_executor, "strict", ""), "false");
+        let mut args = Args {
+            close_parens: CloseParens::Auto,
+            ..Args::default()
+        };
+        let mut set = |arguments: &str, args: &mut Args| {
+            run_result(&mut command_executor, "strict", arguments, args, None, None)
+        };
+        assert_eq!(set("t", &mut args).unwrap(), "Done");
+        assert!(args.strict);
+        assert_eq!(args.effective_close_parens(), CloseParens::Off);
+        assert_eq!(set("maybe", &mut args).unwrap_err(), "Invalid argument");
+        assert_eq!(set("false", &mut args).unwrap(), "Done");
+        assert_eq!(args.effective_close_parens(), CloseParens::Auto);
+    }
+
+    #[test]
+    fn si_suffixes() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(run(&mut command_executor, "sisuffixes", ""), "true");
+        let mut args = Args::default();
+        let mut set = |arguments: &str, args: &mut Args| {
+            run_result(
+                &mut command_executor,
+                "sisuffixes",
+                arguments,
+                args,
+                None,
+                None,
+            )
+        };
+        assert_eq!(set("f", &mut args).unwrap(), "Done");
+        assert!(args.no_si_suffixes);
+        assert_eq!(set("on", &mut args).unwrap_err(), "Invalid argument");
+        assert_eq!(set("true", &mut args).unwrap(), "Done");
+        assert!(!args.no_si_suffixes);
+        assert_eq!(set("milli", &mut args).unwrap(), "Done");
+        assert!(!args.no_si_suffixes && args.milli_suffix);
+        assert_eq!(set("", &mut args).unwrap(), "milli");
+        assert_eq!(set("t", &mut args).unwrap(), "Done");
+        assert!(!args.milli_suffix);
+    }
+
+    #[test]
+    fn angle() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(run(&mut command_executor, "angle", ""), "radians");
+        let mut args = Args::default();
+        let mut set = |arguments: &str, args: &mut Args| {
+            run_result(&mut command_executor, "angle", arguments, args, None, None)
+        };
+        assert_eq!(set("Degrees", &mut args).unwrap(), "Done");
+        assert_eq!(args.angle_unit, AngleUnit::Degrees);
+        assert_eq!(set("gradians", &mut args).unwrap_err(), "Invalid argument");
+        assert_eq!(set("rad", &mut args).unwrap(), "Done");
+        assert_eq!(args.angle_unit, AngleUnit::Radians);
+    }
+
+    #[test]
+    fn bases() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(run(&mut command_executor, "bases", ""), "false");
+        let mut args = Args::default();
+        assert_eq!(
+            run_result(&mut command_executor, "bases", "t", &mut args, None, None).unwrap(),
+            "Done"
+        );
+        assert!(args.bases);
+        let ratio =
+            |numer: i64, denom: i64| Value::Real(BigRational::new(numer.into(), denom.into()));
+        assert_eq!(
+            format_quantity(&ratio(-10, 1), None, &args),
+            "-10  -0xa  -0o12  -0b1010"
+        );
+        assert_eq!(
+            format_quantity(&ratio(5, 4), Some(Dimension::NONE), &args),
+            "1.25  0x1.4  0o1.2  0b1.01"
+        );
+        assert_eq!(rounding_warning(&ratio(5, 4), None, &args), None);
+        // 0.1 has no exact binary representation, even though it's exact in decimal.
+        assert_eq!(
+            rounding_warning(&ratio(1, 10), None, &args),
+            Some(Warning::Rounded(5))
+        );
+        // Values with units are shown as usual.
+        assert_eq!(
+            format_quantity(&ratio(5, 1), Some(Dimension::LENGTH), &args),
+            "5 m"
+        );
+    }
+
+    #[test]
+    fn group_separator() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(run(&mut command_executor, "groupsep", ""), "comma 3");
+        let mut args = Args {
+            commas: true,
+            ..Args::default()
+        };
+        let mut set = |arguments: &str, args: &mut Args| {
+            run_result(
+                &mut command_executor,
+                "groupsep",
+                arguments,
+                args,
+                None,
+                None,
+            )
+        };
+        let ratio =
+            |numer: i64, denom: i64| Value::Real(BigRational::new(numer.into(), denom.into()));
+
+        assert_eq!(set("_ 4", &mut args).unwrap(), "Done");
+        assert_eq!(args.group_separator, GroupSeparator::Underscore);
+        assert_eq!(args.group_size, 4);
+        args.radix = 16;
+        assert_eq!(format_value(&ratio(0xdeadbeef, 1), &args), "dead_beef");
+        assert_eq!(format_value(&ratio(0x123456, 0x1000), &args), "123.456");
+        args.precision = 6;
+        assert_eq!(
+            format_value(&ratio(0x12345678, 0x100000), &args),
+            "123.4567_8"
+        );
+        args.radix = 10;
+        assert_eq!(set("Space", &mut args).unwrap(), "Done");
+        assert_eq!(args.group_size, 4);
+        assert_eq!(format_value(&ratio(12_345_678, 1000), &args), "1 2345.678");
+        assert_eq!(set("period 3", &mut args).unwrap(), "Done");
+        assert_eq!(format_value(&ratio(12_345_678, 1000), &args), "12,345.678");
+        args.decimal_comma = true;
+        assert_eq!(format_value(&ratio(12_345_678, 1000), &args), "12.345,678");
+        assert_eq!(set("", &mut args).unwrap(), "period 3");
+        assert_eq!(set("tab", &mut args).unwrap_err(), "Invalid separator");
+        assert_eq!(
+            set("_ 17", &mut args).unwrap_err(),
+            "Group size must be between 1 and 16"
+        );
+        assert_eq!(set("_ 4 4", &mut args).unwrap_err(), "Too many arguments");
+    }
+
+    #[test]
+    fn fraction_display() {
+        let mut command_executor = CommandExecutor::new();
+        let mut args = Args {
+            fractional: true,
+            ..Args::default()
+        };
+        let mut set = |command: &str, arguments: &str, args: &mut Args| {
+            run_result(&mut command_executor, command, arguments, args, None, None)
+        };
+        let ratio =
+            |numer: i64, denom: i64| Value::Real(BigRational::new(numer.into(), denom.into()));
+
+        assert_eq!(set("maxdenominator", "", &mut args).unwrap(), "None");
+        assert_eq!(set("maxdenominator", "100", &mut args).unwrap(), "Done");
+        assert_eq!(set("maxdenominator", "", &mut args).unwrap(), "100");
+        assert_eq!(format_value(&ratio(70, 24), &args), "35/12");
+        assert_eq!(format_value(&ratio(1, 128), &args), "0.00781");
+        assert_eq!(
+            set("maxdenominator", "0", &mut args).unwrap_err(),
+            "Maximum denominator must be at least 1"
+        );
+
+        assert_eq!(set("factored", "true", &mut args).unwrap(), "Done");
+        assert_eq!(format_value(&ratio(70, 24), &args), "35/(2^2*3)");
+        assert_eq!(set("maxdenominator", "none", &mut args).unwrap(), "Done");
+        assert_eq!(format_value(&ratio(1, 128), &args), "1/(2^7)");
+
+        assert_eq!(set("factored", "false", &mut args).unwrap(), "Done");
+        assert_eq!(set("fractional", "", &mut args).unwrap(), "true");
+        assert_eq!(set("fractional", "mixed", &mut args).unwrap(), "Done");
+        assert_eq!(set("fractional", "", &mut args).unwrap(), "mixed");
+        assert_eq!(format_value(&ratio(-7, 3), &args), "-2 1/3");
+        assert_eq!(set("fractional", "both", &mut args).unwrap(), "Done");
+        assert_eq!(format_value(&ratio(7, 3), &args), "7/3 (2.33333)");
+        assert_eq!(format_value(&ratio(6, 3), &args), "2");
+        assert_eq!(set("fractional", "false", &mut args).unwrap(), "Done");
+        assert_eq!(set("fractional", "", &mut args).unwrap(), "false");
+        assert_eq!(format_value(&ratio(7, 3), &args), "2.33333");
+        assert_eq!(
+            set("fractional", "mix", &mut args).unwrap_err(),
+            "Invalid argument"
+        );
+    }
+
+    #[test]
+    fn register_command() {
+        let mut command_executor = CommandExecutor::new();
+        command_executor
+            .register_command(Box::new(GreetCommand))
+            .unwrap();
+
+        assert_eq!(run(&mut command_executor, "greet", "world"), "Hello, world");
+        assert_eq!(run(&mut command_executor, "hi", "there"), "Hello, there");
+        assert!(run(&mut command_executor, "help", "").contains("Says hello"));
+        assert_eq!(
+            run(&mut command_executor, "help", "hi"),
+            "Usage: /greet name"
+        );
+
+        assert!(command_executor
+            .register_command(Box::new(GreetCommand))
+            .is_err());
+        assert!(command_executor
+            .register_command(Box::new(HelpImpostorCommand))
+            .is_err());
+    }
+
+    #[test]
+    fn convert() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(
+            run(&mut command_executor, "convert", "5 mi to km"),
+            "8.04672 km"
+        );
+        assert_eq!(
+            run(&mut command_executor, "convert", "(1 + 2) * 4 KiB to bytes"),
+            "12288 B"
+        );
+        assert_eq!(
+            run(&mut command_executor, "convert", "1 GAL to floz"),
+            "128 floz"
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(
+            run(&mut command_executor, "diff", "(3 + 1)^2 ; 3^2 + 2 * 3 + 1"),
+            "Difference: 0\nRatio: 1\nExactly equal: yes"
+        );
+        assert_eq!(
+            run(&mut command_executor, "diff", "1/3 ; 0.33333"),
+            "Difference: 0.00000\nRatio: 1.00001\nExactly equal: no"
+        );
+        assert_eq!(
+            run(&mut command_executor, "diff", "2h ; 30m"),
+            "Difference: 1:30:00\nRatio: 4\nExactly equal: no"
+        );
+        assert_eq!(
+            run(&mut command_executor, "diff", "5 ; 0"),
+            "Difference: 5\nRatio: undefined\nExactly equal: no"
+        );
+    }
+
+    #[test]
+    fn plot() {
+        let mut command_executor = CommandExecutor::new();
+        let output = run(&mut command_executor, "plot", "2 * $x from 0 to 4");
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.len() > 4);
+        assert!(lines[0].starts_with("8 |"));
+        assert!(lines[lines.len() - 2].starts_with("0 |"));
+        let axis: Vec<&str> = lines[lines.len() - 1].split_whitespace().collect();
+        assert_eq!(axis, vec!["0", "4"]);
+        // The line rises from the bottom left corner to the top right one.
+        assert!(lines[0].ends_with(|c: char| c != '\u{2800}'));
+        assert_ne!(lines[lines.len() - 2].chars().nth(3), Some('\u{2800}'));
+    }
+
+    #[test]
+    fn table() {
+        let mut command_executor = CommandExecutor::new();
+        let mut table = |arguments: &str| {
+            run_result(
+                &mut command_executor,
+                "table",
+                arguments,
+                &mut Args::default(),
+                None,
+                None,
+            )
+        };
+
+        assert_eq!(
+            table("$x^2 for $x = 0 to 1 step 0.5").unwrap(),
+            " $x  $x^2\n---  ----\n0    0\n0.5  0.25\n1    1"
+        );
+        assert_eq!(
+            table("1/$n for $n=1 to -1").unwrap(),
+            "$n   1/$n\n--  -----\n 1      1\n 0  error\n-1     -1"
+        );
+        assert_eq!(
+            table("$x for $x = 0 to 1 step -1").unwrap_err(),
+            "The step size must go from the start value towards the end value"
+        );
+        assert_eq!(
+            table("$x for $x = 0 to 10000").unwrap_err(),
+            "The table would have more than 1000 rows"
+        );
+        assert_eq!(
+            table("1/$x for $x = 0 to 0").unwrap_err(),
+            table("1/0 for $x = 0 to 0").unwrap_err()
+        );
+        assert!(table("$x from 0 to 1").is_err());
+    }
+
+    #[test]
+    fn constants() {
+        let mut command_executor = CommandExecutor::new();
+        let list = run(&mut command_executor, "constants", "");
+        assert!(list.contains("\n  c "));
+        assert!(list.contains("Speed of light in vacuum"));
+        assert_eq!(
+            run(&mut command_executor, "const", "const.k_B"),
+            "const.k_B = 1.380649e-23 J/K\nBoltzmann constant (exact)"
+        );
+        assert_eq!(
+            run(&mut command_executor, "constants", "alpha"),
+            "const.alpha = 7.2973525693e-3\nFine-structure constant (measured)"
+        );
+    }
+
+    #[test]
+    fn custom_units() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(
+            run(&mut command_executor, "defunit", "furlong = 201.168 m"),
+            "1 furlong = 201.168 m"
+        );
+        assert_eq!(
+            run(&mut command_executor, "defunit", "fortnight = 14 * 24 h"),
+            "1 fortnight = 336 h"
+        );
+        assert_eq!(
+            run(&mut command_executor, "convert", "1 mi to furlong"),
+            "8 furlong"
+        );
+        assert_eq!(
+            run(&mut command_executor, "defunit", ""),
+            "furlong = 201.168 m\nfortnight = 1209600 s"
+        );
+        run(&mut command_executor, "undefunit", "furlong");
+        assert_eq!(
+            run(&mut command_executor, "defunit", ""),
+            "fortnight = 1209600 s"
+        );
+    }
+
+    #[test]
+    fn ip() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(
+            run(&mut command_executor, "ip", "network(192.168.1.10/24) + 1"),
+            "192.168.1.1"
+        );
+        assert_eq!(
+            run(&mut command_executor, "ip", " 192.168.1.10/20 "),
+            concat!(
+                "Address:   192.168.1.10\n",
+                "Netmask:   255.255.240.0\n",
+                "Network:   192.168.0.0\n",
+                "Broadcast: 192.168.15.255\n",
+                "Hosts:     4094"
+            )
+        );
+    }
+
+    #[test]
+    fn base64() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(run(&mut command_executor, "base64", "2^16 + 1"), "AQAB");
+        assert_eq!(run(&mut command_executor, "base64", "0"), "AA==");
+        assert_eq!(
+            run(&mut command_executor, "base64", " decode //4= "),
+            "65534"
+        );
+        assert_eq!(
+            run_result(
+                &mut command_executor,
+                "base64",
+                "decode a.b",
+                &mut Args::default(),
+                None,
+                None
+            )
+            .unwrap_err(),
+            "Invalid base64"
+        );
+    }
+
+    #[test]
+    fn roots() {
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(
+            run(&mut command_executor, "roots", "1 -3 2"),
+            "x = 1\nx = 2"
+        );
+        assert_eq!(
+            run(&mut command_executor, "roots", "$x^2 - 3 * $x + 2"),
+            "x = 1\nx = 2"
+        );
+        assert_eq!(
+            run(&mut command_executor, "roots", "1 0 -2"),
+            "x ~ -1.41421\nx ~ 1.41421"
+        );
+        assert_eq!(
+            run(&mut command_executor, "roots", "1 0 0 -1"),
+            "x = 1\n(2 complex roots not shown)"
+        );
+        assert_eq!(
+            run(&mut command_executor, "roots", "1 0 1"),
+            "No real roots\n(2 complex roots not shown)"
+        );
+    }
+
+    #[test]
+    fn snippets() {
+        let mut command_executor = CommandExecutor::new();
+        let mut inputs = InputHistory::new(false);
+        let mut snippet = |inputs: &mut InputHistory, arguments: &str| {
+            run_result(
+                &mut command_executor,
+                "snippet",
+                arguments,
+                &mut Args::default(),
+                Some(inputs),
+                None,
+            )
+        };
+
+        assert_eq!(snippet(&mut inputs, "").unwrap(), "There are no snippets");
+        assert_eq!(
+            snippet(&mut inputs, "save pyth = sqrt(?a^2 + ?b^2)").unwrap(),
+            "Done"
+        );
+        assert_eq!(
+            snippet(&mut inputs, "save tax = ?x * 1.07").unwrap(),
+            "Done"
+        );
+        assert_eq!(
+            snippet(&mut inputs, "").unwrap(),
+            "pyth = sqrt(?a^2 + ?b^2)\ntax = ?x * 1.07"
+        );
+
+        assert_eq!(snippet(&mut inputs, "insert pyth").unwrap(), "");
+        assert_eq!(inputs.current_line(), "sqrt(?a^2 + ?b^2)");
+        assert_eq!(inputs.take_requested_cursor_pos(), Some(5));
+        assert_eq!(inputs.take_requested_cursor_pos(), None);
+
+        assert_eq!(
+            snippet(&mut inputs, "save 2x = ?a * 2").unwrap_err(),
+            "Expected a snippet name"
+        );
+        assert_eq!(
+            snippet(&mut inputs, "save pyth").unwrap_err(),
+            "Expected '=' after the snippet name"
+        );
+        assert_eq!(
+            snippet(&mut inputs, "paste pyth").unwrap_err(),
+            "Unknown action: 'paste'"
+        );
+        assert_eq!(snippet(&mut inputs, "delete pyth").unwrap(), "Done");
+        assert_eq!(
+            snippet(&mut inputs, "insert pyth").unwrap_err(),
+            "No snippet named 'pyth' has been saved"
+        );
+    }
+
+    #[test]
+    fn history_and_replay() {
+        let mut command_executor = CommandExecutor::new();
+        let mut inputs = InputHistory::new(false);
+        let mut vars = VariableStore::new();
+        let mut command = |inputs: &mut InputHistory, vars: &mut VariableStore, input: &str| {
+            // The input being handled is always already in the history.
+            inputs.import(vec![input.to_string()], None).unwrap();
+            let (command, arguments) = input[1..].split_once(' ').unwrap_or((&input[1..], ""));
+            run_result(
+                &mut command_executor,
+                command,
+                arguments,
+                &mut Args::default(),
+                Some(inputs),
+                Some(vars),
+            )
+        };
+
+        assert_eq!(
+            command(&mut inputs, &mut vars, "/history").unwrap(),
+            "The input history is empty"
+        );
+        inputs
+            .import(vec!["$x = 2".to_string(), "$x * 10".to_string()], None)
+            .unwrap();
+        vars.update(
+            Variable {
+                name: "$x".into(),
+                value: BigInt::from(2).into(),
+            },
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            command(&mut inputs, &mut vars, "/history").unwrap(),
+            "1: $x * 10\n2: $x = 2\n3: /history"
+        );
+        assert_eq!(
+            command(&mut inputs, &mut vars, "/history 1").unwrap(),
+            "1: /history"
+        );
+
+        vars.update(
+            Variable {
+                name: "$x".into(),
+                value: BigInt::from(3).into(),
+            },
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            command(&mut inputs, &mut vars, "/replay 3").unwrap(),
+            "$x * 10  = 30"
+        );
+        assert_eq!(vars.result_count(), 1);
+        assert_eq!(
+            command(&mut inputs, &mut vars, "/replay 5").unwrap(),
+            "$x = 2  = 2"
+        );
+        assert_eq!(
+            command(&mut inputs, &mut vars, "/replay 5").unwrap(),
+            "$x * 10  = 20"
+        );
+        assert_eq!(
+            command(&mut inputs, &mut vars, "/replay 1").unwrap_err(),
+            "'/replay 5' is a command; only expressions can be replayed"
+        );
+        assert_eq!(
+            command(&mut inputs, &mut vars, "/replay 100").unwrap_err(),
+            "There is no input from 100 input(s) ago"
+        );
+        assert_eq!(
+            command(&mut inputs, &mut vars, "/replay 0").unwrap_err(),
+            "Input number must be at least 1"
+        );
+    }
+
+    #[test]
+    fn keystroke_macro() {
+        let mut command_executor = CommandExecutor::new();
+        let execute = |command_executor: &mut CommandExecutor, arguments: &str| {
+            run_result(
+                command_executor,
+                "macro",
+                arguments,
+                &mut Args::default(),
+                None,
+                None,
+            )
+        };
+        assert_eq!(
+            execute(&mut command_executor, "").unwrap(),
+            "No keys are recorded"
+        );
+        assert_eq!(
+            execute(&mut command_executor, "play").unwrap_err(),
+            "No keys are recorded; press Alt+R to start recording"
+        );
+
+        let keystroke_macro = command_executor.keystroke_macro_mut();
+        keystroke_macro.start_recording();
+        keystroke_macro.record(KeyEvent::new(KeyCode::Char('1'), KeyModifiers::NONE));
+        keystroke_macro.record(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(
+            execute(&mut command_executor, "").unwrap(),
+            "Recording. No keys are recorded"
+        );
+        assert_eq!(
+            execute(&mut command_executor, "play").unwrap_err(),
+            "Stop recording with Alt+R before playing the recorded keys"
+        );
+        command_executor.keystroke_macro_mut().stop_recording();
+        assert_eq!(
+            execute(&mut command_executor, "").unwrap(),
+            "2 keys are recorded"
+        );
+
+        assert_eq!(
+            execute(&mut command_executor, "play 0").unwrap_err(),
+            "Expected a number of times from 1 to 1000"
+        );
+        assert_eq!(execute(&mut command_executor, "play 3").unwrap(), "");
+        let keys = command_executor.keystroke_macro_mut().take_requested_keys();
+        assert_eq!(keys.len(), 6);
+
+        assert_eq!(
+            execute(&mut command_executor, "clear all").unwrap_err(),
+            "Unexpected argument"
+        );
+        assert_eq!(execute(&mut command_executor, "clear").unwrap(), "Done");
+        assert_eq!(
+            execute(&mut command_executor, "").unwrap(),
+            "No keys are recorded"
+        );
+    }
+
+    #[test]
+    fn rollback() {
+        let mut command_executor = CommandExecutor::new();
+        let mut db = SavedData::open_in_memory().unwrap();
+        let mut vars = VariableStore::new();
+        for value in [1, 2, 3] {
+            let id = db.add_to_input_history(&format!("$x = {}", value)).unwrap();
+            let variable = Variable {
+                name: "$x".into(),
+                value: BigInt::from(value).into(),
+            };
+            vars.update(variable, Some(id), Some(&mut db)).unwrap();
+        }
+        let mut rollback = |arguments: &str, db: &mut SavedData, vars: &mut VariableStore| {
+            run_with_db(
+                &mut command_executor,
+                &format!("/rollback {}", arguments),
+                db,
+                vars,
+            )
+        };
+
+        assert_eq!(rollback("$x 2", &mut db, &mut vars).unwrap(), "Set $x to 1");
+        assert_eq!(
+            vars.get("$x".into(), None).unwrap().unwrap().value,
+            Value::from(BigInt::from(1))
+        );
+        assert_eq!(
+            db.get_variable("$x").unwrap().unwrap().value,
+            Value::from(BigInt::from(1))
+        );
+        // The value that was replaced is kept, so the rollback can itself be undone.
+        assert_eq!(rollback("$x", &mut db, &mut vars).unwrap(), "Set $x to 3");
+        assert_eq!(
+            db.get_variable_versions("$x").unwrap(),
+            [1, 3, 2, 1].map(|value| Value::from(BigInt::from(value)))
+        );
+
+        assert_eq!(
+            rollback("$x 5", &mut db, &mut vars).unwrap_err(),
+            "$x does not have a value from 5 version(s) ago"
+        );
+        assert_eq!(
+            rollback("$x 0", &mut db, &mut vars).unwrap_err(),
+            "Number of versions must be at least 1"
+        );
+        assert_eq!(
+            rollback("$y", &mut db, &mut vars).unwrap_err(),
+            "$y does not have a value from 1 version(s) ago"
+        );
+    }
+
+    #[test]
+    fn sessions() {
+        let mut command_executor = CommandExecutor::new();
+        let mut db = SavedData::open_in_memory().unwrap();
+        let mut vars = VariableStore::new();
+        let id = db.add_to_input_history("1 + 1").unwrap();
+        db.set_input_history_result(id, "2").unwrap();
+        let session = db.current_session().unwrap();
+        let mut sessions = |input: &str, db: &mut SavedData| {
+            run_with_db(&mut command_executor, input, db, &mut vars)
+        };
+
+        let listing = sessions("/sessions", &mut db).unwrap();
+        assert!(listing.starts_with(&format!("{}: ", session)));
+        assert!(listing.ends_with(" (2 inputs) (current)"));
+        assert_eq!(
+            sessions(&format!("/sessions {}", session), &mut db).unwrap(),
+            format!("1 + 1  = 2\n/sessions\n/sessions {}", session)
+        );
+        assert_eq!(
+            sessions(&format!("/sessions {}", session + 1), &mut db).unwrap_err(),
+            format!("No inputs from session {} are in the history", session + 1)
+        );
+        assert_eq!(
+            sessions("/sessions 1 2", &mut db).unwrap_err(),
+            "Expected at most one session id"
+        );
+    }
+
+    #[test]
+    fn export_and_import_history() {
+        let path =
+            std::env::temp_dir().join(format!("bcalc_test_history_{}.txt", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        let mut command_executor = CommandExecutor::new();
+        let mut inputs = InputHistory::new(false);
+        inputs
+            .import_with_results(
+                vec![
+                    ("$x = 2".to_string(), Some("2".to_string())),
+                    ("/radix 16".to_string(), None),
+                    ("$x * 10".to_string(), Some("14".to_string())),
+                ],
+                None,
+            )
+            .unwrap();
+        let mut history_command = |command: &str, inputs: &mut InputHistory| {
+            run_result(
+                &mut command_executor,
+                command,
+                path_str,
+                &mut Args::default(),
+                Some(inputs),
+                None,
+            )
+        };
+
+        assert_eq!(
+            history_command("exporthistory", &mut inputs).unwrap(),
+            "Exported 3 inputs"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "$x = 2\t2\n/radix 16\n$x * 10\t14\n"
+        );
+        let mut imported = InputHistory::new(false);
+        assert_eq!(
+            history_command("importhistory", &mut imported).unwrap(),
+            "Imported 3 inputs"
+        );
+        assert_eq!(imported.session_inputs(), inputs.session_inputs());
+        assert_eq!(imported.session_results(), inputs.session_results());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn transcript() {
+        let path = std::env::temp_dir().join(format!(
+            "bcalc_test_command_transcript_{}.txt",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let mut command_executor = CommandExecutor::new();
+        assert_eq!(
+            run(&mut command_executor, "transcript", ""),
+            "Not recording"
+        );
+        assert_eq!(
+            run(
+                &mut command_executor,
+                "transcript",
+                &format!("on {}", path.display())
+            ),
+            "Done"
+        );
+        assert_eq!(
+            run(&mut command_executor, "transcript", ""),
+            format!("Recording to '{}'", path.display())
+        );
+        command_executor.record_in_transcript("2 * 3", "6").unwrap();
+        assert_eq!(run(&mut command_executor, "transcript", "off"), "Done");
+        command_executor.record_in_transcript("4", "4").unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].ends_with("] Transcript started"));
+        assert!(lines[1].ends_with("] # 2 * 3"));
+        assert_eq!(lines[2], "6");
+        assert!(lines[3].ends_with("] Transcript stopped"));
+    }
+
+    #[test]
+    fn currency_rates() {
+        let path =
+            std::env::temp_dir().join(format!("bcalc_test_rates_{}.csv", std::process::id()));
+        std::fs::write(&path, "currency,rate\nUSD,1\nEUR,0.8\n").unwrap();
+        let mut command_executor = CommandExecutor::new();
+        let output = run(
+            &mut command_executor,
+            "rates",
+            &format!("load {}", path.display()),
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(output.ends_with(":\n  EUR 0.8\n  USD 1"));
+        assert_eq!(
+            run(&mut command_executor, "convert", "10 $ to eur"),
+            "8 EUR"
+        );
     }
 }
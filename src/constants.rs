@@ -0,0 +1,217 @@
+//! Physical constants that can be used in expressions as `const.name`, such as `const.c` for the
+//! speed of light. Values are the CODATA 2018 recommended values in SI units. Constants that are
+//! exact by the definition of the SI units are stored exactly; the rest are stored to the precision
+//! that they are known to.
+//!
+//! The mathematical constants `pi`, `e`, and `tau` are written without a prefix. Since they are
+//! irrational, they have no stored value and are computed to the current precision when used.
+
+use crate::operations::parse_decimal;
+use num::rational::BigRational;
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Written before the name of a constant to use it in an expression, as in `const.c`.
+pub const CONSTANT_PREFIX: &str = "const.";
+
+pub struct PhysicalConstant {
+    pub name: &'static str,
+    /// The value in SI units, written as a decimal number.
+    pub value: &'static str,
+    /// The SI unit that the value is in, or an empty string if the constant has no units.
+    pub unit: &'static str,
+    pub description: &'static str,
+    /// Whether the value is exact, as opposed to being a measurement.
+    pub exact: bool,
+}
+
+impl PhysicalConstant {
+    pub fn value(&self) -> BigRational {
+        // Every value in `PHYSICAL_CONSTANTS` is checked by the tests below.
+        parse_decimal(self.value).expect("Physical constants should be valid decimal numbers")
+    }
+}
+
+// Keeps the table below to one line per constant.
+const fn constant(
+    name: &'static str,
+    value: &'static str,
+    unit: &'static str,
+    description: &'static str,
+    exact: bool,
+) -> PhysicalConstant {
+    PhysicalConstant {
+        name,
+        value,
+        unit,
+        description,
+        exact,
+    }
+}
+
+pub const PHYSICAL_CONSTANTS: &[PhysicalConstant] = &[
+    constant("c", "299792458", "m/s", "Speed of light in vacuum", true),
+    constant(
+        "G",
+        "6.67430e-11",
+        "m^3/(kg s^2)",
+        "Newtonian constant of gravitation",
+        false,
+    ),
+    constant("h", "6.62607015e-34", "J s", "Planck constant", true),
+    constant(
+        "hbar",
+        "1.054571817e-34",
+        "J s",
+        "Reduced Planck constant",
+        false,
+    ),
+    constant("k_B", "1.380649e-23", "J/K", "Boltzmann constant", true),
+    constant("N_A", "6.02214076e23", "1/mol", "Avogadro constant", true),
+    constant("e", "1.602176634e-19", "C", "Elementary charge", true),
+    constant(
+        "R",
+        "8.31446261815324",
+        "J/(mol K)",
+        "Molar gas constant",
+        true,
+    ),
+    constant(
+        "F",
+        "96485.3321233100184",
+        "C/mol",
+        "Faraday constant",
+        true,
+    ),
+    constant(
+        "eps_0",
+        "8.8541878128e-12",
+        "F/m",
+        "Vacuum electric permittivity",
+        false,
+    ),
+    constant(
+        "mu_0",
+        "1.25663706212e-6",
+        "N/A^2",
+        "Vacuum magnetic permeability",
+        false,
+    ),
+    constant(
+        "sigma",
+        "5.670374419e-8",
+        "W/(m^2 K^4)",
+        "Stefan-Boltzmann constant",
+        false,
+    ),
+    constant("m_e", "9.1093837015e-31", "kg", "Electron mass", false),
+    constant("m_p", "1.67262192369e-27", "kg", "Proton mass", false),
+    constant("m_n", "1.67492749804e-27", "kg", "Neutron mass", false),
+    constant(
+        "u",
+        "1.66053906660e-27",
+        "kg",
+        "Atomic mass constant",
+        false,
+    ),
+    constant("a_0", "5.29177210903e-11", "m", "Bohr radius", false),
+    constant(
+        "alpha",
+        "7.2973525693e-3",
+        "",
+        "Fine-structure constant",
+        false,
+    ),
+    constant("eV", "1.602176634e-19", "J", "Electron volt", true),
+    constant(
+        "g_n",
+        "9.80665",
+        "m/s^2",
+        "Standard acceleration of gravity",
+        true,
+    ),
+    constant("atm", "101325", "Pa", "Standard atmosphere", true),
+];
+
+/// Looks up a constant by its name, without the `const.` prefix. Names are case sensitive, since
+/// some constants differ only by case (ex: `G` and `g_n`).
+pub fn find_constant(name: &str) -> Option<&'static PhysicalConstant> {
+    PHYSICAL_CONSTANTS
+        .iter()
+        .find(|constant| constant.name == name)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MathConstant {
+    Pi,
+    E,
+    /// `2 * pi`.
+    Tau,
+}
+
+impl MathConstant {
+    pub const ALL: [MathConstant; 3] = [MathConstant::Pi, MathConstant::E, MathConstant::Tau];
+
+    /// The name that the constant is written as in expressions.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MathConstant::Pi => "pi",
+            MathConstant::E => "e",
+            MathConstant::Tau => "tau",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MathConstant> {
+        MathConstant::ALL
+            .into_iter()
+            .find(|constant| constant.name() == name)
+    }
+}
+
+impl fmt::Display for MathConstant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod constant_tests {
+    use crate::{
+        constants::{find_constant, PHYSICAL_CONSTANTS},
+        operations::parse_decimal,
+    };
+    use num::{rational::BigRational, BigInt};
+    use std::collections::HashSet;
+
+    #[test]
+    fn values_parse() {
+        for constant in PHYSICAL_CONSTANTS {
+            assert!(
+                parse_decimal(constant.value).is_some(),
+                "Invalid value for {}",
+                constant.name
+            );
+        }
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let names: HashSet<&str> = PHYSICAL_CONSTANTS.iter().map(|c| c.name).collect();
+        assert_eq!(names.len(), PHYSICAL_CONSTANTS.len());
+    }
+
+    #[test]
+    fn derived_exact_values() {
+        let value = |name: &str| find_constant(name).unwrap().value();
+        assert_eq!(value("R"), value("N_A") * value("k_B"));
+        assert_eq!(value("F"), value("N_A") * value("e"));
+        assert_eq!(
+            value("c"),
+            BigRational::from_integer(BigInt::from(299_792_458))
+        );
+        assert!(find_constant("C").is_none());
+    }
+}
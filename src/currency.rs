@@ -0,0 +1,208 @@
+//! Loading of currency exchange rates from a local file so that currencies can be used as units.
+//! Rates are never fetched from the network; the file is expected to be kept up to date by some
+//! other means.
+//!
+//! Two formats are supported. A file whose name ends in `.csv` is read as CSV, with one currency
+//! per line given as its code followed by its rate (ex: `EUR,0.92`). Blank lines and lines starting
+//! with `#` are ignored, as is a header line. Any other file is read as JSON, either as an object
+//! mapping codes to rates or as an object with a `rates` member holding such an object and an
+//! optional `base` member naming the currency that the rates are relative to. The latter is what
+//! many exchange rate services produce:
+//!
+//! ```json
+//! {"base": "USD", "rates": {"EUR": 0.92, "JPY": 149.5}}
+//! ```
+//!
+//! Each rate is the amount of that currency that is equivalent to one unit of the base currency.
+//! Rates are read exactly as written rather than being rounded to floating point values.
+
+use crate::operations::parse_decimal;
+use num::{rational::BigRational, One, Signed};
+use std::{
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub struct RatesFileError {
+    message: String,
+}
+
+impl RatesFileError {
+    pub fn new<S: Into<String>>(message: S) -> RatesFileError {
+        RatesFileError {
+            message: message.into(),
+        }
+    }
+}
+
+impl Error for RatesFileError {}
+
+impl fmt::Display for RatesFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// A currency code and its rate.
+pub type Rate = (String, BigRational);
+
+/// The rates read from a rates file.
+#[derive(Clone, Debug)]
+pub struct CurrencyRates {
+    /// The file that the rates were read from.
+    pub path: PathBuf,
+    /// The currency that the rates are relative to, if the file specifies it.
+    pub maybe_base: Option<String>,
+    /// Each currency code and its rate, sorted by code.
+    pub rates: Vec<Rate>,
+}
+
+impl CurrencyRates {
+    pub fn load(path: &Path) -> Result<CurrencyRates, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        let is_csv = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+        let (maybe_base, mut rates) = if is_csv {
+            (None, parse_csv_rates(&contents)?)
+        } else {
+            parse_json_rates(&contents)?
+        };
+
+        for (code, rate) in rates.iter() {
+            if code.is_empty() || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err(
+                    RatesFileError::new(format!("Invalid currency code: '{}'", code)).into(),
+                );
+            }
+            if !rate.is_positive() {
+                return Err(
+                    RatesFileError::new(format!("Rate for {} is not positive", code)).into(),
+                );
+            }
+        }
+        if let Some(base) = &maybe_base {
+            if !rates.iter().any(|(code, _)| code == base) {
+                rates.push((base.clone(), BigRational::one()));
+            }
+        }
+        rates.sort_by(|(code_1, _), (code_2, _)| code_1.cmp(code_2));
+
+        Ok(CurrencyRates {
+            path: path.to_path_buf(),
+            maybe_base,
+            rates,
+        })
+    }
+}
+
+fn parse_csv_rates(contents: &str) -> Result<Vec<Rate>, RatesFileError> {
+    let mut rates = Vec::new();
+    let mut first_line = true;
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid_line =
+            || RatesFileError::new(format!("Invalid line {}: '{}'", index + 1, line));
+        let (code, rate) = line.split_once(',').ok_or_else(invalid_line)?;
+        let code = code.trim().trim_matches('"');
+        let rate = rate.trim().trim_matches('"');
+        match parse_decimal(rate) {
+            Some(rate) => rates.push((code.to_string(), rate)),
+            // The first line is allowed to be a header, like "currency,rate".
+            None if first_line => {}
+            None => return Err(invalid_line()),
+        }
+        first_line = false;
+    }
+    Ok(rates)
+}
+
+fn parse_json_rates(contents: &str) -> Result<(Option<String>, Vec<Rate>), Box<dyn Error>> {
+    let root: serde_json::Value = serde_json::from_str(contents)?;
+    let root = root
+        .as_object()
+        .ok_or_else(|| RatesFileError::new("Expected a JSON object"))?;
+
+    let (maybe_base, rates_object) = match root.get("rates") {
+        Some(rates) => {
+            let maybe_base = match root.get("base") {
+                Some(serde_json::Value::String(base)) => Some(base.clone()),
+                Some(_) => {
+                    return Err(RatesFileError::new("Expected \"base\" to be a string").into())
+                }
+                None => None,
+            };
+            let rates = rates
+                .as_object()
+                .ok_or_else(|| RatesFileError::new("Expected \"rates\" to be an object"))?;
+            (maybe_base, rates)
+        }
+        None => (None, root),
+    };
+
+    let mut rates = Vec::new();
+    for (code, rate) in rates_object {
+        // Numbers are converted back to the text that they were written as, so that they can be
+        // read exactly.
+        let text = match rate {
+            serde_json::Value::Number(number) => number.to_string(),
+            serde_json::Value::String(text) => text.clone(),
+            _ => return Err(RatesFileError::new(format!("Invalid rate for {}", code)).into()),
+        };
+        let rate = parse_decimal(&text)
+            .ok_or_else(|| RatesFileError::new(format!("Invalid rate for {}: '{}'", code, text)))?;
+        rates.push((code.clone(), rate));
+    }
+    Ok((maybe_base, rates))
+}
+
+#[cfg(test)]
+mod currency_tests {
+    use crate::currency::{parse_csv_rates, parse_json_rates};
+    use num::{rational::BigRational, BigInt};
+
+    fn ratio(numer: i64, denom: i64) -> BigRational {
+        BigRational::new(BigInt::from(numer), BigInt::from(denom))
+    }
+
+    #[test]
+    fn csv() {
+        let rates =
+            parse_csv_rates("# Rates\ncurrency,rate\nEUR,0.92\n\n\"JPY\", 149.5\n").unwrap();
+        assert_eq!(
+            rates,
+            vec![
+                ("EUR".to_string(), ratio(92, 100)),
+                ("JPY".to_string(), ratio(299, 2))
+            ]
+        );
+        assert!(parse_csv_rates("EUR,0.92\nJPY,lots\n").is_err());
+        assert!(parse_csv_rates("EUR 0.92\n").is_err());
+    }
+
+    #[test]
+    fn json() {
+        let (maybe_base, rates) =
+            parse_json_rates(r#"{"base": "USD", "rates": {"EUR": 0.92, "JPY": "149.5"}}"#).unwrap();
+        assert_eq!(maybe_base.as_deref(), Some("USD"));
+        assert_eq!(
+            rates,
+            vec![
+                ("EUR".to_string(), ratio(92, 100)),
+                ("JPY".to_string(), ratio(299, 2))
+            ]
+        );
+
+        let (maybe_base, rates) = parse_json_rates(r#"{"GBP": 0.79}"#).unwrap();
+        assert_eq!(maybe_base, None);
+        assert_eq!(rates, vec![("GBP".to_string(), ratio(79, 100))]);
+
+        assert!(parse_json_rates(r#"{"EUR": true}"#).is_err());
+        assert!(parse_json_rates(r#"[0.92]"#).is_err());
+    }
+}
@@ -0,0 +1,23 @@
+//! Functions supplied at runtime rather than built into bcalc. They are registered with
+//! `Tokenizer::register_function` (or `Calculator::register_function`), after which they can be
+//! called in expressions just like `max` and `min`.
+
+use num::rational::BigRational;
+use std::fmt;
+
+pub trait CustomFunction: Send + Sync {
+    /// The name used to call the function in expressions. See `Tokenizer::register_function` for
+    /// the restrictions on it.
+    fn name(&self) -> &str;
+
+    /// Evaluates the function. The arguments have already been evaluated. If the function does not
+    /// accept the arguments given, an error message should be returned, which will be shown to the
+    /// user pointing at the function's name.
+    fn call(&self, arguments: &[BigRational]) -> Result<BigRational, String>;
+}
+
+impl fmt::Debug for dyn CustomFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CustomFunction({})", self.name())
+    }
+}
@@ -1,21 +1,61 @@
 use crate::{
     position::{MaybePositioned, Positioned},
-    token::{FunctionNameToken, Token},
+    suggestion::format_suggestions,
+    token::{FunctionNameToken, Token, UnaryOperatorToken},
+    units::Dimension,
 };
 use std::fmt;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug)]
 pub enum CalculatorFailure {
     /// Indicates an error that is the user's fault somehow (ex: invalid syntax, divided by 0,
     /// attempted to use a variable when the variable store is not available).
-    InputError(MaybePositioned<String>),
+    InputError(MaybePositioned<InputErrorKind>),
     /// Indicates an error that is not the user's fault, such as failure to read the database.
     RuntimeError(Box<dyn std::error::Error>),
 }
 
+/// Describes what was wrong with the user's input. Displaying it gives a message suitable for
+/// showing to the user.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum InputErrorKind {
+    /// The input could not be split into tokens.
+    Parse(ParseError),
+    /// The tokens did not form a valid expression.
+    Syntax(SyntaxError),
+    /// The expression was valid but could not be evaluated.
+    Math(MathExecutionError),
+    /// Something that the input needed, such as the variable store, is not available.
+    MissingCapability(MissingCapabilityError),
+    /// Any other problem, such as invalid arguments to a command.
+    Other(String),
+}
+
+impl fmt::Display for InputErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputErrorKind::Parse(e) => fmt::Display::fmt(e, f),
+            InputErrorKind::Syntax(e) => fmt::Display::fmt(e, f),
+            InputErrorKind::Math(e) => fmt::Display::fmt(e, f),
+            InputErrorKind::MissingCapability(e) => fmt::Display::fmt(e, f),
+            InputErrorKind::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<String> for InputErrorKind {
+    fn from(item: String) -> Self {
+        InputErrorKind::Other(item)
+    }
+}
+
 impl From<Positioned<String>> for CalculatorFailure {
     fn from(item: Positioned<String>) -> Self {
-        CalculatorFailure::InputError(item.into())
+        CalculatorFailure::InputError(item.map(InputErrorKind::Other).into())
     }
 }
 
@@ -89,10 +129,28 @@ impl fmt::Display for CalculatorDatabaseInconsistencyError {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ParseError {
     NonAscii,
     InvalidNumber(String),
     InvalidVariable(String),
+    UnknownConstant(String),
+    // A word that isn't a number but is close to the name of a function or unit. Contains the word
+    // and the names that it is close to.
+    UnknownName(String, Vec<String>),
+}
+
+impl ParseError {
+    /// The text from the input that the error quotes, if any.
+    pub fn quoted_text_mut(&mut self) -> Option<&mut String> {
+        match self {
+            ParseError::NonAscii => None,
+            ParseError::InvalidNumber(s)
+            | ParseError::InvalidVariable(s)
+            | ParseError::UnknownConstant(s)
+            | ParseError::UnknownName(s, _) => Some(s),
+        }
+    }
 }
 
 impl fmt::Display for ParseError {
@@ -101,17 +159,27 @@ impl fmt::Display for ParseError {
             ParseError::NonAscii => write!(f, "Non-ASCII data in input"),
             ParseError::InvalidNumber(s) => write!(f, "Unable to parse number: '{}'", s),
             ParseError::InvalidVariable(s) => write!(f, "Invalid variable name: '{}'", s),
+            ParseError::UnknownConstant(s) => write!(f, "Unknown constant: '{}'", s),
+            ParseError::UnknownName(s, suggestions) => {
+                write!(
+                    f,
+                    "Unknown name: '{}'{}",
+                    s,
+                    format_suggestions(suggestions)
+                )
+            }
         }
     }
 }
 
 impl From<Positioned<ParseError>> for CalculatorFailure {
     fn from(item: Positioned<ParseError>) -> Self {
-        CalculatorFailure::InputError(item.map(|v| v.to_string()).into())
+        CalculatorFailure::InputError(item.map(InputErrorKind::Parse).into())
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SyntaxError {
     NoInput,
     UnexpectedToken(Token),
@@ -122,7 +190,22 @@ pub enum SyntaxError {
     CommaWithoutOperandBefore,
     CommaWithoutOperandAfter,
     FunctionWithoutParensOrArgument(FunctionNameToken),
+    // A function, or an operator written like one such as `sqrt`, was called without parentheses
+    // while strict mode was on.
+    FunctionWithoutParens(Token),
     MissingOperator,
+    // A CIDR block, like `10.0.0.0/8`, was used somewhere other than as a function argument.
+    CidrOutsideFunction,
+    // `let` wasn't followed by a variable and `=`.
+    LetWithoutVariable,
+    // The value bound by a `let` wasn't followed by `in`.
+    LetWithoutIn,
+    // The `?` of a conditional wasn't followed by a `:`.
+    ConditionalWithoutColon,
+    // An absolute value bar, like the ones in `|$x|`, without a matching bar.
+    MismatchedBar,
+    // A value was assigned to one of the variables that hold previous results, like `$ans`.
+    ReservedVariable(String),
 }
 
 impl fmt::Display for SyntaxError {
@@ -152,52 +235,147 @@ impl fmt::Display for SyntaxError {
                     function
                 )
             }
+            SyntaxError::FunctionWithoutParens(function) => {
+                write!(
+                    f,
+                    "{} must be called with parentheses while strict mode is on",
+                    function
+                )
+            }
             SyntaxError::MissingOperator => {
                 write!(f, "Missing an operator between two consecutive operands")
             }
+            SyntaxError::CidrOutsideFunction => {
+                write!(f, "CIDR blocks can only be used as function arguments")
+            }
+            SyntaxError::ReservedVariable(name) => {
+                write!(
+                    f,
+                    "{} holds a previous result and can't be assigned to",
+                    name
+                )
+            }
+            SyntaxError::LetWithoutVariable => {
+                write!(
+                    f,
+                    "let must be followed by a variable and =, like let $x = 1 in $x"
+                )
+            }
+            SyntaxError::LetWithoutIn => {
+                write!(
+                    f,
+                    "let is missing the in that separates its value from its body"
+                )
+            }
+            SyntaxError::ConditionalWithoutColon => {
+                write!(f, "? must be followed by a value, :, and another value")
+            }
+            SyntaxError::MismatchedBar => write!(f, "Mismatched absolute value bar"),
         }
     }
 }
 
 impl From<Positioned<SyntaxError>> for CalculatorFailure {
     fn from(item: Positioned<SyntaxError>) -> Self {
-        CalculatorFailure::InputError(item.map(|v| v.to_string()).into())
+        CalculatorFailure::InputError(item.map(InputErrorKind::Syntax).into())
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum MathExecutionError {
-    UnknownVariable(String),
+    // Contains the name of the variable and the names of known variables that are close to it.
+    UnknownVariable(String, Vec<String>),
     DivisionByZero,
     FunctionNeedsArguments(FunctionNameToken),
-    ImaginaryResult,
+    // An operator or function that only works with real numbers was given a complex one. Contains
+    // the description of the operator or function.
+    NotReal(String),
+    // An operator or function that needs exact values was given one with an uncertainty. Contains
+    // the description of the operator or function.
+    Uncertain(String),
+    // A value with an uncertainty was combined with a complex number.
+    UncertainComplex,
+    // A `CustomFunction` failed. Contains the message that it returned.
+    CustomFunctionFailed(FunctionNameToken, String),
+    // A built in function was given arguments that it can't accept. Contains a description of the
+    // problem.
+    InvalidArgument(FunctionNameToken, String),
+    // A built in operator was given an operand that it can't accept, like a negative number for
+    // `!`. Contains a description of the problem.
+    InvalidOperand(UnaryOperatorToken, String),
+    // The `Interrupt` that the evaluation was given was cancelled.
+    Cancelled,
+    // Values with different dimensions (such as a duration and a plain number) were combined in a
+    // way that requires them to have the same dimension.
+    DimensionMismatch(Dimension, Dimension),
+    // A value with units was raised to a power that would give it fractional or unknown units.
+    InvalidDimensionPower(Dimension),
+    // A function that produces multiple values, like `divmod`, was used somewhere that needs a
+    // single value.
+    MultipleValues(FunctionNameToken),
 }
 
 impl fmt::Display for MathExecutionError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            MathExecutionError::UnknownVariable(name) => write!(f, "Unknown variable: {}", name),
+            MathExecutionError::UnknownVariable(name, suggestions) => write!(
+                f,
+                "Unknown variable: {}{}",
+                name,
+                format_suggestions(suggestions)
+            ),
             MathExecutionError::DivisionByZero => write!(f, "Cannot divide by 0"),
             MathExecutionError::FunctionNeedsArguments(function) => {
                 write!(f, "{} has no arguments but requires them", function)
             }
-            MathExecutionError::ImaginaryResult => {
-                write!(f, "Unable to take the root of a negative number except unless the degree is an odd integer")
+            MathExecutionError::NotReal(operation) => {
+                write!(f, "{} can only be used with real numbers", operation)
+            }
+            MathExecutionError::Uncertain(operation) => {
+                write!(f, "{} can't be used with uncertain values", operation)
             }
+            MathExecutionError::UncertainComplex => write!(
+                f,
+                "Values with an uncertainty can't be combined with complex numbers"
+            ),
+            MathExecutionError::CustomFunctionFailed(function, message)
+            | MathExecutionError::InvalidArgument(function, message) => {
+                write!(f, "{}: {}", function, message)
+            }
+            MathExecutionError::InvalidOperand(operator, message) => {
+                write!(f, "{}: {}", operator, message)
+            }
+            MathExecutionError::Cancelled => write!(f, "Calculation cancelled"),
+            MathExecutionError::DimensionMismatch(dimension_1, dimension_2) => {
+                write!(f, "Mismatched units: {} and {}", dimension_1, dimension_2)
+            }
+            MathExecutionError::InvalidDimensionPower(dimension) => write!(
+                f,
+                "Unable to determine the units of a value measuring {} raised to this power",
+                dimension
+            ),
+            MathExecutionError::MultipleValues(function) => write!(
+                f,
+                "{} produces multiple values, so it can only be used as a whole input",
+                function
+            ),
         }
     }
 }
 
 impl From<Positioned<MathExecutionError>> for CalculatorFailure {
     fn from(item: Positioned<MathExecutionError>) -> Self {
-        CalculatorFailure::InputError(item.map(|v| v.to_string()).into())
+        CalculatorFailure::InputError(item.map(InputErrorKind::Math).into())
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum MissingCapabilityError {
     NoVariableStore,
     NoDatabase,
+    NoInputHistory,
 }
 
 impl fmt::Display for MissingCapabilityError {
@@ -205,18 +383,53 @@ impl fmt::Display for MissingCapabilityError {
         match self {
             MissingCapabilityError::NoVariableStore => write!(f, "Variable store unavailable"),
             MissingCapabilityError::NoDatabase => write!(f, "Database unavailable"),
+            MissingCapabilityError::NoInputHistory => write!(f, "Input history unavailable"),
         }
     }
 }
 
 impl From<MissingCapabilityError> for CalculatorFailure {
     fn from(item: MissingCapabilityError) -> Self {
-        CalculatorFailure::InputError(MaybePositioned::new_unpositioned(item.to_string()))
+        CalculatorFailure::InputError(MaybePositioned::new_unpositioned(
+            InputErrorKind::MissingCapability(item),
+        ))
     }
 }
 
 impl From<Positioned<MissingCapabilityError>> for CalculatorFailure {
     fn from(item: Positioned<MissingCapabilityError>) -> Self {
-        CalculatorFailure::InputError(item.map(|v| v.to_string()).into())
+        CalculatorFailure::InputError(item.map(InputErrorKind::MissingCapability).into())
+    }
+}
+
+/// Something about a calculation that the user may want to know about, but that doesn't stop it
+/// from producing a result, such as the result having been rounded. Displaying it gives a message
+/// suitable for showing to the user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Warning {
+    /// The result was rounded to the contained number of digits after the radix point when it was
+    /// displayed.
+    Rounded(u8),
+    /// `%` was used with an operand that isn't an integer.
+    NonIntegerModulus,
+    /// The contained number of close parentheses were missing from the end of the input and were
+    /// assumed.
+    AssumedCloseParens(usize),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::Rounded(1) => write!(f, "Result rounded to 1 digit"),
+            Warning::Rounded(digits) => write!(f, "Result rounded to {} digits", digits),
+            Warning::NonIntegerModulus => write!(f, "Modulus of non-integers"),
+            Warning::AssumedCloseParens(1) => {
+                write!(f, "Assumed a missing close parenthesis at the end")
+            }
+            Warning::AssumedCloseParens(count) => {
+                write!(f, "Assumed {} missing close parentheses at the end", count)
+            }
+        }
     }
 }
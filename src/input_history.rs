@@ -1,5 +1,6 @@
 use crate::error::InternalCalculatorError;
 use crate::saved_data::SavedData;
+use crate::storage::Storage;
 
 /// The input history effectively keeps three instances of the history of user input entries.
 /// Two are what we will call "primary" histories. These are only changed when inserting items. We
@@ -27,6 +28,14 @@ pub struct InputHistory {
     /// items added in from the database as they are requested.
     /// This history won't be used if `maybe_db` is `None`.
     primary_db_history: Vec<String>,
+    /// The results that the entries in `primary_internal_history` evaluated to, at the same
+    /// indices. Entries that are commands or that failed to evaluate have no result.
+    internal_results: Vec<Option<String>>,
+    /// The results that the entries in `primary_db_history` evaluated to, at the same indices.
+    db_results: Vec<Option<String>>,
+    /// The ids of the sessions that the entries in `primary_db_history` were entered in, at the
+    /// same indices. See `InputHistoryEntry::maybe_session`.
+    db_sessions: Vec<Option<i64>>,
     /// This is the current history, which remembers changes made during the current line of input
     /// (i.e. between `input_finished` calls). It is sparse in two different ways. It always starts
     /// at length `1`, containing just the empty string that the input line defaults to. As the user
@@ -48,6 +57,29 @@ pub struct InputHistory {
     /// we check again, so once this is `true`, we no longer attempt to read from the database.
     /// This will always be `true` if `InputHistory::new` was passed `false` for `use_db`.
     db_history_exhausted: bool,
+    /// The inputs that the user has marked as favorites, ordered from least to most recently
+    /// favorited. If the database is available, this mirrors the favorites stored there (see
+    /// `load_favorites`).
+    favorites: Vec<String>,
+    /// While the user is cycling through the favorites, this is the index in `favorites` of the one
+    /// that was most recently recalled into the current line. Reset by `input_finished`.
+    favorite_index: Option<usize>,
+    /// The expression templates saved with `/snippet save`, as `(name, template)` pairs ordered
+    /// from least to most recently saved. If the database is available, this mirrors the snippets
+    /// stored there (see `load_snippets`).
+    snippets: Vec<(String, String)>,
+    /// Where the cursor ought to be placed in the `current_line` the next time that it is edited,
+    /// if somewhere other than the start. Set when a snippet is inserted.
+    requested_cursor_pos: Option<usize>,
+}
+
+/// Finds the first placeholder in a snippet template, which is a `?` immediately followed by a
+/// letter, like the `?a` in `sqrt(?a^2 + ?b^2)`. Returns its index.
+pub fn first_placeholder(template: &str) -> Option<usize> {
+    template
+        .as_bytes()
+        .windows(2)
+        .position(|pair| pair[0] == b'?' && pair[1].is_ascii_alphabetic())
 }
 
 impl InputHistory {
@@ -55,12 +87,34 @@ impl InputHistory {
         InputHistory {
             primary_internal_history: Vec::new(),
             primary_db_history: Vec::new(),
+            internal_results: Vec::new(),
+            db_results: Vec::new(),
+            db_sessions: Vec::new(),
             current_history: vec![Some(String::new())],
             current_index: 0,
             db_history_exhausted: !use_db,
+            favorites: Vec::new(),
+            favorite_index: None,
+            snippets: Vec::new(),
+            requested_cursor_pos: None,
         }
     }
 
+    /// Replaces the favorites with the ones stored in the database. This ought to be called after
+    /// construction if the database is available.
+    pub fn load_favorites(&mut self, db: &mut SavedData) -> Result<(), Box<dyn std::error::Error>> {
+        self.favorites = db.get_favorites()?;
+        self.favorite_index = None;
+        Ok(())
+    }
+
+    /// Replaces the snippets with the ones stored in the database. This ought to be called after
+    /// construction if the database is available.
+    pub fn load_snippets(&mut self, db: &mut SavedData) -> Result<(), Box<dyn std::error::Error>> {
+        self.snippets = db.get_snippets()?;
+        Ok(())
+    }
+
     /// Indicates that we are done editing/composing the current line of input. See the docstring
     /// for `InputHistory` for details.
     /// If `SavedData` is available, this will store the `current_line` to the history in the
@@ -72,9 +126,11 @@ impl InputHistory {
     ) -> Result<Option<i64>, Box<dyn std::error::Error>> {
         self.primary_internal_history
             .push(self.current_line().to_string());
+        self.internal_results.push(None);
         self.current_history.clear();
         self.current_history.push(Some(String::new()));
         self.current_index = 0;
+        self.favorite_index = None;
 
         if let Some(db) = maybe_db {
             Ok(Some(db.add_to_input_history(
@@ -85,17 +141,232 @@ impl InputHistory {
         }
     }
 
+    /// Returns the inputs that have been entered during the current session, oldest first.
+    pub fn session_inputs(&self) -> &[String] {
+        &self.primary_internal_history
+    }
+
+    /// Returns what each of the `session_inputs` evaluated to, or `None` where that is unknown.
+    pub fn session_results(&self) -> &[Option<String>] {
+        &self.internal_results
+    }
+
+    /// Adds inputs to the history as if they had been entered, oldest first, without changing the
+    /// current line. If `SavedData` is available, the inputs are also added to the history in the
+    /// database.
+    /// The caller is responsible for ensuring that the inputs are each a single line of ASCII text.
+    pub fn import(
+        &mut self,
+        inputs: Vec<String>,
+        maybe_db: Option<&mut SavedData>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let entries = inputs.into_iter().map(|input| (input, None)).collect();
+        self.import_with_results(entries, maybe_db)
+    }
+
+    /// Like `import`, but each input is given along with what it evaluated to, if that is known.
+    pub fn import_with_results(
+        &mut self,
+        entries: Vec<(String, Option<String>)>,
+        mut maybe_db: Option<&mut SavedData>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for (input, maybe_result) in entries {
+            if let Some(db) = maybe_db.as_deref_mut() {
+                let id = db.add_to_input_history(&input)?;
+                if let Some(result) = &maybe_result {
+                    db.set_input_history_result(id, result)?;
+                }
+            }
+            self.primary_internal_history.push(input);
+            self.internal_results.push(maybe_result);
+        }
+        Ok(())
+    }
+
+    /// Records what the most recently finished input evaluated to. If `SavedData` is available,
+    /// `input_history_id` ought to be the `id` that `input_finished` returned so that the result can
+    /// also be stored in the database.
+    pub fn set_last_result(
+        &mut self,
+        result: &str,
+        maybe_input_history_id: Option<i64>,
+        maybe_db: Option<&mut SavedData>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(last) = self.internal_results.last_mut() {
+            *last = Some(result.to_string());
+        }
+        if let (Some(id), Some(db)) = (maybe_input_history_id, maybe_db) {
+            db.set_input_history_result(id, result)?;
+        }
+        Ok(())
+    }
+
+    /// Returns what the `current_line` evaluated to when it was originally entered. Returns `None`
+    /// if the result is unknown or if the line has been changed since then, since the result may no
+    /// longer apply.
+    pub fn current_line_result(&self) -> Option<&str> {
+        if self.current_history[self.current_index].is_some() {
+            return None;
+        }
+        let maybe_result = if self.current_index <= self.internal_results.len() {
+            &self.internal_results[self.internal_results.len() - self.current_index]
+        } else {
+            &self.db_results[self.current_index - self.internal_results.len() - 1]
+        };
+        maybe_result.as_deref()
+    }
+
+    /// Returns the favorite inputs, ordered from least to most recently favorited.
+    pub fn favorites(&self) -> &[String] {
+        &self.favorites
+    }
+
+    /// Marks the input as a favorite. If `SavedData` is available, the favorite is also stored in
+    /// the database.
+    /// Returns `Ok(false)` if the input was already a favorite.
+    pub fn add_favorite(
+        &mut self,
+        input: &str,
+        maybe_db: Option<&mut SavedData>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(db) = maybe_db {
+            db.add_favorite(input)?;
+        }
+        if self.favorites.iter().any(|f| f == input) {
+            return Ok(false);
+        }
+        self.favorites.push(input.to_string());
+        Ok(true)
+    }
+
+    /// Removes the input from the favorites, both here and in the database if `SavedData` is
+    /// available.
+    /// Returns `Ok(false)` if the input was not a favorite.
+    pub fn remove_favorite(
+        &mut self,
+        input: &str,
+        maybe_db: Option<&mut SavedData>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(db) = maybe_db {
+            db.remove_favorite(input)?;
+        }
+        match self.favorites.iter().position(|f| f == input) {
+            Some(index) => {
+                self.favorites.remove(index);
+                self.favorite_index = None;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Returns the snippets as `(name, template)` pairs, ordered from least to most recently saved.
+    pub fn snippets(&self) -> &[(String, String)] {
+        &self.snippets
+    }
+
+    /// Saves the snippet, replacing any snippet with the same name. If `SavedData` is available,
+    /// the snippet is also stored in the database.
+    pub fn set_snippet(
+        &mut self,
+        name: &str,
+        template: &str,
+        maybe_db: Option<&mut SavedData>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(db) = maybe_db {
+            db.set_snippet(name, template)?;
+        }
+        self.snippets.retain(|(existing, _)| existing != name);
+        self.snippets.push((name.to_string(), template.to_string()));
+        Ok(())
+    }
+
+    /// Removes the snippet, both here and in the database if `SavedData` is available.
+    /// Returns `Ok(false)` if there was no such snippet.
+    pub fn remove_snippet(
+        &mut self,
+        name: &str,
+        maybe_db: Option<&mut SavedData>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(db) = maybe_db {
+            db.remove_snippet(name)?;
+        }
+        let count = self.snippets.len();
+        self.snippets.retain(|(existing, _)| existing != name);
+        Ok(self.snippets.len() < count)
+    }
+
+    /// Replaces the contents of the `current_line` with the snippet's template and requests that
+    /// the cursor be placed on its first placeholder, or at the end if it has none.
+    /// Returns `false` if there is no such snippet.
+    pub fn insert_snippet(&mut self, name: &str) -> bool {
+        let template = match self.snippets.iter().find(|(existing, _)| existing == name) {
+            Some((_, template)) => template.clone(),
+            None => return false,
+        };
+        self.requested_cursor_pos = Some(first_placeholder(&template).unwrap_or(template.len()));
+        self.current_history[self.current_index] = Some(template);
+        true
+    }
+
+    /// Replaces the contents of the `current_line` and requests that the cursor be placed at its
+    /// end.
+    pub fn set_current_line(&mut self, line: String) {
+        self.requested_cursor_pos = Some(line.len());
+        self.current_history[self.current_index] = Some(line);
+    }
+
+    /// Returns where the cursor ought to be placed in the `current_line`, if somewhere other than
+    /// the start, and clears the request.
+    pub fn take_requested_cursor_pos(&mut self) -> Option<usize> {
+        self.requested_cursor_pos.take()
+    }
+
+    /// Replaces the contents of the `current_line` with the next older favorite, wrapping around to
+    /// the newest one once the oldest has been reached. The first call after `input_finished`
+    /// recalls the newest favorite.
+    /// Returns `false` if there are no favorites.
+    pub fn try_to_recall_earlier_favorite(&mut self) -> bool {
+        let len = self.favorites.len();
+        self.recall_favorite(match self.favorite_index {
+            Some(index) if index > 0 => index - 1,
+            _ => len.wrapping_sub(1),
+        })
+    }
+
+    /// Like `try_to_recall_earlier_favorite`, but moves towards newer favorites instead. The first
+    /// call after `input_finished` recalls the oldest favorite.
+    pub fn try_to_recall_later_favorite(&mut self) -> bool {
+        self.recall_favorite(match self.favorite_index {
+            Some(index) if index + 1 < self.favorites.len() => index + 1,
+            _ => 0,
+        })
+    }
+
+    fn recall_favorite(&mut self, index: usize) -> bool {
+        if index >= self.favorites.len() {
+            return false;
+        }
+        self.favorite_index = Some(index);
+        self.current_history[self.current_index] = Some(self.favorites[index].clone());
+        true
+    }
+
     /// Returns the current line selected in the history (what the user should see).
     pub fn current_line(&self) -> &str {
-        match &self.current_history[self.current_index] {
+        self.line_at(self.current_index)
+    }
+
+    /// Returns the line at `index` in `current_history`, fetching it from the relevant primary
+    /// history if it hasn't been changed.
+    fn line_at(&self, index: usize) -> &str {
+        match &self.current_history[index] {
             Some(item) => item,
             None => {
-                if self.current_index <= self.primary_internal_history.len() {
-                    &self.primary_internal_history
-                        [self.primary_internal_history.len() - self.current_index]
+                if index <= self.primary_internal_history.len() {
+                    &self.primary_internal_history[self.primary_internal_history.len() - index]
                 } else {
-                    &self.primary_db_history
-                        [self.current_index - self.primary_internal_history.len() - 1]
+                    &self.primary_db_history[index - self.primary_internal_history.len() - 1]
                 }
             }
         }
@@ -127,7 +398,11 @@ impl InputHistory {
                 }
             };
             match db.get_prev_input_history()? {
-                Some(input) => self.primary_db_history.push(input),
+                Some(entry) => {
+                    self.primary_db_history.push(entry.input);
+                    self.db_results.push(entry.maybe_result);
+                    self.db_sessions.push(entry.maybe_session);
+                }
                 None => {
                     self.db_history_exhausted = true;
                     return Ok(false);
@@ -153,6 +428,91 @@ impl InputHistory {
         return true;
     }
 
+    /// Like `try_to_go_to_earlier_line`, but skips over entries that `is_wanted` returns `false`
+    /// for. If there is no earlier entry that is wanted, the `current_line` is left unchanged.
+    /// Returns `Ok(true)` if `current_line` changed.
+    pub fn try_to_go_to_earlier_line_where<F: Fn(&str) -> bool>(
+        &mut self,
+        mut maybe_db: Option<&mut SavedData>,
+        is_wanted: F,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let starting_index = self.current_index;
+        while self.try_to_go_to_earlier_line(maybe_db.as_deref_mut())? {
+            if is_wanted(self.current_line()) {
+                return Ok(true);
+            }
+        }
+        self.current_index = starting_index;
+        Ok(false)
+    }
+
+    /// Like `try_to_go_to_later_line`, but skips over entries that `is_wanted` returns `false` for.
+    /// The initially-empty "composition" input is always wanted, so this only fails if it is
+    /// already the `current_line`.
+    /// Returns `true` if `current_line` changed.
+    pub fn try_to_go_to_later_line_where<F: Fn(&str) -> bool>(&mut self, is_wanted: F) -> bool {
+        if self.current_index == 0 {
+            return false;
+        }
+        let mut index = self.current_index - 1;
+        while index > 0 && !is_wanted(self.line_at(index)) {
+            index -= 1;
+        }
+        self.current_index = index;
+        true
+    }
+
+    /// Identifies the session that the entry at `index` in `current_history` was entered in.
+    /// Entries from the current session, including the initially-empty "composition" input, are
+    /// `None`. Entries from the database are `Some`, containing their
+    /// `InputHistoryEntry::maybe_session`.
+    fn session_at(&self, index: usize) -> Option<Option<i64>> {
+        if index <= self.primary_internal_history.len() {
+            None
+        } else {
+            Some(self.db_sessions[index - self.primary_internal_history.len() - 1])
+        }
+    }
+
+    /// Moves the `current_line` to the most recent entry of the session before the one that the
+    /// `current_line` is from, loading entries from the database as necessary. If there is no
+    /// earlier session, moves to the earliest entry instead.
+    /// Returns `Ok(true)` if `current_line` changed.
+    pub fn try_to_go_to_earlier_session(
+        &mut self,
+        mut maybe_db: Option<&mut SavedData>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let starting_session = self.session_at(self.current_index);
+        let mut moved = false;
+        while self.try_to_go_to_earlier_line(maybe_db.as_deref_mut())? {
+            moved = true;
+            if self.session_at(self.current_index) != starting_session {
+                break;
+            }
+        }
+        Ok(moved)
+    }
+
+    /// Moves the `current_line` to the most recent entry of the session after the one that the
+    /// `current_line` is from. From an entry of the current session, moves to the latest line.
+    /// Returns `true` if `current_line` changed.
+    pub fn try_to_go_to_later_session(&mut self) -> bool {
+        let starting_session = self.session_at(self.current_index);
+        let mut index = self.current_index;
+        while index > 0 && self.session_at(index) == starting_session {
+            index -= 1;
+        }
+        let target_session = self.session_at(index);
+        while index > 0 && self.session_at(index - 1) == target_session {
+            index -= 1;
+        }
+        if index == self.current_index {
+            return false;
+        }
+        self.current_index = index;
+        true
+    }
+
     /// Ensures that `self.current_history[self.current_index]` is `Some`.
     fn ensure_current_line_populated(&mut self) {
         if self.current_history[self.current_index].is_none() {
@@ -180,3 +540,45 @@ impl InputHistory {
             .remove(index);
     }
 }
+
+#[cfg(test)]
+mod input_history_tests {
+    use crate::input_history::InputHistory;
+
+    #[test]
+    fn filtered_navigation() {
+        let mut inputs = InputHistory::new(false);
+        inputs
+            .import(
+                ["1 + 1", "/help", "2 * 3", "/radix 16"]
+                    .iter()
+                    .map(|input| input.to_string())
+                    .collect(),
+                None,
+            )
+            .unwrap();
+        let is_command = |line: &str| line.starts_with('/');
+
+        assert!(inputs
+            .try_to_go_to_earlier_line_where(None, |line| !is_command(line))
+            .unwrap());
+        assert_eq!(inputs.current_line(), "2 * 3");
+        assert!(inputs
+            .try_to_go_to_earlier_line_where(None, |line| !is_command(line))
+            .unwrap());
+        assert_eq!(inputs.current_line(), "1 + 1");
+        assert!(!inputs
+            .try_to_go_to_earlier_line_where(None, |line| !is_command(line))
+            .unwrap());
+        assert_eq!(inputs.current_line(), "1 + 1");
+
+        assert!(inputs.try_to_go_to_later_line_where(is_command));
+        assert_eq!(inputs.current_line(), "/help");
+        assert!(inputs.try_to_go_to_later_line_where(|line| !is_command(line)));
+        assert_eq!(inputs.current_line(), "2 * 3");
+        // The line being composed is always reachable.
+        assert!(inputs.try_to_go_to_later_line_where(|line| !is_command(line)));
+        assert_eq!(inputs.current_line(), "");
+        assert!(!inputs.try_to_go_to_later_line_where(|line| !is_command(line)));
+    }
+}
@@ -0,0 +1,68 @@
+//! Cooperative cancellation and progress reporting for evaluations that may take a long time, such
+//! as roots computed to a high precision. The evaluation checks its `Interrupt` periodically, so
+//! cancelling a clone of it from another thread makes the evaluation stop early with
+//! `MathExecutionError::Cancelled`. Progress is sent over a channel so that it can be displayed by
+//! whichever thread is not busy evaluating.
+
+use crate::error::MathExecutionError;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{channel, Receiver, Sender},
+    Arc,
+};
+
+/// How far along a long-running operation is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of digits of the result that are known to be correct so far.
+    pub digits: u32,
+    /// The number of digits that will be known once the operation is done.
+    pub total_digits: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Interrupt {
+    cancelled: Arc<AtomicBool>,
+    maybe_progress: Option<Sender<Progress>>,
+}
+
+impl Interrupt {
+    pub fn new() -> Interrupt {
+        Interrupt::default()
+    }
+
+    /// Like `Interrupt::new`, but the progress of long-running operations is also sent to the
+    /// returned receiver.
+    pub fn with_progress() -> (Interrupt, Receiver<Progress>) {
+        let (sender, receiver) = channel();
+        let interrupt = Interrupt {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            maybe_progress: Some(sender),
+        };
+        (interrupt, receiver)
+    }
+
+    /// Requests that any evaluation using this `Interrupt` or a clone of it stop as soon as
+    /// possible.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn check(&self) -> Result<(), MathExecutionError> {
+        if self.is_cancelled() {
+            return Err(MathExecutionError::Cancelled);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn report(&self, progress: Progress) {
+        if let Some(sender) = &self.maybe_progress {
+            // Nobody listening for progress is not a reason to stop calculating.
+            let _ = sender.send(progress);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! A recorded sequence of editor keys that can be played back, so that a repetitive calculation
+//! workflow can be repeated without writing it out as a script. Recording is started and stopped
+//! with Alt+R and the recording is played with Alt+P or `/macro play`.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Pressed with Alt, starts or stops recording.
+pub const RECORD_KEY: char = 'r';
+/// Pressed with Alt, plays the recorded keys once.
+pub const PLAY_KEY: char = 'p';
+
+/// The most times that a single request can play the macro.
+pub const MAX_MACRO_PLAYS: usize = 1000;
+
+#[derive(Default)]
+pub struct KeystrokeMacro {
+    // The keys recorded so far, if recording is in progress.
+    maybe_recording: Option<Vec<KeyEvent>>,
+    keys: Vec<KeyEvent>,
+    // How many times playing the macro has been requested by `/macro play` since the requests were
+    // last taken.
+    requested_plays: usize,
+}
+
+impl KeystrokeMacro {
+    pub fn new() -> KeystrokeMacro {
+        KeystrokeMacro::default()
+    }
+
+    /// Whether the key is one of the ones that control recording and playback. These are never
+    /// recorded.
+    pub fn is_control_key(key: &KeyEvent) -> bool {
+        key.modifiers == KeyModifiers::ALT
+            && matches!(key.code, KeyCode::Char(RECORD_KEY | PLAY_KEY))
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.maybe_recording.is_some()
+    }
+
+    /// The keys of the last completed recording.
+    pub fn keys(&self) -> &[KeyEvent] {
+        &self.keys
+    }
+
+    /// Starts a new recording. The previous one is kept until this one is stopped.
+    pub fn start_recording(&mut self) {
+        self.maybe_recording = Some(Vec::new());
+    }
+
+    /// Stops recording, replacing the previous recording with the new one, and returns the number
+    /// of keys recorded. Does nothing and returns `None` if no recording was in progress.
+    pub fn stop_recording(&mut self) -> Option<usize> {
+        let keys = self.maybe_recording.take()?;
+        self.keys = keys;
+        Some(self.keys.len())
+    }
+
+    /// Adds the key to the recording in progress, if there is one.
+    pub fn record(&mut self, key: KeyEvent) {
+        if let Some(recording) = self.maybe_recording.as_mut() {
+            if !KeystrokeMacro::is_control_key(&key) {
+                recording.push(key);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.maybe_recording = None;
+        self.keys.clear();
+    }
+
+    /// Asks for the recorded keys to be played `times` times, once the current input is done.
+    pub fn request_plays(&mut self, times: usize) {
+        self.requested_plays = (self.requested_plays + times).min(MAX_MACRO_PLAYS);
+    }
+
+    /// Returns the keys that have been requested to be played, in order, and clears the requests.
+    /// Nothing is played while recording, since the keys that requested the playback would then
+    /// become part of the recording.
+    pub fn take_requested_keys(&mut self) -> Vec<KeyEvent> {
+        let times = std::mem::take(&mut self.requested_plays);
+        if self.is_recording() {
+            return Vec::new();
+        }
+        self.keys.repeat(times)
+    }
+}
+
+#[cfg(test)]
+mod keystroke_macro_tests {
+    use crate::keystroke_macro::{KeystrokeMacro, MAX_MACRO_PLAYS, RECORD_KEY};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn record_and_play() {
+        let mut keystroke_macro = KeystrokeMacro::new();
+        keystroke_macro.record(key('1'));
+        assert_eq!(keystroke_macro.stop_recording(), None);
+
+        keystroke_macro.start_recording();
+        keystroke_macro.record(key('1'));
+        keystroke_macro.record(KeyEvent::new(KeyCode::Char(RECORD_KEY), KeyModifiers::ALT));
+        keystroke_macro.record(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        keystroke_macro.request_plays(1);
+        assert!(keystroke_macro.take_requested_keys().is_empty());
+        assert_eq!(keystroke_macro.stop_recording(), Some(2));
+
+        keystroke_macro.request_plays(2);
+        let keys = keystroke_macro.take_requested_keys();
+        assert_eq!(keys.len(), 4);
+        assert_eq!(keys[2], key('1'));
+        assert!(keystroke_macro.take_requested_keys().is_empty());
+
+        keystroke_macro.request_plays(MAX_MACRO_PLAYS);
+        keystroke_macro.request_plays(1);
+        assert_eq!(
+            keystroke_macro.take_requested_keys().len(),
+            2 * MAX_MACRO_PLAYS
+        );
+
+        keystroke_macro.clear();
+        keystroke_macro.request_plays(1);
+        assert!(keystroke_macro.take_requested_keys().is_empty());
+    }
+}
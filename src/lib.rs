@@ -0,0 +1,2129 @@
+//! bcalc's calculation engine, which works with exact rational numbers wherever possible. The
+//! `Calculator` type is the simplest way to evaluate expressions. The lower level pieces that it is
+//! built from, such as `Tokenizer`, `SyntaxTree`, and `VariableStore`, are also available for
+//! programs that need more control.
+//!
+//! The executable's interactive pieces (commands, input history, and the on-disk database) are
+//! behind the `cli` and `db` features, which are on by default. Serialization of tokens and syntax
+//! trees is behind the `serde` feature, which `cli` enables. With them disabled, the engine
+//! builds for `wasm32-unknown-unknown`, and the `wasm` feature adds JavaScript bindings.
+
+#[cfg(feature = "cli")]
+pub mod bench;
+#[cfg(feature = "cli")]
+pub mod commands;
+pub mod constants;
+#[cfg(feature = "cli")]
+pub mod currency;
+pub mod custom_function;
+pub mod error;
+#[cfg(feature = "cli")]
+pub mod input_history;
+pub mod interrupt;
+#[cfg(feature = "cli")]
+pub mod keystroke_macro;
+pub mod network;
+pub mod operations;
+pub mod plot;
+pub mod polynomial;
+pub mod position;
+#[cfg(feature = "cli")]
+pub mod recovery;
+#[cfg(feature = "cli")]
+pub mod registers;
+#[cfg(feature = "db")]
+pub mod saved_data;
+pub mod storage;
+pub mod suggestion;
+pub mod syntax_tree;
+pub mod token;
+#[cfg(feature = "cli")]
+pub mod transcript;
+pub mod units;
+pub mod value;
+pub mod variable;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use custom_function::CustomFunction;
+use error::{
+    CalculatorDatabaseInconsistencyError,
+    CalculatorFailure::{self, InputError},
+    InputErrorKind, ParseError, SyntaxError, Warning,
+};
+use interrupt::Interrupt;
+use num::{bigint::BigInt, rational::BigRational, One, Signed, Zero};
+use operations::{
+    decimal_exponent, engineering_parts, make_bases_string, make_decimal_string,
+    make_duration_string, make_engineering_string, make_fraction_string, make_mixed_number_string,
+    make_scientific_string, scientific_parts, BASES, MAX_RADIX,
+};
+use position::{MaybePositioned, Positioned};
+#[cfg(feature = "db")]
+use saved_data::validate_profile_name;
+use std::{borrow::Cow, collections::HashSet, path::PathBuf, sync::Arc};
+use storage::{reborrow, SavedSetting, Storage};
+pub use syntax_tree::SyntaxTree;
+use syntax_tree::TraceStep;
+pub use token::Tokenizer;
+use token::{close_open_parens, from_decimal_comma, is_command, ParsedInput, Token};
+use units::{base_unit_name, byte_size_display, Dimension};
+pub use value::Value;
+use variable::displayed_variable;
+pub use variable::VariableStore;
+
+#[derive(Parser, Clone, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// Radix (base) to use for input and output, from 2 to 36. Digits above 9 are the letters a to z.
+    #[arg(short, long, default_value_t = 10)]
+    #[arg(value_parser = clap::value_parser!(u8).range(2..=i64::from(MAX_RADIX)))]
+    pub radix: u8,
+
+    /// If specified, input will be read from the provided string rather than interactively.
+    #[arg(short, long)]
+    pub input: Option<String>,
+
+    /// If specified, an alternate terminal screen is opened rather than doing the calculations
+    /// inline. In this mode, entered calculations wrap rather than scrolling.
+    #[arg(short, long)]
+    pub alternate_screen: bool,
+
+    /// Normally, the calculator attempts to load data such as input history from a user-specific
+    /// database. If this option is specified, the database will not be used.
+    #[arg(long)]
+    pub no_db: bool,
+
+    /// If specified, the input history, variables, and saved settings are loaded from and saved to
+    /// a separate database for the named profile rather than the default one.
+    #[arg(long)]
+    #[arg(value_parser = parse_profile_name)]
+    pub profile: Option<String>,
+
+    /// If specified, the output radix (base) will be set to this rather than being the same as the
+    /// input radix.
+    #[arg(long)]
+    #[arg(value_parser = clap::value_parser!(u8).range(2..=i64::from(MAX_RADIX)))]
+    pub convert_to_radix: Option<u8>,
+
+    /// Maximum number of decimal digits to output.
+    #[arg(short, long, default_value_t = 5)]
+    pub precision: u8,
+
+    /// Additional decimal digits to store internally.
+    #[arg(long, default_value_t = 10)]
+    pub extra_precision: u8,
+
+    /// If specified, an alternate terminal screen is opened rather than doing the calculations
+    /// inline. In this mode, entered calculations wrap rather than scrolling.
+    #[arg(short, long)]
+    pub fractional: bool,
+
+    /// If specified, fractional output is only used for values whose denominators are at most this
+    /// large. Other values are output as decimals.
+    #[arg(long)]
+    #[arg(value_parser = clap::value_parser!(u64).range(1..=i64::MAX as u64))]
+    pub max_denominator: Option<u64>,
+
+    /// If specified, the denominators in fractional output are shown as products of prime powers,
+    /// like 35/(2^2*3).
+    #[arg(long)]
+    pub factored: bool,
+
+    /// How fractional output is written: as an improper fraction like 7/3, as a mixed number like
+    /// 2 1/3, or as an improper fraction followed by its decimal value, like 7/3 (2.33333).
+    #[arg(long, value_enum, default_value_t = FractionStyle::Improper)]
+    pub fraction_style: FractionStyle,
+
+    /// If specified, decimal output of values with more than this many digits before the decimal
+    /// point is in scientific notation, like 1.23457e20.
+    #[arg(long)]
+    #[arg(value_parser = clap::value_parser!(u16).range(1..))]
+    pub sci_above: Option<u16>,
+
+    /// If specified, decimal output of nonzero values smaller in magnitude than 10^-M, where M is
+    /// this value, is in scientific notation, like 1.5e-7.
+    #[arg(long)]
+    #[arg(value_parser = clap::value_parser!(u16).range(1..))]
+    pub sci_below: Option<u16>,
+
+    /// How decimal output is written: as a plain decimal, switching to scientific notation only as
+    /// `--sci-above` and `--sci-below` say to, always in scientific notation, like 1.2345e9, or
+    /// always in engineering notation, whose exponents are multiples of 3, like 123.45e-6.
+    #[arg(long, value_enum, default_value_t = Notation::Auto)]
+    pub notation: Notation,
+
+    /// If specified, the output will use commas as thousands separators to make long numbers more
+    /// readable.
+    #[arg(short, long)]
+    pub commas: bool,
+
+    /// The number of digits between each comma when commas are enabled. For example, 4 groups
+    /// hexadecimal and binary output into 16 bit words and nibbles.
+    #[arg(long, default_value_t = 3)]
+    #[arg(value_parser = clap::value_parser!(u8).range(1..=16))]
+    pub group_size: u8,
+
+    /// The character that separates groups of digits when commas are enabled: a comma, an
+    /// underscore, a space, or a period. A separator that would be the same as the decimal
+    /// separator is swapped for the other of comma and period.
+    #[arg(long, value_enum, default_value_t = GroupSeparator::Comma)]
+    pub group_separator: GroupSeparator,
+
+    /// If specified, results without units are shown in decimal, hexadecimal, octal, and binary at
+    /// once, like 255  0xff  0o377  0b11111111.
+    #[arg(long)]
+    pub bases: bool,
+
+    /// If specified, numbers are written with a comma as the decimal separator, like 1,5, both in
+    /// input and output. On input, digits can be grouped with dots or spaces (like 1.234.567,89),
+    /// and function arguments are separated by semicolons instead of commas. When commas are
+    /// enabled, output digits are grouped with dots.
+    #[arg(long)]
+    pub decimal_comma: bool,
+
+    /// If specified and the output radix is above 10, digits above 9 will be displayed in upper
+    /// case.
+    #[arg(short, long)]
+    pub upper: bool,
+
+    /// If specified, the up and down arrow keys scroll through commands in the input history as
+    /// well as expressions. Normally, commands are skipped and can instead be scrolled through by
+    /// additionally using Alt.
+    #[arg(long)]
+    pub recall_commands: bool,
+
+    /// What happens when an input is missing close parentheses at its end: with "offer", pressing
+    /// Enter adds them to the input line so that they can be checked before pressing Enter again;
+    /// with "auto", they are assumed and the input is evaluated right away; with "off", the input
+    /// is rejected.
+    #[arg(long, value_enum, default_value_t = CloseParens::Offer)]
+    pub close_parens: CloseParens,
+
+    /// If specified, conveniences that make input ambiguous are rejected rather than assumed:
+    /// functions must be called with parentheses, and inputs missing close parentheses are rejected
+    /// regardless of --close-parens.
+    #[arg(long)]
+    pub strict: bool,
+
+    /// If specified, numbers can't be written with SI suffixes, like 4.7k for 4700 or 10u for
+    /// 0.00001.
+    #[arg(long)]
+    pub no_si_suffixes: bool,
+
+    /// If specified, `m` written after a number is the SI suffix for milli, so 4.7m is 0.0047
+    /// rather than 4.7 minutes. Minutes can still be written as `min`. Has no effect with
+    /// --no-si-suffixes.
+    #[arg(long)]
+    pub milli_suffix: bool,
+
+    /// The unit that trigonometric functions take plain numbers as angles in and that inverse
+    /// trigonometric functions give angles in. Values with angle units, like `30 deg`, are always
+    /// converted as needed.
+    #[arg(long, value_enum, default_value_t = AngleUnit::Radians)]
+    pub angle_unit: AngleUnit,
+
+    /// How input errors are made noticeable, other than by the error message: by sounding the
+    /// terminal bell, by briefly flashing the input line, or not at all.
+    #[arg(long, value_enum, default_value_t = ErrorAlert::None)]
+    pub error_alert: ErrorAlert,
+
+    /// If specified, the input line is never redrawn in place. Instead, typed characters are echoed
+    /// as they are entered, and the whole line is output again on a new line whenever it changes
+    /// in any other way, so that screen readers can follow along. This is also turned on by setting
+    /// the BCALC_SCREEN_READER environment variable to anything other than 0.
+    #[arg(long)]
+    pub screen_reader: bool,
+
+    /// If specified, currency exchange rates are loaded from this JSON or CSV file so that
+    /// currencies can be converted with `/convert`. See `/help rates` for the file's format.
+    #[arg(long)]
+    pub rates_file: Option<PathBuf>,
+
+    /// If specified along with --input, the result is output as a JSON object, along with any
+    /// warnings about it, such as it having been rounded. Errors are output as JSON objects too.
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: Option<ArgsCommand>,
+}
+
+/// Things that the executable can do other than run the calculator.
+#[derive(Subcommand, Clone, Debug)]
+pub enum ArgsCommand {
+    /// Times each stage of evaluating a suite of representative expressions and prints the results
+    /// as a table.
+    #[command(hide = true)]
+    Bench(BenchArgs),
+}
+
+#[derive(clap::Args, Clone, Debug)]
+pub struct BenchArgs {
+    /// If specified, the results are saved to this file so that later runs can be compared to them.
+    #[arg(long)]
+    pub save: Option<PathBuf>,
+
+    /// If specified, the results are compared to those previously saved to this file with `--save`.
+    #[arg(long)]
+    pub baseline: Option<PathBuf>,
+}
+
+/// The ways of handling inputs that are missing close parentheses at the end. See
+/// `Args::close_parens`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseParens {
+    Off,
+    Offer,
+    Auto,
+}
+
+impl CloseParens {
+    /// The integer that the behavior is stored as by `SavedSetting::CloseParens`.
+    pub fn to_saved_value(self) -> i64 {
+        match self {
+            CloseParens::Off => 0,
+            CloseParens::Offer => 1,
+            CloseParens::Auto => 2,
+        }
+    }
+
+    /// The inverse of `to_saved_value`. Returns `None` if the value doesn't correspond to a
+    /// behavior.
+    pub fn from_saved_value(value: i64) -> Option<CloseParens> {
+        match value {
+            0 => Some(CloseParens::Off),
+            1 => Some(CloseParens::Offer),
+            2 => Some(CloseParens::Auto),
+            _ => None,
+        }
+    }
+
+    /// The name that the behavior is given on the command line and by `/closeparens`.
+    pub fn name(self) -> &'static str {
+        match self {
+            CloseParens::Off => "off",
+            CloseParens::Offer => "offer",
+            CloseParens::Auto => "auto",
+        }
+    }
+}
+
+/// The ways of drawing attention to an input error. See `Args::error_alert`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorAlert {
+    None,
+    Bell,
+    Flash,
+}
+
+impl ErrorAlert {
+    /// The integer that the alert is stored as by `SavedSetting::ErrorAlert`.
+    pub fn to_saved_value(self) -> i64 {
+        match self {
+            ErrorAlert::None => 0,
+            ErrorAlert::Bell => 1,
+            ErrorAlert::Flash => 2,
+        }
+    }
+
+    /// The inverse of `to_saved_value`. Returns `None` if the value doesn't correspond to an alert.
+    pub fn from_saved_value(value: i64) -> Option<ErrorAlert> {
+        match value {
+            0 => Some(ErrorAlert::None),
+            1 => Some(ErrorAlert::Bell),
+            2 => Some(ErrorAlert::Flash),
+            _ => None,
+        }
+    }
+
+    /// The name that the alert is given on the command line and by `/erroralert`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorAlert::None => "none",
+            ErrorAlert::Bell => "bell",
+            ErrorAlert::Flash => "flash",
+        }
+    }
+}
+
+/// The units that plain numbers are treated as being in when they're used as angles. See
+/// `Args::angle_unit`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AngleUnit {
+    Radians,
+    Degrees,
+}
+
+impl AngleUnit {
+    /// The integer that the unit is stored as by `SavedSetting::AngleUnit`.
+    pub fn to_saved_value(self) -> i64 {
+        match self {
+            AngleUnit::Radians => 0,
+            AngleUnit::Degrees => 1,
+        }
+    }
+
+    /// The inverse of `to_saved_value`. Returns `None` if the value doesn't correspond to a unit.
+    pub fn from_saved_value(value: i64) -> Option<AngleUnit> {
+        match value {
+            0 => Some(AngleUnit::Radians),
+            1 => Some(AngleUnit::Degrees),
+            _ => None,
+        }
+    }
+
+    /// The name that the unit is given on the command line and by `/angle`.
+    pub fn name(self) -> &'static str {
+        match self {
+            AngleUnit::Radians => "radians",
+            AngleUnit::Degrees => "degrees",
+        }
+    }
+}
+
+/// The way that decimal output is written. See `Args::notation`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Notation {
+    Auto,
+    Scientific,
+    Engineering,
+}
+
+impl Notation {
+    /// The integer that the notation is stored as by `SavedSetting::Notation`.
+    pub fn to_saved_value(self) -> i64 {
+        match self {
+            Notation::Auto => 0,
+            Notation::Scientific => 1,
+            Notation::Engineering => 2,
+        }
+    }
+
+    /// The inverse of `to_saved_value`. Returns `None` if the value doesn't correspond to a
+    /// notation.
+    pub fn from_saved_value(value: i64) -> Option<Notation> {
+        match value {
+            0 => Some(Notation::Auto),
+            1 => Some(Notation::Scientific),
+            2 => Some(Notation::Engineering),
+            _ => None,
+        }
+    }
+
+    /// The name that the notation is given on the command line and by `/notation`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Notation::Auto => "auto",
+            Notation::Scientific => "scientific",
+            Notation::Engineering => "engineering",
+        }
+    }
+}
+
+/// The character that separates groups of digits in output. See `Args::group_separator`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupSeparator {
+    Comma,
+    Underscore,
+    Space,
+    Period,
+}
+
+impl GroupSeparator {
+    /// The integer that the separator is stored as by `SavedSetting::GroupSeparator`.
+    pub fn to_saved_value(self) -> i64 {
+        match self {
+            GroupSeparator::Comma => 0,
+            GroupSeparator::Underscore => 1,
+            GroupSeparator::Space => 2,
+            GroupSeparator::Period => 3,
+        }
+    }
+
+    /// The inverse of `to_saved_value`. Returns `None` if the value doesn't correspond to a
+    /// separator.
+    pub fn from_saved_value(value: i64) -> Option<GroupSeparator> {
+        match value {
+            0 => Some(GroupSeparator::Comma),
+            1 => Some(GroupSeparator::Underscore),
+            2 => Some(GroupSeparator::Space),
+            3 => Some(GroupSeparator::Period),
+            _ => None,
+        }
+    }
+
+    /// The name that the separator is given on the command line and by `/groupsep`.
+    pub fn name(self) -> &'static str {
+        match self {
+            GroupSeparator::Comma => "comma",
+            GroupSeparator::Underscore => "underscore",
+            GroupSeparator::Space => "space",
+            GroupSeparator::Period => "period",
+        }
+    }
+
+    /// The character that the separator is, before `Args::digit_separator` keeps it from being
+    /// the same as the radix point.
+    pub fn character(self) -> char {
+        match self {
+            GroupSeparator::Comma => ',',
+            GroupSeparator::Underscore => '_',
+            GroupSeparator::Space => ' ',
+            GroupSeparator::Period => '.',
+        }
+    }
+}
+
+/// The way that fractional output is written. See `Args::fraction_style`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FractionStyle {
+    Improper,
+    Mixed,
+    Both,
+}
+
+impl FractionStyle {
+    /// The integer that the style is stored as by `SavedSetting::FractionStyle`.
+    pub fn to_saved_value(self) -> i64 {
+        match self {
+            FractionStyle::Improper => 0,
+            FractionStyle::Mixed => 1,
+            FractionStyle::Both => 2,
+        }
+    }
+
+    /// The inverse of `to_saved_value`. Returns `None` if the value doesn't correspond to a style.
+    pub fn from_saved_value(value: i64) -> Option<FractionStyle> {
+        match value {
+            0 => Some(FractionStyle::Improper),
+            1 => Some(FractionStyle::Mixed),
+            2 => Some(FractionStyle::Both),
+            _ => None,
+        }
+    }
+}
+
+fn parse_profile_name(name: &str) -> Result<String, String> {
+    #[cfg(feature = "db")]
+    validate_profile_name(name)?;
+    Ok(name.to_string())
+}
+
+impl Args {
+    /// Overwrites these settings with any values that were saved to `storage` in previous sessions.
+    /// Settings for which `is_overridden` returns `true` when passed the argument's id (such as those
+    /// that were explicitly specified on the command line) take precedence over the saved values and
+    /// are left alone.
+    pub fn restore_saved_settings<F: Fn(&str) -> bool>(
+        &mut self,
+        storage: &mut dyn Storage,
+        is_overridden: F,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let to_radix = |value: i64| -> Result<u8, CalculatorDatabaseInconsistencyError> {
+            match u8::try_from(value) {
+                Ok(radix) if (2..=MAX_RADIX).contains(&radix) => Ok(radix),
+                _ => Err(CalculatorDatabaseInconsistencyError::new(
+                    "Stored radix is not valid",
+                )),
+            }
+        };
+        let to_u8 = |value: i64| -> Result<u8, CalculatorDatabaseInconsistencyError> {
+            u8::try_from(value).map_err(|_| {
+                CalculatorDatabaseInconsistencyError::new("Stored precision is not valid")
+            })
+        };
+        let to_bool = |value: i64| -> Result<bool, CalculatorDatabaseInconsistencyError> {
+            match value {
+                0 => Ok(false),
+                1 => Ok(true),
+                _ => Err(CalculatorDatabaseInconsistencyError::new(
+                    "Stored boolean setting is not valid",
+                )),
+            }
+        };
+
+        if !is_overridden("radix") {
+            if let Some(value) = storage.get_setting(SavedSetting::Radix)? {
+                self.radix = to_radix(value)?;
+            }
+        }
+        if !is_overridden("convert_to_radix") {
+            if let Some(value) = storage.get_setting(SavedSetting::ConvertToRadix)? {
+                self.convert_to_radix = Some(to_radix(value)?);
+            }
+        }
+        if !is_overridden("precision") {
+            if let Some(value) = storage.get_setting(SavedSetting::Precision)? {
+                self.precision = to_u8(value)?;
+            }
+        }
+        if !is_overridden("extra_precision") {
+            if let Some(value) = storage.get_setting(SavedSetting::ExtraPrecision)? {
+                self.extra_precision = to_u8(value)?;
+            }
+        }
+        if self.precision.checked_add(self.extra_precision).is_none() {
+            return Err(CalculatorDatabaseInconsistencyError::new(
+                "Stored precision and extra precision sum to more than 255",
+            )
+            .into());
+        }
+        if !is_overridden("fractional") {
+            if let Some(value) = storage.get_setting(SavedSetting::Fractional)? {
+                self.fractional = to_bool(value)?;
+            }
+        }
+        if !is_overridden("max_denominator") {
+            if let Some(value) = storage.get_setting(SavedSetting::MaxDenominator)? {
+                self.max_denominator = match u64::try_from(value) {
+                    Ok(max_denominator) if max_denominator >= 1 => Some(max_denominator),
+                    _ => {
+                        return Err(CalculatorDatabaseInconsistencyError::new(
+                            "Stored maximum denominator is not valid",
+                        )
+                        .into())
+                    }
+                };
+            }
+        }
+        if !is_overridden("fraction_style") {
+            if let Some(value) = storage.get_setting(SavedSetting::FractionStyle)? {
+                self.fraction_style = FractionStyle::from_saved_value(value).ok_or_else(|| {
+                    CalculatorDatabaseInconsistencyError::new("Stored fraction style is not valid")
+                })?;
+            }
+        }
+        if !is_overridden("factored") {
+            if let Some(value) = storage.get_setting(SavedSetting::Factored)? {
+                self.factored = to_bool(value)?;
+            }
+        }
+        let to_threshold = |value: i64| -> Result<u16, CalculatorDatabaseInconsistencyError> {
+            match u16::try_from(value) {
+                Ok(threshold) if threshold >= 1 => Ok(threshold),
+                _ => Err(CalculatorDatabaseInconsistencyError::new(
+                    "Stored scientific notation threshold is not valid",
+                )),
+            }
+        };
+        if !is_overridden("sci_above") {
+            if let Some(value) = storage.get_setting(SavedSetting::SciAbove)? {
+                self.sci_above = Some(to_threshold(value)?);
+            }
+        }
+        if !is_overridden("sci_below") {
+            if let Some(value) = storage.get_setting(SavedSetting::SciBelow)? {
+                self.sci_below = Some(to_threshold(value)?);
+            }
+        }
+        if !is_overridden("notation") {
+            if let Some(value) = storage.get_setting(SavedSetting::Notation)? {
+                self.notation = Notation::from_saved_value(value).ok_or_else(|| {
+                    CalculatorDatabaseInconsistencyError::new("Stored notation is not valid")
+                })?;
+            }
+        }
+        if !is_overridden("commas") {
+            if let Some(value) = storage.get_setting(SavedSetting::Commas)? {
+                self.commas = to_bool(value)?;
+            }
+        }
+        if !is_overridden("group_separator") {
+            if let Some(value) = storage.get_setting(SavedSetting::GroupSeparator)? {
+                self.group_separator =
+                    GroupSeparator::from_saved_value(value).ok_or_else(|| {
+                        CalculatorDatabaseInconsistencyError::new(
+                            "Stored group separator is not valid",
+                        )
+                    })?;
+            }
+        }
+        if !is_overridden("bases") {
+            if let Some(value) = storage.get_setting(SavedSetting::Bases)? {
+                self.bases = to_bool(value)?;
+            }
+        }
+        if !is_overridden("group_size") {
+            if let Some(value) = storage.get_setting(SavedSetting::GroupSize)? {
+                self.group_size = match u8::try_from(value) {
+                    Ok(group_size) if (1..=16).contains(&group_size) => group_size,
+                    _ => {
+                        return Err(CalculatorDatabaseInconsistencyError::new(
+                            "Stored group size is not valid",
+                        )
+                        .into())
+                    }
+                };
+            }
+        }
+        if !is_overridden("decimal_comma") {
+            if let Some(value) = storage.get_setting(SavedSetting::DecimalComma)? {
+                self.decimal_comma = to_bool(value)?;
+            }
+        }
+        if !is_overridden("upper") {
+            if let Some(value) = storage.get_setting(SavedSetting::Upper)? {
+                self.upper = to_bool(value)?;
+            }
+        }
+        if !is_overridden("recall_commands") {
+            if let Some(value) = storage.get_setting(SavedSetting::RecallCommands)? {
+                self.recall_commands = to_bool(value)?;
+            }
+        }
+        if !is_overridden("strict") {
+            if let Some(value) = storage.get_setting(SavedSetting::Strict)? {
+                self.strict = to_bool(value)?;
+            }
+        }
+        if !is_overridden("no_si_suffixes") {
+            if let Some(value) = storage.get_setting(SavedSetting::NoSiSuffixes)? {
+                self.no_si_suffixes = to_bool(value)?;
+            }
+        }
+        if !is_overridden("milli_suffix") {
+            if let Some(value) = storage.get_setting(SavedSetting::MilliSuffix)? {
+                self.milli_suffix = to_bool(value)?;
+            }
+        }
+        if !is_overridden("close_parens") {
+            if let Some(value) = storage.get_setting(SavedSetting::CloseParens)? {
+                self.close_parens = CloseParens::from_saved_value(value).ok_or_else(|| {
+                    CalculatorDatabaseInconsistencyError::new(
+                        "Stored close parentheses setting is not valid",
+                    )
+                })?;
+            }
+        }
+        if !is_overridden("angle_unit") {
+            if let Some(value) = storage.get_setting(SavedSetting::AngleUnit)? {
+                self.angle_unit = AngleUnit::from_saved_value(value).ok_or_else(|| {
+                    CalculatorDatabaseInconsistencyError::new("Stored angle unit is not valid")
+                })?;
+            }
+        }
+        if !is_overridden("error_alert") {
+            if let Some(value) = storage.get_setting(SavedSetting::ErrorAlert)? {
+                self.error_alert = ErrorAlert::from_saved_value(value).ok_or_else(|| {
+                    CalculatorDatabaseInconsistencyError::new("Stored error alert is not valid")
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The character that separates the whole and fractional parts of numbers in output.
+    pub fn radix_point(&self) -> char {
+        if self.decimal_comma {
+            ','
+        } else {
+            '.'
+        }
+    }
+
+    /// The character that separates groups of digits in output. This is never the same as the
+    /// radix point, so with `--decimal-comma`, digits are grouped with periods by default.
+    pub fn digit_separator(&self) -> char {
+        match self.group_separator.character() {
+            separator if separator == self.radix_point() => {
+                if separator == ',' {
+                    '.'
+                } else {
+                    ','
+                }
+            }
+            separator => separator,
+        }
+    }
+
+    /// What actually happens when an input is missing close parentheses, which is always rejecting
+    /// it in strict mode.
+    pub fn effective_close_parens(&self) -> CloseParens {
+        if self.strict {
+            CloseParens::Off
+        } else {
+            self.close_parens
+        }
+    }
+}
+
+impl Default for Args {
+    /// Returns the settings that bcalc uses when no command line arguments are given.
+    fn default() -> Args {
+        Args::parse_from([env!("CARGO_PKG_NAME")])
+    }
+}
+
+/// Rewrites the input into the notation that `Tokenizer` expects, according to the input settings in
+/// `args`. Positions in the result are the same as in `input`. Commands are left alone since their
+/// arguments aren't necessarily expressions.
+pub fn normalize_input<'a>(input: &'a str, args: &Args) -> Cow<'a, str> {
+    if args.decimal_comma && !is_command(input) {
+        Cow::Owned(from_decimal_comma(input, args.radix))
+    } else {
+        Cow::Borrowed(input)
+    }
+}
+
+/// Tokenizes the input after rewriting it with `normalize_input`. Since that keeps positions the
+/// same, any text quoted in an error is replaced with the input as the user typed it, so `1,5e`
+/// with a decimal comma is reported as `'1,5e'` rather than `'1.5e'`.
+pub fn tokenize_input(
+    tokenizer: &Tokenizer,
+    input: &str,
+    args: &Args,
+) -> Result<ParsedInput, Positioned<ParseError>> {
+    let normalized = normalize_input(input, args);
+    tokenizer
+        .tokenize(&normalized, args.radix)
+        .map_err(|mut error| {
+            let start = error.position.start;
+            let end = start + error.position.width;
+            if let Some(text) = error.value.quoted_text_mut() {
+                if normalized.get(start..end) == Some(text.as_str()) {
+                    if let Some(typed) = input.get(start..end) {
+                        *text = typed.to_string();
+                    }
+                }
+            }
+            error
+        })
+}
+
+// Whether `format_real` shows the value as a fraction rather than as a decimal.
+fn shown_as_fraction(value: &BigRational, args: &Args) -> bool {
+    match args.max_denominator {
+        _ if !args.fractional => false,
+        Some(max_denominator) => *value.denom() <= BigInt::from(max_denominator),
+        None => true,
+    }
+}
+
+// Whether `format_real` shows the value in scientific or engineering notation. This is only done
+// for decimal output.
+fn shown_in_scientific(value: &BigRational, args: &Args) -> bool {
+    if value.is_zero() || args.convert_to_radix.unwrap_or(args.radix) != 10 {
+        return false;
+    }
+    if args.notation != Notation::Auto {
+        return true;
+    }
+    let exponent = decimal_exponent(value);
+    args.sci_above
+        .is_some_and(|digits| exponent >= i64::from(digits))
+        || args
+            .sci_below
+            .is_some_and(|digits| exponent < -i64::from(digits))
+}
+
+/// Formats the value for output according to the output settings in `args`. Both parts of a
+/// complex value are formatted like real numbers, as in `2 + 3i`. An interval is shown as its
+/// midpoint and uncertainty, as in `25.0 ± 1.0`. A surd is only shown exactly, as in `3*sqrt(2)`,
+/// in fractional mode.
+pub fn format_value(value: &Value, args: &Args) -> String {
+    let (re, im) = match value {
+        Value::Real(value) => return format_real(value, args),
+        Value::Surd {
+            coefficient,
+            radicand,
+            approximation,
+        } => return format_surd(coefficient, radicand, approximation, args),
+        // A decimal after each part would be hard to read, so the parts are only shown as
+        // fractions.
+        _ if args.fraction_style == FractionStyle::Both => {
+            let args = Args {
+                fraction_style: FractionStyle::Improper,
+                ..args.clone()
+            };
+            return format_value(value, &args);
+        }
+        Value::Complex { re, im } => (re, im),
+        Value::Interval { low, high } => return format_interval(low, high, args),
+    };
+    let im_string = match format_real(&im.abs(), args) {
+        im_string if im_string == "1" => "i".to_string(),
+        // Without parentheses, it would look like only the denominator is imaginary.
+        im_string if im_string.contains('/') => format!("({})i", im_string),
+        im_string => format!("{}i", im_string),
+    };
+    let sign = if im.is_negative() { '-' } else { '+' };
+    if re.is_zero() && sign == '-' {
+        format!("-{}", im_string)
+    } else if re.is_zero() {
+        im_string
+    } else {
+        format!("{} {} {}", format_real(re, args), sign, im_string)
+    }
+}
+
+/// Formats a real number for output according to the output settings in `args`.
+pub fn format_real(value: &BigRational, args: &Args) -> String {
+    if !shown_as_fraction(value, args) {
+        return format_non_fraction(value, args);
+    }
+    match args.fraction_style {
+        FractionStyle::Mixed => make_mixed_number_string(value, args.factored),
+        FractionStyle::Both if !value.is_integer() => format!(
+            "{} ({})",
+            make_fraction_string(value, args.factored),
+            format_non_fraction(value, args)
+        ),
+        _ => make_fraction_string(value, args.factored),
+    }
+}
+
+// Formats the surd `coefficient*sqrt(radicand)`. Outside of fractional mode, there is no exact
+// way to write it, so its approximation is shown instead.
+fn format_surd(
+    coefficient: &BigRational,
+    radicand: &BigInt,
+    approximation: &BigRational,
+    args: &Args,
+) -> String {
+    if !args.fractional {
+        return format_real(approximation, args);
+    }
+    let output_radix = u32::from(args.convert_to_radix.unwrap_or(args.radix));
+    let root_string = format!("sqrt({})", radicand.to_str_radix(output_radix));
+    let surd_string = if coefficient.is_one() {
+        root_string
+    } else if (-coefficient).is_one() {
+        format!("-{}", root_string)
+    } else {
+        format!(
+            "{}*{}",
+            make_fraction_string(coefficient, args.factored),
+            root_string
+        )
+    };
+    if args.fraction_style == FractionStyle::Both {
+        format!(
+            "{} ({})",
+            surd_string,
+            format_non_fraction(approximation, args)
+        )
+    } else {
+        surd_string
+    }
+}
+
+// Formats a real number as `format_real` does when it isn't shown as a fraction.
+fn format_non_fraction(value: &BigRational, args: &Args) -> String {
+    if shown_in_scientific(value, args) {
+        let scientific_string = if args.notation == Notation::Engineering {
+            make_engineering_string(value, args.precision)
+        } else {
+            make_scientific_string(value, args.precision)
+        };
+        if args.decimal_comma {
+            scientific_string.replace('.', ",")
+        } else {
+            scientific_string
+        }
+    } else {
+        let output_radix = match args.convert_to_radix {
+            Some(radix) => radix,
+            None => args.radix,
+        };
+        let decimal_string = make_decimal_string(
+            value,
+            output_radix,
+            args.precision,
+            args.commas.then_some(args.group_size),
+            args.upper,
+        );
+        replace_separators(decimal_string, args)
+    }
+}
+
+// `make_decimal_string` writes a period as the radix point and commas between digit groups, which
+// are replaced with the characters that `args` says to use.
+fn replace_separators(decimal_string: String, args: &Args) -> String {
+    let (radix_point, digit_separator) = (args.radix_point(), args.digit_separator());
+    if radix_point == '.' && digit_separator == ',' {
+        return decimal_string;
+    }
+    decimal_string
+        .chars()
+        .map(|c| match c {
+            '.' => radix_point,
+            ',' => digit_separator,
+            c => c,
+        })
+        .collect()
+}
+
+// Formats the interval from `low` to `high` as its midpoint and the distance from there to either
+// end. In decimal, the uncertainty is shown to two significant digits and the midpoint to the same
+// number of places, since any digits after those wouldn't mean anything.
+fn format_interval(low: &BigRational, high: &BigRational, args: &Args) -> String {
+    let two = BigInt::from(2);
+    let center = (low + high) / &two;
+    let radius = (high - low) / two;
+    if args.fractional || args.convert_to_radix.unwrap_or(args.radix) != 10 {
+        return format!(
+            "{} ± {}",
+            format_real(&center, args),
+            format_real(&radius, args)
+        );
+    }
+    let places = (1 - decimal_exponent(&radius)).clamp(0, i64::from(args.precision)) as usize;
+    let fixed_string = |value: &BigRational| {
+        let mut decimal_string = make_decimal_string(
+            value,
+            10,
+            places as u8,
+            args.commas.then_some(args.group_size),
+            args.upper,
+        );
+        // Trailing zeros are left off of exact values, but here they show the precision.
+        let shown_places = decimal_string
+            .find('.')
+            .map_or(0, |point| decimal_string.len() - point - 1);
+        if places > 0 && shown_places == 0 {
+            decimal_string.push('.');
+        }
+        decimal_string.push_str(&"0".repeat(places - shown_places));
+        replace_separators(decimal_string, args)
+    };
+    format!("{} ± {}", fixed_string(&center), fixed_string(&radius))
+}
+
+/// Like `format_value`, but values that are known to be durations are shown as hours, minutes,
+/// and seconds (like `1:30:00`) rather than as a number of seconds, and data sizes are shown with a
+/// byte size unit (like `3 GiB`) rather than as a number of bits. Angles are shown in degrees, and
+/// other values with units are shown in the base unit of their dimension (like `1500 m`).
+/// Complex values and intervals are always shown in the base unit of their dimension, in
+/// parentheses (like `(2 + 3i) s`). Surds with units are shown as their approximation.
+/// With `--bases`, real values without units are shown in several radices (see
+/// `make_bases_string`).
+pub fn format_quantity(value: &Value, maybe_dimension: Option<Dimension>, args: &Args) -> String {
+    if let Some(real) = shown_in_bases(value, maybe_dimension, args) {
+        let bases_string = make_bases_string(
+            real,
+            args.precision,
+            args.commas.then_some(args.group_size),
+            args.upper,
+        );
+        return replace_separators(bases_string, args);
+    }
+    let real = match value {
+        Value::Real(real) => real,
+        Value::Surd { approximation, .. }
+            if maybe_dimension.is_some_and(|dimension| !dimension.is_none()) =>
+        {
+            approximation
+        }
+        Value::Surd { .. } => return format_value(value, args),
+        Value::Complex { .. } | Value::Interval { .. } => {
+            return match maybe_dimension {
+                Some(dimension) if !dimension.is_none() => match base_unit_name(dimension) {
+                    Some(unit_name) => format!("({}) {}", format_value(value, args), unit_name),
+                    None => format!("({}) ({})", format_value(value, args), dimension),
+                },
+                _ => format_value(value, args),
+            }
+        }
+    };
+    match maybe_dimension {
+        Some(Dimension::TIME) => make_duration_string(real, args.precision),
+        Some(Dimension::DATA) => {
+            let (real, unit_name) = byte_size_display(real);
+            format!("{} {}", format_real(&real, args), unit_name)
+        }
+        Some(Dimension::ANGLE) => format!("{} deg", format_real(real, args)),
+        Some(dimension) if !dimension.is_none() => match base_unit_name(dimension) {
+            Some(unit_name) => format!("{} {}", format_real(real, args), unit_name),
+            None => format!("{} ({})", format_real(real, args), dimension),
+        },
+        _ => format_real(real, args),
+    }
+}
+
+/// Returns a warning if showing the value with `format_quantity` and the settings in `args` rounds
+/// it, so that what is shown isn't exactly the result.
+pub fn rounding_warning(
+    value: &Value,
+    maybe_dimension: Option<Dimension>,
+    args: &Args,
+) -> Option<Warning> {
+    if let Some(real) = shown_in_bases(value, maybe_dimension, args) {
+        let rounded = BASES.iter().any(|&(radix, _)| {
+            let scale = BigInt::from(radix).pow(u32::from(args.precision));
+            !(real * BigRational::from_integer(scale)).is_integer()
+        });
+        return rounded.then_some(Warning::Rounded(args.precision));
+    }
+    match value {
+        Value::Real(value) => real_rounding_warning(value, maybe_dimension, args),
+        // See `format_quantity` for why the dimension doesn't matter here.
+        Value::Complex { re, im } => {
+            real_rounding_warning(re, None, args).or_else(|| real_rounding_warning(im, None, args))
+        }
+        // An interval is rounded on purpose, to the precision of its uncertainty.
+        Value::Interval { .. } => None,
+        Value::Surd { approximation, .. }
+            if !args.fractional
+                || maybe_dimension.is_some_and(|dimension| !dimension.is_none()) =>
+        {
+            real_rounding_warning(approximation, maybe_dimension, args)
+        }
+        Value::Surd { .. } => None,
+    }
+}
+
+// The real number to show in several radices, if `format_quantity` shows the value that way.
+fn shown_in_bases<'a>(
+    value: &'a Value,
+    maybe_dimension: Option<Dimension>,
+    args: &Args,
+) -> Option<&'a BigRational> {
+    if !args.bases || maybe_dimension.is_some_and(|dimension| !dimension.is_none()) {
+        return None;
+    }
+    value.as_real()
+}
+
+fn real_rounding_warning(
+    value: &BigRational,
+    maybe_dimension: Option<Dimension>,
+    args: &Args,
+) -> Option<Warning> {
+    let output_radix = args.convert_to_radix.unwrap_or(args.radix);
+    let (shown_value, radix) = match maybe_dimension {
+        // Durations are always shown in decimal, even in fractional mode.
+        Some(Dimension::TIME) => (value.clone(), 10),
+        Some(Dimension::DATA) => (byte_size_display(value).0, output_radix),
+        _ => (value.clone(), output_radix),
+    };
+    if maybe_dimension != Some(Dimension::TIME) && shown_as_fraction(&shown_value, args) {
+        return None;
+    }
+    let shown_value =
+        if maybe_dimension != Some(Dimension::TIME) && shown_in_scientific(&shown_value, args) {
+            if args.notation == Notation::Engineering {
+                engineering_parts(&shown_value).0
+            } else {
+                scientific_parts(&shown_value).0
+            }
+        } else {
+            shown_value
+        };
+    let scale = BigInt::from(radix).pow(u32::from(args.precision));
+    if (shown_value * BigRational::from_integer(scale)).is_integer() {
+        None
+    } else {
+        Some(Warning::Rounded(args.precision))
+    }
+}
+
+/// Formats several values, such as the prime factors from `factor`, as a table with one numbered
+/// row per value. See `format_table`.
+pub fn format_values(values: &[Value], args: &Args) -> String {
+    let rows: Vec<Vec<String>> = values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| vec![(index + 1).to_string(), format_value(value, args)])
+        .collect();
+    format_table(None, &rows, args.radix_point())
+}
+
+/// Lays out rows of cells as a table, with two spaces between columns. The cells in each column are
+/// aligned on their radix points (or the ends of their whole parts) so that numbers can be compared
+/// at a glance. If headers are given, they are shown above the columns, underlined with dashes.
+/// `radix_point` is the character that numbers are written with between their whole and fractional
+/// parts (see `Args::radix_point`).
+pub fn format_table(
+    maybe_headers: Option<&[&str]>,
+    rows: &[Vec<String>],
+    radix_point: char,
+) -> String {
+    // Widths are counted in chars rather than bytes, since that is how `format!` pads, and cells
+    // can hold characters like `±`.
+    let width = |text: &str| text.chars().count();
+    let whole_width = |text: &str| {
+        text.chars()
+            .take_while(|c| *c != radix_point && *c != '/')
+            .count()
+    };
+    let column_count = rows
+        .iter()
+        .map(|row| row.len())
+        .chain(maybe_headers.map(|headers| headers.len()))
+        .max()
+        .unwrap_or(0);
+
+    // The widest whole part and the widest remainder (radix point and fractional digits) in each
+    // column, which together give the width needed to align the column's cells.
+    let mut whole_widths = vec![0; column_count];
+    let mut fraction_widths = vec![0; column_count];
+    for row in rows {
+        for (column, cell) in row.iter().enumerate() {
+            let whole = whole_width(cell);
+            whole_widths[column] = whole_widths[column].max(whole);
+            fraction_widths[column] = fraction_widths[column].max(width(cell) - whole);
+        }
+    }
+    let widths: Vec<usize> = (0..column_count)
+        .map(|column| {
+            let header_width = match maybe_headers {
+                Some(headers) => headers.get(column).map_or(0, |header| width(header)),
+                None => 0,
+            };
+            (whole_widths[column] + fraction_widths[column]).max(header_width)
+        })
+        .collect();
+
+    let mut lines: Vec<String> = Vec::new();
+    if let Some(headers) = maybe_headers {
+        let cells: Vec<String> = (0..column_count)
+            .map(|column| format!("{:>1$}", headers.get(column).unwrap_or(&""), widths[column]))
+            .collect();
+        lines.push(cells.join("  "));
+        let underlines: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        lines.push(underlines.join("  "));
+    }
+    for row in rows {
+        let cells: Vec<String> = (0..column_count)
+            .map(|column| {
+                let cell = row.get(column).map_or("", |cell| cell.as_str());
+                // Cells are right aligned, except that their radix points line up.
+                let trailing = fraction_widths[column] - (width(cell) - whole_width(cell));
+                format!(
+                    "{:>2$}{}",
+                    cell,
+                    " ".repeat(trailing),
+                    widths[column] - trailing
+                )
+            })
+            .collect();
+        lines.push(cells.join("  ").trim_end().to_string());
+    }
+    lines.join("\n")
+}
+
+/// What an expression evaluated to, as returned by `evaluate_expression`.
+pub struct Evaluation {
+    /// There is more than one value only if the whole expression is a function that produces
+    /// several, like `divmod`.
+    pub values: Vec<Value>,
+    /// The values formatted for output, using the format of the variable being displayed, if any.
+    pub output: String,
+    /// The values on a single line, as the input's result is stored in the input history.
+    pub summary: String,
+    /// A warning that formatting the values for `output` rounded them. See `rounding_warning`.
+    pub maybe_rounding_warning: Option<Warning>,
+}
+
+/// Evaluates the expression that `tokenize_input` found in an input. This is what handling an input
+/// comes down to for both the bcalc executable and `Calculator`: the expression is continued from
+/// the previous result and has its parentheses closed as `args` says, the variables in it are
+/// touched, and its result (or its first value) becomes the latest `$ans`. Warnings are added to `warnings`. Storing the
+/// input and its result in the input history is left to the caller.
+#[allow(clippy::too_many_arguments)]
+pub fn evaluate_expression(
+    mut tokens: Vec<Positioned<Token>>,
+    maybe_input_history_id: Option<i64>,
+    mut maybe_vars: Option<&mut VariableStore>,
+    mut maybe_storage: Option<&mut dyn Storage>,
+    args: &Args,
+    maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+    maybe_interrupt: Option<&Interrupt>,
+    warnings: &mut Vec<Warning>,
+) -> Result<Evaluation, CalculatorFailure> {
+    if let Some(vars) = maybe_vars.as_deref() {
+        vars.continue_previous_result(&mut tokens);
+    }
+    if args.effective_close_parens() == CloseParens::Auto {
+        let assumed = close_open_parens(&mut tokens);
+        if assumed > 0 {
+            warnings.push(Warning::AssumedCloseParens(assumed));
+        }
+    }
+
+    if let Some(vars) = maybe_vars.as_deref_mut() {
+        let mut vars_touched: HashSet<&str> = HashSet::new();
+        for positioned_token in &tokens {
+            if let Token::Variable(name) = &positioned_token.value {
+                vars_touched.insert(name);
+            }
+        }
+        for var_name in vars_touched {
+            vars.touch(
+                var_name,
+                maybe_input_history_id,
+                reborrow(&mut maybe_storage),
+            )?;
+        }
+    }
+
+    let maybe_format = match (displayed_variable(&tokens), maybe_vars.as_deref_mut()) {
+        (Some(name), Some(vars)) => vars.format(&name, reborrow(&mut maybe_storage))?,
+        _ => None,
+    };
+
+    let st = SyntaxTree::new_with_args(tokens.into(), args)?;
+    let maybe_dimension = st.dimension()?;
+    let values = st.execute_values(
+        maybe_input_history_id,
+        maybe_vars.as_deref_mut(),
+        reborrow(&mut maybe_storage),
+        args,
+        maybe_observer,
+        maybe_interrupt,
+        Some(warnings),
+    )?;
+    // There is only room for one value per result, so a function like `divmod` leaves its first
+    // value as `$ans`.
+    if let Some(vars) = maybe_vars {
+        vars.push_result(values[0].clone());
+    }
+    if values.len() > 1 {
+        let summary: Vec<String> = values
+            .iter()
+            .map(|value| format_value(value, args))
+            .collect();
+        return Ok(Evaluation {
+            output: format_values(&values, args),
+            summary: summary.join(", "),
+            maybe_rounding_warning: values
+                .iter()
+                .find_map(|value| rounding_warning(value, None, args)),
+            values,
+        });
+    }
+
+    let output_args = match maybe_format {
+        Some(format) => format.apply(args),
+        None => args.clone(),
+    };
+    let output = format_quantity(&values[0], maybe_dimension, &output_args);
+    Ok(Evaluation {
+        summary: output.clone(),
+        maybe_rounding_warning: rounding_warning(&values[0], maybe_dimension, &output_args),
+        output,
+        values,
+    })
+}
+
+/// Evaluates expressions without any of the interactive features of the bcalc executable, such as
+/// commands or input history navigation. Variables assigned by one call to `evaluate` are available
+/// to later calls. By default, nothing is persisted, but a `Storage` backend can be supplied via
+/// `Calculator::with_storage`.
+pub struct Calculator {
+    args: Args,
+    tokenizer: Tokenizer,
+    vars: VariableStore,
+    maybe_storage: Option<Box<dyn Storage>>,
+    warnings: Vec<Warning>,
+}
+
+impl Calculator {
+    /// Only the settings in `args` that affect calculations (such as the radix and precision) are
+    /// used.
+    pub fn new(args: Args) -> Calculator {
+        Calculator {
+            args,
+            tokenizer: Tokenizer::new(),
+            vars: VariableStore::new(),
+            maybe_storage: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Like `Calculator::new`, but each evaluated input is added to the input history in
+    /// `storage`, and variables are saved to and loaded from it. Any settings that were saved to
+    /// `storage` replace the corresponding ones in `args`.
+    pub fn with_storage(
+        mut args: Args,
+        mut storage: Box<dyn Storage>,
+    ) -> Result<Calculator, Box<dyn std::error::Error>> {
+        args.restore_saved_settings(storage.as_mut(), |_| false)?;
+        Ok(Calculator {
+            args,
+            tokenizer: Tokenizer::new(),
+            vars: VariableStore::new(),
+            maybe_storage: Some(storage),
+            warnings: Vec::new(),
+        })
+    }
+
+    /// Makes the function available to later calls to `evaluate`. See
+    /// `Tokenizer::register_function`.
+    pub fn register_function(&mut self, function: Arc<dyn CustomFunction>) -> Result<(), String> {
+        self.tokenizer.register_function(function)
+    }
+
+    pub fn storage_mut(&mut self) -> Option<&mut dyn Storage> {
+        match &mut self.maybe_storage {
+            Some(storage) => Some(storage.as_mut()),
+            None => None,
+        }
+    }
+
+    pub fn args(&self) -> &Args {
+        &self.args
+    }
+
+    pub fn args_mut(&mut self) -> &mut Args {
+        &mut self.args
+    }
+
+    /// Warnings about the last evaluation, such as taking the modulus of non-integers. Whether
+    /// formatting a value rounds it is reported by `rounding_warning` instead.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Evaluates the input, which should be an expression like those accepted by the bcalc
+    /// executable. Commands (inputs starting with `/`) are not supported. If the input is a function
+    /// that produces several values, like `divmod`, the first of them is returned. See
+    /// `evaluate_values` for all of them.
+    pub fn evaluate(&mut self, input: &str) -> Result<Value, CalculatorFailure> {
+        self.evaluate_traced(input, None)
+    }
+
+    /// Like `evaluate`, but returns every value that the input produces, in order.
+    pub fn evaluate_values(&mut self, input: &str) -> Result<Vec<Value>, CalculatorFailure> {
+        self.evaluate_all(input, None, None)
+    }
+
+    /// Like `evaluate`, but if an observer is given, it is called with each step of the evaluation
+    /// as it happens. See `SyntaxTree::execute_traced`.
+    pub fn evaluate_traced(
+        &mut self,
+        input: &str,
+        maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+    ) -> Result<Value, CalculatorFailure> {
+        self.evaluate_interruptible(input, maybe_observer, None)
+    }
+
+    /// Like `evaluate_traced`, but the evaluation can be cancelled through the `Interrupt`, such as
+    /// from another thread. See `SyntaxTree::execute_interruptible`.
+    pub fn evaluate_interruptible(
+        &mut self,
+        input: &str,
+        maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+    ) -> Result<Value, CalculatorFailure> {
+        self.evaluate_all(input, maybe_observer, maybe_interrupt)
+            .map(|mut values| values.remove(0))
+    }
+
+    fn evaluate_all(
+        &mut self,
+        input: &str,
+        maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+    ) -> Result<Vec<Value>, CalculatorFailure> {
+        self.warnings.clear();
+        self.tokenizer.set_si_suffixes(!self.args.no_si_suffixes);
+        self.tokenizer.set_milli_suffix(self.args.milli_suffix);
+        let tokens = match tokenize_input(&self.tokenizer, input, &self.args)? {
+            ParsedInput::Tokens(t) => t,
+            ParsedInput::Command((command, _)) => {
+                return Err(InputError(MaybePositioned::new_positioned(
+                    InputErrorKind::Other("Commands cannot be evaluated".to_string()),
+                    command.position,
+                )))
+            }
+        };
+        if tokens.is_empty() {
+            return Err(InputError(MaybePositioned::new_unpositioned(
+                InputErrorKind::Syntax(SyntaxError::NoInput),
+            )));
+        }
+
+        let mut maybe_storage: Option<&mut dyn Storage> = match &mut self.maybe_storage {
+            Some(storage) => Some(storage.as_mut()),
+            None => None,
+        };
+        // Everything that evaluating the input stores is written together.
+        if let Some(storage) = reborrow(&mut maybe_storage) {
+            storage.begin_batch()?;
+        }
+
+        let evaluate_in_batch = || -> Result<Vec<Value>, CalculatorFailure> {
+            let maybe_input_history_id = match reborrow(&mut maybe_storage) {
+                Some(storage) => Some(storage.add_to_input_history(input)?),
+                None => None,
+            };
+
+            let evaluation = evaluate_expression(
+                tokens,
+                maybe_input_history_id,
+                Some(&mut self.vars),
+                reborrow(&mut maybe_storage),
+                &self.args,
+                maybe_observer,
+                maybe_interrupt,
+                &mut self.warnings,
+            )?;
+
+            if let (Some(storage), Some(id)) =
+                (reborrow(&mut maybe_storage), maybe_input_history_id)
+            {
+                storage.set_input_history_result(id, &evaluation.summary)?;
+            }
+
+            Ok(evaluation.values)
+        };
+        let result = evaluate_in_batch();
+
+        let maybe_end_result = maybe_storage.map(|storage| storage.end_batch());
+        let values = result?;
+        if let Some(end_result) = maybe_end_result {
+            end_result?;
+        }
+        Ok(values)
+    }
+
+    /// Formats the value according to the output settings that the `Calculator` was created with.
+    pub fn format(&self, value: &Value) -> String {
+        format_value(value, &self.args)
+    }
+}
+
+impl Default for Calculator {
+    fn default() -> Calculator {
+        Calculator::new(Args::default())
+    }
+}
+
+#[cfg(test)]
+mod calculator_tests {
+    use crate::{
+        custom_function::CustomFunction,
+        error::{
+            CalculatorFailure, InputErrorKind, MathExecutionError, ParseError, SyntaxError, Warning,
+        },
+        format_table, format_values,
+        interrupt::{Interrupt, Progress},
+        rounding_warning,
+        storage::{SavedSetting, Storage},
+        token::FunctionNameToken,
+        units::Dimension,
+        variable::Variable,
+        AngleUnit, Args, Calculator, CloseParens, FractionStyle, Value,
+    };
+    use num::{bigint::BigInt, rational::BigRational};
+    use std::{collections::HashMap, sync::Arc};
+
+    struct Hypotenuse2;
+
+    impl CustomFunction for Hypotenuse2 {
+        fn name(&self) -> &str {
+            "hyp2"
+        }
+
+        fn call(&self, arguments: &[BigRational]) -> Result<BigRational, String> {
+            match arguments {
+                [a, b] => Ok(a * a + b * b),
+                _ => Err("Expected 2 arguments".to_string()),
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct MemoryStorage {
+        inputs: Vec<(String, Option<String>)>,
+        vars: HashMap<String, Value>,
+        settings: HashMap<String, i64>,
+        in_batch: bool,
+    }
+
+    impl Storage for MemoryStorage {
+        fn add_to_input_history(&mut self, input: &str) -> Result<i64, Box<dyn std::error::Error>> {
+            assert!(self.in_batch);
+            self.inputs.push((input.to_string(), None));
+            Ok(self.inputs.len() as i64)
+        }
+
+        fn set_input_history_result(
+            &mut self,
+            id: i64,
+            result: &str,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            assert!(self.in_batch);
+            self.inputs[id as usize - 1].1 = Some(result.to_string());
+            Ok(())
+        }
+
+        fn get_all_input_history(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+            Ok(self.inputs.iter().map(|(input, _)| input.clone()).collect())
+        }
+
+        fn set_variable(
+            &mut self,
+            var: &Variable,
+            _last_used_by_id: i64,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            assert!(self.in_batch);
+            self.vars.insert(var.name.to_string(), var.value.clone());
+            Ok(())
+        }
+
+        fn touch_variable(
+            &mut self,
+            _name: &str,
+            _last_used_by_id: i64,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            Ok(())
+        }
+
+        fn get_variable(
+            &mut self,
+            name: &str,
+        ) -> Result<Option<Variable>, Box<dyn std::error::Error>> {
+            Ok(self.vars.get(name).map(|value| Variable {
+                name: name.into(),
+                value: value.clone(),
+            }))
+        }
+
+        fn get_variable_names(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+            Ok(self.vars.keys().cloned().collect())
+        }
+
+        fn clear_variable(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+            self.vars.remove(name);
+            Ok(())
+        }
+
+        fn get_setting(
+            &mut self,
+            setting: SavedSetting,
+        ) -> Result<Option<i64>, Box<dyn std::error::Error>> {
+            Ok(self.settings.get(&format!("{:?}", setting)).copied())
+        }
+
+        fn set_setting(
+            &mut self,
+            setting: SavedSetting,
+            maybe_value: Option<i64>,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            let key = format!("{:?}", setting);
+            match maybe_value {
+                Some(value) => self.settings.insert(key, value),
+                None => self.settings.remove(&key),
+            };
+            Ok(())
+        }
+
+        fn begin_batch(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+            assert!(!self.in_batch);
+            self.in_batch = true;
+            Ok(())
+        }
+
+        fn end_batch(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+            assert!(self.in_batch);
+            self.in_batch = false;
+            Ok(())
+        }
+    }
+
+    fn int(i: i64) -> Value {
+        Value::from(BigInt::from(i))
+    }
+
+    #[test]
+    fn evaluate() {
+        let mut calculator = Calculator::default();
+        assert_eq!(calculator.evaluate("1 + 2 * 3").unwrap(), int(7));
+        assert_eq!(
+            calculator.evaluate("1 / 3").unwrap(),
+            Value::Real(BigRational::new(BigInt::from(1), BigInt::from(3)))
+        );
+    }
+
+    #[test]
+    fn variables_persist() {
+        let mut calculator = Calculator::default();
+        assert_eq!(calculator.evaluate("$x = 4").unwrap(), int(4));
+        assert_eq!(calculator.evaluate("$x * $x").unwrap(), int(16));
+    }
+
+    #[test]
+    fn answer_variables() {
+        let mut calculator = Calculator::default();
+        assert!(calculator.evaluate("$ans").is_err());
+        assert_eq!(calculator.evaluate("3 + 4").unwrap(), int(7));
+        assert_eq!(calculator.evaluate("$ans * 2").unwrap(), int(14));
+        assert_eq!(calculator.evaluate("$ans2 + $ans1").unwrap(), int(21));
+        assert_eq!(calculator.evaluate("$ans3").unwrap(), int(7));
+        assert!(calculator.evaluate("$ans9").is_err());
+        assert!(matches!(
+            input_error_kind(&mut calculator, "$ans = 5"),
+            InputErrorKind::Syntax(SyntaxError::ReservedVariable(_))
+        ));
+        assert!(matches!(
+            input_error_kind(&mut calculator, "let $ans2 = 1 in $ans2"),
+            InputErrorKind::Syntax(SyntaxError::ReservedVariable(_))
+        ));
+        assert_eq!(calculator.evaluate("$answer = 2").unwrap(), int(2));
+        assert_eq!(calculator.evaluate("$ans").unwrap(), int(2));
+    }
+
+    #[test]
+    fn continued_results() {
+        let mut calculator = Calculator::default();
+        assert!(calculator.evaluate("* 2").is_err());
+        assert_eq!(calculator.evaluate("100").unwrap(), int(100));
+        assert_eq!(calculator.evaluate(" * 1.5").unwrap(), int(150));
+        assert_eq!(calculator.evaluate("/ 3 + 1").unwrap(), int(51));
+        assert_eq!(calculator.evaluate("- 1").unwrap(), int(50));
+        assert_eq!(calculator.evaluate("-1").unwrap(), int(-1));
+        assert_eq!(calculator.evaluate("-(2)").unwrap(), int(-2));
+        assert_eq!(calculator.evaluate("^ 2").unwrap(), int(4));
+        assert_eq!(calculator.evaluate("|| 0").unwrap(), int(1));
+        assert_eq!(calculator.evaluate("||-3| - 5|").unwrap(), int(2));
+    }
+
+    #[test]
+    fn format() {
+        let mut calculator = Calculator::default();
+        let value = calculator.evaluate("2 / 3").unwrap();
+        assert_eq!(calculator.format(&value), "0.66667");
+        calculator.args_mut().fractional = true;
+        assert_eq!(calculator.format(&value), "2/3");
+    }
+
+    #[test]
+    fn complex_numbers() {
+        let mut calculator = Calculator::default();
+        let mut format = |input: &str| {
+            let value = calculator.evaluate(input).unwrap();
+            calculator.format(&value)
+        };
+        assert_eq!(format("sqrt(-1)"), "i");
+        assert_eq!(format("(2+3i)*(1-i)"), "5 + i");
+        assert_eq!(format("abs(3+4i)"), "5");
+        assert_eq!(format("conj(2+3i)"), "2 - 3i");
+        assert_eq!(format("-2i / 4"), "-0.5i");
+        assert_eq!(format("i^2"), "-1");
+        assert_eq!(format("$z = 1 - i"), "1 - i");
+        assert_eq!(format("$z * $z"), "-2i");
+        calculator.args_mut().fractional = true;
+        let value = calculator.evaluate("1/3 + i/2").unwrap();
+        assert_eq!(calculator.format(&value), "1/3 + (1/2)i");
+    }
+
+    #[test]
+    fn intervals() {
+        let mut calculator = Calculator::default();
+        let mut format = |input: &str| {
+            let value = calculator.evaluate(input).unwrap();
+            calculator.format(&value)
+        };
+        assert_eq!(format("(5 ± 0.1)^2"), "25.0 ± 1.0");
+        assert_eq!(format("interval(4.9, 5.1)"), "5.00 ± 0.10");
+        assert_eq!(format("1234.5 +/- 30"), "1235 ± 30");
+        assert_eq!(format("1 / (2 ± 1)"), "0.67 ± 0.33");
+        assert_eq!(format("$x = 10 ± 1"), "10.0 ± 1.0");
+        assert_eq!(format("$x * 3"), "30.0 ± 3.0");
+        calculator.args_mut().fractional = true;
+        let value = calculator.evaluate("1/3 ± 1/6").unwrap();
+        assert_eq!(calculator.format(&value), "1/3 ± 1/6");
+    }
+
+    #[test]
+    fn surds() {
+        let mut calculator = Calculator::default();
+        let format = |calculator: &mut Calculator, input: &str| {
+            let value = calculator.evaluate(input).unwrap();
+            calculator.format(&value)
+        };
+        assert_eq!(format(&mut calculator, "sqrt(2)"), "1.41421");
+        assert_eq!(format(&mut calculator, "sqrt(2)^2"), "2");
+        calculator.args_mut().fractional = true;
+        assert_eq!(format(&mut calculator, "sqrt(2)"), "sqrt(2)");
+        assert_eq!(format(&mut calculator, "0 - sqrt(2)"), "-sqrt(2)");
+        assert_eq!(format(&mut calculator, "sqrt(18)/4"), "3/4*sqrt(2)");
+        assert_eq!(format(&mut calculator, "$x = 3*sqrt(2)"), "3*sqrt(2)");
+        assert_eq!(format(&mut calculator, "$x^2"), "18");
+        calculator.args_mut().fraction_style = FractionStyle::Both;
+        assert_eq!(format(&mut calculator, "sqrt(2)"), "sqrt(2) (1.41421)");
+    }
+
+    #[test]
+    fn decimal_comma() {
+        let mut calculator = Calculator::default();
+        calculator.args_mut().decimal_comma = true;
+        assert_eq!(calculator.evaluate("1.234,5 * 2").unwrap(), int(2469));
+        let value = calculator.evaluate("max(1 000,25; 2) * 1.000").unwrap();
+        assert_eq!(calculator.format(&value), "1000250");
+        let value = calculator.evaluate("1/8 + 1 234 567").unwrap();
+        assert_eq!(calculator.format(&value), "1234567,125");
+        calculator.args_mut().commas = true;
+        assert_eq!(calculator.format(&value), "1.234.567,125");
+
+        // Errors quote the input as it was typed.
+        match calculator.evaluate("2 + 1,5e") {
+            Err(CalculatorFailure::InputError(error)) => {
+                assert_eq!(error.value.to_string(), "Unable to parse number: '1,5e'");
+                assert_eq!(error.maybe_position.unwrap().start, 4);
+            }
+            _ => panic!("Expected an error for '2 + 1,5e'"),
+        }
+    }
+
+    #[test]
+    fn assumed_close_parens() {
+        let mut calculator = Calculator::default();
+        assert!(matches!(
+            input_error_kind(&mut calculator, "2 * (3 + max(4, 5"),
+            InputErrorKind::Syntax(SyntaxError::MismatchedOpenParen)
+        ));
+        calculator.args_mut().close_parens = CloseParens::Auto;
+        assert_eq!(calculator.evaluate("2 * (3 + max(4, 5").unwrap(), int(16));
+        assert_eq!(calculator.warnings(), &[Warning::AssumedCloseParens(2)]);
+        assert_eq!(calculator.evaluate("(1 + 2)").unwrap(), int(3));
+        assert!(calculator.warnings().is_empty());
+        assert!(calculator.evaluate("(1 + 2))").is_err());
+    }
+
+    #[test]
+    fn multiple_values() {
+        let mut calculator = Calculator::default();
+        assert_eq!(
+            calculator.evaluate_values("divmod(7, 2)").unwrap(),
+            [int(3), int(1)]
+        );
+        assert_eq!(calculator.evaluate("divmod(7, 2)").unwrap(), int(3));
+        assert_eq!(
+            calculator.evaluate_values("factor(12)").unwrap(),
+            [int(2), int(2), int(3)]
+        );
+        assert_eq!(calculator.evaluate_values("2 * 3").unwrap(), [int(6)]);
+        // A function that produces several values leaves the first of them as `$ans`.
+        calculator.evaluate_values("divmod(17, 5)").unwrap();
+        assert_eq!(calculator.evaluate("$ans").unwrap(), int(3));
+        assert_eq!(calculator.evaluate("$ans3").unwrap(), int(6));
+        assert!(calculator.evaluate("1 + divmod(7, 2)").is_err());
+    }
+
+    #[test]
+    fn si_suffixes() {
+        let mut calculator = Calculator::default();
+        assert_eq!(calculator.evaluate("4.7k * 2").unwrap(), int(9400));
+        calculator.args_mut().milli_suffix = true;
+        assert_eq!(
+            calculator.evaluate("4.7m * 2").unwrap(),
+            Value::Real(BigRational::new(BigInt::from(47), BigInt::from(5000)))
+        );
+        calculator.args_mut().no_si_suffixes = true;
+        assert!(calculator.evaluate("4.7k * 2").is_err());
+    }
+
+    #[test]
+    fn strict() {
+        let mut calculator = Calculator::default();
+        calculator.args_mut().close_parens = CloseParens::Auto;
+        assert_eq!(calculator.evaluate("max 3").unwrap(), int(3));
+        calculator.args_mut().strict = true;
+        for input in ["max 3", "sqrt 4", "abs -2"] {
+            assert!(matches!(
+                input_error_kind(&mut calculator, input),
+                InputErrorKind::Syntax(SyntaxError::FunctionWithoutParens(_))
+            ));
+        }
+        assert!(matches!(
+            input_error_kind(&mut calculator, "(1 + 2"),
+            InputErrorKind::Syntax(SyntaxError::MismatchedOpenParen)
+        ));
+        assert_eq!(calculator.evaluate("max(3, 4)").unwrap(), int(4));
+        assert_eq!(calculator.evaluate("sqrt(4)").unwrap(), int(2));
+        assert_eq!(calculator.evaluate("not 0").unwrap(), int(1));
+    }
+
+    #[test]
+    fn angle_units() {
+        let evaluate = |calculator: &mut Calculator, input: &str| {
+            let value = calculator.evaluate(input).unwrap();
+            calculator.format(&value)
+        };
+        let mut calculator = Calculator::default();
+        assert_eq!(evaluate(&mut calculator, "sin(1)"), "0.84147");
+        assert_eq!(evaluate(&mut calculator, "sin(30 deg)"), "0.5");
+        assert_eq!(evaluate(&mut calculator, "asin(1)"), "1.57080");
+        calculator.args_mut().angle_unit = AngleUnit::Degrees;
+        assert_eq!(evaluate(&mut calculator, "sin(30)"), "0.5");
+        assert_eq!(evaluate(&mut calculator, "sin(1 rad)"), "0.84147");
+
+        // Radians are worked out with pi at the working precision, however high it is.
+        calculator.args_mut().precision = 150;
+        assert_eq!(evaluate(&mut calculator, "sin(pi rad)"), "0");
+        assert_eq!(evaluate(&mut calculator, "1 rad == 180 deg / pi"), "1");
+        calculator.args_mut().precision = 5;
+        assert_eq!(evaluate(&mut calculator, "asin(1)"), "90");
+        assert_eq!(evaluate(&mut calculator, "atan2(-1, -1)"), "-135");
+        assert!(matches!(
+            input_error_kind(&mut calculator, "tan(270)"),
+            InputErrorKind::Math(MathExecutionError::InvalidArgument(
+                FunctionNameToken::Tan,
+                _
+            ))
+        ));
+        assert!(matches!(
+            input_error_kind(&mut calculator, "asin(30 deg)"),
+            InputErrorKind::Math(MathExecutionError::DimensionMismatch(..))
+        ));
+    }
+
+    fn input_error_kind(calculator: &mut Calculator, input: &str) -> InputErrorKind {
+        match calculator.evaluate(input) {
+            Err(CalculatorFailure::InputError(message)) => message.value,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn rejected_inputs() {
+        let mut calculator = Calculator::default();
+        assert!(matches!(
+            input_error_kind(&mut calculator, "/help"),
+            InputErrorKind::Other(_)
+        ));
+        assert!(matches!(
+            input_error_kind(&mut calculator, ""),
+            InputErrorKind::Syntax(SyntaxError::NoInput)
+        ));
+    }
+
+    #[test]
+    fn error_kinds() {
+        let mut calculator = Calculator::default();
+        assert!(matches!(
+            input_error_kind(&mut calculator, "1.2.3"),
+            InputErrorKind::Parse(ParseError::InvalidNumber(_))
+        ));
+        assert!(matches!(
+            input_error_kind(&mut calculator, "(1 + 2"),
+            InputErrorKind::Syntax(SyntaxError::MismatchedOpenParen)
+        ));
+        assert!(matches!(
+            input_error_kind(&mut calculator, "1 / 0"),
+            InputErrorKind::Math(MathExecutionError::DivisionByZero)
+        ));
+        assert!(matches!(
+            input_error_kind(&mut calculator, "$nope"),
+            InputErrorKind::Math(MathExecutionError::UnknownVariable(..))
+        ));
+        assert!(matches!(
+            input_error_kind(&mut calculator, "(2i)!"),
+            InputErrorKind::Math(MathExecutionError::NotReal(_))
+        ));
+        assert!(matches!(
+            input_error_kind(&mut calculator, "ln(0)"),
+            InputErrorKind::Math(MathExecutionError::InvalidArgument(
+                FunctionNameToken::Ln,
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn interrupt() {
+        let mut calculator = Calculator::default();
+        calculator.args_mut().precision = 40;
+        let (interrupt, progress) = Interrupt::with_progress();
+        let value = calculator
+            .evaluate_interruptible("2 ^ (1/2)", None, Some(&interrupt))
+            .unwrap();
+        assert!(calculator.format(&value).starts_with("1.41421356237"));
+        let reports: Vec<Progress> = progress.try_iter().collect();
+        assert!(!reports.is_empty());
+        assert!(reports.windows(2).all(|w| w[0].digits <= w[1].digits));
+        let total_digits = reports[0].total_digits;
+        assert!(total_digits > 40);
+        assert!(reports
+            .iter()
+            .all(|p| p.total_digits == total_digits && p.digits <= total_digits));
+
+        interrupt.cancel();
+        match calculator.evaluate_interruptible("2 ^ (1/2)", None, Some(&interrupt)) {
+            Err(CalculatorFailure::InputError(message)) => assert!(matches!(
+                message.value,
+                InputErrorKind::Math(MathExecutionError::Cancelled)
+            )),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn storage() {
+        let mut storage = MemoryStorage::default();
+        storage.vars.insert("$y".to_string(), int(5));
+        storage
+            .set_setting(SavedSetting::Precision, Some(2))
+            .unwrap();
+
+        let mut calculator = Calculator::with_storage(Args::default(), Box::new(storage)).unwrap();
+        assert_eq!(calculator.args().precision, 2);
+        assert_eq!(calculator.evaluate("$x = $y + 1").unwrap(), int(6));
+        assert!(calculator.evaluate("1 +").is_err());
+        // The failed evaluation still needs to have finished its batch for this one to start one.
+        assert_eq!(calculator.evaluate("$x * 2").unwrap(), int(12));
+
+        let storage = calculator.storage_mut().unwrap();
+        assert_eq!(
+            storage.get_all_input_history().unwrap(),
+            vec![
+                "$x = $y + 1".to_string(),
+                "1 +".to_string(),
+                "$x * 2".to_string()
+            ]
+        );
+        assert_eq!(storage.get_variable("$x").unwrap().unwrap().value, int(6));
+    }
+
+    #[test]
+    fn suggestions() {
+        let mut storage = MemoryStorage::default();
+        storage.vars.insert("$width".to_string(), int(5));
+        let mut calculator = Calculator::with_storage(Args::default(), Box::new(storage)).unwrap();
+        calculator.evaluate("$height = 2").unwrap();
+        let error_message =
+            |calculator: &mut Calculator, input: &str| match calculator.evaluate(input) {
+                Err(CalculatorFailure::InputError(message)) => message.value.to_string(),
+                _ => panic!(),
+            };
+        assert_eq!(
+            error_message(&mut calculator, "$widht * $height"),
+            "Unknown variable: $widht (did you mean $width?)"
+        );
+        assert_eq!(
+            error_message(&mut calculator, "$hieght"),
+            "Unknown variable: $hieght (did you mean $height?)"
+        );
+        assert_eq!(
+            error_message(&mut calculator, "mxa(1, 2)"),
+            "Unknown name: 'mxa' (did you mean max?)"
+        );
+        assert_eq!(
+            error_message(&mut calculator, "zzzz"),
+            "Unable to parse number: 'zzzz'"
+        );
+    }
+
+    #[test]
+    fn history_aggregates() {
+        let mut calculator = Calculator::default();
+        assert!(calculator.evaluate("histsum(1)").is_err());
+        for input in ["5", "$x = 7", "2 * 4"] {
+            calculator.evaluate(input).unwrap();
+        }
+        assert_eq!(calculator.evaluate("histsum(3)").unwrap(), int(20));
+        // Each result is recorded, including those of the aggregates themselves.
+        assert_eq!(calculator.evaluate("histmax(2)").unwrap(), int(20));
+        assert_eq!(calculator.evaluate("histmin(5)").unwrap(), int(5));
+        assert_eq!(
+            calculator.evaluate("histmean(2)").unwrap(),
+            Value::Real(BigRational::new(BigInt::from(25), BigInt::from(2)))
+        );
+        assert!(calculator.evaluate("histsum(100)").is_err());
+        assert!(calculator.evaluate("histsum(0)").is_err());
+    }
+
+    #[test]
+    fn custom_function() {
+        let mut calculator = Calculator::default();
+        calculator.register_function(Arc::new(Hypotenuse2)).unwrap();
+        assert_eq!(calculator.evaluate("hyp2(3, 4) + 1").unwrap(), int(26));
+        assert_eq!(calculator.evaluate("hyp2(1, 2) ^ 2").unwrap(), int(25));
+        match calculator.evaluate("1 + hyp2(3)") {
+            Err(CalculatorFailure::InputError(message)) => {
+                assert_eq!(
+                    message.value.to_string(),
+                    "Function (hyp2): Expected 2 arguments"
+                );
+                let position = message.maybe_position.unwrap();
+                assert_eq!((position.start, position.width), (4, 4));
+            }
+            _ => panic!(),
+        }
+        assert!(calculator.register_function(Arc::new(Hypotenuse2)).is_err());
+    }
+
+    #[test]
+    fn warnings() {
+        let mut calculator = Calculator::default();
+        calculator.evaluate("5.5 % 2").unwrap();
+        assert_eq!(calculator.warnings(), [Warning::NonIntegerModulus]);
+        calculator.evaluate("5 % 2").unwrap();
+        assert!(calculator.warnings().is_empty());
+
+        let mut args = Args::default();
+        let third = Value::Real(BigRational::new(BigInt::from(1), BigInt::from(3)));
+        let quarter = Value::Real(BigRational::new(BigInt::from(1), BigInt::from(4)));
+        assert_eq!(
+            rounding_warning(&third, None, &args),
+            Some(Warning::Rounded(5))
+        );
+        assert_eq!(rounding_warning(&quarter, None, &args), None);
+        // One sixteenth of a second, shown as a duration, needs 4 decimal places.
+        let sixteenth = Value::Real(BigRational::new(BigInt::from(1), BigInt::from(16)));
+        assert_eq!(
+            rounding_warning(&sixteenth, Some(Dimension::TIME), &args),
+            None
+        );
+        args.precision = 2;
+        assert_eq!(rounding_warning(&quarter, None, &args), None);
+        assert_eq!(
+            rounding_warning(&sixteenth, Some(Dimension::TIME), &args),
+            Some(Warning::Rounded(2))
+        );
+        args.radix = 3;
+        assert_eq!(rounding_warning(&third, None, &args), None);
+        args.fractional = true;
+        args.radix = 10;
+        assert_eq!(rounding_warning(&third, None, &args), None);
+        assert_eq!(
+            Warning::Rounded(2).to_string(),
+            "Result rounded to 2 digits"
+        );
+    }
+
+    #[test]
+    fn tables() {
+        let args = Args::default();
+        assert_eq!(
+            format_values(
+                &[
+                    int(1234),
+                    Value::Real(BigRational::new(BigInt::from(3), BigInt::from(2)))
+                ],
+                &args
+            ),
+            "1  1234\n2     1.5"
+        );
+        assert_eq!(format_values(&[int(-3), int(-20)], &args), "1   -3\n2  -20");
+
+        let rows = vec![
+            vec!["1".to_string(), "0.25".to_string()],
+            vec!["10".to_string(), "12".to_string()],
+        ];
+        assert_eq!(
+            format_table(Some(&["x", "value"]), &rows, '.'),
+            " x  value\n--  -----\n 1   0.25\n10  12"
+        );
+
+        let rows = vec![
+            vec!["8".to_string(), "8 ± 0.1".to_string()],
+            vec!["10".to_string(), "10 ± 0.1".to_string()],
+        ];
+        assert_eq!(
+            format_table(Some(&["$x", "value"]), &rows, '.'),
+            concat!(
+                "$x     value\n",
+                "--  --------\n",
+                " 8   8 ± 0.1\n",
+                "10  10 ± 0.1"
+            )
+        );
+    }
+}
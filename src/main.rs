@@ -1,38 +1,42 @@
-mod commands;
-mod error;
-mod input_history;
-mod operations;
-mod position;
-mod saved_data;
-mod syntax_tree;
-mod token;
-mod variable;
-
-use clap::Parser;
-use commands::CommandExecutor;
+use bcalc::{
+    bench,
+    commands::{CommandExecutor, MEMORY_VARIABLE_NAME},
+    error::{CalculatorEnvironmentError, CalculatorFailure, InternalCalculatorError, Warning},
+    evaluate_expression,
+    input_history::InputHistory,
+    interrupt::Interrupt,
+    keystroke_macro::{PLAY_KEY, RECORD_KEY},
+    recovery::Recovery,
+    registers::{input_text, Registers},
+    saved_data::{recovery_path, SavedData},
+    storage::Storage,
+    token::{close_open_parens, is_command, ParsedInput},
+    tokenize_input, Args, ArgsCommand, CloseParens, ErrorAlert, Tokenizer, VariableStore,
+};
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches};
 use crossterm::{
-    cursor::{self, MoveTo, MoveToColumn, MoveToNextLine},
-    event::{self, Event, KeyCode, KeyModifiers},
+    cursor::{self, MoveTo, MoveToColumn, MoveToNextLine, MoveToPreviousLine},
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     execute, queue,
-    style::Print,
+    style::{Attribute, Print, SetAttribute},
     terminal::{
         self, Clear,
-        ClearType::{CurrentLine, FromCursorDown},
+        ClearType::{CurrentLine, FromCursorDown, UntilNewLine},
         EnterAlternateScreen, LeaveAlternateScreen,
     },
 };
-use error::{CalculatorEnvironmentError, CalculatorFailure, InternalCalculatorError};
-use input_history::InputHistory;
-use operations::make_decimal_string;
-use saved_data::SavedData;
 use std::{
     cmp::{max, min},
-    collections::HashSet,
-    io::{stdout, Write},
+    collections::VecDeque,
+    io::{self, stdout, Stdout, Write},
+    panic,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+    sync::{Arc, Mutex, TryLockError},
+    thread,
+    time::Duration,
 };
-use syntax_tree::SyntaxTree;
-use token::{ParsedInput, Token, Tokenizer};
-use variable::VariableStore;
 
 // `PROMPT_STR.len()` should equal `SCROLL_LEFT_INDICATOR_STR.len()`.
 const PROMPT_STR: &str = "# ";
@@ -41,62 +45,36 @@ const SCROLL_RIGHT_INDICATOR_STR: &str = " >";
 
 const LARGE_CURSOR_MOVE_DISTANCE: usize = 15;
 
-#[derive(Parser, Clone, Debug)]
-#[command(version, about, long_about = None)]
-pub struct Args {
-    /// Radix (base) to use for input and output.
-    #[arg(short, long, default_value_t = 10)]
-    #[arg(value_parser = clap::value_parser!(u8).range(2..=16))]
-    radix: u8,
-
-    /// If specified, input will be read from the provided string rather than interactively.
-    #[arg(short, long)]
-    input: Option<String>,
-
-    /// If specified, an alternate terminal screen is opened rather than doing the calculations
-    /// inline. In this mode, entered calculations wrap rather than scrolling.
-    #[arg(short, long)]
-    alternate_screen: bool,
+// How often we check whether a calculation has finished while watching for Control+C.
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(20);
 
-    /// Normally, the calculator attempts to load data such as input history from a user-specific
-    /// database. If this option is specified, the database will not be used.
-    #[arg(long)]
-    no_db: bool,
-
-    /// If specified, the output radix (base) will be set to this rather than being the same as the
-    /// input radix.
-    #[arg(long)]
-    #[arg(value_parser = clap::value_parser!(u8).range(1..17))]
-    convert_to_radix: Option<u8>,
-
-    /// Maximum number of decimal digits to output.
-    #[arg(short, long, default_value_t = 5)]
-    precision: u8,
-
-    /// Additional decimal digits to store internally.
-    #[arg(long, default_value_t = 10)]
-    extra_precision: u8,
-
-    /// If specified, an alternate terminal screen is opened rather than doing the calculations
-    /// inline. In this mode, entered calculations wrap rather than scrolling.
-    #[arg(short, long)]
-    fractional: bool,
-
-    /// If specified, the output will use commas as thousands separators to make long numbers more
-    /// readable.
-    #[arg(short, long)]
-    commas: bool,
-
-    /// If specified and the output radix is above 10, digits above 9 will be displayed in upper
-    /// case.
-    #[arg(short, long)]
-    upper: bool,
-}
+// How long the input line stays highlighted when `--error-alert flash` is used.
+const ERROR_FLASH_DURATION: Duration = Duration::from_millis(150);
+
+// If this environment variable is set to anything other than "0", `--screen-reader` is implied.
+const SCREEN_READER_ENV_VAR_NAME: &str = "BCALC_SCREEN_READER";
+
+// Precedes the result shown after an unmodified historical input.
+const RESULT_HINT_PREFIX_STR: &str = "  = ";
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut args = Args::parse();
+    // We parse via `ArgMatches` rather than `Args::parse` so that we can later tell which arguments
+    // were actually specified on the command line. See `restore_saved_settings`.
+    let arg_matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&arg_matches)?;
+    if let Some(ArgsCommand::Bench(bench_args)) = &args.command {
+        println!("{}", bench::run(bench_args)?);
+        return Ok(());
+    }
+    if let Some(value) = std::env::var_os(SCREEN_READER_ENV_VAR_NAME) {
+        if !value.is_empty() && value != "0" {
+            args.screen_reader = true;
+        }
+    }
     let mut command_executor = CommandExecutor::new();
-    let tokenizer = Tokenizer::new();
+    let mut tokenizer = Tokenizer::new();
+    tokenizer.set_si_suffixes(!args.no_si_suffixes);
+    tokenizer.set_milli_suffix(args.milli_suffix);
 
     match args.input.clone() {
         Some(input) => {
@@ -108,8 +86,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 None,
                 None,
                 None,
+                None,
             ) {
-                Ok(result) => println!("{}", result),
+                Ok((result, warnings)) if args.json => println!(
+                    "{}",
+                    serde_json::json!({ "result": result, "warnings": warnings })
+                ),
+                Ok((result, warnings)) => {
+                    println!("{}", result);
+                    for warning in warnings {
+                        eprintln!("Warning: {}", warning);
+                    }
+                }
+                Err(CalculatorFailure::InputError(message)) if args.json => {
+                    println!("{}", serde_json::json!({ "error": message }))
+                }
                 Err(CalculatorFailure::InputError(message)) => {
                     eprintln!("Error: {}", message.value)
                 }
@@ -117,21 +108,40 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
         None => {
-            let mut stdout = stdout();
             terminal::enable_raw_mode()?;
             if args.alternate_screen {
-                if let Err(e) = execute!(stdout, EnterAlternateScreen) {
+                if let Err(e) = execute!(stdout(), EnterAlternateScreen) {
                     let _ = terminal::disable_raw_mode();
                     return Err(e.into());
                 }
             }
+            let recovery: Arc<Mutex<Recovery>> = Arc::default();
+            let maybe_recovery_path = match recovery_path(args.profile.as_deref()) {
+                Ok(maybe_path) => maybe_path,
+                Err(e) => {
+                    restore_terminal(args.alternate_screen);
+                    return Err(e);
+                }
+            };
+            if let Err(e) = install_terminal_restoration(
+                args.alternate_screen,
+                maybe_recovery_path.clone(),
+                Arc::clone(&recovery),
+            ) {
+                restore_terminal(args.alternate_screen);
+                return Err(e.into());
+            }
 
-            let result = interactive_calc(&mut args, command_executor, tokenizer);
+            let result = interactive_calc(
+                &mut args,
+                &arg_matches,
+                command_executor,
+                tokenizer,
+                &recovery,
+                maybe_recovery_path.as_deref(),
+            );
 
-            if args.alternate_screen {
-                let _ = execute!(stdout, LeaveAlternateScreen);
-            }
-            let _ = terminal::disable_raw_mode();
+            restore_terminal(args.alternate_screen);
             result?;
         }
     }
@@ -139,6 +149,111 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Undoes the changes that interactive mode makes to the terminal. This is safe to call more than
+/// once.
+fn restore_terminal(alternate_screen: bool) {
+    if alternate_screen {
+        let _ = execute!(stdout(), LeaveAlternateScreen);
+    }
+    let _ = terminal::disable_raw_mode();
+}
+
+/// Makes sure that the terminal is restored even if interactive mode doesn't end normally. On a
+/// panic, the terminal is restored before the panic message is printed so that the message is
+/// readable, and then `recovery` is saved to `maybe_recovery_path` so that the next session can
+/// offer to restore it. On SIGTERM or SIGHUP, the terminal is restored and then bcalc exits.
+fn install_terminal_restoration(
+    alternate_screen: bool,
+    maybe_recovery_path: Option<PathBuf>,
+    recovery: Arc<Mutex<Recovery>>,
+) -> io::Result<()> {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal(alternate_screen);
+        default_hook(info);
+        if let Some(path) = &maybe_recovery_path {
+            save_recovery(path, &recovery);
+        }
+    }));
+
+    #[cfg(unix)]
+    {
+        use signal_hook::{
+            consts::{SIGHUP, SIGTERM},
+            iterator::Signals,
+        };
+
+        let mut signals = Signals::new([SIGTERM, SIGHUP])?;
+        thread::spawn(move || {
+            if let Some(signal) = signals.forever().next() {
+                restore_terminal(alternate_screen);
+                // The conventional exit status of a process that was terminated by a signal.
+                process::exit(128 + signal);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Writes out the recovery from a panic hook. If the panicking thread is the one holding the lock,
+/// the recovery might be half updated, so nothing is written.
+fn save_recovery(path: &Path, recovery: &Mutex<Recovery>) {
+    let recovery = match recovery.try_lock() {
+        Ok(recovery) => recovery,
+        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(TryLockError::WouldBlock) => return,
+    };
+    if recovery.is_empty() {
+        return;
+    }
+    match recovery.write(path) {
+        Ok(()) => eprintln!(
+            "The input line and variables were saved and will be offered back when bcalc next starts"
+        ),
+        Err(e) => eprintln!("Failed to save the input line and variables: {}", e),
+    }
+}
+
+/// Asks whether what was saved when the last session crashed should be restored, and waits for an
+/// answer. Returns `true` if it should be.
+fn offer_recovery(
+    stdout: &mut Stdout,
+    recovery: &Recovery,
+    alternate_screen: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut lost = Vec::new();
+    if !recovery.input.is_empty() {
+        lost.push(format!("the unfinished input '{}'", recovery.input));
+    }
+    match recovery.variables.len() {
+        0 => {}
+        1 => lost.push("1 variable".to_string()),
+        count => lost.push(format!("{} variables", count)),
+    }
+    print_output(
+        stdout,
+        &format!(
+            "bcalc crashed during the last session. Restore {}? (y/n)",
+            lost.join(" and ")
+        ),
+        alternate_screen,
+        false,
+    )?;
+    loop {
+        if let Event::Key(key) = event::read()? {
+            match key.code {
+                KeyCode::Char('y' | 'Y') => return Ok(true),
+                KeyCode::Char('n' | 'N') | KeyCode::Esc => return Ok(false),
+                KeyCode::Char('c' | 'd') if key.modifiers == KeyModifiers::CONTROL => {
+                    return Ok(false)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
 // We want pretty fine-grained control over the calculator interface so that we can:
 //  - Handle hotkey commands (ex: Control+M).
 //  - Exit cleanly on Control+C, Control+D, and Control+Z.
@@ -148,23 +263,77 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 // bit ridiculous and over-engineered. But it accomplishes what I want.
 fn interactive_calc(
     args: &mut Args,
+    arg_matches: &ArgMatches,
     mut command_executor: CommandExecutor,
-    tokenizer: Tokenizer,
+    mut tokenizer: Tokenizer,
+    recovery: &Mutex<Recovery>,
+    maybe_recovery_path: Option<&Path>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut stdout = stdout();
 
     // If available, we are going to open an SQLite connection to bcalc's saved data file. This
     // will allow us to do things like having the scrollback extend to previous bcalc instances.
-    let mut maybe_db: Option<SavedData> = if args.no_db { None } else { SavedData::open()? };
+    let mut maybe_db: Option<SavedData> = if args.no_db {
+        None
+    } else {
+        SavedData::open(args.profile.as_deref())?
+    };
+    if let Some(db) = maybe_db.as_mut() {
+        if let Some(report) = db.take_rebuild_report() {
+            print_output(&mut stdout, &report, args.alternate_screen, false)?;
+        }
+        args.restore_saved_settings(db, |id| {
+            arg_matches.value_source(id) == Some(ValueSource::CommandLine)
+        })?;
+    }
     let mut inputs = InputHistory::new(maybe_db.is_some());
+    if let Some(db) = maybe_db.as_mut() {
+        inputs.load_favorites(db)?;
+        inputs.load_snippets(db)?;
+        command_executor.load_custom_units(db)?;
+    }
     let mut vars = VariableStore::new();
+    if let Some(path) = maybe_recovery_path {
+        if let Some(recovered) = Recovery::take(path)? {
+            if offer_recovery(&mut stdout, &recovered, args.alternate_screen)? {
+                for variable in recovered.variables {
+                    vars.update(variable, None, None)?;
+                }
+                inputs.set_current_line(recovered.input);
+            }
+        }
+    }
+    let mut registers = Registers::new();
+    // Events that arrived while a calculation was running. These are handled before reading any
+    // more.
+    let mut pending_events: VecDeque<Event> = VecDeque::new();
+    // Recorded keys that are being played back. These are handled before any other events.
+    let mut playback: VecDeque<KeyEvent> = VecDeque::new();
 
     'calculate: loop {
-        let mut cursor_pos: usize = 0;
+        // Settings, including those restored from the database, can be changed by commands.
+        tokenizer.set_si_suffixes(!args.no_si_suffixes);
+        tokenizer.set_milli_suffix(args.milli_suffix);
+        playback.extend(command_executor.keystroke_macro_mut().take_requested_keys());
+        if let Ok(mut recovery) = recovery.lock() {
+            recovery.variables = vars.variables();
+        }
+        // Normally the input line starts out empty, but commands like `/snippet insert` can fill
+        // it in.
+        let mut cursor_pos: usize = inputs.take_requested_cursor_pos().unwrap_or(0);
         let mut scroll_offset: usize = 0;
         let input_start = cursor::position()?;
         let mut cols = usize::from(terminal::size()?.0);
         let mut input_complete = false;
+        let mut input_area = InputArea::default();
+        // Set by Alt+S, which stores the last result in the register picked by the next key.
+        let mut storing_register = false;
+        // What the input line looked like once it was complete, so that it can be flashed if the
+        // input has an error.
+        let mut completed_rows: Vec<Vec<Cell>> = Vec::new();
+        // In screen reader mode, the input as it was last output, or `None` if the prompt hasn't
+        // been output yet.
+        let mut maybe_echoed_input: Option<String> = None;
 
         'get_input_line: loop {
             // We display before we process input so that the prompt shows up without user input.
@@ -173,7 +342,32 @@ fn interactive_calc(
             // still doing inline editing, we may not have any way of returning to previous lines
             // if we wrap, so we will instead allow the current line to scroll.
             let current_input = inputs.current_line();
-            if args.alternate_screen || input_complete {
+            if let Ok(mut recovery) = recovery.lock() {
+                recovery.input = current_input.to_string();
+            }
+            if args.screen_reader {
+                // Output is only ever added after what is already there. If the input just got
+                // longer, only the new text is output. Otherwise, the whole input is output again
+                // on a new line.
+                match maybe_echoed_input.as_deref() {
+                    Some(echoed) if current_input.starts_with(echoed) => {
+                        queue!(stdout, Print(&current_input[echoed.len()..]))?;
+                    }
+                    Some(_) => queue!(
+                        stdout,
+                        Print("\n"),
+                        MoveToColumn(0),
+                        Print(PROMPT_STR),
+                        Print(current_input)
+                    )?,
+                    None => queue!(stdout, Print(PROMPT_STR), Print(current_input))?,
+                }
+                maybe_echoed_input = Some(current_input.to_string());
+                if input_complete {
+                    queue!(stdout, Print("\n"), MoveToColumn(0))?;
+                }
+                stdout.flush()?;
+            } else if args.alternate_screen || input_complete {
                 let wrap_str: String = std::iter::repeat(" ").take(PROMPT_STR.len()).collect();
                 if cols < wrap_str.len() {
                     return Err(CalculatorEnvironmentError::new("Window too narrow").into());
@@ -182,44 +376,56 @@ fn interactive_calc(
                 let cursor_row: u16 = u16::try_from(cursor_pos / available_cols)? + input_start.1;
                 let cursor_col: u16 =
                     u16::try_from((cursor_pos % available_cols) + wrap_str.len())?;
-                let mut end_index = min(available_cols, current_input.len());
-                if args.alternate_screen {
-                    queue!(
-                        stdout,
-                        MoveTo(input_start.0, input_start.1),
-                        Clear(FromCursorDown)
-                    )?;
-                } else {
-                    queue!(stdout, MoveToColumn(0), Clear(CurrentLine))?;
-                }
-                // First display the prompt and as much text as we can fit on the first line. Then
+                // First lay out the prompt and as much text as we can fit on the first line. Then
                 // loop over the remaining text, starting each subsequent line with `wrap_str`
-                // until we have displayed the whole string.
-                queue!(
-                    stdout,
-                    Print(PROMPT_STR),
-                    Print(&current_input[0..end_index])
-                )?;
+                // until we have laid out the whole string.
+                let mut end_index = min(available_cols, current_input.len());
+                let mut rows: Vec<Vec<Cell>> = vec![Vec::new()];
+                push_cells(&mut rows[0], PROMPT_STR, false);
+                push_cells(&mut rows[0], &current_input[0..end_index], false);
                 let mut current_index = end_index;
                 while current_index < current_input.len() {
                     end_index = min(current_index + available_cols, current_input.len());
-                    if args.alternate_screen {
-                        queue!(stdout, MoveToNextLine(1))?;
+                    let mut row = Vec::new();
+                    push_cells(&mut row, &wrap_str, false);
+                    push_cells(&mut row, &current_input[current_index..end_index], false);
+                    rows.push(row);
+                    current_index = end_index;
+                }
+                if !input_complete {
+                    let used_cols = if current_input.is_empty() {
+                        0
                     } else {
-                        // MoveToNextLine doesn't seem to always work properly if we aren't in the
-                        // alternate screen.
-                        queue!(stdout, Print("\n"), MoveToColumn(0))?;
+                        ((current_input.len() - 1) % available_cols) + 1
+                    };
+                    if let Some(hint) =
+                        make_result_hint(inputs.current_line_result(), available_cols - used_cols)
+                    {
+                        push_cells(rows.last_mut().unwrap(), &hint, true);
+                    }
+                }
+                let last_row = u16::try_from(rows.len() - 1)? + input_start.1;
+                if input_complete {
+                    completed_rows = rows.clone();
+                }
+                if args.alternate_screen {
+                    input_area.draw(&mut stdout, rows, Some(input_start.1))?;
+                } else {
+                    // The input line is about to scroll away, so there is nothing to be gained by
+                    // only redrawing part of it.
+                    queue!(stdout, MoveToColumn(0), Clear(CurrentLine))?;
+                    for (index, row) in rows.iter().enumerate() {
+                        if index > 0 {
+                            // MoveToNextLine doesn't seem to always work properly if we aren't in
+                            // the alternate screen.
+                            queue!(stdout, Print("\n"), MoveToColumn(0))?;
+                        }
+                        print_cells(&mut stdout, row)?;
                     }
-                    queue!(
-                        stdout,
-                        Print(&wrap_str),
-                        Print(&current_input[current_index..end_index])
-                    )?;
-                    current_index = end_index;
                 }
                 if input_complete {
                     if args.alternate_screen {
-                        queue!(stdout, MoveToNextLine(1))?;
+                        queue!(stdout, MoveTo(0, last_row), MoveToNextLine(1))?;
                     } else {
                         // MoveToNextLine doesn't seem to always work properly if we aren't in the
                         // alternate screen.
@@ -278,15 +484,20 @@ fn interactive_calc(
                 let scrolled_cursor: u16 =
                     u16::try_from(cursor_pos - scroll_offset + opener_str.len())?;
 
-                execute!(
-                    stdout,
-                    MoveToColumn(0),
-                    Clear(CurrentLine),
-                    Print(&opener_str),
-                    Print(&current_input[scroll_offset..end_index]),
-                    Print(&closer_str),
-                    MoveToColumn(scrolled_cursor)
-                )?;
+                let mut row = Vec::new();
+                push_cells(&mut row, opener_str, false);
+                push_cells(&mut row, &current_input[scroll_offset..end_index], false);
+                push_cells(&mut row, closer_str, false);
+                if !overflow_right {
+                    let used_cols = opener_str.len() + end_index - scroll_offset;
+                    if let Some(hint) =
+                        make_result_hint(inputs.current_line_result(), cols - used_cols)
+                    {
+                        push_cells(&mut row, &hint, true);
+                    }
+                }
+                input_area.draw(&mut stdout, vec![row], None)?;
+                execute!(stdout, MoveToColumn(scrolled_cursor))?;
             }
 
             if input_complete {
@@ -301,12 +512,112 @@ fn interactive_calc(
             // quitting, we will set `input_complete` and break out of this loop, allowing us to
             // update the display one more time before exiting the `'get_input_line` loop.
             'get_event: loop {
-                match event::read()? {
+                let next_event = if let Some(key) = playback.pop_front() {
+                    Event::Key(key)
+                } else if let Some(event) = pending_events.pop_front() {
+                    event
+                } else {
+                    let event = event::read()?;
+                    if let Event::Key(key) = event {
+                        command_executor.keystroke_macro_mut().record(key);
+                    }
+                    event
+                };
+                match next_event {
+                    Event::Key(event) if storing_register => {
+                        storing_register = false;
+                        let maybe_index = match event.code {
+                            KeyCode::Char(c) if event.modifiers.is_empty() => c.to_digit(10),
+                            _ => None,
+                        };
+                        let Some(index) = maybe_index else {
+                            // Anything other than a register number cancels the store and is
+                            // handled normally.
+                            pending_events.push_front(Event::Key(event));
+                            continue 'get_event;
+                        };
+                        if let Some(mut results) = vars.recent_results(1) {
+                            registers.store(index as usize, results.next().unwrap().clone());
+                        }
+                        continue 'get_event;
+                    }
                     Event::Key(event) => match event.code {
                         KeyCode::Char(mut c) => {
+                            // The input line can only hold ASCII, so a typed `±` is spelled out.
+                            if c == '±' {
+                                for c in "+/-".chars() {
+                                    inputs.insert_char_into_current_line(cursor_pos, c);
+                                    cursor_pos += 1;
+                                }
+                                break 'get_event;
+                            }
                             if !c.is_ascii() {
                                 continue 'get_event;
                             }
+                            if event.modifiers == KeyModifiers::ALT {
+                                if c == 's' {
+                                    storing_register = true;
+                                    continue 'get_event;
+                                }
+                                let keystroke_macro = command_executor.keystroke_macro_mut();
+                                if c == RECORD_KEY {
+                                    if keystroke_macro.stop_recording().is_none() {
+                                        keystroke_macro.start_recording();
+                                    }
+                                    continue 'get_event;
+                                }
+                                if c == PLAY_KEY {
+                                    if !keystroke_macro.is_recording() {
+                                        playback.extend(keystroke_macro.keys());
+                                    }
+                                    continue 'get_event;
+                                }
+                                // With an empty input line, these submit the memory commands as
+                                // though they had been typed.
+                                let maybe_memory_command = match c {
+                                    '=' => Some("/m+"),
+                                    '-' => Some("/m-"),
+                                    'c' => Some("/mc"),
+                                    _ => None,
+                                };
+                                if let Some(command) = maybe_memory_command {
+                                    if !inputs.current_line().is_empty() {
+                                        continue 'get_event;
+                                    }
+                                    for (index, c) in command.chars().enumerate() {
+                                        inputs.insert_char_into_current_line(index, c);
+                                    }
+                                    cursor_pos = command.len();
+                                    input_complete = true;
+                                    break 'get_event;
+                                }
+                                if c == 'm' {
+                                    let maybe_memory = vars.get(
+                                        MEMORY_VARIABLE_NAME.into(),
+                                        maybe_db.as_mut().map(|db| db as &mut dyn Storage),
+                                    )?;
+                                    let Some(memory) = maybe_memory else {
+                                        continue 'get_event;
+                                    };
+                                    for c in input_text(&memory.value, args.radix).chars() {
+                                        inputs.insert_char_into_current_line(cursor_pos, c);
+                                        cursor_pos += 1;
+                                    }
+                                    break 'get_event;
+                                }
+                                // Alt plus a register number types the register's value in.
+                                let maybe_value = c
+                                    .to_digit(10)
+                                    .and_then(|index| registers.get(index as usize));
+                                let Some(value) = maybe_value else {
+                                    continue 'get_event;
+                                };
+                                for c in input_text(value, args.radix).chars() {
+                                    inputs.insert_char_into_current_line(cursor_pos, c);
+                                    cursor_pos += 1;
+                                }
+                                break 'get_event;
+                            }
                             if event.modifiers == KeyModifiers::CONTROL {
                                 if c == 'd' || c == 'z' || c == 'c' {
                                     // "Exit" commands.
@@ -393,7 +704,24 @@ fn interactive_calc(
                             break 'get_event;
                         }
                         KeyCode::Up => {
-                            if !inputs.try_to_go_to_earlier_line(maybe_db.as_mut())? {
+                            let moved = if event.modifiers == KeyModifiers::CONTROL
+                                || event.modifiers == KeyModifiers::SHIFT
+                            {
+                                inputs.try_to_go_to_earlier_session(maybe_db.as_mut())?
+                            } else if event.modifiers == KeyModifiers::ALT {
+                                inputs.try_to_go_to_earlier_line_where(
+                                    maybe_db.as_mut(),
+                                    is_command,
+                                )?
+                            } else if args.recall_commands {
+                                inputs.try_to_go_to_earlier_line(maybe_db.as_mut())?
+                            } else {
+                                inputs
+                                    .try_to_go_to_earlier_line_where(maybe_db.as_mut(), |line| {
+                                        !is_command(line)
+                                    })?
+                            };
+                            if !moved {
                                 continue 'get_event;
                             }
                             cursor_pos = inputs.current_line().len();
@@ -401,7 +729,34 @@ fn interactive_calc(
                             break 'get_event;
                         }
                         KeyCode::Down => {
-                            if !inputs.try_to_go_to_later_line() {
+                            let moved = if event.modifiers == KeyModifiers::CONTROL
+                                || event.modifiers == KeyModifiers::SHIFT
+                            {
+                                inputs.try_to_go_to_later_session()
+                            } else if event.modifiers == KeyModifiers::ALT {
+                                inputs.try_to_go_to_later_line_where(is_command)
+                            } else if args.recall_commands {
+                                inputs.try_to_go_to_later_line()
+                            } else {
+                                inputs.try_to_go_to_later_line_where(|line| !is_command(line))
+                            };
+                            if !moved {
+                                continue 'get_event;
+                            }
+                            cursor_pos = inputs.current_line().len();
+                            scroll_offset = 0;
+                            break 'get_event;
+                        }
+                        KeyCode::PageUp => {
+                            if !inputs.try_to_recall_earlier_favorite() {
+                                continue 'get_event;
+                            }
+                            cursor_pos = inputs.current_line().len();
+                            scroll_offset = 0;
+                            break 'get_event;
+                        }
+                        KeyCode::PageDown => {
+                            if !inputs.try_to_recall_later_favorite() {
                                 continue 'get_event;
                             }
                             cursor_pos = inputs.current_line().len();
@@ -454,6 +809,21 @@ fn interactive_calc(
                             break 'get_event;
                         }
                         KeyCode::Enter => {
+                            if args.effective_close_parens() == CloseParens::Offer {
+                                // Rather than rejecting an input that is only missing close
+                                // parentheses at the end, add them so that the user can check
+                                // that that's what they meant before pressing Enter again.
+                                let missing =
+                                    missing_close_parens(inputs.current_line(), args, &tokenizer);
+                                if missing > 0 {
+                                    let end = inputs.current_line().len();
+                                    for offset in 0..missing {
+                                        inputs.insert_char_into_current_line(end + offset, ')');
+                                    }
+                                    cursor_pos = end + missing;
+                                    break 'get_event;
+                                }
+                            }
                             input_complete = true;
                             break 'get_event;
                         }
@@ -468,49 +838,331 @@ fn interactive_calc(
                     }
                     Event::Resize(width, _) => {
                         cols = usize::from(width);
+                        input_area.invalidate();
                         break 'get_event;
                     }
                     _ => {}
-                } // match event::read()?
+                } // match next_event
             } // 'get_event: loop
         } // 'get_input_line: loop
 
         let input = inputs.current_line().to_string();
 
-        let output = match calculate(
-            &input,
-            args,
-            &tokenizer,
-            &mut command_executor,
-            maybe_db.as_mut(),
-            Some(&mut inputs),
-            Some(&mut vars),
-        ) {
+        // Some calculations (ex: roots to a high precision) take a while. In the meantime, Control+C
+        // cancels the calculation rather than exiting.
+        let interrupt = Interrupt::new();
+        let calculation_done = AtomicBool::new(false);
+        let (result, watch_result) = thread::scope(|scope| {
+            let watcher = scope.spawn(|| watch_for_interrupt(&interrupt, &calculation_done));
+            let result = calculate(
+                &input,
+                args,
+                &tokenizer,
+                &mut command_executor,
+                maybe_db.as_mut(),
+                Some(&mut inputs),
+                Some(&mut vars),
+                Some(&interrupt),
+            );
+            calculation_done.store(true, Ordering::Relaxed);
+            (result, watcher.join())
+        });
+        match watch_result {
+            Ok(events) => {
+                let events = events?;
+                for event in &events {
+                    if let Event::Key(key) = event {
+                        command_executor.keystroke_macro_mut().record(*key);
+                    }
+                }
+                pending_events.extend(events);
+            }
+            Err(_) => {
+                return Err(InternalCalculatorError::new("Interrupt watcher panicked").into());
+            }
+        }
+
+        let (output, warnings) = match result {
             Ok(result) => result,
             // TODO: Display error position
-            Err(CalculatorFailure::InputError(message)) => format!("Error: {}", message.value),
-            Err(CalculatorFailure::RuntimeError(e)) => format!("Runtime Error: {}", e),
+            Err(CalculatorFailure::InputError(message)) => {
+                alert_error(&mut stdout, args.error_alert, &completed_rows)?;
+                (format!("Error: {}", message.value), Vec::new())
+            }
+            Err(CalculatorFailure::RuntimeError(e)) => {
+                (format!("Runtime Error: {}", e), Vec::new())
+            }
         };
 
-        // It appears that on macOS, outputting a newline advances the cursor down, but not back to
-        // column 0. So we need to make sure that we do that manually.
-        for line in output.split('\n') {
-            queue!(stdout, Print(line))?;
-            if args.alternate_screen {
+        print_output(&mut stdout, &output, args.alternate_screen, false)?;
+        let warning_lines: Vec<String> = warnings
+            .iter()
+            .map(|warning| format!("Warning: {}", warning))
+            .collect();
+        if !warning_lines.is_empty() {
+            print_output(
+                &mut stdout,
+                &warning_lines.join("\n"),
+                args.alternate_screen,
+                true,
+            )?;
+        }
+
+        let mut recorded_output = output;
+        for line in warning_lines {
+            recorded_output.push('\n');
+            recorded_output.push_str(&line);
+        }
+        if let Err(e) = command_executor.record_in_transcript(&input, &recorded_output) {
+            print_output(
+                &mut stdout,
+                &format!("Error: Stopped recording the transcript: {}", e),
+                args.alternate_screen,
+                false,
+            )?;
+        }
+    } // 'calculate: loop
+
+    Ok(())
+}
+
+/// One character of the input area, along with how it is styled.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    c: char,
+    dim: bool,
+}
+
+fn push_cells(row: &mut Vec<Cell>, s: &str, dim: bool) {
+    row.extend(s.chars().map(|c| Cell { c, dim }));
+}
+
+/// Prints the cells at the cursor's current position.
+fn print_cells(stdout: &mut Stdout, cells: &[Cell]) -> Result<(), Box<dyn std::error::Error>> {
+    for run in cells.chunk_by(|a, b| a.dim == b.dim) {
+        let text: String = run.iter().map(|cell| cell.c).collect();
+        if run[0].dim {
+            queue!(
+                stdout,
+                SetAttribute(Attribute::Dim),
+                Print(text),
+                SetAttribute(Attribute::Reset)
+            )?;
+        } else {
+            queue!(stdout, Print(text))?;
+        }
+    }
+    Ok(())
+}
+
+/// Draws attention to an input error in the way that `alert` specifies. `input_rows` are the rows of
+/// the completed input line, which must be directly above the cursor.
+fn alert_error(
+    stdout: &mut Stdout,
+    alert: ErrorAlert,
+    input_rows: &[Vec<Cell>],
+) -> Result<(), Box<dyn std::error::Error>> {
+    match alert {
+        ErrorAlert::None => {}
+        ErrorAlert::Flash if !input_rows.is_empty() => {
+            let row_count = u16::try_from(input_rows.len())?;
+            // Redraw the input line in reverse video, then redraw it normally once the flash is
+            // over.
+            queue!(stdout, MoveToPreviousLine(row_count))?;
+            for row in input_rows {
+                let text: String = row.iter().map(|cell| cell.c).collect();
+                queue!(
+                    stdout,
+                    SetAttribute(Attribute::Reverse),
+                    Print(text),
+                    SetAttribute(Attribute::Reset),
+                    MoveToNextLine(1)
+                )?;
+            }
+            stdout.flush()?;
+            thread::sleep(ERROR_FLASH_DURATION);
+            queue!(stdout, MoveToPreviousLine(row_count))?;
+            for row in input_rows {
+                print_cells(stdout, row)?;
                 queue!(stdout, MoveToNextLine(1))?;
+            }
+            stdout.flush()?;
+        }
+        // In screen reader mode, there is no input line to flash, so the bell is used instead.
+        ErrorAlert::Bell | ErrorAlert::Flash => {
+            queue!(stdout, Print('\x07'))?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Remembers what the input area of the screen shows so that redrawing it only outputs the cells
+/// that changed. Clearing and reprinting the whole input line on every keypress visibly flickers
+/// over slow connections.
+#[derive(Default)]
+struct InputArea {
+    // `None` when we don't know what is on the screen, such as before the first draw or after the
+    // terminal is resized.
+    maybe_rows: Option<Vec<Vec<Cell>>>,
+}
+
+impl InputArea {
+    /// Causes the next draw to redraw everything.
+    fn invalidate(&mut self) {
+        self.maybe_rows = None;
+    }
+
+    /// Queues the output that changes the input area to show `rows`. If `maybe_top` is given, the
+    /// rows start at that line of the screen. Otherwise, there must be exactly one row, which is
+    /// drawn on the cursor's current line. The cursor is left wherever the last change was made.
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        rows: Vec<Vec<Cell>>,
+        maybe_top: Option<u16>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let old_rows = match self.maybe_rows.take() {
+            Some(old_rows) => old_rows,
+            None => {
+                move_to_cell(stdout, maybe_top, 0, 0)?;
+                match maybe_top {
+                    Some(_) => queue!(stdout, Clear(FromCursorDown))?,
+                    None => queue!(stdout, Clear(CurrentLine))?,
+                };
+                Vec::new()
+            }
+        };
+
+        for (index, row) in rows.iter().enumerate() {
+            let old_row: &[Cell] = old_rows.get(index).map_or(&[], |r| r.as_slice());
+            let first_change =
+                match (0..max(row.len(), old_row.len())).find(|&i| row.get(i) != old_row.get(i)) {
+                    Some(i) => i,
+                    None => continue,
+                };
+            // If the length of the row didn't change, the cells after the last change are already
+            // right. Otherwise, everything after the first change has moved.
+            let end = if row.len() == old_row.len() {
+                (first_change..row.len())
+                    .rev()
+                    .find(|&i| row[i] != old_row[i])
+                    .unwrap()
+                    + 1
             } else {
-                // MoveToNextLine doesn't seem to always work properly if we aren't in the
-                // alternate screen.
-                queue!(stdout, Print("\n"), MoveToColumn(0))?;
+                row.len()
+            };
+            move_to_cell(stdout, maybe_top, index, first_change)?;
+            print_cells(stdout, &row[first_change..end])?;
+            if old_row.len() > row.len() {
+                queue!(stdout, Clear(UntilNewLine))?;
             }
         }
-        stdout.flush()?;
-    } // 'calculate: loop
+        if old_rows.len() > rows.len() {
+            move_to_cell(stdout, maybe_top, rows.len(), 0)?;
+            queue!(stdout, Clear(FromCursorDown))?;
+        }
+
+        self.maybe_rows = Some(rows);
+        Ok(())
+    }
+}
+
+/// Moves the cursor to the given cell of the input area. See `InputArea::draw` for the meaning of
+/// `maybe_top`.
+fn move_to_cell(
+    stdout: &mut Stdout,
+    maybe_top: Option<u16>,
+    row: usize,
+    col: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let col = u16::try_from(col)?;
+    let row = u16::try_from(row)?;
+    match maybe_top {
+        Some(top) => queue!(stdout, MoveTo(col, top + row))?,
+        None => queue!(stdout, MoveToColumn(col))?,
+    };
+    Ok(())
+}
 
+/// Prints output below the input line, leaving the cursor at the start of the following line. Dim
+/// output is for things that are less important than the result, like warnings about it.
+fn print_output(
+    stdout: &mut Stdout,
+    output: &str,
+    alternate_screen: bool,
+    dim: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // It appears that on macOS, outputting a newline advances the cursor down, but not back to
+    // column 0. So we need to make sure that we do that manually.
+    for line in output.split('\n') {
+        if dim {
+            queue!(
+                stdout,
+                SetAttribute(Attribute::Dim),
+                Print(line),
+                SetAttribute(Attribute::Reset)
+            )?;
+        } else {
+            queue!(stdout, Print(line))?;
+        }
+        if alternate_screen {
+            queue!(stdout, MoveToNextLine(1))?;
+        } else {
+            // MoveToNextLine doesn't seem to always work properly if we aren't in the alternate
+            // screen.
+            queue!(stdout, Print("\n"), MoveToColumn(0))?;
+        }
+    }
+    stdout.flush()?;
     Ok(())
 }
 
-/// Evaluates the string input given to bcalc.
+/// The number of close parentheses that the input is missing at its end. See `close_open_parens`.
+fn missing_close_parens(input: &str, args: &Args, tokenizer: &Tokenizer) -> usize {
+    match tokenize_input(tokenizer, input, args) {
+        Ok(ParsedInput::Tokens(mut tokens)) => close_open_parens(&mut tokens),
+        _ => 0,
+    }
+}
+
+/// Returns the text used to show what a historical input evaluated to, if it fits within
+/// `available_cols`.
+fn make_result_hint(maybe_result: Option<&str>, available_cols: usize) -> Option<String> {
+    let result = maybe_result?;
+    if result.contains('\n') {
+        return None;
+    }
+    let hint = format!("{}{}", RESULT_HINT_PREFIX_STR, result);
+    if hint.len() > available_cols {
+        return None;
+    }
+    Some(hint)
+}
+
+/// Reads terminal events until `done` is set, cancelling `interrupt` if Control+C is pressed. The
+/// other events are returned so that they can be handled once the calculation has finished.
+fn watch_for_interrupt(interrupt: &Interrupt, done: &AtomicBool) -> io::Result<Vec<Event>> {
+    let mut events = Vec::new();
+    while !done.load(Ordering::Relaxed) {
+        if !event::poll(INTERRUPT_POLL_INTERVAL)? {
+            continue;
+        }
+        let event = event::read()?;
+        match &event {
+            Event::Key(key)
+                if key.modifiers == KeyModifiers::CONTROL && key.code == KeyCode::Char('c') =>
+            {
+                interrupt.cancel();
+            }
+            _ => events.push(event),
+        }
+    }
+    Ok(events)
+}
+
+/// Evaluates the string input given to bcalc. Returns the output along with any warnings about it.
+#[allow(clippy::too_many_arguments)]
 fn calculate(
     input: &str,
     args: &mut Args,
@@ -519,70 +1171,82 @@ fn calculate(
     mut maybe_db: Option<&mut SavedData>,
     mut maybe_inputs: Option<&mut InputHistory>,
     mut maybe_vars: Option<&mut VariableStore>,
-) -> Result<String, CalculatorFailure> {
-    let maybe_input_history_id = match maybe_inputs.as_mut() {
-        Some(inputs) => inputs.input_finished(maybe_db.as_deref_mut())?,
-        None => None,
-    };
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<(String, Vec<Warning>), CalculatorFailure> {
+    // Everything that handling the input writes to the database is committed together.
+    if let Some(db) = maybe_db.as_deref_mut() {
+        db.begin_batch()?;
+    }
 
-    let tokens = match tokenizer.tokenize(input, args.radix)? {
-        ParsedInput::Tokens(t) => t,
-        ParsedInput::Command((command, command_args)) => {
-            let (message, vars_touched) = command_executor.execute_command(
-                command,
-                command_args,
-                args,
-                tokenizer,
-                maybe_db.as_deref_mut(),
-                maybe_inputs,
-                maybe_vars.as_deref_mut(),
-            )?;
+    let calculate_in_batch = || -> Result<(String, Vec<Warning>), CalculatorFailure> {
+        let maybe_input_history_id = match maybe_inputs.as_mut() {
+            Some(inputs) => inputs.input_finished(maybe_db.as_deref_mut())?,
+            None => None,
+        };
+
+        let tokens = match tokenize_input(tokenizer, input, args)? {
+            ParsedInput::Tokens(t) => t,
+            ParsedInput::Command((command, command_args)) => {
+                let (message, vars_touched) = command_executor.execute_command(
+                    command,
+                    command_args,
+                    args,
+                    tokenizer,
+                    maybe_db.as_deref_mut(),
+                    maybe_inputs,
+                    maybe_vars.as_deref_mut(),
+                    maybe_input_history_id,
+                )?;
 
-            if let Some(vars) = maybe_vars {
-                for var_name in vars_touched {
-                    vars.touch(&var_name, maybe_input_history_id, maybe_db.as_deref_mut())?;
+                if let Some(vars) = maybe_vars {
+                    for var_name in vars_touched {
+                        vars.touch(
+                            &var_name,
+                            maybe_input_history_id,
+                            maybe_db.as_deref_mut().map(|db| db as &mut dyn Storage),
+                        )?;
+                    }
                 }
+
+                return Ok((message, Vec::new()));
             }
+        };
 
-            return Ok(message);
+        if tokens.is_empty() {
+            return Ok((String::new(), Vec::new()));
         }
-    };
 
-    if let Some(vars) = maybe_vars.as_deref_mut() {
-        let mut vars_touched: HashSet<String> = HashSet::new();
-        for positioned_token in &tokens {
-            match &positioned_token.value {
-                Token::Variable(name) => {
-                    vars_touched.insert(name.clone());
-                }
-                _ => {}
-            }
-        }
-        for var_name in &vars_touched {
-            vars.touch(&var_name, maybe_input_history_id, maybe_db.as_deref_mut())?;
-        }
-    }
+        let mut warnings: Vec<Warning> = Vec::new();
+        let evaluation = evaluate_expression(
+            tokens,
+            maybe_input_history_id,
+            maybe_vars,
+            maybe_db.as_deref_mut().map(|db| db as &mut dyn Storage),
+            args,
+            None,
+            maybe_interrupt,
+            &mut warnings,
+        )?;
+        warnings.extend(evaluation.maybe_rounding_warning);
 
-    if tokens.is_empty() {
-        return Ok(String::new());
-    }
+        if let Some(inputs) = maybe_inputs {
+            inputs.set_last_result(
+                &evaluation.summary,
+                maybe_input_history_id,
+                maybe_db.as_deref_mut(),
+            )?;
+        }
 
-    let st = SyntaxTree::new(tokens.into())?;
-    let result = st.execute(maybe_input_history_id, maybe_vars, maybe_db, args)?;
+        Ok((evaluation.output, warnings))
+    };
+    let result = calculate_in_batch();
 
-    if args.fractional {
-        Ok(result.to_string())
-    } else {
-        let output_radix = match args.convert_to_radix {
-            Some(radix) => radix,
-            None => args.radix,
-        };
-        Ok(make_decimal_string(
-            &result,
-            output_radix,
-            args.precision,
-            args.commas,
-            args.upper,
-        ))
+    // The batch has to be finished even if the calculation failed, but the calculation's error is
+    // the more interesting one to report.
+    let maybe_end_result = maybe_db.map(|db| db.end_batch());
+    let output = result?;
+    if let Some(end_result) = maybe_end_result {
+        end_result?;
     }
+    Ok(output)
 }
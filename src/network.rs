@@ -0,0 +1,149 @@
+//! IPv4 addresses and CIDR blocks. Addresses are written as dotted quads (ex: `192.168.1.10`) and
+//! stand for the 32 bit integer that they encode, so they can be combined with other numbers and
+//! shown in any radix. A CIDR block (ex: `10.0.0.0/8`) can be passed to the network functions in
+//! place of an address and a prefix length.
+
+use num::{rational::BigRational, BigInt, ToPrimitive};
+
+/// The largest valid prefix length, which leaves no bits for the host.
+pub const MAX_PREFIX_LENGTH: u32 = 32;
+
+/// Reads an address written as a dotted quad, like `192.168.1.10`. Each part is always read as a
+/// decimal number, regardless of the input radix.
+pub fn parse_ipv4(text: &str) -> Option<u32> {
+    let mut address: u32 = 0;
+    let mut part_count = 0;
+    for part in text.split('.') {
+        if part.is_empty() || part.len() > 3 || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let octet: u8 = part.parse().ok()?;
+        address = (address << 8) | u32::from(octet);
+        part_count += 1;
+    }
+    if part_count == 4 {
+        Some(address)
+    } else {
+        None
+    }
+}
+
+pub fn format_ipv4(address: u32) -> String {
+    let octets = address.to_be_bytes();
+    format!("{}.{}.{}.{}", octets[0], octets[1], octets[2], octets[3])
+}
+
+/// Converts a function argument to an address. Fails unless the value is an integer that fits in
+/// 32 bits.
+pub fn address_argument(value: &BigRational) -> Result<u32, String> {
+    value
+        .to_integer()
+        .to_u32()
+        .filter(|_| value.is_integer())
+        .ok_or_else(|| format!("{} is not a valid IPv4 address", value))
+}
+
+/// Converts a function argument to a prefix length. Fails unless the value is an integer between 0
+/// and 32.
+pub fn prefix_length_argument(value: &BigRational) -> Result<u32, String> {
+    value
+        .to_integer()
+        .to_u32()
+        .filter(|length| value.is_integer() && *length <= MAX_PREFIX_LENGTH)
+        .ok_or_else(|| format!("{} is not a valid prefix length", value))
+}
+
+/// The mask with the first `prefix_length` bits set, like `255.255.255.0` for a prefix length of
+/// 24.
+pub fn netmask(prefix_length: u32) -> u32 {
+    u32::MAX
+        .checked_shl(MAX_PREFIX_LENGTH - prefix_length)
+        .unwrap_or(0)
+}
+
+pub fn network_address(address: u32, prefix_length: u32) -> u32 {
+    address & netmask(prefix_length)
+}
+
+pub fn broadcast_address(address: u32, prefix_length: u32) -> u32 {
+    address | !netmask(prefix_length)
+}
+
+/// The number of addresses in a block that can be assigned to hosts. The network and broadcast
+/// addresses are left out, except in /31 blocks, which are used for point-to-point links and have
+/// no broadcast address (RFC 3021), and in /32 blocks, which are a single host.
+pub fn host_count(prefix_length: u32) -> BigRational {
+    let count: u64 = match prefix_length {
+        MAX_PREFIX_LENGTH => 1,
+        31 => 2,
+        _ => (1u64 << (MAX_PREFIX_LENGTH - prefix_length)) - 2,
+    };
+    BigRational::from_integer(BigInt::from(count))
+}
+
+pub fn in_subnet(address: u32, network: u32, prefix_length: u32) -> bool {
+    network_address(address, prefix_length) == network_address(network, prefix_length)
+}
+
+#[cfg(test)]
+mod network_tests {
+    use crate::network::{
+        address_argument, broadcast_address, format_ipv4, host_count, in_subnet, netmask,
+        network_address, parse_ipv4, prefix_length_argument,
+    };
+    use num::{rational::BigRational, BigInt};
+
+    #[test]
+    fn addresses() {
+        assert_eq!(parse_ipv4("192.168.1.10"), Some(0xC0A8010A));
+        assert_eq!(parse_ipv4("0.0.0.0"), Some(0));
+        assert_eq!(parse_ipv4("255.255.255.255"), Some(u32::MAX));
+        for invalid in [
+            "1.2.3",
+            "1.2.3.4.5",
+            "1.2.3.256",
+            "1..3.4",
+            "1.2.3.-4",
+            "1.2.3.0004",
+        ] {
+            assert_eq!(parse_ipv4(invalid), None, "{}", invalid);
+        }
+        assert_eq!(format_ipv4(0xC0A8010A), "192.168.1.10");
+
+        let ratio =
+            |numer: i64, denom: i64| BigRational::new(BigInt::from(numer), BigInt::from(denom));
+        assert_eq!(address_argument(&ratio(0xC0A8010A, 1)), Ok(0xC0A8010A));
+        assert!(address_argument(&ratio(1, 2)).is_err());
+        assert!(address_argument(&ratio(-1, 1)).is_err());
+        assert!(address_argument(&ratio(1 << 32, 1)).is_err());
+        assert_eq!(prefix_length_argument(&ratio(32, 1)), Ok(32));
+        assert!(prefix_length_argument(&ratio(33, 1)).is_err());
+    }
+
+    #[test]
+    fn blocks() {
+        let address = parse_ipv4("192.168.1.10").unwrap();
+        assert_eq!(format_ipv4(netmask(24)), "255.255.255.0");
+        assert_eq!(netmask(0), 0);
+        assert_eq!(netmask(32), u32::MAX);
+        assert_eq!(format_ipv4(network_address(address, 24)), "192.168.1.0");
+        assert_eq!(
+            format_ipv4(broadcast_address(address, 20)),
+            "192.168.15.255"
+        );
+        assert_eq!(
+            format_ipv4(broadcast_address(address, 0)),
+            "255.255.255.255"
+        );
+
+        let count = |prefix_length: u32| host_count(prefix_length).to_string();
+        assert_eq!(count(24), "254");
+        assert_eq!(count(31), "2");
+        assert_eq!(count(32), "1");
+        assert_eq!(count(0), "4294967294");
+
+        let network = parse_ipv4("192.168.0.0").unwrap();
+        assert!(in_subnet(address, network, 16));
+        assert!(!in_subnet(address, network, 24));
+    }
+}
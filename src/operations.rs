@@ -1,21 +1,109 @@
-use crate::error::MathExecutionError::{self, ImaginaryResult};
+use crate::{
+    error::MathExecutionError::{self, DivisionByZero, Uncertain},
+    interrupt::{Interrupt, Progress},
+    value::Value,
+};
 
 use num::{
-    bigint::BigInt, pow::Pow, rational::BigRational, traits::Inv, BigUint, Integer, Signed, Zero,
+    bigint::BigInt, pow::Pow, rational::BigRational, traits::Inv, BigUint, Integer, One, Signed,
+    ToPrimitive, Zero,
+};
+use std::{
+    cell::RefCell,
+    cmp::min,
+    collections::{HashMap, VecDeque},
+    str::FromStr,
 };
 
+/// The largest radix that numbers can be read and written in, where the digits are `0` to `9`
+/// followed by `a` to `z`.
+pub const MAX_RADIX: u8 = 36;
+
+// The number of recently formatted values that `make_decimal_string` remembers.
+const DECIMAL_STRING_CACHE_CAPACITY: usize = 32;
+
+thread_local! {
+    static DECIMAL_STRING_CACHE: RefCell<DecimalStringCache> =
+        RefCell::new(DecimalStringCache::default());
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct DecimalStringKey {
+    value: BigRational,
+    radix: u8,
+    precision: u8,
+    maybe_group_size: Option<u8>,
+    upper: bool,
+}
+
+// Remembers the output of `format_decimal_string` for the most recently used arguments, since the
+// same value tends to be displayed repeatedly and converting a large value is not cheap.
+#[derive(Default)]
+struct DecimalStringCache {
+    strings: HashMap<DecimalStringKey, String>,
+    // The keys of `strings`, from least to most recently used.
+    order: VecDeque<DecimalStringKey>,
+}
+
+impl DecimalStringCache {
+    fn get_or_insert_with<F: FnOnce() -> String>(&mut self, key: DecimalStringKey, f: F) -> String {
+        if let Some(string) = self.strings.get(&key) {
+            let index = self.order.iter().position(|k| *k == key).unwrap();
+            let key = self.order.remove(index).unwrap();
+            self.order.push_back(key);
+            return string.clone();
+        }
+
+        let string = f();
+        if self.order.len() >= DECIMAL_STRING_CACHE_CAPACITY {
+            let oldest = self.order.pop_front().unwrap();
+            self.strings.remove(&oldest);
+        }
+        self.order.push_back(key.clone());
+        self.strings.insert(key, string.clone());
+        string
+    }
+}
+
 /// `BigRational` only seems to support fractional string conversion, but we want to support decimal
 /// output as well.
 /// We want to display trailing zeros, but in a way such that they are actually significant. We are
 /// only going to display them in order to indicate that we are rounding and the number isn't
 /// precise. For example:
-///   `make_decimal_string(0.01, 10, 5, false) == "0.01"`
-///   `make_decimal_string(0.010001, 10, 5, false) == "0.01000"`
+///   `make_decimal_string(0.01, 10, 5, None, false) == "0.01"`
+///   `make_decimal_string(0.010001, 10, 5, None, false) == "0.01000"`
+/// If `maybe_group_size` is given, the digits before the radix point are split by commas into
+/// groups of that many digits, counting from the radix point. In radices other than 10, the digits
+/// after the radix point are split up the same way, since there they are usually read as bits.
+/// The strings for recently formatted values are cached, so displaying the same value again is
+/// cheap.
 pub fn make_decimal_string(
     value: &BigRational,
     radix: u8,
     precision: u8,
-    commas: bool,
+    maybe_group_size: Option<u8>,
+    upper: bool,
+) -> String {
+    let key = DecimalStringKey {
+        value: value.clone(),
+        radix,
+        precision,
+        maybe_group_size,
+        upper,
+    };
+    DECIMAL_STRING_CACHE.with(|cache| {
+        cache.borrow_mut().get_or_insert_with(key, || {
+            format_decimal_string(value, radix, precision, maybe_group_size, upper)
+        })
+    })
+}
+
+/// Like `make_decimal_string`, but always does the conversion rather than using the cache.
+pub fn format_decimal_string(
+    value: &BigRational,
+    radix: u8,
+    precision: u8,
+    maybe_group_size: Option<u8>,
     upper: bool,
 ) -> String {
     // We need to split off the negative sign now rather than retaining it in the integer part of
@@ -53,11 +141,11 @@ pub fn make_decimal_string(
     if upper {
         int_string.make_ascii_uppercase();
     }
-    let int_string_commas: String = if commas {
+    let int_string_commas: String = if let Some(group_size) = maybe_group_size {
         int_string
             .chars()
             .collect::<Vec<char>>()
-            .rchunks(3)
+            .rchunks(usize::from(group_size.max(1)))
             .rev()
             .map(|s| String::from_iter(s.into_iter()))
             .collect::<Vec<String>>()
@@ -65,6 +153,16 @@ pub fn make_decimal_string(
     } else {
         int_string
     };
+    let fractional_string = match maybe_group_size {
+        Some(group_size) if radix != 10 => fractional_string
+            .chars()
+            .collect::<Vec<char>>()
+            .chunks(usize::from(group_size.max(1)))
+            .map(String::from_iter)
+            .collect::<Vec<String>>()
+            .join(","),
+        _ => fractional_string,
+    };
 
     if fractional_string.is_empty() {
         format!("{}{}", sign_str, int_string_commas)
@@ -73,14 +171,633 @@ pub fn make_decimal_string(
     }
 }
 
+/// The radices that `make_bases_string` shows values in, along with the prefixes that mark them.
+pub const BASES: [(u8, &str); 4] = [(10, ""), (16, "0x"), (8, "0o"), (2, "0b")];
+
+/// Formats the value in each of `BASES`, like `255  0xff  0o377  0b11111111`, as a programmer's
+/// calculator shows it. Each part is formatted by `make_decimal_string` and given its radix prefix,
+/// so that it can be entered as is.
+pub fn make_bases_string(
+    value: &BigRational,
+    precision: u8,
+    maybe_group_size: Option<u8>,
+    upper: bool,
+) -> String {
+    BASES
+        .iter()
+        .map(|&(radix, prefix)| {
+            let digits = make_decimal_string(value, radix, precision, maybe_group_size, upper);
+            match digits.strip_prefix('-') {
+                Some(digits) => format!("-{}{}", prefix, digits),
+                None => format!("{}{}", prefix, digits),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("  ")
+}
+
+// The characters that base64 uses for each 6 bit value, in order.
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes the non-negative integer as base64, using the standard alphabet and padding, from its
+/// big-endian bytes. 0 is a single zero byte, `AA==`.
+pub fn make_base64_string(value: &BigUint) -> String {
+    let bytes = value.to_bytes_be();
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, byte)| {
+            bits | (u32::from(*byte) << (16 - 8 * index))
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (bits >> (18 - 6 * index)) & 0x3f;
+                encoded.push(char::from(BASE64_ALPHABET[sextet as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// The inverse of `make_base64_string`: decodes base64 as a big-endian integer. Padding is
+/// optional. Returns `None` if the text isn't valid base64.
+pub fn parse_base64(text: &str) -> Option<BigUint> {
+    let text = text.trim_end_matches('=');
+    if text.is_empty() || text.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut bits: u32 = 0;
+    let mut bit_count = 0;
+    for c in text.bytes() {
+        let sextet = BASE64_ALPHABET.iter().position(|&digit| digit == c)?;
+        bits = (bits << 6) | sextet as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+    Some(BigUint::from_bytes_be(&bytes))
+}
+
+/// The power of ten that the value's leading digit is multiplied by, i.e. `floor(log10(|value|))`.
+/// The value must not be zero.
+pub fn decimal_exponent(value: &BigRational) -> i64 {
+    let value = value.abs();
+    // A numerator with `a` digits over a denominator with `b` digits is within a factor of ten of
+    // `10^(a - b)`, one way or the other.
+    let digit_count = |n: &BigInt| n.to_string().len() as i64;
+    let exponent = digit_count(value.numer()) - digit_count(value.denom());
+    if value < power_of_ten(exponent) {
+        exponent - 1
+    } else {
+        exponent
+    }
+}
+
+// `10^exponent`, for exponents of either sign.
+fn power_of_ten(exponent: i64) -> BigRational {
+    let power = BigInt::from(10).pow(exponent.unsigned_abs());
+    if exponent < 0 {
+        BigRational::new(BigInt::one(), power)
+    } else {
+        BigRational::from_integer(power)
+    }
+}
+
+/// The mantissa and exponent that the value is written with in scientific notation, such that
+/// `value == mantissa * 10^exponent` and `1 <= |mantissa| < 10`. The value must not be zero.
+pub fn scientific_parts(value: &BigRational) -> (BigRational, i64) {
+    exponent_parts(value, 1)
+}
+
+/// Like `scientific_parts`, but for engineering notation, where the exponent is a multiple of 3
+/// and `1 <= |mantissa| < 1000`.
+pub fn engineering_parts(value: &BigRational) -> (BigRational, i64) {
+    exponent_parts(value, 3)
+}
+
+// The mantissa and exponent of the value when its exponent is the largest multiple of `step` that
+// is at most its decimal exponent.
+fn exponent_parts(value: &BigRational, step: i64) -> (BigRational, i64) {
+    let exponent = decimal_exponent(value).div_euclid(step) * step;
+    (value / power_of_ten(exponent), exponent)
+}
+
+/// Formats the value in decimal scientific notation, like `1.5e-7`, with up to `precision` digits
+/// after the mantissa's decimal point. Like `make_decimal_string`, trailing zeros are only shown
+/// when the mantissa has been rounded.
+pub fn make_scientific_string(value: &BigRational, precision: u8) -> String {
+    make_exponent_string(value, precision, 1)
+}
+
+/// Like `make_scientific_string`, but in engineering notation, like `150e-9`.
+pub fn make_engineering_string(value: &BigRational, precision: u8) -> String {
+    make_exponent_string(value, precision, 3)
+}
+
+// Formats the value with an exponent that is a multiple of `step`, which is 1 for scientific
+// notation and 3 for engineering notation.
+fn make_exponent_string(value: &BigRational, precision: u8, step: i64) -> String {
+    if value.is_zero() {
+        return "0".to_string();
+    }
+    let (mut mantissa, mut exponent) = exponent_parts(value, step);
+    // Rounding can carry into another digit, like 9.999999 becoming 10.00000.
+    let scale = BigRational::from_integer(BigInt::from(10).pow(u32::from(precision)));
+    let limit = power_of_ten(step);
+    if (&mantissa * &scale).abs().round() >= &scale * &limit {
+        mantissa /= limit;
+        exponent += step;
+    }
+    format!(
+        "{}e{}",
+        make_decimal_string(&mantissa, 10, precision, None, false),
+        exponent
+    )
+}
+
+// Exponents larger than this are rejected rather than producing absurdly large numbers.
+const MAX_EXPONENT: u64 = 1_000;
+
+/// Parses a decimal number such as "-12.5" or "1.5e-7" into an exact rational.
+pub fn parse_decimal(text: &str) -> Option<BigRational> {
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, i32::from_str(exponent).ok()?),
+        None => (text, 0),
+    };
+    let (integer_part, fractional_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let (negative, integer_part) = match integer_part.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (
+            false,
+            integer_part.strip_prefix('+').unwrap_or(integer_part),
+        ),
+    };
+    if integer_part.is_empty() && fractional_part.is_empty() {
+        return None;
+    }
+    if !integer_part
+        .chars()
+        .chain(fractional_part.chars())
+        .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let digits = BigInt::from_str(&format!("{}{}", integer_part, fractional_part)).ok()?;
+    let exponent = i64::from(exponent) - fractional_part.len() as i64;
+    if exponent.unsigned_abs() > MAX_EXPONENT {
+        return None;
+    }
+    let scale = num::pow(
+        BigInt::from(10),
+        usize::try_from(exponent.unsigned_abs()).ok()?,
+    );
+    let value = if exponent < 0 {
+        BigRational::new(digits, scale)
+    } else {
+        BigRational::from_integer(digits * scale)
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Formats a number of seconds as hours, minutes, and seconds, like `1:02:03.5`. Seconds are shown
+/// with up to `precision` decimal places, with trailing zeros indicating rounding just as
+/// `make_decimal_string` does.
+pub fn make_duration_string(seconds: &BigRational, precision: u8) -> String {
+    let scale = BigRational::from_integer(BigInt::from(10).pow(precision as u32));
+    let magnitude = seconds.abs();
+    // Rounding happens first so that it can carry into the minutes and hours.
+    let rounded = (&magnitude * &scale).round() / &scale;
+    let sign_str = if seconds.is_negative() && !rounded.is_zero() {
+        "-"
+    } else {
+        ""
+    };
+
+    let seconds_per_hour = BigRational::from_integer(BigInt::from(3_600));
+    let seconds_per_minute = BigRational::from_integer(BigInt::from(60));
+    let hours = (&rounded / &seconds_per_hour).floor();
+    let remainder = &rounded - &hours * &seconds_per_hour;
+    let minutes = (&remainder / &seconds_per_minute).floor();
+    let remainder = remainder - &minutes * &seconds_per_minute;
+
+    let mut seconds_string = make_decimal_string(&remainder, 10, precision, None, false);
+    if rounded != magnitude && precision > 0 {
+        let fractional_len = match seconds_string.split_once('.') {
+            Some((_, fractional)) => fractional.len(),
+            None => {
+                seconds_string.push('.');
+                0
+            }
+        };
+        seconds_string.push_str(&"0".repeat(precision as usize - fractional_len));
+    }
+    let padding = if remainder < BigRational::from_integer(BigInt::from(10)) {
+        "0"
+    } else {
+        ""
+    };
+    format!(
+        "{}{}:{:0>2}:{}{}",
+        sign_str,
+        hours.to_integer(),
+        minutes.to_integer(),
+        padding,
+        seconds_string
+    )
+}
+
+/// The largest bit index that `extract_bits` and `replace_bits` are used with, which keeps them from
+/// building enormous masks.
+pub const MAX_BIT_INDEX: u32 = 65_535;
+
+fn bit_mask(width: u32) -> BigInt {
+    (BigInt::one() << width) - 1
+}
+
+/// Returns bits `hi` through `lo` of `value`, where bit 0 is the least significant bit and `hi` is
+/// at least `lo`. Negative values are treated as their two's complement representation, extended
+/// with as many ones as needed, so bits 7 through 0 of -1 are 255.
+pub fn extract_bits(value: &BigInt, hi: u32, lo: u32) -> BigInt {
+    (value >> lo) & bit_mask(hi - lo + 1)
+}
+
+/// Returns `value` with bits `hi` through `lo` replaced by `field`. Fails if `field` is negative or
+/// doesn't fit in that many bits.
+pub fn replace_bits(value: &BigInt, hi: u32, lo: u32, field: &BigInt) -> Result<BigInt, String> {
+    let width = hi - lo + 1;
+    let mask = bit_mask(width);
+    if field.is_negative() || *field > mask {
+        return Err(format!("{} doesn't fit in {} bits", field, width));
+    }
+    Ok((value & !(mask << lo)) | (field << lo))
+}
+
+/// Returns the prime factors of `n`, which must be at least 2, smallest first. Each factor is
+/// repeated as many times as it divides `n`, so the factors of 12 are 2, 2, and 3.
+pub fn prime_factors(
+    n: &BigInt,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<Vec<BigInt>, MathExecutionError> {
+    let mut remaining = n.clone();
+    let mut factors = Vec::new();
+    let mut candidate = BigInt::from(2);
+    while &candidate * &candidate <= remaining {
+        if let Some(interrupt) = maybe_interrupt {
+            interrupt.check()?;
+        }
+        if remaining.is_multiple_of(&candidate) {
+            remaining /= &candidate;
+            factors.push(candidate.clone());
+        } else if candidate == BigInt::from(2) {
+            candidate += 1;
+        } else {
+            candidate += 2;
+        }
+    }
+    factors.push(remaining);
+    Ok(factors)
+}
+
+// Ranges with fewer integers than this are multiplied out one integer at a time by
+// `product_of_range`.
+const MIN_SPLIT_RANGE: u32 = 16;
+
+/// The product of the integers from `low` through `high`, or 1 if the range is empty.
+pub fn product_of_range(
+    low: &BigInt,
+    high: &BigInt,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigInt, MathExecutionError> {
+    if let Some(interrupt) = maybe_interrupt {
+        interrupt.check()?;
+    }
+    if high - low < BigInt::from(MIN_SPLIT_RANGE) {
+        let mut product = BigInt::one();
+        let mut factor = low.clone();
+        while factor <= *high {
+            product *= &factor;
+            factor += 1;
+        }
+        return Ok(product);
+    }
+    // Multiplying the two halves of the range separately keeps the numbers being multiplied close
+    // in size, which is much faster than multiplying each integer into an ever larger product.
+    let middle: BigInt = (low + high).div_floor(&BigInt::from(2));
+    Ok(product_of_range(low, &middle, maybe_interrupt)?
+        * product_of_range(&(middle + 1), high, maybe_interrupt)?)
+}
+
+/// `n!`, the product of the integers from 1 through `n`, which must not be negative.
+pub fn factorial(
+    n: &BigInt,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigInt, MathExecutionError> {
+    product_of_range(&BigInt::from(2), n, maybe_interrupt)
+}
+
+/// The number of ways to arrange `k` of `n` items in order, `n! / (n - k)!`. Neither can be
+/// negative. If `k` is greater than `n`, there are no ways.
+pub fn permutations(
+    n: &BigInt,
+    k: &BigInt,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigInt, MathExecutionError> {
+    if k > n {
+        return Ok(BigInt::zero());
+    }
+    product_of_range(&(n - k + 1), n, maybe_interrupt)
+}
+
+/// The number of ways to choose `k` of `n` items, ignoring order, `n! / (k! (n - k)!)`. Neither
+/// can be negative. If `k` is greater than `n`, there are no ways.
+pub fn combinations(
+    n: &BigInt,
+    k: &BigInt,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigInt, MathExecutionError> {
+    if k > n {
+        return Ok(BigInt::zero());
+    }
+    // Choosing `k` items is the same as choosing the `n - k` items to leave out, and the smaller
+    // of the two makes for less multiplication.
+    let k = min(k.clone(), n - k);
+    Ok(permutations(n, &k, maybe_interrupt)? / factorial(&k, maybe_interrupt)?)
+}
+
+/// The mean of the values, which must not be empty.
+pub fn mean(values: &[BigRational]) -> BigRational {
+    let count = BigRational::from_integer(BigInt::from(values.len()));
+    values.iter().sum::<BigRational>() / count
+}
+
+/// The median of the values, which must not be empty. With an even number of values, it is the
+/// mean of the middle two.
+pub fn median(mut values: Vec<BigRational>) -> BigRational {
+    values.sort();
+    let middle = values.len() / 2;
+    if values.len() % 2 == 1 {
+        values.swap_remove(middle)
+    } else {
+        (&values[middle - 1] + &values[middle]) / BigRational::from_integer(BigInt::from(2))
+    }
+}
+
+/// The sample variance of the values, which is the sum of the squared differences from the mean
+/// divided by one less than the number of values. There must be at least two values.
+pub fn variance(values: &[BigRational]) -> BigRational {
+    let mean = mean(values);
+    let squares: BigRational = values
+        .iter()
+        .map(|value| {
+            let difference = value - &mean;
+            &difference * &difference
+        })
+        .sum();
+    squares / BigRational::from_integer(BigInt::from(values.len() - 1))
+}
+
+// `make_fraction_string` only looks for prime factors of a denominator below this, so that showing
+// a fraction with a huge prime denominator doesn't take forever. Whatever is left over is shown as
+// a single factor.
+const MAX_DISPLAYED_PRIME_FACTOR: u32 = 10_000;
+
+/// Formats the value as a fraction in lowest terms, like `35/12`, or as an integer if it is one. If
+/// `factored` is set, the denominator is shown as a product of prime powers, like `35/(2^2*3)`.
+/// Either way, the result can be entered as an expression to get the value back.
+pub fn make_fraction_string(value: &BigRational, factored: bool) -> String {
+    // `BigRational` keeps itself in lowest terms, so there is nothing to reduce.
+    if value.is_integer() || !factored {
+        return value.to_string();
+    }
+    let mut remaining = value.denom().clone();
+    // Each prime factor along with how many times it divides the denominator.
+    let mut powers: Vec<(BigInt, u32)> = Vec::new();
+    let mut candidate = BigInt::from(2);
+    while &candidate * &candidate <= remaining
+        && candidate < BigInt::from(MAX_DISPLAYED_PRIME_FACTOR)
+    {
+        let mut count = 0;
+        while remaining.is_multiple_of(&candidate) {
+            remaining /= &candidate;
+            count += 1;
+        }
+        if count > 0 {
+            powers.push((candidate.clone(), count));
+        }
+        candidate += if candidate == BigInt::from(2) { 1 } else { 2 };
+    }
+    if !remaining.is_one() {
+        powers.push((remaining, 1));
+    }
+    let factors: Vec<String> = powers
+        .iter()
+        .map(|(prime, count)| match count {
+            1 => prime.to_string(),
+            _ => format!("{}^{}", prime, count),
+        })
+        .collect();
+    if factors.len() == 1 && powers[0].1 == 1 {
+        format!("{}/{}", value.numer(), factors[0])
+    } else {
+        format!("{}/({})", value.numer(), factors.join("*"))
+    }
+}
+
+/// Formats the value as a mixed number, like `2 1/3`, with the fractional part formatted by
+/// `make_fraction_string`. Values less than 1 in magnitude, and integers, are formatted just as
+/// `make_fraction_string` formats them. The sign applies to the whole number, so `-7/3` is
+/// `-2 1/3`.
+pub fn make_mixed_number_string(value: &BigRational, factored: bool) -> String {
+    // `to_integer` truncates toward 0, so the whole and fractional parts have the same sign.
+    let whole = value.to_integer();
+    if whole.is_zero() || value.is_integer() {
+        return make_fraction_string(value, factored);
+    }
+    let fraction = (value - BigRational::from_integer(whole.clone())).abs();
+    format!("{} {}", whole, make_fraction_string(&fraction, factored))
+}
+
+// Roots are first computed to about this many digits before the precision is repeatedly doubled.
+const MIN_WORKING_DIGITS: u32 = 8;
+
+// Square roots of positive numbers are only kept exact, as surds, when the product of the
+// numerator and denominator is at most this, since finding its square factors means factoring it.
+const MAX_SURD_RADICAND: u64 = 1_000_000_000_000;
+
+// Splits `n` into `s` and `r` such that `n = s^2 * r`, where `r` has no square factors. Returns
+// `None` if `n` is larger than `MAX_SURD_RADICAND` or isn't positive.
+fn square_free_parts(n: &BigInt) -> Option<(BigInt, BigInt)> {
+    if !n.is_positive() || *n > BigInt::from(MAX_SURD_RADICAND) {
+        return None;
+    }
+    let mut remaining = n.clone();
+    let mut square_part = BigInt::one();
+    let mut square_free_part = BigInt::one();
+    let mut factor = BigInt::from(2);
+    while &factor * &factor * &factor <= remaining {
+        let factor_squared = &factor * &factor;
+        while (&remaining % &factor_squared).is_zero() {
+            remaining /= &factor_squared;
+            square_part *= &factor;
+        }
+        if (&remaining % &factor).is_zero() {
+            remaining /= &factor;
+            square_free_part *= &factor;
+        }
+        factor += 1;
+    }
+    // There are no factors below the cube root of what remains, so it is either a prime, the
+    // product of two different primes, or the square of a prime.
+    let root = remaining.sqrt();
+    if &root * &root == remaining {
+        square_part *= root;
+    } else {
+        square_free_part *= remaining;
+    }
+    Some((square_part, square_free_part))
+}
+
+/// `base ^ exponent`, to `precision` digits after the radix point. Results that are rational are
+/// exact, and so are odd multiples of a half power of a positive rational number, which are
+/// surds like `2^(3/2) = 2*sqrt(2)`. Even roots of negative numbers, like `(-4)^(1/2)`, are
+/// complex, as are most powers of complex numbers.
 pub fn exponentiate(
+    base: Value,
+    exponent: BigRational,
+    precision: u8,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<Value, MathExecutionError> {
+    let base = match base {
+        Value::Surd { approximation, .. } if !exponent.is_integer() => Value::Real(approximation),
+        base => base,
+    };
+    let base = match base {
+        // `sqrt(p/q) = sqrt(p*q)/q`, and `p*q` can be split into a square and a square-free part.
+        Value::Real(base) if base.is_positive() && *exponent.denom() == BigInt::from(2) => {
+            let approximation = real_exponentiate(
+                base.clone(),
+                BigRational::new(BigInt::one(), BigInt::from(2)),
+                precision,
+                radix,
+                maybe_interrupt,
+            )?;
+            let root = match square_free_parts(&(base.numer() * base.denom())) {
+                Some((square_part, radicand)) => Value::surd(
+                    BigRational::new(square_part, base.denom().clone()),
+                    radicand,
+                    approximation,
+                ),
+                None => Value::Real(approximation),
+            };
+            return root.pow(exponent.numer()).ok_or(DivisionByZero);
+        }
+        Value::Real(base) if !base.is_negative() || exponent.denom().is_odd() => {
+            return real_exponentiate(base, exponent, precision, radix, maybe_interrupt)
+                .map(Value::Real);
+        }
+        base => base,
+    };
+    if exponent.is_integer() {
+        return base.pow(exponent.numer()).ok_or(DivisionByZero);
+    }
+    // Roots only ever increase, so the root of an interval runs from the root of its low end to the
+    // root of its high end. An even root of a negative number would be complex, though.
+    if let Value::Interval { low, high } = base {
+        if low.is_negative() && exponent.denom().is_even() {
+            return Err(Uncertain("Even roots of negative numbers".to_string()));
+        }
+        let root = BigRational::new(BigInt::one(), exponent.denom().clone());
+        let low = real_exponentiate(low, root.clone(), precision, radix, maybe_interrupt)?;
+        let high = real_exponentiate(high, root, precision, radix, maybe_interrupt)?;
+        return Value::interval(low, high)
+            .pow(exponent.numer())
+            .ok_or(DivisionByZero);
+    }
+    // Square roots have a closed form that gives exact results for perfect squares like `3+4i`,
+    // which going through the angle of the base wouldn't.
+    if *exponent.denom() == BigInt::from(2) {
+        let root = square_root(base, precision, radix, maybe_interrupt)?;
+        return root.pow(exponent.numer()).ok_or(DivisionByZero);
+    }
+    // Otherwise, `z^e = |z|^e * (cos(e*t) + i*sin(e*t))`, where `t` is the angle of `z`. The angle
+    // is worked out in degrees so that the sine and cosine of angles like 90 degrees are exact.
+    let magnitude = real_exponentiate(
+        base.norm_sqr(),
+        &exponent / BigInt::from(2),
+        precision,
+        radix,
+        maybe_interrupt,
+    )?;
+    let angle = arctangent(
+        &base.im(),
+        &base.re(),
+        true,
+        precision,
+        radix,
+        maybe_interrupt,
+    )? * exponent;
+    Ok(Value::complex(
+        &magnitude * cosine(&angle, true, precision, radix, maybe_interrupt)?,
+        magnitude * sine(&angle, true, precision, radix, maybe_interrupt)?,
+    ))
+}
+
+// The square root of `a+bi` is `sqrt((|z|+a)/2) + sqrt((|z|-a)/2)i`, with the imaginary part
+// negated if `b` is negative. The root of a negative real number is positive imaginary.
+fn square_root(
+    value: Value,
+    precision: u8,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<Value, MathExecutionError> {
+    let one_half = BigRational::new(BigInt::one(), BigInt::from(2));
+    let magnitude = real_exponentiate(
+        value.norm_sqr(),
+        one_half.clone(),
+        precision,
+        radix,
+        maybe_interrupt,
+    )?;
+    // If the magnitude had to be rounded, it could end up a little smaller than the real part.
+    let half_root = |radicand: BigRational| {
+        real_exponentiate(
+            (radicand * &one_half).max(BigRational::zero()),
+            one_half.clone(),
+            precision,
+            radix,
+            maybe_interrupt,
+        )
+    };
+    let re = half_root(&magnitude + value.re())?;
+    let im = half_root(magnitude - value.re())?;
+    Ok(Value::complex(
+        re,
+        if value.im().is_negative() { -im } else { im },
+    ))
+}
+
+// `exponentiate` for real results, which are all that `base ^ exponent` can give unless `base` is
+// negative and the denominator of `exponent` is even.
+fn real_exponentiate(
     mut base: BigRational,
     exponent: BigRational,
     precision: u8,
     radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
 ) -> Result<BigRational, MathExecutionError> {
     // Step 1: If necessary, convert `b^-(n/d)` to `(1/b)^(n/d)`.
     if exponent.is_negative() {
+        if base.is_zero() {
+            return Err(DivisionByZero);
+        }
         base = base.inv();
     }
 
@@ -94,110 +811,725 @@ pub fn exponentiate(
     // Step 2: Convert `b^(n/d)` to `(b^n)^(1/d)` and compute `r = b^n` so we are left with
     // `r^(1/d)`.
     let radicand = base.pow(exp_num);
-
-    // Step 3: Newton's Method
-    // Given `r` and `d`, we want to compute `r^(1/d)`. We will call the result `x`.
-    // So `r^(1/d) = x`, or `r = x^d`.
-    // Newton's method concerns finding when a function reaches 0. So we will make our function
-    // `f(x) = x^d - r`.
-    // Newton's method can be summarized as iterations of `x_n+1 = x_n - f(x_n)/f'(x_n)`.
-    // (Using `x` for `x_n` below, for brevity)
-    // `x_n+1 = x - (x^d - r)/(d * x^(d - 1))`
-    // `x_n+1 = x + (r - x^d)/(d * x^(d - 1))`
-    // `x_n+1 = (r - x^d + (x * d * x^(d - 1)))/(d * x^(d - 1))`
-    // `x_n+1 = (r - x^d + d*x^d)/(d * x^(d - 1))
-    // `x_n+1 = (r + (d - 1)*x^d)/(d * x^(d - 1))
-
-    // Step 3.1: Rename, convert, and pre-calculate a few things.
     let degree = exp_denom;
-    let one = BigUint::from(1u8);
-    let one_signed = BigInt::from(1);
-    let degree_ratio: BigRational = BigRational::from(BigInt::from(degree.clone()));
-    let degree_dec: BigUint = &degree - &one;
-    let degree_dec_ratio: BigRational = BigRational::from(BigInt::from(degree_dec.clone()));
-    // We are actually going to add one additional digit of precision. This prevents a rounding
-    // error from making our last guaranteed digit wrong.
-    let precision = BigUint::from(precision + 1);
-    let radix = BigInt::from(radix);
-    // The largest amount we are okay with being wrong by.
-    let max_error = BigRational::new(one_signed.clone(), radix.pow(precision).into());
-    let f_magnitude = |x: &BigInt| -> BigRational {
-        (BigRational::from(x.clone()).pow(&degree) - &radicand).abs()
-    };
-    let next_x = |x: BigRational| -> BigRational {
-        (&radicand + &degree_dec_ratio * x.clone().pow(&degree))
-            / (&degree_ratio * x.pow(&degree_dec))
-    };
 
     // We are already done.
-    if degree == one {
+    if degree.is_one() {
         return Ok(radicand);
     }
 
-    // Step 3.2: Input validation. This function currently cannot output complex numbers.
-    if radicand.is_negative() && degree.is_even() {
-        return Err(ImaginaryResult);
+    // Step 3: The degree is the denominator of the exponent, and `exponentiate` only gives us
+    // negative bases along with odd denominators, so there are no even roots of negative numbers
+    // to worry about.
+
+    // Step 4: An odd root of a negative number is the negation of the root of its magnitude, so
+    // from here on we only need to deal with non-negative numbers.
+    let negative = radicand.is_negative();
+    let (numer, denom) = radicand.abs().into_raw();
+    let (numer, denom) = (numer.to_biguint().unwrap(), denom.to_biguint().unwrap());
+    let signed = |magnitude: BigRational| if negative { -magnitude } else { magnitude };
+
+    // Step 5: If both the numerator and denominator are perfect powers, the root is exact.
+    let numer_root = integer_root(&numer, &degree, None, None)?;
+    if Pow::pow(&numer_root, &degree) == numer {
+        let denom_root = integer_root(&denom, &degree, None, None)?;
+        if Pow::pow(&denom_root, &degree) == denom {
+            return Ok(signed(BigRational::new(
+                numer_root.into(),
+                denom_root.into(),
+            )));
+        }
+    }
+
+    // Step 6: Otherwise, compute the root in fixed point. We want `x = r^(1/d)` to `p` digits. If
+    // we scale by `s = radix^p`, then `x * s = (r * s^d)^(1/d)`, which we can compute as an
+    // integer root. Truncating `r * s^d` to an integer doesn't change the integer part of its
+    // root, so the result is exactly `x` truncated to `p` digits, and no intermediate value needs
+    // more precision than that.
+    // We are actually going to add one additional digit of precision so that rounding the result
+    // for display gives the same digits that rounding the true value would.
+    let total_digits = u32::from(precision) + 1;
+
+    // Step 7: Rather than running Newton's method at the full precision from a rough guess, work
+    // our way up to it, roughly doubling the number of digits each pass. Each pass starts from the
+    // previous one's result, which is only one unit in its last place away from the root, so only
+    // the first pass needs more than a couple of iterations and the expensive full-precision
+    // arithmetic is only done a few times.
+    let mut working_digits = vec![total_digits];
+    while let Some(&digits) = working_digits.last() {
+        if digits <= MIN_WORKING_DIGITS {
+            break;
+        }
+        working_digits.push(digits / 2);
     }
 
-    // Step 3.3: Use a binary search to find a good starting point for Newton's method. Otherwise
-    // it takes forever.
-    let mut x = {
-        let (mut lower_bound, mut upper_bound) = if radicand.is_negative() {
-            (radicand.to_integer(), BigInt::from(0))
-        } else {
-            (BigInt::from(0), radicand.to_integer())
-        };
+    let radix_big = BigUint::from(radix);
+    let mut maybe_previous: Option<(u32, BigUint)> = None;
+    for digits in working_digits.into_iter().rev() {
+        let scale = Pow::pow(&radix_big, digits);
+        let scaled_radicand = &numer * Pow::pow(&scale, &degree) / &denom;
+        // The previous result is the root truncated to fewer digits, so one more than it (in its
+        // last place) is an upper bound on the root, which is where Newton's method needs to
+        // start.
+        let maybe_start = maybe_previous.map(|(previous_digits, previous_root)| {
+            (previous_root + 1u8) * Pow::pow(&radix_big, digits - previous_digits)
+        });
+        let root = integer_root(&scaled_radicand, &degree, maybe_start, maybe_interrupt)?;
+        if let Some(interrupt) = maybe_interrupt {
+            interrupt.report(Progress {
+                digits,
+                total_digits,
+            });
+        }
+        maybe_previous = Some((digits, root));
+    }
 
-        let mut span = &upper_bound - &lower_bound;
-        while span > BigInt::ZERO {
-            let lower_guess: BigInt = (&upper_bound - &lower_bound) / 2 + &lower_bound;
-            let upper_guess: BigInt = &lower_guess + 1;
+    // Step 8: The root lies somewhere between the result and one more than it (in the last
+    // place), so report the middle of that range. Besides being closer on average, this keeps the
+    // result from looking like an exactly representable value when it's displayed.
+    let (_, root) = maybe_previous.unwrap();
+    let scale = Pow::pow(&radix_big, total_digits);
+    Ok(signed(BigRational::new(
+        (root * 2u8 + 1u8).into(),
+        (scale * 2u8).into(),
+    )))
+}
 
-            let lower_guess_error = f_magnitude(&lower_guess);
-            let upper_guess_error = f_magnitude(&upper_guess);
+// Returns the largest integer `x` such that `x^degree <= n`, using Newton's method on integers.
+// If given, `maybe_start` must be at least that large.
+fn integer_root(
+    n: &BigUint,
+    degree: &BigUint,
+    maybe_start: Option<BigUint>,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigUint, MathExecutionError> {
+    if *n < BigUint::from(2u8) {
+        return Ok(n.clone());
+    }
 
-            if lower_guess_error < upper_guess_error {
-                upper_bound = lower_guess;
-            } else {
-                lower_bound = upper_guess;
-            }
+    // Newton's method concerns finding when a function reaches 0. So we will make our function
+    // `f(x) = x^d - n`, giving iterations of `x_n+1 = ((d - 1) * x + n / x^(d - 1)) / d`. From any
+    // positive guess, one iteration lands at or above the root (rounding down). From there, every
+    // iteration stays above it until we have found it, at which point the next iteration will
+    // stop getting smaller.
+    let degree_dec = degree - 1u8;
+    let next = |x: &BigUint| (&degree_dec * x + n / Pow::pow(x, &degree_dec)) / degree;
+    let mut x = match maybe_start {
+        Some(start) => start,
+        None => next(&estimate_root(n, degree)),
+    };
+    loop {
+        if let Some(interrupt) = maybe_interrupt {
+            interrupt.check()?;
+        }
+        let next_x = next(&x);
+        if next_x >= x {
+            return Ok(x);
+        }
+        x = next_x;
+    }
+}
+
+// A positive guess at the `degree`th root of `n`, good to about as many bits as an `f64` holds.
+fn estimate_root(n: &BigUint, degree: &BigUint) -> BigUint {
+    const MANTISSA_BITS: u64 = 52;
+    let shift = n.bits().saturating_sub(64);
+    let leading = (n >> shift).to_f64().unwrap();
+    let log2_root = (leading.log2() + shift as f64) / degree.to_f64().unwrap();
+    let int_bits = log2_root.floor();
+    let mantissa = (log2_root - int_bits + MANTISSA_BITS as f64).exp2() as u64;
+    let estimate = (BigUint::from(mantissa) << int_bits as u64) >> MANTISSA_BITS;
+    estimate.max(BigUint::one())
+}
+
+// Logarithms and exponentials are computed in binary fixed point with this many more bits than the
+// result needs. This covers the rounding error that builds up over the terms of their series.
+const GUARD_BITS: u64 = 32;
+
+/// The largest magnitude that `exponential` accepts. `e^10000` already has over 4000 digits before
+/// the radix point.
+pub const MAX_EXPONENTIAL_ARGUMENT: u32 = 10_000;
+
+// The number of bits after the binary point needed to hold `digits` digits after the radix point.
+fn bits_for_digits(digits: u32, radix: u8) -> u64 {
+    (f64::from(digits) * f64::from(radix).log2()).ceil() as u64
+}
 
-            span = &upper_bound - &lower_bound;
+// Rounds a fixed point value with `bits` bits after the binary point to the nearest value with
+// `digits` digits after the radix point.
+fn round_fixed_point(value: &BigInt, bits: u64, digits: u32, radix: u8) -> BigRational {
+    let scale = Pow::pow(BigInt::from(radix), digits);
+    let one = BigInt::one() << bits;
+    let half = BigInt::one() << (bits - 1);
+    let scaled = (value * &scale + half).div_floor(&one);
+    BigRational::new(scaled, scale)
+}
+
+// `atanh(p / q)` in fixed point with `bits` bits after the binary point, from the series
+// `z + z^3/3 + z^5/5 + ...`, or `atan(p / q)` if `hyperbolic` isn't set, from the same series with
+// alternating signs. These converge quickly as long as `|p / q|` is well below 1.
+fn inverse_tangent_series(
+    p: &BigInt,
+    q: &BigInt,
+    bits: u64,
+    hyperbolic: bool,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigInt, MathExecutionError> {
+    let p_squared = p * p;
+    let q_squared = q * q;
+    let mut power = (p << bits) / q;
+    let mut sum = BigInt::zero();
+    let mut divisor = BigInt::one();
+    let mut subtract = false;
+    while !power.is_zero() {
+        if let Some(interrupt) = maybe_interrupt {
+            interrupt.check()?;
+        }
+        if subtract {
+            sum -= &power / &divisor;
+        } else {
+            sum += &power / &divisor;
         }
+        power = power * &p_squared / &q_squared;
+        divisor += 2;
+        subtract = !hyperbolic && !subtract;
+    }
+    Ok(sum)
+}
+
+// `ln(2)` in fixed point with `bits` bits after the binary point.
+fn ln_2_fixed(
+    bits: u64,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigInt, MathExecutionError> {
+    Ok(inverse_tangent_series(
+        &BigInt::one(),
+        &BigInt::from(3),
+        bits,
+        true,
+        maybe_interrupt,
+    )? * 2)
+}
 
-        let guess = upper_bound;
+// `ln(value)` in fixed point with `bits` bits after the binary point. `value` must be positive.
+fn natural_log_fixed(
+    value: &BigRational,
+    bits: u64,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigInt, MathExecutionError> {
+    // Write the value as `m * 2^k` with `m` between 1/2 and 2. Then `ln(value) = ln(m) + k ln(2)`,
+    // and `ln(m) = 2 atanh((m - 1) / (m + 1))`, where `(m - 1) / (m + 1)` is within 1/3 of 0.
+    let (numer, denom) = (value.numer(), value.denom());
+    let k = numer.bits() as i64 - denom.bits() as i64;
+    let (a, b) = if k >= 0 {
+        (numer.clone(), denom << k.unsigned_abs())
+    } else {
+        (numer << k.unsigned_abs(), denom.clone())
+    };
+    // The error in `ln(2)` is multiplied by `k`, so it needs as many more bits as `k` has.
+    let extra_bits = u64::from(u64::BITS - k.unsigned_abs().leading_zeros());
+    let working_bits = bits + extra_bits;
+    let ln_m =
+        inverse_tangent_series(&(&a - &b), &(&a + &b), working_bits, true, maybe_interrupt)? * 2;
+    let ln_value = ln_m + ln_2_fixed(working_bits, maybe_interrupt)? * k;
+    Ok(ln_value >> extra_bits)
+}
 
-        let error = f_magnitude(&guess);
-        let guess_ratio = BigRational::from(guess);
-        // Return early if it's an exact integer.
-        if error.is_zero() {
-            return Ok(guess_ratio);
+/// The natural logarithm of `value`, which must be positive, to `precision` digits after the radix
+/// point.
+pub fn natural_log(
+    value: &BigRational,
+    precision: u8,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigRational, MathExecutionError> {
+    if value.is_one() {
+        return Ok(BigRational::zero());
+    }
+    // As with roots, one additional digit is computed so that rounding the result for display
+    // gives the same digits that rounding the true value would.
+    let digits = u32::from(precision) + 1;
+    let bits = bits_for_digits(digits, radix) + GUARD_BITS;
+    let ln_value = natural_log_fixed(value, bits, maybe_interrupt)?;
+    Ok(round_fixed_point(&ln_value, bits, digits, radix))
+}
+
+// If `value` is an integer power of `base`, like `1000` or `1/100` are of 10, returns the exponent.
+fn exact_log(value: &BigRational, base: u32) -> Option<BigInt> {
+    let base = BigInt::from(base);
+    let (mut remaining, negative) = if value.denom().is_one() {
+        (value.numer().clone(), false)
+    } else if value.numer().is_one() {
+        (value.denom().clone(), true)
+    } else {
+        return None;
+    };
+    let mut exponent = BigInt::zero();
+    while remaining.is_multiple_of(&base) {
+        remaining /= &base;
+        exponent += 1;
+    }
+    if !remaining.is_one() {
+        return None;
+    }
+    Some(if negative { -exponent } else { exponent })
+}
+
+/// The logarithm of `value`, which must be positive, in the given base to `precision` digits after
+/// the radix point. Powers of the base give exact results.
+pub fn logarithm(
+    value: &BigRational,
+    base: u32,
+    precision: u8,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigRational, MathExecutionError> {
+    if let Some(exponent) = exact_log(value, base) {
+        return Ok(BigRational::from_integer(exponent));
+    }
+    let digits = u32::from(precision) + 1;
+    let bits = bits_for_digits(digits, radix) + GUARD_BITS;
+    let ln_value = natural_log_fixed(value, bits, maybe_interrupt)?;
+    let ln_base = natural_log_fixed(
+        &BigRational::from_integer(BigInt::from(base)),
+        bits,
+        maybe_interrupt,
+    )?;
+    Ok(round_fixed_point(
+        &((ln_value << bits) / ln_base),
+        bits,
+        digits,
+        radix,
+    ))
+}
+
+/// `e` raised to `value`, to `precision` digits after the radix point. The magnitude of `value`
+/// should be at most `MAX_EXPONENTIAL_ARGUMENT`.
+pub fn exponential(
+    value: &BigRational,
+    precision: u8,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigRational, MathExecutionError> {
+    if value.is_zero() {
+        return Ok(BigRational::one());
+    }
+    // Write `value` as `r + k ln(2)` with `r` within about 0.35 of 0. Then `e^value = e^r * 2^k`,
+    // and the Taylor series for `e^r` converges quickly.
+    let k = (value.to_f64().unwrap_or(0.0) / std::f64::consts::LN_2).round() as i64;
+    let digits = u32::from(precision) + 1;
+    let bits = bits_for_digits(digits, radix) + GUARD_BITS;
+    // Multiplying by `2^k` multiplies the error in `e^r` too. `ln(2)` also needs more bits since
+    // its error is multiplied by `k`.
+    let working_bits =
+        bits + k.max(0).unsigned_abs() + u64::from(u64::BITS - k.unsigned_abs().leading_zeros());
+    let value_fixed = (value.numer() << working_bits) / value.denom();
+    let r = value_fixed - ln_2_fixed(working_bits, maybe_interrupt)? * k;
+
+    let one = BigInt::one() << working_bits;
+    let mut term = one.clone();
+    let mut sum = one;
+    let mut n = BigInt::one();
+    while !term.is_zero() {
+        if let Some(interrupt) = maybe_interrupt {
+            interrupt.check()?;
         }
+        term = ((term * &r) >> working_bits) / &n;
+        sum += &term;
+        n += 1;
+    }
+    // `sum` has `working_bits` bits after the binary point, so scaling it by `2^k` leaves
+    // `working_bits - k` of them. That is never fewer than `bits`.
+    let result_bits = (working_bits as i64 - k).unsigned_abs();
+    Ok(round_fixed_point(&sum, result_bits, digits, radix))
+}
+
+// Pi in fixed point with `bits` bits after the binary point, from Machin's formula,
+// `pi = 16 atan(1/5) - 4 atan(1/239)`.
+fn pi_fixed(bits: u64, maybe_interrupt: Option<&Interrupt>) -> Result<BigInt, MathExecutionError> {
+    let one = BigInt::one();
+    let atan_1_5 = inverse_tangent_series(&one, &BigInt::from(5), bits, false, maybe_interrupt)?;
+    let atan_1_239 =
+        inverse_tangent_series(&one, &BigInt::from(239), bits, false, maybe_interrupt)?;
+    Ok(atan_1_5 * 16 - atan_1_239 * 4)
+}
+
+/// Pi multiplied by `multiple`, to `precision` digits after the radix point. `tau` is `pi(2, ...)`.
+pub fn pi(
+    multiple: u32,
+    precision: u8,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigRational, MathExecutionError> {
+    let digits = u32::from(precision) + 1;
+    let bits = bits_for_digits(digits, radix) + GUARD_BITS;
+    let value = pi_fixed(bits, maybe_interrupt)? * multiple;
+    Ok(round_fixed_point(&value, bits, digits, radix))
+}
+
+// Converts a fixed point angle in radians to a result in radians or degrees with `digits` digits
+// after the radix point.
+fn round_angle(
+    radians: BigInt,
+    in_degrees: bool,
+    bits: u64,
+    digits: u32,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigRational, MathExecutionError> {
+    let angle = if in_degrees {
+        ((radians * 180) << bits) / pi_fixed(bits, maybe_interrupt)?
+    } else {
+        radians
+    };
+    Ok(round_fixed_point(&angle, bits, digits, radix))
+}
 
-        guess_ratio
+// `(sin(angle), cos(angle))` in fixed point with `bits` bits after the binary point. The angle is
+// in degrees if `in_degrees` is set and in radians otherwise.
+fn sine_cosine_fixed(
+    angle: &BigRational,
+    in_degrees: bool,
+    bits: u64,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<(BigInt, BigInt), MathExecutionError> {
+    // Converting from degrees and removing whole turns both multiply the error in pi by about the
+    // size of the angle, so pi needs as many more bits as the angle's integer part has.
+    let extra_bits = angle.abs().to_integer().bits() + 2;
+    let working_bits = bits + extra_bits;
+    let pi = pi_fixed(working_bits, maybe_interrupt)?;
+    let x = if in_degrees {
+        &pi * angle.numer() / (angle.denom() * 180)
+    } else {
+        (angle.numer() << working_bits) / angle.denom()
     };
+    // Bring the angle within pi of 0 so that the Taylor series converge quickly.
+    let two_pi = &pi * 2;
+    let x = &x - (&x + &pi).div_floor(&two_pi) * &two_pi;
+
+    // Each term is `x^n / n!`. They alternate between the cosine series, for even `n`, and the
+    // sine series, for odd `n`, with every other term of each being subtracted.
+    let mut sine = BigInt::zero();
+    let mut cosine = BigInt::zero();
+    let mut term = BigInt::one() << working_bits;
+    let mut n = 0u32;
+    while !term.is_zero() {
+        if let Some(interrupt) = maybe_interrupt {
+            interrupt.check()?;
+        }
+        match n % 4 {
+            0 => cosine += &term,
+            1 => sine += &term,
+            2 => cosine -= &term,
+            _ => sine -= &term,
+        }
+        n += 1;
+        term = ((term * &x) >> working_bits) / n;
+    }
+    Ok((sine >> extra_bits, cosine >> extra_bits))
+}
+
+// The sine of an angle in degrees if it is rational, which is only the case for multiples of 30
+// degrees that aren't also odd multiples of 60 degrees.
+fn exact_sine_degrees(degrees: &BigRational) -> Option<BigRational> {
+    if !degrees.is_integer() {
+        return None;
+    }
+    let half = BigRational::new(BigInt::one(), BigInt::from(2));
+    match degrees
+        .to_integer()
+        .mod_floor(&BigInt::from(360))
+        .to_u32()?
+    {
+        0 | 180 => Some(BigRational::zero()),
+        90 => Some(BigRational::one()),
+        270 => Some(-BigRational::one()),
+        30 | 150 => Some(half),
+        210 | 330 => Some(-half),
+        _ => None,
+    }
+}
+
+// The tangent of an angle in degrees if it is rational, which is only the case for multiples of 45
+// degrees. The tangent of an odd multiple of 90 degrees is undefined, so those also give `None`.
+fn exact_tangent_degrees(degrees: &BigRational) -> Option<BigRational> {
+    if !degrees.is_integer() {
+        return None;
+    }
+    match degrees
+        .to_integer()
+        .mod_floor(&BigInt::from(180))
+        .to_u32()?
+    {
+        0 => Some(BigRational::zero()),
+        45 => Some(BigRational::one()),
+        135 => Some(-BigRational::one()),
+        _ => None,
+    }
+}
 
-    // Step 3.4: Newton's method
+/// Whether the tangent of the angle is undefined, which is only possible for angles in degrees,
+/// since an odd multiple of `pi / 2` radians is never rational.
+pub fn is_tangent_undefined(angle: &BigRational, in_degrees: bool) -> bool {
+    let ninety = BigRational::from_integer(BigInt::from(90));
+    in_degrees && ((angle - &ninety) / (ninety * BigInt::from(2))).is_integer()
+}
+
+/// The sine of `angle`, which is in degrees if `in_degrees` is set and in radians otherwise, to
+/// `precision` digits after the radix point. Angles whose sine is rational give exact results.
+pub fn sine(
+    angle: &BigRational,
+    in_degrees: bool,
+    precision: u8,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigRational, MathExecutionError> {
+    // 0 radians is the only angle in radians with a rational sine, and it's also 0 degrees.
+    if in_degrees || angle.is_zero() {
+        if let Some(value) = exact_sine_degrees(angle) {
+            return Ok(value);
+        }
+    }
+    let digits = u32::from(precision) + 1;
+    let bits = bits_for_digits(digits, radix) + GUARD_BITS;
+    let (sine, _) = sine_cosine_fixed(angle, in_degrees, bits, maybe_interrupt)?;
+    Ok(round_fixed_point(&sine, bits, digits, radix))
+}
+
+/// The cosine of `angle`, like `sine`.
+pub fn cosine(
+    angle: &BigRational,
+    in_degrees: bool,
+    precision: u8,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigRational, MathExecutionError> {
+    if in_degrees || angle.is_zero() {
+        let complement = BigRational::from_integer(BigInt::from(90)) - angle;
+        if let Some(value) = exact_sine_degrees(&complement) {
+            return Ok(value);
+        }
+    }
+    let digits = u32::from(precision) + 1;
+    let bits = bits_for_digits(digits, radix) + GUARD_BITS;
+    let (_, cosine) = sine_cosine_fixed(angle, in_degrees, bits, maybe_interrupt)?;
+    Ok(round_fixed_point(&cosine, bits, digits, radix))
+}
+
+/// The tangent of `angle`, like `sine`. The tangent must not be undefined (see
+/// `is_tangent_undefined`).
+pub fn tangent(
+    angle: &BigRational,
+    in_degrees: bool,
+    precision: u8,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigRational, MathExecutionError> {
+    if in_degrees || angle.is_zero() {
+        if let Some(value) = exact_tangent_degrees(angle) {
+            return Ok(value);
+        }
+    }
+    let digits = u32::from(precision) + 1;
+    let bits = bits_for_digits(digits, radix) + GUARD_BITS;
+    let mut working_bits = bits;
     loop {
-        let prev_x = x.clone();
-        x = next_x(x);
-        let error = (&x - prev_x).abs();
-        if error <= max_error {
-            break;
+        let (sine, cosine) = sine_cosine_fixed(angle, in_degrees, working_bits, maybe_interrupt)?;
+        // Dividing by the cosine multiplies its error by about `1 / cos^2`, so when the cosine is
+        // small, it needs twice as many more bits as it has leading zeros after the binary point.
+        // Those leading zeros don't depend on how many bits are used, so one retry is enough.
+        let leading_zeros = working_bits.saturating_sub(cosine.bits());
+        let needed_bits = bits + 2 * leading_zeros;
+        if working_bits >= needed_bits {
+            let tangent = (sine << working_bits) / cosine;
+            return Ok(round_fixed_point(&tangent, working_bits, digits, radix));
         }
+        working_bits = needed_bits;
+    }
+}
+
+// `atan2(y, x)`, the angle from the positive x axis to the point `(x, y)`, in fixed point radians
+// with `bits` bits after the binary point. `x` and `y` can't both be 0.
+fn arctangent_fixed(
+    y: &BigInt,
+    x: &BigInt,
+    bits: u64,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigInt, MathExecutionError> {
+    let one = BigInt::one() << bits;
+    // The series only converges for ratios up to 1, so other points are rotated by a multiple of
+    // 90 degrees to bring them within 45 degrees of the positive x axis. The argument is then
+    // halved twice with `atan(z) = 2 atan(z / (1 + sqrt(1 + z^2)))` so that the series converges
+    // quickly.
+    let (ratio, rotation) = if y.abs() > x.abs() {
+        let half_pi: BigInt = pi_fixed(bits, maybe_interrupt)? / 2;
+        let rotation = if y.is_positive() { half_pi } else { -half_pi };
+        ((-x << bits) / y, rotation)
+    } else if x.is_negative() {
+        let pi = pi_fixed(bits, maybe_interrupt)?;
+        let rotation = if y.is_negative() { -pi } else { pi };
+        ((y << bits) / x, rotation)
+    } else {
+        ((y << bits) / x, BigInt::zero())
+    };
+    let mut z = ratio;
+    for _ in 0..2 {
+        let root = ((((&z * &z) >> bits) + &one) << bits).sqrt();
+        z = (z << bits) / (&one + root);
     }
+    Ok(inverse_tangent_series(&z, &one, bits, false, maybe_interrupt)? * 4 + rotation)
+}
 
-    Ok(x)
+// The arcsine of a value in degrees if it is rational, which is only the case for 0, 1/2, 1, and
+// their negations.
+fn exact_arcsine_degrees(value: &BigRational) -> Option<BigRational> {
+    let degrees = if value.is_zero() {
+        0
+    } else if value.abs().is_one() {
+        90
+    } else if value.abs() == BigRational::new(BigInt::one(), BigInt::from(2)) {
+        30
+    } else {
+        return None;
+    };
+    let degrees = BigRational::from_integer(BigInt::from(degrees));
+    Some(if value.is_negative() {
+        -degrees
+    } else {
+        degrees
+    })
+}
+
+// The arctangent of a value in degrees if it is rational, which is only the case for 0, 1, and -1.
+fn exact_arctangent_degrees(value: &BigRational) -> Option<BigRational> {
+    let degrees = if value.is_zero() {
+        0
+    } else if value.abs().is_one() {
+        45
+    } else {
+        return None;
+    };
+    let degrees = BigRational::from_integer(BigInt::from(degrees));
+    Some(if value.is_negative() {
+        -degrees
+    } else {
+        degrees
+    })
+}
+
+// `sqrt(1 - value^2)` and `value` in fixed point with `bits` bits after the binary point. These
+// are the coordinates of the point on the unit circle whose sine is `value`, for finding its angle.
+fn unit_circle_point_fixed(value: &BigRational, bits: u64) -> (BigInt, BigInt) {
+    let (numer, denom) = (value.numer(), value.denom());
+    let denom_squared = denom * denom;
+    let x = (((&denom_squared - numer * numer) << (2 * bits)) / denom_squared).sqrt();
+    let y = (numer << bits) / denom;
+    (x, y)
+}
+
+/// The arcsine of `value`, which must be between -1 and 1, to `precision` digits after the radix
+/// point. The result is in degrees if `in_degrees` is set and in radians otherwise. Results that
+/// are rational are exact.
+pub fn arcsine(
+    value: &BigRational,
+    in_degrees: bool,
+    precision: u8,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigRational, MathExecutionError> {
+    if let Some(degrees) = exact_arcsine_degrees(value) {
+        if in_degrees || degrees.is_zero() {
+            return Ok(degrees);
+        }
+    }
+    let digits = u32::from(precision) + 1;
+    let bits = bits_for_digits(digits, radix) + GUARD_BITS;
+    let (x, y) = unit_circle_point_fixed(value, bits);
+    let radians = arctangent_fixed(&y, &x, bits, maybe_interrupt)?;
+    round_angle(radians, in_degrees, bits, digits, radix, maybe_interrupt)
+}
+
+/// The arccosine of `value`, like `arcsine`.
+pub fn arccosine(
+    value: &BigRational,
+    in_degrees: bool,
+    precision: u8,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigRational, MathExecutionError> {
+    if let Some(degrees) = exact_arcsine_degrees(value) {
+        let degrees = BigRational::from_integer(BigInt::from(90)) - degrees;
+        if in_degrees || degrees.is_zero() {
+            return Ok(degrees);
+        }
+    }
+    let digits = u32::from(precision) + 1;
+    let bits = bits_for_digits(digits, radix) + GUARD_BITS;
+    let (x, y) = unit_circle_point_fixed(value, bits);
+    let radians = arctangent_fixed(&x, &y, bits, maybe_interrupt)?;
+    round_angle(radians, in_degrees, bits, digits, radix, maybe_interrupt)
+}
+
+/// `atan2(y, x)`, the angle from the positive x axis to the point `(x, y)`, to `precision` digits
+/// after the radix point. It is between -180 and 180 degrees, and is in degrees if `in_degrees` is
+/// set and in radians otherwise. `x` and `y` can't both be 0. Results that are rational are exact.
+/// `atan(z)` is `atan2(z, 1)`.
+pub fn arctangent(
+    y: &BigRational,
+    x: &BigRational,
+    in_degrees: bool,
+    precision: u8,
+    radix: u8,
+    maybe_interrupt: Option<&Interrupt>,
+) -> Result<BigRational, MathExecutionError> {
+    if in_degrees || y.is_zero() {
+        let ninety = BigRational::from_integer(BigInt::from(90));
+        let maybe_degrees = if x.is_zero() {
+            Some(if y.is_negative() { -ninety } else { ninety })
+        } else {
+            // Points on the other side of the y axis are half a turn away from the angle that the
+            // arctangent of their ratio gives.
+            let half_turn = &ninety * BigInt::from(2);
+            exact_arctangent_degrees(&(y / x)).map(|degrees| {
+                if !x.is_negative() {
+                    degrees
+                } else if y.is_negative() {
+                    degrees - half_turn
+                } else {
+                    degrees + half_turn
+                }
+            })
+        };
+        if let Some(degrees) = maybe_degrees {
+            if in_degrees || degrees.is_zero() {
+                return Ok(degrees);
+            }
+        }
+    }
+    let digits = u32::from(precision) + 1;
+    let bits = bits_for_digits(digits, radix) + GUARD_BITS;
+    // Scaling both coordinates by the same positive amount doesn't change the angle, so they can be
+    // given a common denominator and then passed as integers.
+    let y_scaled = y.numer() * x.denom();
+    let x_scaled = x.numer() * y.denom();
+    let radians = arctangent_fixed(&y_scaled, &x_scaled, bits, maybe_interrupt)?;
+    round_angle(radians, in_degrees, bits, digits, radix, maybe_interrupt)
 }
 
 #[cfg(test)]
 mod operation_tests {
     use crate::{
-        operations::make_decimal_string,
+        operations::{
+            decimal_exponent, make_base64_string, make_bases_string, make_decimal_string,
+            make_duration_string, make_engineering_string, make_fraction_string,
+            make_mixed_number_string, make_scientific_string, parse_base64, parse_decimal,
+            DecimalStringCache, DecimalStringKey, DECIMAL_STRING_CACHE_CAPACITY,
+        },
         syntax_tree::SyntaxTree,
         token::{ParsedInput, Tokenizer},
-        Args,
+        AngleUnit, Args, CloseParens, ErrorAlert, FractionStyle, GroupSeparator, Notation,
     };
+    use num::{bigint::BigInt, rational::BigRational, BigUint};
 
     fn evaluate_to_string(
         input: &str,
@@ -212,12 +1544,34 @@ mod operation_tests {
             input: None,
             alternate_screen: false,
             no_db: true,
+            profile: None,
             convert_to_radix: Some(result_radix),
             precision,
             extra_precision: 0,
             fractional: false,
+            max_denominator: None,
+            factored: false,
+            fraction_style: FractionStyle::Improper,
             commas,
+            group_size: 3,
+            group_separator: GroupSeparator::Comma,
+            bases: false,
+            decimal_comma: false,
+            sci_above: None,
+            sci_below: None,
+            notation: Notation::Auto,
             upper,
+            recall_commands: false,
+            close_parens: CloseParens::Offer,
+            strict: false,
+            no_si_suffixes: false,
+            milli_suffix: false,
+            angle_unit: AngleUnit::Radians,
+            error_alert: ErrorAlert::None,
+            screen_reader: false,
+            rates_file: None,
+            json: false,
+            command: None,
         };
         let tokenizer = Tokenizer::new();
         let tokens = match tokenizer.tokenize(input, parse_radix).unwrap() {
@@ -226,7 +1580,110 @@ mod operation_tests {
         };
         let st = SyntaxTree::new(tokens.into()).unwrap();
         let result = st.execute(None, None, None, &args).unwrap();
-        make_decimal_string(&result, result_radix, precision, commas, upper)
+        make_decimal_string(
+            &result.re(),
+            result_radix,
+            precision,
+            commas.then_some(3),
+            upper,
+        )
+    }
+
+    #[test]
+    fn parsed_decimals() {
+        let ratio = |numer: i64, denom: i64| BigRational::new(numer.into(), denom.into());
+        assert_eq!(parse_decimal("0.92"), Some(ratio(92, 100)));
+        assert_eq!(parse_decimal("149.5"), Some(ratio(299, 2)));
+        assert_eq!(parse_decimal("12"), Some(ratio(12, 1)));
+        assert_eq!(parse_decimal(".5"), Some(ratio(1, 2)));
+        assert_eq!(parse_decimal("-2."), Some(ratio(-2, 1)));
+        assert_eq!(parse_decimal("1.5e-7"), Some(ratio(15, 100_000_000)));
+        assert_eq!(parse_decimal("2E3"), Some(ratio(2_000, 1)));
+        assert_eq!(parse_decimal(""), None);
+        assert_eq!(parse_decimal("."), None);
+        assert_eq!(parse_decimal("1,5"), None);
+        assert_eq!(parse_decimal("rate"), None);
+        assert_eq!(parse_decimal("1e999999999"), None);
+    }
+
+    #[test]
+    fn bit_fields() {
+        let hex = |input: &str| evaluate_to_string(input, 16, 16, 0, false, false);
+        assert_eq!(hex("bits(abcd, f, 8)"), "ab");
+        assert_eq!(hex("bits(abcd, 3, 3)"), "1");
+        assert_eq!(hex("bits(-1, 7, 0)"), "ff");
+        assert_eq!(hex("bits(-100, 1f, 0)"), "ffffff00");
+        assert_eq!(hex("setbits(abcd, f, 8, 12)"), "12cd");
+        assert_eq!(hex("setbits(0, 7, 4, f)"), "f0");
+        assert_eq!(hex("setbits(-1, 3, 0, 0)"), "-10");
+    }
+
+    #[test]
+    fn duration_strings() {
+        let seconds = |numer: i64, denom: i64| BigRational::new(numer.into(), denom.into());
+        assert_eq!(make_duration_string(&seconds(5400, 1), 5), "1:30:00");
+        assert_eq!(make_duration_string(&seconds(100, 3), 5), "0:00:33.33333");
+        assert_eq!(make_duration_string(&seconds(-90061, 1), 5), "-25:01:01");
+        assert_eq!(make_duration_string(&seconds(3, 2), 2), "0:00:01.5");
+        assert_eq!(
+            make_duration_string(&seconds(59_999_999, 1_000_000), 5),
+            "0:01:00.00000"
+        );
+        assert_eq!(
+            make_duration_string(&seconds(-1, 1_000_000), 5),
+            "0:00:00.00000"
+        );
+    }
+
+    #[test]
+    fn fraction_strings() {
+        let ratio = |numer: i64, denom: i64| BigRational::new(numer.into(), denom.into());
+        assert_eq!(make_fraction_string(&ratio(5, 1), true), "5");
+        assert_eq!(make_fraction_string(&ratio(70, 24), false), "35/12");
+        assert_eq!(make_fraction_string(&ratio(70, 24), true), "35/(2^2*3)");
+        assert_eq!(make_fraction_string(&ratio(-1, 7), true), "-1/7");
+        assert_eq!(make_fraction_string(&ratio(1, 8), true), "1/(2^3)");
+        assert_eq!(
+            make_fraction_string(&ratio(1, 20_011 * 6), true),
+            "1/(2*3*20011)"
+        );
+
+        assert_eq!(make_mixed_number_string(&ratio(7, 3), false), "2 1/3");
+        assert_eq!(make_mixed_number_string(&ratio(-7, 3), false), "-2 1/3");
+        assert_eq!(make_mixed_number_string(&ratio(-2, 3), false), "-2/3");
+        assert_eq!(make_mixed_number_string(&ratio(6, 3), false), "2");
+        assert_eq!(make_mixed_number_string(&ratio(35, 8), true), "4 3/(2^3)");
+    }
+
+    #[test]
+    fn scientific_strings() {
+        let ratio = |numer: i64, denom: i64| BigRational::new(numer.into(), denom.into());
+        assert_eq!(decimal_exponent(&ratio(1, 1)), 0);
+        assert_eq!(decimal_exponent(&ratio(99, 1)), 1);
+        assert_eq!(decimal_exponent(&ratio(100, 1)), 2);
+        assert_eq!(decimal_exponent(&ratio(-1, 1000)), -3);
+        assert_eq!(decimal_exponent(&ratio(1, 999)), -3);
+        assert_eq!(decimal_exponent(&ratio(999, 1000)), -1);
+        assert_eq!(make_scientific_string(&ratio(15, 100_000_000), 5), "1.5e-7");
+        assert_eq!(
+            make_scientific_string(&ratio(-123_456_789, 1), 3),
+            "-1.235e8"
+        );
+        assert_eq!(make_scientific_string(&ratio(1, 3), 2), "3.33e-1");
+        assert_eq!(make_scientific_string(&ratio(9_999_999, 1), 2), "1.00e7");
+        assert_eq!(make_scientific_string(&ratio(0, 1), 5), "0");
+        assert_eq!(
+            make_engineering_string(&ratio(15, 100_000_000), 5),
+            "150e-9"
+        );
+        assert_eq!(
+            make_engineering_string(&ratio(-123_456_789, 1), 3),
+            "-123.457e6"
+        );
+        assert_eq!(make_engineering_string(&ratio(1, 3), 2), "333.33e-3");
+        assert_eq!(make_engineering_string(&ratio(999_999_999, 1), 2), "1.00e9");
+        assert_eq!(make_engineering_string(&ratio(1234, 1), 5), "1.234e3");
+        assert_eq!(make_engineering_string(&ratio(0, 1), 5), "0");
     }
 
     #[test]
@@ -313,6 +1770,85 @@ mod operation_tests {
         assert_eq!(result, "12,345,678,901".to_string());
     }
 
+    #[test]
+    fn group_sizes() {
+        let value = BigRational::from_integer(BigInt::from(0xdeadbeefu32));
+        assert_eq!(
+            make_decimal_string(&value, 16, 0, Some(4), false),
+            "dead,beef"
+        );
+        assert_eq!(
+            make_decimal_string(&value, 2, 0, Some(4), false),
+            "1101,1110,1010,1101,1011,1110,1110,1111"
+        );
+        assert_eq!(
+            make_decimal_string(&value, 10, 0, Some(5), false),
+            "37359,28559"
+        );
+        let value = BigRational::new(BigInt::from(-1234567), BigInt::from(10));
+        assert_eq!(
+            make_decimal_string(&value, 10, 1, Some(1), false),
+            "-1,2,3,4,5,6.7"
+        );
+        // Fractional digits are only grouped outside of decimal.
+        let value = BigRational::new(BigInt::from(0x1234567), BigInt::from(0x100000));
+        assert_eq!(
+            make_decimal_string(&value, 16, 5, Some(2), false),
+            "12.34,56,7"
+        );
+        assert_eq!(
+            make_decimal_string(&value, 2, 10, Some(4), false),
+            "1,0010.0011,0100,01"
+        );
+        let value = BigRational::new(BigInt::from(1234567), BigInt::from(1_000_000));
+        assert_eq!(
+            make_decimal_string(&value, 10, 6, Some(3), false),
+            "1.234567"
+        );
+    }
+
+    #[test]
+    fn bases() {
+        let ratio = |numer: i64, denom: i64| BigRational::new(numer.into(), denom.into());
+        assert_eq!(
+            make_bases_string(&ratio(255, 1), 5, None, false),
+            "255  0xff  0o377  0b11111111"
+        );
+        assert_eq!(
+            make_bases_string(&ratio(-3, 2), 5, None, true),
+            "-1.5  -0x1.8  -0o1.4  -0b1.1"
+        );
+        assert_eq!(
+            make_bases_string(&ratio(0xbeef, 1), 0, Some(4), true),
+            "4,8879  0xBEEF  0o13,7357  0b1011,1110,1110,1111"
+        );
+        assert_eq!(
+            make_bases_string(&ratio(0, 1), 5, None, false),
+            "0  0x0  0o0  0b0"
+        );
+    }
+
+    #[test]
+    fn base64() {
+        for (value, encoded) in [
+            (0u64, "AA=="),
+            (1, "AQ=="),
+            (65537, "AQAB"),
+            (0x4d616e, "TWFu"),
+            (0xfffe, "//4="),
+            (0x0123456789, "ASNFZ4k="),
+        ] {
+            let value = BigUint::from(value);
+            assert_eq!(make_base64_string(&value), encoded);
+            assert_eq!(parse_base64(encoded), Some(value.clone()));
+            assert_eq!(parse_base64(encoded.trim_end_matches('=')), Some(value));
+        }
+        assert_eq!(parse_base64("AAEC"), Some(BigUint::from(0x0102u32)));
+        assert_eq!(parse_base64(""), None);
+        assert_eq!(parse_base64("A"), None);
+        assert_eq!(parse_base64("AB-C"), None);
+    }
+
     #[test]
     fn round_down() {
         let result = evaluate_to_string("0.0000049", 10, 10, 5, false, false);
@@ -343,6 +1879,53 @@ mod operation_tests {
         assert_eq!(result, "-1".to_string());
     }
 
+    #[test]
+    fn cached_formatting_options() {
+        let value = BigRational::new(BigInt::from(-1234567), BigInt::from(16));
+        assert_eq!(
+            make_decimal_string(&value, 10, 2, Some(3), false),
+            "-77,160.44"
+        );
+        assert_eq!(make_decimal_string(&value, 10, 2, None, false), "-77160.44");
+        assert_eq!(
+            make_decimal_string(&value, 16, 2, Some(3), true),
+            "-12,D68.7"
+        );
+        assert_eq!(
+            make_decimal_string(&value, 16, 2, Some(3), false),
+            "-12,d68.7"
+        );
+        assert_eq!(
+            make_decimal_string(&value, 10, 2, Some(3), false),
+            "-77,160.44"
+        );
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_used() {
+        let key = |i: usize| DecimalStringKey {
+            value: BigRational::from(BigInt::from(i)),
+            radix: 10,
+            precision: 5,
+            maybe_group_size: None,
+            upper: false,
+        };
+        let mut cache = DecimalStringCache::default();
+        for i in 0..DECIMAL_STRING_CACHE_CAPACITY {
+            cache.get_or_insert_with(key(i), || i.to_string());
+        }
+        // Using the oldest entry makes it the most recently used, so the next oldest gets evicted
+        // instead.
+        assert_eq!(cache.get_or_insert_with(key(0), || panic!()), "0");
+        cache.get_or_insert_with(key(DECIMAL_STRING_CACHE_CAPACITY), || "new".to_string());
+        assert_eq!(cache.get_or_insert_with(key(0), || panic!()), "0");
+        assert_eq!(
+            cache.get_or_insert_with(key(1), || "again".to_string()),
+            "again"
+        );
+        assert_eq!(cache.strings.len(), DECIMAL_STRING_CACHE_CAPACITY);
+    }
+
     #[test]
     fn root_integer_result_1() {
         let result = evaluate_to_string("100^(1/2)", 10, 10, 10, false, false);
@@ -355,6 +1938,90 @@ mod operation_tests {
         assert_eq!(result, "3".to_string());
     }
 
+    #[test]
+    fn root_rational_result() {
+        let result = evaluate_to_string("0.0625^(1/2)", 10, 10, 10, false, false);
+        assert_eq!(result, "0.25".to_string());
+    }
+
+    #[test]
+    fn root_high_precision() {
+        let result = evaluate_to_string("2^(1/2)", 10, 10, 50, false, false);
+        assert_eq!(
+            result,
+            "1.41421356237309504880168872420969807856967187537695".to_string()
+        );
+    }
+
+    #[test]
+    fn root_of_large_value() {
+        let result = evaluate_to_string("(10^100 + 10^60)^(1/2)", 10, 10, 50, false, false);
+        assert_eq!(
+            result,
+            "100000000000000000000000000000000000000004999999999.\
+             99999999999999999999999999999987500000000000000000"
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn square_root() {
+        let decimal =
+            |input: &str, precision: u8| evaluate_to_string(input, 10, 10, precision, false, false);
+        assert_eq!(decimal("sqrt 2", 20), "1.41421356237309504880");
+        assert_eq!(decimal("sqrt(2)", 5), "1.41421");
+        assert_eq!(decimal("sqrt 0.0625", 10), "0.25");
+        assert_eq!(decimal("sqrt(16/9) * 3", 10), "4");
+        assert_eq!(decimal("sqrt 0", 10), "0");
+        assert_eq!(decimal("-sqrt 9", 10), "-3");
+    }
+
+    #[test]
+    fn logarithms_and_exponentials() {
+        let decimal =
+            |input: &str, precision: u8| evaluate_to_string(input, 10, 10, precision, false, false);
+        assert_eq!(decimal("ln(2)", 30), "0.693147180559945309417232121458");
+        assert_eq!(decimal("ln(0.5)", 10), "-0.6931471806");
+        assert_eq!(decimal("ln(1)", 10), "0");
+        assert_eq!(decimal("exp(1)", 30), "2.718281828459045235360287471353");
+        assert_eq!(decimal("exp(-7.25)", 15), "0.000710174388843");
+        assert_eq!(
+            decimal("exp(100)", 2),
+            "26881171418161354484126255515800135873611118.77"
+        );
+        assert_eq!(decimal("exp(0)", 10), "1");
+        assert_eq!(decimal("log10(12345.678)", 20), "4.09151494550920127466");
+        assert_eq!(decimal("log10(1000)", 10), "3");
+        assert_eq!(decimal("log10(0.01)", 10), "-2");
+        assert_eq!(decimal("log2(3)", 10), "1.5849625007");
+        assert_eq!(decimal("log2(1/64)", 10), "-6");
+        assert_eq!(
+            evaluate_to_string("exp(1)", 16, 16, 10, false, false),
+            "2.b7e151628b"
+        );
+    }
+
+    #[test]
+    fn trigonometry() {
+        let decimal =
+            |input: &str, precision: u8| evaluate_to_string(input, 10, 10, precision, false, false);
+        assert_eq!(decimal("sin(1)", 30), "0.841470984807896506652502321630");
+        assert_eq!(decimal("cos(1)", 10), "0.5403023059");
+        assert_eq!(decimal("tan(1.5707963)", 10), "37320539.5867165413");
+        assert_eq!(decimal("sin(-10000000000000000000000)", 10), "0.8522008498");
+        assert_eq!(decimal("cos(0)", 10), "1");
+        assert_eq!(decimal("sin(150 deg)", 10), "0.5");
+        assert_eq!(decimal("cos(-120 deg)", 10), "-0.5");
+        assert_eq!(decimal("tan(-45 deg)", 10), "-1");
+        assert_eq!(decimal("sin(17 deg)", 10), "0.2923717047");
+        assert_eq!(decimal("asin(0.999999)", 20), "1.56938211311467236747");
+        assert_eq!(decimal("acos(-1)", 30), "3.141592653589793238462643383280");
+        assert_eq!(decimal("acos(1)", 10), "0");
+        assert_eq!(decimal("atan(7/3)", 10), "1.1659045405");
+        assert_eq!(decimal("atan2(-2, -5)", 10), "-2.7610862765");
+        assert_eq!(decimal("atan2(0, 3)", 10), "0");
+    }
+
     #[test]
     fn exponentiate_integer_result_1() {
         let result = evaluate_to_string("9^10", 10, 10, 10, false, false);
@@ -0,0 +1,64 @@
+//! Drawing graphs out of braille characters, for the `/plot` command. Each character holds a grid
+//! of dots two wide and four tall, so a graph gets twice as many points across as it has columns
+//! and four times as many rows of resolution as it has lines.
+
+/// The braille character with no dots raised. Each dot adds one of `DOT_BITS` to it.
+const BRAILLE_BLANK: u32 = 0x2800;
+
+// The bit for each dot in a braille character, indexed by `[column][row]`, with row 0 at the top.
+const DOT_BITS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+/// Draws a graph of `samples` that is `lines` characters tall, returning each line, top first.
+/// Each sample is drawn as one dot, in its own column of dots, so the lines are half as many
+/// characters long as there are samples (rounded up). Samples are scaled so that `low` is at the
+/// very bottom of the graph and `high` is at the very top. Samples that are `None`, such as where
+/// the function being graphed is undefined, or that are outside of that range are left out.
+pub fn braille_plot(samples: &[Option<f64>], low: f64, high: f64, lines: usize) -> Vec<String> {
+    let columns = samples.len().div_ceil(2);
+    let mut cells = vec![vec![0u32; columns]; lines];
+    let dot_rows = lines * 4;
+    if dot_rows > 0 {
+        for (index, sample) in samples.iter().enumerate() {
+            let Some(y) = sample else {
+                continue;
+            };
+            if !(low..=high).contains(y) {
+                continue;
+            }
+            let fraction = if high > low {
+                (high - y) / (high - low)
+            } else {
+                0.5
+            };
+            let dot_row = (fraction * (dot_rows - 1) as f64).round() as usize;
+            cells[dot_row / 4][index / 2] |= DOT_BITS[index % 2][dot_row % 4];
+        }
+    }
+    cells
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|bits| char::from_u32(BRAILLE_BLANK + bits).unwrap())
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod plot_tests {
+    use crate::plot::braille_plot;
+
+    #[test]
+    fn plots() {
+        // A line rising from the bottom left to the top right.
+        let samples: Vec<Option<f64>> = (0..8).map(|x| Some(f64::from(x))).collect();
+        assert_eq!(braille_plot(&samples, 0.0, 7.0, 2), vec!["⠀⠀⡠⠊", "⡠⠊⠀⠀"]);
+
+        // Missing and out of range samples leave gaps.
+        let samples = [Some(0.0), None, Some(5.0), Some(1.0)];
+        assert_eq!(braille_plot(&samples, 0.0, 1.0, 1), vec!["⡀⠈"]);
+
+        assert_eq!(braille_plot(&[Some(3.0)], 3.0, 3.0, 1), vec!["⠄"]);
+        assert_eq!(braille_plot(&[], 0.0, 1.0, 1), vec![""]);
+    }
+}
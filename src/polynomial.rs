@@ -0,0 +1,372 @@
+//! Finding the roots of polynomials, for the `/roots` command. Polynomials are given as their
+//! coefficients, starting with the constant term. Roots that are rational are found exactly. Other
+//! real roots are approximated to a requested number of digits, and complex roots are only
+//! counted.
+
+use num::{bigint::BigInt, rational::BigRational, Integer, One, Signed, Zero};
+
+/// The highest degree of polynomial that roots will be found for.
+pub const MAX_DEGREE: usize = 20;
+
+// The rational root search tries every divisor of the first and last coefficients, so it is skipped
+// when either of them is larger than this.
+const MAX_RATIONAL_ROOT_SEARCH: u64 = 1_000_000_000_000;
+
+/// The distinct roots of a polynomial.
+#[derive(Debug, Default)]
+pub struct Roots {
+    /// Roots that are rational, in increasing order.
+    pub exact: Vec<BigRational>,
+    /// Approximations of roots that are real but irrational, in increasing order.
+    pub approximate: Vec<BigRational>,
+    /// The number of distinct roots that aren't real.
+    pub complex_count: usize,
+}
+
+// Removes leading zero coefficients, so that the last coefficient is the one for the highest power.
+fn trim(mut polynomial: Vec<BigRational>) -> Vec<BigRational> {
+    while polynomial.last().is_some_and(|c| c.is_zero()) {
+        polynomial.pop();
+    }
+    polynomial
+}
+
+// The degree of a trimmed polynomial. The zero polynomial is given degree 0.
+fn degree(polynomial: &[BigRational]) -> usize {
+    polynomial.len().saturating_sub(1)
+}
+
+fn evaluate(polynomial: &[BigRational], x: &BigRational) -> BigRational {
+    polynomial
+        .iter()
+        .rev()
+        .fold(BigRational::zero(), |total, coefficient| {
+            total * x + coefficient
+        })
+}
+
+fn derivative(polynomial: &[BigRational]) -> Vec<BigRational> {
+    polynomial
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(power, coefficient)| coefficient * BigInt::from(power))
+        .collect()
+}
+
+// Divides `dividend` by the non-zero polynomial `divisor`, returning the quotient and remainder.
+fn divide(
+    dividend: &[BigRational],
+    divisor: &[BigRational],
+) -> (Vec<BigRational>, Vec<BigRational>) {
+    let mut remainder = trim(dividend.to_vec());
+    if remainder.len() < divisor.len() {
+        return (Vec::new(), remainder);
+    }
+    let leading = divisor.last().unwrap();
+    let mut quotient = vec![BigRational::zero(); remainder.len() - divisor.len() + 1];
+    while !remainder.is_empty() && remainder.len() >= divisor.len() {
+        let shift = remainder.len() - divisor.len();
+        let factor = remainder.last().unwrap() / leading;
+        for (index, coefficient) in divisor.iter().enumerate() {
+            remainder[shift + index] -= &factor * coefficient;
+        }
+        quotient[shift] = factor;
+        // The leading coefficient is now zero.
+        remainder.pop();
+        remainder = trim(remainder);
+    }
+    (trim(quotient), remainder)
+}
+
+fn gcd(a: &[BigRational], b: &[BigRational]) -> Vec<BigRational> {
+    let mut a = trim(a.to_vec());
+    let mut b = trim(b.to_vec());
+    while !b.is_empty() {
+        let (_, remainder) = divide(&a, &b);
+        a = b;
+        b = remainder;
+    }
+    a
+}
+
+// Removes repeated factors, so that every root of the result is a simple root.
+fn square_free(polynomial: &[BigRational]) -> Vec<BigRational> {
+    let common = gcd(polynomial, &derivative(polynomial));
+    divide(polynomial, &common).0
+}
+
+// All of the positive divisors of `n`, which must be positive.
+fn divisors(n: u64) -> Vec<u64> {
+    let mut divisors = Vec::new();
+    let mut candidate = 1;
+    while candidate * candidate <= n {
+        if n.is_multiple_of(candidate) {
+            divisors.push(candidate);
+            if candidate * candidate != n {
+                divisors.push(n / candidate);
+            }
+        }
+        candidate += 1;
+    }
+    divisors
+}
+
+// Finds the rational roots of a polynomial whose constant term isn't zero, using the rational root
+// theorem, and divides them out. Returns the roots that were found and the remaining polynomial.
+fn remove_rational_roots(polynomial: Vec<BigRational>) -> (Vec<BigRational>, Vec<BigRational>) {
+    // Scale the coefficients to integers, since the theorem only applies to those.
+    let denominator_lcm = polynomial.iter().fold(BigInt::one(), |lcm, coefficient| {
+        lcm.lcm(coefficient.denom())
+    });
+    let integers: Vec<BigInt> = polynomial
+        .iter()
+        .map(|coefficient| (coefficient * &denominator_lcm).to_integer())
+        .collect();
+    let bound = BigInt::from(MAX_RATIONAL_ROOT_SEARCH);
+    let (constant, leading) = (integers[0].abs(), integers.last().unwrap().abs());
+    if constant > bound || leading > bound {
+        return (Vec::new(), polynomial);
+    }
+    let to_u64 = |n: &BigInt| u64::try_from(n).unwrap();
+
+    let mut roots = Vec::new();
+    let mut remaining = polynomial;
+    for numerator in divisors(to_u64(&constant)) {
+        for denominator in divisors(to_u64(&leading)) {
+            for sign in [1, -1] {
+                let candidate =
+                    BigRational::new(BigInt::from(numerator) * sign, BigInt::from(denominator));
+                if !evaluate(&remaining, &candidate).is_zero() {
+                    continue;
+                }
+                // Divide out the root as many times as it repeats.
+                let factor = vec![-candidate.clone(), BigRational::one()];
+                while degree(&remaining) > 0 && evaluate(&remaining, &candidate).is_zero() {
+                    remaining = divide(&remaining, &factor).0;
+                }
+                roots.push(candidate);
+            }
+        }
+    }
+    (roots, remaining)
+}
+
+// Narrows down the root of `polynomial` between `low` and `high`, where the polynomial has opposite
+// signs at the two ends, until the interval is no wider than `tolerance`.
+fn bisect(
+    polynomial: &[BigRational],
+    mut low: BigRational,
+    mut high: BigRational,
+    tolerance: &BigRational,
+) -> BigRational {
+    let low_sign = evaluate(polynomial, &low).signum();
+    while &high - &low > *tolerance {
+        let middle = (&low + &high) / BigInt::from(2);
+        let sign = evaluate(polynomial, &middle).signum();
+        if sign.is_zero() {
+            return middle;
+        } else if sign == low_sign {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    (low + high) / BigInt::from(2)
+}
+
+// Approximates the real roots of a polynomial with only simple roots, in increasing order. The
+// polynomial is monotonic between consecutive roots of its derivative, so each of those intervals
+// holds at most one root.
+fn approximate_real_roots(polynomial: &[BigRational], tolerance: &BigRational) -> Vec<BigRational> {
+    match degree(polynomial) {
+        0 => return Vec::new(),
+        1 => return vec![-&polynomial[0] / &polynomial[1]],
+        _ => {}
+    }
+    // Every root is smaller in magnitude than this (Cauchy's bound).
+    let leading = polynomial.last().unwrap();
+    let bound = polynomial[..polynomial.len() - 1]
+        .iter()
+        .map(|coefficient| (coefficient / leading).abs())
+        .max()
+        .unwrap()
+        + BigRational::one();
+
+    let critical_points = approximate_real_roots(&square_free(&derivative(polynomial)), tolerance);
+    let mut endpoints = vec![-bound.clone()];
+    endpoints.extend(
+        critical_points
+            .into_iter()
+            .filter(|point| point.abs() < bound),
+    );
+    endpoints.push(bound);
+
+    let mut roots = Vec::new();
+    for pair in endpoints.windows(2) {
+        let (low, high) = (&pair[0], &pair[1]);
+        let (low_value, high_value) = (evaluate(polynomial, low), evaluate(polynomial, high));
+        if low_value.is_zero() {
+            roots.push(low.clone());
+        } else if !high_value.is_zero() && low_value.signum() != high_value.signum() {
+            roots.push(bisect(polynomial, low.clone(), high.clone(), tolerance));
+        }
+    }
+    roots
+}
+
+/// Finds the distinct roots of the polynomial with the given coefficients, starting with the
+/// constant term. Irrational roots are approximated to within `tolerance`. Fails if the polynomial
+/// is a constant or has a degree above `MAX_DEGREE`.
+pub fn find_roots(coefficients: &[BigRational], tolerance: &BigRational) -> Result<Roots, String> {
+    let mut polynomial = trim(coefficients.to_vec());
+    if polynomial.len() <= 1 {
+        return Err(match polynomial.first() {
+            None => "Every value is a root of 0".to_string(),
+            Some(constant) => format!("{} is a constant, so it has no roots", constant),
+        });
+    }
+    if degree(&polynomial) > MAX_DEGREE {
+        return Err(format!(
+            "Polynomials of degree {} are too large, the maximum degree is {}",
+            degree(&polynomial),
+            MAX_DEGREE
+        ));
+    }
+
+    let mut roots = Roots::default();
+    if polynomial[0].is_zero() {
+        roots.exact.push(BigRational::zero());
+        while polynomial[0].is_zero() {
+            polynomial.remove(0);
+        }
+    }
+    let (rational_roots, remaining) = remove_rational_roots(polynomial);
+    roots.exact.extend(rational_roots);
+    roots.exact.sort();
+
+    let remaining = square_free(&remaining);
+    roots.approximate = approximate_real_roots(&remaining, tolerance);
+    roots.complex_count = degree(&remaining) - roots.approximate.len();
+    Ok(roots)
+}
+
+/// Recovers the coefficients of a polynomial, starting with the constant term, from its values at
+/// 0, 1, 2, and so on. Returns `None` unless the values are those of a polynomial whose degree is
+/// low enough that at least two of the values were not needed to determine it, which serves as a
+/// check that they really do come from a polynomial.
+pub fn polynomial_from_values(values: &[BigRational]) -> Option<Vec<BigRational>> {
+    // Forward differences: `differences[k]` is the k-th difference at 0.
+    let mut differences = Vec::new();
+    let mut row = values.to_vec();
+    while !row.is_empty() {
+        if row.iter().all(|value| value.is_zero()) {
+            break;
+        }
+        if row.len() < 2 {
+            return None;
+        }
+        differences.push(row[0].clone());
+        row = row.windows(2).map(|pair| &pair[1] - &pair[0]).collect();
+    }
+    if differences.len() + 2 > values.len() {
+        return None;
+    }
+
+    // p(x) is the sum of differences[k] * (x choose k). Expand each binomial coefficient, which is
+    // x (x - 1) ... (x - k + 1) / k!, into powers of x.
+    let mut coefficients = vec![BigRational::zero(); differences.len()];
+    let mut falling_factorial = vec![BigRational::one()];
+    let mut factorial = BigInt::one();
+    for (k, difference) in differences.iter().enumerate() {
+        if k > 0 {
+            factorial *= BigInt::from(k);
+            // Multiply the falling factorial by (x - (k - 1)).
+            let shift = BigRational::from_integer(BigInt::from(k - 1));
+            let mut next = vec![BigRational::zero(); falling_factorial.len() + 1];
+            for (power, coefficient) in falling_factorial.iter().enumerate() {
+                next[power + 1] += coefficient;
+                next[power] -= coefficient * &shift;
+            }
+            falling_factorial = next;
+        }
+        for (power, coefficient) in falling_factorial.iter().enumerate() {
+            coefficients[power] += coefficient * difference / &factorial;
+        }
+    }
+    Some(trim(coefficients))
+}
+
+#[cfg(test)]
+mod polynomial_tests {
+    use crate::polynomial::{find_roots, polynomial_from_values};
+    use num::{bigint::BigInt, rational::BigRational, Signed};
+
+    fn ratio(numer: i64, denom: i64) -> BigRational {
+        BigRational::new(BigInt::from(numer), BigInt::from(denom))
+    }
+
+    fn integers(values: &[i64]) -> Vec<BigRational> {
+        values.iter().map(|value| ratio(*value, 1)).collect()
+    }
+
+    #[test]
+    fn rational_roots() {
+        let tolerance = ratio(1, 1_000_000);
+        // x^2 - 3x + 2
+        let roots = find_roots(&integers(&[2, -3, 1]), &tolerance).unwrap();
+        assert_eq!(roots.exact, integers(&[1, 2]));
+        assert!(roots.approximate.is_empty());
+        assert_eq!(roots.complex_count, 0);
+
+        // 6x^3 - 5x^2 - 2x + 1 = (x - 1)(2x + 1)(3x - 1)
+        let roots = find_roots(&integers(&[1, -2, -5, 6]), &tolerance).unwrap();
+        assert_eq!(roots.exact, vec![ratio(-1, 2), ratio(1, 3), ratio(1, 1)]);
+
+        // x^3 (x - 1)^2
+        let roots = find_roots(&integers(&[0, 0, 0, 1, -2, 1]), &tolerance).unwrap();
+        assert_eq!(roots.exact, integers(&[0, 1]));
+
+        assert!(find_roots(&integers(&[5]), &tolerance).is_err());
+        assert!(find_roots(&integers(&[0, 0]), &tolerance).is_err());
+    }
+
+    #[test]
+    fn irrational_and_complex_roots() {
+        let tolerance = ratio(1, 1_000_000_000);
+        let close_to = |value: &BigRational, expected: BigRational| {
+            (value - expected).abs() < ratio(1, 100_000_000)
+        };
+        // (x^2 - 2)^2 (x^2 + 1) (x + 3)
+        let roots = find_roots(&integers(&[12, 4, 0, 0, -9, -3, 3, 1]), &tolerance).unwrap();
+        assert_eq!(roots.exact, integers(&[-3]));
+        assert_eq!(roots.approximate.len(), 2);
+        assert!(close_to(
+            &roots.approximate[0],
+            ratio(-141_421_356, 100_000_000)
+        ));
+        assert!(close_to(
+            &roots.approximate[1],
+            ratio(141_421_356, 100_000_000)
+        ));
+        assert_eq!(roots.complex_count, 2);
+    }
+
+    #[test]
+    fn from_values() {
+        // x^2 - 3x + 2 at 0 through 4.
+        let values = integers(&[2, 0, 0, 2, 6]);
+        assert_eq!(polynomial_from_values(&values), Some(integers(&[2, -3, 1])));
+        // Too few values to be sure that this is a quadratic.
+        assert_eq!(polynomial_from_values(&values[..3]), None);
+        // 2^x isn't a polynomial.
+        assert_eq!(
+            polynomial_from_values(&integers(&[1, 2, 4, 8, 16, 32])),
+            None
+        );
+        assert_eq!(
+            polynomial_from_values(&integers(&[0, 0, 0])),
+            Some(Vec::new())
+        );
+    }
+}
@@ -4,10 +4,14 @@ use std::{
     hash::{Hash, Hasher},
 };
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 // These structs are intended to make it easy to point out user errors by literally pointing at
 // them.
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Position {
     pub start: usize,
     pub width: usize,
@@ -24,7 +28,7 @@ impl Position {
     }
 
     pub fn from_between(pos1: Position, pos2: Position) -> Position {
-        let mut points = vec![
+        let mut points = [
             pos1.start,
             pos1.start + pos1.width,
             pos2.start,
@@ -37,7 +41,8 @@ impl Position {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Positioned<T>
 where
     T: Clone + fmt::Debug,
@@ -146,6 +151,7 @@ impl Positioned<String> {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MaybePositioned<T>
 where
     T: Clone + fmt::Debug,
@@ -0,0 +1,109 @@
+//! What an interactive session had that would be lost if it crashed: the input line that was being
+//! edited and the variables that were set. This is written out when bcalc panics so that the next
+//! session can offer to restore it. See `saved_data::recovery_path` for where it is kept.
+
+use crate::{value::Value, variable::Variable};
+use std::{fs, io, path::Path, str::FromStr};
+
+// Precedes the input line in the recovery file. Every other line is a variable.
+const INPUT_PREFIX: &str = "input ";
+
+#[derive(Clone, Debug, Default)]
+pub struct Recovery {
+    pub input: String,
+    pub variables: Vec<Variable>,
+}
+
+impl Recovery {
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty() && self.variables.is_empty()
+    }
+
+    /// The text that the recovery is stored as. The first line is the input line, and each
+    /// following line is a variable's name and its exact value, like `$x 3/4`.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}{}\n", INPUT_PREFIX, self.input);
+        for variable in &self.variables {
+            text.push_str(&format!("{} {}\n", variable.name, variable.value));
+        }
+        text
+    }
+
+    /// Reads the text written by `to_text`. Returns `None` if it isn't in that form.
+    pub fn from_text(text: &str) -> Option<Recovery> {
+        let mut lines = text.lines();
+        let input = lines.next()?.strip_prefix(INPUT_PREFIX)?.to_string();
+        let variables = lines
+            .map(|line| {
+                let (name, value) = line.split_once(' ')?;
+                if !name.starts_with('$') {
+                    return None;
+                }
+                Some(Variable {
+                    name: name.into(),
+                    value: Value::from_str(value).ok()?,
+                })
+            })
+            .collect::<Option<Vec<Variable>>>()?;
+        Some(Recovery { input, variables })
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    /// Reads the recovery at `path` and removes the file so that it is only offered once. Returns
+    /// `Ok(None)` if there is no file, or if it can't be understood.
+    pub fn take(path: &Path) -> io::Result<Option<Recovery>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        fs::remove_file(path)?;
+        Ok(Recovery::from_text(&text))
+    }
+}
+
+#[cfg(test)]
+mod recovery_tests {
+    use crate::{recovery::Recovery, value::Value, variable::Variable};
+    use num::{BigInt, BigRational};
+
+    #[test]
+    fn round_trip() {
+        let recovery = Recovery {
+            input: "2 * (3 + ".to_string(),
+            variables: vec![
+                Variable {
+                    name: "$x".into(),
+                    value: BigRational::new(BigInt::from(-3), BigInt::from(4)).into(),
+                },
+                Variable {
+                    name: "$proj.rate".into(),
+                    value: BigInt::from(12).into(),
+                },
+                Variable {
+                    name: "$z".into(),
+                    value: Value::complex(
+                        BigRational::from_integer(BigInt::from(1)),
+                        BigRational::new(BigInt::from(-1), BigInt::from(2)),
+                    ),
+                },
+            ],
+        };
+        let text = recovery.to_text();
+        assert_eq!(text, "input 2 * (3 + \n$x -3/4\n$proj.rate 12\n$z 1-1/2i\n");
+        let restored = Recovery::from_text(&text).unwrap();
+        assert_eq!(restored.input, recovery.input);
+        assert_eq!(restored.variables.len(), 3);
+        assert_eq!(&*restored.variables[0].name, "$x");
+        assert_eq!(restored.variables[0].value, recovery.variables[0].value);
+        assert_eq!(restored.variables[1].value, recovery.variables[1].value);
+        assert_eq!(restored.variables[2].value, recovery.variables[2].value);
+
+        assert!(Recovery::from_text("").is_none());
+        assert!(Recovery::from_text("$x 1").is_none());
+        assert!(Recovery::from_text("input 1\n$x one").is_none());
+    }
+}
@@ -0,0 +1,106 @@
+//! Numbered registers that results can be stored in and recalled from with hotkeys. Unlike
+//! variables, registers are never saved and can't be referred to in expressions. Recalling one
+//! just types its value into the input line.
+
+use crate::value::Value;
+use num::{rational::BigRational, Signed};
+
+/// Registers are numbered from 0 up to, but not including, this.
+pub const REGISTER_COUNT: usize = 10;
+
+#[derive(Clone, Debug, Default)]
+pub struct Registers {
+    values: [Option<Value>; REGISTER_COUNT],
+}
+
+impl Registers {
+    pub fn new() -> Registers {
+        Registers::default()
+    }
+
+    /// Stores the value in the register, replacing whatever it held. Does nothing if `index` isn't
+    /// less than `REGISTER_COUNT`.
+    pub fn store(&mut self, index: usize, value: Value) {
+        if let Some(register) = self.values.get_mut(index) {
+            *register = Some(value);
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Value> {
+        self.values.get(index)?.as_ref()
+    }
+}
+
+/// Writes the value out so that it can be typed into an input that is parsed with `radix` without
+/// losing any precision. Non-integers are written as a division, and anything that isn't a lone
+/// non-negative integer is parenthesized so that it can be used anywhere in an expression.
+pub fn input_text(value: &Value, radix: u8) -> String {
+    match value {
+        Value::Real(value) => real_input_text(value, radix),
+        Value::Complex { re, im } => format!(
+            "({}{}{}*i)",
+            real_input_text(re, radix),
+            if im.is_negative() { "-" } else { "+" },
+            real_input_text(&im.abs(), radix)
+        ),
+        Value::Interval { low, high } => format!(
+            "interval({}, {})",
+            real_input_text(low, radix),
+            real_input_text(high, radix)
+        ),
+        Value::Surd {
+            coefficient,
+            radicand,
+            ..
+        } => format!(
+            "({}*sqrt({}))",
+            real_input_text(coefficient, radix),
+            radicand.to_str_radix(u32::from(radix))
+        ),
+    }
+}
+
+fn real_input_text(value: &BigRational, radix: u8) -> String {
+    let radix = u32::from(radix);
+    let numer = value.numer().to_str_radix(radix);
+    if value.is_integer() {
+        if value.is_negative() {
+            format!("({})", numer)
+        } else {
+            numer
+        }
+    } else {
+        format!("({}/{})", numer, value.denom().to_str_radix(radix))
+    }
+}
+
+#[cfg(test)]
+mod register_tests {
+    use crate::{
+        registers::{input_text, Registers, REGISTER_COUNT},
+        value::Value,
+    };
+    use num::rational::BigRational;
+
+    #[test]
+    fn registers() {
+        let ratio =
+            |numer: i64, denom: i64| Value::Real(BigRational::new(numer.into(), denom.into()));
+        let mut registers = Registers::new();
+        assert_eq!(registers.get(3), None);
+        registers.store(3, ratio(1, 3));
+        assert_eq!(registers.get(3), Some(&ratio(1, 3)));
+        registers.store(REGISTER_COUNT, ratio(1, 1));
+        assert_eq!(registers.get(REGISTER_COUNT), None);
+
+        assert_eq!(input_text(&ratio(42, 1), 10), "42");
+        assert_eq!(input_text(&ratio(-42, 1), 10), "(-42)");
+        assert_eq!(input_text(&ratio(2, 6), 10), "(1/3)");
+        assert_eq!(input_text(&ratio(-255, 16), 16), "(-ff/10)");
+        let complex = Value::complex(
+            BigRational::from_integer(2.into()),
+            BigRational::new((-1).into(), 2.into()),
+        );
+        assert_eq!(input_text(&complex, 10), "(2-(1/2)*i)");
+    }
+}
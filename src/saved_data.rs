@@ -1,20 +1,41 @@
-use crate::error::CalculatorDatabaseInconsistencyError;
+use crate::error::{CalculatorDatabaseInconsistencyError, InternalCalculatorError};
+use crate::storage::{SavedSetting, Storage};
+use crate::units::{Dimension, Unit};
+use crate::value::Value;
 use crate::variable::Variable;
 use num::{bigint::BigInt, rational::BigRational};
-use rusqlite::{self, named_params, OptionalExtension, Transaction};
-use std::{env, fs::create_dir, io, path::Path};
+use rusqlite::{self, named_params, OpenFlags, OptionalExtension, Row, Savepoint, Transaction};
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, create_dir},
+    io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 const DATA_ROOT_DIR_ENV_VAR_NAME: &str = "_B_UTIL_DATA_DIR";
 const DATA_DIR_NAME: &str = "bcalc";
 const HISTORY_DB_NAME: &str = "saved_data.sqlite";
+const PROFILE_DIR_NAME: &str = "profiles";
+const DEFAULT_PROFILE_NAME: &str = "default";
+const PROFILE_DB_EXTENSION: &str = "sqlite";
+const RECOVERY_EXTENSION: &str = "recovery";
+// Appended to the file name of a damaged database when it is moved aside to be rebuilt.
+const DAMAGED_DB_SUFFIX: &str = ".damaged";
+// Appended by SQLite to the database's file name to get the name of its rollback journal.
+const JOURNAL_SUFFIX: &str = "-journal";
 
-const CURRENT_DB_VERSION: i64 = 1;
+const CURRENT_DB_VERSION: i64 = MIGRATIONS.len() as i64;
 const MINIUM_COMPATIBLE_DB_VERSION: i64 = 1;
 
 const DEFAULT_MAX_HISTORY_SIZE: usize = 100;
 
 const VARIABLE_STORAGE_RADIX: u32 = 10;
 
+// The number of prior values kept for each variable. Once exceeded, the oldest is discarded.
+const MAX_VARIABLE_VERSIONS: i64 = 20;
+
 #[repr(i64)]
 enum MetaInt {
     // The current version of the database schema.
@@ -24,6 +45,63 @@ enum MetaInt {
     MinimumVersion = 2,
     // The maximum size of the input history before we further items are evicted.
     MaxHistorySize = 3,
+    // The remaining keys store settings that were changed via commands so that they can be
+    // restored in future sessions. They are documented by the corresponding `SavedSetting`
+    // variants. Unlike the keys above, these are only present once the setting has been changed.
+    Radix = 4,
+    ConvertToRadix = 5,
+    Precision = 6,
+    ExtraPrecision = 7,
+    Fractional = 8,
+    Commas = 9,
+    Upper = 10,
+    GroupSize = 11,
+    MaxDenominator = 12,
+    Factored = 13,
+    RecallCommands = 14,
+    ErrorAlert = 15,
+    DecimalComma = 16,
+    SciAbove = 17,
+    SciBelow = 18,
+    CloseParens = 19,
+    Strict = 20,
+    AngleUnit = 21,
+    NoSiSuffixes = 22,
+    FractionStyle = 23,
+    Notation = 24,
+    GroupSeparator = 25,
+    Bases = 26,
+    MilliSuffix = 27,
+}
+
+// The `meta_int` key that each `SavedSetting` is stored under.
+fn setting_key(setting: SavedSetting) -> MetaInt {
+    match setting {
+        SavedSetting::Radix => MetaInt::Radix,
+        SavedSetting::ConvertToRadix => MetaInt::ConvertToRadix,
+        SavedSetting::Precision => MetaInt::Precision,
+        SavedSetting::ExtraPrecision => MetaInt::ExtraPrecision,
+        SavedSetting::Fractional => MetaInt::Fractional,
+        SavedSetting::Commas => MetaInt::Commas,
+        SavedSetting::Upper => MetaInt::Upper,
+        SavedSetting::GroupSize => MetaInt::GroupSize,
+        SavedSetting::DecimalComma => MetaInt::DecimalComma,
+        SavedSetting::MaxDenominator => MetaInt::MaxDenominator,
+        SavedSetting::Factored => MetaInt::Factored,
+        SavedSetting::SciAbove => MetaInt::SciAbove,
+        SavedSetting::SciBelow => MetaInt::SciBelow,
+        SavedSetting::RecallCommands => MetaInt::RecallCommands,
+        SavedSetting::ErrorAlert => MetaInt::ErrorAlert,
+        SavedSetting::CloseParens => MetaInt::CloseParens,
+        SavedSetting::Strict => MetaInt::Strict,
+        SavedSetting::AngleUnit => MetaInt::AngleUnit,
+        SavedSetting::NoSiSuffixes => MetaInt::NoSiSuffixes,
+        SavedSetting::FractionStyle => MetaInt::FractionStyle,
+        SavedSetting::Notation => MetaInt::Notation,
+        SavedSetting::GroupSeparator => MetaInt::GroupSeparator,
+        SavedSetting::Bases => MetaInt::Bases,
+        SavedSetting::MilliSuffix => MetaInt::MilliSuffix,
+    }
 }
 
 #[repr(i64)]
@@ -36,6 +114,26 @@ enum InputHistoryTag {
     Back = 2,
 }
 
+/// An item from the input history, as returned by `SavedData::get_prev_input_history`.
+pub struct InputHistoryEntry {
+    pub input: String,
+    /// What the input evaluated to, if it was stored via `SavedData::set_input_history_result`.
+    pub maybe_result: Option<String>,
+    /// The id of the session that the input was entered in. `None` if the input was stored before
+    /// sessions were recorded.
+    pub maybe_session: Option<i64>,
+}
+
+/// Describes one session (one run of bcalc that stored at least one input), as returned by
+/// `SavedData::get_sessions`.
+pub struct SessionSummary {
+    pub id: i64,
+    /// When the session started, formatted as local time.
+    pub started: String,
+    /// The number of inputs from the session that are still in the input history.
+    pub input_count: i64,
+}
+
 /// We will store/load several types of data to/from the file system using SQLite. Some of it is not
 /// super conducive to being stored in table format, so our data structures may be a little awkward.
 ///
@@ -46,7 +144,7 @@ enum InputHistoryTag {
 ///
 /// # Table `meta_int`
 /// This table contains key/value metadata where the value is an integer. The possible keys are
-/// enumerated and documented by `MetaInt`.
+/// enumerated and documented by `MetaInt`. This includes settings saved via `SavedSetting`.
 ///
 /// # Table `input_history`
 /// This table effectively stores an ordered list of calculator inputs in order to allow the
@@ -73,6 +171,15 @@ enum InputHistoryTag {
 /// An `id` within this same table indicating the previous row in the list (i.e. the input that was
 /// inserted just before this one). May be `NULL` if this is the last item in the list.
 ///
+/// ### `result`
+/// The output that the input evaluated to, as it was displayed. `NULL` for commands, for inputs
+/// that failed to evaluate, and for inputs stored before this column was added in database
+/// version 4.
+///
+/// ### `session`
+/// The `id` of the row in `sessions` for the session that the input was entered in. `NULL` for
+/// inputs stored before this column was added in database version 6.
+///
 /// # Table `input_history_tags`
 /// This table contains key/value data mapping "tags" to row `id`s in `input_history`. The possible
 /// keys are enumerated and documented by `InputHistoryTag`.
@@ -105,11 +212,106 @@ enum InputHistoryTag {
 /// stored here. This column will be defined with `ON DELETE CASCADE` so that when the row that it
 /// references is evicted from `input_history`, the corresponding rows in this table will also be
 /// removed.
+///
+/// ### `pinned`
+/// `1` if the variable has been pinned, otherwise `0`. Pinned variables are not removed when the
+/// row referenced by `last_used_by` is evicted. Instead, before the eviction happens, their
+/// `last_used_by` value is moved to the new back of the `input_history` list.
+/// This column was added in database version 2.
+///
+/// ### `format`
+/// The display format attached to the variable with `/varformat`, as text that
+/// `VariableFormat::parse` accepts, or `NULL` if there is none. Like `pinned`, this is kept when
+/// the variable is set to a new value.
+/// This column was added in database version 8.
+///
+/// ### `imaginary_numer` and `imaginary_denom`
+/// The imaginary part of the value stored by the variable, stored as text like `numer` and `denom`
+/// are. Both are `NULL` if the value is real.
+/// These columns were added in database version 10.
+///
+/// ### `high_numer` and `high_denom`
+/// If the value is an interval, `numer` and `denom` store its low end and these store its high end,
+/// as text like `numer` and `denom` are. Both are `NULL` if the value isn't an interval.
+/// These columns were added in database version 11.
+///
+/// ### `surd_numer`, `surd_denom`, and `radicand`
+/// If the value is a surd, `surd_numer` and `surd_denom` store its coefficient and `radicand` its
+/// radicand, as text like `numer` and `denom` are, and `numer` and `denom` store its approximation.
+/// All three are `NULL` if the value isn't a surd.
+/// These columns were added in database version 12.
+///
+/// # Table `favorite_inputs`
+/// This table stores the inputs that the user has marked as favorites, ordered by `id`. It is
+/// deliberately separate from `input_history` so that favorites are never evicted. `input` is
+/// `UNIQUE` so that an input can only be a favorite once.
+/// This table was added in database version 3.
+///
+/// # Table `variable_versions`
+/// This table stores the values that variables in `variable_history` had before they were last
+/// set, with the same `name`, `numer`, `denom`, `imaginary_numer`, `imaginary_denom`, `high_numer`,
+/// `high_denom`, `surd_numer`, `surd_denom`, and `radicand` columns.
+/// Higher `id`s are more recent. At most `MAX_VARIABLE_VERSIONS` rows are kept per variable. There
+/// is no foreign key linking these rows to `variable_history`, so they must be removed explicitly
+/// whenever a variable is removed.
+/// This table was added in database version 5, its imaginary columns in version 10, its high
+/// columns in version 11, and its surd columns in version 12.
+///
+/// # Table `sessions`
+/// Each row represents one run of bcalc that stored at least one input. Rows are created when a
+/// session's first input is stored and removed once all of the session's inputs have been evicted
+/// from `input_history`.
+///
+/// ## Columns
+/// ### `id`
+/// A `rowid` alias, referenced by `input_history`'s `session` column.
+///
+/// ### `started_at`
+/// When the session's first input was stored, as a Unix timestamp in seconds.
+/// This table was added in database version 6.
+///
+/// # Table `custom_units`
+/// This table stores the units defined with `/defunit`. `name` is defined with
+/// `PRIMARY KEY ON CONFLICT REPLACE`, so redefining a unit replaces it. `numer` and `denom` hold the
+/// unit's factor as text, as in `variable_history`. `dimension` holds the exponent of each base
+/// quantity (see `Dimension::exponents`), separated by commas. Units are listed in the order that
+/// they were defined, which is the order of their `rowid`s.
+/// This table was added in database version 7.
+///
+/// # Table `snippets`
+/// This table stores the expression templates saved with `/snippet save`. `name` is defined with
+/// `PRIMARY KEY ON CONFLICT REPLACE`, so saving a snippet under an existing name replaces it.
+/// `template` is the text that `/snippet insert` puts into the input line. Snippets are listed in
+/// the order that they were saved, which is the order of their `rowid`s.
+/// This table was added in database version 9.
+///
+/// # Damage
+/// When the database is opened, it is checked for damage, including inconsistencies in the
+/// `input_history` list. If any is found, the damaged file is moved aside (see `DAMAGED_DB_SUFFIX`)
+/// and a fresh database is created in its place, into which we copy whatever can still be read.
+///
+/// # Profiles
+/// Each profile gets an entirely separate database file with all of the tables above. The default
+/// profile (used when no profile name is given or the name is `DEFAULT_PROFILE_NAME`) is stored in
+/// `HISTORY_DB_NAME`. Named profiles are stored in the `PROFILE_DIR_NAME` directory.
 pub struct SavedData {
+    // Groups of changes that need to happen together are made inside savepoints rather than
+    // transactions, since savepoints can also be used inside of the transaction that
+    // `Storage::begin_batch` starts.
     connection: rusqlite::Connection,
+    // The name of the profile that this database belongs to, or `None` for the default profile.
+    maybe_profile: Option<String>,
     // This will hold the next `id` in the `input_history` table that we should retrieve when
     // `get_prev_input_history` is called. If it holds `None`, there is no history to load.
     input_history_position: Option<i64>,
+    // If the database was rebuilt when it was opened, this describes what happened so that the
+    // user can be told about it. See `take_rebuild_report`.
+    maybe_rebuild_report: Option<String>,
+    // The id of the row in the `sessions` table for this session. It is `None` until the first
+    // input of the session is stored.
+    maybe_session_id: Option<i64>,
+    // Whether the transaction started by `Storage::begin_batch` is still open.
+    in_batch: bool,
 }
 
 impl SavedData {
@@ -120,96 +322,101 @@ impl SavedData {
     /// When the database is opened, we remember the index of the input history that is currently
     /// at the front of the history list (the most recent item inserted). This allows us to iterate
     /// through the history without getting the items that we inserted during our session.
-    pub fn open() -> Result<Option<SavedData>, Box<dyn std::error::Error>> {
-        let data_dir_path_str = match env::var(DATA_ROOT_DIR_ENV_VAR_NAME) {
-            Ok(s) => s,
-            Err(env::VarError::NotPresent) => return Ok(None),
-            Err(e) => return Err(e.into()),
+    /// If `maybe_profile` is `Some`, the database for that profile is opened instead of the default
+    /// one. The caller should validate the profile name via `validate_profile_name` in advance.
+    pub fn open(
+        maybe_profile: Option<&str>,
+    ) -> Result<Option<SavedData>, Box<dyn std::error::Error>> {
+        let maybe_profile = maybe_profile.filter(|p| *p != DEFAULT_PROFILE_NAME);
+        let db_path = match db_path(maybe_profile)? {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let mut saved_data = SavedData {
+            connection: rusqlite::Connection::open(&db_path)?,
+            maybe_profile: maybe_profile.map(|p| p.to_string()),
+            input_history_position: None,
+            maybe_rebuild_report: None,
+            maybe_session_id: None,
+            in_batch: false,
         };
-        let data_dir_path = Path::new(&data_dir_path_str).join(DATA_DIR_NAME);
-        if let Err(e) = create_dir(data_dir_path.clone()) {
-            if e.kind() != io::ErrorKind::AlreadyExists {
-                return Err(e.into());
+        let maybe_damage = match initialize(&mut saved_data.connection) {
+            Ok(initial_front) => {
+                saved_data.input_history_position = initial_front;
+                find_damage(&saved_data.connection)?
             }
+            Err(e) => match e.downcast_ref::<rusqlite::Error>() {
+                Some(sqlite_error) if is_damage_error(sqlite_error) => {
+                    Some(sqlite_error.to_string())
+                }
+                _ => return Err(e),
+            },
+        };
+        if let Some(damage) = maybe_damage {
+            saved_data.rebuild(&db_path, &damage)?;
         }
-        let db_path = data_dir_path.join(HISTORY_DB_NAME);
-        let mut connection = rusqlite::Connection::open(db_path)?;
-        connection.execute("PRAGMA foreign_keys = ON;", ())?;
 
-        let transaction = connection.transaction()?;
+        Ok(Some(saved_data))
+    }
 
-        transaction.execute(
-            "CREATE TABLE IF NOT EXISTS meta_int(
-                key INTEGER PRIMARY KEY ASC,
-                value INTEGER NOT NULL
-            );",
-            (),
-        )?;
-        transaction.execute(
-            "INSERT OR IGNORE INTO meta_int (key, value) VALUES (:key, :value)",
-            named_params! {
-                ":key": MetaInt::MinimumVersion as i64,
-                ":value": MINIUM_COMPATIBLE_DB_VERSION,
-            },
-        )?;
-        let minimum_version: i64 = transaction.query_row(
-            "SELECT value FROM meta_int WHERE key=:key",
-            named_params! {
-                ":key": MetaInt::MinimumVersion as i64,
-            },
-            |row| row.get(0),
-        )?;
-        if minimum_version > CURRENT_DB_VERSION {
-            return Err(CalculatorDatabaseInconsistencyError::new(
-                "Database version is not compatible with executable version",
-            )
-            .into());
+    /// Opens a fresh database that is only kept in memory, so that tests don't need a data
+    /// directory.
+    #[cfg(test)]
+    pub(crate) fn open_in_memory() -> Result<SavedData, Box<dyn std::error::Error>> {
+        let mut connection = rusqlite::Connection::open_in_memory()?;
+        initialize(&mut connection)?;
+        Ok(SavedData {
+            connection,
+            maybe_profile: None,
+            input_history_position: None,
+            maybe_rebuild_report: None,
+            maybe_session_id: None,
+            in_batch: false,
+        })
+    }
+
+    /// If the database was found to be damaged and was rebuilt when it was opened, returns a
+    /// message for the user describing what happened. Subsequent calls return `None`.
+    pub fn take_rebuild_report(&mut self) -> Option<String> {
+        self.maybe_rebuild_report.take()
+    }
+
+    /// Moves the damaged database at `db_path` aside and replaces it with a fresh one, then copies
+    /// over whatever data can still be read from the damaged one.
+    fn rebuild(&mut self, db_path: &Path, damage: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut damaged_path = db_path.as_os_str().to_owned();
+        damaged_path.push(DAMAGED_DB_SUFFIX);
+        let damaged_path = PathBuf::from(damaged_path);
+
+        // The connection needs to be closed before the file can be safely moved. We replace it
+        // with a placeholder until the fresh database is created.
+        let old_connection = std::mem::replace(
+            &mut self.connection,
+            rusqlite::Connection::open_in_memory()?,
+        );
+        old_connection.close().map_err(|(_, e)| e)?;
+        fs::rename(db_path, &damaged_path)?;
+        // If the journal were left behind, SQLite would try to use it to roll back changes to the
+        // fresh database.
+        let mut journal_path = db_path.as_os_str().to_owned();
+        journal_path.push(JOURNAL_SUFFIX);
+        if Path::new(&journal_path).exists() {
+            let mut damaged_journal_path = damaged_path.as_os_str().to_owned();
+            damaged_journal_path.push(JOURNAL_SUFFIX);
+            fs::rename(&journal_path, &damaged_journal_path)?;
         }
-        transaction.execute(
-            "INSERT OR IGNORE INTO meta_int (key, value) VALUES (:key, :value)",
-            named_params! {
-                ":key": MetaInt::Version as i64,
-                ":value": CURRENT_DB_VERSION,
-            },
-        )?;
-        transaction.execute(
-            "INSERT OR IGNORE INTO meta_int (key, value) VALUES (:key, :value)",
-            named_params! {
-                ":key": MetaInt::MaxHistorySize as i64,
-                ":value": DEFAULT_MAX_HISTORY_SIZE,
-            },
-        )?;
 
-        transaction.execute(
-            "CREATE TABLE IF NOT EXISTS input_history(
-                id INTEGER PRIMARY KEY ASC,
-                input TEXT NOT NULL,
-                next REFERENCES input_history(id),
-                prev REFERENCES input_history(id)
-            );",
-            (),
-        )?;
+        self.connection = rusqlite::Connection::open(db_path)?;
+        initialize(&mut self.connection)?;
 
-        transaction.execute(
-            "CREATE TABLE IF NOT EXISTS input_history_tags(
-                key INTEGER PRIMARY KEY ASC,
-                value REFERENCES input_history(id)
-            );",
-            (),
-        )?;
-        transaction.execute(
-            "INSERT OR IGNORE INTO input_history_tags (key, value) VALUES (:key, NULL)",
-            named_params! {
-                ":key": InputHistoryTag::Front as i64,
-            },
-        )?;
-        transaction.execute(
-            "INSERT OR IGNORE INTO input_history_tags (key, value) VALUES (:key, NULL)",
-            named_params! {
-                ":key": InputHistoryTag::Back as i64,
-            },
-        )?;
-        let initial_front: Option<i64> = transaction.query_row(
+        // Salvaging is best-effort. If the damaged database can't even be opened, we still have a
+        // working, albeit empty, database.
+        if let Ok(damaged) =
+            rusqlite::Connection::open_with_flags(&damaged_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        {
+            self.salvage(&damaged)?;
+        }
+        self.input_history_position = self.connection.query_row(
             "SELECT value FROM input_history_tags WHERE key=:key",
             named_params! {
                 ":key": InputHistoryTag::Front as i64,
@@ -217,90 +424,255 @@ impl SavedData {
             |row| row.get(0),
         )?;
 
-        transaction.execute(
-            "CREATE TABLE IF NOT EXISTS variable_history(
-                name TEXT PRIMARY KEY ON CONFLICT REPLACE,
-                numer TEXT NOT NULL,
-                denom TEXT NOT NULL,
-                last_used_by NOT NULL REFERENCES input_history(id) ON DELETE CASCADE
-            );",
-            (),
-        )?;
-
-        transaction.commit()?;
-
-        Ok(Some(SavedData {
-            connection,
-            input_history_position: initial_front,
-        }))
+        self.maybe_rebuild_report = Some(format!(
+            concat!(
+                "Saved data was damaged ({}) and has been rebuilt from what could be recovered. ",
+                "The damaged database was moved to '{}'."
+            ),
+            damage,
+            damaged_path.display()
+        ));
+        Ok(())
     }
 
-    /// Adds the given input to the front of the input history list and updates metadata to maintain
-    /// the internal mechanisms of the list.
-    /// If this causes the history to exceed `MAX_HISTORY_SIZE`, items will be evicted from the
-    /// history until the expected maximum size is reached.
-    /// Returns the id of the history entry that was inserted.
-    pub fn add_to_input_history(&mut self, input: &str) -> Result<i64, Box<dyn std::error::Error>> {
-        let mut transaction = self.connection.transaction()?;
-        let maybe_orig_front: Option<i64> = transaction.query_row(
-            "SELECT value FROM input_history_tags WHERE key=:key",
+    /// Copies whatever can be read out of the `damaged` database into this one, which is expected
+    /// to be freshly created. Data that cannot be read is skipped.
+    fn salvage(
+        &mut self,
+        damaged: &rusqlite::Connection,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // Settings first, since they include the maximum history size.
+        let settings: Vec<(i64, i64)> = read_salvageable_rows(
+            damaged,
+            "SELECT key, value FROM meta_int WHERE key NOT IN (:version, :minimum_version)",
             named_params! {
-                ":key": InputHistoryTag::Front as i64,
+                ":version": MetaInt::Version as i64,
+                ":minimum_version": MetaInt::MinimumVersion as i64,
             },
-            |row| row.get(0),
-        )?;
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        );
+        for (key, value) in settings {
+            if key == MetaInt::MaxHistorySize as i64 && validate_max_history_size(value).is_err() {
+                continue;
+            }
+            self.connection.execute(
+                "INSERT OR REPLACE INTO meta_int (key, value) VALUES (:key, :value)",
+                named_params! {
+                    ":key": key,
+                    ":value": value,
+                },
+            )?;
+        }
 
-        // Insert the new row
-        transaction.execute(
-            "INSERT INTO input_history (input, next, prev) VALUES (:input, NULL, :prev)",
-            named_params! {
-                ":input": input,
-                ":prev": maybe_orig_front,
-            },
-        )?;
-        let added_input_id: i64 = transaction.last_insert_rowid();
-        // Update the front tag to point to the new front.
-        transaction.execute(
-            "UPDATE input_history_tags SET value=:tag_value WHERE key=:key",
-            named_params! {
-                ":key": InputHistoryTag::Front as i64,
-                ":tag_value": added_input_id,
-            },
-        )?;
+        // The linkage of the input history can't be trusted, but `id`s are assigned in increasing
+        // order, so they tell us the order that the inputs were added in. Anything that wouldn't
+        // fit in the history is dropped up front so that the `id`s we map to remain valid.
+        let max_history_size = usize::try_from(self.get_max_history_size()?)?;
+        let mut inputs: Vec<(i64, String, Option<String>)> = read_salvageable_rows(
+            damaged,
+            "SELECT id, input, result FROM input_history ORDER BY id ASC",
+            (),
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        );
+        if inputs.is_empty() {
+            // The `result` column may be what's missing.
+            inputs = read_salvageable_rows(
+                damaged,
+                "SELECT id, input FROM input_history ORDER BY id ASC",
+                (),
+                |row| Ok((row.get(0)?, row.get(1)?, None)),
+            );
+        }
+        let skip = inputs.len().saturating_sub(max_history_size);
+        let mut new_ids: HashMap<i64, i64> = HashMap::new();
+        let mut maybe_newest_id: Option<i64> = None;
+        for (old_id, input, maybe_result) in inputs.into_iter().skip(skip) {
+            let new_id = self.add_to_input_history(&input)?;
+            if let Some(result) = maybe_result {
+                self.set_input_history_result(new_id, &result)?;
+            }
+            new_ids.insert(old_id, new_id);
+            maybe_newest_id = Some(new_id);
+        }
 
-        match maybe_orig_front {
-            Some(orig_front) => {
-                // Update the old front to point to the new front.
-                transaction.execute(
-                    "UPDATE input_history SET next=:new_front WHERE id=:orig_front",
+        // `add_to_input_history` put all of the inputs into a new session. Replace it with the
+        // sessions that the inputs were originally entered in. Sessions keep their original ids.
+        self.maybe_session_id = None;
+        self.connection
+            .execute("UPDATE input_history SET session=NULL", ())?;
+        self.connection.execute("DELETE FROM sessions", ())?;
+        let sessions: Vec<(i64, i64)> =
+            read_salvageable_rows(damaged, "SELECT id, started_at FROM sessions", (), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            });
+        for (id, started_at) in sessions {
+            self.connection.execute(
+                "INSERT INTO sessions (id, started_at) VALUES (:id, :started_at)",
+                named_params! {
+                    ":id": id,
+                    ":started_at": started_at,
+                },
+            )?;
+        }
+        let input_sessions: Vec<(i64, i64)> = read_salvageable_rows(
+            damaged,
+            "SELECT id, session FROM input_history WHERE session IS NOT NULL",
+            (),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        );
+        for (old_id, session) in input_sessions {
+            if let Some(new_id) = new_ids.get(&old_id) {
+                self.connection.execute(
+                    "UPDATE input_history SET session=(SELECT id FROM sessions WHERE id=:session)
+                        WHERE id=:id",
                     named_params! {
-                        ":orig_front": orig_front,
-                        ":new_front": added_input_id,
+                        ":session": session,
+                        ":id": new_id,
                     },
                 )?;
             }
-            None => {
-                // The list was previously empty. We also need to update the back to point to the
-                // front.
-                transaction.execute(
-                    "UPDATE input_history_tags SET value=:tag_value WHERE key=:key",
+        }
+        self.connection.execute(
+            "DELETE FROM sessions WHERE id NOT IN
+                (SELECT session FROM input_history WHERE session IS NOT NULL)",
+            (),
+        )?;
+
+        // Variables have to reference an input. If we don't have the one that the variable
+        // referenced, we use the newest input instead. If we don't have any inputs, the variables
+        // can't be kept.
+        if let Some(newest_id) = maybe_newest_id {
+            let variables: Vec<(String, StoredValue, i64)> = read_salvageable_rows(
+                damaged,
+                "SELECT name, numer, denom, imaginary_numer, imaginary_denom, high_numer,
+                        high_denom, surd_numer, surd_denom, radicand, last_used_by
+                    FROM variable_history",
+                (),
+                |row| Ok((row.get(0)?, StoredValue::from_row(row, 1)?, row.get(10)?)),
+            );
+            for (name, stored, last_used_by) in variables {
+                let value = match parse_stored_variable_value(&name, &stored) {
+                    Ok(value) => value,
+                    Err(_) => continue,
+                };
+                let last_used_by = *new_ids.get(&last_used_by).unwrap_or(&newest_id);
+                self.set_variable(
+                    &Variable {
+                        name: name.into(),
+                        value,
+                    },
+                    last_used_by,
+                )?;
+            }
+            let pinned_names: Vec<String> = read_salvageable_rows(
+                damaged,
+                "SELECT name FROM variable_history WHERE pinned=1",
+                (),
+                |row| row.get(0),
+            );
+            for name in pinned_names {
+                self.set_variable_pinned(&name, true)?;
+            }
+            let formats: Vec<(String, String)> = read_salvageable_rows(
+                damaged,
+                "SELECT name, format FROM variable_history WHERE format IS NOT NULL",
+                (),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            );
+            for (name, format) in formats {
+                self.set_variable_format(&name, Some(&format))?;
+            }
+            let versions: Vec<(String, StoredValue)> = read_salvageable_rows(
+                damaged,
+                "SELECT name, numer, denom, imaginary_numer, imaginary_denom, high_numer,
+                        high_denom, surd_numer, surd_denom, radicand
+                    FROM variable_versions ORDER BY id ASC",
+                (),
+                |row| Ok((row.get(0)?, StoredValue::from_row(row, 1)?)),
+            );
+            for (name, stored) in versions {
+                if parse_stored_variable_value(&name, &stored).is_err() {
+                    continue;
+                }
+                self.connection.execute(
+                    "INSERT INTO variable_versions
+                            (name, numer, denom, imaginary_numer, imaginary_denom, high_numer,
+                                high_denom, surd_numer, surd_denom, radicand)
+                        SELECT :name, :numer, :denom, :imaginary_numer, :imaginary_denom,
+                            :high_numer, :high_denom, :surd_numer, :surd_denom, :radicand
+                        WHERE EXISTS (SELECT 1 FROM variable_history WHERE name=:name)",
                     named_params! {
-                        ":key": InputHistoryTag::Back as i64,
-                        ":tag_value": added_input_id,
+                        ":name": name,
+                        ":numer": stored.numer,
+                        ":denom": stored.denom,
+                        ":imaginary_numer": stored.imaginary_numer,
+                        ":imaginary_denom": stored.imaginary_denom,
+                        ":high_numer": stored.high_numer,
+                        ":high_denom": stored.high_denom,
+                        ":surd_numer": stored.surd_numer,
+                        ":surd_denom": stored.surd_denom,
+                        ":radicand": stored.radicand,
                     },
                 )?;
             }
         }
 
-        SavedData::enforce_history_size_with_transaction(&mut transaction)?;
+        let favorites: Vec<String> = read_salvageable_rows(
+            damaged,
+            "SELECT input FROM favorite_inputs ORDER BY id ASC",
+            (),
+            |row| row.get(0),
+        );
+        for favorite in favorites {
+            self.add_favorite(&favorite)?;
+        }
 
-        transaction.commit()?;
+        let custom_units: Vec<(String, String, String, String)> = read_salvageable_rows(
+            damaged,
+            "SELECT name, numer, denom, dimension FROM custom_units ORDER BY rowid ASC",
+            (),
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        );
+        for (name, numer_str, denom_str, dimension_str) in custom_units {
+            if let Ok(unit) = parse_stored_unit(name, &numer_str, &denom_str, &dimension_str) {
+                self.set_custom_unit(&unit)?;
+            }
+        }
 
-        Ok(added_input_id)
+        let snippets: Vec<(String, String)> = read_salvageable_rows(
+            damaged,
+            "SELECT name, template FROM snippets ORDER BY rowid ASC",
+            (),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        );
+        for (name, template) in snippets {
+            self.set_snippet(&name, &template)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces this database with `other`, as is done when switching profiles. If a batch is in
+    /// progress, it is committed before the switch and a new one is started in `other`, so that
+    /// the caller's eventual `Storage::end_batch` still has a batch to end.
+    pub fn replace(&mut self, mut other: SavedData) -> Result<(), Box<dyn std::error::Error>> {
+        if self.in_batch {
+            self.end_batch()?;
+            other.begin_batch()?;
+        }
+        *self = other;
+        Ok(())
+    }
+
+    /// Returns the name of the profile that this database belongs to.
+    pub fn profile(&self) -> &str {
+        self.maybe_profile
+            .as_deref()
+            .unwrap_or(DEFAULT_PROFILE_NAME)
     }
 
     fn enforce_history_size_with_transaction(
-        transaction: &mut Transaction,
+        transaction: &mut Savepoint,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let max_history_size: i64 = SavedData::get_max_history_size_with_transaction(transaction)?;
 
@@ -345,6 +717,16 @@ impl SavedData {
                     ":tag_value": new_back,
                 },
             )?;
+            // Pinned variables shouldn't be removed along with the row we are evicting, so move
+            // them to the row that is replacing it at the back of the list.
+            transaction.execute(
+                "UPDATE variable_history SET last_used_by=:new_back
+                    WHERE last_used_by=:old_back AND pinned=1",
+                named_params! {
+                    ":new_back": new_back,
+                    ":old_back": old_back,
+                },
+            )?;
             transaction.execute(
                 "DELETE FROM input_history WHERE id=:id",
                 named_params! {
@@ -352,6 +734,18 @@ impl SavedData {
                 },
             )?;
         }
+        // Prior values of variables that were just removed along with their input are not worth
+        // keeping either.
+        transaction.execute(
+            "DELETE FROM variable_versions
+                WHERE name NOT IN (SELECT name FROM variable_history)",
+            (),
+        )?;
+        transaction.execute(
+            "DELETE FROM sessions WHERE id NOT IN
+                (SELECT session FROM input_history WHERE session IS NOT NULL)",
+            (),
+        )?;
 
         Ok(())
     }
@@ -360,20 +754,29 @@ impl SavedData {
     /// of the list when `SavedData::open` was called. Each subsequent time, it retrieves the
     /// history item before the one that was retrieved last time, until the earliest history item
     /// is reached, and `Ok(None)` is returned instead.
-    pub fn get_prev_input_history(&mut self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    pub fn get_prev_input_history(
+        &mut self,
+    ) -> Result<Option<InputHistoryEntry>, Box<dyn std::error::Error>> {
         let next_id = match self.input_history_position.clone() {
             Some(i) => i,
             None => return Ok(None),
         };
         // Remember to account for the possibility that we evicted this id from the history already.
-        let result: Option<(String, Option<i64>)> = self
+        let result: Option<(InputHistoryEntry, Option<i64>)> = self
             .connection
             .query_row(
-                "SELECT input, prev FROM input_history WHERE id=:id",
+                "SELECT input, result, session, prev FROM input_history WHERE id=:id",
                 named_params! {
                     ":id": next_id,
                 },
-                |row| Ok((row.get(0)?, row.get(1)?)),
+                |row| {
+                    let entry = InputHistoryEntry {
+                        input: row.get(0)?,
+                        maybe_result: row.get(1)?,
+                        maybe_session: row.get(2)?,
+                    };
+                    Ok((entry, row.get(3)?))
+                },
             )
             .optional()?;
 
@@ -382,120 +785,292 @@ impl SavedData {
                 self.input_history_position = None;
                 Ok(None)
             }
-            Some((input, maybe_prev)) => {
+            Some((entry, maybe_prev)) => {
                 self.input_history_position = maybe_prev;
-                Ok(Some(input))
+                Ok(Some(entry))
             }
         }
     }
 
-    /// Sets or updates the variable in the variable history.
-    pub fn set_variable(
-        &mut self,
-        var: &Variable,
-        last_used_by_id: i64,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        self.connection.execute(
-            "INSERT INTO variable_history (name, numer, denom, last_used_by)
-                    VALUES (:name, :numer, :denom, :last_used_by)",
-            named_params! {
-                ":name": var.name,
-                ":numer": var.value.numer().to_str_radix(VARIABLE_STORAGE_RADIX),
-                ":denom": var.value.denom().to_str_radix(VARIABLE_STORAGE_RADIX),
-                ":last_used_by": last_used_by_id,
-            },
+    /// Returns the id of the current session, or `None` if no input has been stored yet during it.
+    pub fn current_session(&self) -> Option<i64> {
+        self.maybe_session_id
+    }
+
+    /// Returns every session that still has inputs in the input history, oldest first.
+    pub fn get_sessions(&mut self) -> Result<Vec<SessionSummary>, Box<dyn std::error::Error>> {
+        let mut statement = self.connection.prepare(
+            "SELECT sessions.id, datetime(sessions.started_at, 'unixepoch', 'localtime'),
+                COUNT(input_history.id)
+                FROM sessions JOIN input_history ON input_history.session = sessions.id
+                GROUP BY sessions.id ORDER BY sessions.id ASC",
         )?;
-        Ok(())
+        let sessions = statement
+            .query_map((), |row| {
+                Ok(SessionSummary {
+                    id: row.get(0)?,
+                    started: row.get(1)?,
+                    input_count: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<SessionSummary>, rusqlite::Error>>()?;
+        Ok(sessions)
     }
 
-    /// Updates the `last_used_by` field of the variable specified.
-    pub fn touch_variable(
+    /// Returns the inputs from the given session that are still in the input history, oldest
+    /// first.
+    pub fn get_session_inputs(
         &mut self,
-        name: &str,
-        last_used_by_id: i64,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        self.connection.execute(
-            "UPDATE variable_history SET last_used_by=:last_used_by WHERE name=:name",
-            named_params! {
-                ":last_used_by": last_used_by_id,
-                ":name": name,
-            },
+        session_id: i64,
+    ) -> Result<Vec<InputHistoryEntry>, Box<dyn std::error::Error>> {
+        // `id`s are assigned in increasing order, so they reflect the order the inputs were added.
+        let mut statement = self.connection.prepare(
+            "SELECT input, result, session FROM input_history
+                WHERE session=:session ORDER BY id ASC",
         )?;
-        Ok(())
+        let entries = statement
+            .query_map(named_params! {":session": session_id}, |row| {
+                Ok(InputHistoryEntry {
+                    input: row.get(0)?,
+                    maybe_result: row.get(1)?,
+                    maybe_session: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<InputHistoryEntry>, rusqlite::Error>>()?;
+        Ok(entries)
     }
 
-    /// Gets a variable from the variable history and returns it, if it exists.
-    pub fn get_variable(
+    /// Returns every input in the input history along with what it evaluated to, oldest first.
+    pub fn get_all_input_history_entries(
         &mut self,
-        name: String,
-    ) -> Result<Option<Variable>, Box<dyn std::error::Error>> {
-        let result: Option<(String, String)> = self
-            .connection
-            .query_row(
-                "SELECT numer, denom FROM variable_history WHERE name=:name",
+    ) -> Result<Vec<InputHistoryEntry>, Box<dyn std::error::Error>> {
+        let transaction = self.connection.savepoint()?;
+        let mut maybe_id: Option<i64> = transaction.query_row(
+            "SELECT value FROM input_history_tags WHERE key=:key",
+            named_params! {
+                ":key": InputHistoryTag::Back as i64,
+            },
+            |row| row.get(0),
+        )?;
+        let mut entries: Vec<InputHistoryEntry> = Vec::new();
+        while let Some(id) = maybe_id {
+            let (entry, maybe_next) = transaction.query_row(
+                "SELECT input, result, session, next FROM input_history WHERE id=:id",
                 named_params! {
-                    ":name": &name,
+                    ":id": id,
                 },
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            )
-            .optional()?;
-
-        let (numer_str, denom_str) = match result {
-            None => return Ok(None),
-            Some((numer_str, denom_str)) => (numer_str, denom_str),
-        };
-
-        let numer = match BigInt::parse_bytes(numer_str.as_bytes(), VARIABLE_STORAGE_RADIX) {
-            Some(n) => n,
-            None => {
-                return Err(CalculatorDatabaseInconsistencyError::new(format!(
-                    "Stored numerator ({}) for variable '{}' cannot be parsed",
-                    &numer_str, &name
-                ))
-                .into());
-            }
-        };
-        let denom = match BigInt::parse_bytes(denom_str.as_bytes(), VARIABLE_STORAGE_RADIX) {
-            Some(n) => n,
-            None => {
-                return Err(CalculatorDatabaseInconsistencyError::new(format!(
-                    "Stored denominator ({}) for variable '{}' cannot be parsed",
-                    &denom_str, &name
-                ))
-                .into());
-            }
-        };
-        let value = BigRational::new(numer, denom);
+                |row| {
+                    let entry = InputHistoryEntry {
+                        input: row.get(0)?,
+                        maybe_result: row.get(1)?,
+                        maybe_session: row.get(2)?,
+                    };
+                    Ok((entry, row.get(3)?))
+                },
+            )?;
+            entries.push(entry);
+            maybe_id = maybe_next;
+        }
+        transaction.commit()?;
+        Ok(entries)
+    }
 
-        Ok(Some(Variable { name, value }))
+    /// Returns every favorite input, ordered from least to most recently favorited.
+    pub fn get_favorites(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT input FROM favorite_inputs ORDER BY id ASC")?;
+        let favorites = statement
+            .query_map((), |row| row.get(0))?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+        Ok(favorites)
     }
 
-    pub fn clear_variable(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.connection.execute(
-            "DELETE FROM variable_history WHERE name=:name",
+    /// Adds the input to the favorites. Favorites are stored separately from the input history, so
+    /// they are not evicted along with it. Returns `Ok(false)` if the input was already a favorite.
+    pub fn add_favorite(&mut self, input: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let rows_changed = self.connection.execute(
+            "INSERT OR IGNORE INTO favorite_inputs (input) VALUES (:input)",
             named_params! {
-                ":name": name,
+                ":input": input,
             },
         )?;
-        Ok(())
+        Ok(rows_changed > 0)
     }
 
-    fn get_max_history_size_with_transaction(
-        transaction: &mut Transaction,
-    ) -> Result<i64, Box<dyn std::error::Error>> {
-        let size = transaction.query_row(
-            "SELECT value FROM meta_int WHERE key=:key",
+    /// Removes the input from the favorites. Returns `Ok(false)` if the input was not a favorite.
+    pub fn remove_favorite(&mut self, input: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let rows_changed = self.connection.execute(
+            "DELETE FROM favorite_inputs WHERE input=:input",
             named_params! {
-                ":key": MetaInt::MaxHistorySize as i64,
+                ":input": input,
             },
-            |row| row.get(0),
         )?;
+        Ok(rows_changed > 0)
+    }
 
-        Ok(size)
+    /// Returns the units defined with `/defunit`, in the order that they were defined.
+    pub fn get_custom_units(&mut self) -> Result<Vec<Unit>, Box<dyn std::error::Error>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT name, numer, denom, dimension FROM custom_units ORDER BY rowid ASC")?;
+        let rows = statement
+            .query_map((), |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect::<Result<Vec<(String, String, String, String)>, rusqlite::Error>>()?;
+        let mut units = Vec::new();
+        for (name, numer_str, denom_str, dimension_str) in rows {
+            units.push(parse_stored_unit(
+                name,
+                &numer_str,
+                &denom_str,
+                &dimension_str,
+            )?);
+        }
+        Ok(units)
     }
 
-    pub fn get_max_history_size(&mut self) -> Result<i64, Box<dyn std::error::Error>> {
-        let mut transaction = self.connection.transaction()?;
+    /// Stores a unit defined with `/defunit` under its first name, replacing any unit already
+    /// stored under that name.
+    pub fn set_custom_unit(&mut self, unit: &Unit) -> Result<(), Box<dyn std::error::Error>> {
+        let dimension = unit
+            .dimension
+            .exponents()
+            .iter()
+            .map(|exponent| exponent.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        self.connection.execute(
+            "INSERT INTO custom_units (name, numer, denom, dimension)
+                VALUES (:name, :numer, :denom, :dimension)",
+            named_params! {
+                ":name": unit.name(),
+                ":numer": unit.factor.numer().to_str_radix(VARIABLE_STORAGE_RADIX),
+                ":denom": unit.factor.denom().to_str_radix(VARIABLE_STORAGE_RADIX),
+                ":dimension": dimension,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Removes a unit stored by `set_custom_unit`. Returns `Ok(false)` if there was no such unit.
+    pub fn remove_custom_unit(&mut self, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let rows_changed = self.connection.execute(
+            "DELETE FROM custom_units WHERE name=:name",
+            named_params! {
+                ":name": name,
+            },
+        )?;
+        Ok(rows_changed > 0)
+    }
+
+    /// Returns the snippets saved with `/snippet save` as `(name, template)` pairs, in the order that
+    /// they were saved.
+    pub fn get_snippets(&mut self) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT name, template FROM snippets ORDER BY rowid ASC")?;
+        let snippets = statement
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<(String, String)>, rusqlite::Error>>()?;
+        Ok(snippets)
+    }
+
+    /// Stores the snippet, replacing any snippet already stored under that name.
+    pub fn set_snippet(
+        &mut self,
+        name: &str,
+        template: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.connection.execute(
+            "INSERT INTO snippets (name, template) VALUES (:name, :template)",
+            named_params! {
+                ":name": name,
+                ":template": template,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Removes a snippet stored by `set_snippet`. Returns `Ok(false)` if there was no such snippet.
+    pub fn remove_snippet(&mut self, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let rows_changed = self.connection.execute(
+            "DELETE FROM snippets WHERE name=:name",
+            named_params! {
+                ":name": name,
+            },
+        )?;
+        Ok(rows_changed > 0)
+    }
+
+    /// Returns the prior values of the variable, most recent first. The current value is not
+    /// included. Returns an empty `Vec` if the variable has no prior values or does not exist.
+    pub fn get_variable_versions(
+        &mut self,
+        name: &str,
+    ) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+        let mut statement = self.connection.prepare(
+            "SELECT numer, denom, imaginary_numer, imaginary_denom, high_numer, high_denom,
+                    surd_numer, surd_denom, radicand
+                FROM variable_versions WHERE name=:name ORDER BY id DESC",
+        )?;
+        let stored = statement
+            .query_map(named_params! {":name": name}, |row| {
+                StoredValue::from_row(row, 0)
+            })?
+            .collect::<Result<Vec<StoredValue>, rusqlite::Error>>()?;
+        let mut versions: Vec<Value> = Vec::new();
+        for stored in stored {
+            versions.push(parse_stored_variable_value(name, &stored)?);
+        }
+        Ok(versions)
+    }
+
+    /// Pins or unpins the variable specified. Returns `Ok(false)` if there is no such variable in
+    /// the variable history.
+    pub fn set_variable_pinned(
+        &mut self,
+        name: &str,
+        pinned: bool,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let rows_changed = self.connection.execute(
+            "UPDATE variable_history SET pinned=:pinned WHERE name=:name",
+            named_params! {
+                ":pinned": pinned,
+                ":name": name,
+            },
+        )?;
+        Ok(rows_changed > 0)
+    }
+
+    /// Returns the names of all pinned variables, sorted alphabetically.
+    pub fn get_pinned_variable_names(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT name FROM variable_history WHERE pinned=1 ORDER BY name ASC")?;
+        let names = statement
+            .query_map((), |row| row.get(0))?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+        Ok(names)
+    }
+
+    fn get_max_history_size_with_transaction(
+        transaction: &mut Savepoint,
+    ) -> Result<i64, Box<dyn std::error::Error>> {
+        let size = transaction.query_row(
+            "SELECT value FROM meta_int WHERE key=:key",
+            named_params! {
+                ":key": MetaInt::MaxHistorySize as i64,
+            },
+            |row| row.get(0),
+        )?;
+
+        Ok(size)
+    }
+
+    pub fn get_max_history_size(&mut self) -> Result<i64, Box<dyn std::error::Error>> {
+        let mut transaction = self.connection.savepoint()?;
         let size = SavedData::get_max_history_size_with_transaction(&mut transaction)?;
         transaction.commit()?;
         Ok(size)
@@ -512,7 +1087,7 @@ impl SavedData {
             .into());
         }
 
-        let mut transaction = self.connection.transaction()?;
+        let mut transaction = self.connection.savepoint()?;
         transaction.execute(
             "INSERT OR REPLACE INTO meta_int (key, value) VALUES (:key, :value)",
             named_params! {
@@ -527,9 +1102,1686 @@ impl SavedData {
     }
 }
 
-pub fn validate_max_history_size(value: i64) -> Result<(), String> {
-    if value < 1 {
-        return Err("Maximum history size must be at least 1".to_string());
+impl Storage for SavedData {
+    fn add_to_input_history(&mut self, input: &str) -> Result<i64, Box<dyn std::error::Error>> {
+        let mut transaction = self.connection.savepoint()?;
+        let maybe_orig_front: Option<i64> = transaction.query_row(
+            "SELECT value FROM input_history_tags WHERE key=:key",
+            named_params! {
+                ":key": InputHistoryTag::Front as i64,
+            },
+            |row| row.get(0),
+        )?;
+
+        let session_id = match self.maybe_session_id {
+            Some(id) => id,
+            None => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+                transaction.execute(
+                    "INSERT INTO sessions (started_at) VALUES (:now)",
+                    named_params! {
+                        ":now": i64::try_from(now)?,
+                    },
+                )?;
+                transaction.last_insert_rowid()
+            }
+        };
+
+        // Insert the new row
+        transaction.execute(
+            "INSERT INTO input_history (input, next, prev, session)
+                VALUES (:input, NULL, :prev, :session)",
+            named_params! {
+                ":input": input,
+                ":prev": maybe_orig_front,
+                ":session": session_id,
+            },
+        )?;
+        let added_input_id: i64 = transaction.last_insert_rowid();
+        // Update the front tag to point to the new front.
+        transaction.execute(
+            "UPDATE input_history_tags SET value=:tag_value WHERE key=:key",
+            named_params! {
+                ":key": InputHistoryTag::Front as i64,
+                ":tag_value": added_input_id,
+            },
+        )?;
+
+        match maybe_orig_front {
+            Some(orig_front) => {
+                // Update the old front to point to the new front.
+                transaction.execute(
+                    "UPDATE input_history SET next=:new_front WHERE id=:orig_front",
+                    named_params! {
+                        ":orig_front": orig_front,
+                        ":new_front": added_input_id,
+                    },
+                )?;
+            }
+            None => {
+                // The list was previously empty. We also need to update the back to point to the
+                // front.
+                transaction.execute(
+                    "UPDATE input_history_tags SET value=:tag_value WHERE key=:key",
+                    named_params! {
+                        ":key": InputHistoryTag::Back as i64,
+                        ":tag_value": added_input_id,
+                    },
+                )?;
+            }
+        }
+
+        SavedData::enforce_history_size_with_transaction(&mut transaction)?;
+
+        transaction.commit()?;
+        self.maybe_session_id = Some(session_id);
+
+        Ok(added_input_id)
+    }
+
+    fn set_input_history_result(
+        &mut self,
+        id: i64,
+        result: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.connection.execute(
+            "UPDATE input_history SET result=:result WHERE id=:id",
+            named_params! {
+                ":result": result,
+                ":id": id,
+            },
+        )?;
+        Ok(())
+    }
+
+    fn get_all_input_history(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let transaction = self.connection.savepoint()?;
+        let mut maybe_id: Option<i64> = transaction.query_row(
+            "SELECT value FROM input_history_tags WHERE key=:key",
+            named_params! {
+                ":key": InputHistoryTag::Back as i64,
+            },
+            |row| row.get(0),
+        )?;
+        let mut inputs: Vec<String> = Vec::new();
+        while let Some(id) = maybe_id {
+            let (input, maybe_next): (String, Option<i64>) = transaction.query_row(
+                "SELECT input, next FROM input_history WHERE id=:id",
+                named_params! {
+                    ":id": id,
+                },
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            inputs.push(input);
+            maybe_id = maybe_next;
+        }
+        transaction.commit()?;
+        Ok(inputs)
+    }
+
+    fn set_variable(
+        &mut self,
+        var: &Variable,
+        last_used_by_id: i64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let transaction = self.connection.savepoint()?;
+        let stored = StoredValue::new(&var.value);
+        // `IS` is used for the imaginary, high, and surd columns since they are `NULL` for real
+        // values.
+        transaction.execute(
+            "INSERT INTO variable_versions
+                    (name, numer, denom, imaginary_numer, imaginary_denom, high_numer, high_denom,
+                        surd_numer, surd_denom, radicand)
+                SELECT name, numer, denom, imaginary_numer, imaginary_denom, high_numer, high_denom,
+                    surd_numer, surd_denom, radicand
+                FROM variable_history
+                WHERE name=:name AND NOT (numer=:numer AND denom=:denom
+                    AND imaginary_numer IS :imaginary_numer
+                    AND imaginary_denom IS :imaginary_denom
+                    AND high_numer IS :high_numer
+                    AND high_denom IS :high_denom
+                    AND surd_numer IS :surd_numer
+                    AND surd_denom IS :surd_denom
+                    AND radicand IS :radicand)",
+            named_params! {
+                ":name": var.name,
+                ":numer": stored.numer,
+                ":denom": stored.denom,
+                ":imaginary_numer": stored.imaginary_numer,
+                ":imaginary_denom": stored.imaginary_denom,
+                ":high_numer": stored.high_numer,
+                ":high_denom": stored.high_denom,
+                ":surd_numer": stored.surd_numer,
+                ":surd_denom": stored.surd_denom,
+                ":radicand": stored.radicand,
+            },
+        )?;
+        transaction.execute(
+            "DELETE FROM variable_versions WHERE name=:name AND id NOT IN
+                (SELECT id FROM variable_versions WHERE name=:name ORDER BY id DESC LIMIT :limit)",
+            named_params! {
+                ":name": var.name,
+                ":limit": MAX_VARIABLE_VERSIONS,
+            },
+        )?;
+        transaction.execute(
+            "INSERT INTO variable_history (name, numer, denom, imaginary_numer, imaginary_denom,
+                        high_numer, high_denom, surd_numer, surd_denom, radicand, last_used_by,
+                        pinned, format)
+                    VALUES (:name, :numer, :denom, :imaginary_numer, :imaginary_denom,
+                        :high_numer, :high_denom, :surd_numer, :surd_denom, :radicand,
+                        :last_used_by,
+                        COALESCE((SELECT pinned FROM variable_history WHERE name=:name), 0),
+                        (SELECT format FROM variable_history WHERE name=:name))",
+            named_params! {
+                ":name": var.name,
+                ":numer": stored.numer,
+                ":denom": stored.denom,
+                ":imaginary_numer": stored.imaginary_numer,
+                ":imaginary_denom": stored.imaginary_denom,
+                ":high_numer": stored.high_numer,
+                ":high_denom": stored.high_denom,
+                ":surd_numer": stored.surd_numer,
+                ":surd_denom": stored.surd_denom,
+                ":radicand": stored.radicand,
+                ":last_used_by": last_used_by_id,
+            },
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn touch_variable(
+        &mut self,
+        name: &str,
+        last_used_by_id: i64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.connection.execute(
+            "UPDATE variable_history SET last_used_by=:last_used_by WHERE name=:name",
+            named_params! {
+                ":last_used_by": last_used_by_id,
+                ":name": name,
+            },
+        )?;
+        Ok(())
+    }
+
+    fn get_variable(&mut self, name: &str) -> Result<Option<Variable>, Box<dyn std::error::Error>> {
+        let result: Option<StoredValue> = self
+            .connection
+            .query_row(
+                "SELECT numer, denom, imaginary_numer, imaginary_denom, high_numer, high_denom,
+                        surd_numer, surd_denom, radicand
+                    FROM variable_history WHERE name=:name",
+                named_params! {
+                    ":name": name,
+                },
+                |row| StoredValue::from_row(row, 0),
+            )
+            .optional()?;
+
+        let stored = match result {
+            None => return Ok(None),
+            Some(stored) => stored,
+        };
+        let value = parse_stored_variable_value(name, &stored)?;
+
+        Ok(Some(Variable {
+            name: name.into(),
+            value,
+        }))
+    }
+
+    fn get_variable_names(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT name FROM variable_history")?;
+        let names = statement
+            .query_map((), |row| row.get(0))?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+        Ok(names)
+    }
+
+    fn get_variable_format(
+        &mut self,
+        name: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let maybe_format: Option<Option<String>> = self
+            .connection
+            .query_row(
+                "SELECT format FROM variable_history WHERE name=:name",
+                named_params! {
+                    ":name": name,
+                },
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(maybe_format.flatten())
+    }
+
+    fn set_variable_format(
+        &mut self,
+        name: &str,
+        maybe_format: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.connection.execute(
+            "UPDATE variable_history SET format=:format WHERE name=:name",
+            named_params! {
+                ":format": maybe_format,
+                ":name": name,
+            },
+        )?;
+        Ok(())
+    }
+
+    fn clear_variable(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let transaction = self.connection.savepoint()?;
+        transaction.execute(
+            "DELETE FROM variable_history WHERE name=:name",
+            named_params! {
+                ":name": name,
+            },
+        )?;
+        transaction.execute(
+            "DELETE FROM variable_versions WHERE name=:name",
+            named_params! {
+                ":name": name,
+            },
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn get_setting(
+        &mut self,
+        setting: SavedSetting,
+    ) -> Result<Option<i64>, Box<dyn std::error::Error>> {
+        let value = self
+            .connection
+            .query_row(
+                "SELECT value FROM meta_int WHERE key=:key",
+                named_params! {
+                    ":key": setting_key(setting) as i64,
+                },
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value)
+    }
+
+    fn set_setting(
+        &mut self,
+        setting: SavedSetting,
+        maybe_value: Option<i64>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match maybe_value {
+            Some(value) => self.connection.execute(
+                "INSERT OR REPLACE INTO meta_int (key, value) VALUES (:key, :value)",
+                named_params! {
+                    ":key": setting_key(setting) as i64,
+                    ":value": value,
+                },
+            )?,
+            None => self.connection.execute(
+                "DELETE FROM meta_int WHERE key=:key",
+                named_params! {
+                    ":key": setting_key(setting) as i64,
+                },
+            )?,
+        };
+        Ok(())
+    }
+
+    fn begin_batch(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // SQLite would otherwise commit (and sync to disk) after every statement and savepoint.
+        self.connection.execute_batch("BEGIN")?;
+        self.in_batch = true;
+        Ok(())
+    }
+
+    fn end_batch(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.connection.execute_batch("COMMIT")?;
+        self.in_batch = false;
+        Ok(())
+    }
+}
+
+pub fn validate_max_history_size(value: i64) -> Result<(), String> {
+    if value < 1 {
+        return Err("Maximum history size must be at least 1".to_string());
+    }
+    Ok(())
+}
+
+// A variable's value as it is stored in the `numer`, `denom`, `imaginary_numer`,
+// `imaginary_denom`, `high_numer`, `high_denom`, `surd_numer`, `surd_denom`, and `radicand`
+// columns.
+struct StoredValue {
+    numer: String,
+    denom: String,
+    imaginary_numer: Option<String>,
+    imaginary_denom: Option<String>,
+    high_numer: Option<String>,
+    high_denom: Option<String>,
+    surd_numer: Option<String>,
+    surd_denom: Option<String>,
+    radicand: Option<String>,
+}
+
+impl StoredValue {
+    fn new(value: &Value) -> StoredValue {
+        let to_text = |value: &BigRational| {
+            (
+                value.numer().to_str_radix(VARIABLE_STORAGE_RADIX),
+                value.denom().to_str_radix(VARIABLE_STORAGE_RADIX),
+            )
+        };
+        let (real, maybe_imaginary, maybe_high, maybe_surd) = match value {
+            Value::Real(value) => (value, None, None, None),
+            Value::Complex { re, im } => (re, Some(to_text(im)), None, None),
+            Value::Interval { low, high } => (low, None, Some(to_text(high)), None),
+            Value::Surd {
+                coefficient,
+                radicand,
+                approximation,
+            } => (
+                approximation,
+                None,
+                None,
+                Some((to_text(coefficient), radicand)),
+            ),
+        };
+        let (numer, denom) = to_text(real);
+        let (imaginary_numer, imaginary_denom) = maybe_imaginary.unzip();
+        let (high_numer, high_denom) = maybe_high.unzip();
+        let (maybe_coefficient, maybe_radicand) = maybe_surd.unzip();
+        let (surd_numer, surd_denom) = maybe_coefficient.unzip();
+        let radicand = maybe_radicand.map(|radicand| radicand.to_str_radix(VARIABLE_STORAGE_RADIX));
+        StoredValue {
+            numer,
+            denom,
+            imaginary_numer,
+            imaginary_denom,
+            high_numer,
+            high_denom,
+            surd_numer,
+            surd_denom,
+            radicand,
+        }
+    }
+
+    // Reads the nine columns, in order, starting at column `start`.
+    fn from_row(row: &Row, start: usize) -> Result<StoredValue, rusqlite::Error> {
+        Ok(StoredValue {
+            numer: row.get(start)?,
+            denom: row.get(start + 1)?,
+            imaginary_numer: row.get(start + 2)?,
+            imaginary_denom: row.get(start + 3)?,
+            high_numer: row.get(start + 4)?,
+            high_denom: row.get(start + 5)?,
+            surd_numer: row.get(start + 6)?,
+            surd_denom: row.get(start + 7)?,
+            radicand: row.get(start + 8)?,
+        })
+    }
+}
+
+fn parse_stored_variable_value(
+    name: &str,
+    stored: &StoredValue,
+) -> Result<Value, CalculatorDatabaseInconsistencyError> {
+    let description = format!("variable '{}'", name);
+    let re = parse_stored_value(&description, &stored.numer, &stored.denom)?;
+    match (&stored.surd_numer, &stored.surd_denom, &stored.radicand) {
+        (None, None, None) => {}
+        (Some(numer_str), Some(denom_str), Some(radicand_str)) => {
+            let coefficient = parse_stored_value(
+                &format!("the coefficient of {}", description),
+                numer_str,
+                denom_str,
+            )?;
+            let radicand =
+                match BigInt::parse_bytes(radicand_str.as_bytes(), VARIABLE_STORAGE_RADIX) {
+                    Some(n) => n,
+                    None => {
+                        return Err(CalculatorDatabaseInconsistencyError::new(format!(
+                            "Stored radicand ({}) for {} cannot be parsed",
+                            radicand_str, description
+                        )));
+                    }
+                };
+            return Ok(Value::surd(coefficient, radicand, re));
+        }
+        _ => {
+            return Err(CalculatorDatabaseInconsistencyError::new(format!(
+                "Stored surd for {} is incomplete",
+                description
+            )))
+        }
+    }
+    match (&stored.high_numer, &stored.high_denom) {
+        (None, None) => {}
+        (Some(numer_str), Some(denom_str)) => {
+            let high = parse_stored_value(
+                &format!("the high end of {}", description),
+                numer_str,
+                denom_str,
+            )?;
+            return Ok(Value::interval(re, high));
+        }
+        _ => {
+            return Err(CalculatorDatabaseInconsistencyError::new(format!(
+                "Stored high end for {} is incomplete",
+                description
+            )))
+        }
+    }
+    match (&stored.imaginary_numer, &stored.imaginary_denom) {
+        (None, None) => Ok(Value::Real(re)),
+        (Some(numer_str), Some(denom_str)) => {
+            let im = parse_stored_value(
+                &format!("the imaginary part of {}", description),
+                numer_str,
+                denom_str,
+            )?;
+            Ok(Value::complex(re, im))
+        }
+        _ => Err(CalculatorDatabaseInconsistencyError::new(format!(
+            "Stored imaginary part for {} is incomplete",
+            description
+        ))),
+    }
+}
+
+// Parses a value stored as text in a `numer` and a `denom` column. `description` says what the value
+// belongs to for the sake of error messages.
+fn parse_stored_value(
+    description: &str,
+    numer_str: &str,
+    denom_str: &str,
+) -> Result<BigRational, CalculatorDatabaseInconsistencyError> {
+    let numer = match BigInt::parse_bytes(numer_str.as_bytes(), VARIABLE_STORAGE_RADIX) {
+        Some(n) => n,
+        None => {
+            return Err(CalculatorDatabaseInconsistencyError::new(format!(
+                "Stored numerator ({}) for {} cannot be parsed",
+                numer_str, description
+            )));
+        }
+    };
+    let denom = match BigInt::parse_bytes(denom_str.as_bytes(), VARIABLE_STORAGE_RADIX) {
+        Some(n) => n,
+        None => {
+            return Err(CalculatorDatabaseInconsistencyError::new(format!(
+                "Stored denominator ({}) for {} cannot be parsed",
+                denom_str, description
+            )));
+        }
+    };
+    Ok(BigRational::new(numer, denom))
+}
+
+fn parse_stored_unit(
+    name: String,
+    numer_str: &str,
+    denom_str: &str,
+    dimension_str: &str,
+) -> Result<Unit, CalculatorDatabaseInconsistencyError> {
+    let factor = parse_stored_value(&format!("unit '{}'", name), numer_str, denom_str)?;
+    let dimension = dimension_str
+        .split(',')
+        .map(|exponent| exponent.parse::<i8>().ok())
+        .collect::<Option<Vec<i8>>>()
+        .and_then(|exponents| Dimension::from_exponent_list(&exponents))
+        .ok_or_else(|| {
+            CalculatorDatabaseInconsistencyError::new(format!(
+                "Stored dimension ({}) for unit '{}' cannot be parsed",
+                dimension_str, name
+            ))
+        })?;
+    Ok(Unit {
+        names: vec![name],
+        dimension,
+        factor,
+        is_custom: true,
+    })
+}
+
+/// The path to the database for the profile, or for the default profile if `maybe_profile` is
+/// `None`, creating the directories that it is in if needed. Returns `Ok(None)` if the environment
+/// doesn't say where bcalc's data should go. See `SavedData::open`.
+fn db_path(maybe_profile: Option<&str>) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let data_dir_path_str = match env::var(DATA_ROOT_DIR_ENV_VAR_NAME) {
+        Ok(s) => s,
+        Err(env::VarError::NotPresent) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let data_dir_path = Path::new(&data_dir_path_str).join(DATA_DIR_NAME);
+    if let Err(e) = create_dir(data_dir_path.clone()) {
+        if e.kind() != io::ErrorKind::AlreadyExists {
+            return Err(e.into());
+        }
+    }
+    let db_path = match maybe_profile {
+        None => data_dir_path.join(HISTORY_DB_NAME),
+        Some(profile) => {
+            if validate_profile_name(profile).is_err() {
+                return Err(InternalCalculatorError::new(
+                    "Attempted to open a profile with an invalid name",
+                )
+                .into());
+            }
+            let profile_dir_path = data_dir_path.join(PROFILE_DIR_NAME);
+            if let Err(e) = create_dir(profile_dir_path.clone()) {
+                if e.kind() != io::ErrorKind::AlreadyExists {
+                    return Err(e.into());
+                }
+            }
+            profile_dir_path
+                .join(profile)
+                .with_extension(PROFILE_DB_EXTENSION)
+        }
+    };
+    Ok(Some(db_path))
+}
+
+/// The path to the file that a session using the profile writes to if it crashes, so that the next
+/// session can offer to restore what was lost. It is next to the profile's database. Returns
+/// `Ok(None)` under the same conditions as `SavedData::open`.
+pub fn recovery_path(
+    maybe_profile: Option<&str>,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let maybe_profile = maybe_profile.filter(|p| *p != DEFAULT_PROFILE_NAME);
+    Ok(db_path(maybe_profile)?.map(|path| path.with_extension(RECOVERY_EXTENSION)))
+}
+
+/// Profile names are used as file names, so we keep them to a conservative set of characters.
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Profile name cannot be empty".to_string());
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(
+            "Profile names may only contain letters, numbers, underscores, and dashes".to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// A migration upgrades the database schema by exactly one version. See `MIGRATIONS`.
+type Migration = fn(&Transaction) -> Result<(), rusqlite::Error>;
+
+/// The migration at index `i` upgrades the database schema from version `i` to version `i + 1`. A
+/// database that has never been initialized is considered to be at version `0`.
+/// Once a migration has been released, it must not be changed. Schema changes should instead be
+/// made by appending a new migration to the list. Note that migrations run inside a transaction with
+/// foreign key enforcement enabled, so migrations that need to rebuild tables must take care not to
+/// trigger `ON DELETE` actions.
+const MIGRATIONS: &[Migration] = &[
+    create_initial_schema,
+    add_variable_pinned_column,
+    create_favorite_inputs,
+    add_input_history_result_column,
+    create_variable_versions,
+    create_sessions,
+    create_custom_units,
+    add_variable_format_column,
+    create_snippets,
+    add_variable_imaginary_columns,
+    add_variable_interval_columns,
+    add_variable_surd_columns,
+];
+
+/// Brings the database schema up to `CURRENT_DB_VERSION` by running whichever migrations have not
+/// yet been applied. This all happens in a single transaction, so if anything fails, the database is
+/// left as it was.
+/// Returns the id of the input history entry that is currently at the front of the list.
+fn initialize(
+    connection: &mut rusqlite::Connection,
+) -> Result<Option<i64>, Box<dyn std::error::Error>> {
+    // This has no effect inside of a transaction, so it must be done first.
+    connection.execute("PRAGMA foreign_keys = ON;", ())?;
+
+    let transaction = connection.transaction()?;
+
+    // `meta_int` is where we store the schema version, so it has to exist before we can figure out
+    // which migrations to run.
+    transaction.execute(
+        "CREATE TABLE IF NOT EXISTS meta_int(
+            key INTEGER PRIMARY KEY ASC,
+            value INTEGER NOT NULL
+        );",
+        (),
+    )?;
+    let maybe_minimum_version: Option<i64> = transaction
+        .query_row(
+            "SELECT value FROM meta_int WHERE key=:key",
+            named_params! {
+                ":key": MetaInt::MinimumVersion as i64,
+            },
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(minimum_version) = maybe_minimum_version {
+        if minimum_version > CURRENT_DB_VERSION {
+            return Err(CalculatorDatabaseInconsistencyError::new(
+                "Database version is not compatible with executable version",
+            )
+            .into());
+        }
+    }
+    let stored_version: i64 = transaction
+        .query_row(
+            "SELECT value FROM meta_int WHERE key=:key",
+            named_params! {
+                ":key": MetaInt::Version as i64,
+            },
+            |row| row.get(0),
+        )
+        .optional()?
+        .unwrap_or(0);
+    let applied_migrations = match usize::try_from(stored_version) {
+        Ok(n) => n,
+        Err(_) => {
+            return Err(
+                CalculatorDatabaseInconsistencyError::new("Database version is negative").into(),
+            )
+        }
+    };
+
+    for migration in MIGRATIONS.iter().skip(applied_migrations) {
+        migration(&transaction)?;
+    }
+
+    // A database written by a newer executable may be at a version that we don't know about. Since
+    // it passed the minimum version check, we can still use it, but we must not downgrade it.
+    if stored_version < CURRENT_DB_VERSION {
+        transaction.execute(
+            "INSERT OR REPLACE INTO meta_int (key, value) VALUES (:key, :value)",
+            named_params! {
+                ":key": MetaInt::Version as i64,
+                ":value": CURRENT_DB_VERSION,
+            },
+        )?;
+    }
+    transaction.execute(
+        "INSERT OR IGNORE INTO meta_int (key, value) VALUES (:key, :value)",
+        named_params! {
+            ":key": MetaInt::MinimumVersion as i64,
+            ":value": MINIUM_COMPATIBLE_DB_VERSION,
+        },
+    )?;
+
+    let initial_front: Option<i64> = transaction.query_row(
+        "SELECT value FROM input_history_tags WHERE key=:key",
+        named_params! {
+            ":key": InputHistoryTag::Front as i64,
+        },
+        |row| row.get(0),
+    )?;
+
+    transaction.commit()?;
+
+    Ok(initial_front)
+}
+
+/// Queries that ought to succeed against any database at `CURRENT_DB_VERSION`. Together, they
+/// reference every table and column that we use.
+const SCHEMA_CHECK_QUERIES: &[&str] = &[
+    "SELECT key, value FROM meta_int",
+    "SELECT id, input, next, prev, result FROM input_history",
+    "SELECT key, value FROM input_history_tags",
+    "SELECT name, numer, denom, imaginary_numer, imaginary_denom, high_numer, high_denom,
+        surd_numer, surd_denom, radicand, last_used_by, pinned, format
+        FROM variable_history",
+    "SELECT id, input FROM favorite_inputs",
+    "SELECT id, name, numer, denom, imaginary_numer, imaginary_denom, high_numer, high_denom,
+        surd_numer, surd_denom, radicand
+        FROM variable_versions",
+    "SELECT id, started_at FROM sessions",
+    "SELECT session FROM input_history",
+    "SELECT name, numer, denom, dimension FROM custom_units",
+    "SELECT name, template FROM snippets",
+];
+
+/// Returns `true` if the error indicates that the database file itself is damaged, as opposed to
+/// something like the database being locked.
+fn is_damage_error(error: &rusqlite::Error) -> bool {
+    matches!(
+        error.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseCorrupt) | Some(rusqlite::ErrorCode::NotADatabase)
+    )
+}
+
+/// Checks the database for damage that would otherwise cause later operations to fail. Returns
+/// `Ok(Some(description))` if any is found. Errors that don't indicate damage are returned as-is.
+/// This should only be called on a database that `initialize` succeeded on.
+fn find_damage(connection: &rusqlite::Connection) -> Result<Option<String>, rusqlite::Error> {
+    let check: String = match connection.query_row("PRAGMA quick_check", (), |row| row.get(0)) {
+        Ok(check) => check,
+        Err(e) if is_damage_error(&e) => return Ok(Some(e.to_string())),
+        Err(e) => return Err(e),
+    };
+    if check != "ok" {
+        return Ok(Some(check));
+    }
+
+    for query in SCHEMA_CHECK_QUERIES {
+        if let Err(e) = connection.prepare(query) {
+            return Ok(Some(e.to_string()));
+        }
+    }
+
+    // Walk the input history from back to front, verifying that the links in each direction agree
+    // and that every row is reachable exactly once.
+    let get_tag = |tag: InputHistoryTag| -> Result<Option<Option<i64>>, rusqlite::Error> {
+        connection
+            .query_row(
+                "SELECT value FROM input_history_tags WHERE key=:key",
+                named_params! {
+                    ":key": tag as i64,
+                },
+                |row| row.get(0),
+            )
+            .optional()
+    };
+    let (front, back) = match (
+        get_tag(InputHistoryTag::Front)?,
+        get_tag(InputHistoryTag::Back)?,
+    ) {
+        (Some(front), Some(back)) => (front, back),
+        _ => return Ok(Some("Input history tag missing".to_string())),
+    };
+    let row_count: usize =
+        connection.query_row("SELECT COUNT(*) FROM input_history", (), |row| row.get(0))?;
+    let mut statement = connection.prepare("SELECT prev, next FROM input_history WHERE id=:id")?;
+    let mut visited: usize = 0;
+    let mut maybe_prev: Option<i64> = None;
+    let mut maybe_id = back;
+    while let Some(id) = maybe_id {
+        if visited >= row_count {
+            return Ok(Some("Input history contains a cycle".to_string()));
+        }
+        let (stored_prev, next): (Option<i64>, Option<i64>) = match statement
+            .query_row(named_params! {":id": id}, |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .optional()?
+        {
+            Some(links) => links,
+            None => return Ok(Some("Input history links to a missing entry".to_string())),
+        };
+        if stored_prev != maybe_prev {
+            return Ok(Some("Input history links are inconsistent".to_string()));
+        }
+        visited += 1;
+        maybe_prev = Some(id);
+        maybe_id = next;
+    }
+    if maybe_prev != front {
+        return Ok(Some("Input history front tag is inconsistent".to_string()));
+    }
+    if visited != row_count {
+        return Ok(Some("Input history contains unlinked entries".to_string()));
+    }
+
+    Ok(None)
+}
+
+/// Runs the query against a database that is being salvaged, returning as many rows as could be
+/// read before encountering an error. If the query can't be run at all, returns no rows.
+fn read_salvageable_rows<T, P, F>(
+    connection: &rusqlite::Connection,
+    query: &str,
+    params: P,
+    f: F,
+) -> Vec<T>
+where
+    P: rusqlite::Params,
+    F: FnMut(&Row<'_>) -> Result<T, rusqlite::Error>,
+{
+    let mut statement = match connection.prepare(query) {
+        Ok(statement) => statement,
+        Err(_) => return Vec::new(),
+    };
+    let rows = match statement.query_map(params, f) {
+        Ok(rows) => rows,
+        Err(_) => return Vec::new(),
+    };
+    rows.map_while(Result::ok).collect()
+}
+
+/// Version 0 -> 1: Creates the input history, variable history, and their metadata.
+fn create_initial_schema(transaction: &Transaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "INSERT OR IGNORE INTO meta_int (key, value) VALUES (:key, :value)",
+        named_params! {
+            ":key": MetaInt::MaxHistorySize as i64,
+            ":value": DEFAULT_MAX_HISTORY_SIZE,
+        },
+    )?;
+
+    transaction.execute(
+        "CREATE TABLE input_history(
+            id INTEGER PRIMARY KEY ASC,
+            input TEXT NOT NULL,
+            next REFERENCES input_history(id),
+            prev REFERENCES input_history(id)
+        );",
+        (),
+    )?;
+
+    transaction.execute(
+        "CREATE TABLE input_history_tags(
+            key INTEGER PRIMARY KEY ASC,
+            value REFERENCES input_history(id)
+        );",
+        (),
+    )?;
+    transaction.execute(
+        "INSERT INTO input_history_tags (key, value) VALUES (:key, NULL)",
+        named_params! {
+            ":key": InputHistoryTag::Front as i64,
+        },
+    )?;
+    transaction.execute(
+        "INSERT INTO input_history_tags (key, value) VALUES (:key, NULL)",
+        named_params! {
+            ":key": InputHistoryTag::Back as i64,
+        },
+    )?;
+
+    transaction.execute(
+        "CREATE TABLE variable_history(
+            name TEXT PRIMARY KEY ON CONFLICT REPLACE,
+            numer TEXT NOT NULL,
+            denom TEXT NOT NULL,
+            last_used_by NOT NULL REFERENCES input_history(id) ON DELETE CASCADE
+        );",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Version 1 -> 2: Allows variables to be pinned so that they are kept through history eviction.
+fn add_variable_pinned_column(transaction: &Transaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "ALTER TABLE variable_history ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0",
+        (),
+    )?;
+    Ok(())
+}
+
+/// Version 2 -> 3: Stores favorite inputs.
+fn create_favorite_inputs(transaction: &Transaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "CREATE TABLE favorite_inputs(
+            id INTEGER PRIMARY KEY ASC,
+            input TEXT NOT NULL UNIQUE
+        );",
+        (),
+    )?;
+    Ok(())
+}
+
+/// Version 3 -> 4: Stores what each input evaluated to. Inputs that were stored before this
+/// migration, as well as commands and inputs that failed to evaluate, have a `NULL` result.
+fn add_input_history_result_column(transaction: &Transaction) -> Result<(), rusqlite::Error> {
+    transaction.execute("ALTER TABLE input_history ADD COLUMN result TEXT", ())?;
+    Ok(())
+}
+
+/// Version 4 -> 5: Keeps prior values of variables so that they can be rolled back to. There is
+/// deliberately no foreign key on `name` since replacing a row in `variable_history` would cascade
+/// to this table. Instead, rows are removed explicitly when their variable is removed.
+fn create_variable_versions(transaction: &Transaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "CREATE TABLE variable_versions(
+            id INTEGER PRIMARY KEY ASC,
+            name TEXT NOT NULL,
+            numer TEXT NOT NULL,
+            denom TEXT NOT NULL
+        );",
+        (),
+    )?;
+    transaction.execute(
+        "CREATE INDEX variable_versions_by_name ON variable_versions(name)",
+        (),
+    )?;
+    Ok(())
+}
+
+/// Version 5 -> 6: Records which session each input was entered in. Inputs stored before this
+/// migration don't belong to any session.
+fn create_sessions(transaction: &Transaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "CREATE TABLE sessions(
+            id INTEGER PRIMARY KEY ASC,
+            started_at INTEGER NOT NULL
+        );",
+        (),
+    )?;
+    transaction.execute(
+        "ALTER TABLE input_history ADD COLUMN session REFERENCES sessions(id)",
+        (),
+    )?;
+    Ok(())
+}
+
+/// Version 6 -> 7: Stores units defined by the user.
+fn create_custom_units(transaction: &Transaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "CREATE TABLE custom_units(
+            name TEXT PRIMARY KEY ON CONFLICT REPLACE,
+            numer TEXT NOT NULL,
+            denom TEXT NOT NULL,
+            dimension TEXT NOT NULL
+        );",
+        (),
+    )?;
+    Ok(())
+}
+
+/// Version 7 -> 8: Stores display formats attached to variables.
+fn add_variable_format_column(transaction: &Transaction) -> Result<(), rusqlite::Error> {
+    transaction.execute("ALTER TABLE variable_history ADD COLUMN format TEXT", ())?;
+    Ok(())
+}
+
+/// Version 8 -> 9: Stores expression templates saved by the user.
+fn create_snippets(transaction: &Transaction) -> Result<(), rusqlite::Error> {
+    transaction.execute(
+        "CREATE TABLE snippets(
+            name TEXT PRIMARY KEY ON CONFLICT REPLACE,
+            template TEXT NOT NULL
+        );",
+        (),
+    )?;
+    Ok(())
+}
+
+/// Version 9 -> 10: Stores the imaginary parts of complex variables. Values stored before this
+/// migration are all real, which `NULL` imaginary columns mean.
+fn add_variable_imaginary_columns(transaction: &Transaction) -> Result<(), rusqlite::Error> {
+    for table in ["variable_history", "variable_versions"] {
+        for column in ["imaginary_numer", "imaginary_denom"] {
+            transaction.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} TEXT", table, column),
+                (),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Version 10 -> 11: Stores the high ends of interval variables. Values stored before this migration
+/// are all exact, which `NULL` high columns mean.
+fn add_variable_interval_columns(transaction: &Transaction) -> Result<(), rusqlite::Error> {
+    for table in ["variable_history", "variable_versions"] {
+        for column in ["high_numer", "high_denom"] {
+            transaction.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} TEXT", table, column),
+                (),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Version 11 -> 12: Stores the exact forms of surd variables. Values stored before this migration
+/// are all rational or approximated, which `NULL` surd columns mean.
+fn add_variable_surd_columns(transaction: &Transaction) -> Result<(), rusqlite::Error> {
+    for table in ["variable_history", "variable_versions"] {
+        for column in ["surd_numer", "surd_denom", "radicand"] {
+            transaction.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} TEXT", table, column),
+                (),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod migration_tests {
+    use crate::saved_data::{
+        initialize, MetaInt, CURRENT_DB_VERSION, MIGRATIONS, MINIUM_COMPATIBLE_DB_VERSION,
+        SCHEMA_CHECK_QUERIES,
+    };
+    use rusqlite::{named_params, Connection};
+
+    fn get_meta_int(connection: &Connection, key: MetaInt) -> i64 {
+        connection
+            .query_row(
+                "SELECT value FROM meta_int WHERE key=:key",
+                named_params! {":key": key as i64},
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    fn has_current_schema(connection: &Connection) -> bool {
+        SCHEMA_CHECK_QUERIES
+            .iter()
+            .all(|query| connection.prepare(query).is_ok())
+    }
+
+    /// Creates a database at the given schema version by running only the first `version`
+    /// migrations, the way an older executable would have.
+    fn db_at_version(version: usize) -> Connection {
+        let mut connection = Connection::open_in_memory().unwrap();
+        let transaction = connection.transaction().unwrap();
+        transaction
+            .execute(
+                "CREATE TABLE meta_int(key INTEGER PRIMARY KEY ASC, value INTEGER NOT NULL);",
+                (),
+            )
+            .unwrap();
+        for migration in MIGRATIONS.iter().take(version) {
+            migration(&transaction).unwrap();
+        }
+        for (key, value) in [
+            (MetaInt::Version, version as i64),
+            (MetaInt::MinimumVersion, MINIUM_COMPATIBLE_DB_VERSION),
+        ] {
+            transaction
+                .execute(
+                    "INSERT INTO meta_int (key, value) VALUES (:key, :value)",
+                    named_params! {":key": key as i64, ":value": value},
+                )
+                .unwrap();
+        }
+        transaction.commit().unwrap();
+        connection
+    }
+
+    #[test]
+    fn new_db() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        assert_eq!(initialize(&mut connection).unwrap(), None);
+        assert_eq!(
+            get_meta_int(&connection, MetaInt::Version),
+            CURRENT_DB_VERSION
+        );
+        assert_eq!(
+            get_meta_int(&connection, MetaInt::MinimumVersion),
+            MINIUM_COMPATIBLE_DB_VERSION
+        );
+        assert!(has_current_schema(&connection));
+    }
+
+    #[test]
+    fn upgrade_from_each_version() {
+        for version in 1..MIGRATIONS.len() {
+            let mut connection = db_at_version(version);
+            initialize(&mut connection).unwrap();
+            assert_eq!(
+                get_meta_int(&connection, MetaInt::Version),
+                CURRENT_DB_VERSION
+            );
+            assert!(has_current_schema(&connection));
+        }
+    }
+
+    #[test]
+    fn reopen_current() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        initialize(&mut connection).unwrap();
+        initialize(&mut connection).unwrap();
+        assert_eq!(
+            get_meta_int(&connection, MetaInt::Version),
+            CURRENT_DB_VERSION
+        );
+    }
+
+    #[test]
+    fn upgrade_keeps_data() {
+        let mut connection = db_at_version(1);
+        connection
+            .execute(
+                "INSERT INTO input_history (id, input, next, prev) VALUES (1, 'a = 1', NULL, NULL)",
+                (),
+            )
+            .unwrap();
+        connection
+            .execute("UPDATE input_history_tags SET value=1", ())
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO variable_history (name, numer, denom, last_used_by)
+                VALUES ('a', '1', '1', 1)",
+                (),
+            )
+            .unwrap();
+        assert_eq!(initialize(&mut connection).unwrap(), Some(1));
+        let pinned: i64 = connection
+            .query_row(
+                "SELECT pinned FROM variable_history WHERE name='a'",
+                (),
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(pinned, 0);
+    }
+
+    #[test]
+    fn incompatible_version() {
+        let mut connection = db_at_version(MIGRATIONS.len());
+        connection
+            .execute(
+                "UPDATE meta_int SET value=:value WHERE key=:key",
+                named_params! {
+                    ":key": MetaInt::MinimumVersion as i64,
+                    ":value": CURRENT_DB_VERSION + 1,
+                },
+            )
+            .unwrap();
+        assert!(initialize(&mut connection).is_err());
+    }
+}
+
+#[cfg(test)]
+mod damage_tests {
+    #[cfg(feature = "cli")]
+    use crate::input_history::InputHistory;
+    use crate::{
+        saved_data::{
+            find_damage, initialize, validate_profile_name, SavedData, DATA_ROOT_DIR_ENV_VAR_NAME,
+        },
+        storage::{SavedSetting, Storage},
+        units::{Dimension, Unit},
+        value::Value,
+        variable::Variable,
+        Args,
+    };
+    use clap::{parser::ValueSource, CommandFactory, FromArgMatches};
+    use num::{bigint::BigInt, rational::BigRational};
+    use rusqlite::Connection;
+    use std::{env, fs, path::Path};
+
+    fn new_saved_data() -> SavedData {
+        SavedData::open_in_memory().unwrap()
+    }
+
+    /// Like `new_saved_data`, but the database is stored at `path` so that it can be reopened.
+    fn saved_data_at(path: &Path) -> SavedData {
+        let mut connection = Connection::open(path).unwrap();
+        let input_history_position = initialize(&mut connection).unwrap();
+        SavedData {
+            connection,
+            maybe_profile: None,
+            input_history_position,
+            maybe_rebuild_report: None,
+            maybe_session_id: None,
+            in_batch: false,
+        }
+    }
+
+    fn stored_inputs(saved_data: &SavedData) -> Vec<String> {
+        let mut statement = saved_data
+            .connection
+            .prepare("SELECT input FROM input_history ORDER BY id")
+            .unwrap();
+        let inputs = statement
+            .query_map((), |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<String>, _>>()
+            .unwrap();
+        inputs
+    }
+
+    fn add_inputs(saved_data: &mut SavedData, inputs: &[&str]) {
+        for input in inputs {
+            saved_data.add_to_input_history(input).unwrap();
+        }
+    }
+
+    #[test]
+    fn replace_during_batch() {
+        let path =
+            std::env::temp_dir().join(format!("bcalc_test_replace_{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut saved_data = saved_data_at(&path);
+        saved_data.begin_batch().unwrap();
+        add_inputs(&mut saved_data, &["1 + 1"]);
+        saved_data.replace(new_saved_data()).unwrap();
+        add_inputs(&mut saved_data, &["2 + 2"]);
+        // The batch was carried over to the new database, so there is still one to end.
+        saved_data.end_batch().unwrap();
+        assert_eq!(stored_inputs(&saved_data), ["2 + 2"]);
+        // And the write made before the switch was committed rather than lost.
+        assert_eq!(stored_inputs(&saved_data_at(&path)), ["1 + 1"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn restore_settings() {
+        let path =
+            std::env::temp_dir().join(format!("bcalc_test_settings_{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut saved_data = saved_data_at(&path);
+        saved_data
+            .set_setting(SavedSetting::Radix, Some(16))
+            .unwrap();
+        saved_data
+            .set_setting(SavedSetting::Precision, Some(30))
+            .unwrap();
+        saved_data
+            .set_setting(SavedSetting::NoSiSuffixes, Some(true.into()))
+            .unwrap();
+        drop(saved_data);
+
+        // Settings given on the command line take precedence over the saved ones.
+        let arg_matches = Args::command().get_matches_from(["bcalc", "-p", "8"]);
+        let mut args = Args::from_arg_matches(&arg_matches).unwrap();
+        args.restore_saved_settings(&mut saved_data_at(&path), |id| {
+            arg_matches.value_source(id) == Some(ValueSource::CommandLine)
+        })
+        .unwrap();
+        assert_eq!(args.radix, 16);
+        assert_eq!(args.precision, 8);
+        assert!(args.no_si_suffixes);
+
+        // A setting that was removed goes back to its default.
+        let mut saved_data = saved_data_at(&path);
+        saved_data.set_setting(SavedSetting::Radix, None).unwrap();
+        let mut args = Args::default();
+        args.restore_saved_settings(&mut saved_data, |_| false)
+            .unwrap();
+        assert_eq!(args.radix, 10);
+        assert_eq!(args.precision, 30);
+        drop(saved_data);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn pinned_variable_survives_eviction() {
+        let mut saved_data = new_saved_data();
+        saved_data.set_max_history_size(3).unwrap();
+        for (name, value) in [("$a", 1), ("$b", 2)] {
+            let id = saved_data
+                .add_to_input_history(&format!("{} = {}", name, value))
+                .unwrap();
+            let variable = Variable {
+                name: name.into(),
+                value: BigInt::from(value).into(),
+            };
+            saved_data.set_variable(&variable, id).unwrap();
+        }
+        assert!(saved_data.set_variable_pinned("$a", true).unwrap());
+        add_inputs(&mut saved_data, &["3", "4", "5", "6"]);
+        // Both inputs that set variables were evicted, but only the unpinned variable went with
+        // them.
+        assert_eq!(stored_inputs(&saved_data), ["4", "5", "6"]);
+        assert_eq!(
+            saved_data.get_variable("$a").unwrap().unwrap().value,
+            Value::from(BigInt::from(1))
+        );
+        assert!(saved_data.get_variable("$b").unwrap().is_none());
+        assert_eq!(saved_data.get_pinned_variable_names().unwrap(), ["$a"]);
+        assert_eq!(find_damage(&saved_data.connection).unwrap(), None);
+    }
+
+    #[test]
+    fn profiles() {
+        // This is the only test that goes through `SavedData::open`, so it has the data directory
+        // environment variable to itself.
+        let root = std::env::temp_dir().join(format!("bcalc_test_profiles_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir(&root).unwrap();
+        env::set_var(DATA_ROOT_DIR_ENV_VAR_NAME, &root);
+
+        let mut saved_data = SavedData::open(None).unwrap().unwrap();
+        assert_eq!(saved_data.profile(), "default");
+        add_inputs(&mut saved_data, &["1 + 1"]);
+        saved_data
+            .set_setting(SavedSetting::Radix, Some(16))
+            .unwrap();
+
+        // Switching to a new profile starts out with nothing from the old one.
+        saved_data
+            .replace(SavedData::open(Some("work")).unwrap().unwrap())
+            .unwrap();
+        assert_eq!(saved_data.profile(), "work");
+        assert!(stored_inputs(&saved_data).is_empty());
+        assert_eq!(saved_data.get_setting(SavedSetting::Radix).unwrap(), None);
+        add_inputs(&mut saved_data, &["2 + 2"]);
+
+        // Naming the default profile is the same as not naming one.
+        saved_data
+            .replace(SavedData::open(Some("default")).unwrap().unwrap())
+            .unwrap();
+        assert_eq!(saved_data.profile(), "default");
+        assert_eq!(stored_inputs(&saved_data), ["1 + 1"]);
+        assert_eq!(
+            saved_data.get_setting(SavedSetting::Radix).unwrap(),
+            Some(16)
+        );
+        assert_eq!(
+            stored_inputs(&SavedData::open(Some("work")).unwrap().unwrap()),
+            ["2 + 2"]
+        );
+
+        assert!(validate_profile_name("work-2_b").is_ok());
+        assert!(validate_profile_name("../work").is_err());
+        assert!(validate_profile_name("").is_err());
+        drop(saved_data);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn import_history_with_results() {
+        let mut saved_data = new_saved_data();
+        add_inputs(&mut saved_data, &["1 + 1"]);
+        InputHistory::new(true)
+            .import_with_results(
+                vec![
+                    ("2 * 3".to_string(), Some("6".to_string())),
+                    ("/help".to_string(), None),
+                ],
+                Some(&mut saved_data),
+            )
+            .unwrap();
+        let entries: Vec<(String, Option<String>)> = saved_data
+            .get_all_input_history_entries()
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.input, entry.maybe_result))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("1 + 1".to_string(), None),
+                ("2 * 3".to_string(), Some("6".to_string())),
+                ("/help".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn sessions() {
+        let path =
+            std::env::temp_dir().join(format!("bcalc_test_sessions_{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut saved_data = saved_data_at(&path);
+        assert_eq!(saved_data.current_session(), None);
+        add_inputs(&mut saved_data, &["1", "2"]);
+        let first_session = saved_data.current_session().unwrap();
+        drop(saved_data);
+
+        // Reopening the database starts a new session once something is stored.
+        let mut saved_data = saved_data_at(&path);
+        assert_eq!(saved_data.current_session(), None);
+        add_inputs(&mut saved_data, &["3"]);
+        let second_session = saved_data.current_session().unwrap();
+        assert_ne!(first_session, second_session);
+        let summaries = |saved_data: &mut SavedData| -> Vec<(i64, i64)> {
+            saved_data
+                .get_sessions()
+                .unwrap()
+                .iter()
+                .map(|session| (session.id, session.input_count))
+                .collect()
+        };
+        assert_eq!(
+            summaries(&mut saved_data),
+            [(first_session, 2), (second_session, 1)]
+        );
+        let entries = saved_data.get_session_inputs(first_session).unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.input.as_str())
+                .collect::<Vec<&str>>(),
+            ["1", "2"]
+        );
+        assert!(entries
+            .iter()
+            .all(|entry| entry.maybe_session == Some(first_session)));
+
+        // Once all of a session's inputs have been evicted, the session is no longer listed.
+        saved_data.set_max_history_size(1).unwrap();
+        assert_eq!(summaries(&mut saved_data), [(second_session, 1)]);
+        assert!(saved_data
+            .get_session_inputs(first_session)
+            .unwrap()
+            .is_empty());
+        drop(saved_data);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn undamaged() {
+        let mut saved_data = new_saved_data();
+        assert_eq!(find_damage(&saved_data.connection).unwrap(), None);
+        add_inputs(&mut saved_data, &["1", "2", "3"]);
+        assert_eq!(find_damage(&saved_data.connection).unwrap(), None);
+    }
+
+    #[test]
+    fn broken_link() {
+        let mut saved_data = new_saved_data();
+        add_inputs(&mut saved_data, &["1", "2", "3"]);
+        saved_data
+            .connection
+            .execute("UPDATE input_history SET prev=NULL WHERE input='3'", ())
+            .unwrap();
+        assert!(find_damage(&saved_data.connection).unwrap().is_some());
+    }
+
+    #[test]
+    fn unlinked_entry() {
+        let mut saved_data = new_saved_data();
+        add_inputs(&mut saved_data, &["1", "2"]);
+        saved_data
+            .connection
+            .execute(
+                "INSERT INTO input_history (input, next, prev) VALUES ('x', NULL, NULL)",
+                (),
+            )
+            .unwrap();
+        assert!(find_damage(&saved_data.connection).unwrap().is_some());
+    }
+
+    #[test]
+    fn cycle() {
+        let mut saved_data = new_saved_data();
+        add_inputs(&mut saved_data, &["1", "2"]);
+        saved_data
+            .connection
+            .execute(
+                "UPDATE input_history SET next=(SELECT id FROM input_history WHERE input='1')
+                    WHERE input='2'",
+                (),
+            )
+            .unwrap();
+        assert!(find_damage(&saved_data.connection).unwrap().is_some());
+    }
+
+    #[test]
+    fn missing_table() {
+        let saved_data = new_saved_data();
+        saved_data
+            .connection
+            .execute("DROP TABLE favorite_inputs", ())
+            .unwrap();
+        assert!(find_damage(&saved_data.connection).unwrap().is_some());
+    }
+
+    #[test]
+    fn variable_format() {
+        let mut saved_data = new_saved_data();
+        add_inputs(&mut saved_data, &["$a = 1"]);
+        let variable = |value: i64| Variable {
+            name: "$a".into(),
+            value: BigInt::from(value).into(),
+        };
+        saved_data.set_variable(&variable(1), 1).unwrap();
+        saved_data.set_variable_format("$a", Some("hex")).unwrap();
+        // Setting a new value keeps the format.
+        saved_data.set_variable(&variable(2), 1).unwrap();
+        assert_eq!(
+            saved_data.get_variable_format("$a").unwrap().as_deref(),
+            Some("hex")
+        );
+        saved_data.set_variable_format("$a", None).unwrap();
+        assert_eq!(saved_data.get_variable_format("$a").unwrap(), None);
+        assert_eq!(saved_data.get_variable_format("$b").unwrap(), None);
+    }
+
+    #[test]
+    fn complex_variables() {
+        let mut saved_data = new_saved_data();
+        add_inputs(&mut saved_data, &["$z = 2 - i/3"]);
+        let variable = |value: Value| Variable {
+            name: "$z".into(),
+            value,
+        };
+        let complex = Value::complex(
+            BigRational::from_integer(BigInt::from(2)),
+            BigRational::new(BigInt::from(-1), BigInt::from(3)),
+        );
+        saved_data
+            .set_variable(&variable(complex.clone()), 1)
+            .unwrap();
+        assert_eq!(
+            saved_data.get_variable("$z").unwrap().unwrap().value,
+            complex
+        );
+        // A real value with the same real part is a different value, so it makes a new version.
+        let real = Value::from(BigInt::from(2));
+        saved_data.set_variable(&variable(real.clone()), 1).unwrap();
+        saved_data.set_variable(&variable(real.clone()), 1).unwrap();
+        assert_eq!(saved_data.get_variable("$z").unwrap().unwrap().value, real);
+        assert_eq!(saved_data.get_variable_versions("$z").unwrap(), [complex]);
+    }
+
+    #[test]
+    fn interval_variables() {
+        let mut saved_data = new_saved_data();
+        add_inputs(&mut saved_data, &["$x = 5 ± 0.1"]);
+        let variable = |value: Value| Variable {
+            name: "$x".into(),
+            value,
+        };
+        let interval = Value::interval(
+            BigRational::new(BigInt::from(49), BigInt::from(10)),
+            BigRational::new(BigInt::from(51), BigInt::from(10)),
+        );
+        saved_data
+            .set_variable(&variable(interval.clone()), 1)
+            .unwrap();
+        assert_eq!(
+            saved_data.get_variable("$x").unwrap().unwrap().value,
+            interval
+        );
+        // An exact value at the low end is a different value, so it makes a new version.
+        let exact = Value::from(BigRational::new(BigInt::from(49), BigInt::from(10)));
+        saved_data
+            .set_variable(&variable(exact.clone()), 1)
+            .unwrap();
+        assert_eq!(saved_data.get_variable("$x").unwrap().unwrap().value, exact);
+        assert_eq!(saved_data.get_variable_versions("$x").unwrap(), [interval]);
+    }
+
+    #[test]
+    fn surd_variables() {
+        let mut saved_data = new_saved_data();
+        add_inputs(&mut saved_data, &["$x = sqrt(8)"]);
+        let variable = |value: Value| Variable {
+            name: "$x".into(),
+            value,
+        };
+        let approximation = BigRational::new(BigInt::from(282843), BigInt::from(100000));
+        let surd = Value::surd(
+            BigRational::from_integer(BigInt::from(2)),
+            BigInt::from(2),
+            approximation.clone(),
+        );
+        saved_data.set_variable(&variable(surd.clone()), 1).unwrap();
+        let stored = saved_data.get_variable("$x").unwrap().unwrap().value;
+        assert_eq!(stored, surd);
+        assert_eq!(stored.as_real(), Some(&approximation));
+        // The approximation alone is a different value, so it makes a new version.
+        let exact = Value::from(approximation);
+        saved_data
+            .set_variable(&variable(exact.clone()), 1)
+            .unwrap();
+        assert_eq!(saved_data.get_variable("$x").unwrap().unwrap().value, exact);
+        assert_eq!(saved_data.get_variable_versions("$x").unwrap(), [surd]);
+    }
+
+    #[test]
+    fn salvage() {
+        let mut damaged = new_saved_data();
+        add_inputs(&mut damaged, &["$a = 1", "$b = 2", "3"]);
+        let b_id: i64 = damaged
+            .connection
+            .query_row(
+                "SELECT id FROM input_history WHERE input='$b = 2'",
+                (),
+                |row| row.get(0),
+            )
+            .unwrap();
+        damaged
+            .connection
+            .execute(
+                "INSERT INTO variable_history (name, numer, denom, last_used_by, pinned, format)
+                    VALUES ('$b', '2', '1', :id, 1, 'hex')",
+                rusqlite::named_params! {":id": b_id},
+            )
+            .unwrap();
+        damaged.add_favorite("3").unwrap();
+        let furlong = Unit {
+            names: vec!["furlong".to_string()],
+            dimension: Dimension::LENGTH,
+            factor: BigRational::new(BigInt::from(25_146), BigInt::from(125)),
+            is_custom: true,
+        };
+        damaged.set_custom_unit(&furlong).unwrap();
+        damaged.set_snippet("pyth", "sqrt(?a^2 + ?b^2)").unwrap();
+        damaged
+            .connection
+            .execute("UPDATE input_history SET next=NULL", ())
+            .unwrap();
+
+        let mut rebuilt = new_saved_data();
+        rebuilt.salvage(&damaged.connection).unwrap();
+        assert_eq!(find_damage(&rebuilt.connection).unwrap(), None);
+        assert_eq!(
+            rebuilt.get_all_input_history().unwrap(),
+            vec!["$a = 1", "$b = 2", "3"]
+        );
+        assert_eq!(rebuilt.get_pinned_variable_names().unwrap(), vec!["$b"]);
+        assert_eq!(
+            rebuilt.get_variable_format("$b").unwrap().as_deref(),
+            Some("hex")
+        );
+        assert_eq!(rebuilt.get_favorites().unwrap(), vec!["3"]);
+        let sessions = rebuilt.get_sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, damaged.current_session().unwrap());
+        assert_eq!(sessions[0].input_count, 3);
+
+        let units = rebuilt.get_custom_units().unwrap();
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].name(), "furlong");
+        assert_eq!(units[0].dimension, Dimension::LENGTH);
+        assert_eq!(units[0].factor, furlong.factor);
+        assert!(rebuilt.remove_custom_unit("furlong").unwrap());
+        assert!(!rebuilt.remove_custom_unit("furlong").unwrap());
+        assert!(rebuilt.get_custom_units().unwrap().is_empty());
+
+        assert_eq!(
+            rebuilt.get_snippets().unwrap(),
+            vec![("pyth".to_string(), "sqrt(?a^2 + ?b^2)".to_string())]
+        );
+        assert!(rebuilt.remove_snippet("pyth").unwrap());
+        assert!(!rebuilt.remove_snippet("pyth").unwrap());
     }
-    Ok(())
 }
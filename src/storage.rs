@@ -0,0 +1,146 @@
+//! The interface between the calculation engine and wherever input history, variables, and
+//! settings are persisted. The bcalc executable uses `SavedData`, which stores them in an SQLite
+//! database, but library users can supply their own backend. Anything that accepts an
+//! `Option<&mut dyn Storage>` can also be given `None` to keep everything in memory.
+
+use crate::variable::Variable;
+
+/// Settings that can be remembered across sessions. Each corresponds to a field in `Args`. Values
+/// are stored as integers, with boolean settings stored as `0` or `1`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SavedSetting {
+    Radix,
+    // When `Args::convert_to_radix` is `None`, this setting is removed from storage entirely.
+    ConvertToRadix,
+    Precision,
+    ExtraPrecision,
+    Fractional,
+    // When `Args::max_denominator` is `None`, this setting is removed from storage entirely.
+    MaxDenominator,
+    Factored,
+    FractionStyle,
+    // When `Args::sci_above` or `Args::sci_below` is `None`, the setting is removed from storage
+    // entirely.
+    SciAbove,
+    SciBelow,
+    Notation,
+    Commas,
+    GroupSize,
+    GroupSeparator,
+    Bases,
+    DecimalComma,
+    Upper,
+    RecallCommands,
+    ErrorAlert,
+    CloseParens,
+    Strict,
+    AngleUnit,
+    NoSiSuffixes,
+    MilliSuffix,
+}
+
+pub trait Storage {
+    /// Adds the input to the input history and returns its id, which can be passed to the other
+    /// methods to associate data with the input.
+    fn add_to_input_history(&mut self, input: &str) -> Result<i64, Box<dyn std::error::Error>>;
+
+    /// Records what the input history item with the given id evaluated to. If the item is no
+    /// longer in the history, this should do nothing.
+    fn set_input_history_result(
+        &mut self,
+        id: i64,
+        result: &str,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Returns every input in the input history, ordered from oldest to newest.
+    fn get_all_input_history(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>>;
+
+    /// Stores the variable, overwriting any existing variable with the same name.
+    /// `last_used_by_id` is the id of the input history item that set the variable. Backends that
+    /// evict old inputs may also discard variables once the input that last used them is gone.
+    fn set_variable(
+        &mut self,
+        var: &Variable,
+        last_used_by_id: i64,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Records that the variable was used by the input history item with the given id. If the
+    /// variable isn't stored, this should do nothing.
+    fn touch_variable(
+        &mut self,
+        name: &str,
+        last_used_by_id: i64,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Returns `Ok(None)` if the variable isn't stored.
+    fn get_variable(&mut self, name: &str) -> Result<Option<Variable>, Box<dyn std::error::Error>>;
+
+    /// Returns the names of all the stored variables. Only used to suggest names when a variable
+    /// isn't found, so backends that can't list their variables may leave this unimplemented.
+    fn get_variable_names(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(Vec::new())
+    }
+
+    /// Returns the display format attached to the variable with `/varformat`, in the form that
+    /// `VariableFormat::parse` reads. Returns `Ok(None)` if the variable has no format or isn't
+    /// stored. Backends that don't keep formats may leave this unimplemented.
+    fn get_variable_format(
+        &mut self,
+        _name: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        Ok(None)
+    }
+
+    /// Attaches the format to the variable, or removes its format if `maybe_format` is `None`. If
+    /// the variable isn't stored, this should do nothing. Formats should be kept when the variable
+    /// is set to a new value, but removed along with the variable.
+    fn set_variable_format(
+        &mut self,
+        _name: &str,
+        _maybe_format: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Removes the variable. Returns `Ok` if the variable wasn't stored.
+    fn clear_variable(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Retrieves the saved value of the given setting. Returns `Ok(None)` if the setting has never
+    /// been saved (or, for `SavedSetting::ConvertToRadix` and `SavedSetting::MaxDenominator`, was
+    /// saved as `None`).
+    fn get_setting(
+        &mut self,
+        setting: SavedSetting,
+    ) -> Result<Option<i64>, Box<dyn std::error::Error>>;
+
+    /// Saves the value of the given setting. Passing `None` removes the saved value.
+    fn set_setting(
+        &mut self,
+        setting: SavedSetting,
+        maybe_value: Option<i64>,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Called before the group of changes made while handling a single input, so that backends can
+    /// write them all at once rather than one at a time. Each call is followed by a call to
+    /// `end_batch`, even if handling the input fails part way through. Batches are never nested.
+    fn begin_batch(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Called once the changes that followed `begin_batch` have all been made.
+    fn end_batch(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
+
+/// Reborrows optional storage so that it can be passed along without giving up the original.
+/// `Option::as_deref_mut` can't be used for this, since it would require the reborrow to last as
+/// long as the original reference.
+pub fn reborrow<'a>(
+    maybe_storage: &'a mut Option<&mut dyn Storage>,
+) -> Option<&'a mut dyn Storage> {
+    match maybe_storage {
+        Some(storage) => Some(&mut **storage),
+        None => None,
+    }
+}
@@ -0,0 +1,104 @@
+//! "Did you mean" suggestions for names that don't match any command, function, unit, or variable.
+//! Suggestions are the known names that are the fewest edits away from the unknown name.
+
+/// The most suggestions that will be offered for a single name.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// The number of single character insertions, deletions, substitutions, and swaps of adjacent
+/// characters needed to turn `a` into `b`. Swaps are counted as a single edit since they are such a
+/// common typo.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // `distances[i][j]` is the distance between the first `i` characters of `a` and the first `j`
+    // characters of `b`.
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+/// The candidates that are close enough to `name` that it was plausibly a typo of them, closest
+/// first. Short names allow only a single edit so that nearly everything isn't suggested for them.
+pub fn closest_matches<'a, I>(name: &str, candidates: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = (name.chars().count() / 3).clamp(1, 3);
+    let mut matches: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    matches.sort();
+    matches.dedup();
+    matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// Formats suggestions to be appended to an error message, like ` (did you mean max or min?)`.
+/// Returns an empty string if there are no suggestions.
+pub fn format_suggestions(suggestions: &[String]) -> String {
+    let list = match suggestions {
+        [] => return String::new(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} or {}", rest.join(", "), last),
+    };
+    format!(" (did you mean {}?)", list)
+}
+
+#[cfg(test)]
+mod suggestion_tests {
+    use crate::suggestion::{closest_matches, edit_distance, format_suggestions};
+
+    #[test]
+    fn distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("max", "max"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("mxa", "max"), 1);
+        assert_eq!(edit_distance("hepl", "help"), 1);
+    }
+
+    #[test]
+    fn suggestions() {
+        let candidates = ["max", "min", "network", "netmask", "help", "hosts"];
+        assert_eq!(closest_matches("mx", candidates), vec!["max"]);
+        assert_eq!(closest_matches("mix", candidates), vec!["max", "min"]);
+        assert_eq!(closest_matches("netwrk", candidates), vec!["network"]);
+        assert_eq!(closest_matches("netmsk", candidates), vec!["netmask"]);
+        assert!(closest_matches("xyz", candidates).is_empty());
+        assert!(closest_matches("max", candidates).is_empty());
+
+        assert_eq!(format_suggestions(&[]), "");
+        assert_eq!(
+            format_suggestions(&["max".to_string()]),
+            " (did you mean max?)"
+        );
+        assert_eq!(
+            format_suggestions(&["a".to_string(), "b".to_string(), "c".to_string()]),
+            " (did you mean a, b or c?)"
+        );
+    }
+}
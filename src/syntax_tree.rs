@@ -1,48 +1,252 @@
 use crate::{
+    constants::{find_constant, MathConstant},
     error::{
         CalculatorFailure,
-        MathExecutionError::{DivisionByZero, FunctionNeedsArguments, UnknownVariable},
-        MissingCapabilityError::NoVariableStore,
+        MathExecutionError::{
+            self, CustomFunctionFailed, DimensionMismatch, DivisionByZero, FunctionNeedsArguments,
+            InvalidArgument, InvalidDimensionPower, InvalidOperand, MultipleValues, NotReal,
+            Uncertain, UncertainComplex, UnknownVariable,
+        },
+        MissingCapabilityError::{NoInputHistory, NoVariableStore},
         SyntaxError::{
-            self, CommaWithoutOperandAfter, CommaWithoutOperandBefore, EmptyParens,
-            FunctionWithoutParensOrArgument, MismatchedCloseParen, MismatchedOpenParen,
-            MissingOperand, MissingOperator, NoInput, UnexpectedToken,
+            self, CidrOutsideFunction, CommaWithoutOperandAfter, CommaWithoutOperandBefore,
+            ConditionalWithoutColon, EmptyParens, FunctionWithoutParens,
+            FunctionWithoutParensOrArgument, LetWithoutIn, LetWithoutVariable, MismatchedBar,
+            MismatchedCloseParen, MismatchedOpenParen, MissingOperand, MissingOperator, NoInput,
+            ReservedVariable, UnexpectedToken,
         },
+        Warning,
+    },
+    interrupt::Interrupt,
+    network::{
+        address_argument, broadcast_address, host_count, in_subnet, netmask, network_address,
+        prefix_length_argument,
+    },
+    operations::{
+        arccosine, arcsine, arctangent, combinations, cosine, exponential, exponentiate,
+        extract_bits, factorial, is_tangent_undefined, logarithm, mean, median, natural_log,
+        permutations, pi, prime_factors, replace_bits, sine, tangent, variance, MAX_BIT_INDEX,
+        MAX_EXPONENTIAL_ARGUMENT,
     },
-    operations::exponentiate,
     position::{Position, Positioned},
-    saved_data::SavedData,
+    storage::{reborrow, Storage},
+    suggestion::closest_matches,
     token::{
         BinaryOperatorToken, FunctionNameToken, Token, UnaryOperatorToken, ORDERED_BINARY_OPERATORS,
     },
-    variable::{Variable, VariableStore},
-    Args,
+    units::{expression_unit, is_radian, Dimension},
+    value::Value,
+    variable::{answer_index, Variable, VariableName, VariableStore},
+    AngleUnit, Args,
 };
 use num::{
     bigint::{BigInt, ToBigInt},
     rational::BigRational,
-    Signed,
+    One, Signed, ToPrimitive, Zero,
 };
 use std::{
-    cmp::{max, min},
+    cmp::{max, min, Ordering},
     collections::VecDeque,
-    mem,
+    fmt, mem,
 };
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Identifies a node in the syntax tree, such as an operator or a number.
+#[derive(Clone, Debug)]
+pub enum NodeKind {
+    Number(BigRational),
+    /// A number with units. The value is in the base unit of the dimension.
+    Quantity(BigRational, Dimension),
+    /// An imaginary number, like `4i`. Holds the number that `i` is multiplied by.
+    Imaginary(BigRational),
+    Variable(VariableName),
+    Constant(MathConstant),
+    Unary(UnaryOperatorToken),
+    Binary(BinaryOperatorToken),
+    Function(FunctionNameToken),
+    Parenthesized,
+    /// A `let` expression, which binds a value to the variable while its body is evaluated.
+    Let(VariableName),
+    /// `condition ? if_true : if_false`.
+    Conditional,
+    /// Several comparisons chained together, like `1 < $x <= 10`. Holds the operators in order.
+    ComparisonChain(Vec<BinaryOperatorToken>),
+}
+
+impl fmt::Display for NodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NodeKind::Number(n) => write!(f, "Number ({})", n),
+            NodeKind::Quantity(n, dimension) => write!(f, "Quantity ({}, {})", n, dimension),
+            NodeKind::Imaginary(n) => write!(f, "Imaginary Number ({}i)", n),
+            NodeKind::Variable(name) => write!(f, "Variable '{}'", name),
+            NodeKind::Constant(constant) => write!(f, "Constant ({})", constant),
+            NodeKind::Unary(operator) => fmt::Display::fmt(operator, f),
+            NodeKind::Binary(operator) => fmt::Display::fmt(operator, f),
+            NodeKind::Function(function_name) => fmt::Display::fmt(function_name, f),
+            NodeKind::Parenthesized => write!(f, "Parentheses"),
+            NodeKind::Let(name) => write!(f, "Let Binding '{}'", name),
+            NodeKind::Conditional => write!(f, "Conditional Operator (? :)"),
+            NodeKind::ComparisonChain(operators) => {
+                let operators: Vec<String> = operators
+                    .iter()
+                    .map(|operator| operator.to_string())
+                    .collect();
+                write!(f, "Comparison Chain ({})", operators.join(", "))
+            }
+        }
+    }
+}
+
+/// One step of evaluating a syntax tree, as reported to the observer given to
+/// `SyntaxTree::execute_traced`.
+pub struct TraceStep<'a> {
+    pub kind: NodeKind,
+    /// The position of the whole node, including its operands, within the input.
+    pub position: Position,
+    /// What the node evaluated to.
+    pub value: &'a Value,
+}
+
+// Reborrows the optional observer so that it can be passed to each operand in turn. See
+// `storage::reborrow` for why `Option::as_deref_mut` won't do.
+fn reborrow_observer<'a>(
+    maybe_observer: &'a mut Option<&mut dyn FnMut(&TraceStep)>,
+) -> Option<&'a mut dyn FnMut(&TraceStep)> {
+    match maybe_observer {
+        Some(observer) => Some(&mut **observer),
+        None => None,
+    }
+}
+
+/// The values bound by the `let` expressions that enclose the part of the expression being
+/// evaluated. Each binding links to the scope that it was made in, so inner bindings shadow outer
+/// ones, and bindings disappear once evaluation leaves their `let`. Bound values are never stored
+/// in the `VariableStore`.
+struct Scope<'a> {
+    name: &'a VariableName,
+    value: Value,
+    maybe_outer: Option<&'a Scope<'a>>,
+}
+
+impl Scope<'_> {
+    fn get(&self, name: &VariableName) -> Option<&Value> {
+        if self.name == name {
+            return Some(&self.value);
+        }
+        self.maybe_outer.and_then(|outer| outer.get(name))
+    }
+}
+
 trait OperationNode {
+    #[allow(clippy::too_many_arguments)]
     fn execute(
         self: Box<Self>,
         maybe_vars: Option<&mut VariableStore>,
-        maybe_db: Option<&mut SavedData>,
+        maybe_db: Option<&mut dyn Storage>,
         args: &Args,
-    ) -> Result<BigRational, CalculatorFailure>;
+        maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_scope: Option<&Scope>,
+        maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure>;
+
+    /// Determines the dimension of the value that the node will evaluate to, without evaluating it.
+    /// Returns an error if the node combines values whose dimensions are incompatible. Returns
+    /// `None` if the dimension can't be known ahead of time, such as for variables, which only
+    /// store plain numbers. Unknown dimensions are assumed to be compatible with anything.
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure>;
 
     fn position(&self) -> Position;
 }
 
+// Combines the dimensions of values that must have the same dimension, such as the operands of an
+// addition. If they differ, the error spans both values, from `position_1` to `position_2`.
+fn same_dimension(
+    maybe_dimension_1: Option<Dimension>,
+    position_1: Position,
+    maybe_dimension_2: Option<Dimension>,
+    position_2: Position,
+) -> Result<Option<Dimension>, CalculatorFailure> {
+    match (maybe_dimension_1, maybe_dimension_2) {
+        (Some(dimension_1), Some(dimension_2)) if dimension_1 != dimension_2 => {
+            Err(Positioned::new(
+                DimensionMismatch(dimension_1, dimension_2),
+                Position::from_span(position_1, position_2),
+            )
+            .into())
+        }
+        _ => Ok(maybe_dimension_1.or(maybe_dimension_2)),
+    }
+}
+
+// Checks that a value is an exact real number, for operators and functions (described by
+// `operation`) that don't work with complex numbers or uncertain values. The error points at
+// `position`, which should be where the value came from. Surds give their approximation.
+fn real_operand(
+    value: Value,
+    operation: &impl fmt::Display,
+    position: Position,
+) -> Result<BigRational, CalculatorFailure> {
+    match value {
+        Value::Real(value)
+        | Value::Surd {
+            approximation: value,
+            ..
+        } => Ok(value),
+        Value::Complex { .. } => {
+            Err(Positioned::new(NotReal(operation.to_string()), position).into())
+        }
+        Value::Interval { .. } => {
+            Err(Positioned::new(Uncertain(operation.to_string()), position).into())
+        }
+    }
+}
+
+// Checks that values with an uncertainty aren't combined with complex numbers, since there is no
+// way to represent the result.
+fn check_not_mixed<'a>(
+    values: impl IntoIterator<Item = &'a Value>,
+    position: Position,
+) -> Result<(), CalculatorFailure> {
+    let (mut has_complex, mut has_interval) = (false, false);
+    for value in values {
+        has_complex |= value.is_complex();
+        has_interval |= value.is_interval();
+    }
+    if has_complex && has_interval {
+        Err(Positioned::new(UncertainComplex, position).into())
+    } else {
+        Ok(())
+    }
+}
+
+// Checks that a value that has to be a plain number, such as an exponent, has no units.
+fn require_no_units(
+    maybe_dimension: Option<Dimension>,
+    position: Position,
+) -> Result<(), CalculatorFailure> {
+    match maybe_dimension {
+        Some(dimension) if !dimension.is_none() => {
+            Err(Positioned::new(DimensionMismatch(dimension, Dimension::NONE), position).into())
+        }
+        _ => Ok(()),
+    }
+}
+
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct NumericNode {
+    #[cfg_attr(feature = "serde", serde(with = "crate::token::serde_rational"))]
     value: BigRational,
+    // Left out for plain numbers to keep the JSON simple.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Dimension::is_none")
+    )]
+    dimension: Dimension,
     position: Position,
 }
 
@@ -50,20 +254,61 @@ impl OperationNode for NumericNode {
     fn execute(
         self: Box<Self>,
         _maybe_vars: Option<&mut VariableStore>,
-        _maybe_db: Option<&mut SavedData>,
+        _maybe_db: Option<&mut dyn Storage>,
         _args: &Args,
-    ) -> Result<BigRational, CalculatorFailure> {
-        Ok(self.value)
+        _maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        _maybe_interrupt: Option<&Interrupt>,
+        _maybe_scope: Option<&Scope>,
+        _maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        Ok(Value::Real(self.value))
+    }
+
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        Ok(Some(self.dimension))
     }
 
     fn position(&self) -> Position {
-        self.position.clone()
+        self.position
     }
 }
 
+// An imaginary number written out in the expression, like `4i` or just `i`.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct ImaginaryNode {
+    #[cfg_attr(feature = "serde", serde(with = "crate::token::serde_rational"))]
+    value: BigRational,
+    position: Position,
+}
+
+impl OperationNode for ImaginaryNode {
+    fn execute(
+        self: Box<Self>,
+        _maybe_vars: Option<&mut VariableStore>,
+        _maybe_db: Option<&mut dyn Storage>,
+        _args: &Args,
+        _maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        _maybe_interrupt: Option<&Interrupt>,
+        _maybe_scope: Option<&Scope>,
+        _maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        Ok(Value::complex(BigRational::zero(), self.value))
+    }
+
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        Ok(Some(Dimension::NONE))
+    }
+
+    fn position(&self) -> Position {
+        self.position
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct VariableNode {
-    name: String,
+    name: VariableName,
     position: Position,
 }
 
@@ -71,25 +316,92 @@ impl OperationNode for VariableNode {
     fn execute(
         self: Box<Self>,
         maybe_vars: Option<&mut VariableStore>,
-        maybe_db: Option<&mut SavedData>,
+        maybe_db: Option<&mut dyn Storage>,
         _args: &Args,
-    ) -> Result<BigRational, CalculatorFailure> {
+        _maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        _maybe_interrupt: Option<&Interrupt>,
+        maybe_scope: Option<&Scope>,
+        _maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        if let Some(value) = maybe_scope.and_then(|scope| scope.get(&self.name)) {
+            return Ok(value.clone());
+        }
         let vars = match maybe_vars {
             Some(v) => v,
             None => return Err(Positioned::new(NoVariableStore, self.position).into()),
         };
-        let variable = vars
-            .get(self.name.clone(), maybe_db)?
-            .ok_or_else(|| Positioned::new(UnknownVariable(self.name), self.position))?;
-        Ok(variable.value)
+        let mut maybe_db = maybe_db;
+        if let Some(variable) = vars.get(self.name.clone(), reborrow(&mut maybe_db))? {
+            return Ok(variable.value);
+        }
+        let mut names: Vec<String> = vars.names().map(str::to_string).collect();
+        if let Some(db) = maybe_db {
+            names.extend(db.get_variable_names()?);
+        }
+        let suggestions = closest_matches(&self.name, names.iter().map(String::as_str));
+        Err(Positioned::new(
+            UnknownVariable(self.name.to_string(), suggestions),
+            self.position,
+        )
+        .into())
+    }
+
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        Ok(None)
+    }
+
+    fn position(&self) -> Position {
+        self.position
+    }
+}
+
+// A mathematical constant like `pi`. The value is computed when the node is executed rather than
+// when the tree is built, so that it is always as precise as the current settings ask for.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct ConstantNode {
+    constant: MathConstant,
+    position: Position,
+}
+
+impl OperationNode for ConstantNode {
+    fn execute(
+        self: Box<Self>,
+        _maybe_vars: Option<&mut VariableStore>,
+        _maybe_db: Option<&mut dyn Storage>,
+        args: &Args,
+        _maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        _maybe_scope: Option<&Scope>,
+        _maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        let total_precision = args.precision + args.extra_precision;
+        let result = match self.constant {
+            MathConstant::Pi => pi(1, total_precision, args.radix, maybe_interrupt),
+            MathConstant::E => exponential(
+                &BigRational::one(),
+                total_precision,
+                args.radix,
+                maybe_interrupt,
+            ),
+            MathConstant::Tau => pi(2, total_precision, args.radix, maybe_interrupt),
+        };
+        result
+            .map(Value::Real)
+            .map_err(|e| Positioned::new(e, self.position).into())
+    }
+
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        Ok(Some(Dimension::NONE))
     }
 
     fn position(&self) -> Position {
-        self.position.clone()
+        self.position
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct UnaryNode {
     operator: UnaryOperatorToken,
     operator_position: Position,
@@ -100,33 +412,99 @@ impl OperationNode for UnaryNode {
     fn execute(
         self: Box<Self>,
         mut maybe_vars: Option<&mut VariableStore>,
-        mut maybe_db: Option<&mut SavedData>,
+        mut maybe_db: Option<&mut dyn Storage>,
         args: &Args,
-    ) -> Result<BigRational, CalculatorFailure> {
-        let operand =
-            self.operand
-                .execute(maybe_vars.as_deref_mut(), maybe_db.as_deref_mut(), args)?;
+        mut maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_scope: Option<&Scope>,
+        maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        let operand_position = self.operand.position();
+        let operand = self.operand.execute(
+            maybe_vars.as_deref_mut(),
+            reborrow(&mut maybe_db),
+            args,
+            reborrow_observer(&mut maybe_observer),
+            maybe_interrupt,
+            maybe_scope,
+            maybe_warnings,
+        )?;
+        let total_precision = args.precision + args.extra_precision;
+        let one_half = BigRational::new(
+            ToBigInt::to_bigint(&1).unwrap(),
+            ToBigInt::to_bigint(&2).unwrap(),
+        );
         match self.operator {
-            UnaryOperatorToken::SquareRoot => {
-                let total_precision = args.precision + args.extra_precision;
-                let one_half = BigRational::new(
-                    ToBigInt::to_bigint(&1).unwrap(),
-                    ToBigInt::to_bigint(&2).unwrap(),
-                );
-                exponentiate(operand, one_half, total_precision, args.radix)
-                    .map_err(|e| Positioned::new(e, self.operator_position.clone()).into())
-            }
+            UnaryOperatorToken::SquareRoot => exponentiate(
+                operand,
+                one_half,
+                total_precision,
+                args.radix,
+                maybe_interrupt,
+            )
+            .map_err(|e| Positioned::new(e, self.operator_position).into()),
             UnaryOperatorToken::Negate => Ok(-operand),
-            UnaryOperatorToken::AbsoluteValue => Ok(operand.abs()),
+            // The absolute value of a complex number is its distance from 0.
+            UnaryOperatorToken::AbsoluteValue => match operand.abs() {
+                Some(abs) => Ok(abs),
+                None => exponentiate(
+                    Value::Real(operand.norm_sqr()),
+                    one_half,
+                    total_precision,
+                    args.radix,
+                    maybe_interrupt,
+                )
+                .map_err(|e| Positioned::new(e, self.operator_position).into()),
+            },
+            UnaryOperatorToken::Not => Ok(truth_value(!is_true(&operand))),
+            UnaryOperatorToken::Percent => {
+                Ok(operand * Value::Real(BigRational::new(BigInt::one(), BigInt::from(100))))
+            }
+            UnaryOperatorToken::Factorial => {
+                let operand = real_operand(operand, &self.operator, operand_position)?;
+                let invalid_operand = |message: String| -> CalculatorFailure {
+                    Positioned::new(InvalidOperand(self.operator, message), operand_position).into()
+                };
+                if !operand.is_integer() {
+                    return Err(invalid_operand(format!("{} is not an integer", operand)));
+                }
+                if operand.is_negative() {
+                    return Err(invalid_operand(format!("{} is negative", operand)));
+                }
+                factorial(&operand.to_integer(), maybe_interrupt)
+                    .map(Value::from)
+                    .map_err(|e| Positioned::new(e, self.operator_position).into())
+            }
+        }
+    }
+
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        let maybe_dimension = self.operand.dimension()?;
+        match (self.operator, maybe_dimension) {
+            (UnaryOperatorToken::SquareRoot, Some(dimension)) => match dimension.root(2) {
+                Some(root_dimension) => Ok(Some(root_dimension)),
+                None => Err(Positioned::new(
+                    InvalidDimensionPower(dimension),
+                    self.operator_position,
+                )
+                .into()),
+            },
+            (UnaryOperatorToken::Not, _) => Ok(Some(Dimension::NONE)),
+            (UnaryOperatorToken::Factorial, _) => {
+                require_no_units(maybe_dimension, self.operand.position())?;
+                Ok(Some(Dimension::NONE))
+            }
+            _ => Ok(maybe_dimension),
         }
     }
 
     fn position(&self) -> Position {
-        Position::from_span(self.operator_position.clone(), self.operand.position())
+        Position::from_span(self.operator_position, self.operand.position())
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct BinaryNode {
     operator: BinaryOperatorToken,
     operator_position: Position,
@@ -138,106 +516,1293 @@ impl OperationNode for BinaryNode {
     fn execute(
         self: Box<Self>,
         mut maybe_vars: Option<&mut VariableStore>,
-        mut maybe_db: Option<&mut SavedData>,
+        mut maybe_db: Option<&mut dyn Storage>,
         args: &Args,
-    ) -> Result<BigRational, CalculatorFailure> {
-        let operand_1 =
-            self.operand_1
-                .execute(maybe_vars.as_deref_mut(), maybe_db.as_deref_mut(), args)?;
-        let operand_2 =
-            self.operand_2
-                .execute(maybe_vars.as_deref_mut(), maybe_db.as_deref_mut(), args)?;
+        mut maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_scope: Option<&Scope>,
+        mut maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        let operand_positions = [self.operand_1.position(), self.operand_2.position()];
+        let operand_1 = self.operand_1.execute(
+            maybe_vars.as_deref_mut(),
+            reborrow(&mut maybe_db),
+            args,
+            reborrow_observer(&mut maybe_observer),
+            maybe_interrupt,
+            maybe_scope,
+            maybe_warnings.as_deref_mut(),
+        )?;
+        // `and` and `or` don't evaluate their second operand if the first one alone decides the
+        // result.
+        match (self.operator, is_true(&operand_1)) {
+            (BinaryOperatorToken::And, false) => return Ok(truth_value(false)),
+            (BinaryOperatorToken::Or, true) => return Ok(truth_value(true)),
+            _ => {}
+        }
+        let operand_2 = self.operand_2.execute(
+            maybe_vars.as_deref_mut(),
+            reborrow(&mut maybe_db),
+            args,
+            reborrow_observer(&mut maybe_observer),
+            maybe_interrupt,
+            maybe_scope,
+            maybe_warnings.as_deref_mut(),
+        )?;
+        // For the operators that only work with real numbers.
+        let real_operands = |operand_1: Value, operand_2: Value| {
+            Ok::<_, CalculatorFailure>((
+                real_operand(operand_1, &self.operator, operand_positions[0])?,
+                real_operand(operand_2, &self.operator, operand_positions[1])?,
+            ))
+        };
+        check_not_mixed([&operand_1, &operand_2], self.operator_position)?;
         match self.operator {
             BinaryOperatorToken::Add => Ok(operand_1 + operand_2),
             BinaryOperatorToken::Subtract => Ok(operand_1 - operand_2),
+            BinaryOperatorToken::PlusMinus => {
+                let (low, high) = operand_1.bounds().ok_or_else(|| {
+                    Positioned::new(NotReal(self.operator.to_string()), operand_positions[0])
+                })?;
+                let radius = real_operand(operand_2, &self.operator, operand_positions[1])?.abs();
+                Ok(Value::interval(low - &radius, high + radius))
+            }
             BinaryOperatorToken::Multiply => Ok(operand_1 * operand_2),
-            BinaryOperatorToken::Divide => {
-                if *operand_2.numer() == BigInt::from(0) {
+            BinaryOperatorToken::Divide => operand_1
+                .checked_div(&operand_2)
+                .ok_or_else(|| Positioned::new(DivisionByZero, self.operator_position).into()),
+            BinaryOperatorToken::Modulus => {
+                let (operand_1, operand_2) = real_operands(operand_1, operand_2)?;
+                if let Some(warnings) = maybe_warnings {
+                    if !operand_1.is_integer() || !operand_2.is_integer() {
+                        warnings.push(Warning::NonIntegerModulus);
+                    }
+                }
+                Ok(Value::Real(operand_1 % operand_2))
+            }
+            BinaryOperatorToken::IntegerDivide | BinaryOperatorToken::FloorModulus => {
+                let (operand_1, operand_2) = real_operands(operand_1, operand_2)?;
+                if operand_2.is_zero() {
                     return Err(Positioned::new(DivisionByZero, self.operator_position).into());
                 }
-                Ok(operand_1 / operand_2)
+                let quotient = (&operand_1 / &operand_2).floor();
+                if self.operator == BinaryOperatorToken::IntegerDivide {
+                    Ok(Value::Real(quotient))
+                } else {
+                    Ok(Value::Real(operand_1 - operand_2 * quotient))
+                }
+            }
+            BinaryOperatorToken::Less
+            | BinaryOperatorToken::LessOrEqual
+            | BinaryOperatorToken::Greater
+            | BinaryOperatorToken::GreaterOrEqual
+            | BinaryOperatorToken::Equal
+            | BinaryOperatorToken::NotEqual => {
+                comparison_holds(self.operator, &operand_1, &operand_2)
+                    .map(truth_value)
+                    .map_err(|e| Positioned::new(e, self.operator_position).into())
+            }
+            BinaryOperatorToken::And | BinaryOperatorToken::Or => {
+                Ok(truth_value(is_true(&operand_2)))
+            }
+            BinaryOperatorToken::Exponent => {
+                let exponent = real_operand(operand_2, &self.operator, operand_positions[1])?;
+                let total_precision = args.precision + args.extra_precision;
+                exponentiate(
+                    operand_1,
+                    exponent,
+                    total_precision,
+                    args.radix,
+                    maybe_interrupt,
+                )
+                .map_err(|e| Positioned::new(e, self.operator_position).into())
+            }
+        }
+    }
+
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        let maybe_dimension_1 = self.operand_1.dimension()?;
+        let maybe_dimension_2 = self.operand_2.dimension()?;
+        let (dimension_1, dimension_2) = match self.operator {
+            BinaryOperatorToken::Add
+            | BinaryOperatorToken::Subtract
+            | BinaryOperatorToken::PlusMinus
+            | BinaryOperatorToken::Modulus
+            | BinaryOperatorToken::FloorModulus => {
+                return same_dimension(
+                    maybe_dimension_1,
+                    self.operand_1.position(),
+                    maybe_dimension_2,
+                    self.operand_2.position(),
+                );
+            }
+            BinaryOperatorToken::Less
+            | BinaryOperatorToken::LessOrEqual
+            | BinaryOperatorToken::Greater
+            | BinaryOperatorToken::GreaterOrEqual
+            | BinaryOperatorToken::Equal
+            | BinaryOperatorToken::NotEqual => {
+                same_dimension(
+                    maybe_dimension_1,
+                    self.operand_1.position(),
+                    maybe_dimension_2,
+                    self.operand_2.position(),
+                )?;
+                return Ok(Some(Dimension::NONE));
+            }
+            // Values of any dimension can be used as conditions.
+            BinaryOperatorToken::And | BinaryOperatorToken::Or => return Ok(Some(Dimension::NONE)),
+            BinaryOperatorToken::Exponent => {
+                require_no_units(maybe_dimension_2, self.operand_2.position())?;
+                return match maybe_dimension_1 {
+                    Some(dimension) if !dimension.is_none() => {
+                        self.exponent_dimension(dimension).map(Some)
+                    }
+                    _ => Ok(maybe_dimension_1),
+                };
+            }
+            BinaryOperatorToken::Multiply
+            | BinaryOperatorToken::Divide
+            | BinaryOperatorToken::IntegerDivide => match (maybe_dimension_1, maybe_dimension_2) {
+                (Some(dimension_1), Some(dimension_2)) => (dimension_1, dimension_2),
+                _ => return Ok(None),
+            },
+        };
+        let maybe_result = if self.operator == BinaryOperatorToken::Multiply {
+            dimension_1.multiply(&dimension_2)
+        } else {
+            dimension_1.divide(&dimension_2)
+        };
+        match maybe_result {
+            Some(dimension) => Ok(Some(dimension)),
+            None => Err(Positioned::new(
+                InvalidDimensionPower(dimension_1),
+                self.operator_position,
+            )
+            .into()),
+        }
+    }
+
+    fn position(&self) -> Position {
+        Position::from_span(
+            self.operator_position,
+            Position::from_span(self.operand_1.position(), self.operand_2.position()),
+        )
+    }
+}
+
+impl BinaryNode {
+    // The dimension of `operand_1 ^ operand_2`, where `operand_1` has units. This can only be
+    // known if the exponent is written out in the expression, like the 2 in `(3 m) ^ 2`, and is an
+    // integer or the reciprocal of one.
+    fn exponent_dimension(&self, dimension: Dimension) -> Result<Dimension, CalculatorFailure> {
+        let invalid_power =
+            || Positioned::new(InvalidDimensionPower(dimension), self.operator_position).into();
+        let exponent = self.operand_2.constant_value().ok_or_else(invalid_power)?;
+        let maybe_result = if exponent.is_integer() {
+            exponent
+                .to_integer()
+                .to_i8()
+                .and_then(|power| dimension.pow(power))
+        } else if exponent.numer().abs() == BigInt::from(1) {
+            let degree = exponent.recip().to_integer().to_i8();
+            degree.and_then(|degree| dimension.root(degree))
+        } else {
+            None
+        };
+        maybe_result.ok_or_else(invalid_power)
+    }
+}
+
+// The value that a condition, such as a comparison, evaluates to: 1 if it holds and 0 if it doesn't.
+fn truth_value(holds: bool) -> Value {
+    Value::from(BigInt::from(u8::from(holds)))
+}
+
+// Whether a value counts as true when used as a condition. Any value other than 0 does.
+fn is_true(value: &Value) -> bool {
+    !value.is_zero()
+}
+
+// Whether `operand_1 operator operand_2` holds. `operator` must be a comparison operator. Complex
+// numbers can be compared for equality, but they have no order, so comparing them with anything
+// else fails. Values with an uncertainty can't be compared at all, since the answer might depend on
+// where in their interval they really are.
+fn comparison_holds(
+    operator: BinaryOperatorToken,
+    operand_1: &Value,
+    operand_2: &Value,
+) -> Result<bool, MathExecutionError> {
+    if operand_1.is_interval() || operand_2.is_interval() {
+        return Err(Uncertain(operator.to_string()));
+    }
+    let ordering = match (operand_1.as_real(), operand_2.as_real()) {
+        (Some(operand_1), Some(operand_2)) => Some(operand_1.cmp(operand_2)),
+        _ => None,
+    };
+    let holds = match operator {
+        BinaryOperatorToken::Less => ordering.map(Ordering::is_lt),
+        BinaryOperatorToken::LessOrEqual => ordering.map(Ordering::is_le),
+        BinaryOperatorToken::Greater => ordering.map(Ordering::is_gt),
+        BinaryOperatorToken::GreaterOrEqual => ordering.map(Ordering::is_ge),
+        BinaryOperatorToken::Equal => Some(operand_1 == operand_2),
+        BinaryOperatorToken::NotEqual => Some(operand_1 != operand_2),
+        _ => panic!("{} is not a comparison operator", operator),
+    };
+    holds.ok_or_else(|| NotReal(operator.to_string()))
+}
+
+// `condition ? if_true : if_false`. Only the operand that the condition selects is evaluated, so
+// the other one can contain things that would fail, like dividing by 0.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct ConditionalNode {
+    condition: SyntaxTreeNode,
+    if_true: SyntaxTreeNode,
+    if_false: SyntaxTreeNode,
+}
+
+impl OperationNode for ConditionalNode {
+    fn execute(
+        self: Box<Self>,
+        mut maybe_vars: Option<&mut VariableStore>,
+        mut maybe_db: Option<&mut dyn Storage>,
+        args: &Args,
+        mut maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_scope: Option<&Scope>,
+        mut maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        let condition = self.condition.execute(
+            maybe_vars.as_deref_mut(),
+            reborrow(&mut maybe_db),
+            args,
+            reborrow_observer(&mut maybe_observer),
+            maybe_interrupt,
+            maybe_scope,
+            maybe_warnings.as_deref_mut(),
+        )?;
+        let selected = if is_true(&condition) {
+            self.if_true
+        } else {
+            self.if_false
+        };
+        selected.execute(
+            maybe_vars,
+            maybe_db,
+            args,
+            maybe_observer,
+            maybe_interrupt,
+            maybe_scope,
+            maybe_warnings,
+        )
+    }
+
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        self.condition.dimension()?;
+        same_dimension(
+            self.if_true.dimension()?,
+            self.if_true.position(),
+            self.if_false.dimension()?,
+            self.if_false.position(),
+        )
+    }
+
+    fn position(&self) -> Position {
+        Position::from_span(self.condition.position(), self.if_false.position())
+    }
+}
+
+// A chain of comparisons like `1 < $x <= 10`, which holds if each comparison in it holds, like
+// `1 < $x` and `$x <= 10`. Each operand is evaluated at most once, and evaluation stops at the first
+// comparison that doesn't hold. A single comparison is a `BinaryNode` instead.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct ComparisonChainNode {
+    operands: Vec<SyntaxTreeNode>,
+    // `operators[i]` is between `operands[i]` and `operands[i + 1]`.
+    operators: Vec<Positioned<BinaryOperatorToken>>,
+}
+
+impl OperationNode for ComparisonChainNode {
+    fn execute(
+        self: Box<Self>,
+        mut maybe_vars: Option<&mut VariableStore>,
+        mut maybe_db: Option<&mut dyn Storage>,
+        args: &Args,
+        mut maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_scope: Option<&Scope>,
+        mut maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        let mut operands = self.operands.into_iter();
+        let mut execute_next = || match operands.next() {
+            Some(operand) => operand.execute(
+                maybe_vars.as_deref_mut(),
+                reborrow(&mut maybe_db),
+                args,
+                reborrow_observer(&mut maybe_observer),
+                maybe_interrupt,
+                maybe_scope,
+                maybe_warnings.as_deref_mut(),
+            ),
+            None => panic!("ComparisonChainNode has fewer operands than expected"),
+        };
+        let mut left = execute_next()?;
+        for operator in self.operators {
+            let right = execute_next()?;
+            let holds = comparison_holds(operator.value, &left, &right)
+                .map_err(|e| Positioned::new(e, operator.position))?;
+            if !holds {
+                return Ok(truth_value(false));
+            }
+            left = right;
+        }
+        Ok(truth_value(true))
+    }
+
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        let mut maybe_dimension = None;
+        // Where the first operand with a known dimension is. See `FunctionNode::dimension`.
+        let mut dimension_position = self.operands[0].position();
+        for operand in &self.operands {
+            if maybe_dimension.is_none() {
+                dimension_position = operand.position();
+            }
+            maybe_dimension = same_dimension(
+                maybe_dimension,
+                dimension_position,
+                operand.dimension()?,
+                operand.position(),
+            )?;
+        }
+        Ok(Some(Dimension::NONE))
+    }
+
+    fn position(&self) -> Position {
+        Position::from_span(
+            self.operands[0].position(),
+            self.operands[self.operands.len() - 1].position(),
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct FunctionNode {
+    function_name: FunctionNameToken,
+    function_name_position: Position,
+    operands: Vec<SyntaxTreeNode>,
+    operands_position: Position,
+}
+
+impl FunctionNode {
+    // The dimension of `prod(...)`, which is the product of the dimensions of the operands.
+    fn product_dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        let mut maybe_product = Some(Dimension::NONE);
+        for operand in &self.operands {
+            maybe_product = match (maybe_product, operand.dimension()?) {
+                (Some(product), Some(dimension)) => {
+                    let maybe_result = product.multiply(&dimension);
+                    Some(maybe_result.ok_or_else(|| {
+                        Positioned::new(InvalidDimensionPower(product), self.function_name_position)
+                    })?)
+                }
+                _ => None,
+            };
+        }
+        Ok(maybe_product)
+    }
+
+    // The dimension of `recip(x)`, which is the inverse of the dimension of `x`.
+    fn reciprocal_dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        let dimension = match self.operands.first() {
+            Some(operand) => match operand.dimension()? {
+                Some(dimension) => dimension,
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        match Dimension::NONE.divide(&dimension) {
+            Some(inverse) => Ok(Some(inverse)),
+            None => Err(Positioned::new(
+                InvalidDimensionPower(dimension),
+                self.function_name_position,
+            )
+            .into()),
+        }
+    }
+
+    // The dimension of `root(x, n)`. Like with `^`, the root of a value with units can only be
+    // taken if the degree is written out in the expression.
+    fn root_dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        let (radicand, degree) = match &self.operands[..] {
+            [radicand, degree] => (radicand, degree),
+            // The wrong number of arguments is reported when the function is executed.
+            _ => return Ok(None),
+        };
+        require_no_units(degree.dimension()?, degree.position())?;
+        match radicand.dimension()? {
+            Some(dimension) if !dimension.is_none() => degree
+                .constant_value()
+                .filter(|degree| degree.is_integer())
+                .and_then(|degree| degree.to_integer().to_i8())
+                .and_then(|degree| dimension.root(degree))
+                .map(Some)
+                .ok_or_else(|| {
+                    Positioned::new(
+                        InvalidDimensionPower(dimension),
+                        self.function_name_position,
+                    )
+                    .into()
+                }),
+            maybe_dimension => Ok(maybe_dimension),
+        }
+    }
+
+    // `if(condition, if_true, if_false)`. Like `condition ? if_true : if_false`, only the operand
+    // that the condition selects is evaluated.
+    #[allow(clippy::too_many_arguments)]
+    fn execute_if(
+        self,
+        mut maybe_vars: Option<&mut VariableStore>,
+        mut maybe_db: Option<&mut dyn Storage>,
+        args: &Args,
+        mut maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_scope: Option<&Scope>,
+        mut maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        let operand_count = self.operands.len();
+        let [condition, if_true, if_false]: [SyntaxTreeNode; 3] = match self.operands.try_into() {
+            Ok(operands) => operands,
+            Err(_) => {
+                return Err(Positioned::new(
+                    InvalidArgument(
+                        self.function_name,
+                        format!("Expected 3 arguments, found {}", operand_count),
+                    ),
+                    self.operands_position,
+                )
+                .into());
+            }
+        };
+        let condition = condition.execute(
+            maybe_vars.as_deref_mut(),
+            reborrow(&mut maybe_db),
+            args,
+            reborrow_observer(&mut maybe_observer),
+            maybe_interrupt,
+            maybe_scope,
+            maybe_warnings.as_deref_mut(),
+        )?;
+        let selected = if is_true(&condition) {
+            if_true
+        } else {
+            if_false
+        };
+        selected.execute(
+            maybe_vars,
+            maybe_db,
+            args,
+            maybe_observer,
+            maybe_interrupt,
+            maybe_scope,
+            maybe_warnings,
+        )
+    }
+
+    /// Evaluates the function, returning all of the values that it produces. Most functions produce
+    /// a single value, but some, like `divmod`, produce several. See
+    /// `FunctionNameToken::produces_multiple_values`.
+    #[allow(clippy::too_many_arguments)]
+    fn execute_values(
+        self,
+        mut maybe_vars: Option<&mut VariableStore>,
+        mut maybe_db: Option<&mut dyn Storage>,
+        args: &Args,
+        mut maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_scope: Option<&Scope>,
+        mut maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Vec<Value>, CalculatorFailure> {
+        // Unlike other functions, `if` doesn't evaluate all of its operands.
+        if self.function_name == FunctionNameToken::If {
+            return self
+                .execute_if(
+                    maybe_vars,
+                    maybe_db,
+                    args,
+                    maybe_observer,
+                    maybe_interrupt,
+                    maybe_scope,
+                    maybe_warnings,
+                )
+                .map(|value| vec![value]);
+        }
+        // Values with angle units are stored in degrees, whatever the angle unit setting is.
+        let angle_in_degrees = match self.function_name {
+            FunctionNameToken::Sin | FunctionNameToken::Cos | FunctionNameToken::Tan => {
+                let maybe_dimension = match self.operands.first() {
+                    Some(operand) => operand.dimension()?,
+                    None => None,
+                };
+                maybe_dimension == Some(Dimension::ANGLE) || args.angle_unit == AngleUnit::Degrees
+            }
+            _ => args.angle_unit == AngleUnit::Degrees,
+        };
+        let operand_positions: Vec<Position> = self
+            .operands
+            .iter()
+            .map(|operand| operand.position())
+            .collect();
+        let mut values: Vec<Value> = Vec::new();
+        for operand in self.operands {
+            values.push(operand.execute(
+                maybe_vars.as_deref_mut(),
+                reborrow(&mut maybe_db),
+                args,
+                reborrow_observer(&mut maybe_observer),
+                maybe_interrupt,
+                maybe_scope,
+                maybe_warnings.as_deref_mut(),
+            )?);
+        }
+        let function_name = &self.function_name;
+        let invalid_argument = |message: String, position: Position| -> CalculatorFailure {
+            Positioned::new(InvalidArgument(function_name.clone(), message), position).into()
+        };
+        let operand_count = values.len();
+        let check_count = |allowed: &[usize]| {
+            if allowed.contains(&operand_count) {
+                return Ok(());
+            }
+            let allowed: Vec<String> = allowed.iter().map(|count| count.to_string()).collect();
+            let plural = if allowed == ["1"] { "" } else { "s" };
+            Err(invalid_argument(
+                format!(
+                    "Expected {} argument{}, found {}",
+                    allowed.join(" or "),
+                    plural,
+                    operand_count
+                ),
+                self.operands_position,
+            ))
+        };
+        let total_precision = args.precision + args.extra_precision;
+        check_not_mixed(&values, self.function_name_position)?;
+        // Most functions only work with exact real numbers. The ones that also work with complex
+        // numbers or uncertain values are handled here, before the operands are checked.
+        match self.function_name {
+            // An empty sum is 0 and an empty product is 1, so that a list of values can be totaled
+            // without checking whether it is empty first.
+            FunctionNameToken::Sum => return Ok(vec![values.into_iter().sum()]),
+            FunctionNameToken::Prod => return Ok(vec![values.into_iter().product()]),
+            FunctionNameToken::Recip => {
+                check_count(&[1])?;
+                let reciprocal = Value::from(BigInt::one())
+                    .checked_div(&values[0])
+                    .ok_or_else(|| Positioned::new(DivisionByZero, operand_positions[0]))?;
+                return Ok(vec![reciprocal]);
+            }
+            FunctionNameToken::Root => {
+                check_count(&[2])?;
+                let degree =
+                    real_operand(values.pop().unwrap(), function_name, operand_positions[1])?;
+                if !degree.is_integer() {
+                    return Err(invalid_argument(
+                        format!("{} is not an integer", degree),
+                        operand_positions[1],
+                    ));
+                }
+                if degree.is_zero() {
+                    return Err(invalid_argument(
+                        "The degree can't be 0".to_string(),
+                        operand_positions[1],
+                    ));
+                }
+                return exponentiate(
+                    values.pop().unwrap(),
+                    degree.recip(),
+                    total_precision,
+                    args.radix,
+                    maybe_interrupt,
+                )
+                .map(|value| vec![value])
+                .map_err(|e| Positioned::new(e, self.function_name_position).into());
+            }
+            FunctionNameToken::Re | FunctionNameToken::Im | FunctionNameToken::Conj => {
+                check_count(&[1])?;
+                let value = values.pop().unwrap();
+                return Ok(vec![match (self.function_name.clone(), value) {
+                    (FunctionNameToken::Re, Value::Complex { re, .. }) => Value::Real(re),
+                    (FunctionNameToken::Im, value) => Value::Real(value.im()),
+                    (FunctionNameToken::Conj, value) => value.conj(),
+                    (_, value) => value,
+                }]);
+            }
+            FunctionNameToken::Interval => {
+                check_count(&[2])?;
+                let high =
+                    real_operand(values.pop().unwrap(), function_name, operand_positions[1])?;
+                let low = real_operand(values.pop().unwrap(), function_name, operand_positions[0])?;
+                return Ok(vec![Value::interval(low, high)]);
+            }
+            // The angle of the point `(re, im)`, like `atan2(im, re)`.
+            FunctionNameToken::Arg => {
+                check_count(&[1])?;
+                if values[0].is_interval() {
+                    return Err(Positioned::new(
+                        Uncertain(function_name.to_string()),
+                        operand_positions[0],
+                    )
+                    .into());
+                }
+                if values[0].is_zero() {
+                    return Err(invalid_argument(
+                        "The angle of 0 is undefined".to_string(),
+                        operand_positions[0],
+                    ));
+                }
+                return arctangent(
+                    &values[0].im(),
+                    &values[0].re(),
+                    angle_in_degrees,
+                    total_precision,
+                    args.radix,
+                    maybe_interrupt,
+                )
+                .map(|angle| vec![Value::Real(angle)])
+                .map_err(|e| Positioned::new(e, self.function_name_position).into());
+            }
+            _ => {}
+        }
+        let mut operands: Vec<BigRational> = Vec::new();
+        for (value, position) in values.into_iter().zip(&operand_positions) {
+            operands.push(real_operand(value, function_name, *position)?);
+        }
+        let address_operand = |index: usize| {
+            address_argument(&operands[index])
+                .map_err(|message| invalid_argument(message, operand_positions[index]))
+        };
+        let prefix_length_operand = |index: usize| {
+            prefix_length_argument(&operands[index])
+                .map_err(|message| invalid_argument(message, operand_positions[index]))
+        };
+        let integer_operand = |index: usize| {
+            if operands[index].is_integer() {
+                Ok(operands[index].to_integer())
+            } else {
+                Err(invalid_argument(
+                    format!("{} is not an integer", operands[index]),
+                    operand_positions[index],
+                ))
+            }
+        };
+        // Reads the high and low bit indices for `bits` and `setbits`, which are operands 1 and 2.
+        let bit_range_operands = || {
+            let mut indices = [0; 2];
+            for (index, bit_index) in indices.iter_mut().enumerate() {
+                *bit_index = integer_operand(index + 1)?
+                    .to_u32()
+                    .filter(|bit_index| *bit_index <= MAX_BIT_INDEX)
+                    .ok_or_else(|| {
+                        invalid_argument(
+                            format!("{} is not a valid bit index", operands[index + 1]),
+                            operand_positions[index + 1],
+                        )
+                    })?;
+            }
+            let [hi, lo] = indices;
+            if hi < lo {
+                return Err(invalid_argument(
+                    "The high bit index can't be less than the low bit index".to_string(),
+                    Position::from_span(operand_positions[1], operand_positions[2]),
+                ));
+            }
+            Ok((hi, lo))
+        };
+        let integer = |value: u32| BigRational::from_integer(BigInt::from(value));
+        let value = match self.function_name {
+            FunctionNameToken::Max => {
+                let mut operand_iter = operands.into_iter();
+                let init = match operand_iter.next() {
+                    Some(i) => i,
+                    None => {
+                        return Err(Positioned::new(
+                            FunctionNeedsArguments(self.function_name.clone()),
+                            self.function_name_position,
+                        )
+                        .into())
+                    }
+                };
+                Ok(operand_iter.fold(init, max))
+            }
+            FunctionNameToken::Min => {
+                let mut operand_iter = operands.into_iter();
+                let init = match operand_iter.next() {
+                    Some(i) => i,
+                    None => {
+                        return Err(Positioned::new(
+                            FunctionNeedsArguments(self.function_name.clone()),
+                            self.function_name_position,
+                        )
+                        .into())
+                    }
+                };
+                Ok(operand_iter.fold(init, min))
+            }
+            FunctionNameToken::Network => {
+                check_count(&[2])?;
+                Ok(integer(network_address(
+                    address_operand(0)?,
+                    prefix_length_operand(1)?,
+                )))
+            }
+            FunctionNameToken::Broadcast => {
+                check_count(&[2])?;
+                Ok(integer(broadcast_address(
+                    address_operand(0)?,
+                    prefix_length_operand(1)?,
+                )))
+            }
+            FunctionNameToken::Netmask => {
+                check_count(&[1])?;
+                Ok(integer(netmask(prefix_length_operand(0)?)))
+            }
+            // An address may be given before the prefix length so that `hosts` can be passed a CIDR
+            // block. It doesn't affect the result.
+            FunctionNameToken::Hosts => {
+                check_count(&[1, 2])?;
+                if operands.len() == 2 {
+                    address_operand(0)?;
+                }
+                Ok(host_count(prefix_length_operand(operands.len() - 1)?))
+            }
+            FunctionNameToken::InSubnet => {
+                check_count(&[3])?;
+                let is_in_subnet = in_subnet(
+                    address_operand(0)?,
+                    address_operand(1)?,
+                    prefix_length_operand(2)?,
+                );
+                Ok(integer(is_in_subnet.into()))
+            }
+            FunctionNameToken::Bits => {
+                check_count(&[3])?;
+                let value = integer_operand(0)?;
+                let (hi, lo) = bit_range_operands()?;
+                Ok(BigRational::from_integer(extract_bits(&value, hi, lo)))
+            }
+            FunctionNameToken::SetBits => {
+                check_count(&[4])?;
+                let value = integer_operand(0)?;
+                let (hi, lo) = bit_range_operands()?;
+                let field = integer_operand(3)?;
+                let result = replace_bits(&value, hi, lo, &field)
+                    .map_err(|message| invalid_argument(message, operand_positions[3]))?;
+                Ok(BigRational::from_integer(result))
+            }
+            FunctionNameToken::HistSum
+            | FunctionNameToken::HistMean
+            | FunctionNameToken::HistMin
+            | FunctionNameToken::HistMax => {
+                check_count(&[1])?;
+                let count = integer_operand(0)?
+                    .to_usize()
+                    .filter(|count| *count > 0)
+                    .ok_or_else(|| {
+                        invalid_argument(
+                            format!("{} is not a valid number of results", operands[0]),
+                            operand_positions[0],
+                        )
+                    })?;
+                // Recent results are kept in the variable store, but what the user is missing is
+                // their history.
+                let vars = match maybe_vars {
+                    Some(v) => v,
+                    None => {
+                        return Err(
+                            Positioned::new(NoInputHistory, self.function_name_position).into()
+                        )
+                    }
+                };
+                let results = vars.recent_results(count).ok_or_else(|| {
+                    let message = match vars.result_count() {
+                        0 => "No results are available".to_string(),
+                        1 => "Only 1 result is available".to_string(),
+                        available => format!("Only {} results are available", available),
+                    };
+                    invalid_argument(message, operand_positions[0])
+                })?;
+                check_not_mixed(results.clone(), self.function_name_position)?;
+                let sum = || results.clone().cloned().sum::<Value>();
+                let value = match self.function_name {
+                    FunctionNameToken::HistSum => sum(),
+                    // `count` is at least 1, so this never divides by 0.
+                    FunctionNameToken::HistMean => sum()
+                        .checked_div(&Value::from(BigInt::from(count)))
+                        .unwrap(),
+                    _ => {
+                        let mut reals: Vec<BigRational> = Vec::new();
+                        for value in results.cloned() {
+                            reals.push(real_operand(
+                                value,
+                                function_name,
+                                self.function_name_position,
+                            )?);
+                        }
+                        let extreme = match self.function_name {
+                            FunctionNameToken::HistMin => reals.into_iter().min(),
+                            _ => reals.into_iter().max(),
+                        };
+                        Value::Real(extreme.unwrap())
+                    }
+                };
+                return Ok(vec![value]);
+            }
+            // The quotient is truncated toward zero, so the remainder matches the one from `%`.
+            FunctionNameToken::DivMod => {
+                check_count(&[2])?;
+                if *operands[1].numer() == BigInt::from(0) {
+                    return Err(Positioned::new(DivisionByZero, operand_positions[1]).into());
+                }
+                let quotient = (&operands[0] / &operands[1]).trunc();
+                let remainder = &operands[0] % &operands[1];
+                return Ok(vec![Value::Real(quotient), Value::Real(remainder)]);
+            }
+            FunctionNameToken::Factor => {
+                check_count(&[1])?;
+                let value = integer_operand(0)?;
+                if value < BigInt::from(2) {
+                    return Err(invalid_argument(
+                        format!("Expected an integer greater than 1, found {}", value),
+                        operand_positions[0],
+                    ));
+                }
+                let factors = prime_factors(&value, maybe_interrupt)
+                    .map_err(|e| Positioned::new(e, self.function_name_position))?;
+                return Ok(factors.into_iter().map(Value::from).collect());
+            }
+            FunctionNameToken::Ln | FunctionNameToken::Log2 | FunctionNameToken::Log10 => {
+                check_count(&[1])?;
+                if !operands[0].is_positive() {
+                    return Err(invalid_argument(
+                        format!("{} is not positive", operands[0]),
+                        operand_positions[0],
+                    ));
+                }
+                let result = match self.function_name {
+                    FunctionNameToken::Ln => {
+                        natural_log(&operands[0], total_precision, args.radix, maybe_interrupt)
+                    }
+                    FunctionNameToken::Log2 => logarithm(
+                        &operands[0],
+                        2,
+                        total_precision,
+                        args.radix,
+                        maybe_interrupt,
+                    ),
+                    _ => logarithm(
+                        &operands[0],
+                        10,
+                        total_precision,
+                        args.radix,
+                        maybe_interrupt,
+                    ),
+                };
+                result.map_err(|e| Positioned::new(e, self.function_name_position).into())
+            }
+            FunctionNameToken::Exp => {
+                check_count(&[1])?;
+                let limit = BigRational::from_integer(BigInt::from(MAX_EXPONENTIAL_ARGUMENT));
+                if operands[0].abs() > limit {
+                    let problem = if operands[0].is_positive() {
+                        "too large"
+                    } else {
+                        "too small"
+                    };
+                    return Err(invalid_argument(
+                        format!("{} is {}", operands[0], problem),
+                        operand_positions[0],
+                    ));
+                }
+                exponential(&operands[0], total_precision, args.radix, maybe_interrupt)
+                    .map_err(|e| Positioned::new(e, self.function_name_position).into())
+            }
+            FunctionNameToken::Sin | FunctionNameToken::Cos | FunctionNameToken::Tan => {
+                check_count(&[1])?;
+                let result = match self.function_name {
+                    FunctionNameToken::Sin => sine(
+                        &operands[0],
+                        angle_in_degrees,
+                        total_precision,
+                        args.radix,
+                        maybe_interrupt,
+                    ),
+                    FunctionNameToken::Cos => cosine(
+                        &operands[0],
+                        angle_in_degrees,
+                        total_precision,
+                        args.radix,
+                        maybe_interrupt,
+                    ),
+                    _ => {
+                        if is_tangent_undefined(&operands[0], angle_in_degrees) {
+                            return Err(invalid_argument(
+                                format!("The tangent of {} degrees is undefined", operands[0]),
+                                operand_positions[0],
+                            ));
+                        }
+                        tangent(
+                            &operands[0],
+                            angle_in_degrees,
+                            total_precision,
+                            args.radix,
+                            maybe_interrupt,
+                        )
+                    }
+                };
+                result.map_err(|e| Positioned::new(e, self.function_name_position).into())
+            }
+            FunctionNameToken::Asin | FunctionNameToken::Acos => {
+                check_count(&[1])?;
+                if operands[0].abs() > BigRational::one() {
+                    return Err(invalid_argument(
+                        format!("{} is not between -1 and 1", operands[0]),
+                        operand_positions[0],
+                    ));
+                }
+                let result = if self.function_name == FunctionNameToken::Asin {
+                    arcsine(
+                        &operands[0],
+                        angle_in_degrees,
+                        total_precision,
+                        args.radix,
+                        maybe_interrupt,
+                    )
+                } else {
+                    arccosine(
+                        &operands[0],
+                        angle_in_degrees,
+                        total_precision,
+                        args.radix,
+                        maybe_interrupt,
+                    )
+                };
+                result.map_err(|e| Positioned::new(e, self.function_name_position).into())
+            }
+            FunctionNameToken::Atan | FunctionNameToken::Atan2 => {
+                // `atan(z)` is the angle of the point `(1, z)`, and `atan2(y, x)` is the angle of
+                // the point `(x, y)`.
+                let (y, x) = if self.function_name == FunctionNameToken::Atan {
+                    check_count(&[1])?;
+                    (&operands[0], BigRational::one())
+                } else {
+                    check_count(&[2])?;
+                    if operands[0].is_zero() && operands[1].is_zero() {
+                        return Err(invalid_argument(
+                            "The angle of the point (0, 0) is undefined".to_string(),
+                            self.operands_position,
+                        ));
+                    }
+                    (&operands[0], operands[1].clone())
+                };
+                arctangent(
+                    y,
+                    &x,
+                    angle_in_degrees,
+                    total_precision,
+                    args.radix,
+                    maybe_interrupt,
+                )
+                .map_err(|e| Positioned::new(e, self.function_name_position).into())
+            }
+            FunctionNameToken::Choose | FunctionNameToken::Perm => {
+                check_count(&[2])?;
+                let counts = [integer_operand(0)?, integer_operand(1)?];
+                for (count, position) in counts.iter().zip(&operand_positions) {
+                    if count.is_negative() {
+                        return Err(invalid_argument(
+                            format!("{} is negative", count),
+                            *position,
+                        ));
+                    }
+                }
+                let result = if self.function_name == FunctionNameToken::Choose {
+                    combinations(&counts[0], &counts[1], maybe_interrupt)
+                } else {
+                    permutations(&counts[0], &counts[1], maybe_interrupt)
+                };
+                result
+                    .map(BigRational::from_integer)
+                    .map_err(|e| Positioned::new(e, self.function_name_position).into())
+            }
+            FunctionNameToken::Mean
+            | FunctionNameToken::Median
+            | FunctionNameToken::Variance
+            | FunctionNameToken::StdDev => {
+                if operands.is_empty() {
+                    return Err(Positioned::new(
+                        FunctionNeedsArguments(self.function_name.clone()),
+                        self.function_name_position,
+                    )
+                    .into());
+                }
+                match self.function_name {
+                    FunctionNameToken::Mean => Ok(mean(&operands)),
+                    FunctionNameToken::Median => Ok(median(operands)),
+                    _ if operands.len() == 1 => Err(invalid_argument(
+                        "Expected at least 2 arguments, found 1".to_string(),
+                        operand_positions[0],
+                    )),
+                    FunctionNameToken::Variance => Ok(variance(&operands)),
+                    _ => {
+                        return exponentiate(
+                            Value::Real(variance(&operands)),
+                            BigRational::new(BigInt::one(), BigInt::from(2)),
+                            total_precision,
+                            args.radix,
+                            maybe_interrupt,
+                        )
+                        .map(|value| vec![value])
+                        .map_err(|e| Positioned::new(e, self.function_name_position).into())
+                    }
+                }
+            }
+            FunctionNameToken::Sign => {
+                check_count(&[1])?;
+                Ok(BigRational::from_integer(operands[0].numer().signum()))
+            }
+            FunctionNameToken::Clamp => {
+                check_count(&[3])?;
+                if operands[1] > operands[2] {
+                    return Err(invalid_argument(
+                        format!(
+                            "The lower bound {} is greater than the upper bound {}",
+                            operands[1], operands[2]
+                        ),
+                        Position::from_span(operand_positions[1], operand_positions[2]),
+                    ));
+                }
+                Ok(operands[0]
+                    .clone()
+                    .clamp(operands[1].clone(), operands[2].clone()))
             }
-            BinaryOperatorToken::Modulus => Ok(operand_1 % operand_2),
-            BinaryOperatorToken::Exponent => {
-                let total_precision = args.precision + args.extra_precision;
-                exponentiate(operand_1, operand_2, total_precision, args.radix)
-                    .map_err(|e| Positioned::new(e, self.operator_position.clone()).into())
+            FunctionNameToken::If => unreachable!("`if` is handled by `execute_if`"),
+            FunctionNameToken::Sum
+            | FunctionNameToken::Prod
+            | FunctionNameToken::Recip
+            | FunctionNameToken::Root
+            | FunctionNameToken::Re
+            | FunctionNameToken::Im
+            | FunctionNameToken::Conj
+            | FunctionNameToken::Arg
+            | FunctionNameToken::Interval => {
+                unreachable!(
+                    "{} is handled before the operands are checked",
+                    function_name
+                )
             }
-        }
-    }
-
-    fn position(&self) -> Position {
-        Position::from_span(
-            self.operator_position.clone(),
-            Position::from_span(self.operand_1.position(), self.operand_2.position()),
-        )
+            FunctionNameToken::Custom(ref function) => match function.call(&operands) {
+                Ok(value) => Ok(value),
+                Err(message) => Err(Positioned::new(
+                    CustomFunctionFailed(self.function_name.clone(), message),
+                    self.function_name_position,
+                )
+                .into()),
+            },
+        };
+        value.map(|value| vec![Value::Real(value)])
     }
 }
 
-#[derive(Clone, Debug)]
-struct FunctionNode {
-    function_name: FunctionNameToken,
-    function_name_position: Position,
-    operands: Vec<SyntaxTreeNode>,
-    operands_position: Position,
-}
-
 impl OperationNode for FunctionNode {
     fn execute(
         self: Box<Self>,
-        mut maybe_vars: Option<&mut VariableStore>,
-        mut maybe_db: Option<&mut SavedData>,
+        maybe_vars: Option<&mut VariableStore>,
+        maybe_db: Option<&mut dyn Storage>,
         args: &Args,
-    ) -> Result<BigRational, CalculatorFailure> {
-        let mut operands: Vec<BigRational> = Vec::new();
-        for operand in self.operands {
-            operands.push(operand.execute(
-                maybe_vars.as_deref_mut(),
-                maybe_db.as_deref_mut(),
-                args,
-            )?);
+        maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_scope: Option<&Scope>,
+        maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        if self.function_name.produces_multiple_values() {
+            return Err(Positioned::new(
+                MultipleValues(self.function_name.clone()),
+                self.function_name_position,
+            )
+            .into());
         }
-        match self.function_name {
-            FunctionNameToken::Max => {
-                let mut operand_iter = operands.into_iter();
-                let init = match operand_iter.next() {
-                    Some(i) => i,
-                    None => {
-                        return Err(Positioned::new(
-                            FunctionNeedsArguments(self.function_name),
-                            self.function_name_position,
-                        )
-                        .into())
+        let mut values = (*self).execute_values(
+            maybe_vars,
+            maybe_db,
+            args,
+            maybe_observer,
+            maybe_interrupt,
+            maybe_scope,
+            maybe_warnings,
+        )?;
+        Ok(values.remove(0))
+    }
+
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        let mut maybe_dimension = None;
+        // Where the first operand with a known dimension is, so that a mismatch can be reported as
+        // spanning from it to the operand that doesn't match it.
+        let mut dimension_position = self.operands_position;
+        for operand in &self.operands {
+            let maybe_operand_dimension = operand.dimension()?;
+            match self.function_name {
+                FunctionNameToken::Max
+                | FunctionNameToken::Min
+                | FunctionNameToken::Sum
+                | FunctionNameToken::Mean
+                | FunctionNameToken::Median
+                | FunctionNameToken::Variance
+                | FunctionNameToken::StdDev
+                | FunctionNameToken::Clamp
+                | FunctionNameToken::Re
+                | FunctionNameToken::Im
+                | FunctionNameToken::Conj
+                | FunctionNameToken::Interval => {
+                    if maybe_dimension.is_none() {
+                        dimension_position = operand.position();
                     }
-                };
-                Ok(operand_iter.fold(init, max))
-            }
-            FunctionNameToken::Min => {
-                let mut operand_iter = operands.into_iter();
-                let init = match operand_iter.next() {
-                    Some(i) => i,
-                    None => {
-                        return Err(Positioned::new(
-                            FunctionNeedsArguments(self.function_name),
-                            self.function_name_position,
-                        )
-                        .into())
+                    maybe_dimension = same_dimension(
+                        maybe_dimension,
+                        dimension_position,
+                        maybe_operand_dimension,
+                        operand.position(),
+                    )?;
+                }
+                FunctionNameToken::Network
+                | FunctionNameToken::Broadcast
+                | FunctionNameToken::Netmask
+                | FunctionNameToken::Hosts
+                | FunctionNameToken::InSubnet
+                | FunctionNameToken::Bits
+                | FunctionNameToken::SetBits
+                | FunctionNameToken::HistSum
+                | FunctionNameToken::HistMean
+                | FunctionNameToken::HistMin
+                | FunctionNameToken::HistMax
+                | FunctionNameToken::DivMod
+                | FunctionNameToken::Factor
+                | FunctionNameToken::Ln
+                | FunctionNameToken::Log2
+                | FunctionNameToken::Log10
+                | FunctionNameToken::Exp
+                | FunctionNameToken::Asin
+                | FunctionNameToken::Acos
+                | FunctionNameToken::Atan
+                | FunctionNameToken::Atan2
+                | FunctionNameToken::Choose
+                | FunctionNameToken::Perm => {
+                    require_no_units(maybe_operand_dimension, operand.position())?;
+                }
+                // Angles can be given either with angle units or as plain numbers, which are taken
+                // to be in the angle unit setting's units.
+                FunctionNameToken::Sin | FunctionNameToken::Cos | FunctionNameToken::Tan => {
+                    if maybe_operand_dimension != Some(Dimension::ANGLE) {
+                        require_no_units(maybe_operand_dimension, operand.position())?;
                     }
-                };
-                Ok(operand_iter.fold(init, min))
+                }
+                // Checked below, since these combine the units of their operands. The sign and angle
+                // of a value don't depend on its units.
+                FunctionNameToken::Root
+                | FunctionNameToken::Prod
+                | FunctionNameToken::Recip
+                | FunctionNameToken::Sign
+                | FunctionNameToken::Arg
+                | FunctionNameToken::If
+                | FunctionNameToken::Custom(_) => {}
             }
         }
+        match self.function_name {
+            FunctionNameToken::Max
+            | FunctionNameToken::Min
+            | FunctionNameToken::Sum
+            | FunctionNameToken::Mean
+            | FunctionNameToken::Median
+            | FunctionNameToken::StdDev
+            | FunctionNameToken::Clamp
+            | FunctionNameToken::Re
+            | FunctionNameToken::Im
+            | FunctionNameToken::Conj
+            | FunctionNameToken::Interval => Ok(maybe_dimension),
+            FunctionNameToken::Sign => Ok(Some(Dimension::NONE)),
+            FunctionNameToken::Recip => self.reciprocal_dimension(),
+            // The condition can have any units, but both of the values it chooses between need to
+            // have the same units.
+            FunctionNameToken::If => match &self.operands[..] {
+                [_, if_true, if_false] => same_dimension(
+                    if_true.dimension()?,
+                    if_true.position(),
+                    if_false.dimension()?,
+                    if_false.position(),
+                ),
+                _ => Ok(None),
+            },
+            FunctionNameToken::Variance => match maybe_dimension {
+                Some(dimension) => match dimension.pow(2) {
+                    Some(squared) => Ok(Some(squared)),
+                    None => Err(Positioned::new(
+                        InvalidDimensionPower(dimension),
+                        self.function_name_position,
+                    )
+                    .into()),
+                },
+                None => Ok(None),
+            },
+            FunctionNameToken::Prod => self.product_dimension(),
+            // Addresses, prefix lengths, host counts, bit fields, logarithms, and exponentials are all plain
+            // numbers.
+            FunctionNameToken::Network
+            | FunctionNameToken::Broadcast
+            | FunctionNameToken::Netmask
+            | FunctionNameToken::Hosts
+            | FunctionNameToken::InSubnet
+            | FunctionNameToken::Bits
+            | FunctionNameToken::SetBits
+            | FunctionNameToken::DivMod
+            | FunctionNameToken::Factor
+            | FunctionNameToken::Ln
+            | FunctionNameToken::Log2
+            | FunctionNameToken::Log10
+            | FunctionNameToken::Exp
+            | FunctionNameToken::Sin
+            | FunctionNameToken::Cos
+            | FunctionNameToken::Tan
+            | FunctionNameToken::Choose
+            | FunctionNameToken::Perm => Ok(Some(Dimension::NONE)),
+            // The angles that inverse trigonometric functions give are plain numbers in the angle
+            // unit setting's units, since results with angle units are always shown in degrees.
+            FunctionNameToken::Asin
+            | FunctionNameToken::Acos
+            | FunctionNameToken::Atan
+            | FunctionNameToken::Atan2
+            | FunctionNameToken::Arg => Ok(Some(Dimension::NONE)),
+            FunctionNameToken::Root => self.root_dimension(),
+            // Past results are stored as plain numbers, so there's no way to tell what they were
+            // measuring. The same goes for custom functions, which are given plain numbers and
+            // return plain numbers.
+            FunctionNameToken::HistSum
+            | FunctionNameToken::HistMean
+            | FunctionNameToken::HistMin
+            | FunctionNameToken::HistMax
+            | FunctionNameToken::Custom(_) => Ok(None),
+        }
     }
 
     fn position(&self) -> Position {
-        Position::from_span(
-            self.function_name_position.clone(),
-            self.operands_position.clone(),
-        )
+        Position::from_span(self.function_name_position, self.operands_position)
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct ParenthesizedNode {
     open_position: Position,
     close_position: Position,
@@ -248,63 +1813,262 @@ impl OperationNode for ParenthesizedNode {
     fn execute(
         self: Box<Self>,
         maybe_vars: Option<&mut VariableStore>,
-        maybe_db: Option<&mut SavedData>,
+        maybe_db: Option<&mut dyn Storage>,
+        args: &Args,
+        maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_scope: Option<&Scope>,
+        maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        self.node.execute(
+            maybe_vars,
+            maybe_db,
+            args,
+            maybe_observer,
+            maybe_interrupt,
+            maybe_scope,
+            maybe_warnings,
+        )
+    }
+
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        self.node.dimension()
+    }
+
+    fn position(&self) -> Position {
+        Position::from_span(self.open_position, self.close_position)
+    }
+}
+
+// `let $name = value in body`. The body is evaluated with the variable bound to the value, without
+// the variable store being changed.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct LetNode {
+    let_position: Position,
+    name: VariableName,
+    value: SyntaxTreeNode,
+    body: SyntaxTreeNode,
+}
+
+impl OperationNode for LetNode {
+    fn execute(
+        self: Box<Self>,
+        mut maybe_vars: Option<&mut VariableStore>,
+        mut maybe_db: Option<&mut dyn Storage>,
         args: &Args,
-    ) -> Result<BigRational, CalculatorFailure> {
-        self.node.execute(maybe_vars, maybe_db, args)
+        mut maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_scope: Option<&Scope>,
+        mut maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        let LetNode {
+            name, value, body, ..
+        } = *self;
+        let value = value.execute(
+            maybe_vars.as_deref_mut(),
+            reborrow(&mut maybe_db),
+            args,
+            reborrow_observer(&mut maybe_observer),
+            maybe_interrupt,
+            maybe_scope,
+            maybe_warnings.as_deref_mut(),
+        )?;
+        let scope = Scope {
+            name: &name,
+            value,
+            maybe_outer: maybe_scope,
+        };
+        body.execute(
+            maybe_vars,
+            maybe_db,
+            args,
+            maybe_observer,
+            maybe_interrupt,
+            Some(&scope),
+            maybe_warnings,
+        )
+    }
+
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        self.value.dimension()?;
+        self.body.dimension()
     }
 
     fn position(&self) -> Position {
-        Position::from_span(self.open_position.clone(), self.close_position.clone())
+        Position::from_span(self.let_position, self.body.position())
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 enum SyntaxTreeNode {
     Number(Box<NumericNode>),
+    Imaginary(Box<ImaginaryNode>),
     Variable(Box<VariableNode>),
+    Constant(Box<ConstantNode>),
     Unary(Box<UnaryNode>),
     Binary(Box<BinaryNode>),
     Function(Box<FunctionNode>),
     Parenthesized(Box<ParenthesizedNode>),
+    Let(Box<LetNode>),
+    ComparisonChain(Box<ComparisonChainNode>),
+    Conditional(Box<ConditionalNode>),
 }
 
 impl SyntaxTreeNode {
     fn into_operation_node(self) -> Box<dyn OperationNode> {
         match self {
             SyntaxTreeNode::Number(n) => n,
+            SyntaxTreeNode::Imaginary(n) => n,
             SyntaxTreeNode::Variable(n) => n,
+            SyntaxTreeNode::Constant(n) => n,
             SyntaxTreeNode::Unary(n) => n,
             SyntaxTreeNode::Binary(n) => n,
             SyntaxTreeNode::Function(n) => n,
             SyntaxTreeNode::Parenthesized(n) => n,
+            SyntaxTreeNode::Let(n) => n,
+            SyntaxTreeNode::ComparisonChain(n) => n,
+            SyntaxTreeNode::Conditional(n) => n,
         }
     }
 
     fn as_operation_node(&self) -> &dyn OperationNode {
         match self {
             SyntaxTreeNode::Number(n) => &**n,
+            SyntaxTreeNode::Imaginary(n) => &**n,
             SyntaxTreeNode::Variable(n) => &**n,
+            SyntaxTreeNode::Constant(n) => &**n,
             SyntaxTreeNode::Unary(n) => &**n,
             SyntaxTreeNode::Binary(n) => &**n,
             SyntaxTreeNode::Function(n) => &**n,
             SyntaxTreeNode::Parenthesized(n) => &**n,
+            SyntaxTreeNode::Let(n) => &**n,
+            SyntaxTreeNode::ComparisonChain(n) => &**n,
+            SyntaxTreeNode::Conditional(n) => &**n,
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn execute(
         self,
         maybe_vars: Option<&mut VariableStore>,
-        maybe_db: Option<&mut SavedData>,
+        maybe_db: Option<&mut dyn Storage>,
         args: &Args,
-    ) -> Result<BigRational, CalculatorFailure> {
-        self.into_operation_node()
-            .execute(maybe_vars, maybe_db, args)
+        mut maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_scope: Option<&Scope>,
+        maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        if let Some(interrupt) = maybe_interrupt {
+            interrupt
+                .check()
+                .map_err(|e| Positioned::new(e, self.position()))?;
+        }
+        // Only bother describing the node if someone is going to look at the description.
+        let maybe_kind_and_position = maybe_observer
+            .as_ref()
+            .map(|_| (self.kind(), self.position()));
+        let value = self.into_operation_node().execute(
+            maybe_vars,
+            maybe_db,
+            args,
+            reborrow_observer(&mut maybe_observer),
+            maybe_interrupt,
+            maybe_scope,
+            maybe_warnings,
+        )?;
+        if let (Some(observer), Some((kind, position))) = (maybe_observer, maybe_kind_and_position)
+        {
+            observer(&TraceStep {
+                kind,
+                position,
+                value: &value,
+            });
+        }
+        Ok(value)
+    }
+
+    fn kind(&self) -> NodeKind {
+        match self {
+            SyntaxTreeNode::Number(n) if n.dimension.is_none() => NodeKind::Number(n.value.clone()),
+            SyntaxTreeNode::Number(n) => NodeKind::Quantity(n.value.clone(), n.dimension),
+            SyntaxTreeNode::Imaginary(n) => NodeKind::Imaginary(n.value.clone()),
+            SyntaxTreeNode::Variable(n) => NodeKind::Variable(n.name.clone()),
+            SyntaxTreeNode::Constant(n) => NodeKind::Constant(n.constant),
+            SyntaxTreeNode::Unary(n) => NodeKind::Unary(n.operator),
+            SyntaxTreeNode::Binary(n) => NodeKind::Binary(n.operator),
+            SyntaxTreeNode::Function(n) => NodeKind::Function(n.function_name.clone()),
+            SyntaxTreeNode::Parenthesized(_) => NodeKind::Parenthesized,
+            SyntaxTreeNode::Let(n) => NodeKind::Let(n.name.clone()),
+            SyntaxTreeNode::Conditional(_) => NodeKind::Conditional,
+            SyntaxTreeNode::ComparisonChain(n) => NodeKind::ComparisonChain(
+                n.operators.iter().map(|operator| operator.value).collect(),
+            ),
+        }
     }
 
     fn position(&self) -> Position {
         self.as_operation_node().position()
     }
+
+    fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        self.as_operation_node().dimension()
+    }
+
+    // The value of the node if it is a plain number written out in the expression, possibly negated
+    // or in parentheses.
+    fn constant_value(&self) -> Option<BigRational> {
+        match self {
+            SyntaxTreeNode::Number(n) if n.dimension.is_none() => Some(n.value.clone()),
+            SyntaxTreeNode::Unary(n) if n.operator == UnaryOperatorToken::Negate => {
+                n.operand.constant_value().map(|value| -value)
+            }
+            SyntaxTreeNode::Parenthesized(n) => n.node.constant_value(),
+            _ => None,
+        }
+    }
+
+    // Appends a line describing this node, indented according to `depth`, followed by the lines for
+    // its children.
+    fn write_outline(&self, output: &mut String, depth: usize) {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&"  ".repeat(depth));
+        output.push_str(&self.kind().to_string());
+        match self {
+            SyntaxTreeNode::Number(_)
+            | SyntaxTreeNode::Imaginary(_)
+            | SyntaxTreeNode::Variable(_)
+            | SyntaxTreeNode::Constant(_) => {}
+            SyntaxTreeNode::Unary(n) => n.operand.write_outline(output, depth + 1),
+            SyntaxTreeNode::Binary(n) => {
+                n.operand_1.write_outline(output, depth + 1);
+                n.operand_2.write_outline(output, depth + 1);
+            }
+            SyntaxTreeNode::Function(n) => {
+                for operand in &n.operands {
+                    operand.write_outline(output, depth + 1);
+                }
+            }
+            SyntaxTreeNode::Parenthesized(n) => n.node.write_outline(output, depth + 1),
+            SyntaxTreeNode::Let(n) => {
+                n.value.write_outline(output, depth + 1);
+                n.body.write_outline(output, depth + 1);
+            }
+            SyntaxTreeNode::ComparisonChain(n) => {
+                for operand in &n.operands {
+                    operand.write_outline(output, depth + 1);
+                }
+            }
+            SyntaxTreeNode::Conditional(n) => {
+                n.condition.write_outline(output, depth + 1);
+                n.if_true.write_outline(output, depth + 1);
+                n.if_false.write_outline(output, depth + 1);
+            }
+        }
+    }
 }
 
 // Temporary structure that will help us construct the syntax tree.
@@ -315,6 +2079,10 @@ impl SyntaxTreeNode {
 enum OperandOrOperator {
     Operand(SyntaxTreeNode),
     Operator(Positioned<BinaryOperatorToken>),
+    // A unit written after an operand, like the `km` in `5 km`. Units are multiplied into the
+    // operands before them once exponents have been applied, so they bind like an implicit
+    // multiplication that comes before any other.
+    Unit(SyntaxTreeNode),
 }
 
 impl OperandOrOperator {
@@ -365,6 +2133,23 @@ impl OperandOrOperator {
             _ => panic!("Attempted to unwrap an operand from OperandOrOperator incorrectly"),
         }
     }
+
+    fn is_unit(&self) -> bool {
+        matches!(self, OperandOrOperator::Unit(_))
+    }
+
+    fn unwrap_unit(self) -> SyntaxTreeNode {
+        match self {
+            OperandOrOperator::Unit(u) => u,
+            _ => panic!("Attempted to unwrap a unit from OperandOrOperator incorrectly"),
+        }
+    }
+
+    // Whether this ends an operand, which is true of units too, since they are part of the operand
+    // that they follow.
+    fn ends_operand(&self) -> bool {
+        self.is_operand() || self.is_unit()
+    }
 }
 
 // Temporary structure that will help us construct the syntax tree.
@@ -373,6 +2158,11 @@ impl OperandOrOperator {
 enum ExpressionEnd {
     Comma(Position),
     CloseParen(Position),
+    // The `:` of a conditional, like `$x > 0 ? 1 : 2`, which ends the value used if the condition
+    // holds.
+    Colon(Position),
+    // The `|` that closes an absolute value, like the second one in `|$x|`.
+    Bar(Position),
     InputEmpty,
 }
 
@@ -382,6 +2172,8 @@ enum ExpressionEnd {
 enum InputReadResult {
     Operand(SyntaxTreeNode),
     Operator(Positioned<BinaryOperatorToken>),
+    // The `?` of a conditional. Everything before it in the expression is the condition.
+    QuestionMark(Position),
     End(ExpressionEnd),
 }
 
@@ -407,8 +2199,9 @@ enum OperandReadResult {
 /// variable. Executing the syntax tree will consume it, assign to the specified variable (if
 /// applicable), and return the result.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SyntaxTree {
-    maybe_result_var: Option<Positioned<String>>,
+    maybe_result_var: Option<Positioned<VariableName>>,
     root: SyntaxTreeNode,
 }
 
@@ -421,7 +2214,7 @@ impl SyntaxTree {
         // put the tokens back in the input.
         let first_token = input.pop_front();
         let second_token = input.pop_front();
-        let maybe_result_var: Option<Positioned<String>> = match (first_token, second_token) {
+        let maybe_result_var: Option<Positioned<VariableName>> = match (first_token, second_token) {
             (
                 Some(Positioned {
                     value: Token::Variable(var_name),
@@ -431,7 +2224,15 @@ impl SyntaxTree {
                     value: Token::AssignmentOperator,
                     position: _,
                 }),
-            ) => Some(Positioned::new(var_name, position)),
+            ) => {
+                if answer_index(&var_name).is_some() {
+                    return Err(Positioned::new(
+                        ReservedVariable(var_name.to_string()),
+                        position,
+                    ));
+                }
+                Some(Positioned::new(var_name, position))
+            }
             (first_token, second_token) => {
                 if let Some(token) = second_token {
                     input.push_front(token);
@@ -450,6 +2251,10 @@ impl SyntaxTree {
             (_, ExpressionEnd::CloseParen(p)) => {
                 return Err(Positioned::new(MismatchedCloseParen, p));
             }
+            (_, ExpressionEnd::Colon(p)) => {
+                return Err(Positioned::new(UnexpectedToken(Token::Colon), p));
+            }
+            (_, ExpressionEnd::Bar(p)) => return Err(Positioned::new(MismatchedBar, p)),
             (None, ExpressionEnd::InputEmpty) => return Err(Positioned::new_raw(NoInput, 0, 0)),
             (Some(r), ExpressionEnd::InputEmpty) => r,
         };
@@ -462,6 +2267,35 @@ impl SyntaxTree {
         Ok(st)
     }
 
+    /// Like `new`, but also rejects the conveniences that `args.strict` disallows, namely calling a
+    /// function without parentheses. `sqrt` and `abs` count as functions here even though they are
+    /// parsed as operators. `not` doesn't, since it is the same operator as `!`.
+    pub fn new_with_args(
+        input: VecDeque<Positioned<Token>>,
+        args: &Args,
+    ) -> Result<SyntaxTree, Positioned<SyntaxError>> {
+        if args.strict {
+            let mut tokens = input.iter().peekable();
+            while let Some(token) = tokens.next() {
+                let is_function = matches!(
+                    token.value,
+                    Token::Function(_)
+                        | Token::UnaryOperator(
+                            UnaryOperatorToken::SquareRoot | UnaryOperatorToken::AbsoluteValue
+                        )
+                );
+                let next = tokens.peek().map(|t| &t.value);
+                if is_function && !matches!(next, Some(Token::OpenParen)) {
+                    return Err(Positioned::new(
+                        FunctionWithoutParens(token.value.clone()),
+                        token.position,
+                    ));
+                }
+            }
+        }
+        Self::new(input)
+    }
+
     fn read_expression(
         input: &mut VecDeque<Positioned<Token>>,
     ) -> Result<(Option<SyntaxTreeNode>, ExpressionEnd), Positioned<SyntaxError>> {
@@ -471,10 +2305,60 @@ impl SyntaxTree {
         // operations to the list.
         let mut ooos: VecDeque<OperandOrOperator> = VecDeque::new();
 
+        // If the operands and operators are ended by a `?`, they are the condition of a
+        // conditional, and the rest of the conditional is read once they have been combined.
+        let mut maybe_question_position: Option<Position> = None;
         let expression_end: ExpressionEnd = loop {
+            let follows_operand = ooos.back().is_some_and(OperandOrOperator::ends_operand);
+            // A `|` right after an operand closes an absolute value, like the second one in `|$x|`.
+            // Anywhere else, it opens one.
+            if let (true, Some(Token::Bar)) =
+                (follows_operand, input.front().map(|token| &token.value))
+            {
+                let position = input.pop_front().unwrap().position;
+                break ExpressionEnd::Bar(position);
+            }
+            // A unit right after an operand applies to it, like the `km` in `5 km`. Anywhere else,
+            // it stands for one of that unit.
+            if let (true, Some(Token::Unit(_))) =
+                (follows_operand, input.front().map(|token| &token.value))
+            {
+                let Positioned {
+                    value: Token::Unit(name),
+                    position,
+                } = input.pop_front().unwrap()
+                else {
+                    unreachable!();
+                };
+                ooos.push_back(OperandOrOperator::Unit(Self::unit_node(name, position)?));
+                continue;
+            }
+            // A postfix operator, like the `!` in `5!`, applies to the operand right before it.
+            if let (Some(OperandOrOperator::Operand(_)), Some(Token::UnaryOperator(operator))) =
+                (ooos.back(), input.front().map(|token| &token.value))
+            {
+                if operator.is_postfix() {
+                    let operator = *operator;
+                    let operator_position = input.pop_front().unwrap().position;
+                    let operand = ooos.pop_back().unwrap().unwrap_operand();
+                    let node = UnaryNode {
+                        operator,
+                        operator_position,
+                        operand,
+                    };
+                    ooos.push_back(OperandOrOperator::Operand(SyntaxTreeNode::Unary(Box::new(
+                        node,
+                    ))));
+                    continue;
+                }
+            }
             match Self::read_operand_or_operator(input)? {
                 InputReadResult::Operand(o) => ooos.push_back(OperandOrOperator::Operand(o)),
                 InputReadResult::Operator(o) => ooos.push_back(OperandOrOperator::Operator(o)),
+                InputReadResult::QuestionMark(position) => {
+                    maybe_question_position = Some(position);
+                    break ExpressionEnd::InputEmpty;
+                }
                 InputReadResult::End(e) => break e,
             }
         };
@@ -523,36 +2407,77 @@ impl SyntaxTree {
         // with the nodes surrounding it. Once the `for` loop exits, we should be down to a single
         // node.
         for ordered_operator in ORDERED_BINARY_OPERATORS {
+            // Rather than being grouped left to right, consecutive comparisons are chained so that
+            // `1 < $x <= 10` means `1 < $x` and `$x <= 10`.
+            let chains_comparisons = ordered_operator
+                .iter()
+                .any(|operator| operator.is_comparison());
             loop {
                 let ooo = match ooos.pop_front() {
                     Some(o) => o,
                     None => break,
                 };
-                if ooo.is_operand()
+                if chains_comparisons
+                    && ooo.is_operand()
+                    && ooos.len() >= 4
+                    && ooos[0].in_operator_slice(ordered_operator)
+                    && ooos[1].is_operand()
+                    && ooos[2].in_operator_slice(ordered_operator)
+                    && ooos[3].is_operand()
+                {
+                    let mut operands = vec![ooo.unwrap_operand()];
+                    let mut operators = Vec::new();
+                    while ooos.len() >= 2
+                        && ooos[0].in_operator_slice(ordered_operator)
+                        && ooos[1].is_operand()
+                    {
+                        operators.push(ooos.pop_front().unwrap().unwrap_operator());
+                        operands.push(ooos.pop_front().unwrap().unwrap_operand());
+                    }
+                    let node = ComparisonChainNode {
+                        operands,
+                        operators,
+                    };
+                    temp.push_back(OperandOrOperator::Operand(SyntaxTreeNode::ComparisonChain(
+                        Box::new(node),
+                    )));
+                } else if ooo.ends_operand()
                     && ooos.len() >= 2
                     && ooos[0].in_operator_slice(*ordered_operator)
                     && ooos[1].is_operand()
                 {
-                    let operand_1 = ooo.unwrap_operand();
+                    // Only exponents come before units are applied, so this raises just the unit to
+                    // a power when `ooo` is a unit, like the `m^2` in `5 m^2`.
+                    let is_unit = ooo.is_unit();
+                    let operand_1 = if is_unit {
+                        ooo.unwrap_unit()
+                    } else {
+                        ooo.unwrap_operand()
+                    };
                     let operator = ooos.pop_front().unwrap().unwrap_operator();
                     let operand_2 = ooos.pop_front().unwrap().unwrap_operand();
 
-                    let node = BinaryNode {
+                    let node = SyntaxTreeNode::Binary(Box::new(BinaryNode {
                         operator: operator.value,
                         operator_position: operator.position,
                         operand_1,
                         operand_2,
-                    };
+                    }));
                     // Put this back in `ooos`, not `temp`. This way we check again on the next loop
                     // if there is another consecutive operator that ought to be combined.
-                    ooos.push_front(OperandOrOperator::Operand(SyntaxTreeNode::Binary(
-                        Box::new(node),
-                    )));
+                    ooos.push_front(if is_unit {
+                        OperandOrOperator::Unit(node)
+                    } else {
+                        OperandOrOperator::Operand(node)
+                    });
                 } else {
                     temp.push_back(ooo);
                 }
             }
             mem::swap(&mut temp, &mut ooos);
+            if ordered_operator.contains(&BinaryOperatorToken::Exponent) {
+                Self::apply_units(&mut ooos);
+            }
         }
 
         let root: Option<SyntaxTreeNode> =
@@ -590,9 +2515,64 @@ impl SyntaxTree {
                 ) => {
                     panic!("{} is not in ORDERED_BINARY_OPERATORS", operator.value);
                 }
+                (Some(OperandOrOperator::Unit(_)), _, _)
+                | (_, Some(OperandOrOperator::Unit(_)), _)
+                | (_, _, Some(OperandOrOperator::Unit(_))) => {
+                    panic!("Units should have been applied to the operands before them");
+                }
             };
 
-        Ok((root, expression_end))
+        match maybe_question_position {
+            Some(question_position) => Self::read_conditional(input, root, question_position),
+            None => Ok((root, expression_end)),
+        }
+    }
+
+    // Reads the rest of a conditional, like `$x > 0 ? 1 : 2`, once its condition and `?` have been
+    // read. The value used if the condition doesn't hold extends to the end of the expression, so
+    // conditionals can be chained, like `$x > 0 ? 1 : $x < 0 ? -1 : 0`.
+    fn read_conditional(
+        input: &mut VecDeque<Positioned<Token>>,
+        maybe_condition: Option<SyntaxTreeNode>,
+        question_position: Position,
+    ) -> Result<(Option<SyntaxTreeNode>, ExpressionEnd), Positioned<SyntaxError>> {
+        let condition = match maybe_condition {
+            Some(condition) => condition,
+            None => {
+                return Err(Positioned::new(
+                    MissingOperand(Token::QuestionMark),
+                    question_position,
+                ))
+            }
+        };
+        let (if_true, colon_position) = match Self::read_expression(input)? {
+            (Some(if_true), ExpressionEnd::Colon(position)) => (if_true, position),
+            (None, ExpressionEnd::Colon(_)) => {
+                return Err(Positioned::new(
+                    MissingOperand(Token::QuestionMark),
+                    question_position,
+                ))
+            }
+            _ => return Err(Positioned::new(ConditionalWithoutColon, question_position)),
+        };
+        let (if_false, expression_end) = match Self::read_expression(input)? {
+            (Some(if_false), end) => (if_false, end),
+            (None, _) => {
+                return Err(Positioned::new(
+                    MissingOperand(Token::Colon),
+                    colon_position,
+                ))
+            }
+        };
+        let node = ConditionalNode {
+            condition,
+            if_true,
+            if_false,
+        };
+        Ok((
+            Some(SyntaxTreeNode::Conditional(Box::new(node))),
+            expression_end,
+        ))
     }
 
     // Returns `None` if the input vector is empty or we are at the end of the expression.
@@ -613,6 +2593,8 @@ impl SyntaxTree {
             }
             Token::Comma => return Ok(ExpressionEnd::Comma(position).into()),
             Token::CloseParen => return Ok(ExpressionEnd::CloseParen(position).into()),
+            Token::Colon => return Ok(ExpressionEnd::Colon(position).into()),
+            Token::QuestionMark => return Ok(InputReadResult::QuestionMark(position)),
             Token::BinaryOperator(operator) => {
                 return Ok(InputReadResult::Operator(Positioned::new(
                     operator, position,
@@ -621,16 +2603,189 @@ impl SyntaxTree {
             Token::Variable(name) => {
                 SyntaxTreeNode::Variable(Box::new(VariableNode { name, position }))
             }
-            Token::Number(value) => {
-                SyntaxTreeNode::Number(Box::new(NumericNode { value, position }))
+            Token::Number(value) => SyntaxTreeNode::Number(Box::new(NumericNode {
+                value,
+                dimension: Dimension::NONE,
+                position,
+            })),
+            Token::Quantity(value, dimension) => SyntaxTreeNode::Number(Box::new(NumericNode {
+                value,
+                dimension,
+                position,
+            })),
+            Token::Imaginary(value) => {
+                SyntaxTreeNode::Imaginary(Box::new(ImaginaryNode { value, position }))
+            }
+            // A unit without a value before it stands for one of that unit.
+            Token::Unit(name) => Self::unit_node(name, position)?,
+            Token::Constant(name) => match find_constant(&name) {
+                Some(constant) => SyntaxTreeNode::Number(Box::new(NumericNode {
+                    value: constant.value(),
+                    dimension: Dimension::NONE,
+                    position,
+                })),
+                None => {
+                    return Err(Positioned::new(
+                        UnexpectedToken(Token::Constant(name)),
+                        position,
+                    ))
+                }
+            },
+            Token::MathConstant(constant) => {
+                SyntaxTreeNode::Constant(Box::new(ConstantNode { constant, position }))
+            }
+            Token::Ipv4Address(address) => {
+                if let Some((_, prefix_position)) = Self::cidr_prefix_length(input, 0, position) {
+                    return Err(Positioned::new_span(
+                        CidrOutsideFunction,
+                        position,
+                        prefix_position,
+                    ));
+                }
+                SyntaxTreeNode::Number(Box::new(NumericNode {
+                    value: BigRational::from_integer(BigInt::from(address)),
+                    dimension: Dimension::NONE,
+                    position,
+                }))
+            }
+            Token::UnaryOperator(operator) if operator.is_postfix() => {
+                return Err(Positioned::new(MissingOperand(operator.into()), position));
             }
             Token::UnaryOperator(operator) => Self::read_unary_node(input, operator, position)?,
             Token::OpenParen => Self::read_parenthesized_node(input, position)?,
+            Token::Bar => Self::read_absolute_value_node(input, position)?,
             Token::Function(name) => Self::read_function_node(input, name, position)?,
+            Token::Let => Self::read_let_node(input, position)?,
         };
         Ok(InputReadResult::Operand(node))
     }
 
+    // Multiplies each operand by the units that follow it. This is done right after exponents are
+    // applied, so `2^3 km` is 8 km and `5 m^2` is 5 square meters, but before any other operator,
+    // so `3 GiB / 4 KiB` divides 3 GiB by 4 KiB. Prefix operators and functions only take the
+    // operand before the unit, so `sqrt(4) km` is 2 km.
+    fn apply_units(ooos: &mut VecDeque<OperandOrOperator>) {
+        let mut applied: VecDeque<OperandOrOperator> = VecDeque::new();
+        while let Some(ooo) = ooos.pop_front() {
+            if !ooo.is_unit() {
+                applied.push_back(ooo);
+                continue;
+            }
+            let unit = ooo.unwrap_unit();
+            // Units are only ever read right after an operand or another unit, and the units
+            // before this one have already been applied.
+            let operand = applied.pop_back().unwrap().unwrap_operand();
+            let node = BinaryNode {
+                operator: BinaryOperatorToken::Multiply,
+                operator_position: unit.position(),
+                operand_1: operand,
+                operand_2: unit,
+            };
+            applied.push_back(OperandOrOperator::Operand(SyntaxTreeNode::Binary(
+                Box::new(node),
+            )));
+        }
+        *ooos = applied;
+    }
+
+    fn unit_node(
+        name: String,
+        position: Position,
+    ) -> Result<SyntaxTreeNode, Positioned<SyntaxError>> {
+        // A radian is `180/pi` degrees. Pi is worked out when the node is executed, just like the
+        // `pi` constant, so `pi rad` is exactly 180 degrees at any precision.
+        if is_radian(&name) {
+            return Ok(SyntaxTreeNode::Binary(Box::new(BinaryNode {
+                operator: BinaryOperatorToken::Divide,
+                operator_position: position,
+                operand_1: SyntaxTreeNode::Number(Box::new(NumericNode {
+                    value: BigRational::from_integer(BigInt::from(180)),
+                    dimension: Dimension::ANGLE,
+                    position,
+                })),
+                operand_2: SyntaxTreeNode::Constant(Box::new(ConstantNode {
+                    constant: MathConstant::Pi,
+                    position,
+                })),
+            })));
+        }
+        match expression_unit(&name) {
+            Some((factor, dimension)) => Ok(SyntaxTreeNode::Number(Box::new(NumericNode {
+                value: factor,
+                dimension,
+                position,
+            }))),
+            // The tokenizer only produces known units, but tokens can also come from elsewhere.
+            None => Err(Positioned::new(
+                UnexpectedToken(Token::Unit(name)),
+                position,
+            )),
+        }
+    }
+
+    // Checks whether the address at `address_position` is followed by a prefix length, like the `/8`
+    // in `10.0.0.0/8`, starting at `input[index]`. If so, returns the prefix length and its
+    // position. The prefix length must be written right after the address for the two to be read as
+    // a CIDR block. Otherwise, the `/` is a division.
+    fn cidr_prefix_length(
+        input: &VecDeque<Positioned<Token>>,
+        index: usize,
+        address_position: Position,
+    ) -> Option<(BigRational, Position)> {
+        let adjacent =
+            |first: Position, second: Position| first.start + first.width == second.start;
+        match (input.get(index), input.get(index + 1)) {
+            (
+                Some(Positioned {
+                    value: Token::BinaryOperator(BinaryOperatorToken::Divide),
+                    position: slash_position,
+                }),
+                Some(Positioned {
+                    value: Token::Number(prefix_length),
+                    position: prefix_position,
+                }),
+            ) if adjacent(address_position, *slash_position)
+                && adjacent(*slash_position, *prefix_position) =>
+            {
+                Some((prefix_length.clone(), *prefix_position))
+            }
+            _ => None,
+        }
+    }
+
+    // Reads a CIDR block that makes up an entire function argument, returning the address and the
+    // prefix length as separate operands. Returns `None`, without consuming any input, if the next
+    // argument isn't a CIDR block.
+    fn read_cidr_argument(
+        input: &mut VecDeque<Positioned<Token>>,
+    ) -> Option<(SyntaxTreeNode, SyntaxTreeNode)> {
+        let (address, address_position) = match input.front() {
+            Some(Positioned {
+                value: Token::Ipv4Address(address),
+                position,
+            }) => (*address, *position),
+            _ => return None,
+        };
+        let (prefix_length, prefix_position) =
+            Self::cidr_prefix_length(input, 1, address_position)?;
+        match input.get(3).map(|token| &token.value) {
+            None | Some(Token::Comma) | Some(Token::CloseParen) => {}
+            Some(_) => return None,
+        }
+        input.drain(..3);
+        let address_node = SyntaxTreeNode::Number(Box::new(NumericNode {
+            value: BigRational::from_integer(BigInt::from(address)),
+            dimension: Dimension::NONE,
+            position: address_position,
+        }));
+        let prefix_length_node = SyntaxTreeNode::Number(Box::new(NumericNode {
+            value: prefix_length,
+            dimension: Dimension::NONE,
+            position: prefix_position,
+        }));
+        Some((address_node, prefix_length_node))
+    }
+
     // Returns `None` if the input vector is empty or we are at the end of the expression.
     fn read_operand(
         input: &mut VecDeque<Positioned<Token>>,
@@ -646,6 +2801,10 @@ impl SyntaxTree {
                     Err(op.map(|v| UnexpectedToken(v.into())))
                 }
             }
+            InputReadResult::QuestionMark(position) => Err(Positioned::new(
+                UnexpectedToken(Token::QuestionMark),
+                position,
+            )),
             InputReadResult::End(e) => Ok(OperandReadResult::End(e)),
         }
     }
@@ -655,6 +2814,10 @@ impl SyntaxTree {
         operator: UnaryOperatorToken,
         operator_position: Position,
     ) -> Result<SyntaxTreeNode, Positioned<SyntaxError>> {
+        // `sqrt(x, n)` is another way of writing `root(x, n)`.
+        if operator == UnaryOperatorToken::SquareRoot && Self::starts_with_argument_list(input) {
+            return Self::read_function_node(input, FunctionNameToken::Root, operator_position);
+        }
         let operand = match Self::read_operand(input)? {
             OperandReadResult::Operand(operand) => operand,
             OperandReadResult::End(_) => {
@@ -671,6 +2834,28 @@ impl SyntaxTree {
         })))
     }
 
+    // Whether the input starts with parentheses holding more than one comma separated argument,
+    // like `(8, 3)`.
+    fn starts_with_argument_list(input: &VecDeque<Positioned<Token>>) -> bool {
+        if !matches!(
+            input.front().map(|token| &token.value),
+            Some(Token::OpenParen)
+        ) {
+            return false;
+        }
+        let mut depth = 0;
+        for token in input {
+            match token.value {
+                Token::OpenParen => depth += 1,
+                Token::CloseParen if depth == 1 => return false,
+                Token::CloseParen => depth -= 1,
+                Token::Comma if depth == 1 => return true,
+                _ => {}
+            }
+        }
+        false
+    }
+
     // Assumes that the open parenthesis token has already been pulled off the input vector.
     fn read_parenthesized_node(
         input: &mut VecDeque<Positioned<Token>>,
@@ -684,6 +2869,10 @@ impl SyntaxTree {
             (_, ExpressionEnd::Comma(p)) => {
                 return Err(Positioned::new(UnexpectedToken(Token::Comma), p));
             }
+            (_, ExpressionEnd::Colon(p)) => {
+                return Err(Positioned::new(UnexpectedToken(Token::Colon), p));
+            }
+            (_, ExpressionEnd::Bar(p)) => return Err(Positioned::new(MismatchedBar, p)),
             (_, ExpressionEnd::InputEmpty) => {
                 return Err(Positioned::new(MismatchedOpenParen, open_position));
             }
@@ -695,6 +2884,128 @@ impl SyntaxTree {
         })))
     }
 
+    // Reads an absolute value written with bars, like `|$x - 3|`, which is the same as
+    // `abs($x - 3)`. Assumes that the opening bar has already been pulled off the input vector.
+    fn read_absolute_value_node(
+        input: &mut VecDeque<Positioned<Token>>,
+        open_position: Position,
+    ) -> Result<SyntaxTreeNode, Positioned<SyntaxError>> {
+        let (operand, close_position) = match Self::read_expression(input)? {
+            (Some(operand), ExpressionEnd::Bar(close_position)) => (operand, close_position),
+            // A bar can only close an absolute value right after an operand, so the bars can't be
+            // empty.
+            (None, ExpressionEnd::Bar(_)) => panic!("Absolute value bars can't be empty"),
+            (_, ExpressionEnd::Comma(p)) => {
+                return Err(Positioned::new(UnexpectedToken(Token::Comma), p));
+            }
+            (_, ExpressionEnd::Colon(p)) => {
+                return Err(Positioned::new(UnexpectedToken(Token::Colon), p));
+            }
+            (_, ExpressionEnd::CloseParen(p)) => {
+                return Err(Positioned::new(MismatchedCloseParen, p));
+            }
+            (_, ExpressionEnd::InputEmpty) => {
+                return Err(Positioned::new(MismatchedBar, open_position));
+            }
+        };
+        // The bars together take the place of the `abs`.
+        Ok(SyntaxTreeNode::Unary(Box::new(UnaryNode {
+            operator: UnaryOperatorToken::AbsoluteValue,
+            operator_position: Position::from_span(open_position, close_position),
+            operand,
+        })))
+    }
+
+    // Assumes that the `let` token has already been pulled off the input vector. The value being
+    // bound extends to the first `in` outside of parentheses, and the body extends as far as the
+    // enclosing expression does. Since `in` is also the unit for inches, the value can only use that
+    // unit inside parentheses.
+    fn read_let_node(
+        input: &mut VecDeque<Positioned<Token>>,
+        let_position: Position,
+    ) -> Result<SyntaxTreeNode, Positioned<SyntaxError>> {
+        let name = match (input.pop_front(), input.pop_front()) {
+            (
+                Some(Positioned {
+                    value: Token::Variable(name),
+                    position,
+                }),
+                Some(Positioned {
+                    value: Token::AssignmentOperator,
+                    position: _,
+                }),
+            ) => {
+                if answer_index(&name).is_some() {
+                    return Err(Positioned::new(
+                        ReservedVariable(name.to_string()),
+                        position,
+                    ));
+                }
+                name
+            }
+            _ => return Err(Positioned::new(LetWithoutVariable, let_position)),
+        };
+
+        let mut depth: usize = 0;
+        let mut maybe_in_index = None;
+        for (index, token) in input.iter().enumerate() {
+            match &token.value {
+                Token::OpenParen => depth += 1,
+                Token::CloseParen | Token::Comma if depth == 0 => break,
+                Token::CloseParen => depth -= 1,
+                Token::Unit(unit) if depth == 0 && unit == "in" => {
+                    maybe_in_index = Some(index);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let in_index = match maybe_in_index {
+            Some(index) => index,
+            None => return Err(Positioned::new(LetWithoutIn, let_position)),
+        };
+        let mut value_input: VecDeque<Positioned<Token>> = input.drain(..in_index).collect();
+        // This is the `in` that was just found.
+        input.pop_front();
+
+        let value = match Self::read_expression(&mut value_input)? {
+            (Some(value), ExpressionEnd::InputEmpty) => value,
+            (None, _) => return Err(Positioned::new(MissingOperand(Token::Let), let_position)),
+            (Some(_), ExpressionEnd::Comma(p)) => {
+                return Err(Positioned::new(UnexpectedToken(Token::Comma), p));
+            }
+            (Some(_), ExpressionEnd::CloseParen(p)) => {
+                return Err(Positioned::new(MismatchedCloseParen, p));
+            }
+            (Some(_), ExpressionEnd::Colon(p)) => {
+                return Err(Positioned::new(UnexpectedToken(Token::Colon), p));
+            }
+            (Some(_), ExpressionEnd::Bar(p)) => return Err(Positioned::new(MismatchedBar, p)),
+        };
+
+        let (maybe_body, body_end) = Self::read_expression(input)?;
+        // The body ends where the enclosing expression does, so whatever ended it is put back for
+        // the enclosing expression to find.
+        match body_end {
+            ExpressionEnd::Comma(p) => input.push_front(Positioned::new(Token::Comma, p)),
+            ExpressionEnd::CloseParen(p) => input.push_front(Positioned::new(Token::CloseParen, p)),
+            ExpressionEnd::Colon(p) => input.push_front(Positioned::new(Token::Colon, p)),
+            ExpressionEnd::Bar(p) => input.push_front(Positioned::new(Token::Bar, p)),
+            ExpressionEnd::InputEmpty => {}
+        }
+        let body = match maybe_body {
+            Some(body) => body,
+            None => return Err(Positioned::new(MissingOperand(Token::Let), let_position)),
+        };
+
+        Ok(SyntaxTreeNode::Let(Box::new(LetNode {
+            let_position,
+            name,
+            value,
+            body,
+        })))
+    }
+
     // Note that we do not validate function argument count when we build the syntax tree. We
     // validate it at execution time.
     fn read_function_node(
@@ -742,12 +3053,41 @@ impl SyntaxTree {
         // Read arguments until we find the close parenthesis.
         let mut maybe_comma_pos: Option<Position> = None;
         let close_paren_pos = loop {
+            if let Some((address, prefix_length)) = Self::read_cidr_argument(input) {
+                operands.push(address);
+                operands.push(prefix_length);
+                match input.pop_front() {
+                    Some(Positioned {
+                        value: Token::Comma,
+                        position,
+                    }) => {
+                        maybe_comma_pos = Some(position);
+                        continue;
+                    }
+                    Some(Positioned {
+                        value: Token::CloseParen,
+                        position,
+                    }) => break position,
+                    _ => {
+                        return Err(Positioned::new(
+                            MismatchedOpenParen,
+                            post_fn_name_token.position,
+                        ));
+                    }
+                }
+            }
             match Self::read_expression(input)? {
                 (Some(operand), end) => {
                     operands.push(operand);
                     match end {
                         ExpressionEnd::Comma(pos) => maybe_comma_pos = Some(pos),
                         ExpressionEnd::CloseParen(pos) => break pos,
+                        ExpressionEnd::Colon(pos) => {
+                            return Err(Positioned::new(UnexpectedToken(Token::Colon), pos));
+                        }
+                        ExpressionEnd::Bar(pos) => {
+                            return Err(Positioned::new(MismatchedBar, pos));
+                        }
                         ExpressionEnd::InputEmpty => {
                             return Err(Positioned::new(
                                 MismatchedOpenParen,
@@ -765,6 +3105,12 @@ impl SyntaxTree {
                             return Err(Positioned::new(CommaWithoutOperandBefore, pos));
                         }
                         ExpressionEnd::CloseParen(pos) => break pos,
+                        ExpressionEnd::Colon(pos) => {
+                            return Err(Positioned::new(UnexpectedToken(Token::Colon), pos));
+                        }
+                        ExpressionEnd::Bar(pos) => {
+                            return Err(Positioned::new(MismatchedBar, pos));
+                        }
                         ExpressionEnd::InputEmpty => {
                             return Err(Positioned::new(
                                 MismatchedOpenParen,
@@ -784,16 +3130,158 @@ impl SyntaxTree {
         Ok(SyntaxTreeNode::Function(Box::new(node)))
     }
 
+    /// Describes the structure of the tree, with one line per node. Each node's operands are listed
+    /// below it, indented one level further.
+    pub fn outline(&self) -> String {
+        let mut output = String::new();
+        let depth = match &self.maybe_result_var {
+            Some(result_var) => {
+                output.push_str(&format!("Assignment to '{}'", result_var.value));
+                1
+            }
+            None => 0,
+        };
+        self.root.write_outline(&mut output, depth);
+        output
+    }
+
+    /// Determines the dimension of the expression's result, such as whether it is a duration,
+    /// without evaluating it. Returns an error if the expression combines values whose dimensions
+    /// are incompatible, like adding a duration to a plain number. Returns `None` if the dimension
+    /// can't be known ahead of time because it depends on a variable or a custom function.
+    pub fn dimension(&self) -> Result<Option<Dimension>, CalculatorFailure> {
+        self.root.dimension()
+    }
+
     pub fn execute(
+        self,
+        maybe_input_history_id: Option<i64>,
+        maybe_vars: Option<&mut VariableStore>,
+        maybe_db: Option<&mut dyn Storage>,
+        args: &Args,
+    ) -> Result<Value, CalculatorFailure> {
+        self.execute_traced(maybe_input_history_id, maybe_vars, maybe_db, args, None)
+    }
+
+    /// Like `execute`, but if an observer is given, it is called with each step of the evaluation
+    /// as it happens. A step is reported once the value of a node has been determined, so the
+    /// operands of a node are reported before the node itself is.
+    pub fn execute_traced(
+        self,
+        maybe_input_history_id: Option<i64>,
+        maybe_vars: Option<&mut VariableStore>,
+        maybe_db: Option<&mut dyn Storage>,
+        args: &Args,
+        maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+    ) -> Result<Value, CalculatorFailure> {
+        self.execute_interruptible(
+            maybe_input_history_id,
+            maybe_vars,
+            maybe_db,
+            args,
+            maybe_observer,
+            None,
+        )
+    }
+
+    /// Like `execute_traced`, but if an `Interrupt` is given, the evaluation stops with
+    /// `MathExecutionError::Cancelled` soon after it is cancelled, and long-running operations
+    /// report their progress to it.
+    pub fn execute_interruptible(
+        self,
+        maybe_input_history_id: Option<i64>,
+        maybe_vars: Option<&mut VariableStore>,
+        maybe_db: Option<&mut dyn Storage>,
+        args: &Args,
+        maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+    ) -> Result<Value, CalculatorFailure> {
+        self.execute_warned(
+            maybe_input_history_id,
+            maybe_vars,
+            maybe_db,
+            args,
+            maybe_observer,
+            maybe_interrupt,
+            None,
+        )
+    }
+
+    /// Like `execute_interruptible`, but if a vector is given, any warnings about the evaluation,
+    /// such as taking the modulus of non-integers, are added to it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute_warned(
+        self,
+        maybe_input_history_id: Option<i64>,
+        maybe_vars: Option<&mut VariableStore>,
+        maybe_db: Option<&mut dyn Storage>,
+        args: &Args,
+        maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Value, CalculatorFailure> {
+        self.execute_in_scope(
+            maybe_input_history_id,
+            maybe_vars,
+            maybe_db,
+            args,
+            maybe_observer,
+            maybe_interrupt,
+            maybe_warnings,
+            None,
+        )
+    }
+
+    /// Like `execute`, but `name` refers to `value` while the expression is evaluated, just as if
+    /// the expression were the body of `let name = value in ...`. The value is never stored in the
+    /// `VariableStore`. This is handy for evaluating an expression at many values of a variable.
+    pub fn execute_bound(
+        self,
+        name: &VariableName,
+        value: BigRational,
+        maybe_vars: Option<&mut VariableStore>,
+        maybe_db: Option<&mut dyn Storage>,
+        args: &Args,
+    ) -> Result<Value, CalculatorFailure> {
+        let scope = Scope {
+            name,
+            value: Value::Real(value),
+            maybe_outer: None,
+        };
+        self.execute_in_scope(
+            None,
+            maybe_vars,
+            maybe_db,
+            args,
+            None,
+            None,
+            None,
+            Some(&scope),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn execute_in_scope(
         self,
         maybe_input_history_id: Option<i64>,
         mut maybe_vars: Option<&mut VariableStore>,
-        mut maybe_db: Option<&mut SavedData>,
+        mut maybe_db: Option<&mut dyn Storage>,
         args: &Args,
-    ) -> Result<BigRational, CalculatorFailure> {
-        let result = self
-            .root
-            .execute(maybe_vars.as_deref_mut(), maybe_db.as_deref_mut(), args)?;
+        maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_warnings: Option<&mut Vec<Warning>>,
+        maybe_scope: Option<&Scope>,
+    ) -> Result<Value, CalculatorFailure> {
+        self.root.dimension()?;
+        let result = self.root.execute(
+            maybe_vars.as_deref_mut(),
+            reborrow(&mut maybe_db),
+            args,
+            maybe_observer,
+            maybe_interrupt,
+            maybe_scope,
+            maybe_warnings,
+        )?;
         if let Some(result_var) = self.maybe_result_var {
             let var = Variable {
                 name: result_var.value,
@@ -806,22 +3294,78 @@ impl SyntaxTree {
         }
         Ok(result)
     }
+
+    /// Like `execute_warned`, but if the whole expression is a function that produces multiple
+    /// values, like `divmod`, all of them are returned, in order. Otherwise, the result is returned
+    /// as the only value. Multiple values can't be assigned to a variable.
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute_values(
+        self,
+        maybe_input_history_id: Option<i64>,
+        maybe_vars: Option<&mut VariableStore>,
+        maybe_db: Option<&mut dyn Storage>,
+        args: &Args,
+        maybe_observer: Option<&mut dyn FnMut(&TraceStep)>,
+        maybe_interrupt: Option<&Interrupt>,
+        maybe_warnings: Option<&mut Vec<Warning>>,
+    ) -> Result<Vec<Value>, CalculatorFailure> {
+        let node = match self.root {
+            SyntaxTreeNode::Function(node)
+                if node.function_name.produces_multiple_values()
+                    && self.maybe_result_var.is_none() =>
+            {
+                node
+            }
+            root => {
+                let tree = SyntaxTree {
+                    maybe_result_var: self.maybe_result_var,
+                    root,
+                };
+                return tree
+                    .execute_warned(
+                        maybe_input_history_id,
+                        maybe_vars,
+                        maybe_db,
+                        args,
+                        maybe_observer,
+                        maybe_interrupt,
+                        maybe_warnings,
+                    )
+                    .map(|value| vec![value]);
+            }
+        };
+        node.dimension()?;
+        (*node).execute_values(
+            maybe_vars,
+            maybe_db,
+            args,
+            maybe_observer,
+            maybe_interrupt,
+            None,
+            maybe_warnings,
+        )
+    }
 }
 
 #[cfg(test)]
 mod syntax_tree_tests {
     use crate::{
-        error::SyntaxError,
+        error::{CalculatorFailure, InputErrorKind, MathExecutionError, SyntaxError},
+        operations::parse_decimal,
         position::Positioned,
-        syntax_tree::{SyntaxTree, SyntaxTreeNode},
+        syntax_tree::{SyntaxTree, SyntaxTreeNode, TraceStep},
         token::{
             BinaryOperatorToken::{self, Add, Divide, Exponent, Modulus, Multiply, Subtract},
             FunctionNameToken::{self, Max},
-            ParsedInput, Tokenizer,
+            ParsedInput, Token, Tokenizer,
             UnaryOperatorToken::{self, Negate},
         },
+        units::Dimension,
+        value::Value,
+        variable::VariableStore,
+        Args,
     };
-    use num::BigInt;
+    use num::{pow::Pow, BigInt, BigRational, One, Signed};
     use std::collections::VecDeque;
 
     fn str_to_syntax_tree(input: &str) -> Result<SyntaxTree, Positioned<SyntaxError>> {
@@ -853,7 +3397,7 @@ mod syntax_tree_tests {
             SyntaxTreeNode::Variable(n) => n,
             _ => panic!(),
         };
-        assert_eq!(&node.name, value);
+        assert_eq!(&*node.name, value);
         assert_eq!(node.position.start, start);
         assert_eq!(node.position.width, width);
     }
@@ -973,7 +3517,7 @@ mod syntax_tree_tests {
         let st = str_to_syntax_tree("$var=123").unwrap();
         match st.maybe_result_var {
             Some(var_name) => {
-                assert_eq!(&var_name.value, "$var");
+                assert_eq!(&*var_name.value, "$var");
                 assert_eq!(var_name.position.start, 0);
                 assert_eq!(var_name.position.width, 4);
             }
@@ -1117,4 +3661,1054 @@ mod syntax_tree_tests {
         assert_eq!(operands_max_2.len(), 1);
         assert_int(operands_max_2.pop_front().unwrap(), 5, 20, 1);
     }
+
+    #[test]
+    fn outline() {
+        let st = str_to_syntax_tree("$x = -(1 + 2) * max(3, $y)").unwrap();
+        assert_eq!(
+            st.outline(),
+            concat!(
+                "Assignment to '$x'\n",
+                "  Multiplication Operator (*)\n",
+                "    Negation Operator (-)\n",
+                "      Parentheses\n",
+                "        Addition Operator (+)\n",
+                "          Number (1)\n",
+                "          Number (2)\n",
+                "    Max Function\n",
+                "      Number (3)\n",
+                "      Variable '$y'",
+            )
+        );
+    }
+
+    #[test]
+    fn dimensions() {
+        let dimension = |input: &str| str_to_syntax_tree(input).unwrap().dimension();
+        assert_eq!(dimension("1 + 2").unwrap(), Some(Dimension::NONE));
+        assert_eq!(dimension("1h30m + 45m").unwrap(), Some(Dimension::TIME));
+        assert_eq!(dimension("90min / 4").unwrap(), Some(Dimension::TIME));
+        assert_eq!(dimension("1h / 30m").unwrap(), Some(Dimension::NONE));
+        assert_eq!(
+            dimension("-(2h) ^ -2").unwrap(),
+            Some(Dimension::TIME.pow(-2).unwrap())
+        );
+        assert_eq!(dimension("sqrt(1h * 1h)").unwrap(), Some(Dimension::TIME));
+        assert_eq!(dimension("max(1h, $x, 5m)").unwrap(), Some(Dimension::TIME));
+        assert_eq!(dimension("$x * 1h").unwrap(), None);
+        assert_eq!(dimension("$x + 1h").unwrap(), Some(Dimension::TIME));
+        assert_eq!(dimension("3 GiB / 4 KiB").unwrap(), Some(Dimension::NONE));
+        assert_eq!(dimension("(1 + 2) MB * 2").unwrap(), Some(Dimension::DATA));
+        assert_eq!(dimension("KiB").unwrap(), Some(Dimension::DATA));
+        assert_eq!(dimension("sum(1h, 30m)").unwrap(), Some(Dimension::TIME));
+        assert_eq!(
+            dimension("prod(2, 1h, 3h)").unwrap(),
+            Some(Dimension::TIME.pow(2).unwrap())
+        );
+        assert_eq!(dimension("prod(1h, $x)").unwrap(), None);
+        assert_eq!(
+            dimension("median(1h, 2m, 3s)").unwrap(),
+            Some(Dimension::TIME)
+        );
+        assert_eq!(dimension("sign(-1h)").unwrap(), Some(Dimension::NONE));
+        assert_eq!(
+            dimension("recip(1h)").unwrap(),
+            Some(Dimension::TIME.pow(-1).unwrap())
+        );
+        assert_eq!(
+            dimension("clamp(1h, 0s, 2h)").unwrap(),
+            Some(Dimension::TIME)
+        );
+        assert_eq!(
+            dimension("variance(1h, 2h)").unwrap(),
+            Some(Dimension::TIME.pow(2).unwrap())
+        );
+
+        for input in [
+            "1h + 1",
+            "1 KiB - 1",
+            "min(1, 2h)",
+            "sum(1h, 1)",
+            "clamp(1h, 0, 2h)",
+            "2 ^ 1s",
+            "sqrt(1h)",
+            "1h ^ $x",
+            "1h ^ 1.5",
+        ] {
+            match dimension(input) {
+                Err(CalculatorFailure::InputError(error)) => match error.value {
+                    InputErrorKind::Math(
+                        MathExecutionError::DimensionMismatch(..)
+                        | MathExecutionError::InvalidDimensionPower(_),
+                    ) => {}
+                    kind => panic!("Unexpected error for '{}': {}", input, kind),
+                },
+                _ => panic!("Expected an error for '{}'", input),
+            }
+        }
+    }
+
+    #[test]
+    fn unit_precedence() {
+        let evaluate = |input: &str| {
+            let st = str_to_syntax_tree(input).unwrap();
+            let dimension = st.dimension().unwrap().unwrap();
+            let value = st.execute(None, None, None, &Args::default()).unwrap();
+            (value, dimension)
+        };
+        let int = |i: i64| Value::from(BigInt::from(i));
+        let area = Dimension::LENGTH.pow(2).unwrap();
+        // `^` after a unit applies to the unit alone.
+        assert_eq!(evaluate("5 m^2"), (int(5), area));
+        assert_eq!(evaluate("2 km^2"), (int(2_000_000), area));
+        assert_eq!(evaluate("3 m^2 / 3 m"), (int(1), Dimension::LENGTH));
+        // A unit applies to the whole power or function before it.
+        assert_eq!(evaluate("2^3 km"), (int(8_000), Dimension::LENGTH));
+        assert_eq!(evaluate("sqrt(4) km"), (int(2_000), Dimension::LENGTH));
+        assert_eq!(evaluate("-2 km"), (int(-2_000), Dimension::LENGTH));
+        assert_eq!(evaluate("|-2 km|"), (int(2_000), Dimension::LENGTH));
+        assert_eq!(evaluate("3 GiB / 4 KiB"), (int(786_432), Dimension::NONE));
+        let (value, dimension) = evaluate("sqrt(2 km^2)");
+        assert_eq!(dimension, Dimension::LENGTH);
+        let difference = value
+            - Value::Real(BigRational::new(
+                BigInt::from(14_142_135_623_i64),
+                BigInt::from(10_000_000),
+            ));
+        assert!(
+            matches!(difference, Value::Real(r) if r.abs() < BigRational::new(BigInt::one(), BigInt::from(1_000_000)))
+        );
+    }
+
+    #[test]
+    fn dimension_mismatch_positions() {
+        let error_position = |input: &str| match str_to_syntax_tree(input).unwrap().dimension() {
+            Err(CalculatorFailure::InputError(error)) => match error.value {
+                InputErrorKind::Math(MathExecutionError::DimensionMismatch(..)) => {
+                    let position = error.maybe_position.unwrap();
+                    (position.start, position.width)
+                }
+                kind => panic!("Unexpected error for '{}': {}", input, kind),
+            },
+            _ => panic!("Expected an error for '{}'", input),
+        };
+        // Errors span both of the values that don't match.
+        assert_eq!(error_position("5 m + 3 s"), (0, 9));
+        assert_eq!(error_position("1 + (2 km - 1 h) * 2"), (5, 10));
+        assert_eq!(error_position("max($x, 1 KiB, 2, 3)"), (8, 8));
+        // A value that has to be a plain number is pointed at on its own.
+        assert_eq!(error_position("2 ^ (3 s)"), (4, 5));
+    }
+
+    #[test]
+    fn cidr_blocks() {
+        let st = str_to_syntax_tree("insubnet(10.1.2.3, 10.0.0.0/8)").unwrap();
+        let mut operands = assert_function(st.root, FunctionNameToken::InSubnet, 0, 8, 8, 22);
+        assert_int(operands.pop_front().unwrap(), 0x0A010203, 9, 8);
+        assert_int(operands.pop_front().unwrap(), 0x0A000000, 19, 8);
+        assert_int(operands.pop_front().unwrap(), 8, 28, 1);
+        assert!(operands.is_empty());
+
+        // With a space, the `/` is a division.
+        let st = str_to_syntax_tree("10.0.0.0 /8").unwrap();
+        let (address, prefix_length) = assert_binary_operator(st.root, Divide, 9, 1, 0, 11);
+        assert_int(address, 0x0A000000, 0, 8);
+        assert_int(prefix_length, 8, 10, 1);
+
+        for input in ["10.0.0.0/8", "hosts(10.0.0.0/8 + 1)"] {
+            let error = str_to_syntax_tree(input).unwrap_err();
+            match error.value {
+                SyntaxError::CidrOutsideFunction => {}
+                _ => panic!("Unexpected error for '{}'", input),
+            }
+            assert_eq!(error.position.width, 10);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let st = str_to_syntax_tree("$x = 1.5 * max(2, $y)").unwrap();
+        let json = serde_json::to_string(&st).unwrap();
+        assert!(json.contains(r#"{"Number":{"value":"3/2","position":{"start":5,"width":3}}}"#));
+        let deserialized: SyntaxTree = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.outline(), st.outline());
+    }
+
+    #[test]
+    fn trace() {
+        let st = str_to_syntax_tree("-(1 + 2) * 3").unwrap();
+        let mut steps: Vec<(String, usize, usize, Value)> = Vec::new();
+        let mut observer = |step: &TraceStep| {
+            steps.push((
+                step.kind.to_string(),
+                step.position.start,
+                step.position.width,
+                step.value.clone(),
+            ));
+        };
+        let result = st
+            .execute_traced(None, None, None, &Args::default(), Some(&mut observer))
+            .unwrap();
+        assert_eq!(result, BigRational::from_integer(BigInt::from(-9)));
+
+        let int = |i: i64| Value::from(BigInt::from(i));
+        assert_eq!(
+            steps,
+            vec![
+                ("Number (1)".to_string(), 2, 1, int(1)),
+                ("Number (2)".to_string(), 6, 1, int(2)),
+                ("Addition Operator (+)".to_string(), 2, 5, int(3)),
+                ("Parentheses".to_string(), 1, 7, int(3)),
+                ("Negation Operator (-)".to_string(), 0, 8, int(-3)),
+                ("Number (3)".to_string(), 11, 1, int(3)),
+                ("Multiplication Operator (*)".to_string(), 0, 12, int(-9)),
+            ]
+        );
+    }
+
+    #[test]
+    fn multiple_values() {
+        let execute_values = |input: &str| {
+            str_to_syntax_tree(input).unwrap().execute_values(
+                None,
+                None,
+                None,
+                &Args::default(),
+                None,
+                None,
+                None,
+            )
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        assert_eq!(
+            execute_values("divmod(17, 5)").unwrap(),
+            vec![int(3), int(2)]
+        );
+        assert_eq!(
+            execute_values("divmod(-17, 5)").unwrap(),
+            vec![int(-3), int(-2)]
+        );
+        assert_eq!(
+            execute_values("factor(360)").unwrap(),
+            vec![int(2), int(2), int(2), int(3), int(3), int(5)]
+        );
+        assert_eq!(execute_values("factor(97)").unwrap(), vec![int(97)]);
+        for input in ["factor(-12)", "factor(1)"] {
+            match execute_values(input) {
+                Err(CalculatorFailure::InputError(e)) => {
+                    assert!(e
+                        .value
+                        .to_string()
+                        .contains("Expected an integer greater than 1"));
+                }
+                _ => panic!("Expected an error for '{}'", input),
+            }
+        }
+        assert_eq!(execute_values("2 * 3").unwrap(), vec![int(6)]);
+
+        for input in ["1 + divmod(7, 2)", "(factor(12))", "$x = divmod(7, 2)"] {
+            match execute_values(input) {
+                Err(CalculatorFailure::InputError(e)) => match e.value {
+                    InputErrorKind::Math(MathExecutionError::MultipleValues(_)) => {}
+                    _ => panic!("Unexpected error for '{}'", input),
+                },
+                _ => panic!("Expected an error for '{}'", input),
+            }
+        }
+    }
+
+    #[test]
+    fn let_bindings() {
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        let mut vars = VariableStore::new();
+        let mut execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, Some(&mut vars), None, &Args::default())
+                .unwrap()
+        };
+        assert_eq!(execute("let $h = 3 in $h^2 + $h"), int(12));
+        assert_eq!(
+            execute("2 * (let $a = 2 in let $b = $a + 1 in $a * $b) + 1"),
+            int(13)
+        );
+        assert_eq!(execute("max(let $x = 4 in $x * 2, 7)"), int(8));
+        assert_eq!(execute("let $x = 2 in let $x = $x * 10 in $x"), int(20));
+        assert_eq!(execute("$y = let $x = 5 in $x + 1"), int(6));
+        assert_eq!(execute("$y + 1"), int(7));
+        assert!(vars.get("$x".into(), None).unwrap().is_none());
+
+        let st = str_to_syntax_tree("let $h = 3 in $h + 1").unwrap();
+        assert_eq!(
+            st.outline(),
+            "Let Binding '$h'\n  Number (3)\n  Addition Operator (+)\n    Variable '$h'\n    Number (1)"
+        );
+
+        assert!(matches!(
+            str_to_syntax_tree("let 3 in 4").unwrap_err().value,
+            SyntaxError::LetWithoutVariable
+        ));
+        assert!(matches!(
+            str_to_syntax_tree("let $h = 3").unwrap_err().value,
+            SyntaxError::LetWithoutIn
+        ));
+        for input in ["let $h = 3 in", "let $h = in 4"] {
+            assert!(matches!(
+                str_to_syntax_tree(input).unwrap_err().value,
+                SyntaxError::MissingOperand(Token::Let)
+            ));
+        }
+    }
+
+    #[test]
+    fn comparisons() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+                .unwrap()
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        assert_eq!(execute("1 < 2"), int(1));
+        assert_eq!(execute("2<=1"), int(0));
+        assert_eq!(execute("3 >= 3"), int(1));
+        assert_eq!(execute("1 + 1 == 2"), int(1));
+        assert_eq!(execute("2 != 2"), int(0));
+        assert_eq!(execute("1 < 5 <= 10"), int(1));
+        assert_eq!(execute("1 < 11 <= 10"), int(0));
+        assert_eq!(execute("3 > 2 > 1"), int(1));
+        // Evaluation stops at the first comparison that doesn't hold, so the unknown variable is
+        // never looked up.
+        assert_eq!(execute("2 < 1 < $unknown"), int(0));
+
+        let st = str_to_syntax_tree("1 < 2 - 1 <= 3").unwrap();
+        assert_eq!(
+            st.outline(),
+            concat!(
+                "Comparison Chain (Less Than Operator (<), Less Than or Equal Operator (<=))\n",
+                "  Number (1)\n",
+                "  Subtraction Operator (-)\n",
+                "    Number (2)\n",
+                "    Number (1)\n",
+                "  Number (3)"
+            )
+        );
+        let st = str_to_syntax_tree("$x = 1 == 1").unwrap();
+        assert_eq!(
+            st.outline(),
+            "Assignment to '$x'\n  Equality Operator (==)\n    Number (1)\n    Number (1)"
+        );
+    }
+
+    #[test]
+    fn logical_operators() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+                .unwrap()
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        assert_eq!(execute("1 < 2 and 2 < 3"), int(1));
+        assert_eq!(execute("1 < 2 && 3 < 2"), int(0));
+        assert_eq!(execute("0 or 5"), int(1));
+        assert_eq!(execute("0 || 0"), int(0));
+        assert_eq!(execute("not 0"), int(1));
+        assert_eq!(execute("!7"), int(0));
+        assert_eq!(execute("!(1 > 2)"), int(1));
+        assert_eq!(execute("2 != 2"), int(0));
+        // `and` is applied before `or`.
+        assert_eq!(execute("1 or 0 and 0"), int(1));
+        assert_eq!(execute("(1 or 0) and 0"), int(0));
+        // The second operand isn't evaluated if the first one decides the result.
+        assert_eq!(execute("0 and $unknown"), int(0));
+        assert_eq!(execute("1 || 1 / 0"), int(1));
+
+        let st = str_to_syntax_tree("not $x < 1 && 1").unwrap();
+        assert_eq!(
+            st.outline(),
+            concat!(
+                "Logical And Operator (and)\n",
+                "  Less Than Operator (<)\n",
+                "    Logical Not Operator (not)\n",
+                "      Variable '$x'\n",
+                "    Number (1)\n",
+                "  Number (1)"
+            )
+        );
+    }
+
+    #[test]
+    fn absolute_value_bars() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+                .unwrap()
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        assert_eq!(execute("|3 - 5|"), int(2));
+        assert_eq!(execute("|-2| + |3|"), int(5));
+        assert_eq!(execute("2 * |1 - 4| ^ 2"), int(18));
+        assert_eq!(execute("| |-1| - 3 |"), int(2));
+        assert_eq!(execute("-|-4|"), int(-4));
+        assert_eq!(execute("max(|-5|, 2)"), int(5));
+        assert_eq!(execute("|0 ? 1 : -2|"), int(2));
+        assert_eq!(execute("|1| || 0"), int(1));
+        // Where an operand is expected, `||` is two bars.
+        assert_eq!(execute("||-1| - 5|"), int(4));
+        assert_eq!(execute("2 * ||-3| - 5|"), int(4));
+        assert_eq!(execute("max(1, ||-7| - 2|)"), int(5));
+
+        let st = str_to_syntax_tree("|$x|").unwrap();
+        assert_eq!(
+            st.outline(),
+            "Absolute Value Operator (abs)\n  Variable '$x'"
+        );
+        let root = st.root.as_operation_node();
+        assert_eq!((root.position().start, root.position().width), (0, 4));
+
+        let parse_error = |input: &str| str_to_syntax_tree(input).unwrap_err();
+        let error = parse_error("|1 + 2");
+        assert!(matches!(error.value, SyntaxError::MismatchedBar));
+        assert_eq!(error.position.start, 0);
+        let error = parse_error("1 + 2|");
+        assert!(matches!(error.value, SyntaxError::MismatchedBar));
+        assert_eq!(error.position.start, 5);
+        assert!(matches!(
+            parse_error("(|1)|").value,
+            SyntaxError::MismatchedCloseParen
+        ));
+    }
+
+    #[test]
+    fn conditionals() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+                .unwrap()
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        assert_eq!(execute("1 < 2 ? 10 : 20"), int(10));
+        assert_eq!(execute("1 > 2 ? 10 : 20"), int(20));
+        assert_eq!(execute("0 ? 1 : 2 + 3"), int(5));
+        assert_eq!(execute("(0 ? 1 : 2) + 3"), int(5));
+        assert_eq!(execute("(1 ? 1 : 2) + 3"), int(4));
+        assert_eq!(execute("max(1 ? 5 : 6, 2)"), int(5));
+        // Conditionals group from the right, so these pick the first condition that holds.
+        assert_eq!(execute("-5 > 0 ? 1 : -5 < 0 ? -1 : 0"), int(-1));
+        assert_eq!(execute("0 > 0 ? 1 : 0 < 0 ? -1 : 0"), int(0));
+        assert_eq!(execute("1 ? 0 ? 1 : 2 : 3"), int(2));
+        // Only the selected value is evaluated.
+        assert_eq!(execute("1 ? 2 : $unknown"), int(2));
+        assert_eq!(execute("0 ? 1 / 0 : 3"), int(3));
+
+        let st = str_to_syntax_tree("$x = 1 ? 2 : 3").unwrap();
+        assert_eq!(
+            st.outline(),
+            concat!(
+                "Assignment to '$x'\n",
+                "  Conditional Operator (? :)\n",
+                "    Number (1)\n",
+                "    Number (2)\n",
+                "    Number (3)"
+            )
+        );
+
+        let parse_error = |input: &str| str_to_syntax_tree(input).unwrap_err().value;
+        assert!(matches!(
+            parse_error("1 ? 2"),
+            SyntaxError::ConditionalWithoutColon
+        ));
+        assert!(matches!(
+            parse_error("? 1 : 2"),
+            SyntaxError::MissingOperand(Token::QuestionMark)
+        ));
+        assert!(matches!(
+            parse_error("1 ? : 2"),
+            SyntaxError::MissingOperand(Token::QuestionMark)
+        ));
+        assert!(matches!(
+            parse_error("1 ? 2 :"),
+            SyntaxError::MissingOperand(Token::Colon)
+        ));
+        assert!(matches!(
+            parse_error("1 : 2"),
+            SyntaxError::UnexpectedToken(Token::Colon)
+        ));
+        assert!(matches!(
+            parse_error("max(1 : 2)"),
+            SyntaxError::UnexpectedToken(Token::Colon)
+        ));
+    }
+
+    #[test]
+    fn if_function() {
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        let mut vars = VariableStore::new();
+        let mut execute = |input: &str| {
+            str_to_syntax_tree(input).unwrap().execute(
+                None,
+                Some(&mut vars),
+                None,
+                &Args::default(),
+            )
+        };
+        assert_eq!(execute("$x = -4").unwrap(), int(-4));
+        assert_eq!(execute("if($x < 0, -$x, $x)").unwrap(), int(4));
+        assert_eq!(execute("if($x > 0, -$x, $x) * 2").unwrap(), int(-8));
+        // Only the selected value is evaluated.
+        assert_eq!(execute("if(1, 2, $unknown)").unwrap(), int(2));
+        assert_eq!(execute("if(0, 1 / 0, 3)").unwrap(), int(3));
+        assert!(execute("if(1, 1 / 0, 3)").is_err());
+
+        let st = str_to_syntax_tree("if(1, 2, 3)").unwrap();
+        assert_eq!(
+            st.outline(),
+            "If Function\n  Number (1)\n  Number (2)\n  Number (3)"
+        );
+        match execute("if(1, 2)") {
+            Err(CalculatorFailure::InputError(error)) => {
+                assert_eq!(
+                    error.value.to_string(),
+                    "If Function: Expected 3 arguments, found 2"
+                );
+                assert_eq!(error.maybe_position.unwrap().start, 2);
+            }
+            _ => panic!("Expected an error"),
+        }
+        let dimension = |input: &str| str_to_syntax_tree(input).unwrap().dimension();
+        assert_eq!(
+            dimension("if(1 KiB, 1h, 2m)").unwrap(),
+            Some(Dimension::TIME)
+        );
+        assert!(dimension("if(1, 1h, 2)").is_err());
+    }
+
+    #[test]
+    fn factorials() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        assert_eq!(execute("10!").unwrap(), int(3628800));
+        assert_eq!(execute("0!").unwrap(), int(1));
+        assert_eq!(execute("(2 + 3)! - 1").unwrap(), int(119));
+        assert_eq!(execute("3!!").unwrap(), int(720));
+        // Factorials apply before negation and exponentiation.
+        assert_eq!(execute("-3!").unwrap(), int(-6));
+        assert_eq!(execute("2 ^ 3!").unwrap(), int(64));
+        // A `!` that doesn't directly follow an operand is still a logical not.
+        assert_eq!(execute("!0 + 5 != 4").unwrap(), int(1));
+        assert_eq!(
+            execute("25!").unwrap(),
+            BigRational::from_integer("15511210043330985984000000".parse().unwrap())
+        );
+
+        let st = str_to_syntax_tree("$x!").unwrap();
+        assert_eq!(st.outline(), "Factorial Operator (!)\n  Variable '$x'");
+        let root = st.root.as_operation_node();
+        assert_eq!((root.position().start, root.position().width), (0, 3));
+
+        for (input, message) in [
+            ("(-3)!", "Factorial Operator (!): -3 is negative"),
+            ("2.5!", "Factorial Operator (!): 5/2 is not an integer"),
+        ] {
+            match execute(input) {
+                Err(CalculatorFailure::InputError(error)) => match error.value {
+                    InputErrorKind::Math(e @ MathExecutionError::InvalidOperand(..)) => {
+                        assert_eq!(e.to_string(), message)
+                    }
+                    kind => panic!("Unexpected error for '{}': {}", input, kind),
+                },
+                _ => panic!("Expected an error for '{}'", input),
+            }
+        }
+        assert!(matches!(
+            str_to_syntax_tree("sqrt!").unwrap_err().value,
+            SyntaxError::MissingOperand(Token::UnaryOperator(UnaryOperatorToken::Factorial))
+        ));
+    }
+
+    #[test]
+    fn combinatorics() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        assert_eq!(execute("choose(5, 2)").unwrap(), int(10));
+        assert_eq!(execute("choose(52, 5)").unwrap(), int(2598960));
+        assert_eq!(execute("choose(5, 0)").unwrap(), int(1));
+        assert_eq!(execute("choose(3, 5)").unwrap(), int(0));
+        assert_eq!(execute("perm(5, 2)").unwrap(), int(20));
+        assert_eq!(execute("perm(6, 6) == 6!").unwrap(), int(1));
+        assert_eq!(execute("perm(3, 4)").unwrap(), int(0));
+        assert_eq!(
+            execute("choose(100, 50)").unwrap(),
+            BigRational::from_integer("100891344545564193334812497256".parse().unwrap())
+        );
+
+        // Errors point at the argument that is wrong.
+        for (input, message, start) in [
+            ("choose(5, -1)", "Choose Function: -1 is negative", 10),
+            ("perm(2.5, 1)", "Perm Function: 5/2 is not an integer", 5),
+            (
+                "choose(5)",
+                "Choose Function: Expected 2 arguments, found 1",
+                6,
+            ),
+        ] {
+            match execute(input) {
+                Err(CalculatorFailure::InputError(error)) => {
+                    assert_eq!(error.value.to_string(), message);
+                    assert_eq!(error.maybe_position.unwrap().start, start);
+                }
+                _ => panic!("Expected an error for '{}'", input),
+            }
+        }
+    }
+
+    #[test]
+    fn history_aggregates_without_history() {
+        let result =
+            str_to_syntax_tree("histsum(2)")
+                .unwrap()
+                .execute(None, None, None, &Args::default());
+        match result {
+            Err(CalculatorFailure::InputError(error)) => {
+                assert_eq!(error.value.to_string(), "Input history unavailable");
+                assert_eq!(error.maybe_position.unwrap().start, 0);
+            }
+            _ => panic!("Expected an error for 'histsum(2)'"),
+        }
+    }
+
+    #[test]
+    fn sums_and_products() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+                .unwrap()
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        assert_eq!(execute("sum(1, 2, 3.5, -0.5)"), int(6));
+        assert_eq!(execute("prod(2, 3, 1/2, 4)"), int(12));
+        assert_eq!(execute("sum 5 + prod 5"), int(10));
+        assert_eq!(execute("sum()"), int(0));
+        assert_eq!(execute("prod()"), int(1));
+    }
+
+    #[test]
+    fn statistics() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        let ratio = |n: i64, d: i64| BigRational::new(BigInt::from(n), BigInt::from(d));
+        assert_eq!(execute("mean(1, 2, 3, 4)").unwrap(), ratio(5, 2));
+        assert_eq!(execute("avg(-3)").unwrap(), int(-3));
+        assert_eq!(execute("median(5, 1, 3)").unwrap(), int(3));
+        assert_eq!(execute("median(4, 1, 3, 2)").unwrap(), ratio(5, 2));
+        // The sample variance, which divides by one less than the number of values.
+        assert_eq!(
+            execute("variance(2, 4, 4, 4, 5, 5, 7, 9)").unwrap(),
+            ratio(32, 7)
+        );
+        assert_eq!(execute("stddev(1, 2, 3)").unwrap(), int(1));
+        assert_eq!(execute("stddev(1, 1, 1)").unwrap(), int(0));
+
+        for (input, message, start) in [
+            (
+                "median()",
+                "Median Function has no arguments but requires them",
+                0,
+            ),
+            (
+                "stddev(5)",
+                "StdDev Function: Expected at least 2 arguments, found 1",
+                7,
+            ),
+        ] {
+            match execute(input) {
+                Err(CalculatorFailure::InputError(error)) => {
+                    assert_eq!(error.value.to_string(), message);
+                    assert_eq!(error.maybe_position.unwrap().start, start);
+                }
+                _ => panic!("Expected an error for '{}'", input),
+            }
+        }
+    }
+
+    #[test]
+    fn helper_functions() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        assert_eq!(execute("sign(-3.5)").unwrap(), int(-1));
+        assert_eq!(execute("sign(0)").unwrap(), int(0));
+        assert_eq!(execute("sign(1/1000)").unwrap(), int(1));
+        assert_eq!(execute("recip(-1/3)").unwrap(), int(-3));
+        assert_eq!(
+            execute("recip(4)").unwrap(),
+            BigRational::new(BigInt::from(1), BigInt::from(4))
+        );
+        assert_eq!(execute("clamp(5, 1, 3)").unwrap(), int(3));
+        assert_eq!(execute("clamp(-5, 1, 3)").unwrap(), int(1));
+        assert_eq!(execute("clamp(2, 1, 3)").unwrap(), int(2));
+        assert_eq!(execute("clamp(2, 2, 2)").unwrap(), int(2));
+
+        match execute("1 + recip(2 - 2)") {
+            Err(CalculatorFailure::InputError(error)) => {
+                assert!(matches!(
+                    error.value,
+                    InputErrorKind::Math(MathExecutionError::DivisionByZero)
+                ));
+                assert_eq!(error.maybe_position.unwrap().start, 10);
+            }
+            _ => panic!("Expected an error"),
+        }
+        match execute("clamp(2, 3, 1)") {
+            Err(CalculatorFailure::InputError(error)) => {
+                assert_eq!(
+                    error.value.to_string(),
+                    "Clamp Function: The lower bound 3 is greater than the upper bound 1"
+                );
+                let position = error.maybe_position.unwrap();
+                assert_eq!((position.start, position.width), (9, 4));
+            }
+            _ => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    fn floor_division() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        assert_eq!(execute("7 // 2").unwrap(), int(3));
+        assert_eq!(execute("-7 // 2").unwrap(), int(-4));
+        assert_eq!(execute("7 div -2").unwrap(), int(-4));
+        assert_eq!(execute("(15/2) // (1/2)").unwrap(), int(15));
+        // The remainder has the sign of the divisor, unlike with `%`.
+        assert_eq!(execute("-7 mod 3").unwrap(), int(2));
+        assert_eq!(execute("7 mod -3").unwrap(), int(-2));
+        assert_eq!(execute("-7 % 3").unwrap(), int(-1));
+        assert_eq!(
+            execute("7.5 mod 2").unwrap(),
+            BigRational::new(BigInt::from(3), BigInt::from(2))
+        );
+        // `//` binds like `/`, and `mod` like `%`.
+        assert_eq!(execute("1 + 9 // 2 * 3").unwrap(), int(13));
+        assert_eq!(execute("2 * 7 mod 3").unwrap(), int(2));
+
+        for input in ["1 // 0", "1 mod 0"] {
+            match execute(input) {
+                Err(CalculatorFailure::InputError(error)) => assert!(matches!(
+                    error.value,
+                    InputErrorKind::Math(MathExecutionError::DivisionByZero)
+                )),
+                _ => panic!("Expected an error for '{}'", input),
+            }
+        }
+    }
+
+    #[test]
+    fn percent() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+                .unwrap()
+        };
+        let ratio = |n: i64, d: i64| BigRational::new(BigInt::from(n), BigInt::from(d));
+        assert_eq!(execute("200 * 15%"), ratio(30, 1));
+        assert_eq!(execute("15% of 200"), ratio(30, 1));
+        assert_eq!(execute("15% of 200 + 1"), ratio(31, 1));
+        assert_eq!(execute("-15%"), ratio(-3, 20));
+        assert_eq!(execute("(1 + 2)% * 2"), ratio(3, 50));
+        assert_eq!(execute("17 % 5"), ratio(2, 1));
+        assert_eq!(execute("max(50%, 1%)"), ratio(1, 2));
+
+        let st = str_to_syntax_tree("15%").unwrap();
+        assert_eq!(st.outline(), "Percent Operator (%)\n  Number (15)");
+    }
+
+    #[test]
+    fn math_constants() {
+        let close_to = |value: &Value, expected: &str, precision: i32| {
+            let error = (value.as_real().unwrap() - parse_decimal(expected).unwrap()).abs();
+            error < BigRational::from_integer(BigInt::from(10)).pow(-precision)
+        };
+        let st = str_to_syntax_tree("2 * pi * 3").unwrap();
+        assert_eq!(
+            st.outline(),
+            "Multiplication Operator (*)\n  Multiplication Operator (*)\n    Number (2)\n    \
+             Constant (pi)\n  Number (3)"
+        );
+        // The same tree gives a more precise result when more precision is asked for.
+        let expected = "18.849555921538759430775860299677017";
+        let args = |precision: u8| Args {
+            precision,
+            extra_precision: 0,
+            ..Args::default()
+        };
+        let low = st.clone().execute(None, None, None, &args(5)).unwrap();
+        let high = st.execute(None, None, None, &args(30)).unwrap();
+        assert!(close_to(&low, expected, 4));
+        assert!(!close_to(&low, expected, 10));
+        assert!(close_to(&high, expected, 29));
+
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &args(30))
+                .unwrap()
+        };
+        assert!(close_to(
+            &execute("e"),
+            "2.718281828459045235360287471352662",
+            29
+        ));
+        assert!(close_to(&execute("tau / 2 - pi + 1"), "1", 29));
+    }
+
+    #[test]
+    fn roots() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        assert_eq!(execute("root(27, 3)").unwrap(), int(3));
+        assert_eq!(execute("root(-32, 5)").unwrap(), int(-2));
+        assert_eq!(
+            execute("root(16, -4)").unwrap(),
+            BigRational::new(BigInt::from(1), BigInt::from(2))
+        );
+        assert_eq!(execute("sqrt(8, 3) + 1").unwrap(), int(3));
+        // A comma inside a nested function call doesn't make `sqrt` take two arguments.
+        assert_eq!(execute("sqrt(max(4, 9))").unwrap(), int(3));
+
+        let st = str_to_syntax_tree("sqrt(8, 3)").unwrap();
+        assert_eq!(st.outline(), "Root Function\n  Number (8)\n  Number (3)");
+
+        // Even roots of negative numbers are imaginary.
+        assert_eq!(
+            execute("root(-16, 2)").unwrap(),
+            Value::complex(int(0), int(4))
+        );
+        assert_eq!(
+            execute("1 + sqrt(-4)").unwrap(),
+            Value::complex(int(1), int(2))
+        );
+
+        for (input, message, start) in [
+            ("root(2, 0)", "Root Function: The degree can't be 0", 8),
+            ("root(2, 1/2)", "Root Function: 1/2 is not an integer", 8),
+            ("root(0, -2)", "Cannot divide by 0", 0),
+            ("sqrt(0, -3)", "Cannot divide by 0", 0),
+            ("0 ^ -0.5", "Cannot divide by 0", 2),
+        ] {
+            match execute(input) {
+                Err(CalculatorFailure::InputError(error)) => {
+                    assert_eq!(error.value.to_string(), message);
+                    assert_eq!(error.maybe_position.unwrap().start, start);
+                }
+                _ => panic!("Expected an error for '{}'", input),
+            }
+        }
+    }
+
+    #[test]
+    fn complex_numbers() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+        };
+        let int = |i: i64| BigRational::from_integer(BigInt::from(i));
+        let complex = |re: i64, im: i64| Value::complex(int(re), int(im));
+        assert_eq!(execute("sqrt(-1)").unwrap(), complex(0, 1));
+        assert_eq!(execute("(2+3i)*(1-i)").unwrap(), complex(5, 1));
+        assert_eq!(execute("(5+i)/(1-i)").unwrap(), complex(2, 3));
+        assert_eq!(execute("abs(3+4i)").unwrap(), int(5));
+        assert_eq!(execute("(1+i)^4").unwrap(), int(-4));
+        assert_eq!(execute("(-4)^(3/2)").unwrap(), complex(0, -8));
+        assert_eq!(execute("re(2-3i) + im(2-3i)").unwrap(), int(-1));
+        assert_eq!(execute("conj(2-3i)").unwrap(), complex(2, 3));
+        assert_eq!(execute("sum(i, 2, 3i)").unwrap(), complex(2, 4));
+        assert_eq!(execute("i == 1i").unwrap(), int(1));
+        assert_eq!(execute("i != 1").unwrap(), int(1));
+
+        let st = str_to_syntax_tree("2 + 3i").unwrap();
+        assert_eq!(
+            st.outline(),
+            "Addition Operator (+)\n  Number (2)\n  Imaginary Number (3i)"
+        );
+
+        for (input, message, start) in [
+            (
+                "1 < i",
+                "Less Than Operator (<) can only be used with real numbers",
+                2,
+            ),
+            (
+                "2 ^ i",
+                "Exponentiation Operator (^) can only be used with real numbers",
+                4,
+            ),
+            (
+                "max(1, i)",
+                "Max Function can only be used with real numbers",
+                7,
+            ),
+            ("arg(0)", "Arg Function: The angle of 0 is undefined", 4),
+        ] {
+            match execute(input) {
+                Err(CalculatorFailure::InputError(error)) => {
+                    assert_eq!(error.value.to_string(), message);
+                    assert_eq!(error.maybe_position.unwrap().start, start);
+                }
+                _ => panic!("Expected an error for '{}'", input),
+            }
+        }
+    }
+
+    #[test]
+    fn intervals() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+        };
+        let tenths = |i: i64| BigRational::new(BigInt::from(i), BigInt::from(10));
+        let interval = |low: i64, high: i64| Value::interval(tenths(low), tenths(high));
+        assert_eq!(execute("5 ± 0.1").unwrap(), interval(49, 51));
+        assert_eq!(execute("5 +/- -0.1").unwrap(), interval(49, 51));
+        assert_eq!(execute("interval(5.1, 4.9)").unwrap(), interval(49, 51));
+        assert_eq!(execute("(5 ± 0.1) * 2").unwrap(), interval(98, 102));
+        assert_eq!(execute("(1 ± 1) - (1 ± 1)").unwrap(), interval(-20, 20));
+        assert_eq!(execute("(1 ± 1) * (1 ± 1)").unwrap(), interval(0, 40));
+        assert_eq!(execute("(1 ± 1)^2").unwrap(), interval(0, 40));
+        assert_eq!(execute("(-1 ± 1)^2").unwrap(), interval(0, 40));
+        assert_eq!(
+            execute("1 / (2 ± 1)").unwrap(),
+            Value::interval(
+                BigRational::new(BigInt::from(1), BigInt::from(3)),
+                tenths(10)
+            )
+        );
+        assert_eq!(execute("sqrt(4.5 ± 4.5)").unwrap(), interval(0, 30));
+        assert_eq!(execute("|-3 ± 1|").unwrap(), interval(20, 40));
+        assert_eq!(execute("|0 ± 1|").unwrap(), interval(0, 10));
+        assert_eq!(execute("3 ± 0").unwrap(), tenths(30));
+
+        for (input, message, start) in [
+            ("1 / (0 ± 1)", "Cannot divide by 0", 2),
+            (
+                "i * (1 ± 1)",
+                "Values with an uncertainty can't be combined with complex numbers",
+                2,
+            ),
+            (
+                "(1 +/- 0.1) < 2",
+                "Less Than Operator (<) can't be used with uncertain values",
+                12,
+            ),
+            (
+                "max(1 ± 1, 3)",
+                "Max Function can't be used with uncertain values",
+                4,
+            ),
+            (
+                "sqrt(0 ± 1)",
+                "Even roots of negative numbers can't be used with uncertain values",
+                0,
+            ),
+        ] {
+            match execute(input) {
+                Err(CalculatorFailure::InputError(error)) => {
+                    assert_eq!(error.value.to_string(), message);
+                    assert_eq!(error.maybe_position.unwrap().start, start);
+                }
+                _ => panic!("Expected an error for '{}'", input),
+            }
+        }
+    }
+
+    #[test]
+    fn surds() {
+        let execute = |input: &str| {
+            str_to_syntax_tree(input)
+                .unwrap()
+                .execute(None, None, None, &Args::default())
+                .unwrap()
+        };
+        let int = |i: i64| Value::from(BigInt::from(i));
+        let surd =
+            |coefficient: BigRational, radicand: i64| match execute(&format!("sqrt({})", radicand))
+            {
+                Value::Surd { approximation, .. } => Value::surd(
+                    coefficient.clone(),
+                    BigInt::from(radicand),
+                    approximation * coefficient,
+                ),
+                value => panic!("Expected sqrt({}) to be a surd, not {}", radicand, value),
+            };
+        let one = BigRational::one();
+        assert_eq!(execute("sqrt(2)^2"), int(2));
+        assert_eq!(execute("sqrt(2) * sqrt(8)"), int(4));
+        assert_eq!(
+            execute("sqrt(8)"),
+            surd(BigRational::from_integer(2.into()), 2)
+        );
+        assert_eq!(
+            execute("sqrt(2) * sqrt(6)"),
+            surd(BigRational::from_integer(2.into()), 3)
+        );
+        assert_eq!(
+            execute("1 / sqrt(2)"),
+            surd(BigRational::new(1.into(), 2.into()), 2)
+        );
+        assert_eq!(
+            execute("sqrt(2/3)"),
+            surd(BigRational::new(1.into(), 3.into()), 6)
+        );
+        assert_eq!(
+            execute("2^(3/2)"),
+            surd(BigRational::from_integer(2.into()), 2)
+        );
+        assert_eq!(
+            execute("sqrt(2) + sqrt(2)"),
+            surd(BigRational::from_integer(2.into()), 2)
+        );
+        assert_eq!(execute("sqrt(2) - sqrt(2)"), int(0));
+        assert_eq!(
+            execute("sqrt(9/4)"),
+            Value::from(BigRational::new(3.into(), 2.into()))
+        );
+        assert_eq!(execute("sqrt(2)^2 == 2"), int(1));
+        assert_eq!(execute("sqrt(2) < 1.5"), int(1));
+        assert_eq!(execute("sqrt(3)"), surd(one, 3));
+        // Anything that can't be kept exact is approximated.
+        assert!(!execute("sqrt(2) + 1").is_surd());
+        assert!(!execute("sqrt(2) + sqrt(3)").is_surd());
+        assert!(!execute("sqrt(sqrt(2))").is_surd());
+    }
 }
@@ -1,15 +1,44 @@
 use crate::{
+    constants::{find_constant, MathConstant, CONSTANT_PREFIX},
+    custom_function::CustomFunction,
     error::ParseError,
+    network::{format_ipv4, parse_ipv4},
+    operations::MAX_RADIX,
     position::{Position, Positioned},
+    suggestion::closest_matches,
+    units::{
+        byte_size_bits, duration_suffix_seconds, expression_unit, expression_unit_names, is_radian,
+        Dimension,
+    },
+    variable::VariableName,
 };
-use num::{bigint::BigInt, pow::Pow, rational::BigRational};
-use std::{collections::HashMap, fmt};
+use num::{bigint::BigInt, pow::Pow, rational::BigRational, Num, One, Zero};
+use std::{cmp::min, collections::HashMap, fmt, ops::Range, sync::Arc};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize, Serializer};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum UnaryOperatorToken {
     SquareRoot,
     Negate,
     AbsoluteValue,
+    Not,
+    // Written after its operand, like `5!`.
+    Factorial,
+    // Written after its operand, like `15%`.
+    Percent,
+}
+
+impl UnaryOperatorToken {
+    /// Whether the operator is written after its operand rather than before it.
+    pub fn is_postfix(&self) -> bool {
+        matches!(
+            self,
+            UnaryOperatorToken::Factorial | UnaryOperatorToken::Percent
+        )
+    }
 }
 
 impl fmt::Display for UnaryOperatorToken {
@@ -18,27 +47,83 @@ impl fmt::Display for UnaryOperatorToken {
             UnaryOperatorToken::SquareRoot => write!(f, "Square Root Operator (sqrt)"),
             UnaryOperatorToken::Negate => write!(f, "Negation Operator (-)"),
             UnaryOperatorToken::AbsoluteValue => write!(f, "Absolute Value Operator (abs)"),
+            UnaryOperatorToken::Not => write!(f, "Logical Not Operator (not)"),
+            UnaryOperatorToken::Factorial => write!(f, "Factorial Operator (!)"),
+            UnaryOperatorToken::Percent => write!(f, "Percent Operator (%)"),
         }
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum BinaryOperatorToken {
     Add,
     Subtract,
+    /// Gives a value with an uncertainty, written `±` or `+/-`, as in `5 ± 0.1`.
+    PlusMinus,
     Multiply,
     Divide,
+    /// Division rounded down to an integer, written `//` or `div`.
+    IntegerDivide,
     Modulus,
+    /// The remainder of `IntegerDivide`, written `mod`. Unlike `%`, the result has the sign of the
+    /// divisor.
+    FloorModulus,
     Exponent,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+impl BinaryOperatorToken {
+    /// Whether the operator compares its operands, giving 1 if the comparison holds and 0 if it
+    /// doesn't. Comparisons can be chained, like `1 < $x <= 10`.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOperatorToken::Less
+                | BinaryOperatorToken::LessOrEqual
+                | BinaryOperatorToken::Greater
+                | BinaryOperatorToken::GreaterOrEqual
+                | BinaryOperatorToken::Equal
+                | BinaryOperatorToken::NotEqual
+        )
+    }
 }
 
 // TODO: Is there some way to check, ideally at compile time, that every variant of
 // `BinaryOperatorToken` is in `ORDERED_BINARY_OPERATORS`?
 pub const ORDERED_BINARY_OPERATORS: &'static [&'static [BinaryOperatorToken]] = &[
     &[BinaryOperatorToken::Exponent],
-    &[BinaryOperatorToken::Modulus],
-    &[BinaryOperatorToken::Multiply, BinaryOperatorToken::Divide],
-    &[BinaryOperatorToken::Add, BinaryOperatorToken::Subtract],
+    &[
+        BinaryOperatorToken::Modulus,
+        BinaryOperatorToken::FloorModulus,
+    ],
+    &[
+        BinaryOperatorToken::Multiply,
+        BinaryOperatorToken::Divide,
+        BinaryOperatorToken::IntegerDivide,
+    ],
+    &[
+        BinaryOperatorToken::Add,
+        BinaryOperatorToken::Subtract,
+        BinaryOperatorToken::PlusMinus,
+    ],
+    &[
+        BinaryOperatorToken::Less,
+        BinaryOperatorToken::LessOrEqual,
+        BinaryOperatorToken::Greater,
+        BinaryOperatorToken::GreaterOrEqual,
+        BinaryOperatorToken::Equal,
+        BinaryOperatorToken::NotEqual,
+    ],
+    &[BinaryOperatorToken::And],
+    &[BinaryOperatorToken::Or],
 ];
 
 impl fmt::Display for BinaryOperatorToken {
@@ -46,18 +131,152 @@ impl fmt::Display for BinaryOperatorToken {
         match self {
             BinaryOperatorToken::Add => write!(f, "Addition Operator (+)"),
             BinaryOperatorToken::Subtract => write!(f, "Subtraction Operator (-)"),
+            BinaryOperatorToken::PlusMinus => write!(f, "Plus-Minus Operator (±)"),
             BinaryOperatorToken::Multiply => write!(f, "Multiplication Operator (*)"),
             BinaryOperatorToken::Divide => write!(f, "Division Operator (/)"),
+            BinaryOperatorToken::IntegerDivide => write!(f, "Integer Division Operator (//)"),
             BinaryOperatorToken::Modulus => write!(f, "Modulus Operator (%)"),
+            BinaryOperatorToken::FloorModulus => write!(f, "Floor Modulus Operator (mod)"),
             BinaryOperatorToken::Exponent => write!(f, "Exponentiation Operator (^)"),
+            BinaryOperatorToken::Less => write!(f, "Less Than Operator (<)"),
+            BinaryOperatorToken::LessOrEqual => write!(f, "Less Than or Equal Operator (<=)"),
+            BinaryOperatorToken::Greater => write!(f, "Greater Than Operator (>)"),
+            BinaryOperatorToken::GreaterOrEqual => {
+                write!(f, "Greater Than or Equal Operator (>=)")
+            }
+            BinaryOperatorToken::Equal => write!(f, "Equality Operator (==)"),
+            BinaryOperatorToken::NotEqual => write!(f, "Inequality Operator (!=)"),
+            BinaryOperatorToken::And => write!(f, "Logical And Operator (and)"),
+            BinaryOperatorToken::Or => write!(f, "Logical Or Operator (or)"),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum FunctionNameToken {
     Max,
     Min,
+    Network,
+    Broadcast,
+    Netmask,
+    Hosts,
+    InSubnet,
+    Bits,
+    SetBits,
+    HistSum,
+    HistMean,
+    HistMin,
+    HistMax,
+    DivMod,
+    Factor,
+    Ln,
+    Log2,
+    Log10,
+    Exp,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    Choose,
+    Perm,
+    Root,
+    Sum,
+    Prod,
+    Mean,
+    Median,
+    Variance,
+    StdDev,
+    Sign,
+    Recip,
+    Clamp,
+    If,
+    Re,
+    Im,
+    Conj,
+    Arg,
+    Interval,
+    // Custom functions are serialized as their name. Since a name alone can't be turned back into
+    // a function, they can't be deserialized.
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "serialize_custom_function", skip_deserializing)
+    )]
+    Custom(Arc<dyn CustomFunction>),
+}
+
+#[cfg(feature = "serde")]
+fn serialize_custom_function<S: Serializer>(
+    function: &Arc<dyn CustomFunction>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(function.name())
+}
+
+impl PartialEq for FunctionNameToken {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (FunctionNameToken::Max, FunctionNameToken::Max) => true,
+            (FunctionNameToken::Min, FunctionNameToken::Min) => true,
+            (FunctionNameToken::Network, FunctionNameToken::Network) => true,
+            (FunctionNameToken::Broadcast, FunctionNameToken::Broadcast) => true,
+            (FunctionNameToken::Netmask, FunctionNameToken::Netmask) => true,
+            (FunctionNameToken::Hosts, FunctionNameToken::Hosts) => true,
+            (FunctionNameToken::InSubnet, FunctionNameToken::InSubnet) => true,
+            (FunctionNameToken::Bits, FunctionNameToken::Bits) => true,
+            (FunctionNameToken::SetBits, FunctionNameToken::SetBits) => true,
+            (FunctionNameToken::HistSum, FunctionNameToken::HistSum) => true,
+            (FunctionNameToken::HistMean, FunctionNameToken::HistMean) => true,
+            (FunctionNameToken::HistMin, FunctionNameToken::HistMin) => true,
+            (FunctionNameToken::HistMax, FunctionNameToken::HistMax) => true,
+            (FunctionNameToken::DivMod, FunctionNameToken::DivMod) => true,
+            (FunctionNameToken::Factor, FunctionNameToken::Factor) => true,
+            (FunctionNameToken::Ln, FunctionNameToken::Ln) => true,
+            (FunctionNameToken::Log2, FunctionNameToken::Log2) => true,
+            (FunctionNameToken::Log10, FunctionNameToken::Log10) => true,
+            (FunctionNameToken::Exp, FunctionNameToken::Exp) => true,
+            (FunctionNameToken::Sin, FunctionNameToken::Sin) => true,
+            (FunctionNameToken::Cos, FunctionNameToken::Cos) => true,
+            (FunctionNameToken::Tan, FunctionNameToken::Tan) => true,
+            (FunctionNameToken::Asin, FunctionNameToken::Asin) => true,
+            (FunctionNameToken::Acos, FunctionNameToken::Acos) => true,
+            (FunctionNameToken::Atan, FunctionNameToken::Atan) => true,
+            (FunctionNameToken::Atan2, FunctionNameToken::Atan2) => true,
+            (FunctionNameToken::Choose, FunctionNameToken::Choose) => true,
+            (FunctionNameToken::Perm, FunctionNameToken::Perm) => true,
+            (FunctionNameToken::Root, FunctionNameToken::Root) => true,
+            (FunctionNameToken::Sum, FunctionNameToken::Sum) => true,
+            (FunctionNameToken::Prod, FunctionNameToken::Prod) => true,
+            (FunctionNameToken::Mean, FunctionNameToken::Mean) => true,
+            (FunctionNameToken::Median, FunctionNameToken::Median) => true,
+            (FunctionNameToken::Variance, FunctionNameToken::Variance) => true,
+            (FunctionNameToken::StdDev, FunctionNameToken::StdDev) => true,
+            (FunctionNameToken::Sign, FunctionNameToken::Sign) => true,
+            (FunctionNameToken::Recip, FunctionNameToken::Recip) => true,
+            (FunctionNameToken::Clamp, FunctionNameToken::Clamp) => true,
+            (FunctionNameToken::If, FunctionNameToken::If) => true,
+            (FunctionNameToken::Re, FunctionNameToken::Re) => true,
+            (FunctionNameToken::Im, FunctionNameToken::Im) => true,
+            (FunctionNameToken::Conj, FunctionNameToken::Conj) => true,
+            (FunctionNameToken::Arg, FunctionNameToken::Arg) => true,
+            (FunctionNameToken::Interval, FunctionNameToken::Interval) => true,
+            (FunctionNameToken::Custom(a), FunctionNameToken::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for FunctionNameToken {}
+
+impl FunctionNameToken {
+    /// Whether the function produces multiple values, like the quotient and remainder from
+    /// `divmod`, rather than a single one. Such functions can only be used as a whole input.
+    pub fn produces_multiple_values(&self) -> bool {
+        matches!(self, FunctionNameToken::DivMod | FunctionNameToken::Factor)
+    }
 }
 
 impl fmt::Display for FunctionNameToken {
@@ -65,21 +284,92 @@ impl fmt::Display for FunctionNameToken {
         match self {
             FunctionNameToken::Max => write!(f, "Max Function"),
             FunctionNameToken::Min => write!(f, "Min Function"),
+            FunctionNameToken::Network => write!(f, "Network Function"),
+            FunctionNameToken::Broadcast => write!(f, "Broadcast Function"),
+            FunctionNameToken::Netmask => write!(f, "Netmask Function"),
+            FunctionNameToken::Hosts => write!(f, "Hosts Function"),
+            FunctionNameToken::InSubnet => write!(f, "InSubnet Function"),
+            FunctionNameToken::Bits => write!(f, "Bits Function"),
+            FunctionNameToken::SetBits => write!(f, "SetBits Function"),
+            FunctionNameToken::HistSum => write!(f, "HistSum Function"),
+            FunctionNameToken::HistMean => write!(f, "HistMean Function"),
+            FunctionNameToken::HistMin => write!(f, "HistMin Function"),
+            FunctionNameToken::HistMax => write!(f, "HistMax Function"),
+            FunctionNameToken::DivMod => write!(f, "DivMod Function"),
+            FunctionNameToken::Factor => write!(f, "Factor Function"),
+            FunctionNameToken::Ln => write!(f, "Ln Function"),
+            FunctionNameToken::Log2 => write!(f, "Log2 Function"),
+            FunctionNameToken::Log10 => write!(f, "Log10 Function"),
+            FunctionNameToken::Exp => write!(f, "Exp Function"),
+            FunctionNameToken::Sin => write!(f, "Sin Function"),
+            FunctionNameToken::Cos => write!(f, "Cos Function"),
+            FunctionNameToken::Tan => write!(f, "Tan Function"),
+            FunctionNameToken::Asin => write!(f, "Asin Function"),
+            FunctionNameToken::Acos => write!(f, "Acos Function"),
+            FunctionNameToken::Atan => write!(f, "Atan Function"),
+            FunctionNameToken::Atan2 => write!(f, "Atan2 Function"),
+            FunctionNameToken::Choose => write!(f, "Choose Function"),
+            FunctionNameToken::Perm => write!(f, "Perm Function"),
+            FunctionNameToken::Root => write!(f, "Root Function"),
+            FunctionNameToken::Sum => write!(f, "Sum Function"),
+            FunctionNameToken::Prod => write!(f, "Prod Function"),
+            FunctionNameToken::Mean => write!(f, "Mean Function"),
+            FunctionNameToken::Median => write!(f, "Median Function"),
+            FunctionNameToken::Variance => write!(f, "Variance Function"),
+            FunctionNameToken::StdDev => write!(f, "StdDev Function"),
+            FunctionNameToken::Sign => write!(f, "Sign Function"),
+            FunctionNameToken::Recip => write!(f, "Recip Function"),
+            FunctionNameToken::Clamp => write!(f, "Clamp Function"),
+            FunctionNameToken::If => write!(f, "If Function"),
+            FunctionNameToken::Re => write!(f, "Re Function"),
+            FunctionNameToken::Im => write!(f, "Im Function"),
+            FunctionNameToken::Conj => write!(f, "Conj Function"),
+            FunctionNameToken::Arg => write!(f, "Arg Function"),
+            FunctionNameToken::Interval => write!(f, "Interval Function"),
+            FunctionNameToken::Custom(function) => write!(f, "Function ({})", function.name()),
         }
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Token {
-    Variable(String),
+    Variable(VariableName),
     AssignmentOperator,
     Comma,
-    Number(BigRational),
+    Number(#[cfg_attr(feature = "serde", serde(with = "serde_rational"))] BigRational),
+    /// A number with units, such as the duration `1h30m`. The value is in the base unit of the
+    /// dimension (seconds, for durations).
+    Quantity(
+        #[cfg_attr(feature = "serde", serde(with = "serde_rational"))] BigRational,
+        Dimension,
+    ),
+    /// An imaginary number, like `4i`, or `i` by itself. Holds the number that `i` is multiplied by.
+    Imaginary(#[cfg_attr(feature = "serde", serde(with = "serde_rational"))] BigRational),
+    /// A unit written after a value, like the `KiB` in `4 KiB`. See `units::expression_unit`.
+    Unit(String),
+    /// A physical constant, like `const.c`. Holds the name of the constant without the prefix.
+    Constant(String),
+    /// A mathematical constant written by name, like `pi`. Its value depends on the precision, so
+    /// it is only worked out when the expression is evaluated.
+    MathConstant(MathConstant),
+    /// An IPv4 address written as a dotted quad, like `10.0.0.1`.
+    Ipv4Address(u32),
     OpenParen,
     CloseParen,
     BinaryOperator(BinaryOperatorToken),
     UnaryOperator(UnaryOperatorToken),
     Function(FunctionNameToken),
+    /// The `let` in `let $x = 1 in $x + 1`. The `in` is read as a `Unit`, since it is also the unit
+    /// for inches.
+    Let,
+    /// The `?` in `condition ? if_true : if_false`.
+    QuestionMark,
+    /// The `:` in `condition ? if_true : if_false`.
+    Colon,
+    /// One of the bars around an absolute value, like `|$x - 3|`. Whether it opens or closes the
+    /// absolute value is worked out when the syntax tree is built.
+    Bar,
 }
 
 impl fmt::Display for Token {
@@ -89,11 +379,21 @@ impl fmt::Display for Token {
             Token::AssignmentOperator => write!(f, "Assignment Operator (=)"),
             Token::Comma => write!(f, "Comma"),
             Token::Number(n) => write!(f, "Number ({})", n),
+            Token::Quantity(n, dimension) => write!(f, "Quantity ({}, {})", n, dimension),
+            Token::Imaginary(n) => write!(f, "Imaginary Number ({}i)", n),
+            Token::Unit(name) => write!(f, "Unit ({})", name),
+            Token::Constant(name) => write!(f, "Constant ({}{})", CONSTANT_PREFIX, name),
+            Token::MathConstant(constant) => write!(f, "Constant ({})", constant),
+            Token::Ipv4Address(address) => write!(f, "IPv4 Address ({})", format_ipv4(*address)),
             Token::OpenParen => write!(f, "Open Parenthesis"),
             Token::CloseParen => write!(f, "Close Parenthesis"),
             Token::BinaryOperator(t) => fmt::Display::fmt(t, f),
             Token::UnaryOperator(t) => fmt::Display::fmt(t, f),
             Token::Function(t) => fmt::Display::fmt(t, f),
+            Token::Let => write!(f, "Let Keyword (let)"),
+            Token::QuestionMark => write!(f, "Question Mark (?)"),
+            Token::Colon => write!(f, "Colon (:)"),
+            Token::Bar => write!(f, "Absolute Value Bar (|)"),
         }
     }
 }
@@ -122,6 +422,54 @@ impl From<FunctionNameToken> for Token {
     }
 }
 
+/// Serializes numbers as strings like `"3/4"` (or `"3"` for integers), which are easier for other
+/// tools to read than `BigRational`'s own representation would be.
+#[cfg(feature = "serde")]
+pub(crate) mod serde_rational {
+    use num::rational::BigRational;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &BigRational, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BigRational, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        string
+            .parse()
+            .map_err(|_| D::Error::custom(format!("Invalid number: '{}'", string)))
+    }
+}
+
+// Whether the text after a `%` starts with an operand, in which case the `%` is a modulus (`17 % 5`)
+// rather than a percent (`15% of 200`, `200 * 15%`). A `-` is only counted as the start of an
+// operand when it is attached to it, like the `-3` in `17 % -3`.
+fn starts_with_operand(text: &str) -> bool {
+    let text = text.trim_start();
+    match text.as_bytes() {
+        [] => false,
+        [b'-', next, ..] => !next.is_ascii_whitespace(),
+        [first, ..] if b"+-*/^<>=!&|),?:%".contains(first) => false,
+        _ => {
+            let word_end = text
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(text.len());
+            !matches!(&text[..word_end], "of" | "mod" | "div" | "and" | "or")
+        }
+    }
+}
+
+/// Whether the input is a command, like `/help`, rather than an expression. Command names start
+/// with a letter, so an input like `/ 2` is a division that continues the previous result instead.
+pub fn is_command(input: &str) -> bool {
+    match input.trim_start().strip_prefix('/') {
+        Some(post_slash) => !post_slash.starts_with(|c: char| !c.is_ascii_alphabetic()),
+        None => false,
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum ParsedInput {
     Tokens(Vec<Positioned<Token>>),
@@ -132,6 +480,16 @@ pub enum ParsedInput {
 
 pub struct Tokenizer {
     token_map: HashMap<String, Token>,
+    // Whether numbers can be written with SI suffixes, like `4.7k`.
+    si_suffixes: bool,
+    // Whether `m` after a number is the SI suffix for milli rather than minutes.
+    milli_suffix: bool,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Tokenizer {
+        Tokenizer::new()
+    }
 }
 
 impl Tokenizer {
@@ -139,10 +497,98 @@ impl Tokenizer {
         let mut token_map: HashMap<String, Token> = HashMap::new();
         token_map.insert("sqrt".to_string(), UnaryOperatorToken::SquareRoot.into());
         token_map.insert("abs".to_string(), UnaryOperatorToken::AbsoluteValue.into());
+        token_map.insert("not".to_string(), UnaryOperatorToken::Not.into());
+        token_map.insert("and".to_string(), BinaryOperatorToken::And.into());
+        token_map.insert("or".to_string(), BinaryOperatorToken::Or.into());
+        token_map.insert("div".to_string(), BinaryOperatorToken::IntegerDivide.into());
+        token_map.insert("mod".to_string(), BinaryOperatorToken::FloorModulus.into());
+        token_map.insert("of".to_string(), BinaryOperatorToken::Multiply.into());
         token_map.insert("max".to_string(), FunctionNameToken::Max.into());
         token_map.insert("min".to_string(), FunctionNameToken::Min.into());
+        token_map.insert("network".to_string(), FunctionNameToken::Network.into());
+        token_map.insert("broadcast".to_string(), FunctionNameToken::Broadcast.into());
+        token_map.insert("netmask".to_string(), FunctionNameToken::Netmask.into());
+        token_map.insert("hosts".to_string(), FunctionNameToken::Hosts.into());
+        token_map.insert("insubnet".to_string(), FunctionNameToken::InSubnet.into());
+        token_map.insert("bits".to_string(), FunctionNameToken::Bits.into());
+        token_map.insert("setbits".to_string(), FunctionNameToken::SetBits.into());
+        token_map.insert("histsum".to_string(), FunctionNameToken::HistSum.into());
+        token_map.insert("histmean".to_string(), FunctionNameToken::HistMean.into());
+        token_map.insert("histmin".to_string(), FunctionNameToken::HistMin.into());
+        token_map.insert("histmax".to_string(), FunctionNameToken::HistMax.into());
+        token_map.insert("divmod".to_string(), FunctionNameToken::DivMod.into());
+        token_map.insert("factor".to_string(), FunctionNameToken::Factor.into());
+        token_map.insert("ln".to_string(), FunctionNameToken::Ln.into());
+        token_map.insert("log2".to_string(), FunctionNameToken::Log2.into());
+        token_map.insert("log10".to_string(), FunctionNameToken::Log10.into());
+        token_map.insert("exp".to_string(), FunctionNameToken::Exp.into());
+        token_map.insert("sin".to_string(), FunctionNameToken::Sin.into());
+        token_map.insert("cos".to_string(), FunctionNameToken::Cos.into());
+        token_map.insert("tan".to_string(), FunctionNameToken::Tan.into());
+        token_map.insert("asin".to_string(), FunctionNameToken::Asin.into());
+        token_map.insert("acos".to_string(), FunctionNameToken::Acos.into());
+        token_map.insert("atan".to_string(), FunctionNameToken::Atan.into());
+        token_map.insert("atan2".to_string(), FunctionNameToken::Atan2.into());
+        token_map.insert("choose".to_string(), FunctionNameToken::Choose.into());
+        token_map.insert("perm".to_string(), FunctionNameToken::Perm.into());
+        token_map.insert("root".to_string(), FunctionNameToken::Root.into());
+        token_map.insert("sum".to_string(), FunctionNameToken::Sum.into());
+        token_map.insert("prod".to_string(), FunctionNameToken::Prod.into());
+        token_map.insert("mean".to_string(), FunctionNameToken::Mean.into());
+        token_map.insert("avg".to_string(), FunctionNameToken::Mean.into());
+        token_map.insert("median".to_string(), FunctionNameToken::Median.into());
+        token_map.insert("variance".to_string(), FunctionNameToken::Variance.into());
+        token_map.insert("stddev".to_string(), FunctionNameToken::StdDev.into());
+        token_map.insert("sign".to_string(), FunctionNameToken::Sign.into());
+        token_map.insert("recip".to_string(), FunctionNameToken::Recip.into());
+        token_map.insert("clamp".to_string(), FunctionNameToken::Clamp.into());
+        token_map.insert("if".to_string(), FunctionNameToken::If.into());
+        token_map.insert("re".to_string(), FunctionNameToken::Re.into());
+        token_map.insert("im".to_string(), FunctionNameToken::Im.into());
+        token_map.insert("conj".to_string(), FunctionNameToken::Conj.into());
+        token_map.insert("arg".to_string(), FunctionNameToken::Arg.into());
+        token_map.insert("interval".to_string(), FunctionNameToken::Interval.into());
+        token_map.insert("let".to_string(), Token::Let);
+
+        Tokenizer {
+            token_map,
+            si_suffixes: true,
+            milli_suffix: false,
+        }
+    }
+
+    /// Sets whether numbers in expressions tokenized after this call can be written with SI
+    /// suffixes, like `4.7k` or `10u`. They are allowed by default.
+    pub fn set_si_suffixes(&mut self, enabled: bool) {
+        self.si_suffixes = enabled;
+    }
+
+    /// Sets whether `m` written after a number in expressions tokenized after this call is the SI
+    /// suffix for milli, like `4.7m`, rather than minutes. `m` is minutes by default, and is still
+    /// minutes in a duration with other suffixes, like `1h30m`. Only matters while SI suffixes are
+    /// allowed.
+    pub fn set_milli_suffix(&mut self, enabled: bool) {
+        self.milli_suffix = enabled;
+    }
 
-        Tokenizer { token_map }
+    /// Makes the function available to expressions tokenized after this call. The function's name
+    /// must start with an ASCII letter and contain only ASCII letters, digits, and underscores. It
+    /// may not be the same as a built in function, operator, or constant name or a previously
+    /// registered function. Note that names made up of digits valid in the radix being used (such as `beef`
+    /// in hexadecimal) will hide those numbers.
+    pub fn register_function(&mut self, function: Arc<dyn CustomFunction>) -> Result<(), String> {
+        let name = function.name();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic())
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(format!("Invalid function name: '{}'", name));
+        }
+        if self.token_map.contains_key(name) || MathConstant::from_name(name).is_some() {
+            return Err(format!("'{}' is already defined", name));
+        }
+        self.token_map
+            .insert(name.to_string(), FunctionNameToken::Custom(function).into());
+        Ok(())
     }
 
     /// Takes a string of input. Returns a vector of tokens.
@@ -152,14 +598,9 @@ impl Tokenizer {
     /// really a good way to tell them apart. We'll correct this later when we generate the syntax
     /// tree for the tokens.
     pub fn tokenize(&self, input: &str, radix: u8) -> Result<ParsedInput, Positioned<ParseError>> {
-        let mut tokens: Vec<Positioned<Token>> = Vec::new();
-        // When we are in the middle of a multi-character token (i.e. a number or a variable), we
-        // will store it in this buffer until we are at the end (whitespace or a single-character
-        // token) and then we turn the contents of buffer into a token.
-        let mut buffer: Vec<u8> = Vec::new();
-
         for (position, chr) in input.chars().enumerate() {
-            if !chr.is_ascii() {
+            // `±` is the one exception, since it has no single ASCII equivalent.
+            if !chr.is_ascii() && chr != '±' {
                 return Err(Positioned::new_raw(ParseError::NonAscii, position, 1));
             }
         }
@@ -168,41 +609,160 @@ impl Tokenizer {
             return Ok(command);
         }
 
-        let input = input.as_bytes();
+        let tokens = self.tokenize_expression(input, 0..input.len(), radix)?;
+        Ok(ParsedInput::Tokens(tokens))
+    }
 
-        for (position, chr) in input.iter().enumerate() {
+    // Tokenizes `input[range]`, which must be ASCII and must not be a command. The positions of the
+    // tokens are relative to the start of `input`, not the start of `range`.
+    fn tokenize_expression(
+        &self,
+        input: &str,
+        range: Range<usize>,
+        radix: u8,
+    ) -> Result<Vec<Positioned<Token>>, Positioned<ParseError>> {
+        let mut tokens: Vec<Positioned<Token>> = Vec::new();
+        // When we are in the middle of a multi-character token (i.e. a number or a variable), we
+        // will remember where it started until we are at the end (whitespace or a single-character
+        // token) and then we turn that part of the input into a token.
+        let mut multichar_start = range.start;
+        let end = range.end;
+        // The number of bytes after the current one that belong to an operator that has already
+        // been tokenized, like the `=` in `<=`.
+        let mut skip = 0;
+
+        for position in range {
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            let chr = &input.as_bytes()[position];
+            let maybe_next = if position + 1 < end {
+                Some(input.as_bytes()[position + 1])
+            } else {
+                None
+            };
+            let followed_by_equals = maybe_next == Some(b'=');
+            // A `!` written right after an operand, like in `5!` or `(2 + 3)!`, is a factorial.
+            // Anywhere else, it is a logical not.
+            let follows_operand = multichar_start < position
+                || matches!(
+                    tokens.last(),
+                    Some(Positioned {
+                        value: Token::CloseParen
+                            | Token::UnaryOperator(
+                                UnaryOperatorToken::Factorial | UnaryOperatorToken::Percent
+                            ),
+                        position: previous,
+                    }) if previous.start + previous.width == position
+                );
+            // Where an operand is expected, `||` is two absolute value bars, like in `||-1| - 5|`,
+            // rather than a logical or. At the start of the input, though, `|| ` followed by a
+            // space is still an or that continues the previous result, like a leading `- ` is.
+            let operand_expected = multichar_start == position
+                && match tokens.last().map(|token| &token.value) {
+                    None => !input[position..end].starts_with("|| "),
+                    Some(token) => matches!(
+                        token,
+                        Token::BinaryOperator(_)
+                            | Token::UnaryOperator(UnaryOperatorToken::Not)
+                            | Token::OpenParen
+                            | Token::Comma
+                            | Token::AssignmentOperator
+                            | Token::QuestionMark
+                            | Token::Colon
+                            | Token::Function(_)
+                    ),
+                };
             if (*chr as char).is_ascii_whitespace() {
-                self.tokenize_on_multichar_end(&mut tokens, &mut buffer, position, radix)?;
+                self.tokenize_on_multichar_end(
+                    &mut tokens,
+                    input,
+                    multichar_start..position,
+                    radix,
+                )?;
+                multichar_start = position + 1;
             } else {
                 let maybe_token: Option<Token> = match chr {
+                    b'+' if input[position..end].starts_with("+/-") => {
+                        Some(BinaryOperatorToken::PlusMinus.into())
+                    }
                     b'+' => Some(BinaryOperatorToken::Add.into()),
                     b'-' => Some(BinaryOperatorToken::Subtract.into()),
                     b'*' => Some(BinaryOperatorToken::Multiply.into()),
+                    b'/' if maybe_next == Some(b'/') => {
+                        Some(BinaryOperatorToken::IntegerDivide.into())
+                    }
                     b'/' => Some(BinaryOperatorToken::Divide.into()),
+                    b'%' if (multichar_start < position || !tokens.is_empty())
+                        && !starts_with_operand(&input[position + 1..end]) =>
+                    {
+                        Some(UnaryOperatorToken::Percent.into())
+                    }
                     b'%' => Some(BinaryOperatorToken::Modulus.into()),
                     b'^' => Some(BinaryOperatorToken::Exponent.into()),
+                    b'<' if followed_by_equals => Some(BinaryOperatorToken::LessOrEqual.into()),
+                    b'<' => Some(BinaryOperatorToken::Less.into()),
+                    b'>' if followed_by_equals => Some(BinaryOperatorToken::GreaterOrEqual.into()),
+                    b'>' => Some(BinaryOperatorToken::Greater.into()),
+                    b'=' if followed_by_equals => Some(BinaryOperatorToken::Equal.into()),
+                    b'!' if followed_by_equals => Some(BinaryOperatorToken::NotEqual.into()),
+                    b'!' if follows_operand => Some(UnaryOperatorToken::Factorial.into()),
+                    b'!' => Some(UnaryOperatorToken::Not.into()),
+                    b'&' if maybe_next == Some(b'&') => Some(BinaryOperatorToken::And.into()),
+                    b'|' if maybe_next == Some(b'|') && !operand_expected => {
+                        Some(BinaryOperatorToken::Or.into())
+                    }
+                    b'|' => Some(Token::Bar),
                     b'(' => Some(Token::OpenParen),
                     b')' => Some(Token::CloseParen),
                     b'=' => Some(Token::AssignmentOperator),
                     b',' => Some(Token::Comma),
+                    b'?' => Some(Token::QuestionMark),
+                    b':' => Some(Token::Colon),
+                    _ if input.as_bytes()[position..end].starts_with("±".as_bytes()) => {
+                        Some(BinaryOperatorToken::PlusMinus.into())
+                    }
                     _ => None,
                 };
 
-                match maybe_token {
-                    Some(token) => {
-                        self.tokenize_on_multichar_end(&mut tokens, &mut buffer, position, radix)?;
-                        tokens.push(Positioned::new_raw(token, position, 1));
-                    }
-                    None => {
-                        buffer.push(*chr);
-                    }
+                if let Some(token) = maybe_token {
+                    self.tokenize_on_multichar_end(
+                        &mut tokens,
+                        input,
+                        multichar_start..position,
+                        radix,
+                    )?;
+                    let width = match token {
+                        Token::BinaryOperator(operator)
+                            if operator.is_comparison() && followed_by_equals =>
+                        {
+                            2
+                        }
+                        Token::BinaryOperator(
+                            BinaryOperatorToken::And
+                            | BinaryOperatorToken::Or
+                            | BinaryOperatorToken::IntegerDivide,
+                        ) => 2,
+                        Token::BinaryOperator(BinaryOperatorToken::PlusMinus) => {
+                            if *chr == b'+' {
+                                "+/-".len()
+                            } else {
+                                '±'.len_utf8()
+                            }
+                        }
+                        _ => 1,
+                    };
+                    tokens.push(Positioned::new_raw(token, position, width));
+                    multichar_start = position + width;
+                    skip = width - 1;
                 }
             }
         }
 
-        self.tokenize_on_multichar_end(&mut tokens, &mut buffer, input.len(), radix)?;
+        self.tokenize_on_multichar_end(&mut tokens, input, multichar_start..end, radix)?;
 
-        Ok(ParsedInput::Tokens(tokens))
+        Ok(tokens)
     }
 
     fn maybe_extract_command(
@@ -212,10 +772,10 @@ impl Tokenizer {
         let trimmed_input = input.trim_start();
         let command_start = input.len() - trimmed_input.len();
 
-        let post_slash = match trimmed_input.strip_prefix('/') {
-            None => return Ok(None),
-            Some(suffix) => suffix,
-        };
+        if !is_command(input) {
+            return Ok(None);
+        }
+        let post_slash = &trimmed_input[1..];
         let (command, args) = match post_slash.split_once(|c: char| c.is_ascii_whitespace()) {
             None => {
                 let command =
@@ -245,98 +805,149 @@ impl Tokenizer {
 
     // Helper function for `tokenize`. When we get to the boundary between tokens (whitespace,
     // single character operators, or the end of input), we will call this function to interpret
-    // what we have read and, assuming that anything is in the buffer, turn it into some sort of
-    // token and add it to the `tokens` vector.
-    // `buffer` will always be empty after this function returns `Ok`.
+    // `input[range]`, the multi-character token that we have read, and, assuming that the range
+    // isn't empty, turn it into some sort of token and add it to the `tokens` vector.
     fn tokenize_on_multichar_end(
         &self,
         tokens: &mut Vec<Positioned<Token>>,
-        buffer: &mut Vec<u8>,
-        buffer_stop_position: usize,
+        input: &str,
+        range: Range<usize>,
         radix: u8,
     ) -> Result<(), Positioned<ParseError>> {
-        if buffer.is_empty() {
+        if range.is_empty() {
             return Ok(());
         }
 
-        let width = buffer.len();
-        let buffer_start = buffer_stop_position - width;
-        // Since `buffer` only contains ASCII, this is safe.
-        let buffer_as_string = String::from_utf8(buffer.clone()).unwrap();
+        let start = range.start;
+        let width = range.len();
+        let text = &input[range];
 
-        if buffer[0] == b'$' {
+        if text.starts_with('$') {
             tokens.push(Positioned::new_raw(
-                Token::Variable(buffer_as_string),
-                buffer_start,
+                Token::Variable(text.into()),
+                start,
                 width,
             ));
-            buffer.clear();
             return Ok(());
         }
 
-        if let Some(token) = self.token_map.get(&buffer_as_string) {
-            tokens.push(Positioned::new_raw(token.clone(), buffer_start, width));
-            buffer.clear();
+        if let Some(token) = self.token_map.get(text) {
+            tokens.push(Positioned::new_raw(token.clone(), start, width));
             return Ok(());
         }
 
-        // We've exhausted the other options. The fall through case is that this is a number.
-        // To parse it, we first need to pull out any '_' characters (which we allow as arbitrary
-        // separators) and, if there is a decimal point, we need to pull it out and note its
-        // position.
-        let mut clean_buffer: Vec<u8> = Vec::new();
-        let mut maybe_dec_index: Option<usize> = None;
-        for chr in buffer.iter() {
-            if *chr == b'_' {
-                continue;
-            } else if *chr == b'.' && maybe_dec_index.is_none() {
-                // We specifically only pull out the first decimal point  found. Finding more than
-                // one should generate an error, which is just what will happen below if we give a
-                // buffer with a decimal to `BigInt::parse_bytes`.
-                maybe_dec_index = Some(clean_buffer.len());
-                continue;
+        if let Some(name) = text.strip_prefix(CONSTANT_PREFIX) {
+            if find_constant(name).is_none() {
+                return Err(Positioned::new_raw(
+                    ParseError::UnknownConstant(text.to_string()),
+                    start,
+                    width,
+                ));
             }
-            clean_buffer.push(*chr);
+            tokens.push(Positioned::new_raw(
+                Token::Constant(name.to_string()),
+                start,
+                width,
+            ));
+            return Ok(());
         }
 
-        let numer = BigInt::parse_bytes(&clean_buffer, radix.into()).ok_or_else(|| {
-            Positioned::new_raw(
-                ParseError::InvalidNumber(buffer_as_string),
-                buffer_start,
-                width,
-            )
-        })?;
+        // Checked after numbers so that `e` is still a digit in radixes above 14.
+        if parse_number(text, radix).is_none() {
+            if let Some(constant) = MathConstant::from_name(text) {
+                tokens.push(Positioned::new_raw(
+                    Token::MathConstant(constant),
+                    start,
+                    width,
+                ));
+                return Ok(());
+            }
+            if let Some(value) = parse_imaginary(text, radix) {
+                tokens.push(Positioned::new_raw(Token::Imaginary(value), start, width));
+                return Ok(());
+            }
+        }
 
-        let denom = match maybe_dec_index {
-            Some(dec_index) => {
-                let big_radix = BigInt::from(radix);
-                big_radix.pow(clean_buffer.len() - dec_index)
+        // We've exhausted the other options. The fall through case is that this is a number, or
+        // failing that, an IPv4 address, a unit, a duration like `1h30m`, a number with a radix
+        // prefix or suffix like `0xff`, `16#ff`, or `FFh`, a byte size like `4KiB`, or a number with
+        // an SI suffix like `4.7k`. Numbers are checked first so that a unit
+        // name made of digits (like `B` in hexadecimal) is still read as a number. Since `h` is
+        // also the suffix for hours, a leading 0 is needed to make something like `020h`
+        // hexadecimal rather than a duration. With the milli suffix turned on, a number followed by
+        // just `m`, like `4.7m`, is read as milli rather than minutes.
+        let maybe_milli = if self.si_suffixes && self.milli_suffix {
+            parse_milli_suffixed_number(text, radix)
+        } else {
+            None
+        };
+        let token = match (parse_number(text, radix), parse_ipv4(text), maybe_milli) {
+            (Some(value), _, _) => Token::Number(value),
+            (None, Some(address), _) => Token::Ipv4Address(address),
+            (None, None, _) if expression_unit(text).is_some() || is_radian(text) => {
+                Token::Unit(text.to_string())
             }
-            None => BigInt::from(1),
+            (None, None, Some(value)) => Token::Number(value),
+            (None, None, None) => match (
+                text.starts_with('0'),
+                parse_duration(text, radix),
+                parse_radix_prefixed_number(text).or_else(|| parse_radix_suffixed_number(text)),
+            ) {
+                (true, _, Some(value)) | (false, None, Some(value)) => Token::Number(value),
+                (_, Some(seconds), _) => Token::Quantity(seconds, Dimension::TIME),
+                (_, None, None) => match (
+                    parse_byte_size(text, radix),
+                    parse_si_suffixed_number(text, radix),
+                ) {
+                    (Some(bits), _) => Token::Quantity(bits, Dimension::DATA),
+                    (None, Some(value)) if self.si_suffixes => Token::Number(value),
+                    _ => {
+                        return Err(Positioned::new_raw(
+                            self.unknown_word_error(text),
+                            start,
+                            width,
+                        ));
+                    }
+                },
+            },
         };
+        tokens.push(Positioned::new_raw(token, start, width));
 
-        tokens.push(Positioned::new_raw(
-            Token::Number(BigRational::new(numer, denom)),
-            buffer_start,
-            width,
-        ));
-
-        buffer.clear();
         Ok(())
     }
 
+    // Words that start with a letter are more likely to be misspelled names than numbers, so if
+    // there are any names close to the word, they are suggested in the error.
+    fn unknown_word_error(&self, text: &str) -> ParseError {
+        if text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let mut names: Vec<&str> = self.token_map.keys().map(String::as_str).collect();
+            for name in expression_unit_names() {
+                names.push(name);
+            }
+            for constant in MathConstant::ALL {
+                names.push(constant.name());
+            }
+            let suggestions = closest_matches(text, names);
+            if !suggestions.is_empty() {
+                return ParseError::UnknownName(text.to_string(), suggestions);
+            }
+        }
+        ParseError::InvalidNumber(text.to_string())
+    }
+
     pub fn tokenize_variable_list(
         &self,
         input: &str,
-    ) -> Result<Vec<Positioned<String>>, Positioned<String>> {
+    ) -> Result<Vec<Positioned<VariableName>>, Positioned<String>> {
         // `radix` should be meaningless here, but passing in 10 makes the error messages a bit
         // easier to deal with.
         let positioned_tokens = match self.tokenize(input, 10) {
             Err(positioned_error) => {
                 let message = match positioned_error.value {
-                    ParseError::InvalidVariable(s) | ParseError::InvalidNumber(s) => {
-                        ParseError::InvalidVariable(s).to_string()
-                    }
+                    ParseError::InvalidVariable(s)
+                    | ParseError::InvalidNumber(s)
+                    | ParseError::UnknownConstant(s)
+                    | ParseError::UnknownName(s, _) => ParseError::InvalidVariable(s).to_string(),
                     ParseError::NonAscii => ParseError::NonAscii.to_string(),
                 };
                 return Err(Positioned::new(message, positioned_error.position));
@@ -350,7 +961,7 @@ impl Tokenizer {
             Ok(ParsedInput::Tokens(t)) => t,
         };
 
-        let mut result: Vec<Positioned<String>> = Vec::new();
+        let mut result: Vec<Positioned<VariableName>> = Vec::new();
         for positioned_token in positioned_tokens {
             match positioned_token.value {
                 Token::Variable(s) => result.push(Positioned::new(s, positioned_token.position)),
@@ -374,9 +985,10 @@ impl Tokenizer {
         let positioned_tokens = match self.tokenize(input, radix) {
             Err(positioned_error) => {
                 let message = match positioned_error.value {
-                    ParseError::InvalidVariable(s) | ParseError::InvalidNumber(s) => {
-                        ParseError::InvalidVariable(s).to_string()
-                    }
+                    ParseError::InvalidVariable(s)
+                    | ParseError::InvalidNumber(s)
+                    | ParseError::UnknownConstant(s)
+                    | ParseError::UnknownName(s, _) => ParseError::InvalidVariable(s).to_string(),
                     ParseError::NonAscii => ParseError::NonAscii.to_string(),
                 };
                 return Err(Positioned::new(message, positioned_error.position));
@@ -455,17 +1067,337 @@ impl Tokenizer {
     }
 }
 
+/// If every parenthesis in the tokens is matched except for some open ones, adds the close
+/// parentheses needed to match them at the end and returns how many were added. Otherwise, returns
+/// `0` without changing anything.
+pub fn close_open_parens(tokens: &mut Vec<Positioned<Token>>) -> usize {
+    let mut depth: usize = 0;
+    for token in tokens.iter() {
+        match token.value {
+            Token::OpenParen => depth += 1,
+            Token::CloseParen if depth == 0 => return 0,
+            Token::CloseParen => depth -= 1,
+            _ => {}
+        }
+    }
+    let end = tokens
+        .last()
+        .map_or(0, |token| token.position.start + token.position.width);
+    for _ in 0..depth {
+        tokens.push(Positioned::new_raw(Token::CloseParen, end, 0));
+    }
+    depth
+}
+
+/// Rewrites an expression written with `,` as the decimal separator, `.` or spaces between groups
+/// of three digits, and `;` between function arguments (like `max(1.234,5; 2 000)`) into the usual
+/// notation (like `max(1_234.5, 2_000)`). Dots in IPv4 addresses are left alone. Each character is
+/// replaced by at most one other character, so positions in the result are the same as in `input`.
+pub fn from_decimal_comma(input: &str, radix: u8) -> String {
+    let chars: Vec<char> = input.chars().collect();
+    let is_digit = |index: usize| chars.get(index).is_some_and(|c| c.is_digit(radix.into()));
+
+    // Dots in runs of digits and dots that make up an IPv4 address.
+    let mut address_dots = vec![false; chars.len()];
+    let mut run_start = 0;
+    for index in 0..=chars.len() {
+        if index < chars.len() && (chars[index] == '.' || chars[index].is_ascii_digit()) {
+            continue;
+        }
+        let run: String = chars[run_start..index].iter().collect();
+        if parse_ipv4(&run).is_some() {
+            address_dots[run_start..index].fill(true);
+        }
+        run_start = index + 1;
+    }
+
+    // Whether the character at `index` separates a group of exactly three digits from a number
+    // before it, as opposed to, say, the digits at the end of a variable name.
+    let separates_group = |index: usize| {
+        if index == 0 || !is_digit(index - 1) || is_digit(index + 4) {
+            return false;
+        }
+        if !(1..=3).all(|offset| is_digit(index + offset)) {
+            return false;
+        }
+        let number_start = chars[..index]
+            .iter()
+            .rposition(|c| !(c.is_digit(radix.into()) || *c == '.'))
+            .map_or(0, |index| index + 1);
+        number_start == 0
+            || !(chars[number_start - 1].is_ascii_alphanumeric()
+                || chars[number_start - 1] == '$'
+                || chars[number_start - 1] == '_')
+    };
+
+    chars
+        .iter()
+        .enumerate()
+        .map(|(index, c)| match c {
+            ',' => '.',
+            ';' => ',',
+            '.' if !address_dots[index] && separates_group(index) => '_',
+            ' ' if separates_group(index) => '_',
+            c => *c,
+        })
+        .collect()
+}
+
+// Parses a number in the given radix. We allow '_' characters as arbitrary separators and, if there
+// is a decimal point, the digits after it scale the value down. Both halves are parsed separately,
+// which saves us from building a copy of the number without the decimal point. We specifically only
+// split on the first decimal point found. Finding more than one should fail, which is just what
+// will happen below when the fractional half fails to parse.
+fn parse_number(text: &str, radix: u8) -> Option<BigRational> {
+    let (int_digits, frac_digits) = text.split_once('.').unwrap_or((text, ""));
+    let (int_value, int_len) = parse_digits(int_digits, radix)?;
+    let (frac_value, frac_len) = parse_digits(frac_digits, radix)?;
+    if int_len + frac_len == 0 {
+        return None;
+    }
+
+    let denom = BigInt::from(radix).pow(frac_len);
+    let numer = int_value * &denom + frac_value;
+    Some(BigRational::new(numer, denom))
+}
+
+// Parses an imaginary number, which is `i` by itself or a number with `i` attached, like `4i` or
+// `0.5i`. Returns the number that `i` is multiplied by.
+fn parse_imaginary(text: &str, radix: u8) -> Option<BigRational> {
+    match text.strip_suffix('i')? {
+        "" => Some(BigRational::one()),
+        number => parse_number(number, radix),
+    }
+}
+
+// Parses a number written with a prefix giving its radix, regardless of the current one: `0x` for
+// hexadecimal (`0xff`), `0o` for octal (`0o777`), `0b` for binary (`0b1010`), or any radix from 2
+// to 36 followed by `#` (`16#ff`, `3#210`).
+fn parse_radix_prefixed_number(text: &str) -> Option<BigRational> {
+    if let Some((radix, digits)) = text.split_once('#') {
+        let radix: u8 = radix.parse().ok()?;
+        if !(2..=MAX_RADIX).contains(&radix) {
+            return None;
+        }
+        return parse_number(digits, radix);
+    }
+    let digits = text.get(2..)?;
+    let radix = match &text[..2] {
+        "0x" | "0X" => 16,
+        "0o" | "0O" => 8,
+        "0b" | "0B" => 2,
+        _ => return None,
+    };
+    parse_number(digits, radix)
+}
+
+// Parses a number written with an assembler style suffix giving its radix, regardless of the
+// current one: `h` for hexadecimal (`FFh`), `o` for octal (`777o`), or `b` for binary (`1010b`).
+fn parse_radix_suffixed_number(text: &str) -> Option<BigRational> {
+    let (digits, suffix) = text.split_at(text.len().checked_sub(1)?);
+    let radix = match suffix {
+        "h" => 16,
+        "o" => 8,
+        "b" => 2,
+        _ => return None,
+    };
+    parse_number(digits, radix)
+}
+
+// Parses a number with a byte size unit attached, like `4KiB` or `1.5GB`, into a number of bits. The
+// unit starts at the first letter that isn't a digit in the radix, so in hexadecimal, `1B` is still
+// a number rather than a byte.
+fn parse_byte_size(text: &str, radix: u8) -> Option<BigRational> {
+    let unit_start = text.find(|c: char| c.is_ascii_alphabetic() && !c.is_digit(radix.into()))?;
+    let (number, unit) = text.split_at(unit_start);
+    Some(parse_number(number, radix)? * byte_size_bits(unit)?)
+}
+
+// SI suffixes that can be written after a number, along with the power of 10 that they multiply it
+// by. Milli isn't included, since `m` is minutes by default (see `parse_duration`). It is handled
+// by `parse_milli_suffixed_number` instead, for when `Tokenizer::set_milli_suffix` turns it on.
+const SI_SUFFIXES: &[(char, i32)] = &[
+    ('P', 15),
+    ('T', 12),
+    ('G', 9),
+    ('M', 6),
+    ('k', 3),
+    ('u', -6),
+    ('n', -9),
+    ('p', -12),
+];
+
+// Parses a number in the given radix written with an SI suffix, like `4.7k` or `10u`.
+fn parse_si_suffixed_number(text: &str, radix: u8) -> Option<BigRational> {
+    let suffix = text.chars().last()?;
+    let (_, exponent) = SI_SUFFIXES.iter().find(|(name, _)| *name == suffix)?;
+    let number = parse_number(&text[..text.len() - 1], radix)?;
+    Some(number * BigRational::from_integer(BigInt::from(10)).pow(*exponent))
+}
+
+// Parses a number in the given radix written with `m`, the SI suffix for milli, like `4.7m`.
+fn parse_milli_suffixed_number(text: &str, radix: u8) -> Option<BigRational> {
+    let number = parse_number(text.strip_suffix('m')?, radix)?;
+    Some(number / BigInt::from(1000))
+}
+
+// Parses a duration made up of numbers with suffixes attached, like `1h30m` or `1.5d`, into a
+// number of seconds. A suffix ends at the first character that could be part of a number, so in
+// radices above 10, suffixes that are also digits (like the `d` of `1d` in hexadecimal) will be read
+// as part of the number instead.
+fn parse_duration(text: &str, radix: u8) -> Option<BigRational> {
+    let is_number_char = |c: char| c == '.' || c == '_' || c.is_digit(radix.into());
+    let mut seconds = BigRational::zero();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let number_len = rest.find(|c| !is_number_char(c)).unwrap_or(rest.len());
+        let suffix_len = rest[number_len..]
+            .find(|c: char| !c.is_ascii_alphabetic() || is_number_char(c))
+            .unwrap_or(rest.len() - number_len);
+        if suffix_len == 0 {
+            return None;
+        }
+        let number = parse_number(&rest[..number_len], radix)?;
+        let suffix = &rest[number_len..number_len + suffix_len];
+        seconds += number * duration_suffix_seconds(suffix)?;
+        rest = &rest[number_len + suffix_len..];
+    }
+    Some(seconds)
+}
+
+// Parses a run of digits in the given radix, ignoring any '_' separators. Returns the value and the
+// number of digits, or `None` if there is anything else in the run. An empty run is zero digits
+// with a value of zero.
+fn parse_digits(digits: &str, radix: u8) -> Option<(BigInt, usize)> {
+    // The parser itself skips separators, but won't allow them at the start.
+    let digits = digits.trim_start_matches('_');
+    if digits.is_empty() {
+        return Some((BigInt::zero(), 0));
+    }
+    let value = BigInt::from_str_radix(digits, radix.into()).ok()?;
+    Some((value, digits.bytes().filter(|b| *b != b'_').count()))
+}
+
+/// Tokenizes successive versions of an input line, such as the line being edited after each
+/// keystroke. Tokens that the edit could not have affected are reused rather than being tokenized
+/// again, but the result is always the same as `Tokenizer::tokenize` would give.
+#[derive(Default)]
+pub struct IncrementalTokenizer {
+    // The last input that was tokenized as an expression, along with the radix that was used and
+    // the resulting tokens.
+    maybe_previous: Option<(String, u8, Vec<Positioned<Token>>)>,
+}
+
+impl IncrementalTokenizer {
+    pub fn new() -> IncrementalTokenizer {
+        IncrementalTokenizer::default()
+    }
+
+    /// Forgets the previous input so that the next input is tokenized from scratch. This is needed
+    /// after registering a function with the `Tokenizer`, since that can change the meaning of
+    /// names that were already tokenized.
+    pub fn reset(&mut self) {
+        self.maybe_previous = None;
+    }
+
+    /// Like `Tokenizer::tokenize`, but only the part of `input` that differs from the previous
+    /// input is tokenized again.
+    pub fn tokenize(
+        &mut self,
+        tokenizer: &Tokenizer,
+        input: &str,
+        radix: u8,
+    ) -> Result<ParsedInput, Positioned<ParseError>> {
+        let maybe_previous = self.maybe_previous.take();
+        // Non-ASCII input is an error, which `Tokenizer::tokenize` reports.
+        if !input.is_ascii() {
+            return tokenizer.tokenize(input, radix);
+        }
+        if let Some(command) = tokenizer.maybe_extract_command(input)? {
+            return Ok(command);
+        }
+
+        let tokens = match maybe_previous {
+            Some((previous_input, previous_radix, previous_tokens)) if previous_radix == radix => {
+                Self::retokenize(tokenizer, &previous_input, previous_tokens, input, radix)?
+            }
+            _ => tokenizer.tokenize_expression(input, 0..input.len(), radix)?,
+        };
+        self.maybe_previous = Some((input.to_string(), radix, tokens.clone()));
+        Ok(ParsedInput::Tokens(tokens))
+    }
+
+    fn retokenize(
+        tokenizer: &Tokenizer,
+        previous_input: &str,
+        previous_tokens: Vec<Positioned<Token>>,
+        input: &str,
+        radix: u8,
+    ) -> Result<Vec<Positioned<Token>>, Positioned<ParseError>> {
+        // Find the span of the input that the edit changed. Everything before `prefix_len` and
+        // everything in the last `suffix_len` bytes is the same as it was.
+        let previous_bytes = previous_input.as_bytes();
+        let bytes = input.as_bytes();
+        let prefix_len = previous_bytes
+            .iter()
+            .zip(bytes)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix_len = previous_bytes
+            .iter()
+            .rev()
+            .zip(bytes.iter().rev())
+            .take(min(previous_bytes.len(), bytes.len()) - prefix_len)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let previous_edit_end = previous_bytes.len() - suffix_len;
+        let edit_end = bytes.len() - suffix_len;
+
+        // A token can only be reused if an unchanged boundary (whitespace or a single character
+        // token) separates it from the edit. Otherwise the edit may have extended it, as when `3`
+        // is typed after `12`.
+        let mut tokens: Vec<Positioned<Token>> = Vec::new();
+        let mut tokens_after: Vec<Positioned<Token>> = Vec::new();
+        for mut token in previous_tokens {
+            if token.position.start + token.position.width < prefix_len {
+                tokens.push(token);
+            } else if token.position.start > previous_edit_end {
+                token.position.start = token.position.start - previous_edit_end + edit_end;
+                tokens_after.push(token);
+            }
+        }
+
+        let retokenize_start = tokens
+            .last()
+            .map_or(0, |t| t.position.start + t.position.width);
+        let retokenize_end = tokens_after
+            .first()
+            .map_or(input.len(), |t| t.position.start);
+        tokens.extend(tokenizer.tokenize_expression(
+            input,
+            retokenize_start..retokenize_end,
+            radix,
+        )?);
+        tokens.extend(tokens_after);
+        Ok(tokens)
+    }
+}
+
 #[cfg(test)]
 mod token_parsing_tests {
     use crate::{
+        constants::MathConstant,
         error::ParseError,
         position::Positioned,
         token::{
-            BinaryOperatorToken, FunctionNameToken, ParsedInput, Token, Tokenizer,
+            close_open_parens, from_decimal_comma, is_command, BinaryOperatorToken,
+            FunctionNameToken, IncrementalTokenizer, ParsedInput, Token, Tokenizer,
             UnaryOperatorToken,
         },
+        units::Dimension,
     };
     use num::bigint::BigInt;
+    use std::fmt;
 
     fn get_tokens(input: &str, radix: u8) -> Vec<Positioned<Token>> {
         let tokenizer = Tokenizer::new();
@@ -480,7 +1412,7 @@ mod token_parsing_tests {
         assert_eq!(token.position.start, start);
         assert_eq!(token.position.width, width);
         match token.value {
-            Token::Variable(n) => assert_eq!(n, name),
+            Token::Variable(n) => assert_eq!(&*n, name),
             _ => panic!(),
         }
     }
@@ -625,7 +1557,7 @@ mod token_parsing_tests {
 
     #[test]
     fn all_tokens_no_spaces() {
-        let tokens = get_tokens("$var=1,.1()+-*/%^sqrt,abs,max,min", 10);
+        let tokens = get_tokens("$var=1,.1()+-*/^%sqrt,abs,max,min", 10);
         let mut token_iter = tokens.into_iter();
         assert_variable(token_iter.next().unwrap(), "$var", 0, 4);
         assert_assignment(token_iter.next().unwrap(), 4, 1);
@@ -638,8 +1570,8 @@ mod token_parsing_tests {
         assert_subtract_op(token_iter.next().unwrap(), 12, 1);
         assert_multiply_op(token_iter.next().unwrap(), 13, 1);
         assert_divide_op(token_iter.next().unwrap(), 14, 1);
-        assert_modulus_op(token_iter.next().unwrap(), 15, 1);
-        assert_exponent_op(token_iter.next().unwrap(), 16, 1);
+        assert_exponent_op(token_iter.next().unwrap(), 15, 1);
+        assert_modulus_op(token_iter.next().unwrap(), 16, 1);
         assert_sqrt_op(token_iter.next().unwrap(), 17, 4);
         assert_comma(token_iter.next().unwrap(), 21, 1);
         assert_abs_op(token_iter.next().unwrap(), 22, 3);
@@ -652,7 +1584,7 @@ mod token_parsing_tests {
 
     #[test]
     fn all_tokens_with_spaces() {
-        let tokens = get_tokens(" $var = , 1 1.1 ( ) + - * / % ^ sqrt abs max min ", 10);
+        let tokens = get_tokens(" $var = , 1 1.1 ( ) + - * / ^ % sqrt abs max min ", 10);
         let mut token_iter = tokens.into_iter();
         assert_variable(token_iter.next().unwrap(), "$var", 1, 4);
         assert_assignment(token_iter.next().unwrap(), 6, 1);
@@ -665,8 +1597,8 @@ mod token_parsing_tests {
         assert_subtract_op(token_iter.next().unwrap(), 22, 1);
         assert_multiply_op(token_iter.next().unwrap(), 24, 1);
         assert_divide_op(token_iter.next().unwrap(), 26, 1);
-        assert_modulus_op(token_iter.next().unwrap(), 28, 1);
-        assert_exponent_op(token_iter.next().unwrap(), 30, 1);
+        assert_exponent_op(token_iter.next().unwrap(), 28, 1);
+        assert_modulus_op(token_iter.next().unwrap(), 30, 1);
         assert_sqrt_op(token_iter.next().unwrap(), 32, 4);
         assert_abs_op(token_iter.next().unwrap(), 37, 3);
         assert_max_fn(token_iter.next().unwrap(), 41, 3);
@@ -674,6 +1606,65 @@ mod token_parsing_tests {
         assert!(token_iter.next().is_none());
     }
 
+    #[test]
+    fn integer_division() {
+        let tokens = get_tokens("7//2/1 div 3 mod 4", 10);
+        let operators: Vec<(BinaryOperatorToken, usize, usize)> = tokens
+            .into_iter()
+            .filter_map(|token| match token.value {
+                Token::BinaryOperator(operator) => {
+                    Some((operator, token.position.start, token.position.width))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            operators,
+            vec![
+                (BinaryOperatorToken::IntegerDivide, 1, 2),
+                (BinaryOperatorToken::Divide, 4, 1),
+                (BinaryOperatorToken::IntegerDivide, 7, 3),
+                (BinaryOperatorToken::FloorModulus, 13, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn percent() {
+        let operators = |input: &str| -> Vec<String> {
+            get_tokens(input, 10)
+                .into_iter()
+                .filter(|token| {
+                    matches!(
+                        token.value,
+                        Token::BinaryOperator(_) | Token::UnaryOperator(_)
+                    )
+                })
+                .map(|token| token.value.to_string())
+                .collect()
+        };
+        let percent = "Percent Operator (%)";
+        let modulus = "Modulus Operator (%)";
+        assert_eq!(
+            operators("200 * 15%"),
+            ["Multiplication Operator (*)", percent]
+        );
+        assert_eq!(
+            operators("15% of 200"),
+            [percent, "Multiplication Operator (*)"]
+        );
+        assert_eq!(
+            operators("(1 + 2) % , 3%)"),
+            ["Addition Operator (+)", percent, percent]
+        );
+        assert_eq!(operators("17 % 5"), [modulus]);
+        assert_eq!(operators("17%(5)"), [modulus]);
+        assert_eq!(operators("17 % -3"), [modulus, "Subtraction Operator (-)"]);
+        assert_eq!(operators("50% - 3"), [percent, "Subtraction Operator (-)"]);
+        // With nothing before it, `%` continues the previous result.
+        assert_eq!(operators("% 5"), [modulus]);
+    }
+
     #[test]
     fn multiple_decimal_points() {
         let tokenizer = Tokenizer::new();
@@ -702,6 +1693,21 @@ mod token_parsing_tests {
         assert!(token_iter.next().is_none());
     }
 
+    #[test]
+    fn base_36() {
+        let tokens = get_tokens("zz.i Bcalc max(z)", 36);
+        let mut token_iter = tokens.into_iter();
+        assert_number(token_iter.next().unwrap(), 2591, 2, 0, 4);
+        assert_number(token_iter.next().unwrap(), 19_049_376, 1, 5, 5);
+        // Names are still read as names, even though they are made of digits.
+        assert!(matches!(
+            token_iter.next().unwrap().value,
+            Token::Function(FunctionNameToken::Max)
+        ));
+        token_iter.next().unwrap();
+        assert_number(token_iter.next().unwrap(), 35, 1, 15, 1);
+    }
+
     #[test]
     fn out_of_radix_range() {
         let tokenizer = Tokenizer::new();
@@ -723,8 +1729,11 @@ mod token_parsing_tests {
         }
     }
 
-    fn assert_pos_string(input: Positioned<String>, value: &str, start: usize, width: usize) {
-        assert_eq!(input.value, value);
+    fn assert_pos_string<S>(input: Positioned<S>, value: &str, start: usize, width: usize)
+    where
+        S: AsRef<str> + Clone + fmt::Debug,
+    {
+        assert_eq!(input.value.as_ref(), value);
         assert_eq!(input.position.start, start);
         assert_eq!(input.position.width, width);
     }
@@ -831,4 +1840,459 @@ mod token_parsing_tests {
         assert_int(int_iter.next().unwrap(), -789, 9, 4);
         assert!(int_iter.next().is_none());
     }
+
+    #[test]
+    fn number_separators_and_decimals() {
+        let tokens = get_tokens("1_000.2_5 _5 .5 12. a.8", 16);
+        let mut token_iter = tokens.into_iter();
+        assert_number(token_iter.next().unwrap(), 0x100025, 0x100, 0, 9);
+        assert_number(token_iter.next().unwrap(), 5, 1, 10, 2);
+        assert_number(token_iter.next().unwrap(), 5, 16, 13, 2);
+        assert_number(token_iter.next().unwrap(), 0x12, 1, 16, 3);
+        assert_number(token_iter.next().unwrap(), 21, 2, 20, 3);
+        assert!(token_iter.next().is_none());
+
+        let tokenizer = Tokenizer::new();
+        for input in ["1.2.3", ".", "_", "1g"] {
+            let error = tokenizer.tokenize(input, 16).err().unwrap();
+            match error.value {
+                ParseError::InvalidNumber(s) => assert_eq!(s, input),
+                _ => panic!(),
+            }
+            assert_eq!(error.position.start, 0);
+            assert_eq!(error.position.width, input.len());
+        }
+    }
+
+    fn assert_duration(
+        token: Positioned<Token>,
+        numer: u64,
+        denom: u64,
+        start: usize,
+        width: usize,
+    ) {
+        assert_eq!(token.position.start, start);
+        assert_eq!(token.position.width, width);
+        match token.value {
+            Token::Quantity(n, dimension) => {
+                assert_eq!(n.numer(), &BigInt::from(numer));
+                assert_eq!(n.denom(), &BigInt::from(denom));
+                assert_eq!(dimension, Dimension::TIME);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn durations() {
+        let tokens = get_tokens("1h30m 90min 1.5d 250ms 2w", 10);
+        let mut token_iter = tokens.into_iter();
+        assert_duration(token_iter.next().unwrap(), 5400, 1, 0, 5);
+        assert_duration(token_iter.next().unwrap(), 5400, 1, 6, 5);
+        assert_duration(token_iter.next().unwrap(), 129600, 1, 12, 4);
+        assert_duration(token_iter.next().unwrap(), 1, 4, 17, 5);
+        assert_duration(token_iter.next().unwrap(), 1209600, 1, 23, 2);
+        assert!(token_iter.next().is_none());
+
+        // Suffixes that are also digits are read as part of the number.
+        let tokens = get_tokens("1d", 16);
+        assert_number(tokens.into_iter().next().unwrap(), 0x1d, 1, 0, 2);
+
+        let tokenizer = Tokenizer::new();
+        for input in ["1h30", "1hh", "1.2.3h"] {
+            let error = tokenizer.tokenize(input, 10).err().unwrap();
+            match error.value {
+                ParseError::InvalidNumber(s) => assert_eq!(s, input),
+                _ => panic!(),
+            }
+        }
+        // Words that start with a letter are assumed to be misspelled names.
+        let error = tokenizer.tokenize("hh", 10).err().unwrap();
+        match error.value {
+            ParseError::UnknownName(s, suggestions) => {
+                assert_eq!(s, "hh");
+                assert!(suggestions.contains(&"h".to_string()));
+            }
+            _ => panic!(),
+        }
+    }
+
+    fn assert_byte_size(token: Positioned<Token>, bits: u64, start: usize, width: usize) {
+        assert_eq!(token.position.start, start);
+        assert_eq!(token.position.width, width);
+        match token.value {
+            Token::Quantity(n, dimension) => {
+                assert!(n.is_integer());
+                assert_eq!(n.numer(), &BigInt::from(bits));
+                assert_eq!(dimension, Dimension::DATA);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn byte_sizes() {
+        let tokens = get_tokens("4KiB 1.5GB 10kB 2KB 3B 1TiB", 10);
+        let mut token_iter = tokens.into_iter();
+        assert_byte_size(token_iter.next().unwrap(), 4 * 8 * 1024, 0, 4);
+        assert_byte_size(token_iter.next().unwrap(), 12_000_000_000, 5, 5);
+        assert_byte_size(token_iter.next().unwrap(), 80_000, 11, 4);
+        assert_byte_size(token_iter.next().unwrap(), 16_000, 16, 3);
+        assert_byte_size(token_iter.next().unwrap(), 24, 20, 2);
+        assert_byte_size(token_iter.next().unwrap(), 8 << 40, 23, 4);
+        assert!(token_iter.next().is_none());
+
+        // Units whose letters are also digits are read as part of the number.
+        let tokens = get_tokens("1B 1KiB", 16);
+        let mut token_iter = tokens.into_iter();
+        assert_number(token_iter.next().unwrap(), 0x1b, 1, 0, 2);
+        assert_byte_size(token_iter.next().unwrap(), 8 * 1024, 3, 4);
+
+        let tokenizer = Tokenizer::new();
+        for input in ["4KIB", "1.2.3MB", "4bit"] {
+            let error = tokenizer.tokenize(input, 10).err().unwrap();
+            match error.value {
+                ParseError::InvalidNumber(s) => assert_eq!(s, input),
+                _ => panic!("Expected an invalid number error for '{}'", input),
+            }
+        }
+    }
+
+    #[test]
+    fn commands() {
+        assert!(is_command("/help"));
+        assert!(is_command("  /radix 16"));
+        assert!(is_command("/"));
+        assert!(!is_command("/ 2"));
+        assert!(!is_command("/2"));
+        assert!(!is_command("/($x + 1)"));
+        assert!(!is_command("1 / 2"));
+        assert!(matches!(
+            Tokenizer::new().tokenize("/ 2", 10).unwrap(),
+            ParsedInput::Tokens(_)
+        ));
+    }
+
+    #[test]
+    fn closing_parens() {
+        let mut tokens = get_tokens("max(1, (2 + 3", 10);
+        assert_eq!(close_open_parens(&mut tokens), 2);
+        assert_eq!(tokens.len(), 10);
+        assert_close_paren(tokens.pop().unwrap(), 13, 0);
+        assert_close_paren(tokens.pop().unwrap(), 13, 0);
+
+        let mut tokens = get_tokens("(1))(", 10);
+        assert_eq!(close_open_parens(&mut tokens), 0);
+        assert_eq!(tokens.len(), 5);
+        let mut tokens = get_tokens("(1)", 10);
+        assert_eq!(close_open_parens(&mut tokens), 0);
+        assert_eq!(tokens.len(), 3);
+    }
+
+    #[test]
+    fn decimal_comma() {
+        assert_eq!(from_decimal_comma("1,5 + 2", 10), "1.5 + 2");
+        assert_eq!(from_decimal_comma("1.234.567,89", 10), "1_234_567.89");
+        assert_eq!(from_decimal_comma("1 234,5 * 2", 10), "1_234.5 * 2");
+        assert_eq!(from_decimal_comma("max(1,5; 2)", 10), "max(1.5, 2)");
+        // Only groups of three digits are separated.
+        assert_eq!(from_decimal_comma("1.23 + 1 2345", 10), "1.23 + 1 2345");
+        assert_eq!(
+            from_decimal_comma("network(192.168.100.1/24)", 10),
+            "network(192.168.100.1/24)"
+        );
+        assert_eq!(from_decimal_comma("$x1 000", 10), "$x1 000");
+        assert_eq!(from_decimal_comma("1.ABC", 16), "1_ABC");
+    }
+
+    #[test]
+    fn radix_prefixes() {
+        let tokens = get_tokens("0xff 0o777 0b1010 0b1.1 0XFF 16#ff 3#210 36#z", 10);
+        let mut token_iter = tokens.into_iter();
+        assert_number(token_iter.next().unwrap(), 255, 1, 0, 4);
+        assert_number(token_iter.next().unwrap(), 511, 1, 5, 5);
+        assert_number(token_iter.next().unwrap(), 10, 1, 11, 6);
+        assert_number(token_iter.next().unwrap(), 3, 2, 18, 5);
+        assert_number(token_iter.next().unwrap(), 255, 1, 24, 4);
+        assert_number(token_iter.next().unwrap(), 255, 1, 29, 5);
+        assert_number(token_iter.next().unwrap(), 21, 1, 35, 5);
+        assert_number(token_iter.next().unwrap(), 35, 1, 41, 4);
+        assert!(token_iter.next().is_none());
+
+        // Prefixes are read the same way regardless of the current radix, except where the prefix
+        // is itself made of digits, like `0b` in hexadecimal.
+        let tokens = get_tokens("0x10 0o10 10#10 0b10", 16);
+        let mut token_iter = tokens.into_iter();
+        assert_number(token_iter.next().unwrap(), 16, 1, 0, 4);
+        assert_number(token_iter.next().unwrap(), 8, 1, 5, 4);
+        assert_number(token_iter.next().unwrap(), 10, 1, 10, 5);
+        assert_number(token_iter.next().unwrap(), 0xb10, 1, 16, 4);
+
+        let tokenizer = Tokenizer::new();
+        for input in ["0b102", "0o8", "0xfg", "37#1", "1#0", "16#", "#ff"] {
+            let error = tokenizer.tokenize(input, 10).err().unwrap();
+            match error.value {
+                ParseError::InvalidNumber(s) => assert_eq!(s, input),
+                _ => panic!("Expected an invalid number error for '{}'", input),
+            }
+        }
+    }
+
+    #[test]
+    fn si_suffixes() {
+        let tokens = get_tokens("4.7k 2M 1G 3T 1P 10u 5n 1.5p 5m", 10);
+        let mut token_iter = tokens.into_iter();
+        assert_number(token_iter.next().unwrap(), 4700, 1, 0, 4);
+        assert_number(token_iter.next().unwrap(), 2_000_000, 1, 5, 2);
+        assert_number(token_iter.next().unwrap(), 1_000_000_000, 1, 8, 2);
+        assert_number(token_iter.next().unwrap(), 3_000_000_000_000, 1, 11, 2);
+        assert_number(token_iter.next().unwrap(), 1_000_000_000_000_000, 1, 14, 2);
+        assert_number(token_iter.next().unwrap(), 1, 100_000, 17, 3);
+        assert_number(token_iter.next().unwrap(), 1, 200_000_000, 21, 2);
+        assert_number(token_iter.next().unwrap(), 3, 2_000_000_000_000, 24, 4);
+        // `m` is minutes rather than milli.
+        assert_duration(token_iter.next().unwrap(), 300, 1, 29, 2);
+        assert!(token_iter.next().is_none());
+
+        // The number before the suffix is read in the current radix.
+        let tokens = get_tokens("Fk", 16);
+        assert_number(tokens.into_iter().next().unwrap(), 15_000, 1, 0, 2);
+
+        let mut tokenizer = Tokenizer::new();
+        tokenizer.set_si_suffixes(false);
+        for input in ["4.7k", "10u"] {
+            let error = tokenizer.tokenize(input, 10).err().unwrap();
+            match error.value {
+                ParseError::InvalidNumber(s) => assert_eq!(s, input),
+                _ => panic!(),
+            }
+        }
+        assert!(tokenizer.tokenize("5m", 10).is_ok());
+
+        // With the milli suffix on, `m` by itself after a number is milli, but it is still minutes
+        // alongside other duration suffixes.
+        let mut tokenizer = Tokenizer::new();
+        tokenizer.set_milli_suffix(true);
+        let tokens = match tokenizer.tokenize("4.7m 1h30m 2min m", 10).unwrap() {
+            ParsedInput::Tokens(tokens) => tokens,
+            _ => panic!(),
+        };
+        let mut token_iter = tokens.into_iter();
+        assert_number(token_iter.next().unwrap(), 47, 10_000, 0, 4);
+        assert_duration(token_iter.next().unwrap(), 5_400, 1, 5, 5);
+        assert_duration(token_iter.next().unwrap(), 120, 1, 11, 4);
+        assert!(matches!(token_iter.next().unwrap().value, Token::Unit(_)));
+        assert!(token_iter.next().is_none());
+        tokenizer.set_si_suffixes(false);
+        assert!(tokenizer.tokenize("4.7m", 10).is_ok());
+    }
+
+    #[test]
+    fn radix_suffixes() {
+        let tokens = get_tokens("FFh 777o 1010b 1.1b 2h 020h", 10);
+        let mut token_iter = tokens.into_iter();
+        assert_number(token_iter.next().unwrap(), 255, 1, 0, 3);
+        assert_number(token_iter.next().unwrap(), 511, 1, 4, 4);
+        assert_number(token_iter.next().unwrap(), 10, 1, 9, 5);
+        assert_number(token_iter.next().unwrap(), 3, 2, 15, 4);
+        // Without a leading 0, digits followed by `h` are a number of hours.
+        assert_duration(token_iter.next().unwrap(), 7200, 1, 20, 2);
+        assert_number(token_iter.next().unwrap(), 32, 1, 23, 4);
+        assert!(token_iter.next().is_none());
+
+        // Suffixes that are also digits are read as part of the number.
+        let tokens = get_tokens("1010b", 16);
+        assert_number(tokens.into_iter().next().unwrap(), 0x1010b, 1, 0, 5);
+
+        let tokenizer = Tokenizer::new();
+        for input in ["12b", "8o", "FGh"] {
+            let error = tokenizer.tokenize(input, 10).err().unwrap();
+            match error.value {
+                ParseError::InvalidNumber(s) => assert_eq!(s, input),
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn units() {
+        let tokens = get_tokens("3 GiB/KiB", 10);
+        let mut token_iter = tokens.into_iter();
+        assert_number(token_iter.next().unwrap(), 3, 1, 0, 1);
+        let unit = token_iter.next().unwrap();
+        assert_eq!((unit.position.start, unit.position.width), (2, 3));
+        match unit.value {
+            Token::Unit(name) => assert_eq!(name, "GiB"),
+            _ => panic!(),
+        }
+        token_iter.next().unwrap();
+        assert!(matches!(token_iter.next().unwrap().value, Token::Unit(..)));
+        assert!(token_iter.next().is_none());
+
+        // Unit names that are also numbers are read as numbers.
+        let tokens = get_tokens("B", 16);
+        assert_number(tokens.into_iter().next().unwrap(), 11, 1, 0, 1);
+    }
+
+    #[test]
+    fn constants() {
+        let tokens = get_tokens("const.c*2", 10);
+        let mut token_iter = tokens.into_iter();
+        let constant = token_iter.next().unwrap();
+        assert_eq!((constant.position.start, constant.position.width), (0, 7));
+        match constant.value {
+            Token::Constant(name) => assert_eq!(name, "c"),
+            _ => panic!(),
+        }
+        assert_multiply_op(token_iter.next().unwrap(), 7, 1);
+        assert_number(token_iter.next().unwrap(), 2, 1, 8, 1);
+        assert!(token_iter.next().is_none());
+
+        let tokenizer = Tokenizer::new();
+        for input in ["const.C", "const.", "const.c.c"] {
+            let error = tokenizer.tokenize(input, 10).err().unwrap();
+            match error.value {
+                ParseError::UnknownConstant(s) => assert_eq!(s, input),
+                _ => panic!(),
+            }
+        }
+
+        let tokens = get_tokens("2*pi*e", 10);
+        let mut token_iter = tokens.into_iter();
+        token_iter.next().unwrap();
+        token_iter.next().unwrap();
+        let constant = token_iter.next().unwrap();
+        assert_eq!((constant.position.start, constant.position.width), (2, 2));
+        assert!(matches!(
+            constant.value,
+            Token::MathConstant(MathConstant::Pi)
+        ));
+        token_iter.next().unwrap();
+        assert!(matches!(
+            token_iter.next().unwrap().value,
+            Token::MathConstant(MathConstant::E)
+        ));
+        assert!(token_iter.next().is_none());
+
+        // `e` is a digit in hexadecimal.
+        let tokens = get_tokens("e", 16);
+        assert_number(tokens.into_iter().next().unwrap(), 14, 1, 0, 1);
+    }
+
+    #[test]
+    fn imaginary_numbers() {
+        let tokens = get_tokens("4i+i*1.5i im(2)", 10);
+        let descriptions: Vec<(String, usize, usize)> = tokens
+            .into_iter()
+            .map(|token| {
+                (
+                    token.value.to_string(),
+                    token.position.start,
+                    token.position.width,
+                )
+            })
+            .collect();
+        assert_eq!(
+            descriptions[..5],
+            [
+                ("Imaginary Number (4i)".to_string(), 0, 2),
+                ("Addition Operator (+)".to_string(), 2, 1),
+                ("Imaginary Number (1i)".to_string(), 3, 1),
+                ("Multiplication Operator (*)".to_string(), 4, 1),
+                ("Imaginary Number (3/2i)".to_string(), 5, 4),
+            ]
+        );
+        assert_eq!(descriptions[5].0, "Im Function");
+
+        // `i` is a digit in radixes above 18.
+        let tokens = get_tokens("i", 19);
+        assert_number(tokens.into_iter().next().unwrap(), 18, 1, 0, 1);
+    }
+
+    #[test]
+    fn plus_minus() {
+        let tokens = get_tokens("5±0.1 +/- 2+-1 interval(1, 2)", 10);
+        let descriptions: Vec<(String, usize, usize)> = tokens
+            .into_iter()
+            .map(|token| {
+                (
+                    token.value.to_string(),
+                    token.position.start,
+                    token.position.width,
+                )
+            })
+            .collect();
+        assert_eq!(
+            descriptions[..8],
+            [
+                ("Number (5)".to_string(), 0, 1),
+                ("Plus-Minus Operator (±)".to_string(), 1, 2),
+                ("Number (1/10)".to_string(), 3, 3),
+                ("Plus-Minus Operator (±)".to_string(), 7, 3),
+                ("Number (2)".to_string(), 11, 1),
+                ("Addition Operator (+)".to_string(), 12, 1),
+                ("Subtraction Operator (-)".to_string(), 13, 1),
+                ("Number (1)".to_string(), 14, 1),
+            ]
+        );
+        assert_eq!(descriptions[8].0, "Interval Function");
+    }
+
+    #[test]
+    fn ipv4_addresses() {
+        let tokens = get_tokens("192.168.1.10/24", 16);
+        let mut token_iter = tokens.into_iter();
+        let address = token_iter.next().unwrap();
+        assert_eq!((address.position.start, address.position.width), (0, 12));
+        match address.value {
+            Token::Ipv4Address(address) => assert_eq!(address, 0xC0A8010A),
+            _ => panic!(),
+        }
+        assert_divide_op(token_iter.next().unwrap(), 12, 1);
+        assert_number(token_iter.next().unwrap(), 0x24, 1, 13, 2);
+        assert!(token_iter.next().is_none());
+
+        let tokenizer = Tokenizer::new();
+        for input in ["1.2.3.256", "1.2.3"] {
+            match tokenizer.tokenize(input, 10).err().unwrap().value {
+                ParseError::InvalidNumber(s) => assert_eq!(s, input),
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn incremental() {
+        let tokenizer = Tokenizer::new();
+        let mut incremental = IncrementalTokenizer::new();
+        let edits = [
+            ("1", 10),
+            ("12", 10),
+            ("12 + 3", 10),
+            ("12 + 34", 10),
+            ("912 + 34", 10),
+            ("91 + 34", 10),
+            ("91+34", 10),
+            ("$a = 91+34", 10),
+            ("$ab = 91+34", 10),
+            ("$ab = max(91, 34)", 10),
+            ("$ab = max(91, 3.4.)", 10),
+            ("$ab = max(91, 3.4)", 10),
+            ("$ab = max(ff, 3.4)", 10),
+            ("$ab = max(ff, 3.4)", 16),
+            ("/help max", 16),
+            ("(1 + 2) * (3 - 4)", 16),
+            ("(1 + 2)(3 - 4)", 16),
+            ("(1 + 2)x(3 - 4)", 16),
+            ("", 16),
+            ("sqrt 4", 16),
+            ("3 GiB / 4 KiB", 10),
+            ("3 GiB / 4 KiBs", 10),
+        ];
+        for (input, radix) in edits {
+            let expected = format!("{:?}", tokenizer.tokenize(input, radix));
+            let actual = format!("{:?}", incremental.tokenize(&tokenizer, input, radix));
+            assert_eq!(actual, expected, "input: {}", input);
+        }
+    }
 }
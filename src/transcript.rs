@@ -0,0 +1,139 @@
+//! A plain text record of a session, kept for auditing. Each input is appended along with a
+//! timestamp, the prompt it was entered at, and whatever was output in response. Unlike the input
+//! history, the transcript is never trimmed and doesn't depend on the on-disk database.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The prompt that inputs are recorded as having been entered at.
+const TRANSCRIPT_PROMPT_STR: &str = "# ";
+
+pub struct Transcript {
+    path: PathBuf,
+    file: File,
+}
+
+impl Transcript {
+    /// Opens the file at `path` for appending, creating it if it doesn't exist, and records that
+    /// the transcript was started.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Transcript> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut transcript = Transcript { path, file };
+        transcript.write_entry("Transcript started")?;
+        Ok(transcript)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the input and the output that it produced.
+    pub fn record(&mut self, input: &str, output: &str) -> io::Result<()> {
+        let mut entry = format!("{}{}", TRANSCRIPT_PROMPT_STR, input);
+        if !output.is_empty() {
+            entry.push('\n');
+            entry.push_str(output);
+        }
+        self.write_entry(&entry)
+    }
+
+    /// Records that the transcript was stopped. Nothing more is written after this.
+    pub fn close(mut self) -> io::Result<()> {
+        self.write_entry("Transcript stopped")
+    }
+
+    fn write_entry(&mut self, entry: &str) -> io::Result<()> {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        writeln!(self.file, "[{}] {}", format_timestamp(seconds), entry)?;
+        self.file.flush()
+    }
+}
+
+/// Formats a Unix timestamp, in seconds, as an ISO 8601 UTC date and time, like
+/// `2023-11-14T22:13:20Z`.
+pub fn format_timestamp(seconds: u64) -> String {
+    let days = seconds / 86400;
+    let time = seconds % 86400;
+    // Converts the number of days since 1970-01-01 to a date in the proleptic Gregorian calendar.
+    // The calendar repeats every 400 years ("eras"), and counting years from March makes the leap
+    // day the last day of the year.
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod transcript_tests {
+    use crate::transcript::{format_timestamp, Transcript};
+    use std::fs;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1700000000), "2023-11-14T22:13:20Z");
+        assert_eq!(format_timestamp(4102444799), "2099-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn records() {
+        let path =
+            std::env::temp_dir().join(format!("bcalc_test_transcript_{}.txt", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut transcript = Transcript::open(&path).unwrap();
+        transcript.record("1 + 2", "3").unwrap();
+        transcript.record("", "").unwrap();
+        transcript.close().unwrap();
+        Transcript::open(&path).unwrap().close().unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // Strip the timestamps, which are the only part of each entry that starts with '['.
+        let lines: Vec<&str> = contents
+            .lines()
+            .map(|line| match line.strip_prefix('[') {
+                Some(rest) => &rest[rest.find("] ").unwrap() + 2..],
+                None => line,
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Transcript started",
+                "# 1 + 2",
+                "3",
+                "# ",
+                "Transcript stopped",
+                "Transcript started",
+                "Transcript stopped",
+            ]
+        );
+    }
+}
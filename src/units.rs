@@ -0,0 +1,792 @@
+//! Units of measurement that values can be converted between, such as for the `/convert` command.
+//! Each unit is described by what it measures (its `Dimension`) and the exact factor that converts
+//! a value in that unit to the base unit of that dimension. Values can only be converted between
+//! units with the same dimension.
+
+use crate::{operations::pi, Args};
+use num::{rational::BigRational, BigInt, One, Signed};
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The quantities that every dimension is made up of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BaseQuantity {
+    Length,
+    Mass,
+    Time,
+    Data,
+    Currency,
+    Angle,
+}
+
+const BASE_QUANTITIES: [BaseQuantity; 6] = [
+    BaseQuantity::Length,
+    BaseQuantity::Mass,
+    BaseQuantity::Time,
+    BaseQuantity::Data,
+    BaseQuantity::Currency,
+    BaseQuantity::Angle,
+];
+
+impl fmt::Display for BaseQuantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BaseQuantity::Length => write!(f, "length"),
+            BaseQuantity::Mass => write!(f, "mass"),
+            BaseQuantity::Time => write!(f, "time"),
+            BaseQuantity::Data => write!(f, "data"),
+            BaseQuantity::Currency => write!(f, "currency"),
+            BaseQuantity::Angle => write!(f, "angle"),
+        }
+    }
+}
+
+/// The kind of quantity that a unit measures, given as the power that each `BaseQuantity` is
+/// raised to. For example, speed is length divided by time, so it has a length exponent of 1 and a
+/// time exponent of -1. Plain numbers have every exponent set to 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Dimension {
+    exponents: [i8; BASE_QUANTITIES.len()],
+}
+
+impl Dimension {
+    pub const NONE: Dimension = Dimension::from_exponents([0, 0, 0, 0, 0, 0]);
+    pub const LENGTH: Dimension = Dimension::from_exponents([1, 0, 0, 0, 0, 0]);
+    pub const MASS: Dimension = Dimension::from_exponents([0, 1, 0, 0, 0, 0]);
+    pub const TIME: Dimension = Dimension::from_exponents([0, 0, 1, 0, 0, 0]);
+    pub const DATA: Dimension = Dimension::from_exponents([0, 0, 0, 1, 0, 0]);
+    pub const CURRENCY: Dimension = Dimension::from_exponents([0, 0, 0, 0, 1, 0]);
+    pub const ANGLE: Dimension = Dimension::from_exponents([0, 0, 0, 0, 0, 1]);
+    pub const AREA: Dimension = Dimension::from_exponents([2, 0, 0, 0, 0, 0]);
+    pub const VOLUME: Dimension = Dimension::from_exponents([3, 0, 0, 0, 0, 0]);
+    pub const SPEED: Dimension = Dimension::from_exponents([1, 0, -1, 0, 0, 0]);
+
+    const fn from_exponents(exponents: [i8; BASE_QUANTITIES.len()]) -> Dimension {
+        Dimension { exponents }
+    }
+
+    pub fn exponent(&self, quantity: BaseQuantity) -> i8 {
+        let index = BASE_QUANTITIES.iter().position(|q| *q == quantity).unwrap();
+        self.exponents[index]
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Dimension::NONE
+    }
+
+    /// The exponent of each `BaseQuantity`, in the order that they are declared in.
+    pub fn exponents(&self) -> &[i8] {
+        &self.exponents
+    }
+
+    /// The inverse of `exponents`. Missing exponents are taken to be 0, so that dimensions saved
+    /// before a base quantity was added can still be read. Returns `None` if there are more
+    /// exponents than base quantities and any of the extra ones are not 0.
+    pub fn from_exponent_list(list: &[i8]) -> Option<Dimension> {
+        let mut exponents = [0; BASE_QUANTITIES.len()];
+        for (index, exponent) in list.iter().enumerate() {
+            match exponents.get_mut(index) {
+                Some(slot) => *slot = *exponent,
+                None if *exponent == 0 => {}
+                None => return None,
+            }
+        }
+        Some(Dimension { exponents })
+    }
+
+    /// The dimension of the product of values with these dimensions. Returns `None` if an exponent
+    /// would be too large to represent.
+    pub fn multiply(&self, other: &Dimension) -> Option<Dimension> {
+        let mut exponents = self.exponents;
+        for (exponent, other_exponent) in exponents.iter_mut().zip(other.exponents) {
+            *exponent = exponent.checked_add(other_exponent)?;
+        }
+        Some(Dimension { exponents })
+    }
+
+    /// The dimension of the quotient of values with these dimensions. Returns `None` if an
+    /// exponent would be too large to represent.
+    pub fn divide(&self, other: &Dimension) -> Option<Dimension> {
+        self.multiply(&other.pow(-1)?)
+    }
+
+    /// The dimension of a value with this dimension raised to the given power. Returns `None` if an
+    /// exponent would be too large to represent.
+    pub fn pow(&self, power: i8) -> Option<Dimension> {
+        let mut exponents = self.exponents;
+        for exponent in exponents.iter_mut() {
+            *exponent = exponent.checked_mul(power)?;
+        }
+        Some(Dimension { exponents })
+    }
+
+    /// The dimension of the given root of a value with this dimension. Returns `None` if the result
+    /// would have fractional exponents, such as the square root of a length.
+    pub fn root(&self, degree: i8) -> Option<Dimension> {
+        let mut exponents = self.exponents;
+        for exponent in exponents.iter_mut() {
+            if degree == 0 || *exponent % degree != 0 {
+                return None;
+            }
+            *exponent /= degree;
+        }
+        Some(Dimension { exponents })
+    }
+}
+
+impl Default for Dimension {
+    fn default() -> Dimension {
+        Dimension::NONE
+    }
+}
+
+// Dimensions with names of their own. Other dimensions are described in terms of the base
+// quantities.
+const NAMED_DIMENSIONS: &[(Dimension, &str)] = &[
+    (Dimension::NONE, "no units"),
+    (Dimension::AREA, "area"),
+    (Dimension::VOLUME, "volume"),
+    (Dimension::SPEED, "speed"),
+];
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((_, name)) = NAMED_DIMENSIONS.iter().find(|(d, _)| d == self) {
+            return write!(f, "{}", name);
+        }
+        // Positive exponents are listed before the '/' and negative ones after it, like
+        // "length/time^2".
+        let describe = |sign: i8| {
+            let mut parts: Vec<String> = Vec::new();
+            for (quantity, exponent) in BASE_QUANTITIES.iter().zip(self.exponents) {
+                let exponent = exponent * sign;
+                if exponent == 1 {
+                    parts.push(quantity.to_string());
+                } else if exponent > 1 {
+                    parts.push(format!("{}^{}", quantity, exponent));
+                }
+            }
+            parts.join("*")
+        };
+        let numerator = describe(1);
+        let denominator = describe(-1);
+        match (numerator.is_empty(), denominator.is_empty()) {
+            (_, true) => write!(f, "{}", numerator),
+            (true, false) => write!(f, "1/{}", denominator),
+            (false, false) => write!(f, "{}/{}", numerator, denominator),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Unit {
+    /// The first name is the one that the unit is listed under. The rest are alternative spellings.
+    pub names: Vec<String>,
+    pub dimension: Dimension,
+    /// Multiplying a value in this unit by this factor gives the value in the base unit of the
+    /// dimension.
+    pub factor: BigRational,
+    /// Whether the unit was defined by the user (via `/defunit`) rather than being built in.
+    pub is_custom: bool,
+}
+
+impl Unit {
+    pub fn name(&self) -> &str {
+        &self.names[0]
+    }
+}
+
+// Each unit is listed with its names and the numerator and denominator of its factor, so that the
+// factors are exact.
+type UnitDefinition = (&'static [&'static str], u64, u64);
+
+// Factors are relative to meters.
+const LENGTH_UNITS: &[UnitDefinition] = &[
+    (&["nm", "nanometer", "nanometers"], 1, 1_000_000_000),
+    (&["um", "micrometer", "micrometers"], 1, 1_000_000),
+    (&["mm", "millimeter", "millimeters"], 1, 1_000),
+    (&["cm", "centimeter", "centimeters"], 1, 100),
+    (&["m", "meter", "meters"], 1, 1),
+    (&["km", "kilometer", "kilometers"], 1_000, 1),
+    (&["in", "inch", "inches"], 254, 10_000),
+    (&["ft", "foot", "feet"], 3_048, 10_000),
+    (&["yd", "yard", "yards"], 9_144, 10_000),
+    (&["mi", "mile", "miles"], 1_609_344, 1_000),
+    (&["nmi", "nauticalmile", "nauticalmiles"], 1_852, 1),
+];
+
+// Factors are relative to grams.
+const MASS_UNITS: &[UnitDefinition] = &[
+    (&["mg", "milligram", "milligrams"], 1, 1_000),
+    (&["g", "gram", "grams"], 1, 1),
+    (&["kg", "kilogram", "kilograms"], 1_000, 1),
+    (&["t", "tonne", "tonnes"], 1_000_000, 1),
+    (&["oz", "ounce", "ounces"], 45_359_237, 1_600_000),
+    (&["lb", "lbs", "pound", "pounds"], 45_359_237, 100_000),
+    (&["st", "stone", "stones"], 45_359_237 * 14, 100_000),
+    (&["ton", "tons", "shortton"], 45_359_237 * 2_000, 100_000),
+];
+
+// Factors are relative to cubic meters. Cups, pints, and so on are the US customary units, which
+// are defined as fractions of a gallon. A gallon is 3_785_411_784 / GALLON_DENOM cubic meters.
+const GALLON_DENOM: u64 = 1_000_000_000_000;
+const VOLUME_UNITS: &[UnitDefinition] = &[
+    (&["ml", "milliliter", "milliliters"], 1, 1_000_000),
+    (&["cl", "centiliter", "centiliters"], 1, 100_000),
+    (&["l", "liter", "liters"], 1, 1_000),
+    (&["m3", "m^3"], 1, 1),
+    (
+        &["tsp", "teaspoon", "teaspoons"],
+        3_785_411_784,
+        768 * GALLON_DENOM,
+    ),
+    (
+        &["tbsp", "tablespoon", "tablespoons"],
+        3_785_411_784,
+        256 * GALLON_DENOM,
+    ),
+    (
+        &["floz", "fluidounce", "fluidounces"],
+        3_785_411_784,
+        128 * GALLON_DENOM,
+    ),
+    (&["cup", "cups"], 3_785_411_784, 16 * GALLON_DENOM),
+    (&["pt", "pint", "pints"], 3_785_411_784, 8 * GALLON_DENOM),
+    (&["qt", "quart", "quarts"], 3_785_411_784, 4 * GALLON_DENOM),
+    (&["gal", "gallon", "gallons"], 3_785_411_784, GALLON_DENOM),
+    (
+        &["impgal", "imperialgallon", "imperialgallons"],
+        454_609,
+        100_000_000,
+    ),
+];
+
+// Factors are relative to seconds.
+const TIME_UNITS: &[UnitDefinition] = &[
+    (&["ms", "millisecond", "milliseconds"], 1, 1_000),
+    (&["s", "sec", "second", "seconds"], 1, 1),
+    (&["min", "minute", "minutes"], 60, 1),
+    (&["h", "hr", "hour", "hours"], 3_600, 1),
+    (&["d", "day", "days"], 86_400, 1),
+    (&["wk", "week", "weeks"], 604_800, 1),
+];
+
+// Factors are relative to meters per second.
+const SPEED_UNITS: &[UnitDefinition] = &[
+    (&["m/s", "mps"], 1, 1),
+    (&["km/h", "kph", "kmh"], 1_000, 3_600),
+    (&["mph", "mi/h"], 1_609_344, 3_600_000),
+    (&["ft/s", "fps"], 3_048, 10_000),
+    (&["kn", "kt", "knot", "knots"], 1_852, 3_600),
+];
+
+// Factors are relative to square meters.
+const AREA_UNITS: &[UnitDefinition] = &[
+    (&["mm2", "mm^2"], 1, 1_000_000),
+    (&["cm2", "cm^2"], 1, 10_000),
+    (&["m2", "m^2", "sqm"], 1, 1),
+    (&["ha", "hectare", "hectares"], 10_000, 1),
+    (&["km2", "km^2"], 1_000_000, 1),
+    (&["in2", "in^2", "sqin"], 64_516, 100_000_000),
+    (&["ft2", "ft^2", "sqft"], 9_290_304, 100_000_000),
+    (&["yd2", "yd^2", "sqyd"], 83_612_736, 100_000_000),
+    (&["ac", "acre", "acres"], 40_468_564_224, 10_000_000),
+    (&["mi2", "mi^2", "sqmi"], 2_589_988_110_336, 1_000_000),
+];
+
+// Factors are relative to bits.
+const DATA_UNITS: &[UnitDefinition] = &[
+    (&["b", "bit", "bits"], 1, 1),
+    (&["B", "byte", "bytes"], 8, 1),
+    (&["kbit", "kilobit", "kilobits"], 1_000, 1),
+    (&["Mbit", "megabit", "megabits"], 1_000_000, 1),
+    (&["Gbit", "gigabit", "gigabits"], 1_000_000_000, 1),
+    (&["kB", "KB", "kilobyte", "kilobytes"], 8 * 1_000, 1),
+    (&["MB", "megabyte", "megabytes"], 8 * 1_000_000, 1),
+    (&["GB", "gigabyte", "gigabytes"], 8 * 1_000_000_000, 1),
+    (&["TB", "terabyte", "terabytes"], 8 * 1_000_000_000_000, 1),
+    (
+        &["PB", "petabyte", "petabytes"],
+        8 * 1_000_000_000_000_000,
+        1,
+    ),
+    (&["KiB", "kibibyte", "kibibytes"], 8 << 10, 1),
+    (&["MiB", "mebibyte", "mebibytes"], 8 << 20, 1),
+    (&["GiB", "gibibyte", "gibibytes"], 8 << 30, 1),
+    (&["TiB", "tebibyte", "tebibytes"], 8 << 40, 1),
+    (&["PiB", "pebibyte", "pebibytes"], 8 << 50, 1),
+];
+
+// Factors are relative to degrees rather than radians so that values in degrees are exact. Radians
+// are added separately by `UnitTable::new`, since their factor isn't rational.
+const ANGLE_UNITS: &[UnitDefinition] = &[
+    (&["deg", "degree", "degrees"], 1, 1),
+    (&["arcmin", "arcminute", "arcminutes"], 1, 60),
+    (&["arcsec", "arcsecond", "arcseconds"], 1, 3_600),
+    (&["grad", "gradian", "gradians"], 9, 10),
+    (&["turn", "turns", "rev"], 360, 1),
+];
+
+const RADIAN_NAMES: &[&str] = &["rad", "radian", "radians"];
+
+/// The number of degrees in a radian, `180 / pi`, with pi worked out to `precision` digits after
+/// the radix point in `radix`, the same as the `pi` constant is.
+pub fn degrees_per_radian(precision: u8, radix: u8) -> BigRational {
+    let pi = pi(1, precision, radix, None).expect("pi can only fail when interrupted");
+    BigRational::from_integer(BigInt::from(180)) / pi
+}
+
+const BUILTIN_UNITS: &[(Dimension, &[UnitDefinition])] = &[
+    (Dimension::LENGTH, LENGTH_UNITS),
+    (Dimension::MASS, MASS_UNITS),
+    (Dimension::TIME, TIME_UNITS),
+    (Dimension::VOLUME, VOLUME_UNITS),
+    (Dimension::SPEED, SPEED_UNITS),
+    (Dimension::AREA, AREA_UNITS),
+    (Dimension::DATA, DATA_UNITS),
+    (Dimension::ANGLE, ANGLE_UNITS),
+];
+
+// Suffixes that can be attached directly to a number to write a duration, like `1h30m`, along
+// with the number of seconds that they stand for. These aren't the same as the names of the time
+// units, since `m` is a minute here but a meter as a unit name.
+const DURATION_SUFFIXES: &[(&str, u64, u64)] = &[
+    ("ms", 1, 1_000),
+    ("s", 1, 1),
+    ("sec", 1, 1),
+    ("m", 60, 1),
+    ("min", 60, 1),
+    ("h", 3_600, 1),
+    ("hr", 3_600, 1),
+    ("d", 86_400, 1),
+    ("w", 604_800, 1),
+];
+
+/// Returns the number of seconds that a duration suffix, like the `h` in `1h30m`, stands for.
+pub fn duration_suffix_seconds(suffix: &str) -> Option<BigRational> {
+    DURATION_SUFFIXES
+        .iter()
+        .find(|(name, _, _)| *name == suffix)
+        .map(|(_, numer, denom)| BigRational::new(BigInt::from(*numer), BigInt::from(*denom)))
+}
+
+// Byte size units that results measuring data are shown in, along with the number of bits that
+// they stand for.
+const BYTE_SIZE_UNITS: &[(&str, u64)] = &[
+    ("B", 8),
+    ("kB", 8 * 1_000),
+    ("KB", 8 * 1_000),
+    ("MB", 8 * 1_000_000),
+    ("GB", 8 * 1_000_000_000),
+    ("TB", 8 * 1_000_000_000_000),
+    ("PB", 8 * 1_000_000_000_000_000),
+    ("KiB", 8 << 10),
+    ("MiB", 8 << 20),
+    ("GiB", 8 << 30),
+    ("TiB", 8 << 40),
+    ("PiB", 8 << 50),
+];
+
+/// Returns the factor and dimension of a unit that can be written after a value in an expression,
+/// like the `GiB` in `3 GiB / 4 KiB` or the `km` in `5 km`. Any of the built in units can be used,
+/// but unlike with `UnitTable::find`, names have to be written in the right case. Radians aren't
+/// included, since their factor depends on the precision; see `is_radian`.
+pub fn expression_unit(name: &str) -> Option<(BigRational, Dimension)> {
+    BUILTIN_UNITS.iter().find_map(|(dimension, definitions)| {
+        definitions
+            .iter()
+            .find(|(names, _, _)| names.contains(&name))
+            .map(|(_, numer, denom)| {
+                (
+                    BigRational::new(BigInt::from(*numer), BigInt::from(*denom)),
+                    *dimension,
+                )
+            })
+    })
+}
+
+/// Whether the name is one of the names of radians, which can be written after a value in an
+/// expression like the units that `expression_unit` accepts.
+pub fn is_radian(name: &str) -> bool {
+    RADIAN_NAMES.contains(&name)
+}
+
+/// Every name that `expression_unit` or `is_radian` accepts.
+pub fn expression_unit_names() -> impl Iterator<Item = &'static str> {
+    RADIAN_NAMES.iter().copied().chain(
+        BUILTIN_UNITS
+            .iter()
+            .flat_map(|(_, definitions)| definitions.iter())
+            .flat_map(|(names, _, _)| names.iter().copied()),
+    )
+}
+
+/// The name of the built in unit that values with the given dimension are measured in, like `m`
+/// for lengths. Returns `None` if there is no such unit, such as for lengths multiplied by times.
+pub fn base_unit_name(dimension: Dimension) -> Option<&'static str> {
+    let (_, definitions) = BUILTIN_UNITS.iter().find(|(d, _)| *d == dimension)?;
+    definitions
+        .iter()
+        .find(|(_, numer, denom)| *numer == 1 && *denom == 1)
+        .map(|(names, _, _)| names[0])
+}
+
+/// Returns the number of bits that a byte size unit, like the `KiB` in `4KiB`, stands for.
+pub fn byte_size_bits(name: &str) -> Option<BigRational> {
+    BYTE_SIZE_UNITS
+        .iter()
+        .find(|(unit_name, _)| *unit_name == name)
+        .map(|(_, bits)| BigRational::from_integer(BigInt::from(*bits)))
+}
+
+/// Picks the byte size unit to show a number of bits in: the largest unit that the value is a whole
+/// multiple of, or failing that, the largest binary unit that the value is at least one of. Returns
+/// the value in that unit along with the unit's name.
+pub fn byte_size_display(bits: &BigRational) -> (BigRational, &'static str) {
+    let magnitude = bits.abs();
+    // Bytes are always included so that there is something to show values smaller than a byte in.
+    let mut candidates: Vec<(&'static str, BigRational)> = BYTE_SIZE_UNITS
+        .iter()
+        .map(|(name, bits)| (*name, BigRational::from_integer(BigInt::from(*bits))))
+        .filter(|(name, factor)| *name == "B" || *factor <= magnitude)
+        .collect();
+    // The sort is stable, so `kB` stays ahead of its alias `KB`.
+    candidates.sort_by(|(_, factor_1), (_, factor_2)| factor_2.cmp(factor_1));
+    let (name, factor) = candidates
+        .iter()
+        .find(|(_, factor)| (bits / factor).is_integer())
+        .or_else(|| {
+            candidates
+                .iter()
+                .find(|(name, _)| *name == "B" || name.ends_with("iB"))
+        })
+        .cloned()
+        .unwrap_or(("B", BigRational::from_integer(BigInt::from(8))));
+    (bits / factor, name)
+}
+
+// Currencies whose symbol can be used in place of their code. Input has to be ASCII, so symbols
+// like the euro sign can't be supported.
+const CURRENCY_SYMBOLS: &[(&str, &str)] = &[("USD", "$")];
+
+/// A set of units that can be looked up by name.
+#[derive(Clone, Debug)]
+pub struct UnitTable {
+    units: Vec<Unit>,
+    // The precision and radix that the factor of radians was last worked out with.
+    radian_precision: (u8, u8),
+}
+
+impl Default for UnitTable {
+    fn default() -> UnitTable {
+        UnitTable::new()
+    }
+}
+
+impl UnitTable {
+    /// Creates a table containing the built in units, with radians worked out to the precision that
+    /// bcalc uses by default. See `set_precision`.
+    pub fn new() -> UnitTable {
+        let mut units = BUILTIN_UNITS
+            .iter()
+            .flat_map(|(dimension, definitions)| {
+                definitions.iter().map(|(names, numer, denom)| Unit {
+                    names: names.iter().map(|name| name.to_string()).collect(),
+                    dimension: *dimension,
+                    factor: BigRational::new(BigInt::from(*numer), BigInt::from(*denom)),
+                    is_custom: false,
+                })
+            })
+            .collect::<Vec<Unit>>();
+        let args = Args::default();
+        let radian_precision = (args.precision + args.extra_precision, args.radix);
+        units.push(Unit {
+            names: RADIAN_NAMES.iter().map(|name| name.to_string()).collect(),
+            dimension: Dimension::ANGLE,
+            factor: degrees_per_radian(radian_precision.0, radian_precision.1),
+            is_custom: false,
+        });
+        UnitTable {
+            units,
+            radian_precision,
+        }
+    }
+
+    /// Works out the factor of radians, which depends on pi, to `precision` digits after the radix
+    /// point in `radix`. Using the precision that expressions are evaluated with keeps conversions
+    /// to and from radians in agreement with the `rad` unit and the `pi` constant.
+    pub fn set_precision(&mut self, precision: u8, radix: u8) {
+        if self.radian_precision == (precision, radix) {
+            return;
+        }
+        self.radian_precision = (precision, radix);
+        if let Some(unit) = self
+            .units
+            .iter_mut()
+            .find(|unit| !unit.is_custom && is_radian(unit.name()))
+        {
+            unit.factor = degrees_per_radian(precision, radix);
+        }
+    }
+
+    /// Replaces any currencies in the table with the given ones. Each currency is given by its code
+    /// and its rate, which is how much of it is equivalent to one unit of some base currency.
+    pub fn set_currencies(&mut self, rates: &[(String, BigRational)]) {
+        self.units
+            .retain(|unit| unit.is_custom || unit.dimension != Dimension::CURRENCY);
+        for (code, rate) in rates {
+            let mut names = vec![code.clone()];
+            if let Some((_, symbol)) = CURRENCY_SYMBOLS.iter().find(|(c, _)| c == code) {
+                names.push(symbol.to_string());
+            }
+            self.units.push(Unit {
+                names,
+                dimension: Dimension::CURRENCY,
+                factor: rate.recip(),
+                is_custom: false,
+            });
+        }
+    }
+
+    /// Adds a unit defined by the user, replacing any custom unit with the same name. Returns an
+    /// error if any of the unit's names belong to a unit that isn't custom.
+    pub fn add_custom_unit(&mut self, unit: Unit) -> Result<(), String> {
+        for name in unit.names.iter() {
+            if self
+                .units
+                .iter()
+                .any(|u| !u.is_custom && u.names.iter().any(|n| n == name))
+            {
+                return Err(format!("'{}' is already the name of a built in unit", name));
+            }
+        }
+        self.units
+            .retain(|u| !(u.is_custom && u.names.iter().any(|n| unit.names.contains(n))));
+        self.units.push(Unit {
+            is_custom: true,
+            ..unit
+        });
+        Ok(())
+    }
+
+    /// Removes the custom unit with the given name. Returns `false` if there isn't one.
+    pub fn remove_custom_unit(&mut self, name: &str) -> bool {
+        let count = self.units.len();
+        self.units
+            .retain(|unit| !(unit.is_custom && unit.names.iter().any(|n| n == name)));
+        self.units.len() != count
+    }
+
+    /// Finds the built in unit that other units of the dimension are measured relative to, such as
+    /// meters for length. Not every dimension has one.
+    pub fn base_unit(&self, dimension: Dimension) -> Option<&Unit> {
+        self.units
+            .iter()
+            .find(|unit| !unit.is_custom && unit.dimension == dimension && unit.factor.is_one())
+    }
+
+    pub fn clear_custom_units(&mut self) {
+        self.units.retain(|unit| !unit.is_custom);
+    }
+
+    pub fn units(&self) -> &[Unit] {
+        &self.units
+    }
+
+    /// Finds the unit with the given name. Names are case sensitive, since some units (such as `b`
+    /// and `B`) differ only by case. But if no name matches exactly and all the names that match
+    /// when ignoring case belong to the same unit, that unit is returned.
+    pub fn find(&self, name: &str) -> Option<&Unit> {
+        if let Some(unit) = self
+            .units
+            .iter()
+            .find(|unit| unit.names.iter().any(|n| n == name))
+        {
+            return Some(unit);
+        }
+        let mut matches = self
+            .units
+            .iter()
+            .filter(|unit| unit.names.iter().any(|n| n.eq_ignore_ascii_case(name)));
+        match (matches.next(), matches.next()) {
+            (Some(unit), None) => Some(unit),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DimensionMismatchError {
+    pub from: Dimension,
+    pub to: Dimension,
+}
+
+impl std::error::Error for DimensionMismatchError {}
+
+impl fmt::Display for DimensionMismatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cannot convert {} to {}", self.from, self.to)
+    }
+}
+
+/// Converts a value in the `from` unit to the `to` unit.
+pub fn convert(
+    value: &BigRational,
+    from: &Unit,
+    to: &Unit,
+) -> Result<BigRational, DimensionMismatchError> {
+    if from.dimension != to.dimension {
+        return Err(DimensionMismatchError {
+            from: from.dimension,
+            to: to.dimension,
+        });
+    }
+    Ok(value * &from.factor / &to.factor)
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use crate::operations::pi;
+    use crate::units::{
+        base_unit_name, byte_size_display, convert, expression_unit, is_radian, Dimension,
+        UnitTable,
+    };
+    use num::{rational::BigRational, BigInt, Signed};
+
+    fn ratio(numer: i64, denom: i64) -> BigRational {
+        BigRational::new(BigInt::from(numer), BigInt::from(denom))
+    }
+
+    #[test]
+    fn find_units() {
+        let table = UnitTable::new();
+        assert_eq!(table.find("mi").unwrap().name(), "mi");
+        assert_eq!(table.find("miles").unwrap().name(), "mi");
+        assert_eq!(table.find("KM").unwrap().name(), "km");
+        assert_eq!(table.find("b").unwrap().name(), "b");
+        assert_eq!(table.find("B").unwrap().name(), "B");
+        assert_eq!(table.find("kb").unwrap().name(), "kB");
+        assert!(table.find("furlong").is_none());
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let table = UnitTable::new();
+        let mut names: Vec<&str> = table
+            .units()
+            .iter()
+            .flat_map(|unit| unit.names.iter().map(|name| name.as_str()))
+            .collect();
+        let count = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), count);
+    }
+
+    #[test]
+    fn conversions() {
+        let table = UnitTable::new();
+        let run = |value: BigRational, from: &str, to: &str| {
+            convert(&value, table.find(from).unwrap(), table.find(to).unwrap())
+        };
+        assert_eq!(
+            run(ratio(5, 1), "mi", "km").unwrap(),
+            ratio(1_005_840, 125_000)
+        );
+        assert_eq!(run(ratio(1, 1), "ft", "in").unwrap(), ratio(12, 1));
+        assert_eq!(run(ratio(1, 1), "lb", "oz").unwrap(), ratio(16, 1));
+        assert_eq!(run(ratio(1, 1), "gal", "floz").unwrap(), ratio(128, 1));
+        assert_eq!(run(ratio(36, 1), "km/h", "m/s").unwrap(), ratio(10, 1));
+        assert_eq!(run(ratio(1, 1), "ac", "yd2").unwrap(), ratio(4_840, 1));
+        assert_eq!(run(ratio(3, 1), "GiB", "KiB").unwrap(), ratio(3 << 20, 1));
+        assert_eq!(run(ratio(1, 1), "kB", "b").unwrap(), ratio(8_000, 1));
+
+        let error = run(ratio(1, 1), "m", "kg").unwrap_err();
+        assert_eq!(error.from, Dimension::LENGTH);
+        assert_eq!(error.to, Dimension::MASS);
+    }
+
+    #[test]
+    fn currencies() {
+        let mut table = UnitTable::new();
+        assert!(table.find("EUR").is_none());
+        table.set_currencies(&[
+            ("EUR".to_string(), ratio(92, 100)),
+            ("USD".to_string(), ratio(1, 1)),
+        ]);
+        let run = |value: BigRational, from: &str, to: &str| {
+            convert(&value, table.find(from).unwrap(), table.find(to).unwrap()).unwrap()
+        };
+        assert_eq!(run(ratio(10, 1), "USD", "EUR"), ratio(92, 10));
+        assert_eq!(run(ratio(46, 1), "eur", "$"), ratio(50, 1));
+
+        table.set_currencies(&[("GBP".to_string(), ratio(79, 100))]);
+        assert!(table.find("EUR").is_none());
+        assert_eq!(table.find("gbp").unwrap().dimension, Dimension::CURRENCY);
+    }
+
+    #[test]
+    fn byte_sizes() {
+        let bytes = |count: i64| ratio(count * 8, 1);
+        let display = |bits: BigRational| {
+            let (value, name) = byte_size_display(&bits);
+            (value.to_string(), name)
+        };
+        assert_eq!(display(bytes(3 << 30)), ("3".to_string(), "GiB"));
+        assert_eq!(display(bytes(3 << 29)), ("1536".to_string(), "MiB"));
+        assert_eq!(display(bytes(2_000_000)), ("2".to_string(), "MB"));
+        assert_eq!(display(bytes(-4_096)), ("-4".to_string(), "KiB"));
+        assert_eq!(display(bytes(1_500)), ("1500".to_string(), "B"));
+        assert_eq!(display(ratio(8_196, 1)), ("2049/2048".to_string(), "KiB"));
+        assert_eq!(display(bytes(0)), ("0".to_string(), "B"));
+        assert_eq!(display(ratio(4, 1)), ("1/2".to_string(), "B"));
+
+        assert_eq!(
+            expression_unit("KiB"),
+            Some((bytes(1_024), Dimension::DATA))
+        );
+        assert_eq!(expression_unit("kB"), expression_unit("KB"));
+        assert_eq!(expression_unit("kib"), None);
+        assert_eq!(
+            expression_unit("km"),
+            Some((ratio(1_000, 1), Dimension::LENGTH))
+        );
+        assert_eq!(base_unit_name(Dimension::SPEED), Some("m/s"));
+        assert_eq!(base_unit_name(Dimension::LENGTH.pow(4).unwrap()), None);
+    }
+
+    #[test]
+    fn angles() {
+        let table = UnitTable::new();
+        let run = |value: BigRational, from: &str, to: &str| {
+            convert(&value, table.find(from).unwrap(), table.find(to).unwrap()).unwrap()
+        };
+        assert_eq!(run(ratio(1, 4), "turn", "deg"), ratio(90, 1));
+        assert_eq!(run(ratio(100, 1), "grad", "degrees"), ratio(90, 1));
+        assert_eq!(run(ratio(1, 1), "deg", "arcsec"), ratio(3_600, 1));
+
+        // Converting 1 radian to degrees should give 57.29577951308232... to well beyond the
+        // precision of a double.
+        let degrees = run(ratio(1, 1), "rad", "deg");
+        let expected = ratio(5_729_577_951_308_232, 100_000_000_000_000);
+        assert!((degrees - expected).abs() < ratio(1, 100_000_000_000_000));
+        assert!(is_radian("rad") && expression_unit("rad").is_none());
+
+        // Radians agree with pi at whatever precision it is worked out to.
+        let mut table = UnitTable::new();
+        table.set_precision(200, 10);
+        let pi = pi(1, 200, 10, None).unwrap();
+        assert_eq!(
+            convert(&pi, table.find("rad").unwrap(), table.find("deg").unwrap()).unwrap(),
+            ratio(180, 1)
+        );
+        assert_eq!(
+            expression_unit("deg"),
+            Some((ratio(1, 1), Dimension::ANGLE))
+        );
+        assert_eq!(Dimension::ANGLE.to_string(), "angle");
+    }
+}
@@ -0,0 +1,730 @@
+//! The values that expressions evaluate to. Most are real numbers, but roots of negative numbers
+//! and anything involving `i` can give complex ones. Both parts of a complex number are exact
+//! rationals, just like real numbers are. Values written with an uncertainty, like `5 ± 0.1`, are
+//! intervals, which arithmetic widens so that they always contain every possible result. Square
+//! roots that aren't rational, like `sqrt(2)`, are kept exactly as surds for as long as the
+//! arithmetic done with them allows, so that `sqrt(2)^2` is exactly 2.
+
+use num::{
+    bigint::BigInt,
+    rational::{BigRational, ParseRatioError},
+    Integer, One, Signed, Zero,
+};
+use std::{
+    cmp::Ordering,
+    fmt,
+    iter::{Product, Sum},
+    ops::{Add, Mul, Neg, Sub},
+    str::FromStr,
+};
+
+/// The result of evaluating an expression. A complex value never has an imaginary part of 0, so
+/// anything that is mathematically real is a `Value::Real` or a `Value::Surd`. Likewise, an
+/// interval's `low` is always less than its `high`. Use `Value::complex`, `Value::interval`, and
+/// `Value::surd` to build values from their parts and keep it that way.
+///
+/// There is no way to represent an interval of complex numbers, so arithmetic that mixes intervals
+/// with complex numbers uses the middle of the interval. Evaluation reports an error instead of
+/// getting that far.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Real(BigRational),
+    Complex {
+        re: BigRational,
+        im: BigRational,
+    },
+    /// A real number that is only known to be somewhere from `low` to `high`.
+    Interval {
+        low: BigRational,
+        high: BigRational,
+    },
+    /// `coefficient * sqrt(radicand)`, where `radicand` is an integer above 1 with no square
+    /// factors and `coefficient` isn't 0. `approximation` is the value worked out to the precision
+    /// that was in effect when the root was taken, which anything that can't keep the exact form
+    /// uses instead.
+    Surd {
+        coefficient: BigRational,
+        radicand: BigInt,
+        approximation: BigRational,
+    },
+}
+
+impl Value {
+    /// `re + im * i`, which is real if `im` is 0.
+    pub fn complex(re: BigRational, im: BigRational) -> Value {
+        if im.is_zero() {
+            Value::Real(re)
+        } else {
+            Value::Complex { re, im }
+        }
+    }
+
+    /// The values from `a` to `b`, in either order, which is real if they are equal.
+    pub fn interval(a: BigRational, b: BigRational) -> Value {
+        match a.cmp(&b) {
+            Ordering::Less => Value::Interval { low: a, high: b },
+            Ordering::Equal => Value::Real(a),
+            Ordering::Greater => Value::Interval { low: b, high: a },
+        }
+    }
+
+    /// `coefficient * sqrt(radicand)`, which is real if `coefficient` is 0 or `radicand` is 1.
+    /// `radicand` must be positive and have no square factors, and `approximation` should be the
+    /// value of the whole thing.
+    pub fn surd(coefficient: BigRational, radicand: BigInt, approximation: BigRational) -> Value {
+        if coefficient.is_zero() || radicand.is_one() {
+            Value::Real(coefficient)
+        } else {
+            Value::Surd {
+                coefficient,
+                radicand,
+                approximation,
+            }
+        }
+    }
+
+    /// The value with a surd replaced by its approximation.
+    pub fn approximated(self) -> Value {
+        match self {
+            Value::Surd { approximation, .. } => Value::Real(approximation),
+            value => value,
+        }
+    }
+
+    /// The imaginary unit, `i`.
+    pub fn i() -> Value {
+        Value::Complex {
+            re: BigRational::zero(),
+            im: BigRational::one(),
+        }
+    }
+
+    /// The real part. For an interval, this is its midpoint, and for a surd, its approximation.
+    pub fn re(&self) -> BigRational {
+        match self {
+            Value::Real(value)
+            | Value::Surd {
+                approximation: value,
+                ..
+            } => value.clone(),
+            Value::Complex { re, .. } => re.clone(),
+            Value::Interval { low, high } => (low + high) / BigInt::from(2),
+        }
+    }
+
+    /// The imaginary part, which is 0 for anything but complex values.
+    pub fn im(&self) -> BigRational {
+        match self {
+            Value::Complex { im, .. } => im.clone(),
+            _ => BigRational::zero(),
+        }
+    }
+
+    /// The lowest and highest values that the value could be. Returns `None` if the value is
+    /// complex.
+    pub fn bounds(&self) -> Option<(BigRational, BigRational)> {
+        match self {
+            Value::Real(value)
+            | Value::Surd {
+                approximation: value,
+                ..
+            } => Some((value.clone(), value.clone())),
+            Value::Complex { .. } => None,
+            Value::Interval { low, high } => Some((low.clone(), high.clone())),
+        }
+    }
+
+    /// Returns `None` if the value is complex or an interval. Surds give their approximation.
+    pub fn as_real(&self) -> Option<&BigRational> {
+        match self {
+            Value::Real(value) => Some(value),
+            Value::Surd { approximation, .. } => Some(approximation),
+            _ => None,
+        }
+    }
+
+    /// Returns `None` if the value is complex or an interval. Surds give their approximation.
+    pub fn into_real(self) -> Option<BigRational> {
+        match self {
+            Value::Real(value) => Some(value),
+            Value::Surd { approximation, .. } => Some(approximation),
+            _ => None,
+        }
+    }
+
+    /// Whether the value is a single real number, which surds are too.
+    pub fn is_real(&self) -> bool {
+        matches!(self, Value::Real(_) | Value::Surd { .. })
+    }
+
+    pub fn is_complex(&self) -> bool {
+        matches!(self, Value::Complex { .. })
+    }
+
+    pub fn is_interval(&self) -> bool {
+        matches!(self, Value::Interval { .. })
+    }
+
+    pub fn is_surd(&self) -> bool {
+        matches!(self, Value::Surd { .. })
+    }
+
+    pub fn is_zero(&self) -> bool {
+        matches!(self, Value::Real(value) if value.is_zero())
+    }
+
+    /// The complex conjugate, which has the opposite imaginary part.
+    pub fn conj(self) -> Value {
+        match self {
+            Value::Complex { re, im } => Value::Complex { re, im: -im },
+            value => value,
+        }
+    }
+
+    /// The square of the absolute value, which unlike the absolute value itself is always exact.
+    /// For an interval, this is the square of its midpoint.
+    pub fn norm_sqr(&self) -> BigRational {
+        match self {
+            Value::Real(value) => value * value,
+            Value::Complex { re, im } => re * re + im * im,
+            Value::Interval { .. } => self.re() * self.re(),
+            Value::Surd {
+                coefficient,
+                radicand,
+                ..
+            } => coefficient * coefficient * radicand,
+        }
+    }
+
+    /// The absolute value. Returns `None` if the value is complex, since its absolute value is
+    /// generally irrational.
+    pub fn abs(&self) -> Option<Value> {
+        if self.is_surd() {
+            return Some(if self.re().is_negative() {
+                -self.clone()
+            } else {
+                self.clone()
+            });
+        }
+        let (low, high) = self.bounds()?;
+        Some(if high.is_negative() {
+            Value::interval(-high, -low)
+        } else if low.is_negative() {
+            // The interval includes 0.
+            Value::interval(BigRational::zero(), high.max(-low))
+        } else {
+            Value::interval(low, high)
+        })
+    }
+
+    /// Returns `None` if `divisor` is 0.
+    pub fn checked_div(self, divisor: &Value) -> Option<Value> {
+        if divisor.is_zero() {
+            return None;
+        }
+        match (self, divisor) {
+            (Value::Real(dividend), Value::Real(divisor)) => Some(Value::Real(dividend / divisor)),
+            // `1 / (c * sqrt(r))` is `(1 / (c * r)) * sqrt(r)`, a surd that can be multiplied by.
+            (
+                dividend,
+                Value::Surd {
+                    coefficient,
+                    radicand,
+                    approximation,
+                },
+            ) if dividend.is_real() => Some(
+                dividend
+                    * Value::surd(
+                        (coefficient * radicand).recip(),
+                        radicand.clone(),
+                        approximation.recip(),
+                    ),
+            ),
+            (dividend @ Value::Surd { .. }, Value::Real(divisor)) => {
+                Some(dividend * Value::Real(divisor.recip()))
+            }
+            (dividend, divisor) if dividend.is_surd() || divisor.is_surd() => dividend
+                .approximated()
+                .checked_div(&divisor.clone().approximated()),
+            // Multiplying by the conjugate of the divisor leaves a real number to divide by.
+            (dividend, divisor) if dividend.is_complex() || divisor.is_complex() => {
+                let norm_sqr = divisor.norm_sqr();
+                let product = dividend * divisor.clone().conj();
+                Some(Value::complex(
+                    product.re() / &norm_sqr,
+                    product.im() / norm_sqr,
+                ))
+            }
+            // Dividing by an interval that includes 0 could give anything at all.
+            (dividend, divisor) => {
+                let (low, high) = divisor.bounds()?;
+                if !low.is_positive() && !high.is_negative() {
+                    return None;
+                }
+                Some(dividend * Value::interval(high.recip(), low.recip()))
+            }
+        }
+    }
+
+    /// Raises the value to an integer power, exactly. Returns `None` if the value is 0 and the
+    /// exponent is negative.
+    pub fn pow(&self, exponent: &BigInt) -> Option<Value> {
+        let base = if exponent.is_negative() {
+            Value::Real(BigRational::one()).checked_div(self)?
+        } else {
+            self.clone()
+        };
+        let magnitude = exponent.abs().to_biguint().unwrap();
+        match &base {
+            Value::Real(base) => return Some(Value::Real(num::pow::Pow::pow(base, magnitude))),
+            Value::Interval { low, high } => {
+                let low_power = num::pow::Pow::pow(low, &magnitude);
+                let high_power = num::pow::Pow::pow(high, &magnitude);
+                // An even power of an interval that includes 0 has its lowest value at 0.
+                if magnitude.is_even() && low.is_negative() && high.is_positive() {
+                    return Some(Value::interval(
+                        BigRational::zero(),
+                        low_power.max(high_power),
+                    ));
+                }
+                return Some(Value::interval(low_power, high_power));
+            }
+            Value::Complex { .. } | Value::Surd { .. } => {}
+        }
+        // Square and multiply, from the lowest bit of the exponent up.
+        let mut result = Value::Real(BigRational::one());
+        let mut square = base;
+        let mut remaining = exponent.abs();
+        while !remaining.is_zero() {
+            if remaining.is_odd() {
+                result = result * square.clone();
+            }
+            remaining >>= 1;
+            if !remaining.is_zero() {
+                square = square.clone() * square;
+            }
+        }
+        Some(result)
+    }
+}
+
+impl From<BigRational> for Value {
+    fn from(value: BigRational) -> Value {
+        Value::Real(value)
+    }
+}
+
+impl From<BigInt> for Value {
+    fn from(value: BigInt) -> Value {
+        Value::Real(BigRational::from_integer(value))
+    }
+}
+
+/// Surds are compared by their exact form, so their approximations don't matter.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Real(a), Value::Real(b)) => a == b,
+            (Value::Complex { re, im }, Value::Complex { re: b_re, im: b_im }) => {
+                re == b_re && im == b_im
+            }
+            (
+                Value::Interval { low, high },
+                Value::Interval {
+                    low: b_low,
+                    high: b_high,
+                },
+            ) => low == b_low && high == b_high,
+            (
+                Value::Surd {
+                    coefficient,
+                    radicand,
+                    ..
+                },
+                Value::Surd {
+                    coefficient: b_coefficient,
+                    radicand: b_radicand,
+                    ..
+                },
+            ) => coefficient == b_coefficient && radicand == b_radicand,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialEq<BigRational> for Value {
+    fn eq(&self, other: &BigRational) -> bool {
+        matches!(self, Value::Real(value) if value == other)
+    }
+}
+
+impl Add for Value {
+    type Output = Value;
+
+    fn add(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Real(a), Value::Real(b)) => Value::Real(a + b),
+            (
+                Value::Surd {
+                    coefficient: a,
+                    radicand,
+                    approximation: a_approximation,
+                },
+                Value::Surd {
+                    coefficient: b,
+                    radicand: b_radicand,
+                    approximation: b_approximation,
+                },
+            ) if radicand == b_radicand => {
+                Value::surd(a + b, radicand, a_approximation + b_approximation)
+            }
+            (Value::Real(zero), surd @ Value::Surd { .. })
+            | (surd @ Value::Surd { .. }, Value::Real(zero))
+                if zero.is_zero() =>
+            {
+                surd
+            }
+            // Surds with different radicands, or anything else, can't be added exactly.
+            (a, b) if a.is_surd() || b.is_surd() => a.approximated() + b.approximated(),
+            (a, b) if a.is_complex() || b.is_complex() => {
+                Value::complex(a.re() + b.re(), a.im() + b.im())
+            }
+            (a, b) => {
+                let ((a_low, a_high), (b_low, b_high)) = (a.bounds().unwrap(), b.bounds().unwrap());
+                Value::interval(a_low + b_low, a_high + b_high)
+            }
+        }
+    }
+}
+
+impl Sub for Value {
+    type Output = Value;
+
+    fn sub(self, other: Value) -> Value {
+        self + -other
+    }
+}
+
+impl Mul for Value {
+    type Output = Value;
+
+    fn mul(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Real(a), Value::Real(b)) => Value::Real(a * b),
+            // `sqrt(g * a) * sqrt(g * b)` is `g * sqrt(a * b)`. Since neither radicand has square
+            // factors, `a * b` doesn't either.
+            (
+                Value::Surd {
+                    coefficient: a,
+                    radicand: a_radicand,
+                    approximation: a_approximation,
+                },
+                Value::Surd {
+                    coefficient: b,
+                    radicand: b_radicand,
+                    approximation: b_approximation,
+                },
+            ) => {
+                let common = a_radicand.gcd(&b_radicand);
+                Value::surd(
+                    a * b * &common,
+                    (a_radicand / &common) * (b_radicand / common),
+                    a_approximation * b_approximation,
+                )
+            }
+            (
+                Value::Surd {
+                    coefficient,
+                    radicand,
+                    approximation,
+                },
+                Value::Real(factor),
+            )
+            | (
+                Value::Real(factor),
+                Value::Surd {
+                    coefficient,
+                    radicand,
+                    approximation,
+                },
+            ) => Value::surd(coefficient * &factor, radicand, approximation * factor),
+            (a, b) if a.is_surd() || b.is_surd() => a.approximated() * b.approximated(),
+            (a, b) if a.is_complex() || b.is_complex() => {
+                let (a_re, a_im, b_re, b_im) = (a.re(), a.im(), b.re(), b.im());
+                Value::complex(&a_re * &b_re - &a_im * &b_im, a_re * b_im + a_im * b_re)
+            }
+            // The extremes of the product are among the products of the ends of the intervals.
+            (a, b) => {
+                let ((a_low, a_high), (b_low, b_high)) = (a.bounds().unwrap(), b.bounds().unwrap());
+                let products = [
+                    &a_low * &b_low,
+                    &a_low * &b_high,
+                    &a_high * &b_low,
+                    a_high * b_high,
+                ];
+                let low = products.iter().min().unwrap().clone();
+                let high = products.iter().max().unwrap().clone();
+                Value::interval(low, high)
+            }
+        }
+    }
+}
+
+impl Neg for Value {
+    type Output = Value;
+
+    fn neg(self) -> Value {
+        match self {
+            Value::Real(value) => Value::Real(-value),
+            Value::Complex { re, im } => Value::Complex { re: -re, im: -im },
+            Value::Interval { low, high } => Value::Interval {
+                low: -high,
+                high: -low,
+            },
+            Value::Surd {
+                coefficient,
+                radicand,
+                approximation,
+            } => Value::Surd {
+                coefficient: -coefficient,
+                radicand,
+                approximation: -approximation,
+            },
+        }
+    }
+}
+
+impl Sum for Value {
+    fn sum<I: Iterator<Item = Value>>(iter: I) -> Value {
+        iter.fold(Value::Real(BigRational::zero()), Add::add)
+    }
+}
+
+impl Product for Value {
+    fn product<I: Iterator<Item = Value>>(iter: I) -> Value {
+        iter.fold(Value::Real(BigRational::one()), Mul::mul)
+    }
+}
+
+/// Shows the value like `BigRational` does, with complex values written like `2+3i` or `-1/2i`,
+/// intervals like `interval(49/10, 51/10)`, and surds like `3/4*sqrt(2)~1060660/1000000`, with their
+/// approximation after the `~`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Real(value) => write!(f, "{}", value),
+            Value::Complex { re, im } => {
+                if !re.is_zero() {
+                    write!(f, "{}", re)?;
+                    if im.is_positive() {
+                        write!(f, "+")?;
+                    }
+                }
+                write!(f, "{}i", im)
+            }
+            Value::Interval { low, high } => write!(f, "interval({}, {})", low, high),
+            Value::Surd {
+                coefficient,
+                radicand,
+                approximation,
+            } => write!(f, "{}*sqrt({})~{}", coefficient, radicand, approximation),
+        }
+    }
+}
+
+/// Reads the text written by `Display`.
+impl FromStr for Value {
+    type Err = ParseRatioError;
+
+    fn from_str(text: &str) -> Result<Value, ParseRatioError> {
+        if let Some((surd, approximation)) = text.split_once('~') {
+            // Anything missing is left empty, which fails to parse.
+            let (coefficient, radicand) = surd
+                .strip_suffix(')')
+                .and_then(|surd| surd.split_once("*sqrt("))
+                .unwrap_or((surd, ""));
+            return Ok(Value::surd(
+                BigRational::from_str(coefficient)?,
+                BigRational::from_str(radicand)?.to_integer(),
+                BigRational::from_str(approximation)?,
+            ));
+        }
+        if let Some(bounds) = text
+            .strip_prefix("interval(")
+            .and_then(|text| text.strip_suffix(')'))
+        {
+            // A missing comma leaves an empty high end, which fails to parse.
+            let (low, high) = bounds.split_once(", ").unwrap_or((bounds, ""));
+            return Ok(Value::interval(
+                BigRational::from_str(low)?,
+                BigRational::from_str(high)?,
+            ));
+        }
+        let Some(text) = text.strip_suffix('i') else {
+            return BigRational::from_str(text).map(Value::Real);
+        };
+        // The imaginary part starts at the last sign, unless that is the sign of the real part.
+        match text.rfind(['+', '-']).filter(|index| *index > 0) {
+            Some(index) => Ok(Value::complex(
+                BigRational::from_str(&text[..index])?,
+                BigRational::from_str(text[index..].trim_start_matches('+'))?,
+            )),
+            None => Ok(Value::complex(
+                BigRational::zero(),
+                BigRational::from_str(text)?,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod value_tests {
+    use crate::value::Value;
+    use num::{bigint::BigInt, rational::BigRational};
+
+    fn complex(re: i64, im: i64) -> Value {
+        Value::complex(
+            BigRational::from_integer(BigInt::from(re)),
+            BigRational::from_integer(BigInt::from(im)),
+        )
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(complex(2, 3) * complex(1, -1), complex(5, 1));
+        assert_eq!(complex(2, 3) + complex(1, -3), complex(3, 0));
+        assert!((complex(2, 3) + complex(1, -3)).is_real());
+        assert_eq!(complex(2, 3) - complex(2, 3), complex(0, 0));
+        assert_eq!(Value::i() * Value::i(), complex(-1, 0));
+        assert_eq!(
+            complex(5, 1).checked_div(&complex(1, -1)).unwrap(),
+            complex(2, 3)
+        );
+        assert!(complex(1, 1).checked_div(&complex(0, 0)).is_none());
+        assert_eq!(complex(1, 1).pow(&BigInt::from(4)).unwrap(), complex(-4, 0));
+        assert_eq!(
+            complex(0, 2).pow(&BigInt::from(-1)).unwrap(),
+            Value::complex(
+                BigRational::from_integer(BigInt::from(0)),
+                BigRational::new(BigInt::from(-1), BigInt::from(2)),
+            )
+        );
+        assert_eq!(
+            complex(3, 4).norm_sqr(),
+            BigRational::from_integer(25.into())
+        );
+    }
+
+    #[test]
+    fn intervals() {
+        let interval = |low: i64, high: i64| {
+            Value::interval(
+                BigRational::from_integer(BigInt::from(low)),
+                BigRational::from_integer(BigInt::from(high)),
+            )
+        };
+        assert_eq!(interval(3, 1), interval(1, 3));
+        assert!(interval(2, 2).is_real());
+        assert_eq!(interval(1, 3) + interval(-1, 1), interval(0, 4));
+        assert_eq!(interval(1, 3) - interval(1, 3), interval(-2, 2));
+        assert_eq!(interval(-1, 3) * interval(-2, 1), interval(-6, 3));
+        assert_eq!(interval(-1, 3) * complex(-2, 0), interval(-6, 2));
+        assert_eq!(
+            complex(6, 0).checked_div(&interval(2, 3)).unwrap(),
+            interval(2, 3)
+        );
+        assert!(complex(6, 0).checked_div(&interval(0, 3)).is_none());
+        assert_eq!(
+            interval(-2, 1).pow(&BigInt::from(2)).unwrap(),
+            interval(0, 4)
+        );
+        assert_eq!(
+            interval(-2, -1).pow(&BigInt::from(2)).unwrap(),
+            interval(1, 4)
+        );
+        assert_eq!(
+            interval(-2, 1).pow(&BigInt::from(3)).unwrap(),
+            interval(-8, 1)
+        );
+        assert_eq!(interval(-3, 1).abs().unwrap(), interval(0, 3));
+        assert_eq!(interval(-3, -1).abs().unwrap(), interval(1, 3));
+        assert_eq!(
+            interval(1, 3).re(),
+            BigRational::from_integer(BigInt::from(2))
+        );
+    }
+
+    #[test]
+    fn surds() {
+        // `c * sqrt(r)`, approximated to two decimal places, which is all these tests need.
+        let surd = |c: i64, r: i64| {
+            let approximation = (f64::sqrt(r as f64) * c as f64 * 100.0).round() as i64;
+            Value::surd(
+                BigRational::from_integer(BigInt::from(c)),
+                BigInt::from(r),
+                BigRational::new(BigInt::from(approximation), BigInt::from(100)),
+            )
+        };
+        let int = |i: i64| Value::from(BigInt::from(i));
+        assert!(surd(1, 2).is_surd());
+        assert_eq!(surd(3, 1), int(3));
+        assert_eq!(surd(1, 2) * surd(1, 2), int(2));
+        assert_eq!(surd(1, 2) * surd(1, 6), surd(2, 3));
+        assert_eq!(surd(1, 2) + surd(2, 2), surd(3, 2));
+        assert_eq!(surd(1, 2) - surd(1, 2), int(0));
+        assert_eq!(surd(1, 2) * int(3), surd(3, 2));
+        assert_eq!(int(0) - surd(1, 2), surd(-1, 2));
+        assert_eq!(int(2).checked_div(&surd(1, 2)).unwrap(), surd(1, 2));
+        assert_eq!(surd(2, 2).checked_div(&int(2)).unwrap(), surd(1, 2));
+        assert_eq!(surd(1, 2).pow(&BigInt::from(3)).unwrap(), surd(2, 2));
+        assert_eq!(surd(-1, 2).abs().unwrap(), surd(1, 2));
+        // Surds with different radicands can't be added exactly.
+        assert_eq!(
+            surd(1, 2) + surd(1, 3),
+            Value::from(BigRational::new(BigInt::from(314), BigInt::from(100)))
+        );
+        assert!((surd(1, 2) + int(1)).is_real());
+        assert!(!(surd(1, 2) + int(1)).is_surd());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(complex(2, 3).to_string(), "2+3i");
+        assert_eq!(complex(2, -3).to_string(), "2-3i");
+        assert_eq!(complex(0, -1).to_string(), "-1i");
+        assert_eq!(complex(7, 0).to_string(), "7");
+        let interval = Value::interval(
+            BigRational::new(BigInt::from(49), BigInt::from(10)),
+            BigRational::new(BigInt::from(51), BigInt::from(10)),
+        );
+        assert_eq!(interval.to_string(), "interval(49/10, 51/10)");
+    }
+
+    #[test]
+    fn from_str() {
+        for value in [
+            complex(2, 3),
+            complex(-2, -3),
+            complex(0, -1),
+            complex(7, 0),
+        ] {
+            assert_eq!(value.to_string().parse::<Value>().unwrap(), value);
+        }
+        let half = BigRational::new(BigInt::from(-1), BigInt::from(2));
+        let value = Value::complex(half.clone(), half.clone());
+        assert_eq!(value.to_string().parse::<Value>().unwrap(), value);
+        let value = Value::interval(BigRational::from_integer(BigInt::from(-1)), half);
+        assert_eq!(value.to_string().parse::<Value>().unwrap(), value);
+        let value = Value::surd(
+            BigRational::new(BigInt::from(-3), BigInt::from(2)),
+            BigInt::from(2),
+            BigRational::new(BigInt::from(-212), BigInt::from(100)),
+        );
+        assert_eq!(value.to_string(), "-3/2*sqrt(2)~-53/25");
+        assert_eq!(value.to_string().parse::<Value>().unwrap(), value);
+        assert!("2+i".parse::<Value>().is_err());
+        assert!("interval(1)".parse::<Value>().is_err());
+    }
+}
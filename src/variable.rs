@@ -1,25 +1,227 @@
-use crate::{error::InternalCalculatorError, saved_data::SavedData};
-use num::rational::BigRational;
-use std::collections::HashMap;
+use crate::{
+    error::InternalCalculatorError,
+    operations::MAX_RADIX,
+    position::Positioned,
+    storage::Storage,
+    token::{BinaryOperatorToken, FunctionNameToken, Token, UnaryOperatorToken},
+    value::Value,
+    Args,
+};
+use num::BigRational;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::Arc,
+};
+
+/// A variable name, including the leading `$`. Names are shared rather than copied as they are
+/// passed from tokens to syntax tree nodes to the `VariableStore`.
+pub type VariableName = Arc<str>;
+
+/// Separates a variable's namespace from the rest of its name, as in `$proj.rate`.
+pub const NAMESPACE_SEPARATOR: char = '.';
+
+/// The variable that holds the value that the last input evaluated to. Earlier results are in
+/// `$ans2`, `$ans3`, and so on, and `$ans1` is another name for `$ans`.
+pub const ANSWER_VARIABLE: &str = "$ans";
+
+/// If the name is one of the answer variables, how many inputs ago its result was (ex: `1` for
+/// `$ans`, `3` for `$ans3`).
+pub fn answer_index(name: &str) -> Option<usize> {
+    let suffix = name.strip_prefix(ANSWER_VARIABLE)?;
+    if suffix.is_empty() {
+        return Some(1);
+    }
+    if suffix.starts_with('0') || !suffix.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    suffix.parse().ok()
+}
+
+/// Whether the named variable is in the namespace, which may be given with or without a leading
+/// `$`. Namespaces nest, so `$proj.tax.rate` is in both `proj` and `proj.tax`.
+pub fn in_namespace(name: &str, namespace: &str) -> bool {
+    let namespace = namespace
+        .trim_start_matches('$')
+        .trim_end_matches(NAMESPACE_SEPARATOR);
+    name.strip_prefix('$')
+        .and_then(|rest| rest.strip_prefix(namespace))
+        .is_some_and(|rest| rest.starts_with(NAMESPACE_SEPARATOR))
+}
 
 #[derive(Clone, Debug)]
 pub struct Variable {
-    pub name: String,
-    pub value: BigRational,
+    pub name: VariableName,
+    pub value: Value,
+}
+
+/// How a variable's value should be shown, attached to it with `/varformat`. Each setting that is
+/// `None` falls back to the corresponding global setting.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VariableFormat {
+    pub maybe_radix: Option<u8>,
+    pub maybe_precision: Option<u8>,
+    pub fractional: bool,
 }
 
-/// `VariableStore` may be constructed with or without access to `SavedData`. In either case,
-/// we store the variables internally. But if we have `SavedData`, we also write them out to the
+// Names that can be given in place of `radix N` for common radixes.
+const RADIX_NAMES: &[(&str, u8)] = &[("bin", 2), ("oct", 8), ("dec", 10), ("hex", 16)];
+
+impl VariableFormat {
+    /// Reads a format written as space separated settings, such as `hex` or
+    /// `radix 3 precision 10`. This is also the form that `Display` produces.
+    pub fn parse(text: &str) -> Result<VariableFormat, String> {
+        let mut format = VariableFormat::default();
+        let mut words = text.split_ascii_whitespace();
+        while let Some(word) = words.next() {
+            let mut number_argument = |min: u8, max: u8| -> Result<u8, String> {
+                words
+                    .next()
+                    .and_then(|argument| argument.parse::<u8>().ok())
+                    .filter(|number| (min..=max).contains(number))
+                    .ok_or_else(|| {
+                        format!(
+                            "Expected '{}' to be followed by a number from {} to {}",
+                            word, min, max
+                        )
+                    })
+            };
+            match word {
+                "radix" => format.maybe_radix = Some(number_argument(2, MAX_RADIX)?),
+                "precision" => format.maybe_precision = Some(number_argument(0, u8::MAX)?),
+                "fractional" => format.fractional = true,
+                _ => match RADIX_NAMES.iter().find(|(name, _)| *name == word) {
+                    Some((_, radix)) => format.maybe_radix = Some(*radix),
+                    None => return Err(format!("Unknown format setting: '{}'", word)),
+                },
+            }
+        }
+        if format == VariableFormat::default() {
+            return Err("No format settings given".to_string());
+        }
+        Ok(format)
+    }
+
+    /// Returns a copy of `args` with the output settings replaced by this format's. Values shown
+    /// in a particular radix or precision are never shown as fractions unless `fractional` is set.
+    pub fn apply(&self, args: &Args) -> Args {
+        let mut args = args.clone();
+        if self.fractional {
+            args.fractional = true;
+        } else if self.maybe_radix.is_some() || self.maybe_precision.is_some() {
+            args.fractional = false;
+        }
+        if let Some(radix) = self.maybe_radix {
+            args.convert_to_radix = Some(radix);
+        }
+        if let Some(precision) = self.maybe_precision {
+            args.precision = precision;
+        }
+        args
+    }
+}
+
+impl fmt::Display for VariableFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut settings: Vec<String> = Vec::new();
+        if let Some(radix) = self.maybe_radix {
+            match RADIX_NAMES.iter().find(|(_, r)| *r == radix) {
+                Some((name, _)) => settings.push(name.to_string()),
+                None => settings.push(format!("radix {}", radix)),
+            }
+        }
+        if let Some(precision) = self.maybe_precision {
+            settings.push(format!("precision {}", precision));
+        }
+        if self.fractional {
+            settings.push("fractional".to_string());
+        }
+        write!(f, "{}", settings.join(" "))
+    }
+}
+
+// Tokens that evaluate to the value. Complex values are written out in parentheses, like `(2+3i)`,
+// so that they stay together when an operator follows them. Intervals are written as a call to
+// `interval`, and surds as a multiple of a square root, like `(3*sqrt(2))`.
+fn value_tokens(value: &Value) -> Vec<Token> {
+    match value {
+        Value::Real(value) => vec![Token::Number(value.clone())],
+        Value::Complex { re, im } => vec![
+            Token::OpenParen,
+            Token::Number(re.clone()),
+            Token::BinaryOperator(BinaryOperatorToken::Add),
+            Token::Imaginary(im.clone()),
+            Token::CloseParen,
+        ],
+        Value::Interval { low, high } => vec![
+            Token::Function(FunctionNameToken::Interval),
+            Token::OpenParen,
+            Token::Number(low.clone()),
+            Token::Comma,
+            Token::Number(high.clone()),
+            Token::CloseParen,
+        ],
+        Value::Surd {
+            coefficient,
+            radicand,
+            ..
+        } => vec![
+            Token::OpenParen,
+            Token::Number(coefficient.clone()),
+            Token::BinaryOperator(BinaryOperatorToken::Multiply),
+            Token::UnaryOperator(UnaryOperatorToken::SquareRoot),
+            Token::OpenParen,
+            Token::Number(BigRational::from_integer(radicand.clone())),
+            Token::CloseParen,
+            Token::CloseParen,
+        ],
+    }
+}
+
+/// The variable whose format the result of an input should be shown in. This is only the case when
+/// the input just refers to a single variable or assigns to one (ex: `$mask` or `$mask = 0xff00`).
+pub fn displayed_variable(tokens: &[Positioned<Token>]) -> Option<VariableName> {
+    match tokens {
+        [first]
+        | [first, Positioned {
+            value: Token::AssignmentOperator,
+            ..
+        }, ..] => match &first.value {
+            Token::Variable(name) => Some(name.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `VariableStore` may be constructed with or without access to `Storage`. In either case,
+/// we store the variables internally. But if we have `Storage`, we also write them out to the
 /// database. We also load them from the database, but only if we don't have that variable
 /// internally.
 pub struct VariableStore {
-    vars: HashMap<String, BigRational>,
+    vars: HashMap<VariableName, Value>,
+    formats: HashMap<VariableName, VariableFormat>,
+    // The values that the most recent inputs evaluated to, from oldest to newest. These are used by
+    // functions like `histsum` and are never written to `Storage`, since the results stored there
+    // are only the rounded text that was displayed.
+    results: VecDeque<Value>,
+}
+
+/// The number of results that `VariableStore` remembers for functions like `histsum`.
+pub const MAX_STORED_RESULTS: usize = 1000;
+
+impl Default for VariableStore {
+    fn default() -> VariableStore {
+        VariableStore::new()
+    }
 }
 
 impl VariableStore {
     pub fn new() -> VariableStore {
         VariableStore {
             vars: HashMap::new(),
+            formats: HashMap::new(),
+            results: VecDeque::new(),
         }
     }
 
@@ -31,7 +233,7 @@ impl VariableStore {
         &mut self,
         var: Variable,
         maybe_input_history_id: Option<i64>,
-        maybe_db: Option<&mut SavedData>,
+        maybe_db: Option<&mut dyn Storage>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let result = match (maybe_db, maybe_input_history_id) {
             (Some(db), Some(input_history_id)) => db.set_variable(&var, input_history_id),
@@ -56,7 +258,7 @@ impl VariableStore {
         &mut self,
         name: &str,
         maybe_input_history_id: Option<i64>,
-        maybe_db: Option<&mut SavedData>,
+        maybe_db: Option<&mut dyn Storage>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         match (maybe_db, maybe_input_history_id) {
             (Some(db), Some(input_history_id)) => db.touch_variable(name, input_history_id),
@@ -74,33 +276,166 @@ impl VariableStore {
     }
 
     /// Returns the value in the instance's variable store. If the value isn't available, we attempt
-    /// to populate the value from `SavedData` and return that.
+    /// to populate the value from `Storage` and return that.
     pub fn get(
         &mut self,
-        name: String,
-        maybe_db: Option<&mut SavedData>,
+        name: VariableName,
+        maybe_db: Option<&mut dyn Storage>,
     ) -> Result<Option<Variable>, Box<dyn std::error::Error>> {
+        if let Some(index) = answer_index(&name) {
+            let value = self.results.iter().rev().nth(index - 1).cloned();
+            return Ok(value.map(|value| Variable { name, value }));
+        }
+
         if let Some(value) = self.vars.get(&name) {
             return Ok(Some(Variable {
-                name: name,
+                name,
                 value: value.clone(),
             }));
         }
 
         if let Some(db) = maybe_db {
-            self.reload(name, db)
+            self.reload(&name, db)
         } else {
             Ok(None)
         }
     }
 
-    // Attempts to load a variable from `SavedData`'s variable history and, if it exists, overwrites
+    /// Returns the format attached to the variable, loading it from `Storage` if it isn't in the
+    /// instance's variable store. Formats in `Storage` that can't be read are ignored.
+    pub fn format(
+        &mut self,
+        name: &VariableName,
+        maybe_db: Option<&mut dyn Storage>,
+    ) -> Result<Option<VariableFormat>, Box<dyn std::error::Error>> {
+        if let Some(format) = self.formats.get(name) {
+            return Ok(Some(format.clone()));
+        }
+        let maybe_format = match maybe_db {
+            Some(db) => db
+                .get_variable_format(name)?
+                .and_then(|text| VariableFormat::parse(&text).ok()),
+            None => None,
+        };
+        if let Some(format) = &maybe_format {
+            self.formats.insert(name.clone(), format.clone());
+        }
+        Ok(maybe_format)
+    }
+
+    /// Attaches the format to the variable, or removes its format if `maybe_format` is `None`. If
+    /// `Storage` is available, the change is also made there.
+    pub fn set_format(
+        &mut self,
+        name: &VariableName,
+        maybe_format: Option<VariableFormat>,
+        maybe_db: Option<&mut dyn Storage>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(db) = maybe_db {
+            let maybe_text = maybe_format.as_ref().map(VariableFormat::to_string);
+            db.set_variable_format(name, maybe_text.as_deref())?;
+        }
+        match maybe_format {
+            Some(format) => self.formats.insert(name.clone(), format),
+            None => self.formats.remove(name),
+        };
+        Ok(())
+    }
+
+    /// Records the value that an input evaluated to. Only the most recent `MAX_STORED_RESULTS` are
+    /// kept.
+    pub fn push_result(&mut self, value: Value) {
+        if self.results.len() >= MAX_STORED_RESULTS {
+            self.results.pop_front();
+        }
+        self.results.push_back(value);
+    }
+
+    /// The values that the last `count` inputs evaluated to, from newest to oldest. Returns `None`
+    /// if fewer than `count` results have been recorded.
+    pub fn recent_results(&self, count: usize) -> Option<impl Iterator<Item = &Value> + Clone> {
+        if count > self.results.len() {
+            return None;
+        }
+        Some(self.results.iter().rev().take(count))
+    }
+
+    pub fn result_count(&self) -> usize {
+        self.results.len()
+    }
+
+    /// If the input starts with a binary operator, like ` * 1.07`, inserts the value that the last
+    /// input evaluated to in front of it, like a desk calculator would. A leading `-` is only
+    /// treated this way if it is followed by a space (`- 5`), since `-5` is a negative number. The
+    /// tokens are left alone if there is no previous result.
+    pub fn continue_previous_result(&self, tokens: &mut Vec<Positioned<Token>>) {
+        let Some(previous) = self.results.back() else {
+            return;
+        };
+        let continues = match tokens.as_slice() {
+            [Positioned {
+                value: Token::BinaryOperator(BinaryOperatorToken::Subtract),
+                position,
+            }, next, ..] => next.position.start > position.start + position.width,
+            [Positioned {
+                value: Token::BinaryOperator(_),
+                ..
+            }, ..] => true,
+            _ => false,
+        };
+        if continues {
+            tokens.splice(
+                0..0,
+                value_tokens(previous)
+                    .into_iter()
+                    .map(|token| Positioned::new_raw(token, 0, 0)),
+            );
+        }
+    }
+
+    /// The names of the variables in the instance's variable store. Variables that are only in
+    /// `Storage` aren't included.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.vars.keys().map(|name| name.as_ref())
+    }
+
+    /// The variables in the instance's variable store, sorted by name. Variables that are only in
+    /// `Storage` aren't included.
+    pub fn variables(&self) -> Vec<Variable> {
+        let mut variables: Vec<Variable> = self
+            .vars
+            .iter()
+            .map(|(name, value)| Variable {
+                name: name.clone(),
+                value: value.clone(),
+            })
+            .collect();
+        variables.sort_by(|a, b| a.name.cmp(&b.name));
+        variables
+    }
+
+    /// The names of the variables in the instance's variable store and, if it is available,
+    /// `Storage`, sorted and without duplicates.
+    pub fn all_names(
+        &self,
+        maybe_db: Option<&mut dyn Storage>,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut names: Vec<String> = self.names().map(str::to_string).collect();
+        if let Some(db) = maybe_db {
+            names.extend(db.get_variable_names()?);
+        }
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    // Attempts to load a variable from `Storage`'s variable history and, if it exists, overwrites
     // any value in the instance's variable store. If the variable is not found in the variable
     // history, this has no effect and `Ok(None)` is returned.
     pub fn reload(
         &mut self,
-        name: String,
-        db: &mut SavedData,
+        name: &str,
+        db: &mut dyn Storage,
     ) -> Result<Option<Variable>, Box<dyn std::error::Error>> {
         if let Some(var) = db.get_variable(name)? {
             self.vars.insert(var.name.clone(), var.value.clone());
@@ -110,16 +445,17 @@ impl VariableStore {
         }
     }
 
-    // Removes the variable from the instance's variable store. If `SavedData`'s variable history is
+    // Removes the variable from the instance's variable store. If `Storage`'s variable history is
     // available, the variable is removed from it too.
     // `Ok` will be returned if the variable does not exist in either location, regardless of
     // whether or not it did before.
     pub fn purge(
         &mut self,
         name: &str,
-        maybe_db: Option<&mut SavedData>,
+        maybe_db: Option<&mut dyn Storage>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         self.vars.remove(name);
+        self.formats.remove(name);
 
         if let Some(db) = maybe_db {
             db.clear_variable(name)?;
@@ -128,3 +464,20 @@ impl VariableStore {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod variable_tests {
+    use crate::variable::in_namespace;
+
+    #[test]
+    fn namespaces() {
+        assert!(in_namespace("$proj.rate", "proj"));
+        assert!(in_namespace("$proj.rate", "$proj"));
+        assert!(in_namespace("$proj.rate", "proj."));
+        assert!(in_namespace("$proj.tax.rate", "proj"));
+        assert!(in_namespace("$proj.tax.rate", "proj.tax"));
+        assert!(!in_namespace("$proj", "proj"));
+        assert!(!in_namespace("$project.rate", "proj"));
+        assert!(!in_namespace("$other.proj.rate", "proj"));
+    }
+}
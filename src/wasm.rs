@@ -0,0 +1,60 @@
+//! JavaScript bindings for the calculation engine, for building a browser calculator.
+
+use crate::{error::CalculatorFailure, operations::MAX_RADIX, Args};
+use wasm_bindgen::prelude::*;
+
+/// Exported to JavaScript as `Calculator`. Variables assigned by one call to `evaluate` are
+/// available to later calls on the same instance.
+#[wasm_bindgen(js_name = Calculator)]
+pub struct WasmCalculator {
+    calculator: crate::Calculator,
+}
+
+#[wasm_bindgen(js_class = Calculator)]
+impl WasmCalculator {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmCalculator {
+        WasmCalculator {
+            calculator: crate::Calculator::new(Args::default()),
+        }
+    }
+
+    /// Returns the formatted result of evaluating the expression. On failure, an error message is
+    /// thrown.
+    pub fn evaluate(&mut self, input: &str) -> Result<String, JsError> {
+        match self.calculator.evaluate(input) {
+            Ok(value) => Ok(self.calculator.format(&value)),
+            Err(CalculatorFailure::InputError(message)) => {
+                Err(JsError::new(&format!("Error: {}", message.value)))
+            }
+            Err(CalculatorFailure::RuntimeError(e)) => {
+                Err(JsError::new(&format!("Runtime Error: {}", e)))
+            }
+        }
+    }
+
+    pub fn set_radix(&mut self, radix: u8) -> Result<(), JsError> {
+        if !(2..=MAX_RADIX).contains(&radix) {
+            return Err(JsError::new(&format!(
+                "Radix must be between 2 and {}",
+                MAX_RADIX
+            )));
+        }
+        self.calculator.args_mut().radix = radix;
+        Ok(())
+    }
+
+    pub fn set_precision(&mut self, precision: u8) {
+        self.calculator.args_mut().precision = precision;
+    }
+
+    pub fn set_fractional(&mut self, fractional: bool) {
+        self.calculator.args_mut().fractional = fractional;
+    }
+}
+
+impl Default for WasmCalculator {
+    fn default() -> WasmCalculator {
+        WasmCalculator::new()
+    }
+}